@@ -0,0 +1,551 @@
+//! Dumps a syntax tree as a Clang-style indented tree, JSON or
+//! S-expressions.
+
+use serde::Serialize;
+use token::{Position, Token};
+
+use crate::{
+    Decl, DeclKind, DeclSpec, Declarator, Designator, Expr, ExprKind, File, GenDecl, Node,
+    SizeofArg, StaticAssert, Stmt, StmtKind, TypeName, TypeSpec,
+};
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Loc {
+    pub offset: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Range {
+    pub begin: Loc,
+    pub end: Loc, // location of the last character of the node
+}
+
+/// A generic description of a node, independent of its concrete type.
+#[derive(Debug, Serialize)]
+pub struct Tree {
+    pub kind: &'static str,
+    pub range: Range,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub inner: Vec<Tree>,
+}
+
+/// Builds [`Tree`]s, `position` converts an offset into a line and column,
+/// see `Scanner::position`.
+pub struct Dumper<F: Fn(usize) -> Position> {
+    position: F,
+}
+
+impl<F: Fn(usize) -> Position> Dumper<F> {
+    pub fn new(position: F) -> Self {
+        Self { position }
+    }
+
+    fn tree(&self, kind: &'static str, node: &dyn Node, detail: Option<String>) -> Tree {
+        Tree {
+            detail,
+            ..self.span(kind, node.start(), node.end())
+        }
+    }
+
+    fn span(&self, kind: &'static str, start: usize, end: usize) -> Tree {
+        let loc = |offset| {
+            let p = (self.position)(offset);
+            Loc {
+                offset,
+                line: p.line,
+                col: p.column,
+            }
+        };
+
+        Tree {
+            kind,
+            range: Range {
+                begin: loc(start),
+                end: loc(end.max(start + 1) - 1),
+            },
+            detail: None,
+            inner: Vec::new(),
+        }
+    }
+
+    pub fn file(&self, file: &File) -> Tree {
+        let mut t = self.tree("File", file, None);
+        t.inner = file.decls.iter().map(|d| self.decl(d.as_ref())).collect();
+        t
+    }
+
+    pub fn decl(&self, decl: &dyn Decl) -> Tree {
+        match decl.kind() {
+            DeclKind::GenDecl(d) => self.gen_decl(d),
+
+            DeclKind::FuncDecl(d) => {
+                let mut t = self.tree("FuncDecl", d, Some(named(&d.specs, &d.declarator)));
+
+                t.inner.extend(self.spec_body(&d.specs));
+                if let Some(f) = d.declarator.function() {
+                    for p in f.params.iter() {
+                        t.inner.push(self.declarator(
+                            "ParamDecl",
+                            p.specs.pos,
+                            &p.specs,
+                            &p.declarator,
+                            None,
+                        ));
+                    }
+                }
+                t.inner.push(self.stmt(&d.body));
+
+                t
+            }
+
+            DeclKind::StaticAssert(s) => self.static_assert(s),
+        }
+    }
+
+    fn static_assert(&self, s: &StaticAssert) -> Tree {
+        let mut t = self.tree("StaticAssert", s, None);
+        t.inner.push(self.expr(s.cond.as_ref()));
+        t.inner.push(self.expr(&s.msg));
+        t
+    }
+
+    fn gen_decl(&self, d: &GenDecl) -> Tree {
+        let mut t = self.tree("GenDecl", d, d.specs.storage.map(|s| s.to_str()));
+
+        t.inner.extend(self.spec_body(&d.specs));
+        for init in d.decls.iter() {
+            t.inner.push(self.declarator(
+                "InitDecl",
+                init.declarator.start(&d.specs),
+                &d.specs,
+                &init.declarator,
+                init.init.as_deref(),
+            ));
+        }
+
+        t
+    }
+
+    fn declarator(
+        &self,
+        kind: &'static str,
+        start: usize,
+        specs: &DeclSpec,
+        d: &Declarator,
+        init: Option<&dyn Expr>,
+    ) -> Tree {
+        let end = init.map_or(d.end, |x| x.end());
+
+        let mut t = Tree {
+            detail: Some(named(specs, d)),
+            ..self.span(kind, start, end)
+        };
+        t.inner.extend(init.map(|x| self.expr(x)));
+
+        t
+    }
+
+    // the fields of a struct or the constants of an enum defined in
+    // declaration specifiers
+    fn spec_body(&self, specs: &DeclSpec) -> Option<Tree> {
+        match &specs.typ {
+            TypeSpec::Struct(s) => {
+                let fields = s.fields.as_ref()?;
+
+                let mut t = Tree {
+                    detail: Some(specs.typ.name()),
+                    ..self.span("RecordDecl", s.pos, s.end)
+                };
+
+                for f in fields.iter() {
+                    t.inner.extend(self.spec_body(&f.specs));
+                    for d in f.decls.iter() {
+                        t.inner.push(self.declarator(
+                            "FieldDecl",
+                            d.declarator.start(&f.specs),
+                            &f.specs,
+                            &d.declarator,
+                            d.bits.as_deref(),
+                        ));
+                    }
+                }
+
+                Some(t)
+            }
+
+            TypeSpec::Enum(e) => {
+                let enumerators = e.enumerators.as_ref()?;
+
+                let mut t = Tree {
+                    detail: Some(specs.typ.name()),
+                    ..self.span("EnumDecl", e.pos, e.end)
+                };
+
+                for c in enumerators.iter() {
+                    let end = c.value.as_ref().map_or(c.name.end(), |x| x.end());
+                    let mut ct = Tree {
+                        detail: Some(c.name.name.clone()),
+                        ..self.span("Enumerator", c.name.pos, end)
+                    };
+                    ct.inner
+                        .extend(c.value.iter().map(|x| self.expr(x.as_ref())));
+                    t.inner.push(ct);
+                }
+
+                Some(t)
+            }
+
+            _ => None,
+        }
+    }
+
+    pub fn stmt(&self, stmt: &dyn Stmt) -> Tree {
+        match stmt.kind() {
+            StmtKind::ReturnStmt(s) => {
+                let mut t = self.tree("ReturnStmt", s, None);
+                t.inner
+                    .extend(s.value.iter().map(|x| self.expr(x.as_ref())));
+                t
+            }
+
+            StmtKind::BreakStmt(s) => self.tree("BreakStmt", s, None),
+            StmtKind::ContinueStmt(s) => self.tree("ContinueStmt", s, None),
+
+            StmtKind::IfStmt(s) => {
+                let mut t = self.tree("IfStmt", s, None);
+                t.inner.push(self.expr(s.cond.as_ref()));
+                t.inner.push(self.stmt(s.init.as_ref()));
+
+                for elif in s.elifs.iter() {
+                    t.inner.push(Tree {
+                        inner: vec![self.expr(elif.cond.as_ref()), self.stmt(elif.init.as_ref())],
+                        ..self.span("ElseIf", elif.else_pos, elif.init.end())
+                    });
+                }
+
+                if let Some(_else) = &s._else {
+                    t.inner.push(Tree {
+                        inner: vec![self.stmt(_else.init.as_ref())],
+                        ..self.span("Else", _else.pos, _else.init.end())
+                    });
+                }
+
+                t
+            }
+
+            StmtKind::BlockStmt(s) => {
+                let mut t = self.tree("BlockStmt", s, None);
+                t.inner = s.stmts.iter().map(|s| self.stmt(s.as_ref())).collect();
+                t
+            }
+
+            StmtKind::WhileStmt(s) => {
+                let mut t = self.tree("WhileStmt", s, None);
+                t.inner.push(self.expr(s.cond.as_ref()));
+                t.inner.push(self.stmt(s.init.as_ref()));
+                t
+            }
+
+            StmtKind::SemiColonStmt(s) => self.tree("SemiColonStmt", s, None),
+
+            StmtKind::ExprStmt(s) => {
+                let mut t = self.tree("ExprStmt", s, None);
+                t.inner.push(self.expr(s.x.as_ref()));
+                t
+            }
+
+            StmtKind::DeclStmt(s) => {
+                let mut t = self.tree("DeclStmt", s, None);
+                t.inner.push(self.gen_decl(&s.decl));
+                t
+            }
+
+            StmtKind::DowhileStmt(s) => {
+                let mut t = self.tree("DowhileStmt", s, None);
+                t.inner.push(self.stmt(s.init.as_ref()));
+                t.inner.push(self.expr(s.cond.as_ref()));
+                t
+            }
+
+            StmtKind::ForStmt(s) => {
+                let mut t = self.tree("ForStmt", s, None);
+                t.inner.extend(s.init.iter().map(|s| self.stmt(s.as_ref())));
+                t.inner.extend(s.cond.iter().map(|x| self.expr(x.as_ref())));
+                t.inner.extend(s.post.iter().map(|x| self.expr(x.as_ref())));
+                t.inner.push(self.stmt(s.body.as_ref()));
+                t
+            }
+
+            StmtKind::SwitchStmt(s) => {
+                let mut t = self.tree("SwitchStmt", s, None);
+                t.inner.push(self.expr(s.tag.as_ref()));
+                t.inner.push(self.stmt(s.body.as_ref()));
+                t
+            }
+
+            StmtKind::CaseStmt(s) => {
+                let mut t = match &s.value {
+                    Some(x) => {
+                        let mut t = self.tree("CaseStmt", s, None);
+                        t.inner.push(self.expr(x.as_ref()));
+                        t
+                    }
+                    None => self.tree("DefaultStmt", s, None),
+                };
+                t.inner.push(self.stmt(s.stmt.as_ref()));
+                t
+            }
+
+            StmtKind::LabeledStmt(s) => {
+                let mut t = self.tree("LabeledStmt", s, Some(s.label.name.clone()));
+                t.inner.push(self.stmt(s.stmt.as_ref()));
+                t
+            }
+
+            StmtKind::GotoStmt(s) => self.tree("GotoStmt", s, Some(s.label.name.clone())),
+            StmtKind::StaticAssert(s) => self.static_assert(s),
+        }
+    }
+
+    pub fn expr(&self, expr: &dyn Expr) -> Tree {
+        match expr.kind() {
+            ExprKind::BasicLit(x) => self.tree("BasicLit", x, Some(x.lit.clone())),
+
+            ExprKind::UnaryExpr(x) => {
+                let mut t = self.tree("UnaryExpr", x, Some(x.op.to_str()));
+                t.inner.push(self.expr(x.x.as_ref()));
+                t
+            }
+
+            ExprKind::InfixExpr(x) => {
+                let mut t = self.tree("InfixExpr", x, Some(x.op.to_str()));
+                t.inner.push(self.expr(x.x.as_ref()));
+                t.inner.push(self.expr(x.y.as_ref()));
+                t
+            }
+
+            ExprKind::Ident(x) => self.tree("Ident", x, Some(x.name.clone())),
+
+            ExprKind::StarExpr(x) => {
+                let mut t = self.tree("StarExpr", x, None);
+                t.inner.push(self.expr(x.x.as_ref()));
+                t
+            }
+
+            ExprKind::ParenExpr(x) => {
+                let mut t = self.tree("ParenExpr", x, None);
+                t.inner.push(self.expr(x.x.as_ref()));
+                t
+            }
+
+            ExprKind::PostfixExpr(x) => {
+                let mut t = self.tree("PostfixExpr", x, Some(x.op.to_str()));
+                t.inner.push(self.expr(x.x.as_ref()));
+                t
+            }
+
+            ExprKind::AssignExpr(x) => {
+                let mut t = self.tree("AssignExpr", x, Some(x.op.to_str()));
+                t.inner.push(self.expr(x.x.as_ref()));
+                t.inner.push(self.expr(x.y.as_ref()));
+                t
+            }
+
+            ExprKind::CondExpr(x) => {
+                let mut t = self.tree("CondExpr", x, None);
+                t.inner.push(self.expr(x.cond.as_ref()));
+                t.inner.push(self.expr(x.x.as_ref()));
+                t.inner.push(self.expr(x.y.as_ref()));
+                t
+            }
+
+            ExprKind::CallExpr(x) => {
+                let mut t = self.tree("CallExpr", x, None);
+                t.inner.push(self.expr(x.fun.as_ref()));
+                t.inner.extend(x.args.iter().map(|a| self.expr(a.as_ref())));
+                t
+            }
+
+            ExprKind::IndexExpr(x) => {
+                let mut t = self.tree("IndexExpr", x, None);
+                t.inner.push(self.expr(x.x.as_ref()));
+                t.inner.push(self.expr(x.index.as_ref()));
+                t
+            }
+
+            ExprKind::SelectorExpr(x) => {
+                let detail = format!("{}{}", x.op.to_str(), x.sel.name);
+                let mut t = self.tree("SelectorExpr", x, Some(detail));
+                t.inner.push(self.expr(x.x.as_ref()));
+                t
+            }
+
+            ExprKind::CastExpr(x) => {
+                let mut t = self.tree("CastExpr", x, Some(quoted(&x.typ)));
+                t.inner.push(self.expr(x.x.as_ref()));
+                t
+            }
+
+            ExprKind::SizeofExpr(x) => {
+                let name = match x.op {
+                    Token::ALIGNOF => "AlignofExpr",
+                    _ => "SizeofExpr",
+                };
+
+                match &x.arg {
+                    SizeofArg::Expr(e) => {
+                        let mut t = self.tree(name, x, None);
+                        t.inner.push(self.expr(e.as_ref()));
+                        t
+                    }
+                    SizeofArg::Type(typ) => self.tree(name, x, Some(quoted(typ))),
+                }
+            }
+
+            ExprKind::OffsetofExpr(x) => {
+                let path: String = x.path.iter().map(|d| d.format(&|x| x.string())).collect();
+                let detail = format!("{} {}{}", quoted(&x.typ), x.member.name, path);
+                let mut t = self.tree("OffsetofExpr", x, Some(detail));
+
+                for d in x.path.iter() {
+                    if let Designator::Index { index, .. } = d {
+                        t.inner.push(self.expr(index.as_ref()));
+                    }
+                }
+                t
+            }
+
+            ExprKind::CompositeLit(x) => {
+                let mut t = self.tree("CompositeLit", x, Some(quoted(&x.typ)));
+                t.inner.push(self.expr(&x.init));
+                t
+            }
+
+            ExprKind::InitList(x) => {
+                let mut t = self.tree("InitList", x, None);
+
+                for e in x.elems.iter() {
+                    let value = self.expr(e.value.as_ref());
+
+                    if e.designators.is_empty() {
+                        t.inner.push(value);
+                        continue;
+                    }
+
+                    let designation: String = e
+                        .designators
+                        .iter()
+                        .map(|d| d.format(&|x| x.string()))
+                        .collect();
+                    let start = e.designators[0].start();
+
+                    t.inner.push(Tree {
+                        detail: Some(designation),
+                        inner: vec![value],
+                        ..self.span("Designation", start, e.value.end())
+                    });
+                }
+
+                t
+            }
+        }
+    }
+}
+
+// `name 'type'`, or `'type'` without a name
+fn named(specs: &DeclSpec, d: &Declarator) -> String {
+    let typ = format!("'{}'", d.type_string(specs, &|x| x.string()));
+
+    match &d.name {
+        Some(name) => format!("{} {}", name.name, typ),
+        None => typ,
+    }
+}
+
+fn quoted(typ: &TypeName) -> String {
+    format!(
+        "'{}'",
+        typ.declarator.type_string(&typ.specs, &|x| x.string())
+    )
+}
+
+impl Tree {
+    /// Formats the tree like `clang -Xclang -ast-dump`.
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let mut last_line = 0;
+
+        self.write_text(&mut out, "", "", &mut last_line);
+
+        out
+    }
+
+    fn write_text(&self, out: &mut String, prefix: &str, branch: &str, last_line: &mut usize) {
+        let mut loc = |l: &Loc| {
+            if l.line == *last_line {
+                format!("col:{}", l.col)
+            } else {
+                *last_line = l.line;
+                format!("line:{}:{}", l.line, l.col)
+            }
+        };
+
+        let begin = loc(&self.range.begin);
+        let end = loc(&self.range.end);
+
+        out.push_str(&format!(
+            "{}{}{} <{}, {}>",
+            prefix, branch, self.kind, begin, end
+        ));
+        if let Some(detail) = &self.detail {
+            out.push_str(&format!(" {}", detail));
+        }
+        out.push('\n');
+
+        let prefix = format!(
+            "{}{}",
+            prefix,
+            match branch {
+                "|-" => "| ",
+                "`-" => "  ",
+                _ => "",
+            }
+        );
+
+        for (i, t) in self.inner.iter().enumerate() {
+            let branch = if i + 1 == self.inner.len() {
+                "`-"
+            } else {
+                "|-"
+            };
+            t.write_text(out, &prefix, branch, last_line);
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// Formats the tree as an S-expression, without locations.
+    pub fn to_sexpr(&self) -> String {
+        let mut s = format!("({}", self.kind);
+
+        if let Some(detail) = &self.detail {
+            s.push_str(&format!(" {}", detail));
+        }
+
+        for t in self.inner.iter() {
+            s.push(' ');
+            s.push_str(&t.to_sexpr());
+        }
+
+        s.push(')');
+        s
+    }
+}
@@ -1,22 +1,36 @@
 use token::Token;
 
+pub mod dump;
+pub mod printer;
+
 pub trait Node {
     fn start(&self) -> usize;
-    // fn end(&self) -> usize;
+    fn end(&self) -> usize; // position just past the node
     fn string(&self) -> String;
 }
 
-pub trait Expr: Node {}
+pub trait Expr: Node {
+    fn kind(&self) -> ExprKind<'_>;
+}
 
-pub trait Stmt: Node {}
+pub trait Stmt: Node {
+    fn kind(&self) -> StmtKind<'_>;
+}
 
-pub trait Decl: Node {}
+pub trait Decl: Node {
+    fn kind(&self) -> DeclKind<'_>;
+}
 
 pub struct SemiColonStmt {
     pub pos: usize,
 }
 pub struct ExprStmt {
     pub x: Box<dyn Expr>,
+    pub semi: usize,
+}
+
+pub struct DeclStmt {
+    pub decl: GenDecl,
 }
 
 pub struct IncDecStmt {
@@ -73,10 +87,49 @@ pub struct DowhileStmt {
     pub do_pos: usize,
     pub init: Box<dyn Stmt>,
     pub while_pos: usize,
-    pub lbrace_pos: usize,
+    pub lparen_pos: usize,
     pub cond: Box<dyn Expr>,
-    pub rbrace_pos: usize,
+    pub rparen_pos: usize,
+    pub semi: usize,
+}
+
+pub struct ForStmt {
+    pub pos: usize,
+    pub lparen_pos: usize,
+    pub init: Option<Box<dyn Stmt>>, // an expression or a declaration statement
+    pub cond: Option<Box<dyn Expr>>,
+    pub post: Option<Box<dyn Expr>>,
+    pub rparen_pos: usize,
+    pub body: Box<dyn Stmt>,
+}
+
+pub struct SwitchStmt {
+    pub pos: usize,
+    pub lparen_pos: usize,
+    pub tag: Box<dyn Expr>,
+    pub rparen_pos: usize,
+    pub body: Box<dyn Stmt>,
+}
+
+pub struct CaseStmt {
+    pub pos: usize,                   // position of the 'case' or 'default' keyword
+    pub value: Option<Box<dyn Expr>>, // None for 'default'
+    pub colon: usize,
+    pub stmt: Box<dyn Stmt>,
+}
+
+pub struct LabeledStmt {
+    pub label: Ident,
+    pub colon: usize,
+    pub stmt: Box<dyn Stmt>,
 }
+
+pub struct GotoStmt {
+    pub pos: usize,
+    pub label: Ident,
+    pub semi: usize,
+}
+
 pub struct ReturnStmt {
     pub pos: usize,                   // position of the 'return' keyword
     pub value: Option<Box<dyn Expr>>, // the return value
@@ -89,7 +142,62 @@ pub struct BreakStmt {
 }
 
 pub struct ContinueStmt {
-    pub pos: usize, // position of the 'continue' keyword
+    pub pos: usize,  // position of the 'continue' keyword
+    pub semi: usize, // position of the ';'
+}
+
+/// A typed view of a statement, used to walk the tree.
+pub enum StmtKind<'a> {
+    SemiColonStmt(&'a SemiColonStmt),
+    ExprStmt(&'a ExprStmt),
+    DeclStmt(&'a DeclStmt),
+    ReturnStmt(&'a ReturnStmt),
+    BreakStmt(&'a BreakStmt),
+    ContinueStmt(&'a ContinueStmt),
+    IfStmt(&'a IfStmt),
+    BlockStmt(&'a BlockStmt),
+    WhileStmt(&'a WhileStmt),
+    DowhileStmt(&'a DowhileStmt),
+    ForStmt(&'a ForStmt),
+    SwitchStmt(&'a SwitchStmt),
+    CaseStmt(&'a CaseStmt),
+    LabeledStmt(&'a LabeledStmt),
+    GotoStmt(&'a GotoStmt),
+    StaticAssert(&'a StaticAssert),
+}
+
+/// The root of a parsed source file.
+pub struct File {
+    pub decls: Vec<Box<dyn Decl>>,
+    pub comments: Vec<Comment>, // all the comments in source order
+    pub eof: usize,             // position of the end of file
+}
+
+#[derive(Debug, Clone)]
+pub struct Comment {
+    pub pos: usize,
+    pub text: String,
+    pub trailing: bool, // on the same line as the preceding token
+}
+
+impl Node for File {
+    fn start(&self) -> usize {
+        self.decls.first().map_or(self.eof, |d| d.start())
+    }
+
+    fn end(&self) -> usize {
+        self.eof
+    }
+
+    fn string(&self) -> String {
+        let mut s = String::new();
+
+        for decl in self.decls.iter() {
+            s.push_str(format!("{}\n", decl.string()).as_str());
+        }
+
+        s
+    }
 }
 
 impl Node for ReturnStmt {
@@ -97,6 +205,10 @@ impl Node for ReturnStmt {
         self.pos
     }
 
+    fn end(&self) -> usize {
+        self.semi + 1
+    }
+
     fn string(&self) -> String {
         match &self.value {
             Some(return_value) => {
@@ -113,6 +225,10 @@ impl Node for BreakStmt {
         self.pos
     }
 
+    fn end(&self) -> usize {
+        self.semi + 1
+    }
+
     fn string(&self) -> String {
         "break;".to_string()
     }
@@ -123,6 +239,10 @@ impl Node for ContinueStmt {
         self.pos
     }
 
+    fn end(&self) -> usize {
+        self.semi + 1
+    }
+
     fn string(&self) -> String {
         "continue;".to_string()
     }
@@ -133,6 +253,14 @@ impl Node for IfStmt {
         self.if_pos
     }
 
+    fn end(&self) -> usize {
+        match (&self._else, self.elifs.last()) {
+            (Some(_else), _) => _else.init.end(),
+            (None, Some(elif)) => elif.init.end(),
+            (None, None) => self.init.end(),
+        }
+    }
+
     fn string(&self) -> String {
         let mut s = format!("if ({}) {}", self.cond.string(), self.init.string());
 
@@ -153,6 +281,10 @@ impl Node for BlockStmt {
         self.lbrace
     }
 
+    fn end(&self) -> usize {
+        self.rbrace + 1
+    }
+
     fn string(&self) -> String {
         let mut s = String::from("{\n");
 
@@ -160,7 +292,7 @@ impl Node for BlockStmt {
             s.push_str(format!("\t{}\n", stmt.string()).as_str());
         }
 
-        s.push_str("}");
+        s.push('}');
 
         s
     }
@@ -170,19 +302,207 @@ impl Node for WhileStmt {
     fn start(&self) -> usize {
         self.pos
     }
+
+    fn end(&self) -> usize {
+        self.init.end()
+    }
     fn string(&self) -> String {
         format!("while ({}) {}", self.cond.string(), self.init.string())
     }
 }
 
+impl Node for SemiColonStmt {
+    fn start(&self) -> usize {
+        self.pos
+    }
+
+    fn end(&self) -> usize {
+        self.pos + 1
+    }
+
+    fn string(&self) -> String {
+        ";".to_string()
+    }
+}
+
+impl Node for ExprStmt {
+    fn start(&self) -> usize {
+        self.x.start()
+    }
+
+    fn end(&self) -> usize {
+        self.semi + 1
+    }
+
+    fn string(&self) -> String {
+        format!("{};", self.x.string())
+    }
+}
+
+impl Node for DeclStmt {
+    fn start(&self) -> usize {
+        self.decl.start()
+    }
+
+    fn end(&self) -> usize {
+        self.decl.end()
+    }
+
+    fn string(&self) -> String {
+        self.decl.string()
+    }
+}
+
+impl Node for DowhileStmt {
+    fn start(&self) -> usize {
+        self.do_pos
+    }
+
+    fn end(&self) -> usize {
+        self.semi + 1
+    }
+
+    fn string(&self) -> String {
+        format!("do {} while ({});", self.init.string(), self.cond.string())
+    }
+}
+
+impl Node for ForStmt {
+    fn start(&self) -> usize {
+        self.pos
+    }
+
+    fn end(&self) -> usize {
+        self.body.end()
+    }
+
+    fn string(&self) -> String {
+        let opt = |x: &Option<Box<dyn Expr>>| x.as_ref().map_or(String::new(), |x| x.string());
+
+        format!(
+            "for ({} {}; {}) {}",
+            self.init.as_ref().map_or(";".to_string(), |s| s.string()),
+            opt(&self.cond),
+            opt(&self.post),
+            self.body.string()
+        )
+    }
+}
+
+impl Node for SwitchStmt {
+    fn start(&self) -> usize {
+        self.pos
+    }
+
+    fn end(&self) -> usize {
+        self.body.end()
+    }
+
+    fn string(&self) -> String {
+        format!("switch ({}) {}", self.tag.string(), self.body.string())
+    }
+}
+
+impl Node for CaseStmt {
+    fn start(&self) -> usize {
+        self.pos
+    }
+
+    fn end(&self) -> usize {
+        self.stmt.end()
+    }
+
+    fn string(&self) -> String {
+        match &self.value {
+            Some(x) => format!("case {}: {}", x.string(), self.stmt.string()),
+            None => format!("default: {}", self.stmt.string()),
+        }
+    }
+}
+
+impl Node for LabeledStmt {
+    fn start(&self) -> usize {
+        self.label.pos
+    }
+
+    fn end(&self) -> usize {
+        self.stmt.end()
+    }
+
+    fn string(&self) -> String {
+        format!("{}: {}", self.label.name, self.stmt.string())
+    }
+}
+
+impl Node for GotoStmt {
+    fn start(&self) -> usize {
+        self.pos
+    }
+
+    fn end(&self) -> usize {
+        self.semi + 1
+    }
+
+    fn string(&self) -> String {
+        format!("goto {};", self.label.name)
+    }
+}
+
 // mark all the statement nodes
 
-impl Stmt for ReturnStmt {}
-impl Stmt for BreakStmt {}
-impl Stmt for ContinueStmt {}
-impl Stmt for IfStmt {}
-impl Stmt for BlockStmt {}
-impl Stmt for WhileStmt {}
+macro_rules! impl_kind {
+    ($tr:ident, $kind:ident, $($node:ident),*) => {
+        $(
+            impl $tr for $node {
+                fn kind(&self) -> $kind<'_> {
+                    $kind::$node(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_kind!(
+    Stmt,
+    StmtKind,
+    SemiColonStmt,
+    ExprStmt,
+    DeclStmt,
+    ReturnStmt,
+    BreakStmt,
+    ContinueStmt,
+    IfStmt,
+    BlockStmt,
+    WhileStmt,
+    DowhileStmt,
+    ForStmt,
+    SwitchStmt,
+    CaseStmt,
+    LabeledStmt,
+    GotoStmt,
+    StaticAssert
+);
+
+/// A typed view of an expression, used to walk the tree.
+pub enum ExprKind<'a> {
+    BasicLit(&'a BasicLit),
+    UnaryExpr(&'a UnaryExpr),
+    InfixExpr(&'a InfixExpr),
+    Ident(&'a Ident),
+    StarExpr(&'a StarExpr),
+    ParenExpr(&'a ParenExpr),
+    PostfixExpr(&'a PostfixExpr),
+    AssignExpr(&'a AssignExpr),
+    CondExpr(&'a CondExpr),
+    CallExpr(&'a CallExpr),
+    IndexExpr(&'a IndexExpr),
+    SelectorExpr(&'a SelectorExpr),
+    CastExpr(&'a CastExpr),
+    SizeofExpr(&'a SizeofExpr),
+    OffsetofExpr(&'a OffsetofExpr),
+    CompositeLit(&'a CompositeLit),
+    InitList(&'a InitList),
+}
 
 pub struct BasicLit {
     pub pos: usize,
@@ -213,11 +533,146 @@ pub struct StarExpr {
     pub x: Box<dyn Expr>,
 }
 
+pub struct ParenExpr {
+    pub lparen: usize,
+    pub x: Box<dyn Expr>,
+    pub rparen: usize,
+}
+
+// x++ or x--
+pub struct PostfixExpr {
+    pub x: Box<dyn Expr>,
+    pub op_pos: usize,
+    pub op: Token,
+}
+
+pub struct AssignExpr {
+    pub x: Box<dyn Expr>,
+    pub op_pos: usize,
+    pub op: Token, // =, +=, -=, ...
+    pub y: Box<dyn Expr>,
+}
+
+// cond ? x : y
+pub struct CondExpr {
+    pub cond: Box<dyn Expr>,
+    pub question: usize,
+    pub x: Box<dyn Expr>,
+    pub colon: usize,
+    pub y: Box<dyn Expr>,
+}
+
+pub struct CallExpr {
+    pub fun: Box<dyn Expr>,
+    pub lparen: usize,
+    pub args: Vec<Box<dyn Expr>>,
+    pub rparen: usize,
+}
+
+pub struct IndexExpr {
+    pub x: Box<dyn Expr>,
+    pub lbrack: usize,
+    pub index: Box<dyn Expr>,
+    pub rbrack: usize,
+}
+
+// x.sel or x->sel
+pub struct SelectorExpr {
+    pub x: Box<dyn Expr>,
+    pub op_pos: usize,
+    pub op: Token,
+    pub sel: Ident,
+}
+
+pub struct CastExpr {
+    pub lparen: usize,
+    pub typ: TypeName,
+    pub rparen: usize,
+    pub x: Box<dyn Expr>,
+}
+
+pub enum SizeofArg {
+    Expr(Box<dyn Expr>),
+    Type(TypeName),
+}
+
+// sizeof x, sizeof(T) or _Alignof(T)
+pub struct SizeofExpr {
+    pub pos: usize,
+    pub op: Token, // SIZEOF or ALIGNOF
+    pub arg: SizeofArg,
+    pub end: usize,
+}
+
+// offsetof(T, member.path[1])
+pub struct OffsetofExpr {
+    pub name: Ident, // offsetof or __builtin_offsetof
+    pub lparen: usize,
+    pub typ: TypeName,
+    pub member: Ident,
+    pub path: Vec<Designator>,
+    pub rparen: usize,
+}
+
+// (type){ ... }
+pub struct CompositeLit {
+    pub lparen: usize,
+    pub typ: TypeName,
+    pub rparen: usize,
+    pub init: InitList,
+}
+
+pub enum Designator {
+    // .name
+    Field {
+        dot: usize,
+        name: Ident,
+    },
+    // [index]
+    Index {
+        lbrack: usize,
+        index: Box<dyn Expr>,
+        rbrack: usize,
+    },
+}
+
+impl Designator {
+    pub fn start(&self) -> usize {
+        match self {
+            Designator::Field { dot, .. } => *dot,
+            Designator::Index { lbrack, .. } => *lbrack,
+        }
+    }
+
+    pub fn format(&self, expr: &dyn Fn(&dyn Expr) -> String) -> String {
+        match self {
+            Designator::Field { name, .. } => format!(".{}", name.name),
+            Designator::Index { index, .. } => format!("[{}]", expr(index.as_ref())),
+        }
+    }
+}
+
+pub struct Initializer {
+    pub designators: Vec<Designator>,
+    pub value: Box<dyn Expr>,
+}
+
+// { a, .b = c, [1] = d }
+pub struct InitList {
+    pub lbrace: usize,
+    pub elems: Vec<Initializer>,
+    pub rbrace: usize,
+}
+
 impl Node for BasicLit {
     fn start(&self) -> usize {
         self.pos
     }
 
+    fn end(&self) -> usize {
+        self.pos + self.lit.len()
+    }
+
     fn string(&self) -> String {
         self.lit.clone()
     }
@@ -228,6 +683,10 @@ impl Node for UnaryExpr {
         self.op_pos
     }
 
+    fn end(&self) -> usize {
+        self.x.end()
+    }
+
     fn string(&self) -> String {
         format!("({}{})", self.op.to_str(), self.x.string())
     }
@@ -238,6 +697,10 @@ impl Node for Ident {
         self.pos
     }
 
+    fn end(&self) -> usize {
+        self.pos + self.name.len()
+    }
+
     fn string(&self) -> String {
         self.name.clone()
     }
@@ -248,6 +711,10 @@ impl Node for StarExpr {
         self.pos
     }
 
+    fn end(&self) -> usize {
+        self.x.end()
+    }
+
     fn string(&self) -> String {
         format!("(*{})", self.x.string())
     }
@@ -258,6 +725,10 @@ impl Node for InfixExpr {
         self.x.start()
     }
 
+    fn end(&self) -> usize {
+        self.y.end()
+    }
+
     fn string(&self) -> String {
         format!(
             "({} {} {})",
@@ -268,8 +739,686 @@ impl Node for InfixExpr {
     }
 }
 
-impl Expr for BasicLit {}
-impl Expr for UnaryExpr {}
-impl Expr for Ident {}
-impl Expr for StarExpr {}
-impl Expr for InfixExpr {}
+impl Node for ParenExpr {
+    fn start(&self) -> usize {
+        self.lparen
+    }
+
+    fn end(&self) -> usize {
+        self.rparen + 1
+    }
+
+    fn string(&self) -> String {
+        self.x.string()
+    }
+}
+
+impl Node for PostfixExpr {
+    fn start(&self) -> usize {
+        self.x.start()
+    }
+
+    fn end(&self) -> usize {
+        self.op_pos + 2
+    }
+
+    fn string(&self) -> String {
+        format!("({}{})", self.x.string(), self.op.to_str())
+    }
+}
+
+impl Node for AssignExpr {
+    fn start(&self) -> usize {
+        self.x.start()
+    }
+
+    fn end(&self) -> usize {
+        self.y.end()
+    }
+
+    fn string(&self) -> String {
+        format!(
+            "({} {} {})",
+            self.x.string(),
+            self.op.to_str(),
+            self.y.string()
+        )
+    }
+}
+
+impl Node for CondExpr {
+    fn start(&self) -> usize {
+        self.cond.start()
+    }
+
+    fn end(&self) -> usize {
+        self.y.end()
+    }
+
+    fn string(&self) -> String {
+        format!(
+            "({} ? {} : {})",
+            self.cond.string(),
+            self.x.string(),
+            self.y.string()
+        )
+    }
+}
+
+impl Node for CallExpr {
+    fn start(&self) -> usize {
+        self.fun.start()
+    }
+
+    fn end(&self) -> usize {
+        self.rparen + 1
+    }
+
+    fn string(&self) -> String {
+        let args: Vec<_> = self.args.iter().map(|x| x.string()).collect();
+        format!("{}({})", self.fun.string(), args.join(", "))
+    }
+}
+
+impl Node for IndexExpr {
+    fn start(&self) -> usize {
+        self.x.start()
+    }
+
+    fn end(&self) -> usize {
+        self.rbrack + 1
+    }
+
+    fn string(&self) -> String {
+        format!("{}[{}]", self.x.string(), self.index.string())
+    }
+}
+
+impl Node for SelectorExpr {
+    fn start(&self) -> usize {
+        self.x.start()
+    }
+
+    fn end(&self) -> usize {
+        self.sel.end()
+    }
+
+    fn string(&self) -> String {
+        format!("{}{}{}", self.x.string(), self.op.to_str(), self.sel.name)
+    }
+}
+
+impl Node for CastExpr {
+    fn start(&self) -> usize {
+        self.lparen
+    }
+
+    fn end(&self) -> usize {
+        self.x.end()
+    }
+
+    fn string(&self) -> String {
+        format!("(({}) {})", self.typ.string(), self.x.string())
+    }
+}
+
+impl Node for SizeofExpr {
+    fn start(&self) -> usize {
+        self.pos
+    }
+
+    fn end(&self) -> usize {
+        self.end
+    }
+
+    fn string(&self) -> String {
+        match &self.arg {
+            SizeofArg::Expr(x) => format!("{} {}", self.op.to_str(), x.string()),
+            SizeofArg::Type(t) => format!("{}({})", self.op.to_str(), t.string()),
+        }
+    }
+}
+
+impl Node for OffsetofExpr {
+    fn start(&self) -> usize {
+        self.name.pos
+    }
+
+    fn end(&self) -> usize {
+        self.rparen + 1
+    }
+
+    fn string(&self) -> String {
+        let path: String = self
+            .path
+            .iter()
+            .map(|d| d.format(&|x| x.string()))
+            .collect();
+
+        format!(
+            "{}({}, {}{})",
+            self.name.name,
+            self.typ.string(),
+            self.member.name,
+            path
+        )
+    }
+}
+
+impl Node for CompositeLit {
+    fn start(&self) -> usize {
+        self.lparen
+    }
+
+    fn end(&self) -> usize {
+        self.init.end()
+    }
+
+    fn string(&self) -> String {
+        format!("({}){}", self.typ.string(), self.init.string())
+    }
+}
+
+impl Node for InitList {
+    fn start(&self) -> usize {
+        self.lbrace
+    }
+
+    fn end(&self) -> usize {
+        self.rbrace + 1
+    }
+
+    fn string(&self) -> String {
+        self.format(&|x| x.string())
+    }
+}
+
+impl InitList {
+    /// Spells the list, `expr` formats the expressions in it.
+    pub fn format(&self, expr: &dyn Fn(&dyn Expr) -> String) -> String {
+        let elems: Vec<_> = self
+            .elems
+            .iter()
+            .map(|e| {
+                let mut s = String::new();
+
+                for d in e.designators.iter() {
+                    s.push_str(&d.format(expr));
+                }
+
+                if !e.designators.is_empty() {
+                    s.push_str(" = ");
+                }
+
+                s + &expr(e.value.as_ref())
+            })
+            .collect();
+
+        if elems.is_empty() {
+            "{}".to_string()
+        } else {
+            format!("{{ {} }}", elems.join(", "))
+        }
+    }
+}
+
+impl_kind!(
+    Expr,
+    ExprKind,
+    BasicLit,
+    UnaryExpr,
+    InfixExpr,
+    Ident,
+    StarExpr,
+    ParenExpr,
+    PostfixExpr,
+    AssignExpr,
+    CondExpr,
+    CallExpr,
+    IndexExpr,
+    SelectorExpr,
+    CastExpr,
+    SizeofExpr,
+    OffsetofExpr,
+    CompositeLit,
+    InitList
+);
+
+/// A typed view of a declaration, used to walk the tree.
+pub enum DeclKind<'a> {
+    GenDecl(&'a GenDecl),
+    FuncDecl(&'a FuncDecl),
+    StaticAssert(&'a StaticAssert),
+}
+
+// int x = 1, *p;
+pub struct GenDecl {
+    pub specs: DeclSpec,
+    pub decls: Vec<InitDecl>,
+    pub semi: usize,
+}
+
+pub struct InitDecl {
+    pub declarator: Declarator,
+    pub init: Option<Box<dyn Expr>>, // an expression or an InitList
+}
+
+// int main(void) { ... }
+pub struct FuncDecl {
+    pub specs: DeclSpec,
+    pub declarator: Declarator, // its outermost derived type is a function
+    pub body: BlockStmt,
+}
+
+// _Static_assert(sizeof(int) == 4, "int is 32 bits");
+// both a declaration and a statement
+pub struct StaticAssert {
+    pub pos: usize, // position of the '_Static_assert' keyword
+    pub cond: Box<dyn Expr>,
+    pub msg: BasicLit,
+    pub semi: usize,
+}
+
+/// The declaration specifiers in front of the declarators.
+pub struct DeclSpec {
+    pub pos: usize,
+    pub storage: Option<Token>, // typedef, extern, static, auto or register
+    pub quals: Vec<Token>,      // const, volatile, restrict
+    pub inline: bool,
+    pub typ: TypeSpec,
+    pub end: usize,
+}
+
+pub enum TypeSpec {
+    Basic(Vec<Token>), // the keywords in source order, e.g. `unsigned long int`
+    Struct(StructSpec),
+    Enum(EnumSpec),
+    Typedef(Ident),
+}
+
+// struct tag { ... } or union tag { ... }
+pub struct StructSpec {
+    pub pos: usize,
+    pub kw: Token, // STRUCT or UNION
+    pub tag: Option<Ident>,
+    pub fields: Option<Vec<FieldDecl>>, // None without a body
+    pub end: usize,
+}
+
+pub struct FieldDecl {
+    pub specs: DeclSpec,
+    pub decls: Vec<FieldDeclarator>,
+    pub semi: usize,
+}
+
+pub struct FieldDeclarator {
+    pub declarator: Declarator,
+    pub bits: Option<Box<dyn Expr>>, // width of a bit-field
+}
+
+pub struct EnumSpec {
+    pub pos: usize,
+    pub tag: Option<Ident>,
+    pub enumerators: Option<Vec<Enumerator>>, // None without a body
+    pub end: usize,
+}
+
+pub struct Enumerator {
+    pub name: Ident,
+    pub value: Option<Box<dyn Expr>>,
+}
+
+/// A declarator, `derived` lists the type derivations from the name
+/// outward: `*a[3]` is an array of pointers, `(*a)[3]` a pointer to an
+/// array.
+pub struct Declarator {
+    pub pos: usize,
+    pub name: Option<Ident>, // None in abstract declarators
+    pub derived: Vec<Derived>,
+    pub end: usize,
+}
+
+pub enum Derived {
+    Pointer {
+        pos: usize,
+        quals: Vec<Token>,
+    },
+    Array {
+        lbrack: usize,
+        len: Option<Box<dyn Expr>>,
+        rbrack: usize,
+    },
+    Function(FuncType),
+}
+
+pub struct FuncType {
+    pub lparen: usize,
+    pub params: Vec<ParamDecl>,
+    pub variadic: bool,
+    pub rparen: usize,
+}
+
+pub struct ParamDecl {
+    pub specs: DeclSpec,
+    pub declarator: Declarator,
+}
+
+// the type in a cast, sizeof or compound literal
+pub struct TypeName {
+    pub specs: DeclSpec,
+    pub declarator: Declarator, // abstract
+}
+
+impl DeclSpec {
+    /// Spells the specifiers, `expr` formats the expressions in them.
+    pub fn format(&self, expr: &dyn Fn(&dyn Expr) -> String) -> String {
+        let mut words: Vec<String> = Vec::new();
+
+        words.extend(self.storage.iter().map(|t| t.to_str()));
+        if self.inline {
+            words.push("inline".to_string());
+        }
+        words.extend(self.quals.iter().map(|t| t.to_str()));
+        words.push(self.typ.format(expr));
+
+        words.join(" ")
+    }
+}
+
+impl TypeSpec {
+    /// Names the type, without the body of a struct or an enum.
+    pub fn name(&self) -> String {
+        let tag = |tag: &Option<Ident>| {
+            tag.as_ref()
+                .map_or("<anonymous>".to_string(), |t| t.name.clone())
+        };
+
+        match self {
+            TypeSpec::Struct(s) => format!("{} {}", s.kw.to_str(), tag(&s.tag)),
+            TypeSpec::Enum(e) => format!("enum {}", tag(&e.tag)),
+            _ => self.format(&|x| x.string()),
+        }
+    }
+
+    pub fn format(&self, expr: &dyn Fn(&dyn Expr) -> String) -> String {
+        match self {
+            TypeSpec::Basic(toks) => toks
+                .iter()
+                .map(|t| t.to_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+
+            TypeSpec::Struct(s) => {
+                let mut out = s.kw.to_str();
+
+                if let Some(tag) = &s.tag {
+                    out.push(' ');
+                    out.push_str(&tag.name);
+                }
+
+                if let Some(fields) = &s.fields {
+                    out.push_str(" {");
+                    for f in fields.iter() {
+                        out.push(' ');
+                        out.push_str(&f.format(expr));
+                    }
+                    out.push_str(" }");
+                }
+
+                out
+            }
+
+            TypeSpec::Enum(e) => {
+                let mut out = "enum".to_string();
+
+                if let Some(tag) = &e.tag {
+                    out.push(' ');
+                    out.push_str(&tag.name);
+                }
+
+                if let Some(enumerators) = &e.enumerators {
+                    let list: Vec<_> = enumerators.iter().map(|e| e.format(expr)).collect();
+                    out.push_str(&format!(" {{ {} }}", list.join(", ")));
+                }
+
+                out
+            }
+
+            TypeSpec::Typedef(name) => name.name.clone(),
+        }
+    }
+}
+
+impl FieldDecl {
+    pub fn format(&self, expr: &dyn Fn(&dyn Expr) -> String) -> String {
+        let decls: Vec<_> = self
+            .decls
+            .iter()
+            .map(|d| match &d.bits {
+                Some(bits) => format!(
+                    "{} : {}",
+                    d.declarator.format("", expr),
+                    expr(bits.as_ref())
+                )
+                .trim_start()
+                .to_string(),
+                None => d.declarator.format("", expr),
+            })
+            .collect();
+
+        join_decl(self.specs.format(expr), &decls.join(", ")) + ";"
+    }
+}
+
+impl Enumerator {
+    pub fn format(&self, expr: &dyn Fn(&dyn Expr) -> String) -> String {
+        match &self.value {
+            Some(x) => format!("{} = {}", self.name.name, expr(x.as_ref())),
+            None => self.name.name.clone(),
+        }
+    }
+}
+
+impl Declarator {
+    /// Spells the declarator around `inner`, which is put in place of the
+    /// name.
+    pub fn format(&self, inner: &str, expr: &dyn Fn(&dyn Expr) -> String) -> String {
+        let s = match &self.name {
+            Some(name) => format!("{}{}", name.name, inner),
+            None => inner.to_string(),
+        };
+
+        self.derive(s, expr, true)
+    }
+
+    /// Spells the type of the declared name, e.g. `int *[3]` for `int *a[3]`.
+    pub fn type_string(&self, specs: &DeclSpec, expr: &dyn Fn(&dyn Expr) -> String) -> String {
+        let mut words: Vec<String> = specs.quals.iter().map(|t| t.to_str()).collect();
+        words.push(specs.typ.name());
+
+        join_decl(words.join(" "), &self.derive(String::new(), expr, false))
+    }
+
+    // wraps `s` in the derived types, `names` keeps the names of parameters
+    fn derive(&self, mut s: String, expr: &dyn Fn(&dyn Expr) -> String, names: bool) -> String {
+        let mut pointer = false; // s starts with a pointer declarator
+
+        for d in self.derived.iter() {
+            match d {
+                Derived::Pointer { quals, .. } => {
+                    let mut p = "*".to_string();
+                    for q in quals.iter() {
+                        p.push_str(&q.to_str());
+                        p.push(' ');
+                    }
+                    s = p + &s;
+                    pointer = true;
+                }
+
+                Derived::Array { len, .. } => {
+                    if pointer {
+                        s = format!("({})", s.trim_end());
+                    }
+                    let len = len.as_ref().map_or(String::new(), |x| expr(x.as_ref()));
+                    s = format!("{}[{}]", s, len);
+                    pointer = false;
+                }
+
+                Derived::Function(f) => {
+                    if pointer {
+                        s = format!("({})", s.trim_end());
+                    }
+                    s = format!("{}({})", s, f.format(expr, names));
+                    pointer = false;
+                }
+            }
+        }
+
+        s.trim_end().to_string()
+    }
+
+    /// Returns the start of the declarator, or of the specifiers when it is
+    /// empty.
+    pub fn start(&self, specs: &DeclSpec) -> usize {
+        if self.end > self.pos {
+            self.pos
+        } else {
+            specs.pos
+        }
+    }
+
+    /// Returns the parameters if the declarator declares a function.
+    pub fn function(&self) -> Option<&FuncType> {
+        match self.derived.first() {
+            Some(Derived::Function(f)) => Some(f),
+            _ => None,
+        }
+    }
+}
+
+impl FuncType {
+    /// Spells the parameter list without the parentheses, `names` keeps
+    /// the names of the parameters.
+    pub fn format(&self, expr: &dyn Fn(&dyn Expr) -> String, names: bool) -> String {
+        let mut params: Vec<_> = self
+            .params
+            .iter()
+            .map(|p| {
+                if names {
+                    join_decl(p.specs.format(expr), &p.declarator.format("", expr))
+                } else {
+                    p.declarator.type_string(&p.specs, expr)
+                }
+            })
+            .collect();
+
+        if self.variadic {
+            params.push("...".to_string());
+        }
+
+        params.join(", ")
+    }
+}
+
+impl TypeName {
+    pub fn format(&self, expr: &dyn Fn(&dyn Expr) -> String) -> String {
+        join_decl(self.specs.format(expr), &self.declarator.format("", expr))
+    }
+}
+
+impl GenDecl {
+    pub fn format(&self, expr: &dyn Fn(&dyn Expr) -> String) -> String {
+        let decls: Vec<_> = self
+            .decls
+            .iter()
+            .map(|d| match &d.init {
+                Some(x) => format!("{} = {}", d.declarator.format("", expr), expr(x.as_ref())),
+                None => d.declarator.format("", expr),
+            })
+            .collect();
+
+        join_decl(self.specs.format(expr), &decls.join(", ")) + ";"
+    }
+}
+
+// joins specifiers and declarators, `int` and `*p` into `int *p`
+pub fn join_decl(specs: String, decls: &str) -> String {
+    if decls.is_empty() {
+        specs
+    } else {
+        format!("{} {}", specs, decls)
+    }
+}
+
+impl Node for GenDecl {
+    fn start(&self) -> usize {
+        self.specs.pos
+    }
+
+    fn end(&self) -> usize {
+        self.semi + 1
+    }
+
+    fn string(&self) -> String {
+        self.format(&|x| x.string())
+    }
+}
+
+impl Node for FuncDecl {
+    fn start(&self) -> usize {
+        self.specs.pos
+    }
+
+    fn end(&self) -> usize {
+        self.body.end()
+    }
+
+    fn string(&self) -> String {
+        let head = join_decl(
+            self.specs.format(&|x| x.string()),
+            &self.declarator.format("", &|x| x.string()),
+        );
+
+        format!("{} {}", head, self.body.string())
+    }
+}
+
+impl StaticAssert {
+    pub fn format(&self, expr: &dyn Fn(&dyn Expr) -> String) -> String {
+        format!(
+            "_Static_assert({}, {});",
+            expr(self.cond.as_ref()),
+            self.msg.lit
+        )
+    }
+}
+
+impl Node for StaticAssert {
+    fn start(&self) -> usize {
+        self.pos
+    }
+
+    fn end(&self) -> usize {
+        self.semi + 1
+    }
+
+    fn string(&self) -> String {
+        self.format(&|x| x.string())
+    }
+}
+
+impl Node for TypeName {
+    fn start(&self) -> usize {
+        self.specs.pos
+    }
+
+    fn end(&self) -> usize {
+        self.declarator.end.max(self.specs.end)
+    }
+
+    fn string(&self) -> String {
+        self.format(&|x| x.string())
+    }
+}
+
+impl_kind!(Decl, DeclKind, GenDecl, FuncDecl, StaticAssert);
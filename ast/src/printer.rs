@@ -0,0 +1,648 @@
+//! Prints a syntax tree back as C source, with the minimum of parentheses.
+
+use token::{Token, HIGHEST_PREC, UNARY_PREC};
+
+use crate::{
+    join_decl, BlockStmt, Comment, Decl, DeclKind, DeclSpec, Expr, ExprKind, File, GenDecl,
+    SizeofArg, StaticAssert, Stmt, StmtKind, TypeName, TypeSpec,
+};
+
+// the precedence of an operand that must not be a comma expression, like a
+// function argument
+const ASSIGN_PREC: usize = 2;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BraceStyle {
+    #[default]
+    Attach, // `if (x) {` on one line
+    Allman, // braces on their own line
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Style {
+    pub brace: BraceStyle,
+    pub indent: usize,     // columns per indentation level
+    pub tabs: bool,        // indent with tabs instead of spaces
+    pub line_width: usize, // long expressions are wrapped past this column
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            brace: BraceStyle::Attach,
+            indent: 4,
+            tabs: false,
+            line_width: 80,
+        }
+    }
+}
+
+pub struct Printer<'a> {
+    style: Style,
+    out: String,
+    level: usize, // indentation level
+
+    comments: &'a [Comment], // comments not printed yet
+}
+
+impl<'a> Printer<'a> {
+    pub fn new(style: Style) -> Self {
+        Self {
+            style,
+            out: String::new(),
+            level: 0,
+            comments: &[],
+        }
+    }
+
+    /// Prints a whole file, keeping its comments.
+    pub fn file(mut self, file: &'a File) -> String {
+        self.comments = &file.comments;
+
+        for (i, d) in file.decls.iter().enumerate() {
+            // put function definitions apart from their neighbours
+            let func = |d: &dyn Decl| matches!(d.kind(), DeclKind::FuncDecl(_));
+            if i > 0 && (func(d.as_ref()) || func(file.decls[i - 1].as_ref())) {
+                self.out.push('\n');
+            }
+
+            self.decl(d.as_ref());
+        }
+        self.comments_before(usize::MAX);
+
+        self.out
+    }
+
+    pub fn print_stmt(mut self, s: &dyn Stmt) -> String {
+        self.stmt(s);
+        self.out
+    }
+
+    pub fn print_expr(&self, x: &dyn Expr) -> String {
+        self.expr(x)
+    }
+
+    fn indentation(&self, level: usize) -> String {
+        if self.style.tabs {
+            "\t".repeat(level)
+        } else {
+            " ".repeat(level * self.style.indent)
+        }
+    }
+
+    // the column at which the next character is printed
+    fn column(&self) -> usize {
+        let line = self.out.rsplit('\n').next().unwrap_or_default();
+        let tabs = line.chars().filter(|&c| c == '\t').count();
+
+        line.chars().count() + tabs * self.style.indent.saturating_sub(1)
+    }
+
+    fn line(&mut self, text: &str) {
+        self.out.push_str(&self.indentation(self.level));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn comments_before(&mut self, pos: usize) {
+        while let Some((c, rest)) = self.comments.split_first() {
+            if c.pos >= pos {
+                break;
+            }
+
+            if c.trailing && self.out.ends_with('\n') {
+                self.out.pop();
+                self.out.push_str("  ");
+                self.out.push_str(&c.text);
+                self.out.push('\n');
+            } else {
+                self.line(&c.text);
+            }
+
+            self.comments = rest;
+        }
+    }
+
+    fn decl(&mut self, d: &dyn Decl) {
+        self.comments_before(d.start());
+
+        match d.kind() {
+            DeclKind::GenDecl(d) => {
+                self.out.push_str(&self.indentation(self.level));
+                self.gen_decl(d);
+                self.out.push('\n');
+            }
+
+            DeclKind::FuncDecl(d) => {
+                self.out.push_str(&self.indentation(self.level));
+                self.specs(&d.specs);
+
+                let declarator = d.declarator.format("", &|x| self.operand(x, ASSIGN_PREC));
+                self.out.push(' ');
+                self.out.push_str(&declarator);
+
+                self.body(&d.body);
+            }
+
+            DeclKind::StaticAssert(s) => self.static_assert(s),
+        }
+    }
+
+    fn static_assert(&mut self, s: &StaticAssert) {
+        let text = s.format(&|x| self.operand(x, ASSIGN_PREC));
+        self.line(&text);
+    }
+
+    // prints a declaration without indentation or newline
+    fn gen_decl(&mut self, d: &GenDecl) {
+        self.specs(&d.specs);
+
+        let decls: Vec<_> = d
+            .decls
+            .iter()
+            .map(|d| {
+                let declarator = d.declarator.format("", &|x| self.operand(x, ASSIGN_PREC));
+                match &d.init {
+                    Some(x) => {
+                        format!("{} = {}", declarator, self.operand(x.as_ref(), ASSIGN_PREC))
+                    }
+                    None => declarator,
+                }
+            })
+            .collect();
+
+        if !decls.is_empty() {
+            self.out.push(' ');
+            self.out.push_str(&decls.join(", "));
+        }
+        self.out.push(';');
+    }
+
+    // prints declaration specifiers, with the body of a struct or an enum
+    // on lines of its own
+    fn specs(&mut self, specs: &DeclSpec) {
+        let expr = |x: &dyn Expr| self.operand(x, ASSIGN_PREC);
+
+        let mut words: Vec<String> = Vec::new();
+        words.extend(specs.storage.iter().map(|t| t.to_str()));
+        if specs.inline {
+            words.push("inline".to_string());
+        }
+        words.extend(specs.quals.iter().map(|t| t.to_str()));
+
+        let (head, lines) = match &specs.typ {
+            TypeSpec::Struct(s) if s.fields.is_some() => {
+                let fields = s.fields.as_ref().unwrap();
+                (
+                    specs.typ.name().replace(" <anonymous>", ""),
+                    Some(fields.iter().map(|f| f.format(&expr)).collect::<Vec<_>>()),
+                )
+            }
+
+            TypeSpec::Enum(e) if e.enumerators.is_some() => {
+                let enumerators = e.enumerators.as_ref().unwrap();
+                (
+                    specs.typ.name().replace(" <anonymous>", ""),
+                    Some(
+                        enumerators
+                            .iter()
+                            .map(|e| format!("{},", e.format(&expr)))
+                            .collect(),
+                    ),
+                )
+            }
+
+            typ => (typ.format(&expr), None),
+        };
+
+        words.push(head);
+        self.out.push_str(&words.join(" "));
+
+        let Some(lines) = lines else {
+            return;
+        };
+
+        match self.style.brace {
+            BraceStyle::Attach => self.out.push_str(" {\n"),
+            BraceStyle::Allman => {
+                self.out.push('\n');
+                self.line("{");
+            }
+        }
+
+        self.level += 1;
+        for l in lines.iter() {
+            self.line(l);
+        }
+        self.level -= 1;
+
+        self.out.push_str(&self.indentation(self.level));
+        self.out.push('}');
+    }
+
+    fn stmt(&mut self, s: &dyn Stmt) {
+        self.comments_before(s.start());
+
+        match s.kind() {
+            StmtKind::ReturnStmt(s) => match &s.value {
+                Some(x) => {
+                    self.out.push_str(&self.indentation(self.level));
+                    self.out.push_str("return ");
+                    self.out.push_str(&self.wrapped(x.as_ref()));
+                    self.out.push_str(";\n");
+                }
+                None => self.line("return;"),
+            },
+
+            StmtKind::BreakStmt(_) => self.line("break;"),
+            StmtKind::ContinueStmt(_) => self.line("continue;"),
+
+            StmtKind::IfStmt(s) => {
+                self.out.push_str(&self.indentation(self.level));
+                self.head("if", s.cond.as_ref());
+                self.body(s.init.as_ref());
+
+                for elif in s.elifs.iter() {
+                    self.else_();
+                    self.out.push(' ');
+                    self.head("if", elif.cond.as_ref());
+                    self.body(elif.init.as_ref());
+                }
+
+                if let Some(_else) = &s._else {
+                    self.else_();
+                    self.body(_else.init.as_ref());
+                }
+            }
+
+            StmtKind::BlockStmt(s) => {
+                self.out.push_str(&self.indentation(self.level));
+                self.block(s);
+            }
+
+            StmtKind::WhileStmt(s) => {
+                self.out.push_str(&self.indentation(self.level));
+                self.head("while", s.cond.as_ref());
+                self.body(s.init.as_ref());
+            }
+
+            StmtKind::SemiColonStmt(_) => self.line(";"),
+
+            StmtKind::ExprStmt(s) => {
+                self.out.push_str(&self.indentation(self.level));
+                self.out.push_str(&self.wrapped(s.x.as_ref()));
+                self.out.push_str(";\n");
+            }
+
+            StmtKind::DeclStmt(s) => {
+                self.out.push_str(&self.indentation(self.level));
+                self.gen_decl(&s.decl);
+                self.out.push('\n');
+            }
+
+            StmtKind::DowhileStmt(s) => {
+                self.out.push_str(&self.indentation(self.level));
+                self.out.push_str("do");
+                self.body(s.init.as_ref());
+
+                if self.style.brace == BraceStyle::Attach && self.out.ends_with("}\n") {
+                    self.out.pop();
+                    self.out.push(' ');
+                } else {
+                    self.out.push_str(&self.indentation(self.level));
+                }
+                self.head("while", s.cond.as_ref());
+                self.out.push_str(";\n");
+            }
+
+            StmtKind::ForStmt(s) => {
+                self.out.push_str(&self.indentation(self.level));
+                self.out.push_str("for (");
+
+                match s.init.as_ref().map(|s| s.kind()) {
+                    Some(StmtKind::DeclStmt(d)) => self.gen_decl(&d.decl),
+                    Some(StmtKind::ExprStmt(x)) => {
+                        self.out.push_str(&self.expr(x.x.as_ref()));
+                        self.out.push(';');
+                    }
+                    _ => self.out.push(';'),
+                }
+
+                if let Some(cond) = &s.cond {
+                    self.out.push(' ');
+                    self.out.push_str(&self.expr(cond.as_ref()));
+                }
+                self.out.push(';');
+
+                if let Some(post) = &s.post {
+                    self.out.push(' ');
+                    self.out.push_str(&self.expr(post.as_ref()));
+                }
+                self.out.push(')');
+
+                self.body(s.body.as_ref());
+            }
+
+            StmtKind::SwitchStmt(s) => {
+                self.out.push_str(&self.indentation(self.level));
+                self.head("switch", s.tag.as_ref());
+                self.body(s.body.as_ref());
+            }
+
+            // labels are outdented, the statements following them in the
+            // block are siblings of the labeled statement
+            StmtKind::CaseStmt(s) => {
+                let label = match &s.value {
+                    Some(x) => format!("case {}:", self.expr(x.as_ref())),
+                    None => "default:".to_string(),
+                };
+                self.label(&label, s.stmt.as_ref());
+            }
+
+            StmtKind::LabeledStmt(s) => {
+                self.label(&format!("{}:", s.label.name), s.stmt.as_ref());
+            }
+
+            StmtKind::GotoStmt(s) => self.line(&format!("goto {};", s.label.name)),
+            StmtKind::StaticAssert(s) => self.static_assert(s),
+        }
+    }
+
+    fn label(&mut self, label: &str, s: &dyn Stmt) {
+        let level = self.level;
+
+        self.level = level.saturating_sub(1);
+        self.line(label);
+        self.level = level;
+
+        self.stmt(s);
+    }
+
+    // prints `keyword (cond)`
+    fn head(&mut self, keyword: &str, cond: &dyn Expr) {
+        self.out.push_str(keyword);
+        self.out.push_str(" (");
+        self.out.push_str(&self.wrapped(cond));
+        self.out.push(')');
+    }
+
+    // prints the statement controlled by an if or a loop, the head is
+    // already printed.
+    fn body(&mut self, s: &dyn Stmt) {
+        match s.kind() {
+            StmtKind::BlockStmt(b) => {
+                match self.style.brace {
+                    BraceStyle::Attach => self.out.push(' '),
+                    BraceStyle::Allman => {
+                        self.out.push('\n');
+                        self.out.push_str(&self.indentation(self.level));
+                    }
+                }
+                self.block(b);
+            }
+
+            _ => {
+                self.out.push('\n');
+                self.level += 1;
+                self.stmt(s);
+                self.level -= 1;
+            }
+        }
+    }
+
+    fn else_(&mut self) {
+        if self.style.brace == BraceStyle::Attach && self.out.ends_with("}\n") {
+            self.out.pop();
+            self.out.push_str(" else");
+        } else {
+            self.out.push_str(&self.indentation(self.level));
+            self.out.push_str("else");
+        }
+    }
+
+    // prints a block, the indentation of the opening brace is already printed
+    fn block(&mut self, b: &BlockStmt) {
+        self.out.push_str("{\n");
+
+        self.level += 1;
+        for s in b.stmts.iter() {
+            self.stmt(s.as_ref());
+        }
+        self.comments_before(b.rbrace);
+        self.level -= 1;
+
+        self.line("}");
+    }
+
+    // formats an expression starting at the current column, wrapping
+    // long chains of binary operators.
+    fn wrapped(&self, x: &dyn Expr) -> String {
+        let flat = self.expr(x);
+        let col = self.column();
+
+        if col + flat.chars().count() <= self.style.line_width {
+            return flat;
+        }
+
+        let ExprKind::InfixExpr(infix) = x.kind() else {
+            return flat;
+        };
+
+        // collect a left associative chain of operators of the same precedence
+        let prec = infix.op.precedence();
+        let mut chain = Vec::new();
+        let mut left: &dyn Expr = x;
+
+        while let ExprKind::InfixExpr(e) = left.kind() {
+            if e.op.precedence() != prec {
+                break;
+            }
+            chain.push((e.op, e.y.as_ref()));
+            left = e.x.as_ref();
+        }
+
+        let cont = self.indentation(self.level + 2);
+
+        let mut s = self.operand(left, prec);
+        let mut width = col + s.chars().count();
+
+        for (op, y) in chain.into_iter().rev() {
+            let y = self.operand(y, prec + 1);
+            let piece = format!("{}{}", spaced(op), y);
+
+            if width + piece.chars().count() > self.style.line_width {
+                s.push_str(&format!("{}\n{}{}", spaced(op).trim_end(), cont, y));
+                width = cont.chars().count() + y.chars().count();
+            } else {
+                width += piece.chars().count();
+                s.push_str(&piece);
+            }
+        }
+
+        s
+    }
+
+    fn expr(&self, x: &dyn Expr) -> String {
+        match x.kind() {
+            ExprKind::BasicLit(x) => x.lit.clone(),
+            ExprKind::Ident(x) => x.name.clone(),
+
+            ExprKind::UnaryExpr(x) => {
+                let op = x.op.to_str();
+                let operand = self.operand(x.x.as_ref(), UNARY_PREC);
+
+                // keep `- -x` from turning into `--x`
+                if matches!(op.as_str(), "-" | "+" | "&") && operand.starts_with(&op) {
+                    format!("{} {}", op, operand)
+                } else {
+                    format!("{}{}", op, operand)
+                }
+            }
+
+            ExprKind::InfixExpr(x) => {
+                let prec = x.op.precedence();
+
+                // binary operators are left associative
+                format!(
+                    "{}{}{}",
+                    self.operand(x.x.as_ref(), prec),
+                    spaced(x.op),
+                    self.operand(x.y.as_ref(), prec + 1)
+                )
+            }
+
+            ExprKind::StarExpr(x) => format!("*{}", self.operand(x.x.as_ref(), UNARY_PREC)),
+
+            ExprKind::ParenExpr(x) => format!("({})", self.expr(x.x.as_ref())),
+
+            ExprKind::PostfixExpr(x) => {
+                format!(
+                    "{}{}",
+                    self.operand(x.x.as_ref(), HIGHEST_PREC),
+                    x.op.to_str()
+                )
+            }
+
+            // assignments are right associative
+            ExprKind::AssignExpr(x) => format!(
+                "{} {} {}",
+                self.operand(x.x.as_ref(), UNARY_PREC),
+                x.op.to_str(),
+                self.operand(x.y.as_ref(), ASSIGN_PREC)
+            ),
+
+            ExprKind::CondExpr(x) => {
+                let prec = Token::TERNERY.precedence();
+
+                format!(
+                    "{} ? {} : {}",
+                    self.operand(x.cond.as_ref(), prec + 1),
+                    self.expr(x.x.as_ref()),
+                    self.operand(x.y.as_ref(), prec)
+                )
+            }
+
+            ExprKind::CallExpr(x) => {
+                let args: Vec<_> = x
+                    .args
+                    .iter()
+                    .map(|a| self.operand(a.as_ref(), ASSIGN_PREC))
+                    .collect();
+
+                format!(
+                    "{}({})",
+                    self.operand(x.fun.as_ref(), HIGHEST_PREC),
+                    args.join(", ")
+                )
+            }
+
+            ExprKind::IndexExpr(x) => format!(
+                "{}[{}]",
+                self.operand(x.x.as_ref(), HIGHEST_PREC),
+                self.expr(x.index.as_ref())
+            ),
+
+            ExprKind::SelectorExpr(x) => format!(
+                "{}{}{}",
+                self.operand(x.x.as_ref(), HIGHEST_PREC),
+                x.op.to_str(),
+                x.sel.name
+            ),
+
+            ExprKind::CastExpr(x) => format!(
+                "({}) {}",
+                self.type_name(&x.typ),
+                self.operand(x.x.as_ref(), UNARY_PREC)
+            ),
+
+            ExprKind::SizeofExpr(x) => match &x.arg {
+                SizeofArg::Expr(e) => {
+                    let operand = self.operand(e.as_ref(), UNARY_PREC);
+
+                    if operand.starts_with('(') {
+                        format!("{}{}", x.op.to_str(), operand)
+                    } else {
+                        format!("{} {}", x.op.to_str(), operand)
+                    }
+                }
+                SizeofArg::Type(typ) => format!("{}({})", x.op.to_str(), self.type_name(typ)),
+            },
+
+            ExprKind::OffsetofExpr(x) => {
+                let path: String = x.path.iter().map(|d| d.format(&|x| self.expr(x))).collect();
+
+                format!(
+                    "{}({}, {}{})",
+                    x.name.name,
+                    self.type_name(&x.typ),
+                    x.member.name,
+                    path
+                )
+            }
+
+            ExprKind::CompositeLit(x) => format!(
+                "({}){}",
+                self.type_name(&x.typ),
+                x.init.format(&|x| self.operand(x, ASSIGN_PREC))
+            ),
+
+            ExprKind::InitList(x) => x.format(&|x| self.operand(x, ASSIGN_PREC)),
+        }
+    }
+
+    fn type_name(&self, typ: &TypeName) -> String {
+        let expr = |x: &dyn Expr| self.operand(x, ASSIGN_PREC);
+
+        join_decl(typ.specs.format(&expr), &typ.declarator.format("", &expr))
+    }
+
+    // formats an operand, with parentheses if it binds looser than `prec`
+    fn operand(&self, x: &dyn Expr, prec: usize) -> String {
+        if precedence(x) < prec {
+            format!("({})", self.expr(x))
+        } else {
+            self.expr(x)
+        }
+    }
+}
+
+// a binary operator with the spaces around it
+fn spaced(op: Token) -> String {
+    match op {
+        Token::COMMA => ", ".to_string(),
+        _ => format!(" {} ", op.to_str()),
+    }
+}
+
+fn precedence(x: &dyn Expr) -> usize {
+    match x.kind() {
+        ExprKind::InfixExpr(x) => x.op.precedence(),
+        ExprKind::AssignExpr(_) => ASSIGN_PREC,
+        ExprKind::CondExpr(_) => Token::TERNERY.precedence(),
+        ExprKind::UnaryExpr(_)
+        | ExprKind::StarExpr(_)
+        | ExprKind::CastExpr(_)
+        | ExprKind::SizeofExpr(_) => UNARY_PREC,
+        _ => HIGHEST_PREC,
+    }
+}
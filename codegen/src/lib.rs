@@ -0,0 +1,324 @@
+//! Native code for Linux on x86-64. A checked file is lowered to the IR,
+//! optimized, and each function is emitted as assembly for the System V
+//! ABI in the syntax of the GNU assembler. The code is simple: every value
+//! has a slot in the frame of its function and each instruction loads its
+//! operands in registers and stores its result. `build` assembles and
+//! links the result with the C library by the system's `cc`.
+
+use std::path::Path;
+use std::process::Command;
+
+use ast::File;
+use ir::{Error, Module, Operand};
+use sema::{Info, SymbolKind};
+
+mod x86;
+
+/// Compiles a file checked without errors to assembly, after the passes
+/// of `ir::optimize` if `optimize` is set.
+pub fn compile(file: &File, info: &Info, optimize: bool) -> Result<String, Error> {
+    let mut m = ir::lower(file, info)?;
+    check_calls(&m, info)?;
+    if optimize {
+        ir::optimize(&mut m);
+    }
+    Ok(x86::emit(&m))
+}
+
+// records are passed and returned through pointers to copies, which the
+// functions of the C library do not expect
+fn check_calls(m: &Module, info: &Info) -> Result<(), Error> {
+    for sym in info.symbols.iter() {
+        let Some(f) = sym.ty.func() else {
+            continue;
+        };
+        if sym.kind != SymbolKind::Func || sym.defined || m.func(&sym.name).is_some() {
+            continue;
+        }
+        if !f.ret.is_record() && !f.params.iter().any(|p| p.is_record()) {
+            continue;
+        }
+        if uses(m, &sym.name) {
+            return Err(Error {
+                pos: sym.pos,
+                msg: format!(
+                    "'{}' takes or returns a struct by value, which native code does not support for functions it does not define",
+                    sym.name
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn uses(m: &Module, name: &str) -> bool {
+    let global = |x: &Operand| matches!(x, Operand::Global(n, _) if n == name);
+    m.funcs.iter().any(|f| {
+        f.blocks.iter().any(|b| {
+            b.insts
+                .iter()
+                .any(|&v| f.inst(v).kind.operands().into_iter().any(global))
+                || b.term.operand().is_some_and(global)
+        })
+    }) || m
+        .globals
+        .iter()
+        .any(|g| g.relocs.iter().any(|r| r.symbol == name))
+}
+
+/// Assembles and links a program with `cc`, returns its diagnostics if it
+/// fails.
+pub fn build(asm: &str, output: &Path) -> Result<(), String> {
+    let src = std::env::temp_dir().join(format!("crepl-{}.s", std::process::id()));
+    std::fs::write(&src, asm).map_err(|err| format!("{}: {}", src.display(), err))?;
+
+    let status = Command::new("cc")
+        .arg("-no-pie")
+        .arg("-o")
+        .arg(output)
+        .arg(&src)
+        .arg("-lm")
+        .output();
+    let _ = std::fs::remove_file(&src);
+
+    match status {
+        Ok(out) if out.status.success() => Ok(()),
+        Ok(out) => Err(String::from_utf8_lossy(&out.stderr).trim_end().to_string()),
+        Err(err) => Err(format!("cannot run cc: {}", err)),
+    }
+}
+
+#[cfg(all(test, target_arch = "x86_64", target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static PROGRAMS: AtomicUsize = AtomicUsize::new(0);
+
+    // runs a program interpreted and compiled, with and without the
+    // passes, and returns its exit status and output if they all agree
+    fn run(src: &str) -> (i32, String) {
+        let dl = sema::layout::DataLayout::default();
+        let src = interp::include::expand(src.as_bytes(), &dl).unwrap();
+        let mut p = parser::Parser::from(String::from_utf8(src).unwrap());
+        let file = p.parse_file();
+        assert!(p.errors().is_empty(), "{:?}", p.errors());
+        let info = sema::check(&file);
+        assert_eq!(0, info.errors().count(), "{:?}", info.diagnostics);
+
+        let mut out = Vec::new();
+        let mut it = interp::Interpreter::new(&file, &info).with_output(&mut out);
+        let status = it.run_main(&["prog".to_string()]).unwrap();
+        drop(it);
+        let want = (status, String::from_utf8(out).unwrap());
+
+        for optimize in [false, true] {
+            let asm = compile(&file, &info, optimize).unwrap();
+            let n = PROGRAMS.fetch_add(1, Ordering::Relaxed);
+            let exe = std::env::temp_dir().join(format!("crepl-test-{}-{}", std::process::id(), n));
+            build(&asm, &exe).unwrap_or_else(|err| panic!("{}\n{}", err, asm));
+            let out = Command::new(&exe).output().unwrap();
+            let _ = std::fs::remove_file(&exe);
+
+            // a program killed by a signal has the status a shell gives it,
+            // 134 for SIGABRT like the interpreter's abort
+            let status = out.status.code().or(out.status.signal().map(|s| 128 + s));
+            let got = (status.unwrap_or(-1), String::from_utf8(out.stdout).unwrap());
+            assert_eq!(got, want, "optimize: {}\n{}", optimize, asm);
+        }
+        want
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let tests = [
+            ("int main(void) { return 6 * 7; }", 42),
+            ("int main(void) { int a = -7, b = 2; return (a / b) * 10 + (a % b) + 50; }", 19),
+            ("int main(void) { unsigned a = 4000000000u; return (a / 3u) % 101u + (a >> 28); }", 27),
+            ("int main(void) { long x = 1L << 40; return (int)(x >> 38) + (int)(-x >> 60); }", 3),
+            ("int main(void) { signed char c = 127; c++; unsigned char u = 255; u += 2; return c + 200 + u; }", 73),
+            ("int main(void) { short s = -3; unsigned short u = s; return (u > 60000) + (s < 0) * 2; }", 3),
+            ("int main(void) { double d = 7.5; float f = 2.25f; return (int)(d * f) + (d > f) + (f == 2.25); }", 18),
+            ("int main(void) { double d = -3.9; unsigned long u = 1e19; return (int)d + 10 + (u > 9000000000000000000ul); }", 8),
+            ("int main(void) { unsigned long u = 18000000000000000000ul; double d = u; return d > 1.7e19; }", 1),
+            ("int main(void) { int x = 5; return !x + ~x + -x + 20 + !!x; }", 10),
+        ];
+        for (i, (src, want)) in tests.iter().enumerate() {
+            let (status, _) = run(src);
+            assert_eq!(
+                status,
+                *want,
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn test_programs() {
+        let (status, out) = run(r#"
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+struct point { int x, y; char name[8]; };
+
+static struct point make(int x, int y) {
+    struct point p = { x, y, "p" };
+    return p;
+}
+
+static int cmp(const void *a, const void *b) {
+    return *(const int *)a - *(const int *)b;
+}
+
+static double mean(int n, const double *xs) {
+    double s = 0;
+    for (int i = 0; i < n; i++)
+        s += xs[i];
+    return n ? s / n : 0;
+}
+
+int many(int a, int b, int c, int d, int e, int f, int g, int h, double x, double y) {
+    return a + b + c + d + e + f + g * 10 + h * 100 + (int)(x * y);
+}
+
+int fib(int n) { return n < 2 ? n : fib(n - 1) + fib(n - 2); }
+
+const char *names[] = { "zero", "one", "two" };
+int counts[4] = { 1, 2 };
+
+int main(void) {
+    struct point p = make(3, 4);
+    printf("%s %d %d\n", p.name, p.x, p.y);
+
+    int xs[] = { 5, 3, 9, 1, 7 };
+    qsort(xs, 5, sizeof xs[0], cmp);
+    for (int i = 0; i < 5; i++)
+        printf("%d ", xs[i]);
+    printf("\n");
+
+    double ds[] = { 1.5, 2.5, 4.0 };
+    printf("%.3f %s\n", mean(3, ds), names[2]);
+    printf("%d %d\n", many(1, 2, 3, 4, 5, 6, 7, 8, 1.5, 3.0), fib(15));
+
+    char *s = malloc(32);
+    strcpy(s, "hello");
+    strcat(s, ", world");
+    printf("%s %zu\n", s, strlen(s));
+    free(s);
+
+    int total = 0;
+    for (int i = 0; i < 4; i++) {
+        switch (counts[i]) {
+        case 1: total += 10; break;
+        case 2: total += 20;
+        default: total += 1;
+        }
+    }
+    return total;
+}
+"#);
+        assert_eq!(status, 33);
+        assert_eq!(
+            out,
+            "p 3 4\n1 3 5 7 9 \n2.667 two\n895 610\nhello, world 12\n"
+        );
+
+        // a failed assert aborts after the output is flushed
+        let src = "#include <assert.h>\n#include <stdio.h>\n\
+                   int main(void) { int x = 2; printf(\"x\"); assert(x == 2); assert(x > 2); return 0; }";
+        assert_eq!((134, "x".to_string()), run(src));
+    }
+
+    #[test]
+    fn test_memory() {
+        let (status, out) = run(r#"
+#include <stdio.h>
+
+struct flags { unsigned a : 3; int b : 5; unsigned c : 1; };
+union word { unsigned u; unsigned char bytes[4]; float f; };
+struct pair { long a; double b; };
+
+static int counter(void) { static int n; return ++n; }
+
+double sum10(double a, double b, double c, double d, double e,
+             double f, double g, double h, double i, float j) {
+    return a + b + c + d + e + f + g + h + i + j;
+}
+
+int apply(int (*f)(int), int x) { return f(x); }
+int twice(int x) { return 2 * x; }
+
+int grid[3][4];
+char *hello = "hello";
+struct pair pairs[2] = { { 1, 2.5 }, [1].b = 4.5 };
+
+int main(void) {
+    struct flags fl = { 5, -3, 1 };
+    fl.b += 20;
+    printf("%u %d %u\n", fl.a, fl.b, fl.c);
+
+    union word w;
+    w.u = 0x01020304;
+    printf("%d %d\n", w.bytes[0], w.bytes[3]);
+    w.f = 1.0f;
+    printf("%x\n", w.u);
+
+    for (int i = 0; i < 3; i++)
+        for (int j = 0; j < 4; j++)
+            grid[i][j] = i * j;
+    int s = 0;
+    for (int *p = &grid[0][0]; p < &grid[3][0]; p++)
+        s += *p;
+
+    struct pair q = pairs[0];
+    pairs[0] = pairs[1];
+    pairs[1] = q;
+
+    counter();
+    counter();
+    printf("%d %d %c %ld %.1f %d\n", s, counter(), hello[1], pairs[1].a, pairs[0].b,
+           apply(twice, 21));
+    printf("%.2f\n", sum10(1, 2, 3, 4, 5, 6, 7, 8, 9, 0.5f));
+
+    int x = 0, n = 0;
+    while (x < 100) {
+        if (x % 7 == 3 || (x > 50 && x % 11 == 0))
+            n++;
+        x += 3;
+    }
+    unsigned long big = 0;
+    for (int i = 0; i < 70; i++)
+        big = big * 3 + (i ^ (i >> 2));
+    printf("%d %lu\n", n, big % 1000003);
+    return n;
+}
+"#);
+        assert_eq!(status, 6);
+        assert_eq!(
+            out,
+            "5 -15 1\n4 1\n3f800000\n18 3 e 1 4.5 42\n45.50\n6 320053\n"
+        );
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let mut p = parser::Parser::from(
+            "typedef struct { int quot, rem; } div_t; div_t div(int, int);
+             int main(void) { return div(7, 2).rem; }"
+                .to_string(),
+        );
+        let file = p.parse_file();
+        let info = sema::check(&file);
+        let err = compile(&file, &info, true).unwrap_err();
+        assert!(
+            err.msg.contains("'div' takes or returns a struct by value"),
+            "{}",
+            err.msg
+        );
+    }
+}
@@ -0,0 +1,745 @@
+//! The assembly of a module, in AT&T syntax. The value of an instruction
+//! is kept in the low bytes of an 8-byte slot of the frame, the others are
+//! undefined, so each instruction that depends on them extends its
+//! operands first. A phi has a second slot its predecessors store their
+//! value in before they jump, copied to its own when its block starts, so
+//! the phis of a block take their values at once.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use ir::{
+    BinOp, BlockId, CastOp, CmpOp, Function, Global, InstKind, Module, Operand, Term, Ty, UnOp,
+    ValueId,
+};
+
+// the registers by size: 1, 2, 4 and 8 bytes
+type Reg = [&'static str; 4];
+
+const RAX: Reg = ["%al", "%ax", "%eax", "%rax"];
+const RCX: Reg = ["%cl", "%cx", "%ecx", "%rcx"];
+const RDX: Reg = ["%dl", "%dx", "%edx", "%rdx"];
+const RSI: Reg = ["%sil", "%si", "%esi", "%rsi"];
+const RDI: Reg = ["%dil", "%di", "%edi", "%rdi"];
+const R8: Reg = ["%r8b", "%r8w", "%r8d", "%r8"];
+const R9: Reg = ["%r9b", "%r9w", "%r9d", "%r9"];
+const R10: Reg = ["%r10b", "%r10w", "%r10d", "%r10"];
+
+// the registers of the integer and floating arguments
+const INT_ARGS: [Reg; 6] = [RDI, RSI, RDX, RCX, R8, R9];
+const FLOAT_ARGS: usize = 8;
+
+/// `assert` is a function of the bundled headers, the C library only has
+/// the macro. Its calls also pass the text of the argument, which is
+/// printed after the output is flushed, before the program aborts.
+const ASSERT: &str = "\
+\t.section .rodata
+.Lassert.msg:
+\t.string \"Assertion `%s' failed.\\n\"
+\t.text
+assert:
+\ttestl %edi, %edi
+\tjne 1f
+\tpushq %rsi
+\txorl %edi, %edi
+\tcall fflush
+\tmovq stderr(%rip), %rdi
+\tleaq .Lassert.msg(%rip), %rsi
+\tmovq (%rsp), %rdx
+\txorl %eax, %eax
+\tcall fprintf
+\tcall abort
+1:
+\tret
+";
+
+pub fn emit(m: &Module) -> String {
+    let mut out = String::new();
+    for g in m.globals.iter() {
+        global(&mut out, g);
+    }
+    for (i, f) in m.funcs.iter().enumerate() {
+        Emitter::new(f, i, &mut out).func();
+    }
+    if m.func("assert").is_none() && uses_assert(m) {
+        out.push_str(ASSERT);
+    }
+    out.push_str("\t.section .note.GNU-stack,\"\",@progbits\n");
+    out
+}
+
+fn uses_assert(m: &Module) -> bool {
+    m.funcs.iter().any(|f| {
+        f.insts
+            .iter()
+            .any(|i| matches!(&i.kind, InstKind::Call(Operand::Global(n, _), _) if n == "assert"))
+    })
+}
+
+// the name of a global for the assembler, those the IR makes up start
+// with a dot and become local labels
+fn symbol(name: &str) -> String {
+    match name.starts_with('.') {
+        true => format!(".L{}", name),
+        false => name.to_string(),
+    }
+}
+
+fn global(out: &mut String, g: &Global) {
+    let section = match (g.readonly, g.data.is_empty() && g.relocs.is_empty()) {
+        (true, _) => "\t.section .rodata",
+        (false, true) => "\t.bss",
+        (false, false) => "\t.data",
+    };
+    let name = symbol(&g.name);
+    writeln!(out, "{}", section).unwrap();
+    if !g.internal {
+        writeln!(out, "\t.globl {}", name).unwrap();
+    }
+    writeln!(out, "\t.p2align {}", g.align.max(1).trailing_zeros()).unwrap();
+    writeln!(out, "{}:", name).unwrap();
+
+    if g.data.is_empty() && g.relocs.is_empty() {
+        writeln!(out, "\t.zero {}", g.size.max(1)).unwrap();
+        return;
+    }
+
+    // the bytes, with the addresses of the relocations in between
+    let mut data = g.data.clone();
+    data.resize(g.size as usize, 0);
+    let mut relocs: Vec<_> = g.relocs.iter().collect();
+    relocs.sort_by_key(|r| r.offset);
+    let mut at = 0;
+    let bytes = |out: &mut String, to: usize, at: usize| {
+        for chunk in data[at..to].chunks(16) {
+            let list: Vec<String> = chunk.iter().map(|b| b.to_string()).collect();
+            writeln!(out, "\t.byte {}", list.join(", ")).unwrap();
+        }
+    };
+    for r in relocs {
+        bytes(out, r.offset as usize, at);
+        match r.addend {
+            0 => writeln!(out, "\t.quad {}", symbol(&r.symbol)).unwrap(),
+            n => writeln!(out, "\t.quad {}{:+}", symbol(&r.symbol), n).unwrap(),
+        }
+        at = r.offset as usize + 8;
+    }
+    bytes(out, data.len(), at);
+}
+
+// the suffix of an instruction on values of a size
+fn suffix(ty: Ty) -> &'static str {
+    match ty.size() {
+        1 => "b",
+        2 => "w",
+        4 => "l",
+        _ => "q",
+    }
+}
+
+fn reg(r: Reg, ty: Ty) -> &'static str {
+    match ty.size() {
+        1 => r[0],
+        2 => r[1],
+        4 => r[2],
+        _ => r[3],
+    }
+}
+
+// the type arithmetic is done in, 32 bits at least
+fn wide(ty: Ty) -> Ty {
+    match ty {
+        Ty::I8 | Ty::I16 => Ty::I32,
+        ty => ty,
+    }
+}
+
+struct Emitter<'f, 'o> {
+    func: &'f Function,
+    index: usize, // of the function, for its labels
+    out: &'o mut String,
+    slots: HashMap<ValueId, i64>,   // offsets from %rbp
+    phis: HashMap<ValueId, i64>,    // where the predecessors store the value of a phi
+    allocas: HashMap<ValueId, i64>, // the memory of an alloca
+    frame: i64,
+    labels: usize,
+}
+
+impl<'f, 'o> Emitter<'f, 'o> {
+    fn new(func: &'f Function, index: usize, out: &'o mut String) -> Self {
+        let mut e = Emitter {
+            func,
+            index,
+            out,
+            slots: HashMap::new(),
+            phis: HashMap::new(),
+            allocas: HashMap::new(),
+            frame: 0,
+            labels: 0,
+        };
+
+        let mut size = 0i64;
+        for &v in func.blocks.iter().flat_map(|b| b.insts.iter()) {
+            let inst = func.inst(v);
+            if inst.ty != Ty::Void {
+                size = (size + 15) / 8 * 8;
+                e.slots.insert(v, -size);
+            }
+            match inst.kind {
+                InstKind::Phi(_) => {
+                    size += 8;
+                    e.phis.insert(v, -size);
+                }
+                // %rbp is aligned to 16 bytes, more is aligned at run time
+                InstKind::Alloca { size: n, align } => {
+                    let a = align.clamp(1, 16) as i64;
+                    let n = n as i64 + if align > 16 { align as i64 } else { 0 };
+                    size = (size + n.max(1) + a - 1) / a * a;
+                    e.allocas.insert(v, -size);
+                }
+                _ => {}
+            }
+        }
+        e.frame = (size + 15) / 16 * 16;
+        e
+    }
+
+    fn line(&mut self, s: impl AsRef<str>) {
+        self.out.push('\t');
+        self.out.push_str(s.as_ref());
+        self.out.push('\n');
+    }
+
+    fn block_label(&self, b: BlockId) -> String {
+        format!(".L{}.{}", self.index, b.0)
+    }
+
+    fn new_label(&mut self) -> String {
+        self.labels += 1;
+        format!(".L{}.x{}", self.index, self.labels)
+    }
+
+    fn func(&mut self) {
+        let f = self.func;
+        let name = symbol(&f.name);
+        self.line(".text");
+        if !f.internal {
+            self.line(format!(".globl {}", name));
+        }
+        self.line(format!(".type {}, @function", name));
+        writeln!(self.out, "{}:", name).unwrap();
+        self.line("pushq %rbp");
+        self.line("movq %rsp, %rbp");
+        if self.frame > 0 {
+            self.line(format!("subq ${}, %rsp", self.frame));
+        }
+
+        // the parameters in registers, then those on the stack
+        let mut params = HashMap::new();
+        let (mut ints, mut floats, mut stack) = (0, 0, 0);
+        for (i, &ty) in f.params.iter().enumerate() {
+            let from = if ty.is_float() && floats < FLOAT_ARGS {
+                floats += 1;
+                format!("%xmm{}", floats - 1)
+            } else if !ty.is_float() && ints < INT_ARGS.len() {
+                ints += 1;
+                INT_ARGS[ints - 1][3].to_string()
+            } else {
+                stack += 1;
+                format!("{}(%rbp)", 8 + 8 * stack)
+            };
+            params.insert(i, from);
+        }
+        for &v in f.blocks[0].insts.iter() {
+            let InstKind::Param(i) = f.inst(v).kind else {
+                continue;
+            };
+            let slot = self.slots[&v];
+            match &params[&i] {
+                r if r.ends_with("(%rbp)") => {
+                    self.line(format!("movq {}, %rax", r));
+                    self.line(format!("movq %rax, {}(%rbp)", slot));
+                }
+                r => self.line(format!("movq {}, {}(%rbp)", r, slot)),
+            }
+        }
+
+        for (b, block) in f.blocks.iter().enumerate() {
+            writeln!(self.out, "{}:", self.block_label(BlockId(b))).unwrap();
+            for &v in block.insts.iter() {
+                self.inst(v);
+            }
+            self.term(BlockId(b), &block.term);
+        }
+        self.line(format!(".size {}, .-{}", name, name));
+    }
+
+    // loads an operand in a register, a float as its bits
+    fn load(&mut self, x: &Operand, r: Reg) {
+        let r = r[3];
+        match x {
+            Operand::Value(v) => {
+                let slot = self.slots[v];
+                self.line(format!("movq {}(%rbp), {}", slot, r))
+            }
+            Operand::Int(n, _) => self.imm(*n, r),
+            Operand::Float(f, Ty::F32) => self.imm((*f as f32).to_bits() as i64, r),
+            Operand::Float(f, _) => self.imm(f.to_bits() as i64, r),
+            Operand::Global(name, 0) => self.line(format!("leaq {}(%rip), {}", symbol(name), r)),
+            Operand::Global(name, n) => {
+                self.line(format!("leaq {}{:+}(%rip), {}", symbol(name), n, r))
+            }
+            Operand::Undef(_) => self.imm(0, r),
+        }
+    }
+
+    fn imm(&mut self, n: i64, r: &str) {
+        match i32::try_from(n) {
+            Ok(n) => self.line(format!("movq ${}, {}", n, r)),
+            Err(_) => self.line(format!("movabsq ${}, {}", n, r)),
+        }
+    }
+
+    fn load_float(&mut self, x: &Operand, xmm: &str) {
+        match x {
+            Operand::Value(v) => {
+                let slot = self.slots[v];
+                self.line(format!("movq {}(%rbp), {}", slot, xmm));
+            }
+            x => {
+                self.load(x, RAX);
+                self.line(format!("movq %rax, {}", xmm));
+            }
+        }
+    }
+
+    fn store(&mut self, v: ValueId, r: Reg) {
+        let slot = self.slots[&v];
+        self.line(format!("movq {}, {}(%rbp)", r[3], slot));
+    }
+
+    fn store_float(&mut self, v: ValueId, xmm: &str) {
+        let slot = self.slots[&v];
+        self.line(format!("movq {}, {}(%rbp)", xmm, slot));
+    }
+
+    // extends an integer in %rax to 64 bits
+    fn extend(&mut self, r: Reg, ty: Ty, signed: bool) {
+        let line = match (ty.size(), signed) {
+            (1, true) => format!("movsbq {}, {}", r[0], r[3]),
+            (2, true) => format!("movswq {}, {}", r[1], r[3]),
+            (4, true) => format!("movslq {}, {}", r[2], r[3]),
+            (1, false) => format!("movzbl {}, {}", r[0], r[2]),
+            (2, false) => format!("movzwl {}, {}", r[1], r[2]),
+            (4, false) => format!("movl {}, {}", r[2], r[2]),
+            _ => return,
+        };
+        self.line(line);
+    }
+
+    fn inst(&mut self, v: ValueId) {
+        let f = self.func;
+        let inst = f.inst(v);
+        let ty = inst.ty;
+        match &inst.kind {
+            InstKind::Param(_) => {}
+
+            InstKind::Phi(_) => {
+                let from = self.phis[&v];
+                self.line(format!("movq {}(%rbp), %rax", from));
+                self.store(v, RAX);
+            }
+
+            InstKind::Alloca { align, .. } => {
+                let at = self.allocas[&v];
+                self.line(format!("leaq {}(%rbp), %rax", at));
+                if *align > 16 {
+                    self.line(format!("addq ${}, %rax", align - 1));
+                    self.line(format!("andq ${}, %rax", -(*align as i64)));
+                }
+                self.store(v, RAX);
+            }
+
+            InstKind::Load(p) => {
+                self.load(p, RCX);
+                let t = if ty.is_float() {
+                    Ty::int(ty.size())
+                } else {
+                    ty
+                };
+                self.line(format!("mov{} (%rcx), {}", suffix(t), reg(RAX, t)));
+                self.store(v, RAX);
+            }
+
+            InstKind::Store(p, x) => {
+                let t = f.type_of(x);
+                let t = if t.is_float() { Ty::int(t.size()) } else { t };
+                self.load(p, RCX);
+                self.load(x, RAX);
+                self.line(format!("mov{} {}, (%rcx)", suffix(t), reg(RAX, t)));
+            }
+
+            InstKind::Binary(op, a, b) if ty.is_float() => {
+                self.load_float(a, "%xmm0");
+                self.load_float(b, "%xmm1");
+                let p = if ty == Ty::F32 { "ss" } else { "sd" };
+                let name = match op {
+                    BinOp::FAdd => "add",
+                    BinOp::FSub => "sub",
+                    BinOp::FMul => "mul",
+                    _ => "div",
+                };
+                self.line(format!("{}{} %xmm1, %xmm0", name, p));
+                self.store_float(v, "%xmm0");
+            }
+
+            InstKind::Binary(op, a, b) => {
+                self.load(a, RAX);
+                self.load(b, RCX);
+                self.binary(*op, ty);
+                let r = if matches!(op, BinOp::SRem | BinOp::URem) {
+                    RDX
+                } else {
+                    RAX
+                };
+                self.store(v, r);
+            }
+
+            InstKind::Unary(op, x) => {
+                self.load(x, RAX);
+                match op {
+                    UnOp::Neg => {
+                        self.line(format!("neg{} {}", suffix(wide(ty)), reg(RAX, wide(ty))))
+                    }
+                    UnOp::Not => {
+                        self.line(format!("not{} {}", suffix(wide(ty)), reg(RAX, wide(ty))))
+                    }
+                    UnOp::FNeg if ty == Ty::F32 => self.line("btcl $31, %eax"),
+                    UnOp::FNeg => self.line("btcq $63, %rax"),
+                }
+                self.store(v, RAX);
+            }
+
+            InstKind::Cmp(op, a, b) => {
+                let t = f.type_of(a);
+                if t.is_float() {
+                    self.load_float(a, "%xmm0");
+                    self.load_float(b, "%xmm1");
+                    self.fcmp(*op, t);
+                } else {
+                    self.load(a, RAX);
+                    self.load(b, RCX);
+                    self.line(format!("cmp{} {}, {}", suffix(t), reg(RCX, t), reg(RAX, t)));
+                    let cc = match op {
+                        CmpOp::Eq => "e",
+                        CmpOp::Ne => "ne",
+                        CmpOp::SLt => "l",
+                        CmpOp::SLe => "le",
+                        CmpOp::SGt => "g",
+                        CmpOp::SGe => "ge",
+                        CmpOp::ULt => "b",
+                        CmpOp::ULe => "be",
+                        CmpOp::UGt => "a",
+                        _ => "ae",
+                    };
+                    self.line(format!("set{} %al", cc));
+                }
+                self.line("movzbl %al, %eax");
+                self.store(v, RAX);
+            }
+
+            InstKind::Cast(op, x) => {
+                let from = f.type_of(x);
+                self.cast(*op, x, from, ty);
+                match ty.is_float() {
+                    true => self.store_float(v, "%xmm0"),
+                    false => self.store(v, RAX),
+                }
+            }
+
+            InstKind::PtrAdd(p, n) => {
+                self.load(p, RAX);
+                self.load(n, RCX);
+                self.line("addq %rcx, %rax");
+                self.store(v, RAX);
+            }
+
+            InstKind::Copy(d, s, n) => {
+                self.load(d, RDI);
+                self.load(s, RSI);
+                self.line(format!("movq ${}, %rcx", n));
+                self.line("rep movsb");
+            }
+
+            InstKind::Zero(p, n) => {
+                self.load(p, RDI);
+                self.line(format!("movq ${}, %rcx", n));
+                self.line("xorl %eax, %eax");
+                self.line("rep stosb");
+            }
+
+            InstKind::Call(callee, args) => self.call(v, callee, args),
+        }
+    }
+
+    fn binary(&mut self, op: BinOp, ty: Ty) {
+        let w = wide(ty);
+        let (s, a, c) = (suffix(w), reg(RAX, w), reg(RCX, w));
+        match op {
+            BinOp::Add => self.line(format!("add{} {}, {}", s, c, a)),
+            BinOp::Sub => self.line(format!("sub{} {}, {}", s, c, a)),
+            BinOp::Mul => self.line(format!("imul{} {}, {}", s, c, a)),
+            BinOp::And => self.line(format!("and{} {}, {}", s, c, a)),
+            BinOp::Or => self.line(format!("or{} {}, {}", s, c, a)),
+            BinOp::Xor => self.line(format!("xor{} {}, {}", s, c, a)),
+
+            // the count is in %cl, the bits above the type are not shifted
+            // in
+            BinOp::Shl => self.line(format!("shl{} %cl, {}", suffix(ty), reg(RAX, ty))),
+            BinOp::LShr => self.line(format!("shr{} %cl, {}", suffix(ty), reg(RAX, ty))),
+            BinOp::AShr => self.line(format!("sar{} %cl, {}", suffix(ty), reg(RAX, ty))),
+
+            BinOp::SDiv | BinOp::SRem => {
+                self.extend(RAX, ty, true);
+                self.extend(RCX, ty, true);
+                match w {
+                    Ty::I64 | Ty::Ptr => self.line("cqto"),
+                    _ => self.line("cltd"),
+                }
+                self.line(format!("idiv{} {}", s, c));
+            }
+            BinOp::UDiv | BinOp::URem => {
+                self.extend(RAX, ty, false);
+                self.extend(RCX, ty, false);
+                self.line("xorl %edx, %edx");
+                self.line(format!("div{} {}", s, c));
+            }
+            BinOp::FAdd | BinOp::FSub | BinOp::FMul | BinOp::FDiv => {
+                unreachable!("float operator on integers")
+            }
+        }
+    }
+
+    // compares %xmm0 to %xmm1, a comparison with NaN is false but for !=
+    fn fcmp(&mut self, op: CmpOp, ty: Ty) {
+        let ucomi = if ty == Ty::F32 { "ucomiss" } else { "ucomisd" };
+        let (swap, cc) = match op {
+            CmpOp::FGt => (false, "a"),
+            CmpOp::FGe => (false, "ae"),
+            CmpOp::FLt => (true, "a"),
+            CmpOp::FLe => (true, "ae"),
+            CmpOp::FEq => (false, "e"),
+            _ => (false, "ne"),
+        };
+        match swap {
+            true => self.line(format!("{} %xmm0, %xmm1", ucomi)),
+            false => self.line(format!("{} %xmm1, %xmm0", ucomi)),
+        }
+        self.line(format!("set{} %al", cc));
+        match op {
+            CmpOp::FEq => {
+                self.line("setnp %cl");
+                self.line("andb %cl, %al");
+            }
+            CmpOp::FNe => {
+                self.line("setp %cl");
+                self.line("orb %cl, %al");
+            }
+            _ => {}
+        }
+    }
+
+    // converts `x` to `to`, in %rax or %xmm0
+    fn cast(&mut self, op: CastOp, x: &Operand, from: Ty, to: Ty) {
+        let p = |t: Ty| if t == Ty::F32 { "ss" } else { "sd" };
+        match op {
+            CastOp::Trunc | CastOp::PtrToInt | CastOp::IntToPtr => self.load(x, RAX),
+            CastOp::SExt | CastOp::ZExt => {
+                self.load(x, RAX);
+                self.extend(RAX, from, op == CastOp::SExt);
+            }
+
+            CastOp::FpExt => {
+                self.load_float(x, "%xmm0");
+                self.line("cvtss2sd %xmm0, %xmm0");
+            }
+            CastOp::FpTrunc => {
+                self.load_float(x, "%xmm0");
+                self.line("cvtsd2ss %xmm0, %xmm0");
+            }
+
+            CastOp::SiToFp => {
+                self.load(x, RAX);
+                self.extend(RAX, from, true);
+                self.line(format!("cvtsi2{}q %rax, %xmm0", p(to)));
+            }
+            CastOp::UiToFp if from.size() < 8 => {
+                self.load(x, RAX);
+                self.extend(RAX, from, false);
+                self.line(format!("cvtsi2{}q %rax, %xmm0", p(to)));
+            }
+            // halves the values with the high bit set, keeping the low bit
+            // for the rounding, and doubles the result
+            CastOp::UiToFp => {
+                let (big, done) = (self.new_label(), self.new_label());
+                self.load(x, RAX);
+                self.line("testq %rax, %rax");
+                self.line(format!("js {}", big));
+                self.line(format!("cvtsi2{}q %rax, %xmm0", p(to)));
+                self.line(format!("jmp {}", done));
+                writeln!(self.out, "{}:", big).unwrap();
+                self.line("movq %rax, %rcx");
+                self.line("shrq %rcx");
+                self.line("andl $1, %eax");
+                self.line("orq %rax, %rcx");
+                self.line(format!("cvtsi2{}q %rcx, %xmm0", p(to)));
+                self.line(format!("add{} %xmm0, %xmm0", p(to)));
+                writeln!(self.out, "{}:", done).unwrap();
+            }
+
+            CastOp::FpToSi => {
+                self.load_float(x, "%xmm0");
+                self.line(format!("cvtt{}2siq %xmm0, %rax", p(from)));
+            }
+            CastOp::FpToUi if to.size() < 8 => {
+                self.load_float(x, "%xmm0");
+                self.line(format!("cvtt{}2siq %xmm0, %rax", p(from)));
+            }
+            // the values from 2^63 are converted less 2^63
+            CastOp::FpToUi => {
+                let (big, done) = (self.new_label(), self.new_label());
+                self.load_float(x, "%xmm0");
+                match from {
+                    Ty::F32 => self.line("movl $0x5f000000, %ecx"),
+                    _ => self.line("movabsq $0x43e0000000000000, %rcx"),
+                }
+                self.line("movq %rcx, %xmm1");
+                self.line(format!("ucomi{} %xmm1, %xmm0", p(from)));
+                self.line(format!("jae {}", big));
+                self.line(format!("cvtt{}2siq %xmm0, %rax", p(from)));
+                self.line(format!("jmp {}", done));
+                writeln!(self.out, "{}:", big).unwrap();
+                self.line(format!("sub{} %xmm1, %xmm0", p(from)));
+                self.line(format!("cvtt{}2siq %xmm0, %rax", p(from)));
+                self.line("btcq $63, %rax");
+                writeln!(self.out, "{}:", done).unwrap();
+            }
+        }
+    }
+
+    fn call(&mut self, v: ValueId, callee: &Operand, args: &[Operand]) {
+        let f = self.func;
+
+        // the arguments that do not fit in registers go on the stack, which
+        // stays aligned to 16 bytes
+        let (mut ints, mut floats) = (Vec::new(), Vec::new());
+        let mut stack = Vec::new();
+        for a in args.iter() {
+            let float = f.type_of(a).is_float();
+            match float {
+                true if floats.len() < FLOAT_ARGS => floats.push(a),
+                false if ints.len() < INT_ARGS.len() => ints.push(a),
+                _ => stack.push(a),
+            }
+        }
+        let size = (stack.len() as i64 + 1) / 2 * 16;
+        if size > 0 {
+            self.line(format!("subq ${}, %rsp", size));
+        }
+        for (i, a) in stack.iter().enumerate() {
+            self.load(a, RAX);
+            self.line(format!("movq %rax, {}(%rsp)", 8 * i));
+        }
+        for (i, a) in floats.iter().enumerate() {
+            self.load_float(a, &format!("%xmm{}", i));
+        }
+        if !matches!(callee, Operand::Global(_, 0)) {
+            self.load(callee, R10);
+        }
+        for (i, a) in ints.iter().enumerate() {
+            self.load(a, INT_ARGS[i]);
+        }
+
+        // the number of vector registers, for variadic functions
+        self.line(format!("movl ${}, %eax", floats.len()));
+        match callee {
+            Operand::Global(name, 0) => self.line(format!("call {}", symbol(name))),
+            _ => self.line("call *%r10"),
+        }
+        if size > 0 {
+            self.line(format!("addq ${}, %rsp", size));
+        }
+
+        match f.inst(v).ty {
+            Ty::Void => {}
+            ty if ty.is_float() => self.store_float(v, "%xmm0"),
+            _ => self.store(v, RAX),
+        }
+    }
+
+    // stores the values of the phis of a successor
+    fn phi_moves(&mut self, from: BlockId, to: BlockId) {
+        let f = self.func;
+        for &v in f.blocks[to.0].insts.iter() {
+            let InstKind::Phi(args) = &f.inst(v).kind else {
+                break;
+            };
+            if let Some((_, x)) = args.iter().find(|(b, _)| *b == from) {
+                self.load(x, RAX);
+                let slot = self.phis[&v];
+                self.line(format!("movq %rax, {}(%rbp)", slot));
+            }
+        }
+    }
+
+    fn term(&mut self, b: BlockId, term: &Term) {
+        let f = self.func;
+        let mut succs = term.succs();
+        succs.dedup();
+        for s in succs {
+            self.phi_moves(b, s);
+        }
+
+        match term {
+            Term::Jump(to) => {
+                let to = self.block_label(*to);
+                self.line(format!("jmp {}", to));
+            }
+            Term::Branch(c, t, e) => {
+                let ty = f.type_of(c);
+                self.load(c, RAX);
+                self.line(format!("test{} {1}, {1}", suffix(ty), reg(RAX, ty)));
+                let (t, e) = (self.block_label(*t), self.block_label(*e));
+                self.line(format!("jne {}", t));
+                self.line(format!("jmp {}", e));
+            }
+            Term::Switch(x, cases, default) => {
+                let ty = f.type_of(x);
+                self.load(x, RAX);
+                for (n, to) in cases.iter() {
+                    self.imm(*n, "%rcx");
+                    self.line(format!(
+                        "cmp{} {}, {}",
+                        suffix(ty),
+                        reg(RCX, ty),
+                        reg(RAX, ty)
+                    ));
+                    let to = self.block_label(*to);
+                    self.line(format!("je {}", to));
+                }
+                let to = self.block_label(*default);
+                self.line(format!("jmp {}", to));
+            }
+            Term::Return(x) => {
+                match x {
+                    Some(x) if f.type_of(x).is_float() => self.load_float(x, "%xmm0"),
+                    Some(x) => self.load(x, RAX),
+                    None => {}
+                }
+                self.line("leave");
+                self.line("ret");
+            }
+            Term::Unreachable => self.line("ud2"),
+        }
+    }
+}
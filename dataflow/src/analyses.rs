@@ -0,0 +1,145 @@
+//! The classic analyses on the tracked variables of a function.
+
+use sema::cfg::BlockId;
+
+use crate::vars::{Event, Vars};
+use crate::{Analysis, BitSet, Direction, Intersection, Union};
+
+/// The stores that may reach each point without another store to their
+/// variable in between. Facts are sets of indexes into `Vars::defs`.
+pub struct ReachingDefinitions<'v> {
+    pub vars: &'v Vars,
+}
+
+impl ReachingDefinitions<'_> {
+    pub fn apply(&self, e: &Event, fact: &mut Union) {
+        match *e {
+            Event::Def { var, def, kill } => {
+                if kill {
+                    self.kill(var, fact);
+                }
+                fact.0.insert(def);
+            }
+            Event::Undef(var) => self.kill(var, fact),
+            Event::Use(..) => {}
+        }
+    }
+
+    fn kill(&self, var: usize, fact: &mut Union) {
+        for (i, d) in self.vars.defs.iter().enumerate() {
+            if d.var == var {
+                fact.0.remove(i);
+            }
+        }
+    }
+}
+
+impl Analysis for ReachingDefinitions<'_> {
+    type Fact = Union;
+
+    const DIRECTION: Direction = Direction::Forward;
+
+    // the arguments are stored to the parameters at the entry
+    fn boundary(&self) -> Union {
+        let mut s = BitSet::empty(self.vars.defs.len());
+        for (i, d) in self.vars.defs.iter().enumerate() {
+            if d.param {
+                s.insert(i);
+            }
+        }
+        Union(s)
+    }
+
+    fn bottom(&self) -> Union {
+        Union(BitSet::empty(self.vars.defs.len()))
+    }
+
+    fn transfer(&self, b: BlockId, i: usize, fact: &mut Union) {
+        for e in self.vars.events[b.0][i].iter() {
+            self.apply(e, fact);
+        }
+    }
+}
+
+/// The variables whose value may be read before the next store to them.
+/// Facts are sets of variables.
+pub struct Liveness<'v> {
+    pub vars: &'v Vars,
+}
+
+impl Liveness<'_> {
+    /// Applies an event backwards, from the fact after it to the one
+    /// before it.
+    pub fn apply(&self, e: &Event, fact: &mut Union) {
+        match *e {
+            Event::Use(var, _) => fact.0.insert(var),
+            Event::Def {
+                var, kill: true, ..
+            }
+            | Event::Undef(var) => fact.0.remove(var),
+            Event::Def { .. } => {}
+        }
+    }
+}
+
+impl Analysis for Liveness<'_> {
+    type Fact = Union;
+
+    const DIRECTION: Direction = Direction::Backward;
+
+    fn boundary(&self) -> Union {
+        self.bottom()
+    }
+
+    fn bottom(&self) -> Union {
+        Union(BitSet::empty(self.vars.syms.len()))
+    }
+
+    fn transfer(&self, b: BlockId, i: usize, fact: &mut Union) {
+        for e in self.vars.events[b.0][i].iter().rev() {
+            self.apply(e, fact);
+        }
+    }
+}
+
+/// The variables stored to on all the paths to each point. Facts are sets
+/// of variables, the parameters are assigned at the entry.
+pub struct DefiniteAssignment<'v> {
+    pub vars: &'v Vars,
+}
+
+impl DefiniteAssignment<'_> {
+    pub fn apply(&self, e: &Event, fact: &mut Intersection) {
+        match *e {
+            Event::Def {
+                var, kill: true, ..
+            } => fact.0.insert(var),
+            Event::Undef(var) => fact.0.remove(var),
+            Event::Def { .. } | Event::Use(..) => {}
+        }
+    }
+}
+
+impl Analysis for DefiniteAssignment<'_> {
+    type Fact = Intersection;
+
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn boundary(&self) -> Intersection {
+        let mut s = BitSet::empty(self.vars.syms.len());
+        for d in self.vars.defs.iter().filter(|d| d.param) {
+            s.insert(d.var);
+        }
+        Intersection(s)
+    }
+
+    fn bottom(&self) -> Intersection {
+        Intersection(BitSet::full(self.vars.syms.len()))
+    }
+
+    fn transfer(&self, b: BlockId, i: usize, fact: &mut Intersection) {
+        for e in self.vars.events[b.0][i].iter() {
+            self.apply(e, fact);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::{HashMap, HashSet};
+
+use ast::FuncDecl;
+use sema::cfg::Cfg;
+use sema::Info;
+
+use crate::analyses::{DefiniteAssignment, Liveness, ReachingDefinitions};
+use crate::solve;
+use crate::vars::{Event, Vars};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportKind {
+    Uninitialized,      // read where no store reaches
+    MaybeUninitialized, // read where a store reaches on some paths only
+    DeadStore,          // stored to and never read after
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub pos: usize,
+    pub kind: ReportKind,
+    pub msg: String,
+}
+
+/// Checks the local variables of a function definition resolved into
+/// `info`: the first read of each before it is assigned, and the stores
+/// that are never read. The reports are in source order.
+pub fn check(f: &FuncDecl, info: &Info) -> Vec<Report> {
+    let (cfg, _) = Cfg::build(&f.body, info);
+    let vars = Vars::new(f, &cfg, info);
+
+    let rd = ReachingDefinitions { vars: &vars };
+    let live = Liveness { vars: &vars };
+    let da = DefiniteAssignment { vars: &vars };
+
+    let reaching = solve(&cfg, &rd);
+    let liveness = solve(&cfg, &live);
+    let assigned = solve(&cfg, &da);
+
+    let reachable = cfg.reachable(cfg.entry);
+    let mut reports = Vec::new();
+
+    // the first read of each variable that is not definitely assigned
+    let mut reads: HashMap<usize, (usize, bool)> = HashMap::new();
+
+    for b in (0..cfg.blocks.len()).filter(|&b| reachable[b]) {
+        let mut defs = reaching.before[b].clone();
+        let mut set = assigned.before[b].clone();
+
+        for events in vars.events[b].iter() {
+            for e in events.iter() {
+                if let Event::Use(var, pos) = *e {
+                    if !set.0.contains(var) {
+                        let some = defs.0.iter().any(|d| vars.defs[d].var == var);
+                        let first = reads.entry(var).or_insert((pos, some));
+                        if pos < first.0 {
+                            *first = (pos, some);
+                        }
+                    }
+                }
+
+                rd.apply(e, &mut defs);
+                da.apply(e, &mut set);
+            }
+        }
+    }
+
+    for (var, (pos, some)) in reads {
+        let (kind, how) = if some {
+            (ReportKind::MaybeUninitialized, "may be")
+        } else {
+            (ReportKind::Uninitialized, "is")
+        };
+        let msg = format!(
+            "variable '{}' {} uninitialized when used here",
+            vars.name(var, info),
+            how
+        );
+        reports.push(Report { pos, kind, msg });
+    }
+
+    // the variables never read at all are left to the unused variable
+    // warnings
+    let used: HashSet<_> = info.uses.values().collect();
+
+    for b in (0..cfg.blocks.len()).filter(|&b| reachable[b]) {
+        let mut set = liveness.after[b].clone();
+
+        for events in vars.events[b].iter().rev() {
+            for e in events.iter().rev() {
+                if let Event::Def { var, def, .. } = *e {
+                    let d = &vars.defs[def];
+                    let quiet = d.init && d.constant || !used.contains(&vars.syms[var]);
+
+                    if !set.0.contains(var) && !quiet {
+                        let during = if d.init {
+                            " during its initialization"
+                        } else {
+                            ""
+                        };
+                        let msg = format!(
+                            "value stored to '{}'{} is never read",
+                            vars.name(var, info),
+                            during
+                        );
+                        reports.push(Report {
+                            pos: d.pos,
+                            kind: ReportKind::DeadStore,
+                            msg,
+                        });
+                    }
+                }
+
+                live.apply(e, &mut set);
+            }
+        }
+    }
+
+    reports.sort_by_key(|r| r.pos);
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::DeclKind;
+
+    // checks all the functions of a file, the reports are formatted as
+    // `line:column: message`
+    fn reports(src: &str) -> Vec<String> {
+        let mut p = parser::Parser::from(src.to_string());
+        let file = p.parse_file();
+        assert!(p.errors().is_empty(), "{:?}", p.errors());
+
+        let info = sema::check(&file);
+        assert_eq!(0, info.errors().count(), "{:?}", info.diagnostics);
+
+        let mut out = Vec::new();
+        for d in file.decls.iter() {
+            if let DeclKind::FuncDecl(f) = d.kind() {
+                for r in check(f, &info) {
+                    let pos = p.position(r.pos);
+                    out.push(format!("{}:{}: {}", pos.line, pos.column, r.msg));
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_check() {
+        let tests = [
+            (
+                "int f(int c) { int x; if (c) x = 1; return x; }",
+                vec!["1:44: variable 'x' may be uninitialized when used here"],
+            ),
+            (
+                "int f(int c) { int x, y; y = x + 1; if (c) x = 2; else x = 3; return x + y; }",
+                vec!["1:30: variable 'x' is uninitialized when used here"],
+            ),
+            (
+                "int f(int n) { int s, i; for (i = 0; i < n; i++) s += i; return s; }",
+                vec!["1:50: variable 's' may be uninitialized when used here"],
+            ),
+            (
+                "int f(int n) { int s = 0; while (n--) { int t; t = n; s += t; } return s; }",
+                vec![],
+            ),
+            (
+                "int g(int *);\nint f(void) { int x; g(&x); int y; int z = sizeof y; return x + z; }",
+                vec![],
+            ),
+            (
+                "int f(int c) { int x; c && (x = 1); return x; }",
+                vec!["1:44: variable 'x' may be uninitialized when used here"],
+            ),
+            (
+                "int f(int a) { int x = a * 2; x = 3; a = 4; int y = 0; y = a; return x + y; }",
+                vec!["1:20: value stored to 'x' during its initialization is never read"],
+            ),
+            (
+                "int f(int n) { int i = 0, last; while (i < n) { last = i; i++; } i = 0; return n; }",
+                vec![
+                    "1:49: value stored to 'last' is never read",
+                    "1:66: value stored to 'i' is never read",
+                ],
+            ),
+            (
+                "void f(int n) { int k = n + 1; int j; j = 2; }",
+                vec!["1:39: value stored to 'j' is never read"],
+            ),
+        ];
+
+        for (i, (src, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                *want,
+                reports(src),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Data-flow analysis on the control-flow graphs of `sema::cfg`: a
+//! worklist solver for analyses whose facts form a lattice, the classic
+//! analyses on the local variables of a function, and the checks built on
+//! them, reads of uninitialized variables and stores never read.
+
+use std::collections::VecDeque;
+
+use sema::cfg::{BlockId, Cfg};
+
+pub mod analyses;
+mod check;
+pub mod vars;
+
+pub use check::{check, Report, ReportKind};
+
+/// The facts of an analysis. `join` merges the facts flowing in from
+/// another edge, it only moves up the lattice so the solver terminates.
+pub trait Lattice: Clone + PartialEq {
+    /// Merges `other` into `self`, reports whether `self` changed.
+    fn join(&mut self, other: &Self) -> bool;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,  // facts flow from the entry along the edges
+    Backward, // facts flow from the exit against the edges
+}
+
+pub trait Analysis {
+    type Fact: Lattice;
+
+    const DIRECTION: Direction;
+
+    /// The fact at the entry of a forward analysis, or at the exit of a
+    /// backward one.
+    fn boundary(&self) -> Self::Fact;
+
+    /// The fact every other block starts from, the bottom of the lattice.
+    fn bottom(&self) -> Self::Fact;
+
+    /// Applies the effect of node `i` of block `b` to `fact`.
+    fn transfer(&self, b: BlockId, i: usize, fact: &mut Self::Fact);
+}
+
+/// The facts at the start and at the end of each block, in program order
+/// whatever the direction of the analysis.
+#[derive(Debug)]
+pub struct Results<F> {
+    pub before: Vec<F>,
+    pub after: Vec<F>,
+}
+
+/// Computes the fixed point of an analysis on a graph.
+pub fn solve<A: Analysis>(cfg: &Cfg, a: &A) -> Results<A::Fact> {
+    let n = cfg.blocks.len();
+    let forward = A::DIRECTION == Direction::Forward;
+
+    // `ins` are the facts flowing into a block, `outs` the ones flowing out,
+    // the start of a block in a forward analysis and its end in a backward
+    // one
+    let mut ins = vec![a.bottom(); n];
+    let mut outs = vec![a.bottom(); n];
+
+    let (start, edges) = if forward {
+        (cfg.entry, cfg.preds())
+    } else {
+        let succs = cfg.blocks.iter().map(|b| b.succs.clone()).collect();
+        (cfg.exit, succs)
+    };
+    ins[start.0] = a.boundary();
+
+    // the blocks whose facts flow into each block, and the other way
+    let mut flows_to = vec![Vec::new(); n];
+    for (b, from) in edges.iter().enumerate() {
+        for f in from.iter() {
+            flows_to[f.0].push(BlockId(b));
+        }
+    }
+
+    let mut work: VecDeque<BlockId> = (0..n).map(BlockId).collect();
+    let mut queued = vec![true; n];
+
+    while let Some(b) = work.pop_front() {
+        queued[b.0] = false;
+
+        if b != start {
+            let mut fact = a.bottom();
+            for from in edges[b.0].iter() {
+                fact.join(&outs[from.0]);
+            }
+            ins[b.0] = fact;
+        }
+
+        let mut fact = ins[b.0].clone();
+        let nodes = cfg.block(b).nodes.len();
+        if forward {
+            (0..nodes).for_each(|i| a.transfer(b, i, &mut fact));
+        } else {
+            (0..nodes).rev().for_each(|i| a.transfer(b, i, &mut fact));
+        }
+
+        if fact != outs[b.0] {
+            outs[b.0] = fact;
+
+            for &to in flows_to[b.0].iter() {
+                if !std::mem::replace(&mut queued[to.0], true) {
+                    work.push_back(to);
+                }
+            }
+        }
+    }
+
+    if forward {
+        Results {
+            before: ins,
+            after: outs,
+        }
+    } else {
+        Results {
+            before: outs,
+            after: ins,
+        }
+    }
+}
+
+/// A set of small integers, the facts of most analyses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitSet {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitSet {
+    /// Returns an empty set of integers below `len`.
+    pub fn empty(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    /// Returns the set of all the integers below `len`.
+    pub fn full(len: usize) -> Self {
+        let mut s = Self::empty(len);
+        (0..len).for_each(|i| s.insert(i));
+        s
+    }
+
+    pub fn insert(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    pub fn remove(&mut self, i: usize) {
+        self.words[i / 64] &= !(1 << (i % 64));
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len).filter(|&i| self.contains(i))
+    }
+
+    fn merge(&mut self, other: &Self, f: impl Fn(u64, u64) -> u64) -> bool {
+        let mut changed = false;
+
+        for (w, o) in self.words.iter_mut().zip(other.words.iter()) {
+            let v = f(*w, *o);
+            changed |= v != *w;
+            *w = v;
+        }
+
+        changed
+    }
+}
+
+/// Sets merged by union, for the facts true on some path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Union(pub BitSet);
+
+impl Lattice for Union {
+    fn join(&mut self, other: &Self) -> bool {
+        self.0.merge(&other.0, |a, b| a | b)
+    }
+}
+
+/// Sets merged by intersection, for the facts true on all the paths. The
+/// bottom of the lattice is the full set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Intersection(pub BitSet);
+
+impl Lattice for Intersection {
+    fn join(&mut self, other: &Self) -> bool {
+        self.0.merge(&other.0, |a, b| a & b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use analyses::{DefiniteAssignment, Liveness, ReachingDefinitions};
+    use ast::DeclKind;
+    use vars::Vars;
+
+    #[test]
+    fn test_bitset() {
+        let mut a = BitSet::empty(70);
+        a.insert(3);
+        a.insert(68);
+        assert!(a.contains(68) && !a.contains(4));
+        assert_eq!(vec![3, 68], a.iter().collect::<Vec<_>>());
+
+        let mut u = Union(a.clone());
+        assert!(!u.join(&Union(BitSet::empty(70))));
+        let mut b = BitSet::empty(70);
+        b.insert(5);
+        assert!(u.join(&Union(b.clone())));
+        assert_eq!(vec![3, 5, 68], u.0.iter().collect::<Vec<_>>());
+
+        let mut i = Intersection(BitSet::full(70));
+        assert!(i.join(&Intersection(a)));
+        assert!(i.join(&Intersection(b)));
+        assert_eq!(0, i.0.iter().count());
+    }
+
+    #[test]
+    fn test_solve() {
+        let src = "int f(int a, int b) { int x = a; if (b) x = 2; return x; }";
+        let mut p = parser::Parser::from(src.to_string());
+        let file = p.parse_file();
+        let info = sema::check(&file);
+        let DeclKind::FuncDecl(f) = file.decls[0].kind() else {
+            panic!("{}", src);
+        };
+
+        let (cfg, _) = Cfg::build(&f.body, &info);
+        let vars = Vars::new(f, &cfg, &info);
+        let names = |s: &BitSet| -> Vec<&str> { s.iter().map(|v| vars.name(v, &info)).collect() };
+
+        // the body starts in block 2, its return ends in the exit, 1
+        let live = solve(&cfg, &Liveness { vars: &vars });
+        assert_eq!(vec!["a", "b"], names(&live.before[2].0));
+        assert_eq!(Vec::<&str>::new(), names(&live.before[1].0));
+
+        let assigned = solve(&cfg, &DefiniteAssignment { vars: &vars });
+        assert_eq!(vec!["a", "b"], names(&assigned.before[2].0));
+        assert_eq!(vec!["a", "b", "x"], names(&assigned.before[1].0));
+
+        // both stores to x reach the return
+        let reaching = solve(&cfg, &ReachingDefinitions { vars: &vars });
+        let defs: Vec<_> = reaching.before[1]
+            .0
+            .iter()
+            .map(|d| vars.defs[d].pos)
+            .collect();
+        assert_eq!(vec![10, 17, 26, 40], defs);
+    }
+}
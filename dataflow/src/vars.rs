@@ -0,0 +1,344 @@
+//! The local variables of a function the analyses track, and what each
+//! node of its graph does to them.
+
+use std::collections::HashMap;
+
+use ast::{Expr, ExprKind, FuncDecl};
+use sema::cfg::{Cfg, Node};
+use sema::{Info, SymbolId, SymbolKind};
+use token::Token;
+
+/// What a node does to a tracked variable, in evaluation order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// Reads the variable, at the position of its identifier.
+    Use(usize, usize),
+    /// Stores to the variable. A store made on some paths through the node
+    /// only, like in the right operand of `&&`, does not kill the earlier
+    /// ones.
+    Def { var: usize, def: usize, kill: bool },
+    /// Declares the variable without an initializer, its value is
+    /// indeterminate again.
+    Undef(usize),
+}
+
+/// A store to a variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Def {
+    pub var: usize,
+    pub pos: usize,     // of the identifier stored to
+    pub init: bool,     // by the initializer of its declaration
+    pub param: bool,    // the argument, stored at the entry
+    pub constant: bool, // the value stored is a constant expression
+}
+
+/// The tracked variables of a function: the scalar locals and parameters
+/// whose address is never taken, so that only their names access them.
+pub struct Vars {
+    pub syms: Vec<SymbolId>,
+    pub defs: Vec<Def>,
+    pub events: Vec<Vec<Vec<Event>>>, // by block and node
+
+    index: HashMap<SymbolId, usize>,
+}
+
+impl Vars {
+    pub fn new(f: &FuncDecl, cfg: &Cfg, info: &Info) -> Self {
+        let mut escaped = Vec::new();
+        for b in cfg.blocks.iter() {
+            for n in b.nodes.iter() {
+                match n {
+                    Node::Decl(_, d) => {
+                        if let Some(x) = &d.init {
+                            address_taken(x.as_ref(), info, &mut escaped);
+                        }
+                    }
+                    Node::Expr(x) => address_taken(*x, info, &mut escaped),
+                }
+            }
+        }
+
+        let mut v = Vars {
+            syms: Vec::new(),
+            defs: Vec::new(),
+            events: Vec::new(),
+            index: HashMap::new(),
+        };
+
+        let tracked = |id: &SymbolId| {
+            let s = info.symbol(*id);
+            s.ty.is_scalar() && !escaped.contains(id)
+        };
+
+        let params = f.declarator.function().map_or(&[][..], |f| &f.params);
+        for p in params.iter() {
+            let Some(name) = &p.declarator.name else {
+                continue;
+            };
+            let Some(&id) = info.decls.get(&name.pos).filter(|id| tracked(id)) else {
+                continue;
+            };
+
+            let var = v.add(id);
+            v.defs.push(Def {
+                var,
+                pos: name.pos,
+                init: false,
+                param: true,
+                constant: false,
+            });
+        }
+
+        for b in cfg.blocks.iter() {
+            for n in b.nodes.iter() {
+                let Node::Decl(specs, d) = n else {
+                    continue;
+                };
+                let Some(&id) = d
+                    .declarator
+                    .name
+                    .as_ref()
+                    .and_then(|n| info.decls.get(&n.pos))
+                else {
+                    continue;
+                };
+
+                // static locals are initialized before the program starts
+                let storage = matches!(specs.storage, Some(Token::STATIC | Token::EXTERN));
+                if info.symbol(id).kind == SymbolKind::Var && !storage && tracked(&id) {
+                    v.add(id);
+                }
+            }
+        }
+
+        v.events = cfg
+            .blocks
+            .iter()
+            .map(|b| b.nodes.iter().map(|n| v.node(n, info)).collect())
+            .collect();
+
+        v
+    }
+
+    fn add(&mut self, id: SymbolId) -> usize {
+        *self.index.entry(id).or_insert_with(|| {
+            self.syms.push(id);
+            self.syms.len() - 1
+        })
+    }
+
+    /// Returns the tracked variable an identifier at `pos` refers to.
+    pub fn var(&self, pos: usize, info: &Info) -> Option<usize> {
+        let id = info.decls.get(&pos).or_else(|| info.uses.get(&pos))?;
+        self.index.get(id).copied()
+    }
+
+    pub fn name<'i>(&self, var: usize, info: &'i Info) -> &'i str {
+        &info.symbol(self.syms[var]).name
+    }
+
+    fn node(&mut self, n: &Node, info: &Info) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        match n {
+            Node::Decl(_, d) => {
+                let var = d
+                    .declarator
+                    .name
+                    .as_ref()
+                    .and_then(|n| Some((self.var(n.pos, info)?, n.pos)));
+
+                if let Some(x) = &d.init {
+                    self.expr(x.as_ref(), false, info, &mut events);
+                }
+
+                if let Some((var, pos)) = var {
+                    match &d.init {
+                        Some(x) => {
+                            let constant = sema::consts::Eval::new(info).int(x.as_ref()).is_ok();
+                            self.def(var, pos, true, constant, false, &mut events);
+                        }
+                        None => events.push(Event::Undef(var)),
+                    }
+                }
+            }
+            Node::Expr(x) => self.expr(*x, false, info, &mut events),
+        }
+
+        events
+    }
+
+    fn def(
+        &mut self,
+        var: usize,
+        pos: usize,
+        init: bool,
+        constant: bool,
+        cond: bool,
+        events: &mut Vec<Event>,
+    ) {
+        self.defs.push(Def {
+            var,
+            pos,
+            init,
+            param: false,
+            constant,
+        });
+        events.push(Event::Def {
+            var,
+            def: self.defs.len() - 1,
+            kill: !cond,
+        });
+    }
+
+    // the tracked variable an lvalue names
+    fn lvalue(&self, x: &dyn Expr, info: &Info) -> Option<(usize, usize)> {
+        match strip_parens(x).kind() {
+            ExprKind::Ident(id) => Some((self.var(id.pos, info)?, id.pos)),
+            _ => None,
+        }
+    }
+
+    // collects the events of an expression, `cond` when it is evaluated
+    // on some paths through its node only
+    fn expr(&mut self, x: &dyn Expr, cond: bool, info: &Info, events: &mut Vec<Event>) {
+        match x.kind() {
+            ExprKind::Ident(id) => {
+                if let Some(var) = self.var(id.pos, info) {
+                    events.push(Event::Use(var, id.pos));
+                }
+            }
+
+            ExprKind::AssignExpr(e) => match self.lvalue(e.x.as_ref(), info) {
+                Some((var, pos)) => {
+                    if e.op != Token::ASSIGN {
+                        events.push(Event::Use(var, pos));
+                    }
+                    self.expr(e.y.as_ref(), cond, info, events);
+
+                    let constant = e.op == Token::ASSIGN
+                        && sema::consts::Eval::new(info).int(e.y.as_ref()).is_ok();
+                    self.def(var, pos, false, constant, cond, events);
+                }
+                None => {
+                    self.expr(e.x.as_ref(), cond, info, events);
+                    self.expr(e.y.as_ref(), cond, info, events);
+                }
+            },
+
+            ExprKind::UnaryExpr(e) if matches!(e.op, Token::INC | Token::DEC) => {
+                self.step(e.x.as_ref(), cond, info, events)
+            }
+            ExprKind::PostfixExpr(e) => self.step(e.x.as_ref(), cond, info, events),
+
+            // the operand of sizeof is not evaluated
+            ExprKind::SizeofExpr(_) | ExprKind::OffsetofExpr(_) | ExprKind::BasicLit(_) => {}
+
+            ExprKind::UnaryExpr(e) => self.expr(e.x.as_ref(), cond, info, events),
+            ExprKind::StarExpr(e) => self.expr(e.x.as_ref(), cond, info, events),
+            ExprKind::ParenExpr(e) => self.expr(e.x.as_ref(), cond, info, events),
+            ExprKind::SelectorExpr(e) => self.expr(e.x.as_ref(), cond, info, events),
+            ExprKind::CastExpr(e) => self.expr(e.x.as_ref(), cond, info, events),
+
+            ExprKind::InfixExpr(e) => {
+                let short = matches!(e.op, Token::LAND | Token::LOR);
+                self.expr(e.x.as_ref(), cond, info, events);
+                self.expr(e.y.as_ref(), cond || short, info, events);
+            }
+
+            ExprKind::CondExpr(e) => {
+                self.expr(e.cond.as_ref(), cond, info, events);
+                self.expr(e.x.as_ref(), true, info, events);
+                self.expr(e.y.as_ref(), true, info, events);
+            }
+
+            ExprKind::CallExpr(e) => {
+                self.expr(e.fun.as_ref(), cond, info, events);
+                for a in e.args.iter() {
+                    self.expr(a.as_ref(), cond, info, events);
+                }
+            }
+
+            ExprKind::IndexExpr(e) => {
+                self.expr(e.x.as_ref(), cond, info, events);
+                self.expr(e.index.as_ref(), cond, info, events);
+            }
+
+            ExprKind::CompositeLit(e) => {
+                for e in e.init.elems.iter() {
+                    self.expr(e.value.as_ref(), cond, info, events);
+                }
+            }
+            ExprKind::InitList(l) => {
+                for e in l.elems.iter() {
+                    self.expr(e.value.as_ref(), cond, info, events);
+                }
+            }
+        }
+    }
+
+    // ++ and -- read and store their operand
+    fn step(&mut self, x: &dyn Expr, cond: bool, info: &Info, events: &mut Vec<Event>) {
+        match self.lvalue(x, info) {
+            Some((var, pos)) => {
+                events.push(Event::Use(var, pos));
+                self.def(var, pos, false, false, cond, events);
+            }
+            None => self.expr(x, cond, info, events),
+        }
+    }
+}
+
+fn strip_parens(mut x: &dyn Expr) -> &dyn Expr {
+    while let ExprKind::ParenExpr(p) = x.kind() {
+        x = p.x.as_ref();
+    }
+    x
+}
+
+// collects the variables whose address is taken, they can be read and
+// stored through pointers
+fn address_taken(x: &dyn Expr, info: &Info, escaped: &mut Vec<SymbolId>) {
+    if let ExprKind::UnaryExpr(e) = x.kind() {
+        if let (Token::AND, ExprKind::Ident(id)) = (e.op, strip_parens(e.x.as_ref()).kind()) {
+            escaped.extend(info.uses.get(&id.pos));
+        }
+    }
+
+    let mut sub = |x: &dyn Expr| address_taken(x, info, escaped);
+
+    match x.kind() {
+        ExprKind::Ident(_) | ExprKind::BasicLit(_) | ExprKind::OffsetofExpr(_) => {}
+        ExprKind::SizeofExpr(_) => {}
+
+        ExprKind::UnaryExpr(e) => sub(e.x.as_ref()),
+        ExprKind::StarExpr(e) => sub(e.x.as_ref()),
+        ExprKind::ParenExpr(e) => sub(e.x.as_ref()),
+        ExprKind::PostfixExpr(e) => sub(e.x.as_ref()),
+        ExprKind::SelectorExpr(e) => sub(e.x.as_ref()),
+        ExprKind::CastExpr(e) => sub(e.x.as_ref()),
+        ExprKind::InfixExpr(e) => {
+            sub(e.x.as_ref());
+            sub(e.y.as_ref());
+        }
+        ExprKind::AssignExpr(e) => {
+            sub(e.x.as_ref());
+            sub(e.y.as_ref());
+        }
+        ExprKind::CondExpr(e) => {
+            sub(e.cond.as_ref());
+            sub(e.x.as_ref());
+            sub(e.y.as_ref());
+        }
+        ExprKind::CallExpr(e) => {
+            sub(e.fun.as_ref());
+            e.args.iter().for_each(|a| sub(a.as_ref()));
+        }
+        ExprKind::IndexExpr(e) => {
+            sub(e.x.as_ref());
+            sub(e.index.as_ref());
+        }
+        ExprKind::CompositeLit(e) => e.init.elems.iter().for_each(|e| sub(e.value.as_ref())),
+        ExprKind::InitList(l) => l.elems.iter().for_each(|e| sub(e.value.as_ref())),
+    }
+}
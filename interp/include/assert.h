@@ -0,0 +1,4 @@
+/* assert.h: assert is a function, the call whose argument is zero prints
+   the argument as written and aborts the program */
+
+void assert(int expr);
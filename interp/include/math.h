@@ -0,0 +1,57 @@
+/* math.h: each function has a float variant with the suffix f */
+
+double sin(double x);
+double cos(double x);
+double tan(double x);
+double asin(double x);
+double acos(double x);
+double atan(double x);
+double atan2(double y, double x);
+double sinh(double x);
+double cosh(double x);
+double tanh(double x);
+double exp(double x);
+double exp2(double x);
+double log(double x);
+double log10(double x);
+double log2(double x);
+double pow(double x, double y);
+double sqrt(double x);
+double cbrt(double x);
+double hypot(double x, double y);
+double ceil(double x);
+double floor(double x);
+double round(double x);
+double trunc(double x);
+double fabs(double x);
+double fmod(double x, double y);
+double fmin(double x, double y);
+double fmax(double x, double y);
+
+float sinf(float x);
+float cosf(float x);
+float tanf(float x);
+float asinf(float x);
+float acosf(float x);
+float atanf(float x);
+float atan2f(float y, float x);
+float sinhf(float x);
+float coshf(float x);
+float tanhf(float x);
+float expf(float x);
+float exp2f(float x);
+float logf(float x);
+float log10f(float x);
+float log2f(float x);
+float powf(float x, float y);
+float sqrtf(float x);
+float cbrtf(float x);
+float hypotf(float x, float y);
+float ceilf(float x);
+float floorf(float x);
+float roundf(float x);
+float truncf(float x);
+float fabsf(float x);
+float fmodf(float x, float y);
+float fminf(float x, float y);
+float fmaxf(float x, float y);
@@ -0,0 +1,50 @@
+/* stdio.h: the streams of the interpreter, stdin and stdout are those of
+   crepl, files are read from and written back to the host file system */
+
+#include <stddef.h>
+
+typedef struct FILE FILE;
+typedef long fpos_t;
+
+enum { EOF = -1 };
+enum { SEEK_SET = 0, SEEK_CUR = 1, SEEK_END = 2 };
+enum { BUFSIZ = 8192, FILENAME_MAX = 4096 };
+
+extern FILE *stdin;
+extern FILE *stdout;
+extern FILE *stderr;
+
+FILE *fopen(const char *path, const char *mode);
+int fclose(FILE *f);
+int fflush(FILE *f);
+int remove(const char *path);
+
+int printf(const char *format, ...);
+int fprintf(FILE *f, const char *format, ...);
+int sprintf(char *s, const char *format, ...);
+int snprintf(char *s, size_t n, const char *format, ...);
+int scanf(const char *format, ...);
+int fscanf(FILE *f, const char *format, ...);
+int sscanf(const char *s, const char *format, ...);
+
+int getchar(void);
+int getc(FILE *f);
+int fgetc(FILE *f);
+char *fgets(char *s, int n, FILE *f);
+int ungetc(int c, FILE *f);
+int putchar(int c);
+int putc(int c, FILE *f);
+int fputc(int c, FILE *f);
+int puts(const char *s);
+int fputs(const char *s, FILE *f);
+
+size_t fread(void *p, size_t size, size_t n, FILE *f);
+size_t fwrite(const void *p, size_t size, size_t n, FILE *f);
+
+int fseek(FILE *f, long offset, int whence);
+long ftell(FILE *f);
+void rewind(FILE *f);
+int feof(FILE *f);
+int ferror(FILE *f);
+void clearerr(FILE *f);
+void perror(const char *s);
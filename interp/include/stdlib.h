@@ -0,0 +1,42 @@
+/* stdlib.h */
+
+#include <stddef.h>
+
+enum { EXIT_SUCCESS = 0, EXIT_FAILURE = 1 };
+enum { RAND_MAX = 2147483647 };
+
+typedef struct { int quot, rem; } div_t;
+typedef struct { long quot, rem; } ldiv_t;
+
+void *malloc(size_t n);
+void *calloc(size_t n, size_t size);
+void *realloc(void *p, size_t n);
+void free(void *p);
+
+void exit(int status);
+void abort(void);
+char *getenv(const char *name);
+
+int atoi(const char *s);
+long atol(const char *s);
+long long atoll(const char *s);
+double atof(const char *s);
+long strtol(const char *s, char **end, int base);
+unsigned long strtoul(const char *s, char **end, int base);
+long long strtoll(const char *s, char **end, int base);
+unsigned long long strtoull(const char *s, char **end, int base);
+double strtod(const char *s, char **end);
+float strtof(const char *s, char **end);
+
+int abs(int n);
+long labs(long n);
+long long llabs(long long n);
+div_t div(int x, int y);
+ldiv_t ldiv(long x, long y);
+
+int rand(void);
+void srand(unsigned seed);
+
+void qsort(void *base, size_t n, size_t size, int (*cmp)(const void *, const void *));
+void *bsearch(const void *key, const void *base, size_t n, size_t size,
+              int (*cmp)(const void *, const void *));
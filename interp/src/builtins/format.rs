@@ -0,0 +1,746 @@
+//! The formats of the printf and scanf functions.
+
+use sema::types::{FloatKind, IntKind, Type};
+
+use super::stdlib::{parse_float, parse_int};
+use super::Args;
+use crate::value::Value;
+use crate::{error, fault, Interpreter, Result};
+
+/// A length modifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Len {
+    None,
+    Char,     // hh
+    Short,    // h
+    Long,     // l
+    LongLong, // ll
+    Max,      // j
+    Size,     // z
+    Ptrdiff,  // t
+    Double,   // L
+}
+
+/// A conversion specification.
+#[derive(Debug, Default)]
+struct Spec {
+    left: bool,  // -
+    plus: bool,  // +
+    space: bool, // ' '
+    alt: bool,   // #
+    zero: bool,  // 0
+    width: Option<usize>,
+    prec: Option<usize>,
+}
+
+impl Len {
+    // the length modifier at the start of `fmt` and its length
+    fn parse(fmt: &[u8]) -> (Len, usize) {
+        match fmt {
+            [b'h', b'h', ..] => (Len::Char, 2),
+            [b'l', b'l', ..] => (Len::LongLong, 2),
+            [b'h', ..] => (Len::Short, 1),
+            [b'l', ..] => (Len::Long, 1),
+            [b'j', ..] => (Len::Max, 1),
+            [b'z', ..] => (Len::Size, 1),
+            [b't', ..] => (Len::Ptrdiff, 1),
+            [b'L', ..] => (Len::Double, 1),
+            _ => (Len::None, 0),
+        }
+    }
+
+    // the integer type of a conversion with this modifier
+    fn int_kind(self, signed: bool, it: &Interpreter) -> IntKind {
+        let dl = it.layout();
+        let k = match self {
+            Len::Char => IntKind::SChar,
+            Len::Short => IntKind::Short,
+            Len::Long => IntKind::Long,
+            Len::LongLong | Len::Max => IntKind::LongLong,
+            Len::Size => dl.size_t,
+            Len::Ptrdiff => dl.ptrdiff_t,
+            Len::None | Len::Double => IntKind::Int,
+        };
+        match signed {
+            true if !dl.is_signed(k) => match k {
+                IntKind::UShort => IntKind::Short,
+                IntKind::UInt => IntKind::Int,
+                IntKind::ULong => IntKind::Long,
+                _ => IntKind::LongLong,
+            },
+            true => k,
+            false => k.to_unsigned(),
+        }
+    }
+}
+
+/// Formats the arguments of a printf function, `%n` stores the number of
+/// bytes formatted so far.
+pub(super) fn printf(
+    it: &mut Interpreter,
+    fmt: &[u8],
+    args: &[Value],
+    pos: usize,
+) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut args = args.iter().cloned();
+    let mut next = || args.next().ok_or(());
+    let too_few = || error(pos, "too few arguments for the format string");
+
+    let mut i = 0;
+    while i < fmt.len() {
+        if fmt[i] != b'%' {
+            out.push(fmt[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+
+        let mut spec = Spec::default();
+        while let Some(&c) = fmt.get(i) {
+            match c {
+                b'-' => spec.left = true,
+                b'+' => spec.plus = true,
+                b' ' => spec.space = true,
+                b'#' => spec.alt = true,
+                b'0' => spec.zero = true,
+                _ => break,
+            }
+            i += 1;
+        }
+
+        // a negative width from an argument is the flag -
+        if fmt.get(i) == Some(&b'*') {
+            let Ok(w) = next() else { return too_few() };
+            let w = w.as_int() as i32;
+            spec.left |= w < 0;
+            spec.width = Some(w.unsigned_abs() as usize);
+            i += 1;
+        } else {
+            let (w, n) = number(&fmt[i..]);
+            spec.width = w;
+            i += n;
+        }
+
+        // a negative precision is none
+        if fmt.get(i) == Some(&b'.') {
+            i += 1;
+            if fmt.get(i) == Some(&b'*') {
+                let Ok(p) = next() else { return too_few() };
+                let p = p.as_int() as i32;
+                spec.prec = (p >= 0).then_some(p as usize);
+                i += 1;
+            } else {
+                let (p, n) = number(&fmt[i..]);
+                spec.prec = Some(p.unwrap_or(0));
+                i += n;
+            }
+        }
+
+        let (len, n) = Len::parse(&fmt[i..]);
+        i += n;
+        let Some(&conv) = fmt.get(i) else {
+            return error(pos, "incomplete format specifier");
+        };
+        i += 1;
+
+        if conv == b'%' {
+            out.push(b'%');
+            continue;
+        }
+        let Ok(arg) = next() else { return too_few() };
+
+        let (prefix, body) = match conv {
+            b'd' | b'i' => {
+                let v = it.layout().wrap(arg.as_int(), len.int_kind(true, it));
+                let sign = match v {
+                    _ if v < 0 => "-",
+                    _ if spec.plus => "+",
+                    _ if spec.space => " ",
+                    _ => "",
+                };
+                (sign.to_string(), digits(v.unsigned_abs(), 10, false, &spec))
+            }
+            b'u' | b'o' | b'x' | b'X' => {
+                let v = it.layout().wrap(arg.as_int(), len.int_kind(false, it)) as u128;
+                let base = match conv {
+                    b'u' => 10,
+                    b'o' => 8,
+                    _ => 16,
+                };
+                let mut body = digits(v, base, conv == b'X', &spec);
+                let mut prefix = String::new();
+                if spec.alt && base == 8 && !body.starts_with('0') {
+                    body.insert(0, '0');
+                }
+                if spec.alt && base == 16 && v != 0 {
+                    prefix = if conv == b'X' { "0X" } else { "0x" }.to_string();
+                }
+                (prefix, body)
+            }
+
+            b'f' | b'F' | b'e' | b'E' | b'g' | b'G' | b'a' | b'A' => {
+                let f = arg.as_float();
+                let sign = match f.is_sign_negative() {
+                    true if !f.is_nan() => "-",
+                    _ if spec.plus => "+",
+                    _ if spec.space => " ",
+                    _ => "",
+                };
+                let body = float(f.abs(), conv, &spec);
+                if !f.is_finite() {
+                    spec.zero = false;
+                }
+                (sign.to_string(), body)
+            }
+
+            b'c' => {
+                let mut s = vec![arg.as_int() as u8];
+                spec.zero = false;
+                pad(&mut out, b"", &mut s, &spec);
+                continue;
+            }
+            b's' => {
+                let p = super::pointer(arg);
+                let mut s = match (p.addr, spec.prec) {
+                    (0, Some(n)) if n < 6 => Vec::new(),
+                    (0, _) => b"(null)".to_vec(),
+                    (_, None) => super::read_string(it, p, pos)?,
+                    // the array needs no terminator within the precision
+                    (_, Some(n)) => {
+                        let mut s = Vec::new();
+                        while s.len() < n {
+                            let c = it
+                                .mem
+                                .read(p.offset(s.len() as i64), 1)
+                                .map_err(fault(pos))?[0];
+                            if c == 0 {
+                                break;
+                            }
+                            s.push(c);
+                        }
+                        s
+                    }
+                };
+                spec.zero = false;
+                pad(&mut out, b"", &mut s, &spec);
+                continue;
+            }
+            b'p' => {
+                let addr = arg.as_int();
+                let body = match addr {
+                    0 => "(nil)".to_string(),
+                    _ => format!("{:#x}", addr),
+                };
+                spec.zero = false;
+                (String::new(), body)
+            }
+
+            b'n' => {
+                let p = super::pointer(arg);
+                let ty = Type::int(len.int_kind(true, it));
+                it.store(p, &ty, Value::Int(out.len() as i128), pos)?;
+                continue;
+            }
+
+            _ => {
+                return error(
+                    pos,
+                    format!("invalid conversion specifier '%{}'", conv as char),
+                )
+            }
+        };
+
+        // the precision of an integer disables the flag 0
+        if spec.prec.is_some() && b"diouxX".contains(&conv) {
+            spec.zero = false;
+        }
+        pad(&mut out, prefix.as_bytes(), &mut body.into_bytes(), &spec);
+    }
+
+    Ok(out)
+}
+
+// a decimal number at the start of `s` and its length
+fn number(s: &[u8]) -> (Option<usize>, usize) {
+    let n = s.iter().take_while(|c| c.is_ascii_digit()).count();
+    let v = std::str::from_utf8(&s[..n])
+        .ok()
+        .and_then(|s| s.parse().ok());
+    (v, n)
+}
+
+// the digits of an integer, at least as many as the precision
+fn digits(v: u128, base: u32, upper: bool, spec: &Spec) -> String {
+    let mut s = match base {
+        8 => format!("{:o}", v),
+        16 if upper => format!("{:X}", v),
+        16 => format!("{:x}", v),
+        _ => v.to_string(),
+    };
+
+    match spec.prec {
+        Some(0) if v == 0 => s.clear(),
+        Some(p) if s.len() < p => s.insert_str(0, &"0".repeat(p - s.len())),
+        _ => {}
+    }
+    s
+}
+
+// appends a converted value padded to the width of its specification,
+// zeros go between the prefix and the body
+fn pad(out: &mut Vec<u8>, prefix: &[u8], body: &mut Vec<u8>, spec: &Spec) {
+    let len = prefix.len() + body.len();
+    let fill = spec.width.unwrap_or(0).saturating_sub(len);
+
+    if spec.left {
+        out.extend_from_slice(prefix);
+        out.append(body);
+        out.extend(std::iter::repeat_n(b' ', fill));
+    } else if spec.zero {
+        out.extend_from_slice(prefix);
+        out.extend(std::iter::repeat_n(b'0', fill));
+        out.append(body);
+    } else {
+        out.extend(std::iter::repeat_n(b' ', fill));
+        out.extend_from_slice(prefix);
+        out.append(body);
+    }
+}
+
+// formats a non-negative floating value
+fn float(f: f64, conv: u8, spec: &Spec) -> String {
+    let upper = conv.is_ascii_uppercase();
+    let s = if f.is_nan() {
+        "nan".to_string()
+    } else if f.is_infinite() {
+        "inf".to_string()
+    } else {
+        match conv.to_ascii_lowercase() {
+            b'f' => fixed(f, spec.prec.unwrap_or(6), spec.alt),
+            b'e' => exponent(f, spec.prec.unwrap_or(6), spec.alt),
+            b'g' => general(f, spec.prec.unwrap_or(6), spec.alt),
+            _ => hex(f, spec.prec, spec.alt),
+        }
+    };
+
+    match upper {
+        true => s.to_ascii_uppercase(),
+        false => s,
+    }
+}
+
+fn fixed(f: f64, prec: usize, alt: bool) -> String {
+    let mut s = format!("{:.*}", prec, f);
+    if alt && prec == 0 {
+        s.push('.');
+    }
+    s
+}
+
+// d.ddde+dd, the exponent has at least two digits
+fn exponent(f: f64, prec: usize, alt: bool) -> String {
+    let s = format!("{:.*e}", prec, f);
+    let (mant, exp) = s.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+
+    let dot = if alt && prec == 0 { "." } else { "" };
+    let sign = if exp < 0 { '-' } else { '+' };
+    format!("{}{}e{}{:02}", mant, dot, sign, exp.abs())
+}
+
+// the style of %e if the exponent is less than -4 or not less than the
+// precision, that of %f otherwise; trailing zeros are removed without #
+fn general(f: f64, prec: usize, alt: bool) -> String {
+    let p = prec.max(1);
+    let e = format!("{:.*e}", p - 1, f);
+    let x: i32 = e.split_once('e').unwrap().1.parse().unwrap();
+
+    let mut s = if x < -4 || x >= p as i32 {
+        exponent(f, p - 1, alt)
+    } else {
+        fixed(f, (p as i32 - 1 - x) as usize, alt)
+    };
+    if alt {
+        return s;
+    }
+
+    let (mant, exp) = match s.find('e') {
+        Some(i) => (s[..i].to_string(), s[i..].to_string()),
+        None => (s.clone(), String::new()),
+    };
+    if mant.contains('.') {
+        let mant = mant.trim_end_matches('0').trim_end_matches('.');
+        s = format!("{}{}", mant, exp);
+    }
+    s
+}
+
+// 0xh.hhhp+d, the leading digit is 1 for normal values
+fn hex(f: f64, prec: Option<usize>, alt: bool) -> String {
+    let bits = f.to_bits();
+    let (mut lead, mut mant, exp) = match (bits >> 52) & 0x7ff {
+        0 if f == 0.0 => (0, 0, 0),
+        0 => (0, bits & ((1 << 52) - 1), -1022),
+        e => (1, bits & ((1 << 52) - 1), e as i64 - 1023),
+    };
+
+    let mut digits = 13;
+    if let Some(p) = prec.filter(|&p| p < 13) {
+        // rounds the mantissa to p digits, half to even
+        let shift = 4 * (13 - p);
+        let rest = mant & ((1 << shift) - 1);
+        let half = 1 << (shift - 1);
+        mant >>= shift;
+        if rest > half || (rest == half && mant & 1 == 1) {
+            mant += 1;
+            if mant >> (4 * p) != 0 {
+                mant &= (1 << (4 * p)) - 1;
+                lead += 1;
+            }
+        }
+        digits = p;
+    }
+
+    let mut frac = match digits {
+        0 => String::new(),
+        n => format!("{:0width$x}", mant, width = n),
+    };
+    match prec {
+        None => frac = frac.trim_end_matches('0').to_string(),
+        Some(p) if p > 13 => frac.push_str(&"0".repeat(p - 13)),
+        _ => {}
+    }
+
+    let dot = if !frac.is_empty() || alt { "." } else { "" };
+    let sign = if exp < 0 { '-' } else { '+' };
+    format!("0x{}{}{}p{}{}", lead, dot, frac, sign, exp.abs())
+}
+
+/// The input of a scanf function, a byte at a time with one of look
+/// ahead.
+pub(super) struct Reader<'r> {
+    next: Box<dyn FnMut() -> Option<u8> + 'r>,
+    peeked: Option<Option<u8>>,
+    count: usize, // the bytes read
+}
+
+impl<'r> Reader<'r> {
+    pub(super) fn new(next: impl FnMut() -> Option<u8> + 'r) -> Self {
+        Self {
+            next: Box::new(next),
+            peeked: None,
+            count: 0,
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        *self.peeked.get_or_insert_with(&mut self.next)
+    }
+
+    fn get(&mut self) -> Option<u8> {
+        let c = self.peek();
+        self.peeked = None;
+        self.count += c.is_some() as usize;
+        c
+    }
+
+    fn skip_space(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+            self.get();
+        }
+    }
+
+    /// Returns the byte read ahead, it goes back to the stream.
+    pub(super) fn rest(self) -> Option<u8> {
+        self.peeked.flatten()
+    }
+}
+
+/// A value a scanf conversion read.
+enum Item {
+    Int(i128, Len, bool), // signed
+    Float(f64, Len),
+    Bytes(Vec<u8>, bool), // null terminated
+    Count(usize, Len),
+}
+
+/// The values a scanf format matched.
+pub(super) struct Scanned {
+    items: Vec<Option<Item>>, // None for a conversion without assignment
+    eof: bool,                // the input ended before the first conversion
+}
+
+impl Scanned {
+    /// Stores the values through the pointers of `args`, returns the
+    /// result of scanf: the number of values assigned or EOF.
+    pub(super) fn store(self, it: &mut Interpreter, args: &[Value], pos: usize) -> Result<i128> {
+        if self.eof {
+            return Ok(-1);
+        }
+
+        let args = Args(args);
+        let mut assigned = 0;
+        for (n, item) in self.items.into_iter().flatten().enumerate() {
+            if n >= args.0.len() {
+                return error(pos, "too few arguments for the format string");
+            }
+            let p = args.ptr(n);
+
+            match item {
+                Item::Int(v, len, signed) => {
+                    let ty = Type::int(len.int_kind(signed, it));
+                    it.store(p, &ty, Value::Int(v).convert(&ty, it.layout()), pos)?;
+                }
+                Item::Float(f, len) => {
+                    let k = match len {
+                        Len::Long => FloatKind::Double,
+                        Len::Double => FloatKind::LongDouble,
+                        _ => FloatKind::Float,
+                    };
+                    let ty = Type::float(k);
+                    it.store(p, &ty, Value::Float(f).convert(&ty, it.layout()), pos)?;
+                }
+                Item::Bytes(mut s, nul) => {
+                    if nul {
+                        s.push(0);
+                    }
+                    it.mem.write(p, &s).map_err(fault(pos))?;
+                }
+                Item::Count(c, len) => {
+                    let ty = Type::int(len.int_kind(true, it));
+                    it.store(p, &ty, Value::Int(c as i128), pos)?;
+                    continue;
+                }
+            }
+            assigned += 1;
+        }
+
+        Ok(assigned)
+    }
+}
+
+/// Reads the input of a scanf function by its format, until the format
+/// ends or the input does not match.
+pub(super) fn scanf(fmt: &[u8], r: &mut Reader) -> Scanned {
+    let mut items = Vec::new();
+    let mut converted = false;
+    let mut eof = false;
+
+    let mut i = 0;
+    while i < fmt.len() {
+        let c = fmt[i];
+        i += 1;
+
+        if c.is_ascii_whitespace() {
+            r.skip_space();
+            continue;
+        }
+        if c != b'%' || fmt.get(i) == Some(&b'%') {
+            i += (c == b'%') as usize;
+            if c == b'%' {
+                r.skip_space();
+            }
+            match r.peek() {
+                Some(b) if b == c => {
+                    r.get();
+                    continue;
+                }
+                None => eof = true,
+                _ => {}
+            }
+            break;
+        }
+
+        let assign = fmt.get(i) != Some(&b'*');
+        i += !assign as usize;
+        let (width, n) = number(&fmt[i..]);
+        i += n;
+        let (len, n) = Len::parse(&fmt[i..]);
+        i += n;
+        let Some(&conv) = fmt.get(i) else {
+            break;
+        };
+        i += 1;
+
+        if !matches!(conv, b'c' | b'[' | b'n') {
+            r.skip_space();
+        }
+        if conv != b'n' && r.peek().is_none() {
+            eof = true;
+            break;
+        }
+
+        let item = match conv {
+            b'd' | b'i' | b'u' | b'o' | b'x' | b'X' | b'p' => {
+                let base = match conv {
+                    b'd' | b'u' => 10,
+                    b'o' => 8,
+                    b'i' => 0,
+                    _ => 16,
+                };
+                let text = read_int(r, base, width.unwrap_or(usize::MAX));
+                match parse_int(&text, base) {
+                    (neg, v, n) if n > 0 && n == text.len() => {
+                        let v = if neg {
+                            (v as i128).wrapping_neg()
+                        } else {
+                            v as i128
+                        };
+                        let len = if conv == b'p' { Len::Size } else { len };
+                        Item::Int(v, len, matches!(conv, b'd' | b'i'))
+                    }
+                    _ => break,
+                }
+            }
+            b'f' | b'F' | b'e' | b'E' | b'g' | b'G' | b'a' | b'A' => {
+                let text = read_float(r, width.unwrap_or(usize::MAX));
+                match parse_float(&text) {
+                    (f, n) if n > 0 => Item::Float(f, len),
+                    _ => break,
+                }
+            }
+
+            b'c' => {
+                let mut s = Vec::new();
+                while s.len() < width.unwrap_or(1) {
+                    match r.get() {
+                        Some(c) => s.push(c),
+                        None => break,
+                    }
+                }
+                Item::Bytes(s, false)
+            }
+            b's' => {
+                let mut s = Vec::new();
+                while s.len() < width.unwrap_or(usize::MAX)
+                    && r.peek().is_some_and(|c| !c.is_ascii_whitespace())
+                {
+                    s.push(r.get().unwrap());
+                }
+                Item::Bytes(s, true)
+            }
+            b'[' => {
+                let (set, n) = scanset(&fmt[i..]);
+                i += n;
+                let mut s = Vec::new();
+                while s.len() < width.unwrap_or(usize::MAX) && r.peek().is_some_and(&set) {
+                    s.push(r.get().unwrap());
+                }
+                if s.is_empty() {
+                    break;
+                }
+                Item::Bytes(s, true)
+            }
+
+            b'n' => {
+                if assign {
+                    items.push(Some(Item::Count(r.count, len)));
+                }
+                continue;
+            }
+            _ => break,
+        };
+
+        converted = true;
+        items.push(assign.then_some(item));
+    }
+
+    Scanned {
+        items,
+        eof: eof && !converted,
+    }
+}
+
+// the text of an integer: a sign, the prefix 0x for base 16 and digits
+fn read_int(r: &mut Reader, base: u32, width: usize) -> Vec<u8> {
+    let mut s = Vec::new();
+    let more = |s: &Vec<u8>| s.len() < width;
+
+    if let Some(c @ (b'+' | b'-')) = r.peek() {
+        s.push(c);
+        r.get();
+    }
+
+    let mut base = base;
+    if (base == 0 || base == 16) && more(&s) && r.peek() == Some(b'0') {
+        s.push(r.get().unwrap());
+        if more(&s) && matches!(r.peek(), Some(b'x' | b'X')) {
+            s.push(r.get().unwrap());
+            base = 16;
+        } else if base == 0 {
+            base = 8;
+        }
+    }
+    let base = if base == 0 { 10 } else { base };
+
+    while more(&s) && r.peek().is_some_and(|c| (c as char).is_digit(base)) {
+        s.push(r.get().unwrap());
+    }
+    s
+}
+
+// the text of a floating constant: a sign, digits with a point and an
+// exponent, or inf or nan
+fn read_float(r: &mut Reader, width: usize) -> Vec<u8> {
+    let mut s: Vec<u8> = Vec::new();
+
+    while s.len() < width {
+        let Some(c) = r.peek() else {
+            break;
+        };
+        let last = s.last().copied();
+        let ok = match c {
+            b'+' | b'-' => last.is_none() || matches!(last, Some(b'e' | b'E')),
+            b'0'..=b'9' => true,
+            b'.' => !s.contains(&b'.') && !s.iter().any(|c| matches!(c, b'e' | b'E')),
+            b'e' | b'E' => {
+                s.iter().any(u8::is_ascii_digit) && !s.iter().any(|c| matches!(c, b'e' | b'E'))
+            }
+            _ => {
+                // inf and nan, infinity is read as inf
+                let word = [&s[..], &[c.to_ascii_lowercase()]].concat();
+                let word = word.strip_prefix(b"-").unwrap_or(&word);
+                let word = word.strip_prefix(b"+").unwrap_or(word);
+                b"inf".starts_with(&word.to_ascii_lowercase())
+                    || b"nan".starts_with(&word.to_ascii_lowercase())
+            }
+        };
+        if !ok {
+            break;
+        }
+        s.push(r.get().unwrap());
+    }
+    s
+}
+
+// the set of a %[ conversion: its bytes, a leading ^ for the complement
+// and ranges; a ] right after the [ or ^ is in the set
+fn scanset(fmt: &[u8]) -> (impl Fn(u8) -> bool, usize) {
+    let mut i = 0;
+    let negate = fmt.first() == Some(&b'^');
+    i += negate as usize;
+
+    let mut set = [false; 256];
+    let start = i;
+    while let Some(&c) = fmt.get(i) {
+        if c == b']' && i > start {
+            break;
+        }
+        match (fmt.get(i + 1), fmt.get(i + 2)) {
+            (Some(b'-'), Some(&end)) if end != b']' => {
+                for b in c..=end {
+                    set[b as usize] = true;
+                }
+                i += 3;
+            }
+            _ => {
+                set[c as usize] = true;
+                i += 1;
+            }
+        }
+    }
+
+    let n = (i + 1).min(fmt.len());
+    (move |c: u8| set[c as usize] != negate, n)
+}
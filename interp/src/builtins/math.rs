@@ -0,0 +1,50 @@
+//! The functions of math.h, those with the suffix f are rounded to float
+//! by the conversion of the result to the declared type.
+
+use super::{set_errno, Args, EDOM, ERANGE};
+use crate::value::Value;
+use crate::{Interpreter, Result};
+
+pub(super) fn call(it: &mut Interpreter, name: &str, args: Args) -> Option<Result<Value>> {
+    let name = name.strip_suffix('f').unwrap_or(name);
+    let (x, y) = (args.float(0), args.float(1));
+
+    let v = match name {
+        "sin" => x.sin(),
+        "cos" => x.cos(),
+        "tan" => x.tan(),
+        "asin" => x.asin(),
+        "acos" => x.acos(),
+        "atan" => x.atan(),
+        "atan2" => x.atan2(y),
+        "sinh" => x.sinh(),
+        "cosh" => x.cosh(),
+        "tanh" => x.tanh(),
+        "exp" => x.exp(),
+        "exp2" => x.exp2(),
+        "log" => x.ln(),
+        "log10" => x.log10(),
+        "log2" => x.log2(),
+        "pow" => x.powf(y),
+        "sqrt" => x.sqrt(),
+        "cbrt" => x.cbrt(),
+        "hypot" => x.hypot(y),
+        "ceil" => x.ceil(),
+        "floor" => x.floor(),
+        "round" => x.round(),
+        "trunc" => x.trunc(),
+        "fabs" => x.abs(),
+        "fmod" => x % y,
+        "fmin" => x.min(y),
+        "fmax" => x.max(y),
+        _ => return None,
+    };
+
+    // a domain error gives nan, a pole or an overflow infinity
+    if v.is_nan() && !x.is_nan() && !y.is_nan() {
+        set_errno(it, EDOM);
+    } else if v.is_infinite() && x.is_finite() && y.is_finite() {
+        set_errno(it, ERANGE);
+    }
+    Some(Ok(Value::Float(v)))
+}
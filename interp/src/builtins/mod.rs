@@ -0,0 +1,195 @@
+//! The library functions the interpreter provides itself, the subset of
+//! the C standard library declared by the bundled headers, see `include`.
+
+mod format;
+mod math;
+mod stdio;
+mod stdlib;
+mod string;
+
+use std::collections::HashMap;
+
+use sema::types::{IntKind, Type};
+
+use crate::eval::pointer;
+use crate::memory::Region;
+use crate::value::{Pointer, Value};
+use crate::{fault, Interpreter, Result, Stop};
+
+pub(crate) use stdio::Stream;
+
+/// The errno values the library sets.
+const EDOM: i128 = 33;
+const ERANGE: i128 = 34;
+
+/// The state of the library: the open streams, the objects of the globals
+/// it defines and the strings it returns.
+#[derive(Default)]
+pub(crate) struct Libc {
+    streams: HashMap<usize, Stream>, // by the allocation of their FILE
+    std: [Pointer; 3],               // stdin, stdout and stderr
+    input: Vec<u8>,                  // the line of stdin being read
+    globals: HashMap<String, Pointer>,
+    strings: HashMap<String, Pointer>, // of getenv and strerror
+    rand: Option<stdlib::Rand>,
+    strtok: Pointer,
+}
+
+/// The arguments of a call, a missing one is 0.
+#[derive(Clone, Copy)]
+struct Args<'v>(&'v [Value]);
+
+impl Args<'_> {
+    fn value(&self, i: usize) -> Value {
+        self.0.get(i).cloned().unwrap_or(Value::Int(0))
+    }
+
+    fn int(&self, i: usize) -> i128 {
+        self.value(i).as_int()
+    }
+
+    fn size(&self, i: usize) -> u64 {
+        self.int(i) as u64
+    }
+
+    fn float(&self, i: usize) -> f64 {
+        self.value(i).as_float()
+    }
+
+    fn ptr(&self, i: usize) -> Pointer {
+        pointer(self.value(i))
+    }
+
+    // the arguments from the i-th on, those of a variadic function
+    fn from(&self, i: usize) -> &[Value] {
+        self.0.get(i..).unwrap_or(&[])
+    }
+}
+
+/// Opens the standard streams, unless the program resumed did.
+pub(crate) fn init(it: &mut Interpreter) {
+    if it.libc.std[1] != Pointer::NULL {
+        return;
+    }
+    for (i, s) in [Stream::stdin(), Stream::stdout(), Stream::stderr()]
+        .into_iter()
+        .enumerate()
+    {
+        it.libc.std[i] = stdio::open(it, s);
+    }
+}
+
+/// Writes back the files the program did not close.
+pub(crate) fn finish(it: &mut Interpreter) {
+    stdio::flush_all(it);
+}
+
+/// Writes the output buffered so far, before foreign code writes its own.
+pub(crate) fn flush(it: &mut Interpreter) {
+    stdio::flush_all(it);
+}
+
+/// Returns the object of a global the library defines, None if there is
+/// no such global.
+pub(crate) fn global(it: &mut Interpreter, name: &str, pos: usize) -> Option<Result<Pointer>> {
+    if let Some(&p) = it.libc.globals.get(name) {
+        return Some(Ok(p));
+    }
+
+    let ptr = it.layout().pointer.size;
+    let p = match name {
+        "stdin" | "stdout" | "stderr" => {
+            let s = ["stdin", "stdout", "stderr"]
+                .iter()
+                .position(|&s| s == name)?;
+            it.alloc(ptr, ptr, Region::Global, pos).and_then(|p| {
+                let v = it.libc.std[s];
+                it.mem.write_pointer(p, v).map_err(fault(pos))?;
+                Ok(p)
+            })
+        }
+        "errno" => {
+            let int = Type::int(IntKind::Int);
+            it.alloc_object(&int, Region::Global, pos)
+        }
+        _ => return None,
+    };
+
+    if let Ok(p) = p {
+        it.libc.globals.insert(name.to_string(), p);
+    }
+    Some(p)
+}
+
+/// Calls the library function `name` returning `ret`, returns None if
+/// there is no such function.
+pub(crate) fn call(
+    it: &mut Interpreter,
+    name: &str,
+    args: &[Value],
+    ret: &Type,
+    pos: usize,
+) -> Option<Result<Value>> {
+    let args = Args(args);
+
+    let v = match name {
+        "exit" => Err(Stop::Exit(args.int(0) as i32)),
+        "abort" => Err(Stop::Exit(134)), // killed by SIGABRT
+        "assert" if args.int(0) == 0 => {
+            let text = it.info.asserts.get(&pos).map_or("", String::as_str);
+            let msg = format!("Assertion `{}' failed.\n", text);
+            let stderr = it.libc.std[2];
+            stdio::fputs(it, stderr, msg.as_bytes(), pos).and(Err(Stop::Exit(134)))
+        }
+        "assert" => Ok(Value::Void),
+
+        _ => {
+            return stdio::call(it, name, args, pos)
+                .or_else(|| stdlib::call(it, name, args, ret, pos))
+                .or_else(|| string::call(it, name, args, pos))
+                .or_else(|| math::call(it, name, args))
+        }
+    };
+
+    Some(v)
+}
+
+fn set_errno(it: &mut Interpreter, n: i128) {
+    if let Some(Ok(p)) = global(it, "errno", 0) {
+        let _ = it.mem.write(p, &(n as i32).to_le_bytes());
+    }
+}
+
+// allocates a global holding a string, the strings the library returns
+// are kept for the next calls
+fn string(it: &mut Interpreter, s: &str, pos: usize) -> Result<Pointer> {
+    if let Some(&p) = it.libc.strings.get(s) {
+        return Ok(p);
+    }
+
+    let mut bytes = s.as_bytes().to_vec();
+    bytes.push(0);
+    let p = it.alloc(bytes.len() as u64, 1, Region::Global, pos)?;
+    it.mem.write(p, &bytes).map_err(fault(pos))?;
+
+    it.libc.strings.insert(s.to_string(), p);
+    Ok(p)
+}
+
+// the null terminated string at `p`, without its terminator
+fn read_string(it: &Interpreter, p: Pointer, pos: usize) -> Result<Vec<u8>> {
+    it.mem.string(p).map_err(fault(pos))
+}
+
+// the bytes of a string and its terminator written at `p`
+fn write_string(it: &mut Interpreter, p: Pointer, s: &[u8], pos: usize) -> Result<()> {
+    it.mem.write(p, s).map_err(fault(pos))?;
+    it.mem
+        .write(p.offset(s.len() as i64), &[0])
+        .map_err(fault(pos))
+}
+
+// a pointer to the i-th byte of the object at `p`, null for None
+fn offset(p: Pointer, i: Option<usize>) -> Value {
+    Value::Pointer(i.map_or(Pointer::NULL, |i| p.offset(i as i64)))
+}
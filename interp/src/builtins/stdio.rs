@@ -0,0 +1,546 @@
+//! The streams of stdio.h. A FILE is an empty allocation, the stream it
+//! stands for is kept by the library. An open file is read into memory and
+//! written back when it is flushed or closed, or when the program ends.
+
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use super::format::{printf, scanf, Reader};
+use super::{read_string, set_errno, write_string, Args};
+use crate::memory::Region;
+use crate::value::{Pointer, Value};
+use crate::{error, fault, Interpreter, Result};
+
+const EOF: i128 = -1;
+const ESPIPE: i128 = 29;
+
+pub(crate) struct Stream {
+    kind: Kind,
+    read: bool,
+    write: bool,
+    append: bool,
+
+    data: Vec<u8>, // the contents of a file
+    pos: usize,
+    dirty: bool,
+
+    unget: Option<u8>,
+    eof: bool,
+    error: bool,
+}
+
+enum Kind {
+    Stdin,
+    Stdout,
+    Stderr,
+    File(PathBuf),
+}
+
+impl Stream {
+    pub(crate) fn stdin() -> Self {
+        Self::new(Kind::Stdin, true, false)
+    }
+
+    pub(crate) fn stdout() -> Self {
+        Self::new(Kind::Stdout, false, true)
+    }
+
+    pub(crate) fn stderr() -> Self {
+        Self::new(Kind::Stderr, false, true)
+    }
+
+    fn new(kind: Kind, read: bool, write: bool) -> Self {
+        Self {
+            kind,
+            read,
+            write,
+            append: false,
+            data: Vec::new(),
+            pos: 0,
+            dirty: false,
+            unget: None,
+            eof: false,
+            error: false,
+        }
+    }
+
+    // writes a file back to the file system
+    fn flush(&mut self) -> bool {
+        let Kind::File(path) = &self.kind else {
+            return true;
+        };
+        if self.dirty {
+            self.dirty = false;
+            return std::fs::write(path, &self.data).is_ok();
+        }
+        true
+    }
+}
+
+/// Opens a stream, its FILE is a new allocation.
+pub(super) fn open(it: &mut Interpreter, s: Stream) -> Pointer {
+    let p = it.mem.alloc(0, 1, Region::Global).unwrap();
+    it.libc.streams.insert(p.alloc, s);
+    p
+}
+
+pub(super) fn flush_all(it: &mut Interpreter) {
+    let _ = it.out.flush();
+    for s in it.libc.streams.values_mut() {
+        s.flush();
+    }
+}
+
+pub(super) fn call(
+    it: &mut Interpreter,
+    name: &str,
+    args: Args,
+    pos: usize,
+) -> Option<Result<Value>> {
+    let stdin = it.libc.std[0];
+    let stdout = it.libc.std[1];
+
+    let v = match name {
+        "fopen" => fopen(it, args.ptr(0), args.ptr(1), pos),
+        "fclose" => stream(it, args.ptr(0), pos).map(|id| {
+            let mut s = it.libc.streams.remove(&id).unwrap();
+            it.mem.free(id);
+            int(if s.flush() { 0 } else { EOF })
+        }),
+        "fflush" if args.ptr(0).addr == 0 => {
+            flush_all(it);
+            Ok(int(0))
+        }
+        "fflush" => stream(it, args.ptr(0), pos).map(|id| {
+            let _ = it.out.flush();
+            let ok = it.libc.streams.get_mut(&id).unwrap().flush();
+            int(if ok { 0 } else { EOF })
+        }),
+        "remove" => read_string(it, args.ptr(0), pos).map(|path| {
+            let path = String::from_utf8_lossy(&path).into_owned();
+            match std::fs::remove_file(path) {
+                Ok(()) => int(0),
+                Err(err) => {
+                    set_errno(it, err.raw_os_error().unwrap_or(0) as i128);
+                    int(-1)
+                }
+            }
+        }),
+
+        "printf" => fprintf(it, stdout, args.ptr(0), args.from(1), pos),
+        "fprintf" => fprintf(it, args.ptr(0), args.ptr(1), args.from(2), pos),
+        "sprintf" => sprintf(it, args.ptr(0), None, args.ptr(1), args.from(2), pos),
+        "snprintf" => sprintf(
+            it,
+            args.ptr(0),
+            Some(args.size(1)),
+            args.ptr(2),
+            args.from(3),
+            pos,
+        ),
+        "scanf" => fscanf(it, stdin, args.ptr(0), args.from(1), pos),
+        "fscanf" => fscanf(it, args.ptr(0), args.ptr(1), args.from(2), pos),
+        "sscanf" => sscanf(it, args.ptr(0), args.ptr(1), args.from(2), pos),
+
+        "getchar" => stream(it, stdin, pos).map(|id| char(getc(it, id))),
+        "getc" | "fgetc" => stream(it, args.ptr(0), pos).map(|id| char(getc(it, id))),
+        "fgets" => fgets(it, args.ptr(0), args.int(1), args.ptr(2), pos),
+        "ungetc" => stream(it, args.ptr(1), pos).map(|id| {
+            let c = args.int(0);
+            if c == EOF {
+                return int(EOF);
+            }
+            let s = it.libc.streams.get_mut(&id).unwrap();
+            s.unget = Some(c as u8);
+            s.eof = false;
+            int(c as u8 as i128)
+        }),
+        "putchar" => fputs(it, stdout, &[args.int(0) as u8], pos).map(|n| {
+            int(if n < 0 {
+                EOF
+            } else {
+                args.int(0) as u8 as i128
+            })
+        }),
+        "putc" | "fputc" => fputs(it, args.ptr(1), &[args.int(0) as u8], pos).map(|n| {
+            int(if n < 0 {
+                EOF
+            } else {
+                args.int(0) as u8 as i128
+            })
+        }),
+        "puts" => read_string(it, args.ptr(0), pos).and_then(|mut s| {
+            s.push(b'\n');
+            fputs(it, stdout, &s, pos).map(int)
+        }),
+        "fputs" => {
+            read_string(it, args.ptr(0), pos).and_then(|s| fputs(it, args.ptr(1), &s, pos).map(int))
+        }
+
+        "fread" => fread(
+            it,
+            args.ptr(0),
+            args.size(1),
+            args.size(2),
+            args.ptr(3),
+            pos,
+        ),
+        "fwrite" => {
+            let n = args.size(1).saturating_mul(args.size(2));
+            it.mem
+                .read(args.ptr(0), n as usize)
+                .map(|b| b.to_vec())
+                .map_err(fault(pos))
+                .and_then(|b| fputs(it, args.ptr(3), &b, pos))
+                .map(|n| match n {
+                    n if n < 0 => int(0),
+                    _ => int(args.size(2) as i128),
+                })
+        }
+
+        "fseek" => stream(it, args.ptr(0), pos).map(|id| {
+            let ok = seek(it, id, args.int(1), args.int(2));
+            int(if ok { 0 } else { -1 })
+        }),
+        "rewind" => stream(it, args.ptr(0), pos).map(|id| {
+            seek(it, id, 0, 0);
+            it.libc.streams.get_mut(&id).unwrap().error = false;
+            Value::Void
+        }),
+        "ftell" => stream(it, args.ptr(0), pos).map(|id| {
+            let s = &it.libc.streams[&id];
+            if let Kind::File(_) = s.kind {
+                return int(s.pos as i128 - s.unget.is_some() as i128);
+            }
+            set_errno(it, ESPIPE);
+            int(-1)
+        }),
+        "feof" => stream(it, args.ptr(0), pos).map(|id| int(it.libc.streams[&id].eof as i128)),
+        "ferror" => stream(it, args.ptr(0), pos).map(|id| int(it.libc.streams[&id].error as i128)),
+        "clearerr" => stream(it, args.ptr(0), pos).map(|id| {
+            let s = it.libc.streams.get_mut(&id).unwrap();
+            s.eof = false;
+            s.error = false;
+            Value::Void
+        }),
+        "perror" => perror(it, args.ptr(0), pos),
+
+        _ => return None,
+    };
+
+    Some(v)
+}
+
+fn int(n: impl Into<i128>) -> Value {
+    Value::Int(n.into())
+}
+
+// the result of getc
+fn char(c: Option<u8>) -> Value {
+    int(c.map_or(EOF, |c| c as i128))
+}
+
+// the stream of a FILE pointer
+fn stream(it: &Interpreter, f: Pointer, pos: usize) -> Result<usize> {
+    let id = match f.alloc {
+        0 => it.mem.find(f.addr).unwrap_or(0),
+        id => id,
+    };
+    if f.addr != 0 && it.libc.streams.contains_key(&id) && it.mem.get(id).base == f.addr {
+        return Ok(id);
+    }
+    error(pos, "invalid FILE pointer")
+}
+
+/// Reads a byte of a stream, None at the end of the input.
+pub(super) fn getc(it: &mut Interpreter, id: usize) -> Option<u8> {
+    let s = it.libc.streams.get_mut(&id)?;
+    if let Some(c) = s.unget.take() {
+        return Some(c);
+    }
+    if !s.read {
+        s.error = true;
+        return None;
+    }
+
+    if !matches!(s.kind, Kind::Stdin) {
+        let c = s.data.get(s.pos).copied();
+        s.pos += c.is_some() as usize;
+        s.eof |= c.is_none();
+        return c;
+    }
+
+    // the program may have written a prompt
+    if it.libc.input.is_empty() {
+        let _ = it.out.flush();
+        let mut line = Vec::new();
+        if it.input.read_until(b'\n', &mut line).is_err() {
+            it.libc.streams.get_mut(&id)?.error = true;
+        }
+        line.reverse();
+        it.libc.input = line;
+    }
+
+    let c = it.libc.input.pop();
+    it.libc.streams.get_mut(&id)?.eof |= c.is_none();
+    c
+}
+
+// gives a byte back to a stream
+fn ungetc(it: &mut Interpreter, id: usize, c: Option<u8>) {
+    if let (Some(c), Some(s)) = (c, it.libc.streams.get_mut(&id)) {
+        s.unget = Some(c);
+        s.eof = false;
+    }
+}
+
+// writes bytes to a stream, returns their number or EOF
+pub(super) fn fputs(it: &mut Interpreter, f: Pointer, bytes: &[u8], pos: usize) -> Result<i128> {
+    let id = stream(it, f, pos)?;
+    let s = it.libc.streams.get_mut(&id).unwrap();
+    if !s.write {
+        s.error = true;
+        return Ok(EOF);
+    }
+
+    let ok = match s.kind {
+        Kind::Stdout => it.out.write_all(bytes).is_ok(),
+        Kind::Stderr => {
+            let _ = it.out.flush();
+            std::io::stderr().write_all(bytes).is_ok()
+        }
+        // a file, stdin is not writable
+        _ => {
+            if s.append {
+                s.pos = s.data.len();
+            }
+            let end = s.pos + bytes.len();
+            if end > s.data.len() {
+                s.data.resize(end, 0);
+            }
+            s.data[s.pos..end].copy_from_slice(bytes);
+            s.pos = end;
+            s.unget = None;
+            s.dirty = true;
+            true
+        }
+    };
+
+    if !ok {
+        it.libc.streams.get_mut(&id).unwrap().error = true;
+        return Ok(EOF);
+    }
+    Ok(bytes.len() as i128)
+}
+
+fn fopen(it: &mut Interpreter, path: Pointer, mode: Pointer, pos: usize) -> Result<Value> {
+    let path = String::from_utf8_lossy(&read_string(it, path, pos)?).into_owned();
+    let mode = read_string(it, mode, pos)?;
+
+    let plus = mode.contains(&b'+');
+    let (read, write, append) = match mode.first() {
+        Some(b'r') => (true, plus, false),
+        Some(b'w') => (plus, true, false),
+        Some(b'a') => (plus, true, true),
+        _ => {
+            set_errno(it, 22); // EINVAL
+            return Ok(Value::Pointer(Pointer::NULL));
+        }
+    };
+
+    // "w" truncates the file, the others read it
+    let data = match mode[0] {
+        b'w' => std::fs::write(&path, []).map(|_| Vec::new()),
+        b'a' => match std::fs::read(&path) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                std::fs::write(&path, []).map(|_| Vec::new())
+            }
+            r => r,
+        },
+        _ => std::fs::read(&path),
+    };
+    let data = match data {
+        Ok(data) => data,
+        Err(err) => {
+            set_errno(it, err.raw_os_error().unwrap_or(0) as i128);
+            return Ok(Value::Pointer(Pointer::NULL));
+        }
+    };
+
+    let mut s = Stream::new(Kind::File(PathBuf::from(path)), read, write);
+    s.append = append;
+    s.data = data;
+    Ok(Value::Pointer(open(it, s)))
+}
+
+fn fprintf(
+    it: &mut Interpreter,
+    f: Pointer,
+    fmt: Pointer,
+    args: &[Value],
+    pos: usize,
+) -> Result<Value> {
+    let fmt = read_string(it, fmt, pos)?;
+    let out = printf(it, &fmt, args, pos)?;
+    let n = fputs(it, f, &out, pos)?;
+    Ok(int(if n < 0 { -1 } else { n }))
+}
+
+// prints to a string, at most n bytes with its terminator for snprintf;
+// returns the length of the whole output
+fn sprintf(
+    it: &mut Interpreter,
+    s: Pointer,
+    n: Option<u64>,
+    fmt: Pointer,
+    args: &[Value],
+    pos: usize,
+) -> Result<Value> {
+    let fmt = read_string(it, fmt, pos)?;
+    let out = printf(it, &fmt, args, pos)?;
+
+    match n {
+        None => write_string(it, s, &out, pos)?,
+        Some(0) => {}
+        Some(n) => {
+            let len = out.len().min(n as usize - 1);
+            write_string(it, s, &out[..len], pos)?;
+        }
+    }
+    Ok(int(out.len() as i128))
+}
+
+fn fscanf(
+    it: &mut Interpreter,
+    f: Pointer,
+    fmt: Pointer,
+    args: &[Value],
+    pos: usize,
+) -> Result<Value> {
+    let fmt = read_string(it, fmt, pos)?;
+    let id = stream(it, f, pos)?;
+
+    let mut r = Reader::new(|| getc(it, id));
+    let items = scanf(&fmt, &mut r);
+    let rest = r.rest();
+    ungetc(it, id, rest);
+
+    items.store(it, args, pos).map(int)
+}
+
+fn sscanf(
+    it: &mut Interpreter,
+    s: Pointer,
+    fmt: Pointer,
+    args: &[Value],
+    pos: usize,
+) -> Result<Value> {
+    let s = read_string(it, s, pos)?;
+    let fmt = read_string(it, fmt, pos)?;
+
+    let mut bytes = s.into_iter();
+    let items = scanf(&fmt, &mut Reader::new(|| bytes.next()));
+    items.store(it, args, pos).map(int)
+}
+
+// reads a line of at most n - 1 bytes
+fn fgets(it: &mut Interpreter, s: Pointer, n: i128, f: Pointer, pos: usize) -> Result<Value> {
+    let id = stream(it, f, pos)?;
+    if n <= 0 {
+        return Ok(Value::Pointer(Pointer::NULL));
+    }
+
+    let mut line = Vec::new();
+    while line.len() + 1 < n as usize {
+        let Some(c) = getc(it, id) else {
+            break;
+        };
+        line.push(c);
+        if c == b'\n' {
+            break;
+        }
+    }
+
+    if line.is_empty() && n > 1 {
+        return Ok(Value::Pointer(Pointer::NULL));
+    }
+    write_string(it, s, &line, pos)?;
+    Ok(Value::Pointer(s))
+}
+
+fn fread(
+    it: &mut Interpreter,
+    p: Pointer,
+    size: u64,
+    n: u64,
+    f: Pointer,
+    pos: usize,
+) -> Result<Value> {
+    let id = stream(it, f, pos)?;
+    let want = size.saturating_mul(n);
+
+    let mut bytes = Vec::new();
+    while (bytes.len() as u64) < want {
+        match getc(it, id) {
+            Some(c) => bytes.push(c),
+            None => break,
+        }
+    }
+
+    // only whole items are stored
+    let items = match size {
+        0 => 0,
+        size => bytes.len() as u64 / size,
+    };
+    it.mem
+        .write(p, &bytes[..(items * size) as usize])
+        .map_err(fault(pos))?;
+    Ok(int(items as i128))
+}
+
+// moves the position of a file, the standard streams cannot seek
+fn seek(it: &mut Interpreter, id: usize, offset: i128, whence: i128) -> bool {
+    let s = &it.libc.streams[&id];
+    if !matches!(s.kind, Kind::File(_)) {
+        set_errno(it, ESPIPE);
+        return false;
+    }
+
+    let base = match whence {
+        0 => 0,
+        1 => s.pos as i128 - s.unget.is_some() as i128,
+        2 => s.data.len() as i128,
+        _ => -1,
+    };
+    if base < 0 || base + offset < 0 {
+        set_errno(it, 22); // EINVAL
+        return false;
+    }
+
+    let s = it.libc.streams.get_mut(&id).unwrap();
+    s.pos = (base + offset) as usize;
+    s.unget = None;
+    s.eof = false;
+    true
+}
+
+fn perror(it: &mut Interpreter, s: Pointer, pos: usize) -> Result<Value> {
+    let errno = match it.libc.globals.get("errno") {
+        Some(&p) => it.load(p, &sema::types::Type::int(sema::types::IntKind::Int), pos)?,
+        None => int(0),
+    };
+
+    let mut msg = match s.addr {
+        0 => Vec::new(),
+        _ => read_string(it, s, pos)?,
+    };
+    if !msg.is_empty() {
+        msg.extend_from_slice(b": ");
+    }
+    msg.extend_from_slice(super::string::strerror(errno.as_int()).as_bytes());
+    msg.push(b'\n');
+
+    let stderr = it.libc.std[2];
+    fputs(it, stderr, &msg, pos)?;
+    Ok(Value::Void)
+}
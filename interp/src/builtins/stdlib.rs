@@ -0,0 +1,465 @@
+//! The functions of stdlib.h: the heap, conversions of strings to numbers,
+//! sorting and searching, and random numbers.
+
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use sema::types::{IntKind, Type, TypeKind};
+
+use super::{read_string, set_errno, string, Args, ERANGE};
+use crate::memory::{func_at, Region};
+use crate::value::{Pointer, Record, Value};
+use crate::{error, fault, Interpreter, Result};
+
+/// The largest block the heap hands out, malloc fails beyond it.
+const MAX_BLOCK: u64 = 1 << 32;
+
+pub(super) fn call(
+    it: &mut Interpreter,
+    name: &str,
+    args: Args,
+    ret: &Type,
+    pos: usize,
+) -> Option<Result<Value>> {
+    let v = match name {
+        "malloc" => malloc(it, args.size(0), pos),
+        "calloc" => match args.size(0).checked_mul(args.size(1)) {
+            Some(n) => malloc(it, n, pos),
+            None => Ok(Value::Pointer(Pointer::NULL)),
+        },
+        "realloc" => realloc(it, args.ptr(0), args.size(1), pos),
+        "free" => free(it, args.ptr(0), pos).map(|_| Value::Void),
+
+        "getenv" => read_string(it, args.ptr(0), pos).and_then(|name| {
+            let name = String::from_utf8_lossy(&name).into_owned();
+            match std::env::var(name) {
+                Ok(v) => string(it, &v, pos).map(Value::Pointer),
+                Err(_) => Ok(Value::Pointer(Pointer::NULL)),
+            }
+        }),
+
+        "atoi" => strtol(it, args.ptr(0), Pointer::NULL, 10, IntKind::Int, pos),
+        "atol" => strtol(it, args.ptr(0), Pointer::NULL, 10, IntKind::Long, pos),
+        "atoll" => strtol(it, args.ptr(0), Pointer::NULL, 10, IntKind::LongLong, pos),
+        "strtol" => strtol(
+            it,
+            args.ptr(0),
+            args.ptr(1),
+            args.int(2),
+            IntKind::Long,
+            pos,
+        ),
+        "strtoul" => strtol(
+            it,
+            args.ptr(0),
+            args.ptr(1),
+            args.int(2),
+            IntKind::ULong,
+            pos,
+        ),
+        "strtoll" => strtol(
+            it,
+            args.ptr(0),
+            args.ptr(1),
+            args.int(2),
+            IntKind::LongLong,
+            pos,
+        ),
+        "strtoull" => strtol(
+            it,
+            args.ptr(0),
+            args.ptr(1),
+            args.int(2),
+            IntKind::ULongLong,
+            pos,
+        ),
+        "atof" => strtod(it, args.ptr(0), Pointer::NULL, pos),
+        "strtod" | "strtof" => strtod(it, args.ptr(0), args.ptr(1), pos),
+
+        "abs" | "labs" | "llabs" => {
+            let k = ret.int_kind().unwrap_or(IntKind::Int);
+            Ok(Value::Int(it.layout().wrap(args.int(0).abs(), k)))
+        }
+        "div" | "ldiv" => div(it, args.int(0), args.int(1), ret, pos),
+
+        "rand" => {
+            let r = it.libc.rand.get_or_insert_with(|| Rand::new(1));
+            Ok(Value::Int(r.next() as i128))
+        }
+        "srand" => {
+            it.libc.rand = Some(Rand::new(args.int(0) as u32));
+            Ok(Value::Void)
+        }
+
+        "qsort" => qsort(it, args, pos).map(|_| Value::Void),
+        "bsearch" => bsearch(it, args, pos),
+
+        _ => return None,
+    };
+
+    Some(v)
+}
+
+// allocates a zeroed heap block, null if it is too large
+fn malloc(it: &mut Interpreter, n: u64, pos: usize) -> Result<Value> {
+    if n > MAX_BLOCK {
+        return Ok(Value::Pointer(Pointer::NULL));
+    }
+    it.alloc(n, 16, Region::Heap, pos).map(Value::Pointer)
+}
+
+fn realloc(it: &mut Interpreter, p: Pointer, n: u64, pos: usize) -> Result<Value> {
+    if p.addr == 0 {
+        return malloc(it, n, pos);
+    }
+    let id = block(it, p, pos)?;
+
+    let q = malloc(it, n, pos)?;
+    if let Value::Pointer(q) = q {
+        if q.addr != 0 {
+            let old = it.mem.get(id).size.min(n);
+            it.mem.copy(q, p, old as usize).map_err(fault(pos))?;
+            it.mem.free(id);
+        }
+    }
+    Ok(q)
+}
+
+fn free(it: &mut Interpreter, p: Pointer, pos: usize) -> Result<()> {
+    if p.addr == 0 {
+        return Ok(());
+    }
+    let id = block(it, p, pos)?;
+    it.mem.free(id);
+    Ok(())
+}
+
+// the heap block a pointer passed to free or realloc points to
+fn block(it: &Interpreter, p: Pointer, pos: usize) -> Result<usize> {
+    let id = match p.alloc {
+        0 => it.mem.find(p.addr).unwrap_or(0),
+        id => id,
+    };
+
+    let a = it.mem.get(id);
+    if id == 0 || a.region != Region::Heap || a.base != p.addr {
+        return error(pos, "free of a pointer that was not allocated by malloc");
+    }
+    if !a.live {
+        return error(pos, "double free");
+    }
+    Ok(id)
+}
+
+/// Parses an integer after white space as strtol does, base 0 takes the
+/// base from the prefix. Returns its sign, its magnitude saturated at
+/// u128::MAX and the length of the text, 0 if there are no digits.
+pub(super) fn parse_int(s: &[u8], base: u32) -> (bool, u128, usize) {
+    if base == 1 || base > 36 {
+        return (false, 0, 0);
+    }
+
+    let mut i = s.iter().take_while(|c| c.is_ascii_whitespace()).count();
+    let neg = s.get(i) == Some(&b'-');
+    if matches!(s.get(i), Some(b'+' | b'-')) {
+        i += 1;
+    }
+
+    let hex = s.get(i) == Some(&b'0')
+        && matches!(s.get(i + 1), Some(b'x' | b'X'))
+        && s.get(i + 2).is_some_and(u8::is_ascii_hexdigit);
+    let base = match base {
+        0 if hex => 16,
+        0 if s.get(i) == Some(&b'0') => 8,
+        0 => 10,
+        b => b,
+    };
+    if hex && base == 16 {
+        i += 2;
+    }
+
+    let start = i;
+    let mut v: u128 = 0;
+    while let Some(d) = s.get(i).and_then(|&c| (c as char).to_digit(base)) {
+        v = v.saturating_mul(base as u128).saturating_add(d as u128);
+        i += 1;
+    }
+
+    match i {
+        _ if i == start => (false, 0, 0),
+        _ => (neg, v, i),
+    }
+}
+
+/// Parses a floating constant after white space as strtod does, returns
+/// its value and the length of the text, 0 if there is none.
+pub(super) fn parse_float(s: &[u8]) -> (f64, usize) {
+    let start = s.iter().take_while(|c| c.is_ascii_whitespace()).count();
+    let mut i = start;
+    if matches!(s.get(i), Some(b'+' | b'-')) {
+        i += 1;
+    }
+
+    // inf, infinity and nan in any case
+    let word = |w: &[u8]| s.len() >= i + w.len() && s[i..i + w.len()].eq_ignore_ascii_case(w);
+    let special = if word(b"infinity") {
+        Some((f64::INFINITY, 8))
+    } else if word(b"inf") {
+        Some((f64::INFINITY, 3))
+    } else if word(b"nan") {
+        Some((f64::NAN, 3))
+    } else {
+        None
+    };
+    if let Some((f, n)) = special {
+        let f = if s[start] == b'-' { -f } else { f };
+        return (f, i + n);
+    }
+
+    let digits = |i: &mut usize| {
+        let n = s[*i..].iter().take_while(|c| c.is_ascii_digit()).count();
+        *i += n;
+        n
+    };
+    let mut n = digits(&mut i);
+    if s.get(i) == Some(&b'.') {
+        i += 1;
+        n += digits(&mut i);
+    }
+    if n == 0 {
+        return (0.0, 0);
+    }
+
+    // an exponent needs digits
+    if matches!(s.get(i), Some(b'e' | b'E')) {
+        let mut j = i + 1;
+        if matches!(s.get(j), Some(b'+' | b'-')) {
+            j += 1;
+        }
+        if digits(&mut j) > 0 {
+            i = j;
+        }
+    }
+
+    let text = std::str::from_utf8(&s[start..i]).unwrap();
+    let text = text.strip_suffix('.').unwrap_or(text);
+    (text.parse().unwrap_or(0.0), i)
+}
+
+// converts a string to an integer of kind k, the value saturates at the
+// limits of the type with errno ERANGE; *end points past the number
+fn strtol(
+    it: &mut Interpreter,
+    s: Pointer,
+    end: Pointer,
+    base: i128,
+    k: IntKind,
+    pos: usize,
+) -> Result<Value> {
+    let text = read_string(it, s, pos)?;
+    let (neg, v, n) = parse_int(&text, base.clamp(0, 37) as u32);
+
+    let dl = it.layout();
+    let (min, max) = (dl.min(k), dl.max(k));
+    let v = if dl.is_signed(k) {
+        match neg {
+            true if v > min.unsigned_abs() => None,
+            true => Some((v as i128).wrapping_neg()),
+            false if v > max => None,
+            false => Some(v as i128),
+        }
+    } else if v > max {
+        None
+    } else {
+        // the negation is in the unsigned type
+        Some(if neg {
+            dl.wrap(-(v as i128), k)
+        } else {
+            v as i128
+        })
+    };
+
+    let v = v.unwrap_or_else(|| {
+        set_errno(it, ERANGE);
+        if neg && dl.is_signed(k) {
+            min
+        } else {
+            max as i128
+        }
+    });
+
+    store_end(it, end, s, n, pos)?;
+    Ok(Value::Int(v))
+}
+
+fn strtod(it: &mut Interpreter, s: Pointer, end: Pointer, pos: usize) -> Result<Value> {
+    let text = read_string(it, s, pos)?;
+    let (f, n) = parse_float(&text);
+    if f.is_infinite() && !text[..n].iter().any(u8::is_ascii_alphabetic) {
+        set_errno(it, ERANGE);
+    }
+
+    store_end(it, end, s, n, pos)?;
+    Ok(Value::Float(f))
+}
+
+// stores the end of a number of n bytes at s through `end`, if it is not
+// null; there is no number if n is 0
+fn store_end(it: &mut Interpreter, end: Pointer, s: Pointer, n: usize, pos: usize) -> Result<()> {
+    if end.addr == 0 {
+        return Ok(());
+    }
+    let ptr = Type::int(IntKind::Char).pointer_to();
+    it.store(end, &ptr, Value::Pointer(s.offset(n as i64)), pos)
+}
+
+// the quotient and remainder of div and ldiv, in a div_t or an ldiv_t
+fn div(it: &mut Interpreter, x: i128, y: i128, ret: &Type, pos: usize) -> Result<Value> {
+    if y == 0 {
+        return error(pos, "division by zero");
+    }
+    let fields = match ret.kind {
+        TypeKind::Struct(id) => it.member(id, "quot").zip(it.member(id, "rem")),
+        _ => None,
+    };
+    let Some((quot, rem)) = fields else {
+        return error(pos, format!("invalid type '{}'", it.info.spell(ret)));
+    };
+
+    let size = it.size_of(ret, pos)?;
+    let k = quot.ty.int_kind().unwrap_or(IntKind::Int);
+    let width = it.size_of(&quot.ty, pos)? as usize;
+
+    let mut bytes = vec![0; size as usize];
+    for (f, v) in [(quot, x / y), (rem, x % y)] {
+        let v = it.layout().wrap(v, k).to_le_bytes();
+        let at = (f.offset / 8) as usize;
+        bytes[at..at + width].copy_from_slice(&v[..width]);
+    }
+
+    Ok(Value::Record(Rc::new(Record {
+        bytes,
+        pointers: Vec::new(),
+        uninit: Vec::new(),
+    })))
+}
+
+// the function a comparison function argument points to
+fn compare(
+    it: &mut Interpreter,
+    cmp: &Value,
+    a: Pointer,
+    b: Pointer,
+    pos: usize,
+) -> Result<Ordering> {
+    let id = match cmp {
+        Value::Func(id) => Some(*id),
+        v => func_at(v.as_int() as u64),
+    };
+    let Some(id) = id else {
+        return error(pos, "call through a pointer that is not a function");
+    };
+
+    let v = it.call(id, vec![Value::Pointer(a), Value::Pointer(b)], pos)?;
+    Ok(v.as_int().cmp(&0))
+}
+
+// sorts with a merge sort, it is stable and calls the comparison function
+// with pointers into the array; the elements are moved at the end
+fn qsort(it: &mut Interpreter, args: Args, pos: usize) -> Result<()> {
+    let (base, n, size, cmp) = (args.ptr(0), args.size(1), args.size(2), args.value(3));
+    let elem = |i: usize| base.offset((i as u64 * size) as i64);
+    if n < 2 || size == 0 {
+        return Ok(());
+    }
+
+    let mut order: Vec<usize> = (0..n as usize).collect();
+    let mut tmp = order.clone();
+    let mut width = 1;
+    while width < order.len() {
+        for lo in (0..order.len()).step_by(2 * width) {
+            let mid = (lo + width).min(order.len());
+            let hi = (lo + 2 * width).min(order.len());
+            let (mut i, mut j) = (lo, mid);
+            for t in tmp[lo..hi].iter_mut() {
+                let left = i < mid
+                    && (j >= hi
+                        || compare(it, &cmp, elem(order[i]), elem(order[j]), pos)?
+                            != Ordering::Greater);
+                if left {
+                    *t = order[i];
+                    i += 1;
+                } else {
+                    *t = order[j];
+                    j += 1;
+                }
+            }
+        }
+        std::mem::swap(&mut order, &mut tmp);
+        width *= 2;
+    }
+
+    let elems = order
+        .iter()
+        .map(|&i| it.mem.snapshot(elem(i), size as usize))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(fault(pos))?;
+    for (i, r) in elems.iter().enumerate() {
+        it.mem.restore(elem(i), r).map_err(fault(pos))?;
+    }
+    Ok(())
+}
+
+fn bsearch(it: &mut Interpreter, args: Args, pos: usize) -> Result<Value> {
+    let (key, base, size, cmp) = (args.ptr(0), args.ptr(1), args.size(3), args.value(4));
+    let (mut lo, mut hi) = (0, args.size(2));
+
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        let p = base.offset((mid * size) as i64);
+        match compare(it, &cmp, key, p, pos)? {
+            Ordering::Less => hi = mid,
+            Ordering::Greater => lo = mid + 1,
+            Ordering::Equal => return Ok(Value::Pointer(p)),
+        }
+    }
+    Ok(Value::Pointer(Pointer::NULL))
+}
+
+/// The random numbers of rand, the additive feedback generator of glibc:
+/// a seed gives the same sequence it does.
+pub(super) struct Rand {
+    r: [u32; 34], // the last 34 values, by index modulo 34
+    i: usize,
+}
+
+impl Rand {
+    pub(super) fn new(seed: u32) -> Self {
+        let mut r = [0u32; 34];
+        r[0] = if seed == 0 { 1 } else { seed };
+        for i in 1..31 {
+            let v = (16807 * r[i - 1] as i32 as i64) % 2147483647;
+            r[i] = if v < 0 { v + 2147483647 } else { v } as u32;
+        }
+        for i in 31..34 {
+            r[i] = r[i - 31];
+        }
+
+        let mut rand = Self { r, i: 34 };
+        for _ in 34..344 {
+            rand.step();
+        }
+        rand
+    }
+
+    fn step(&mut self) -> u32 {
+        let v = self.r[(self.i - 31) % 34].wrapping_add(self.r[(self.i - 3) % 34]);
+        self.r[self.i % 34] = v;
+        self.i += 1;
+        v
+    }
+
+    pub(super) fn next(&mut self) -> u32 {
+        self.step() >> 1
+    }
+}
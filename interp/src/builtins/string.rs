@@ -0,0 +1,189 @@
+//! The functions of string.h and ctype.h.
+
+use super::{offset, read_string, string, write_string, Args};
+use crate::value::{Pointer, Value};
+use crate::{fault, Interpreter, Result};
+
+pub(super) fn call(
+    it: &mut Interpreter,
+    name: &str,
+    args: Args,
+    pos: usize,
+) -> Option<Result<Value>> {
+    if let Some(v) = ctype(name, args.int(0)) {
+        return Some(Ok(Value::Int(v)));
+    }
+
+    let (a, b) = (args.ptr(0), args.ptr(1));
+    let v = match name {
+        // the ranges of memcpy may overlap like those of memmove
+        "memcpy" | "memmove" => it
+            .mem
+            .copy(a, b, args.size(2) as usize)
+            .map(|_| Value::Pointer(a))
+            .map_err(fault(pos)),
+        "memset" => it
+            .mem
+            .fill(a, args.int(1) as u8, args.size(2) as usize)
+            .map(|_| Value::Pointer(a))
+            .map_err(fault(pos)),
+        "memcmp" => bytes(it, a, args.size(2), pos).and_then(|x| {
+            let y = bytes(it, b, args.size(2), pos)?;
+            Ok(Value::Int(compare(&x, &y)))
+        }),
+        "memchr" => bytes(it, a, args.size(2), pos).map(|s| {
+            let c = args.int(1) as u8;
+            offset(a, s.iter().position(|&b| b == c))
+        }),
+
+        "strlen" => read_string(it, a, pos).map(|s| Value::Int(s.len() as i128)),
+        "strcpy" => read_string(it, b, pos)
+            .and_then(|s| write_string(it, a, &s, pos))
+            .map(|_| Value::Pointer(a)),
+        "strncpy" => read_string(it, b, pos).and_then(|mut s| {
+            // the rest of the n bytes are zeros, zeroing them first checks
+            // the bounds before the string is copied
+            let n = args.size(2) as usize;
+            it.mem.fill(a, 0, n).map_err(fault(pos))?;
+            s.truncate(n);
+            it.mem.write(a, &s).map_err(fault(pos))?;
+            Ok(Value::Pointer(a))
+        }),
+        "strcat" | "strncat" => read_string(it, b, pos).and_then(|mut s| {
+            if name == "strncat" {
+                s.truncate(args.size(2) as usize);
+            }
+            let end = read_string(it, a, pos)?.len();
+            write_string(it, a.offset(end as i64), &s, pos)?;
+            Ok(Value::Pointer(a))
+        }),
+        "strcmp" => read_string(it, a, pos).and_then(|x| {
+            let y = read_string(it, b, pos)?;
+            Ok(Value::Int(compare(&x, &y)))
+        }),
+        "strncmp" => read_string(it, a, pos).and_then(|mut x| {
+            let mut y = read_string(it, b, pos)?;
+            x.truncate(args.size(2) as usize);
+            y.truncate(args.size(2) as usize);
+            Ok(Value::Int(compare(&x, &y)))
+        }),
+
+        // the terminator is part of the string
+        "strchr" | "strrchr" => read_string(it, a, pos).map(|mut s| {
+            s.push(0);
+            let c = args.int(1) as u8;
+            let i = match name {
+                "strchr" => s.iter().position(|&b| b == c),
+                _ => s.iter().rposition(|&b| b == c),
+            };
+            offset(a, i)
+        }),
+        "strstr" => read_string(it, a, pos).and_then(|s| {
+            let sub = read_string(it, b, pos)?;
+            let i = (0..=s.len().saturating_sub(sub.len())).find(|&i| s[i..].starts_with(&sub));
+            Ok(offset(a, i.filter(|&i| i + sub.len() <= s.len())))
+        }),
+        "strspn" | "strcspn" | "strpbrk" => read_string(it, a, pos).and_then(|s| {
+            let set = read_string(it, b, pos)?;
+            let accept = name == "strspn";
+            let n = s
+                .iter()
+                .position(|c| set.contains(c) != accept)
+                .unwrap_or(s.len());
+            Ok(match name {
+                "strpbrk" => offset(a, Some(n).filter(|&n| n < s.len())),
+                _ => Value::Int(n as i128),
+            })
+        }),
+        "strtok" => strtok(it, a, b, pos),
+        "strdup" => read_string(it, a, pos).and_then(|s| {
+            let p = it.alloc(s.len() as u64 + 1, 16, crate::memory::Region::Heap, pos)?;
+            write_string(it, p, &s, pos)?;
+            Ok(Value::Pointer(p))
+        }),
+        "strerror" => string(it, &strerror(args.int(0)), pos).map(Value::Pointer),
+
+        _ => return None,
+    };
+
+    Some(v)
+}
+
+// the n bytes at `p`
+fn bytes(it: &Interpreter, p: Pointer, n: u64, pos: usize) -> Result<Vec<u8>> {
+    match n {
+        0 => Ok(Vec::new()),
+        n => Ok(it.mem.read(p, n as usize).map_err(fault(pos))?.to_vec()),
+    }
+}
+
+// compares as unsigned char, the result is the difference of the first
+// bytes that differ
+fn compare(x: &[u8], y: &[u8]) -> i128 {
+    let i = x.iter().zip(y).position(|(a, b)| a != b);
+    let i = i.unwrap_or(x.len().min(y.len()));
+    let (a, b) = (x.get(i).copied(), y.get(i).copied());
+    a.unwrap_or(0) as i128 - b.unwrap_or(0) as i128
+}
+
+// splits a string at the bytes of `delim`, a null string continues the
+// previous one
+fn strtok(it: &mut Interpreter, s: Pointer, delim: Pointer, pos: usize) -> Result<Value> {
+    let s = if s.addr == 0 { it.libc.strtok } else { s };
+    if s.addr == 0 {
+        return Ok(Value::Pointer(Pointer::NULL));
+    }
+
+    let text = read_string(it, s, pos)?;
+    let delim = read_string(it, delim, pos)?;
+
+    let Some(start) = text.iter().position(|c| !delim.contains(c)) else {
+        it.libc.strtok = Pointer::NULL;
+        return Ok(Value::Pointer(Pointer::NULL));
+    };
+    let token = s.offset(start as i64);
+
+    match text[start..].iter().position(|c| delim.contains(c)) {
+        Some(n) => {
+            let end = token.offset(n as i64);
+            it.mem.write(end, &[0]).map_err(fault(pos))?;
+            it.libc.strtok = end.offset(1);
+        }
+        None => it.libc.strtok = Pointer::NULL,
+    }
+    Ok(Value::Pointer(token))
+}
+
+/// The message of an errno value.
+pub(super) fn strerror(n: i128) -> String {
+    let msg = std::io::Error::from_raw_os_error(n as i32).to_string();
+    match msg.find(" (os error") {
+        Some(i) => msg[..i].to_string(),
+        None => msg,
+    }
+}
+
+// the functions of ctype.h, in the C locale; EOF is in no class
+fn ctype(name: &str, c: i128) -> Option<i128> {
+    let b = u8::try_from(c).ok().filter(u8::is_ascii);
+    let is = |f: fn(&u8) -> bool| Some(b.is_some_and(|b| f(&b)) as i128);
+
+    match name {
+        "isalnum" => is(u8::is_ascii_alphanumeric),
+        "isalpha" => is(u8::is_ascii_alphabetic),
+        "isblank" => is(|&b| b == b' ' || b == b'\t'),
+        "iscntrl" => is(u8::is_ascii_control),
+        "isdigit" => is(u8::is_ascii_digit),
+        "isgraph" => is(u8::is_ascii_graphic),
+        "islower" => is(u8::is_ascii_lowercase),
+        "isprint" => is(|&b| b == b' ' || b.is_ascii_graphic()),
+        "ispunct" => is(u8::is_ascii_punctuation),
+        // \v is white space in C
+        "isspace" => is(|&b| b.is_ascii_whitespace() || b == 0x0b),
+        "isupper" => is(u8::is_ascii_uppercase),
+        "isxdigit" => is(u8::is_ascii_hexdigit),
+        "tolower" => Some(b.map_or(c, |b| b.to_ascii_lowercase() as i128)),
+        "toupper" => Some(b.map_or(c, |b| b.to_ascii_uppercase() as i128)),
+        _ => None,
+    }
+}
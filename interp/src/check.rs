@@ -0,0 +1,203 @@
+//! The checks of checked mode: an operation whose behavior C leaves
+//! undefined stops the program instead of doing what the hardware would.
+//! Out of bounds accesses, accesses to dead objects, double frees, writes
+//! to string literals and divisions by zero are errors in any mode.
+
+use sema::types::{IntKind, Type, TypeKind};
+use token::Token;
+
+use crate::eval::Place;
+use crate::{error, fault, Interpreter, Result};
+
+impl<'a> Interpreter<'a> {
+    /// Checks a load through an lvalue of type `ty`: its alignment, that
+    /// its bytes were written and that the value stored there may be read
+    /// through that type.
+    pub(crate) fn check_load(&self, p: Place, ty: &Type, pos: usize) -> Result<()> {
+        self.check_align(p, ty, "load", pos)?;
+        if ty.is_record() {
+            return Ok(()); // records are copied with their padding
+        }
+
+        let size = self.size_of(ty, pos)?;
+        self.mem
+            .check_init(p.ptr, size as usize)
+            .map_err(fault(pos))?;
+
+        // type punning through a union is allowed
+        if p.bits.is_some() || p.punned {
+            return Ok(());
+        }
+        match self.mem.stored_type(p.ptr, size) {
+            Some(stored) if !may_alias(ty, stored) => error(
+                pos,
+                format!(
+                    "strict aliasing violation: load of type '{}' from an object of type '{}'",
+                    self.info.spell(ty),
+                    self.info.spell(stored)
+                ),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the alignment of a store through an lvalue of type `ty`.
+    pub(crate) fn check_store(&self, p: Place, ty: &Type, pos: usize) -> Result<()> {
+        self.check_align(p, ty, "store", pos)
+    }
+
+    /// Records the type of the value just stored, character types leave
+    /// the bytes without one.
+    pub(crate) fn stored(&mut self, p: Place, ty: &Type, pos: usize) -> Result<()> {
+        if p.bits.is_none() && alias_key(ty).is_some() {
+            let size = self.size_of(ty, pos)?;
+            self.mem.set_stored_type(p.ptr, ty.unqualified(), size);
+        }
+        Ok(())
+    }
+
+    fn check_align(&self, p: Place, ty: &Type, access: &str, pos: usize) -> Result<()> {
+        let align = self.align_of(ty);
+        if p.ptr.addr.is_multiple_of(align) {
+            return Ok(());
+        }
+        error(
+            pos,
+            format!(
+                "misaligned {} of address {:#x} for type '{}', which requires {} byte alignment",
+                access,
+                p.ptr.addr,
+                self.info.spell(ty),
+                align
+            ),
+        )
+    }
+
+    /// Checks that the result `v` of `x op y` in the type `t` of a binary
+    /// operator is representable, signed integers do not wrap.
+    pub(crate) fn check_arith(
+        &self,
+        op: Token,
+        x: i128,
+        y: i128,
+        v: i128,
+        t: &Type,
+        pos: usize,
+    ) -> Result<()> {
+        let dl = self.layout();
+        let k = t.int_kind().unwrap_or(IntKind::Int);
+        if !dl.is_signed(k) {
+            return Ok(());
+        }
+
+        match op {
+            Token::SLASH | Token::REM if x == dl.min(k) && y == -1 => error(
+                pos,
+                format!(
+                    "division of {} by -1 cannot be represented in type '{}'",
+                    x,
+                    self.info.spell(t)
+                ),
+            ),
+            Token::PLUS | Token::MINUS | Token::ASTERISK if dl.wrap(v, k) != v => error(
+                pos,
+                format!(
+                    "signed integer overflow: {} {} {} cannot be represented in type '{}'",
+                    x,
+                    op.to_str(),
+                    y,
+                    self.info.spell(t)
+                ),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the operands of a shift of `x` of the promoted type `t` by
+    /// `n`.
+    pub(crate) fn check_shift(
+        &self,
+        op: Token,
+        x: i128,
+        n: i128,
+        t: &Type,
+        pos: usize,
+    ) -> Result<()> {
+        let dl = self.layout();
+        let k = t.int_kind().unwrap_or(IntKind::Int);
+        let width = dl.width(k) as i128;
+
+        if n < 0 {
+            return error(pos, format!("shift exponent {} is negative", n));
+        }
+        if n >= width {
+            return error(
+                pos,
+                format!(
+                    "shift exponent {} is too large for {}-bit type '{}'",
+                    n,
+                    width,
+                    self.info.spell(t)
+                ),
+            );
+        }
+
+        if op == Token::SHL && dl.is_signed(k) {
+            if x < 0 {
+                return error(pos, format!("left shift of negative value {}", x));
+            }
+            if dl.wrap(x << n, k) != x << n {
+                return error(
+                    pos,
+                    format!(
+                        "left shift of {} by {} places cannot be represented in type '{}'",
+                        x,
+                        n,
+                        self.info.spell(t)
+                    ),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the negation of `x` of type `t`.
+    pub(crate) fn check_neg(&self, x: i128, t: &Type, pos: usize) -> Result<()> {
+        let dl = self.layout();
+        match t.int_kind() {
+            Some(k) if dl.is_signed(k) && x == dl.min(k) => error(
+                pos,
+                format!(
+                    "negation of {} cannot be represented in type '{}'",
+                    x,
+                    self.info.spell(t)
+                ),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+// reports whether an object holding a value of type `stored` may be read
+// through an lvalue of type `ty` (C11 6.5p7): the types agree up to
+// signedness and qualifiers, character types read anything
+fn may_alias(ty: &Type, stored: &Type) -> bool {
+    match (alias_key(ty), alias_key(stored)) {
+        (Some(a), Some(b)) => a == b,
+        _ => true,
+    }
+}
+
+// the scalar types that may alias each other have the same key, character
+// types have none; pointers all have the same one
+fn alias_key(ty: &Type) -> Option<u8> {
+    match ty.kind {
+        TypeKind::Int(IntKind::Char | IntKind::SChar | IntKind::UChar) => None,
+        TypeKind::Int(k) => Some(k.to_unsigned() as u8),
+        TypeKind::Enum(_) => Some(IntKind::UInt as u8),
+        TypeKind::Float(k) => Some(16 + k as u8),
+        TypeKind::Pointer(_) => Some(32),
+        _ => None,
+    }
+}
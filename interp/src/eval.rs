@@ -0,0 +1,879 @@
+//! Expressions: values, lvalues and the arithmetic of the operators, with
+//! the conversions of the usual arithmetic conversions.
+
+use ast::{Designator, Expr, ExprKind, InitList, SizeofArg};
+use sema::consts::{parse_float, string_units, Eval};
+use sema::layout::FieldLayout;
+use sema::types::{usual_arithmetic, ArrayLen, IntKind, Type, TypeKind};
+use sema::{SymbolId, SymbolKind};
+use token::Token;
+
+use crate::builtins;
+use crate::memory::{le, Region};
+use crate::value::{round, Pointer, Value};
+use crate::{error, fault, Frame, Interpreter, Result};
+
+/// An lvalue: the object at `ptr`, or a bit-field in the storage unit at
+/// `ptr`, `bits` is its shift and width. `punned` marks the members of
+/// unions, they may be read as a type other than the one stored.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Place {
+    pub ptr: Pointer,
+    pub bits: Option<(u32, u32)>,
+    pub punned: bool,
+}
+
+impl From<Pointer> for Place {
+    fn from(ptr: Pointer) -> Self {
+        Self {
+            ptr,
+            bits: None,
+            punned: false,
+        }
+    }
+}
+
+// the position of an initializer in the object of a braced list
+struct Cursor {
+    ty: Type,
+    place: Place,
+    index: usize, // the next element or member
+}
+
+impl<'a> Interpreter<'a> {
+    pub(crate) fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("no frame")
+    }
+
+    /// Evaluates an expression to its value, arrays decay to a pointer to
+    /// their first element and functions to a pointer to them.
+    pub(crate) fn eval(&mut self, x: &'a dyn Expr) -> Result<Value> {
+        match x.kind() {
+            ExprKind::BasicLit(b) => match b.tok {
+                Token::INTEGER | Token::CHARACTER => match Eval::new(self.info).int(x) {
+                    Ok(v) => Ok(Value::Int(v)),
+                    Err(n) => error(n.pos, n.msg),
+                },
+                Token::FLOATING => {
+                    let f = parse_float(&b.lit).unwrap_or_default();
+                    match self.type_of(x).kind {
+                        TypeKind::Float(k) => Ok(Value::Float(round(f, k))),
+                        _ => Ok(Value::Float(f)),
+                    }
+                }
+                _ => self.address(x).map(Value::Pointer),
+            },
+
+            ExprKind::Ident(id) => match self.info.lookup(id.pos) {
+                Some(s) if s.kind == SymbolKind::EnumConst => Ok(Value::Int(s.value.unwrap_or(0))),
+                Some(s) if s.kind == SymbolKind::Func => match self.info.uses.get(&id.pos) {
+                    Some(&f) => Ok(Value::Func(f)),
+                    None => error(id.pos, format!("undeclared identifier '{}'", id.name)),
+                },
+                _ => self.rvalue(x),
+            },
+
+            ExprKind::ParenExpr(p) => self.eval(p.x.as_ref()),
+
+            ExprKind::UnaryExpr(u) => match u.op {
+                Token::AND => match self.type_of(u.x.as_ref()).kind {
+                    TypeKind::Function(_) => self.eval(u.x.as_ref()),
+                    _ => self.address(u.x.as_ref()).map(Value::Pointer),
+                },
+                Token::INC | Token::DEC => self.step(u.op, u.x.as_ref(), true),
+                Token::NOT | Token::BANG => {
+                    let v = self.eval(u.x.as_ref())?;
+                    Ok(Value::Int(!v.is_true() as i128))
+                }
+                op => {
+                    let ty = self.type_of(x);
+                    let v = self.eval(u.x.as_ref())?.convert(&ty, self.layout());
+
+                    Ok(match (op, v) {
+                        (Token::MINUS, Value::Float(f)) => Value::Float(-f),
+                        (Token::MINUS, v) => {
+                            if self.checked {
+                                self.check_neg(v.as_int(), &ty, u.op_pos)?;
+                            }
+                            Value::Int(-v.as_int()).convert(&ty, self.layout())
+                        }
+                        (Token::TILDE, v) => Value::Int(!v.as_int()).convert(&ty, self.layout()),
+                        (_, v) => v,
+                    })
+                }
+            },
+
+            ExprKind::StarExpr(s) => match self.type_of(x).kind {
+                TypeKind::Function(_) => self.eval(s.x.as_ref()),
+                _ => self.rvalue(x),
+            },
+            ExprKind::IndexExpr(_) | ExprKind::CompositeLit(_) | ExprKind::SelectorExpr(_) => {
+                self.rvalue(x)
+            }
+
+            ExprKind::PostfixExpr(p) => self.step(p.op, p.x.as_ref(), false),
+
+            ExprKind::InfixExpr(e) => match e.op {
+                Token::COMMA => {
+                    self.eval(e.x.as_ref())?;
+                    self.eval(e.y.as_ref())
+                }
+                Token::LAND => {
+                    let v =
+                        self.eval(e.x.as_ref())?.is_true() && self.eval(e.y.as_ref())?.is_true();
+                    Ok(Value::Int(v as i128))
+                }
+                Token::LOR => {
+                    let v =
+                        self.eval(e.x.as_ref())?.is_true() || self.eval(e.y.as_ref())?.is_true();
+                    Ok(Value::Int(v as i128))
+                }
+                op => {
+                    let a = self.eval(e.x.as_ref())?;
+                    let b = self.eval(e.y.as_ref())?;
+                    let (ta, tb) = (self.type_of(e.x.as_ref()), self.type_of(e.y.as_ref()));
+                    self.binary(op, e.op_pos, a, &ta, b, &tb)
+                }
+            },
+
+            ExprKind::AssignExpr(e) => {
+                let p = self.place(e.x.as_ref())?;
+                let ty = self.type_of(e.x.as_ref()).unqualified();
+                let b = self.eval(e.y.as_ref())?;
+
+                let v = match e.op.binary_op() {
+                    Token::ASSIGN => b,
+                    op => {
+                        let a = self.load_place(p, &ty, e.x.start())?;
+                        let tb = self.type_of(e.y.as_ref());
+                        self.binary(op, e.op_pos, a, &ty, b, &tb)?
+                    }
+                };
+
+                let v = v.convert(&ty, self.layout());
+                self.store_place(p, &ty, v.clone(), e.x.start())?;
+                match p.bits {
+                    // the value of a bit-field is truncated to its width
+                    Some(_) => self.load_place(p, &ty, e.x.start()),
+                    None => Ok(v),
+                }
+            }
+
+            ExprKind::CondExpr(e) => {
+                let v = if self.eval(e.cond.as_ref())?.is_true() {
+                    self.eval(e.x.as_ref())?
+                } else {
+                    self.eval(e.y.as_ref())?
+                };
+                Ok(v.convert(&self.type_of(x).decay(), self.layout()))
+            }
+
+            ExprKind::CallExpr(c) => {
+                let f = self.eval(c.fun.as_ref())?;
+                let Value::Func(id) = f else {
+                    return error(
+                        c.fun.start(),
+                        "call through a pointer that is not a function",
+                    );
+                };
+
+                // the arguments convert to the types of the parameters of a
+                // prototype, the others are promoted
+                let ty = self.type_of(c.fun.as_ref());
+                let func = ty.func().or_else(|| ty.pointee().and_then(Type::func));
+                let params = func.filter(|f| f.prototype).map_or(&[][..], |f| &f.params);
+
+                let mut args = Vec::new();
+                for (i, a) in c.args.iter().enumerate() {
+                    let v = self.eval(a.as_ref())?;
+                    let ty = match params.get(i) {
+                        Some(t) => t.clone(),
+                        None => self.type_of(a.as_ref()).promote_arg(),
+                    };
+                    args.push(v.convert(&ty, self.layout()));
+                }
+
+                self.call(id, args, c.fun.start())
+            }
+
+            ExprKind::CastExpr(c) => {
+                let v = self.eval(c.x.as_ref())?;
+                Ok(v.convert(&self.type_of(x), self.layout()))
+            }
+
+            ExprKind::SizeofExpr(s) => match Eval::new(self.info).int(x) {
+                Ok(v) => Ok(Value::Int(v)),
+
+                // the size of a variable length array object is what is
+                // left of its allocation
+                Err(n) => match &s.arg {
+                    SizeofArg::Expr(arg) => {
+                        let p = self.address(arg.as_ref())?;
+                        let id = match p.alloc {
+                            0 => self.mem.find(p.addr).unwrap_or(0),
+                            id => id,
+                        };
+                        let a = self.mem.get(id);
+                        Ok(Value::Int((a.base + a.size).saturating_sub(p.addr) as i128))
+                    }
+                    SizeofArg::Type(_) => error(n.pos, n.msg),
+                },
+            },
+
+            ExprKind::OffsetofExpr(_) => match Eval::new(self.info).int(x) {
+                Ok(v) => Ok(Value::Int(v)),
+                Err(n) => error(n.pos, n.msg),
+            },
+
+            ExprKind::InitList(l) => error(l.lbrace, "unexpected initializer list"),
+        }
+    }
+
+    // loads the value of an lvalue, an array decays to a pointer
+    fn rvalue(&mut self, x: &'a dyn Expr) -> Result<Value> {
+        let p = self.place(x)?;
+        let ty = self.type_of(x);
+        match ty.kind {
+            TypeKind::Array(..) => Ok(Value::Pointer(p.ptr)),
+            _ => self.load_place(p, &ty, x.start()),
+        }
+    }
+
+    /// Evaluates an lvalue to the address of the object it designates.
+    pub(crate) fn address(&mut self, x: &'a dyn Expr) -> Result<Pointer> {
+        self.place(x).map(|p| p.ptr)
+    }
+
+    /// Evaluates an lvalue to the object it designates.
+    pub(crate) fn place(&mut self, x: &'a dyn Expr) -> Result<Place> {
+        match x.kind() {
+            ExprKind::ParenExpr(p) => self.place(p.x.as_ref()),
+
+            ExprKind::Ident(id) => match self.info.uses.get(&id.pos) {
+                Some(&sym) => self.var(sym, id.pos).map(Place::from),
+                None => error(id.pos, format!("undeclared identifier '{}'", id.name)),
+            },
+
+            ExprKind::StarExpr(s) => {
+                let v = self.eval(s.x.as_ref())?;
+                Ok(pointer(v).into())
+            }
+
+            ExprKind::IndexExpr(e) => {
+                let a = self.eval(e.x.as_ref())?;
+                let b = self.eval(e.index.as_ref())?;
+                let (p, i) = match (a, b) {
+                    (Value::Pointer(p), i) | (i, Value::Pointer(p)) => (p, i.as_int()),
+                    (a, i) => (pointer(a), i.as_int()),
+                };
+
+                let size = self.size_of(&self.type_of(x), e.lbrack)?;
+                Ok(p.offset(i as i64 * size as i64).into())
+            }
+
+            ExprKind::SelectorExpr(s) => {
+                let (base, ty) = if s.op == Token::ARROW {
+                    let ty = self.type_of(s.x.as_ref());
+                    let p = pointer(self.eval(s.x.as_ref())?);
+                    (p.into(), ty.pointee().cloned().unwrap_or_default())
+                } else if is_lvalue(s.x.as_ref()) {
+                    (self.place(s.x.as_ref())?, self.type_of(s.x.as_ref()))
+                } else {
+                    // a member of a struct returned by a call or assigned
+                    let ty = self.type_of(s.x.as_ref());
+                    let v = self.eval(s.x.as_ref())?;
+                    let p = self.temporary(s.op_pos, &ty)?;
+                    self.store(p, &ty, v, s.x.start())?;
+                    (p.into(), ty)
+                };
+                let punned = base.punned || matches!(ty.kind, TypeKind::Union(_));
+                let base: Place = base.ptr.into();
+
+                let (TypeKind::Struct(id) | TypeKind::Union(id)) = ty.kind else {
+                    return error(s.op_pos, "member access in a value that is not a record");
+                };
+                let Some(f) = self.member(id, &s.sel.name) else {
+                    return error(s.sel.pos, format!("no member named '{}'", s.sel.name));
+                };
+
+                let place = self.field(base, &f, s.sel.pos)?;
+                Ok(Place { punned, ..place })
+            }
+
+            ExprKind::BasicLit(b) if b.tok == Token::STRING => {
+                if let Some(&p) = self.literals.get(&b.pos) {
+                    return Ok(p.into());
+                }
+
+                let ty = self.type_of(x);
+                let elem = ty.elem().cloned().unwrap_or_default();
+                let size = self.size_of(&elem, b.pos)? as usize;
+
+                let mut bytes = Vec::new();
+                for u in string_units(&b.lit, self.layout().wchar_t) {
+                    let v = Value::Int(u as i128).convert(&elem, self.layout()).as_int();
+                    bytes.extend_from_slice(&v.to_le_bytes()[..size]);
+                }
+                bytes.resize(bytes.len() + size, 0);
+
+                let p = self.mem.literal(bytes, size as u64);
+                self.literals.insert(b.pos, p);
+                Ok(p.into())
+            }
+
+            // a compound literal is an object of the enclosing block, or of
+            // static storage duration outside functions
+            ExprKind::CompositeLit(c) => {
+                let ty = self.type_of(x);
+                let p = self.temporary(c.lparen, &ty)?;
+
+                let size = self.size_of(&ty, c.lparen)?;
+                self.mem
+                    .fill(p, 0, size as usize)
+                    .map_err(fault(c.lparen))?;
+                self.init_list(p.into(), &ty, &c.init)?;
+                Ok(p.into())
+            }
+
+            _ => error(x.start(), "expression is not an lvalue"),
+        }
+    }
+
+    // the object of a compound literal or a temporary at `pos`, allocated
+    // the first time it is evaluated
+    pub(crate) fn temporary(&mut self, pos: usize, ty: &Type) -> Result<Pointer> {
+        let found = match self.frames.last() {
+            Some(f) => f.literals.get(&pos),
+            None => self.literals.get(&pos),
+        };
+        if let Some(&p) = found {
+            return Ok(p);
+        }
+
+        let p = match self.frames.last() {
+            Some(_) => {
+                let p = self.alloc_object(ty, Region::Stack, pos)?;
+                self.frame().literals.insert(pos, p);
+                p
+            }
+            None => {
+                let p = self.alloc_object(ty, Region::Global, pos)?;
+                self.literals.insert(pos, p);
+                p
+            }
+        };
+        Ok(p)
+    }
+
+    // the object of a variable, the declaration of an automatic one may
+    // have been jumped over
+    pub(crate) fn var(&mut self, id: SymbolId, pos: usize) -> Result<Pointer> {
+        if let Some(&p) = self.frames.last().and_then(|f| f.locals.get(&id)) {
+            return Ok(p);
+        }
+        if let Some(&p) = self.globals.get(&id) {
+            return Ok(p);
+        }
+
+        let sym = self.info.symbol(id);
+        if self.info.scope(sym.scope).parent.is_none() || self.frames.is_empty() {
+            // stdin, stdout, stderr and errno
+            if let Some(p) = builtins::global(self, &sym.name, pos) {
+                let p = p?;
+                self.globals.insert(id, p);
+                return Ok(p);
+            }
+            return error(pos, format!("undefined reference to '{}'", sym.name));
+        }
+
+        let ty = sym.ty.clone();
+        let p = self.alloc_object(&ty, Region::Stack, pos)?;
+        self.frame().locals.insert(id, p);
+        Ok(p)
+    }
+
+    // the member `f` of the record at `base`
+    pub(crate) fn field(&self, base: Place, f: &FieldLayout, pos: usize) -> Result<Place> {
+        let Some(width) = f.bits else {
+            return Ok(base.ptr.offset((f.offset / 8) as i64).into());
+        };
+
+        // the storage unit of a bit-field is aligned to the size of its type
+        let unit = self.size_of(&f.ty, pos)?;
+        let start = f.offset / (unit * 8) * unit;
+        Ok(Place {
+            ptr: base.ptr.offset(start as i64),
+            bits: Some(((f.offset - start * 8) as u32, width)),
+            punned: false,
+        })
+    }
+
+    /// Loads the value of an lvalue of type `ty`.
+    pub(crate) fn load_place(&self, p: Place, ty: &Type, pos: usize) -> Result<Value> {
+        if self.checked {
+            self.check_load(p, ty, pos)?;
+        }
+        let Some((shift, width)) = p.bits else {
+            return self.load(p.ptr, ty, pos);
+        };
+
+        let unit = self.size_of(ty, pos)? as usize;
+        let raw = le(self.mem.read(p.ptr, unit).map_err(fault(pos))?);
+        let v = (raw >> shift) & ((1 << width) - 1);
+
+        // a signed bit-field extends its sign bit
+        let k = ty.int_kind().unwrap_or(IntKind::Int);
+        let v = if self.layout().is_signed(k) && v >> (width - 1) != 0 {
+            v as i128 - (1 << width)
+        } else {
+            v as i128
+        };
+        Ok(Value::Int(v))
+    }
+
+    /// Stores a value converted to `ty` in an lvalue, the other bits of
+    /// the unit of a bit-field are kept.
+    pub(crate) fn store_place(&mut self, p: Place, ty: &Type, v: Value, pos: usize) -> Result<()> {
+        if self.checked {
+            self.check_store(p, ty, pos)?;
+        }
+        let Some((shift, width)) = p.bits else {
+            self.store(p.ptr, ty, v, pos)?;
+            return match self.checked {
+                true => self.stored(p, ty, pos),
+                false => Ok(()),
+            };
+        };
+
+        let unit = self.size_of(ty, pos)? as usize;
+        let raw = le(self.mem.read(p.ptr, unit).map_err(fault(pos))?);
+        let mask = ((1u128 << width) - 1) << shift;
+        let v = v.convert(ty, self.layout()).as_int() as u128;
+        let raw = (raw & !mask) | ((v << shift) & mask);
+
+        let bytes = raw.to_le_bytes();
+        self.mem.write(p.ptr, &bytes[..unit]).map_err(fault(pos))
+    }
+
+    // ++ and --, `prefix` for the value after the step
+    fn step(&mut self, op: Token, x: &'a dyn Expr, prefix: bool) -> Result<Value> {
+        let p = self.place(x)?;
+        let ty = self.type_of(x).unqualified();
+        let delta = if op == Token::INC { 1 } else { -1 };
+        self.step_place(p, &ty, delta, prefix, x.start())
+    }
+
+    /// Adds `delta` to the lvalue `p` of type `ty`, returns its value
+    /// after the step if `prefix`, before it otherwise.
+    pub(crate) fn step_place(
+        &mut self,
+        p: Place,
+        ty: &Type,
+        delta: i64,
+        prefix: bool,
+        pos: usize,
+    ) -> Result<Value> {
+        let old = self.load_place(p, ty, pos)?;
+
+        let new = match &old {
+            Value::Pointer(q) => {
+                let size = self.stride(ty, pos)?;
+                Value::Pointer(q.offset(delta * size))
+            }
+            Value::Float(f) => Value::Float(f + delta as f64).convert(ty, self.layout()),
+            v => {
+                let n = v.as_int() + delta as i128;
+                if self.checked && ty.is_integer() {
+                    let op = if delta > 0 { Token::PLUS } else { Token::MINUS };
+                    self.check_arith(op, v.as_int(), 1, n, &ty.promote(), pos)?;
+                }
+                Value::Int(n).convert(ty, self.layout())
+            }
+        };
+
+        self.store_place(p, ty, new.clone(), pos)?;
+        match (prefix, p.bits) {
+            (true, Some(_)) => self.load_place(p, ty, pos),
+            (true, None) => Ok(new),
+            (false, _) => Ok(old),
+        }
+    }
+
+    // the size of the type a pointer of type `ty` points to, 1 for void
+    // and functions
+    pub(crate) fn stride(&self, ty: &Type, pos: usize) -> Result<i64> {
+        match ty.pointee() {
+            Some(p) if !p.is_void() && !p.is_function() => Ok(self.size_of(p, pos)? as i64),
+            _ => Ok(1),
+        }
+    }
+
+    /// Applies a binary operator to operands of types `ta` and `tb`.
+    pub(crate) fn binary(
+        &mut self,
+        op: Token,
+        pos: usize,
+        a: Value,
+        ta: &Type,
+        b: Value,
+        tb: &Type,
+    ) -> Result<Value> {
+        let (ta, tb) = (ta.decay(), tb.decay());
+        let dl = self.layout();
+
+        if ta.is_pointer() || tb.is_pointer() {
+            return self.pointer_op(op, pos, a, &ta, b, &tb);
+        }
+
+        if matches!(op, Token::SHL | Token::SHR) {
+            let t = ta.promote();
+            let k = t.int_kind().unwrap_or(IntKind::Int);
+            let v = a.convert(&t, dl).as_int();
+
+            if self.checked {
+                self.check_shift(op, v, b.as_int(), &t, pos)?;
+            }
+
+            // the count is masked to the width like x86 does
+            let n = (b.as_int() & (dl.width(k) as i128 - 1)) as u32;
+            let v = match op {
+                Token::SHL => v.wrapping_shl(n),
+                _ => v >> n,
+            };
+            return Ok(Value::Int(dl.wrap(v, k)));
+        }
+
+        let t = usual_arithmetic(&ta, &tb, dl);
+        self.arith(op, pos, a.convert(&t, dl), b.convert(&t, dl), &t)
+    }
+
+    /// Applies an arithmetic or comparison operator to operands converted
+    /// to the type `t` of the usual arithmetic conversions.
+    pub(crate) fn arith(
+        &self,
+        op: Token,
+        pos: usize,
+        a: Value,
+        b: Value,
+        t: &Type,
+    ) -> Result<Value> {
+        let dl = self.layout();
+        if let (Value::Float(x), Value::Float(y)) = (&a, &b) {
+            let (x, y) = (*x, *y);
+            let v = match op {
+                Token::PLUS => x + y,
+                Token::MINUS => x - y,
+                Token::ASTERISK => x * y,
+                Token::SLASH => x / y,
+                op => return Ok(Value::Int(compare(op, x, y) as i128)),
+            };
+            return Ok(Value::Float(v).convert(t, dl));
+        }
+
+        let (x, y) = (a.as_int(), b.as_int());
+        let v = match op {
+            Token::PLUS => x.wrapping_add(y),
+            Token::MINUS => x.wrapping_sub(y),
+            Token::ASTERISK => x.wrapping_mul(y),
+            Token::SLASH | Token::REM if y == 0 => return error(pos, "division by zero"),
+            Token::SLASH => x.wrapping_div(y),
+            Token::REM => x.wrapping_rem(y),
+            Token::AND => x & y,
+            Token::OR => x | y,
+            Token::XOR => x ^ y,
+            op => return Ok(Value::Int(compare(op, x, y) as i128)),
+        };
+        if self.checked {
+            self.check_arith(op, x, y, v, t, pos)?;
+        }
+        Ok(Value::Int(v).convert(t, dl))
+    }
+
+    // the arithmetic and comparisons of pointers, in units of the type
+    // pointed to
+    pub(crate) fn pointer_op(
+        &mut self,
+        op: Token,
+        pos: usize,
+        a: Value,
+        ta: &Type,
+        b: Value,
+        tb: &Type,
+    ) -> Result<Value> {
+        match (op, a, b) {
+            (Token::PLUS | Token::MINUS, Value::Pointer(p), n) if tb.is_integer() => {
+                let n = n.as_int() as i64 * self.stride(ta, pos)?;
+                let n = if op == Token::MINUS { -n } else { n };
+                Ok(Value::Pointer(p.offset(n)))
+            }
+            (Token::PLUS, n, Value::Pointer(p)) if ta.is_integer() => Ok(Value::Pointer(
+                p.offset(n.as_int() as i64 * self.stride(tb, pos)?),
+            )),
+            (Token::MINUS, a, b) if ta.is_pointer() && tb.is_pointer() => {
+                let d = (a.as_int() as i64).wrapping_sub(b.as_int() as i64);
+                Ok(Value::Int((d / self.stride(ta, pos)?.max(1)) as i128))
+            }
+            (op, a, b) => Ok(Value::Int(compare(op, a.as_int(), b.as_int()) as i128)),
+        }
+    }
+
+    /// Initializes the object of type `ty` at `p` with an initializer,
+    /// the parts an initializer list leaves out are not written.
+    pub(crate) fn init(&mut self, p: Place, ty: &Type, x: &'a dyn Expr) -> Result<()> {
+        match x.kind() {
+            ExprKind::InitList(l) => self.init_list(p, ty, l),
+
+            // a string literal initializes the elements of a character
+            // array, its null character only if there is room
+            ExprKind::BasicLit(b) if b.tok == Token::STRING && ty.is_array() => {
+                let src = self.address(x)?;
+                let len = self.mem.get(src.alloc).size;
+                let n = self.size_of(ty, x.start())?.min(len);
+                self.mem
+                    .copy(p.ptr, src, n as usize)
+                    .map_err(fault(x.start()))
+            }
+
+            _ => {
+                let v = self.eval(x)?.convert(ty, self.layout());
+                self.store_place(p, ty, v, x.start())
+            }
+        }
+    }
+
+    // the elements of an initializer list in order, braces around the
+    // initializers of nested arrays and records may be left out and a
+    // designator moves to the member or element it names
+    fn init_list(&mut self, p: Place, ty: &Type, l: &'a InitList) -> Result<()> {
+        if !is_aggregate(ty) {
+            return match l.elems.first() {
+                Some(e) => self.init(p, ty, e.value.as_ref()),
+                None => Ok(()),
+            };
+        }
+
+        // `char s[] = {"abc"}`
+        if let [e] = &l.elems[..] {
+            let x = e.value.as_ref();
+            let string = matches!(x.kind(), ExprKind::BasicLit(b) if b.tok == Token::STRING);
+            if string && e.designators.is_empty() && ty.elem().is_some_and(Type::is_integer) {
+                return self.init(p, ty, x);
+            }
+        }
+
+        let mut stack = vec![Cursor {
+            ty: ty.clone(),
+            place: p,
+            index: 0,
+        }];
+
+        for e in l.elems.iter() {
+            let x = e.value.as_ref();
+
+            if !e.designators.is_empty() {
+                stack.truncate(1);
+                for (i, d) in e.designators.iter().enumerate() {
+                    self.designate(&mut stack, d)?;
+                    if i + 1 < e.designators.len() {
+                        let c = stack.last().unwrap();
+                        let (ty, place) = self.subobject(c, c.index, d.start())?;
+                        stack.push(Cursor {
+                            ty,
+                            place,
+                            index: 0,
+                        });
+                    }
+                }
+            } else {
+                // the next subobject, after those that are complete
+                while stack.len() > 1 && self.next(stack.last().unwrap()).is_none() {
+                    stack.pop();
+                    advance(stack.last_mut().unwrap());
+                }
+                let c = stack.last_mut().unwrap();
+                match self.next(c) {
+                    Some(i) => c.index = i,
+                    None => break, // excess initializers
+                }
+            }
+
+            // descends into aggregates until the initializer fits
+            loop {
+                let c = stack.last().unwrap();
+                let (ty, place) = self.subobject(c, c.index, x.start())?;
+
+                let fits = match x.kind() {
+                    ExprKind::InitList(_) => true,
+                    ExprKind::BasicLit(b) if b.tok == Token::STRING => {
+                        !is_aggregate(&ty) || ty.elem().is_some_and(Type::is_integer)
+                    }
+                    _ => !is_aggregate(&ty) || self.type_of(x).is_record(),
+                };
+                if fits {
+                    self.init(place, &ty, x)?;
+                    break;
+                }
+
+                stack.push(Cursor {
+                    ty,
+                    place,
+                    index: 0,
+                });
+                let c = stack.last_mut().unwrap();
+                match self.next(c) {
+                    Some(i) => c.index = i,
+                    None => break,
+                }
+            }
+
+            advance(stack.last_mut().unwrap());
+        }
+
+        Ok(())
+    }
+
+    // the element or member at or after the cursor that an initializer
+    // without a designator initializes, unnamed bit-fields are skipped
+    fn next(&self, c: &Cursor) -> Option<usize> {
+        match &c.ty.kind {
+            TypeKind::Array(_, ArrayLen::Fixed(n)) => (c.index < *n as usize).then_some(c.index),
+            TypeKind::Struct(id) | TypeKind::Union(id) => {
+                let fields = &self.records[id.0].as_ref()?.fields;
+                (c.index..fields.len()).find(|&i| {
+                    let f = &fields[i];
+                    f.name.is_some() || f.ty.is_record()
+                })
+            }
+            _ => None,
+        }
+    }
+
+    // moves the innermost cursor to the subobject a designator names,
+    // through the anonymous members that contain a named one
+    fn designate(&mut self, stack: &mut Vec<Cursor>, d: &'a Designator) -> Result<()> {
+        match d {
+            Designator::Index { index, .. } => {
+                let n = match Eval::new(self.info).int(index.as_ref()) {
+                    Ok(n) => n as usize,
+                    Err(n) => return error(n.pos, n.msg),
+                };
+                let c = stack.last_mut().unwrap();
+                if !c.ty.is_array() {
+                    return error(d.start(), "array designator in a non-array object");
+                }
+                c.index = n;
+            }
+
+            Designator::Field { name, .. } => loop {
+                let c = stack.last_mut().unwrap();
+                let (TypeKind::Struct(id) | TypeKind::Union(id)) = c.ty.kind else {
+                    return error(d.start(), "field designator in a non-record object");
+                };
+                let Some(r) = &self.records[id.0] else {
+                    return error(d.start(), "field designator in an incomplete record");
+                };
+
+                if let Some(i) = r
+                    .fields
+                    .iter()
+                    .position(|f| f.name == Some(name.name.clone()))
+                {
+                    c.index = i;
+                    break;
+                }
+
+                // the anonymous member that contains it
+                let inner = r.fields.iter().position(|f| match (&f.name, &f.ty.kind) {
+                    (None, TypeKind::Struct(inner) | TypeKind::Union(inner)) => {
+                        self.member(*inner, &name.name).is_some()
+                    }
+                    _ => false,
+                });
+                let Some(i) = inner else {
+                    return error(name.pos, format!("no member named '{}'", name.name));
+                };
+
+                c.index = i;
+                let c = stack.last().unwrap();
+                let (ty, place) = self.subobject(c, i, d.start())?;
+                stack.push(Cursor {
+                    ty,
+                    place,
+                    index: 0,
+                });
+            },
+        }
+
+        Ok(())
+    }
+
+    // the type and place of the element or member `i` of a cursor
+    fn subobject(&self, c: &Cursor, i: usize, pos: usize) -> Result<(Type, Place)> {
+        match &c.ty.kind {
+            TypeKind::Array(elem, _) => {
+                let size = self.size_of(elem, pos)?;
+                let ptr = c.place.ptr.offset((i as u64 * size) as i64);
+                Ok((elem.as_ref().clone(), ptr.into()))
+            }
+            TypeKind::Struct(id) | TypeKind::Union(id) => {
+                let fields = self.records[id.0].as_ref().map_or(&[][..], |r| &r.fields);
+                let Some(f) = fields.get(i) else {
+                    return error(pos, "excess elements in initializer");
+                };
+
+                Ok((f.ty.clone(), self.field(c.place, f, pos)?))
+            }
+            _ => error(pos, "excess elements in initializer"),
+        }
+    }
+}
+
+/// The pointer a value converts to.
+pub(crate) fn pointer(v: Value) -> Pointer {
+    match v {
+        Value::Pointer(p) => p,
+        v => Pointer {
+            alloc: 0,
+            addr: v.as_int() as u64,
+        },
+    }
+}
+
+// moves a cursor past the subobject it is at, one member of a union is
+// initialized
+fn advance(c: &mut Cursor) {
+    c.index = match c.ty.kind {
+        TypeKind::Union(_) => usize::MAX,
+        _ => c.index + 1,
+    };
+}
+
+pub(crate) fn is_aggregate(ty: &Type) -> bool {
+    ty.is_array() || ty.is_record()
+}
+
+// reports whether an expression designates an object, the records that
+// calls and assignments return do not
+pub(crate) fn is_lvalue(x: &dyn Expr) -> bool {
+    match x.kind() {
+        ExprKind::ParenExpr(p) => is_lvalue(p.x.as_ref()),
+        ExprKind::SelectorExpr(s) => s.op == Token::ARROW || is_lvalue(s.x.as_ref()),
+        ExprKind::Ident(_)
+        | ExprKind::StarExpr(_)
+        | ExprKind::IndexExpr(_)
+        | ExprKind::CompositeLit(_) => true,
+        ExprKind::BasicLit(b) => b.tok == Token::STRING,
+        _ => false,
+    }
+}
+
+pub(crate) fn compare<T: PartialOrd>(op: Token, x: T, y: T) -> bool {
+    match op {
+        Token::LT => x < y,
+        Token::GT => x > y,
+        Token::LEQ => x <= y,
+        Token::GEQ => x >= y,
+        Token::EQL => x == y,
+        _ => x != y,
+    }
+}
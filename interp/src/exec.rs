@@ -0,0 +1,401 @@
+//! Statements: blocks, declarations and the control flow between them.
+//! A jump to a label or a case runs the statements that contain it in
+//! seek mode, which skips everything before the target.
+
+use ast::{BlockStmt, DeclStmt, Stmt, StmtKind};
+use sema::consts::Eval;
+use sema::types::{ArrayLen, Type, TypeKind};
+use sema::SymbolKind;
+use token::Token;
+
+use crate::memory::Region;
+use crate::value::Value;
+use crate::{error, fault, Interpreter, Result};
+
+/// How a statement completed.
+pub(crate) enum Flow<'a> {
+    Normal,
+    Break,
+    Continue,
+    Return(Option<Value>),
+    Goto(&'a str), // to a label outside the statement
+}
+
+/// The statement a jump is looking for.
+#[derive(Clone, Copy)]
+enum Target<'t> {
+    Label(&'t str),
+    Case(usize), // the position of a case or default label
+}
+
+impl<'a> Interpreter<'a> {
+    /// Runs the body of a function, returns the value of its return
+    /// statement.
+    pub(crate) fn body(&mut self, body: &'a BlockStmt) -> Result<Option<Value>> {
+        match self.block(body, None)? {
+            Flow::Return(v) => Ok(v),
+            Flow::Goto(label) => error(body.lbrace, format!("label '{}' not found", label)),
+            _ => Ok(None),
+        }
+    }
+
+    fn block(&mut self, b: &'a BlockStmt, mut seek: Option<Target<'a>>) -> Result<Flow<'a>> {
+        let mut i = 0;
+        if let Some(t) = seek {
+            match b.stmts.iter().position(|s| contains(s.as_ref(), t)) {
+                Some(at) => i = at,
+                None => return Ok(Flow::Normal),
+            }
+        }
+
+        while i < b.stmts.len() {
+            match self.stmt(b.stmts[i].as_ref(), seek.take())? {
+                Flow::Normal => i += 1,
+
+                // the label may be in this block
+                Flow::Goto(label) => {
+                    let t = Target::Label(label);
+                    match b.stmts.iter().position(|s| contains(s.as_ref(), t)) {
+                        Some(at) => {
+                            i = at;
+                            seek = Some(t);
+                        }
+                        None => return Ok(Flow::Goto(label)),
+                    }
+                }
+
+                flow => return Ok(flow),
+            }
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    /// Runs a statement, or the part of it from the target of a jump.
+    fn stmt(&mut self, s: &'a dyn Stmt, seek: Option<Target<'a>>) -> Result<Flow<'a>> {
+        match s.kind() {
+            StmtKind::SemiColonStmt(_) | StmtKind::StaticAssert(_) => Ok(Flow::Normal),
+
+            StmtKind::ExprStmt(s) => {
+                if seek.is_none() {
+                    self.eval(s.x.as_ref())?;
+                }
+                Ok(Flow::Normal)
+            }
+
+            StmtKind::DeclStmt(d) => {
+                if seek.is_none() {
+                    self.decl(d)?;
+                }
+                Ok(Flow::Normal)
+            }
+
+            StmtKind::ReturnStmt(r) => {
+                let v = match &r.value {
+                    Some(x) => Some(self.eval(x.as_ref())?),
+                    None => None,
+                };
+                Ok(Flow::Return(v))
+            }
+            StmtKind::BreakStmt(_) => Ok(Flow::Break),
+            StmtKind::ContinueStmt(_) => Ok(Flow::Continue),
+            StmtKind::GotoStmt(g) => {
+                self.tick(g.pos)?;
+                Ok(Flow::Goto(&g.label.name))
+            }
+
+            StmtKind::BlockStmt(b) => self.block(b, seek),
+
+            StmtKind::IfStmt(s) => {
+                let mut branches = vec![(Some(s.cond.as_ref()), s.init.as_ref())];
+                branches.extend(
+                    s.elifs
+                        .iter()
+                        .map(|e| (Some(e.cond.as_ref()), e.init.as_ref())),
+                );
+                branches.extend(s._else.iter().map(|e| (None, e.init.as_ref())));
+
+                if let Some(t) = seek {
+                    return match branches.iter().find(|b| contains(b.1, t)) {
+                        Some(b) => self.stmt(b.1, seek),
+                        None => Ok(Flow::Normal),
+                    };
+                }
+
+                for (cond, body) in branches {
+                    let taken = match cond {
+                        Some(x) => self.eval(x)?.is_true(),
+                        None => true,
+                    };
+                    if taken {
+                        return self.stmt(body, None);
+                    }
+                }
+                Ok(Flow::Normal)
+            }
+
+            StmtKind::WhileStmt(w) => {
+                let mut seek = seek;
+                loop {
+                    if seek.is_none() {
+                        self.tick(w.pos)?;
+                        if !self.eval(w.cond.as_ref())?.is_true() {
+                            return Ok(Flow::Normal);
+                        }
+                    }
+                    match self.stmt(w.init.as_ref(), seek.take())? {
+                        Flow::Normal | Flow::Continue => {}
+                        Flow::Break => return Ok(Flow::Normal),
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+
+            StmtKind::DowhileStmt(d) => {
+                let mut seek = seek;
+                loop {
+                    if seek.is_none() {
+                        self.tick(d.do_pos)?;
+                    }
+                    match self.stmt(d.init.as_ref(), seek.take())? {
+                        Flow::Normal | Flow::Continue => {}
+                        Flow::Break => return Ok(Flow::Normal),
+                        flow => return Ok(flow),
+                    }
+                    if !self.eval(d.cond.as_ref())?.is_true() {
+                        return Ok(Flow::Normal);
+                    }
+                }
+            }
+
+            StmtKind::ForStmt(f) => {
+                let mut seek = seek;
+                if seek.is_none() {
+                    if let Some(init) = &f.init {
+                        self.stmt(init.as_ref(), None)?;
+                    }
+                }
+
+                loop {
+                    if seek.is_none() {
+                        self.tick(f.pos)?;
+                        if let Some(cond) = &f.cond {
+                            if !self.eval(cond.as_ref())?.is_true() {
+                                return Ok(Flow::Normal);
+                            }
+                        }
+                    }
+                    match self.stmt(f.body.as_ref(), seek.take())? {
+                        Flow::Normal | Flow::Continue => {}
+                        Flow::Break => return Ok(Flow::Normal),
+                        flow => return Ok(flow),
+                    }
+                    if let Some(post) = &f.post {
+                        self.eval(post.as_ref())?;
+                    }
+                }
+            }
+
+            StmtKind::SwitchStmt(s) => {
+                let seek = match seek {
+                    Some(t) => Some(t),
+                    None => {
+                        let v = self.eval(s.tag.as_ref())?;
+                        let ty = self.type_of(s.tag.as_ref()).promote();
+                        let v = v.convert(&ty, self.layout()).as_int();
+
+                        match self.case(s.body.as_ref(), v, &ty)? {
+                            Some(pos) => Some(Target::Case(pos)),
+                            None => return Ok(Flow::Normal),
+                        }
+                    }
+                };
+
+                match self.stmt(s.body.as_ref(), seek)? {
+                    Flow::Break => Ok(Flow::Normal),
+                    flow => Ok(flow),
+                }
+            }
+
+            StmtKind::CaseStmt(c) => match seek {
+                Some(Target::Case(pos)) if pos == c.pos => self.stmt(c.stmt.as_ref(), None),
+                _ => self.stmt(c.stmt.as_ref(), seek),
+            },
+
+            StmtKind::LabeledStmt(l) => match seek {
+                Some(Target::Label(name)) if name == l.label.name => {
+                    self.stmt(l.stmt.as_ref(), None)
+                }
+                _ => self.stmt(l.stmt.as_ref(), seek),
+            },
+        }
+    }
+
+    // the position of the case label of a switch body matching `v`, or of
+    // its default label
+    fn case(&self, body: &dyn Stmt, v: i128, ty: &Type) -> Result<Option<usize>> {
+        let mut cases = Vec::new();
+        collect_cases(body, &mut cases);
+
+        let mut default = None;
+        for (pos, value) in cases {
+            let Some(x) = value else {
+                default = Some(pos);
+                continue;
+            };
+
+            let c = match Eval::new(self.info).int(x) {
+                Ok(c) => Value::Int(c).convert(ty, self.layout()).as_int(),
+                Err(n) => return error(n.pos, n.msg),
+            };
+            if c == v {
+                return Ok(Some(pos));
+            }
+        }
+
+        Ok(default)
+    }
+
+    // creates the objects of a declaration and runs its initializers,
+    // static locals are initialized once
+    pub(crate) fn decl(&mut self, d: &'a DeclStmt) -> Result<()> {
+        let g = &d.decl;
+        if matches!(g.specs.storage, Some(Token::TYPEDEF | Token::EXTERN)) {
+            return Ok(());
+        }
+
+        for d in g.decls.iter() {
+            let name = d.declarator.name.as_ref();
+            let Some(&id) = name.and_then(|n| self.info.decls.get(&n.pos)) else {
+                continue;
+            };
+            let sym = self.info.symbol(id);
+            if sym.kind != SymbolKind::Var {
+                continue;
+            }
+            let ty = sym.ty.clone();
+
+            if g.specs.storage == Some(Token::STATIC) {
+                if !self.globals.contains_key(&id) {
+                    if let Some(p) = self.carried(id) {
+                        self.globals.insert(id, p);
+                        continue;
+                    }
+                    let p = self.alloc_object(&ty, Region::Global, d.declarator.pos)?;
+                    self.globals.insert(id, p);
+                    if let Some(x) = &d.init {
+                        self.init(p.into(), &ty, x.as_ref())?;
+                    }
+                }
+                continue;
+            }
+
+            // a VLA gets its length each time its declaration is reached,
+            // the previous object ends its lifetime
+            let p = if ty.is_vla() {
+                let pos = d.declarator.pos;
+                let size = self.vla_size(&ty, &d.declarator.derived, pos)?;
+                let p = self.alloc(size, self.align_of(&ty), Region::Heap, pos)?;
+
+                let frame = self.frame();
+                frame.allocs.push(p.alloc);
+                if let Some(old) = frame.locals.insert(id, p) {
+                    frame.allocs.retain(|&a| a != old.alloc);
+                    self.mem.free(old.alloc);
+                }
+                p
+            } else {
+                match self.frame().locals.get(&id) {
+                    Some(&p) => p,
+                    None => {
+                        let p = self.alloc_object(&ty, Region::Stack, d.declarator.pos)?;
+                        self.frame().locals.insert(id, p);
+                        p
+                    }
+                }
+            };
+
+            if let Some(x) = &d.init {
+                // the parts an initializer list leaves out are zero
+                let size = self.size_of(&ty, x.start())?;
+                self.mem
+                    .fill(p, 0, size as usize)
+                    .map_err(fault(x.start()))?;
+                self.init(p.into(), &ty, x.as_ref())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // the size of a variable length array, the lengths of its dimensions
+    // are the leading array declarators
+    fn vla_size(&mut self, ty: &Type, derived: &'a [ast::Derived], pos: usize) -> Result<u64> {
+        let mut len = 1;
+        let mut ty = ty;
+        let mut derived = derived.iter();
+
+        while let TypeKind::Array(elem, n) = &ty.kind {
+            let n = match (n, derived.next()) {
+                (ArrayLen::Fixed(n), _) => *n as i128,
+                (ArrayLen::Variable, Some(ast::Derived::Array { len: Some(x), .. })) => {
+                    self.eval(x.as_ref())?.as_int()
+                }
+                _ => return error(pos, "variable length array types are not supported here"),
+            };
+            if n <= 0 {
+                return error(pos, format!("variable length array has a length of {}", n));
+            }
+            len *= n as u64;
+            ty = elem;
+        }
+
+        Ok(len * self.size_of(ty, pos)?)
+    }
+}
+// collects the case and default labels of a switch body, not those of the
+// switches nested in it
+fn collect_cases<'s>(s: &'s dyn Stmt, cases: &mut Vec<(usize, Option<&'s dyn ast::Expr>)>) {
+    match s.kind() {
+        StmtKind::CaseStmt(c) => {
+            cases.push((c.pos, c.value.as_deref()));
+            collect_cases(c.stmt.as_ref(), cases);
+        }
+        StmtKind::SwitchStmt(_) => {}
+        _ => children(s, &mut |s| collect_cases(s, cases)),
+    }
+}
+
+// reports whether a statement contains the target of a jump, the cases of
+// nested switches are not targets
+fn contains(s: &dyn Stmt, t: Target) -> bool {
+    match (s.kind(), t) {
+        (StmtKind::LabeledStmt(l), Target::Label(name)) if l.label.name == name => true,
+        (StmtKind::CaseStmt(c), Target::Case(pos)) if c.pos == pos => true,
+        (StmtKind::SwitchStmt(_), Target::Case(_)) => false,
+        _ => {
+            let mut found = false;
+            children(s, &mut |s| found = found || contains(s, t));
+            found
+        }
+    }
+}
+
+// calls `f` on the statements directly nested in `s`
+fn children<'s>(s: &'s dyn Stmt, f: &mut dyn FnMut(&'s dyn Stmt)) {
+    match s.kind() {
+        StmtKind::BlockStmt(b) => b.stmts.iter().for_each(|s| f(s.as_ref())),
+        StmtKind::IfStmt(s) => {
+            f(s.init.as_ref());
+            s.elifs.iter().for_each(|e| f(e.init.as_ref()));
+            s._else.iter().for_each(|e| f(e.init.as_ref()));
+        }
+        StmtKind::WhileStmt(w) => f(w.init.as_ref()),
+        StmtKind::DowhileStmt(d) => f(d.init.as_ref()),
+        StmtKind::ForStmt(s) => f(s.body.as_ref()),
+        StmtKind::SwitchStmt(s) => f(s.body.as_ref()),
+        StmtKind::CaseStmt(c) => f(c.stmt.as_ref()),
+        StmtKind::LabeledStmt(l) => f(l.stmt.as_ref()),
+        _ => {}
+    }
+}
@@ -0,0 +1,780 @@
+//! Calls of the functions of shared libraries. `load` opens a library with
+//! dlopen for the rest of the process, and a function the program declares
+//! but neither defines nor finds in the bundled C library is looked up in
+//! the libraries loaded, in order, with dlsym.
+//!
+//! Calls follow the System V ABI of x86-64: integers and pointers go in the
+//! general registers, floating values in the SSE registers and the rest on
+//! the stack. A struct or union of up to 16 bytes is split in eightbytes,
+//! each passed like an integer or a double by the members in it, a larger
+//! one is copied on the stack or returned through a hidden pointer.
+//!
+//! A pointer is passed as the host address of the bytes of its object, the
+//! foreign code reads and writes them in place, and so are the pointers
+//! stored in the objects it reaches, see `Memory::lend`. Pointers to memory
+//! the program does not own can only be passed back, except that the string
+//! of a `const char *` result is copied in a read-only string.
+
+use std::ffi::{c_char, CStr, CString};
+use std::rc::Rc;
+use std::sync::Mutex;
+
+use sema::types::{ArrayLen, FloatKind, IntKind, Type, TypeKind};
+use sema::SymbolId;
+
+use crate::builtins;
+use crate::memory::{le, Loan};
+use crate::value::{Pointer, Record, Value};
+use crate::{error, fault, Interpreter, Result};
+
+// the libraries loaded and their handles, in order
+static LIBRARIES: Mutex<Vec<(String, usize)>> = Mutex::new(Vec::new());
+
+// the error of a library loaded or a call made where `invoke` cannot call
+const UNSUPPORTED: &str = "foreign calls are unsupported on this target";
+
+/// Loads a shared library for the programs run after, a path without a
+/// slash is searched for like dlopen does.
+pub fn load(path: &str) -> std::result::Result<(), String> {
+    if !cfg!(all(target_arch = "x86_64", unix)) {
+        return Err(UNSUPPORTED.to_string());
+    }
+
+    let mut libs = LIBRARIES.lock().unwrap();
+    if libs.iter().any(|(p, _)| p == path) {
+        return Ok(());
+    }
+
+    let handle = dl::open(path)?;
+    libs.push((path.to_string(), handle));
+    Ok(())
+}
+
+/// The paths of the libraries loaded, in order.
+pub fn libraries() -> Vec<String> {
+    let libs = LIBRARIES.lock().unwrap();
+    libs.iter().map(|(p, _)| p.clone()).collect()
+}
+
+// the address of a function of the libraries loaded
+fn lookup(name: &str) -> Option<u64> {
+    let libs = LIBRARIES.lock().unwrap();
+    libs.iter()
+        .find_map(|&(_, handle)| dl::symbol(handle, name))
+}
+
+#[cfg(unix)]
+mod dl {
+    use super::*;
+
+    pub(super) fn open(path: &str) -> std::result::Result<usize, String> {
+        let name = CString::new(path).map_err(|_| format!("{}: invalid path", path))?;
+        let handle = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_GLOBAL) };
+        if handle.is_null() {
+            let err = unsafe { libc::dlerror() };
+            return Err(match err.is_null() {
+                true => format!("{}: cannot load the library", path),
+                false => unsafe { CStr::from_ptr(err) }
+                    .to_string_lossy()
+                    .into_owned(),
+            });
+        }
+        Ok(handle as usize)
+    }
+
+    pub(super) fn symbol(handle: usize, name: &str) -> Option<u64> {
+        let name = CString::new(name).ok()?;
+        let f = unsafe { libc::dlsym(handle as *mut libc::c_void, name.as_ptr()) };
+        (!f.is_null()).then_some(f as u64)
+    }
+}
+
+#[cfg(not(unix))]
+mod dl {
+    pub(super) fn open(_: &str) -> std::result::Result<usize, String> {
+        Err("shared libraries are not supported on this system".to_string())
+    }
+
+    pub(super) fn symbol(_: usize, _: &str) -> Option<u64> {
+        None
+    }
+}
+
+/// The class of an eightbyte of an argument or result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Integer,
+    Sse,
+}
+
+/// The arguments of a call, in registers and on the stack.
+#[derive(Default)]
+struct Args {
+    ints: Vec<u64>,
+    floats: Vec<u64>,
+    stack: Vec<u64>,
+}
+
+impl Args {
+    fn int(&mut self, v: u64) {
+        match self.ints.len() < 6 {
+            true => self.ints.push(v),
+            false => self.stack.push(v),
+        }
+    }
+
+    fn float(&mut self, v: u64) {
+        match self.floats.len() < 8 {
+            true => self.floats.push(v),
+            false => self.stack.push(v),
+        }
+    }
+}
+
+impl Interpreter<'_> {
+    /// Calls the function of a loaded library a declared function names,
+    /// returns None if there is none.
+    pub(crate) fn call_foreign(
+        &mut self,
+        id: SymbolId,
+        args: &[Value],
+        pos: usize,
+    ) -> Option<Result<Value>> {
+        let sym = self.info.symbol(id);
+        let f = lookup(&sym.name)?;
+
+        if self.layout().pointer.size != 8 {
+            return Some(error(pos, "foreign functions need the lp64 data layout"));
+        }
+
+        let func = sym.ty.func();
+        let params = func.map_or(&[][..], |f| &f.params);
+        let ret = func.map_or_else(Type::default, |f| f.ret.clone());
+        Some(self.foreign(f, params, &ret, args, pos))
+    }
+
+    fn foreign(
+        &mut self,
+        f: u64,
+        params: &[Type],
+        ret: &Type,
+        args: &[Value],
+        pos: usize,
+    ) -> Result<Value> {
+        let mut regs = Args::default();
+
+        // a large record is returned in memory the caller provides
+        let classes = match ret.kind {
+            TypeKind::Void => Some(Vec::new()),
+            _ => self.classify(ret, pos)?,
+        };
+        let mut sret = Vec::new();
+        if classes.is_none() {
+            sret = vec![0u64; self.size_of(ret, pos)?.div_ceil(8) as usize];
+            regs.int(sret.as_mut_ptr() as u64);
+        }
+
+        // the objects the arguments point to are lent for the call
+        let mut roots = Vec::new();
+        for v in args.iter() {
+            match v {
+                Value::Pointer(p) => roots.push(*p),
+                Value::Record(r) => roots.extend(r.pointers.iter().map(|&(o, alloc)| Pointer {
+                    alloc,
+                    addr: le(&r.bytes[o as usize..o as usize + 8]) as u64,
+                })),
+                _ => {}
+            }
+        }
+        let loan = self.mem.lend(&roots);
+
+        let passed = args.iter().enumerate().try_for_each(|(i, v)| {
+            // the other arguments were promoted
+            let ty = params.get(i).cloned().unwrap_or_else(|| match v {
+                Value::Float(_) => Type::float(FloatKind::Double),
+                Value::Pointer(_) | Value::Func(_) => Type::void().pointer_to(),
+                _ => Type::int(IntKind::Long),
+            });
+            self.pass(&mut regs, v, &ty, pos)
+        });
+        if let Err(err) = passed {
+            self.mem.reclaim(loan);
+            return Err(err);
+        }
+
+        // the output of the program so far comes first
+        builtins::flush(self);
+        let v = match unsafe { invoke(f, &regs) } {
+            Some(out) => self.returned(&loan, ret, classes, out, &sret, pos),
+            None => error(pos, UNSUPPORTED),
+        };
+        self.mem.reclaim(loan);
+        v
+    }
+
+    // passes an argument converted to type `ty`
+    fn pass(&self, regs: &mut Args, v: &Value, ty: &Type, pos: usize) -> Result<()> {
+        match &ty.kind {
+            TypeKind::Float(FloatKind::Float) => regs.float((v.as_float() as f32).to_bits() as u64),
+            TypeKind::Float(FloatKind::Double) => regs.float(v.as_float().to_bits()),
+            TypeKind::Float(_) => {
+                return error(pos, "long double cannot be passed to foreign code")
+            }
+
+            TypeKind::Pointer(_) => {
+                let h = match v {
+                    Value::Pointer(p) => self.mem.host(*p).map_err(fault(pos))?,
+                    Value::Func(id) => self.function(*id, pos)?,
+                    v => v.as_int() as u64,
+                };
+                regs.int(h);
+            }
+
+            TypeKind::Struct(_) | TypeKind::Union(_) => {
+                let Value::Record(r) = v else {
+                    return error(pos, "expected a struct or union argument");
+                };
+
+                let mut bytes = r.bytes.clone();
+                for &(o, alloc) in r.pointers.iter() {
+                    let at = o as usize..o as usize + 8;
+                    let p = Pointer {
+                        alloc,
+                        addr: le(&bytes[at.clone()]) as u64,
+                    };
+                    let h = self.mem.host(p).unwrap_or(p.addr);
+                    bytes[at].copy_from_slice(&h.to_le_bytes());
+                }
+                bytes.resize(bytes.len().next_multiple_of(8), 0);
+                let words: Vec<u64> = bytes
+                    .chunks(8)
+                    .map(|w| u64::from_le_bytes(w.try_into().unwrap()))
+                    .collect();
+
+                // a record goes in registers whole or on the stack
+                let classes = self.classify(ty, pos)?.filter(|cs| {
+                    let n = cs.iter().filter(|&&c| c == Class::Integer).count();
+                    regs.ints.len() + n <= 6 && regs.floats.len() + cs.len() - n <= 8
+                });
+                match classes {
+                    Some(cs) => {
+                        for (c, w) in cs.into_iter().zip(words) {
+                            match c {
+                                Class::Integer => regs.int(w),
+                                Class::Sse => regs.float(w),
+                            }
+                        }
+                    }
+                    None => {
+                        if self.align_of(ty) > 8 && regs.stack.len() % 2 == 1 {
+                            regs.stack.push(0);
+                        }
+                        regs.stack.extend(words);
+                    }
+                }
+            }
+
+            _ => regs.int(v.as_int() as u64),
+        }
+        Ok(())
+    }
+
+    // the address of a function passed to foreign code, which can only call
+    // the functions of the libraries
+    fn function(&self, id: SymbolId, pos: usize) -> Result<u64> {
+        let name = &self.info.symbol(id).name;
+        match self.funcs.contains_key(&id) {
+            false => lookup(name),
+            true => None,
+        }
+        .map_or_else(
+            || {
+                error(
+                    pos,
+                    format!("the function '{}' cannot be passed to foreign code", name),
+                )
+            },
+            Ok,
+        )
+    }
+
+    // the value of a result in the registers or in `sret`
+    fn returned(
+        &mut self,
+        loan: &Loan,
+        ret: &Type,
+        classes: Option<Vec<Class>>,
+        out: [u64; 4],
+        sret: &[u64],
+        pos: usize,
+    ) -> Result<Value> {
+        let [rax, _, xmm0, _] = out;
+
+        match &ret.kind {
+            TypeKind::Void => Ok(Value::Void),
+            TypeKind::Float(FloatKind::Float) => {
+                Ok(Value::Float(f32::from_bits(xmm0 as u32) as f64))
+            }
+            TypeKind::Float(_) => Ok(Value::Float(f64::from_bits(xmm0))),
+
+            TypeKind::Pointer(to) => {
+                let p = self.mem.guest(loan, rax);
+                let string = to.quals.is_const
+                    && matches!(
+                        to.kind,
+                        TypeKind::Int(IntKind::Char | IntKind::SChar | IntKind::UChar)
+                    );
+                match p.alloc == 0 && p.addr != 0 && string {
+                    true => self.mirror(rax),
+                    false => Ok(Value::Pointer(p)),
+                }
+            }
+
+            TypeKind::Struct(_) | TypeKind::Union(_) => {
+                let words = match classes {
+                    Some(cs) => {
+                        let (mut ints, mut floats) = (out[..2].iter(), out[2..].iter());
+                        cs.iter()
+                            .map(|c| match c {
+                                Class::Integer => *ints.next().unwrap(),
+                                Class::Sse => *floats.next().unwrap(),
+                            })
+                            .collect()
+                    }
+                    None => sret.to_vec(),
+                };
+
+                let size = self.size_of(ret, pos)? as usize;
+                let mut bytes: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+                bytes.truncate(size);
+
+                // the pointers into the objects lent keep their provenance
+                let mut pointers = Vec::new();
+                for o in (0..size / 8).map(|i| i * 8) {
+                    let p = self.mem.guest(loan, le(&bytes[o..o + 8]) as u64);
+                    if p.alloc != 0 {
+                        bytes[o..o + 8].copy_from_slice(&p.addr.to_le_bytes());
+                        pointers.push((o as u64, p.alloc));
+                    }
+                }
+                Ok(Value::Record(Rc::new(Record {
+                    bytes,
+                    pointers,
+                    uninit: Vec::new(),
+                })))
+            }
+
+            _ => {
+                let k = ret.int_kind().unwrap_or(IntKind::Int);
+                let raw = match k {
+                    IntKind::Bool => rax & 0xff,
+                    _ => rax,
+                };
+                Ok(Value::Int(self.layout().wrap(raw as i128, k)))
+            }
+        }
+    }
+
+    // copies a string of foreign memory, once for each content
+    fn mirror(&mut self, h: u64) -> Result<Value> {
+        let bytes = unsafe { CStr::from_ptr(h as *const c_char) }
+            .to_bytes_with_nul()
+            .to_vec();
+        if let Some(&p) = self.mirrors.get(&bytes) {
+            return Ok(Value::Pointer(p));
+        }
+
+        let p = self.mem.literal(bytes.clone(), 1);
+        self.mirrors.insert(bytes, p);
+        Ok(Value::Pointer(p))
+    }
+
+    // the classes of the eightbytes of a type, None for a record passed in
+    // memory
+    fn classify(&self, ty: &Type, pos: usize) -> Result<Option<Vec<Class>>> {
+        let size = self.size_of(ty, pos)?;
+        if size > 16 {
+            return Ok(None);
+        }
+
+        let mut classes = vec![None; size.div_ceil(8) as usize];
+        self.classify_at(ty, 0, &mut classes, pos)?;
+        Ok(Some(
+            classes
+                .into_iter()
+                .map(|c| c.unwrap_or(Class::Sse))
+                .collect(),
+        ))
+    }
+
+    // an eightbyte holding an integer is passed like one, else like a double
+    fn classify_at(
+        &self,
+        ty: &Type,
+        offset: u64,
+        classes: &mut [Option<Class>],
+        pos: usize,
+    ) -> Result<()> {
+        let at = (offset / 8) as usize;
+
+        match &ty.kind {
+            TypeKind::Float(FloatKind::LongDouble) => {
+                return error(pos, "long double cannot be passed to foreign code")
+            }
+            TypeKind::Float(_) => {
+                classes[at].get_or_insert(Class::Sse);
+            }
+            TypeKind::Array(elem, ArrayLen::Fixed(n)) => {
+                let size = self.size_of(elem, pos)?;
+                for i in 0..*n {
+                    self.classify_at(elem, offset + i * size, classes, pos)?;
+                }
+            }
+            TypeKind::Array(..) => {}
+            TypeKind::Struct(id) | TypeKind::Union(id) => {
+                let Some(r) = &self.records[id.0] else {
+                    return error(pos, format!("incomplete type '{}'", self.info.spell(ty)));
+                };
+                for f in r.fields.iter() {
+                    let offset = offset + f.offset / 8;
+                    match f.bits {
+                        Some(_) => classes[(offset / 8) as usize] = Some(Class::Integer),
+                        None => self.classify_at(&f.ty, offset, classes, pos)?,
+                    }
+                }
+            }
+            _ => classes[at] = Some(Class::Integer),
+        }
+        Ok(())
+    }
+}
+
+/// The registers and stack arguments of a call, as `invoke` reads them,
+/// and the registers of its result.
+#[cfg(all(target_arch = "x86_64", unix))]
+#[repr(C)]
+struct Regs {
+    ints: [u64; 6],
+    floats: [u64; 8],
+    nstack: u64,
+    stack: *const u64,
+    nfloat: u64,
+    func: u64,
+    out: [u64; 4], // rax, rdx, xmm0 and xmm1
+}
+
+// calls `func` with the arguments, returns the registers of its result
+#[cfg(all(target_arch = "x86_64", unix))]
+unsafe fn invoke(func: u64, args: &Args) -> Option<[u64; 4]> {
+    let mut f = Regs {
+        ints: [0; 6],
+        floats: [0; 8],
+        nstack: args.stack.len() as u64,
+        stack: args.stack.as_ptr(),
+        nfloat: args.floats.len() as u64,
+        func,
+        out: [0; 4],
+    };
+    f.ints[..args.ints.len()].copy_from_slice(&args.ints);
+    f.floats[..args.floats.len()].copy_from_slice(&args.floats);
+
+    // the stack arguments are copied below an aligned stack pointer, %al
+    // holds the number of SSE registers for variadic functions
+    std::arch::asm!(
+        "mov r13, rsp",
+        "mov rcx, [r12 + {nstack}]",
+        "lea rax, [rcx * 8]",
+        "sub rsp, rax",
+        "and rsp, -16",
+        "mov rsi, [r12 + {stack}]",
+        "mov rdi, rsp",
+        "rep movsq",
+        "movsd xmm0, [r12 + {floats}]",
+        "movsd xmm1, [r12 + {floats} + 8]",
+        "movsd xmm2, [r12 + {floats} + 16]",
+        "movsd xmm3, [r12 + {floats} + 24]",
+        "movsd xmm4, [r12 + {floats} + 32]",
+        "movsd xmm5, [r12 + {floats} + 40]",
+        "movsd xmm6, [r12 + {floats} + 48]",
+        "movsd xmm7, [r12 + {floats} + 56]",
+        "mov rdi, [r12 + {ints}]",
+        "mov rsi, [r12 + {ints} + 8]",
+        "mov rdx, [r12 + {ints} + 16]",
+        "mov rcx, [r12 + {ints} + 24]",
+        "mov r8, [r12 + {ints} + 32]",
+        "mov r9, [r12 + {ints} + 40]",
+        "mov rax, [r12 + {nfloat}]",
+        "call qword ptr [r12 + {func}]",
+        "mov rsp, r13",
+        "mov [r12 + {out}], rax",
+        "mov [r12 + {out} + 8], rdx",
+        "movsd [r12 + {out} + 16], xmm0",
+        "movsd [r12 + {out} + 24], xmm1",
+        ints = const std::mem::offset_of!(Regs, ints),
+        floats = const std::mem::offset_of!(Regs, floats),
+        nstack = const std::mem::offset_of!(Regs, nstack),
+        stack = const std::mem::offset_of!(Regs, stack),
+        nfloat = const std::mem::offset_of!(Regs, nfloat),
+        func = const std::mem::offset_of!(Regs, func),
+        out = const std::mem::offset_of!(Regs, out),
+        in("r12") &mut f as *mut Regs,
+        out("r13") _,
+        clobber_abi("C"),
+    );
+    Some(f.out)
+}
+
+// other targets have no calls, None
+#[cfg(not(all(target_arch = "x86_64", unix)))]
+unsafe fn invoke(_: u64, _: &Args) -> Option<[u64; 4]> {
+    None
+}
+
+#[cfg(all(test, target_arch = "x86_64", target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::{include, Engine};
+
+    const LIBRARY: &str = r#"
+#include <stdarg.h>
+#include <stdlib.h>
+#include <string.h>
+
+struct pair { int a; double b; };
+struct vec2 { float x, y; };
+struct big { long a, b, c; };
+struct buf { char *data; int len; };
+
+int add8(int a, int b, int c, int d, int e, int f, int g, int h) {
+    return a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f + 7 * g + 8 * h;
+}
+double sum9(double a, double b, double c, double d, double e, double f,
+            double g, double h, float i) {
+    return a + b + c + d + e + f + g + h + i;
+}
+double vsum(int n, ...) {
+    va_list ap;
+    double s = 0;
+    va_start(ap, n);
+    for (int i = 0; i < n; i++)
+        s += va_arg(ap, double) * va_arg(ap, int);
+    va_end(ap);
+    return s;
+}
+unsigned char low(unsigned x) { return x; }
+int twice(int x) { return 2 * x; }
+int apply(int (*f)(int), int x) { return f(x); }
+
+struct pair make_pair(int a, double b) { struct pair p = { a, b }; return p; }
+double pair_sum(struct pair p) { return p.a + p.b; }
+struct vec2 scale(struct vec2 v, float k) { v.x *= k; v.y *= k; return v; }
+struct big make_big(long x) { struct big b = { x, 2 * x, 3 * x }; return b; }
+long big_sum(struct big b) { return b.a + b.b + b.c; }
+
+int fill(struct buf *b, char c) {
+    memset(b->data, c, b->len - 1);
+    b->data[b->len - 1] = 0;
+    return b->len;
+}
+char *find(struct buf *b, char c) { return strchr(b->data, c); }
+void split(char *s, char **rest) {
+    char *p = strchr(s, ',');
+    *p = 0;
+    *rest = p + 1;
+}
+const char *version(void) { return "1.2.3"; }
+void *handle_new(int v) { int *p = malloc(sizeof *p); *p = v; return p; }
+int handle_get(void *h) { return *(int *)h; }
+"#;
+
+    // builds the test library once and loads it
+    fn library() {
+        static BUILT: Mutex<bool> = Mutex::new(false);
+        let mut built = BUILT.lock().unwrap();
+        if *built {
+            return;
+        }
+
+        let dir = std::env::temp_dir();
+        let src = dir.join(format!("crepl-ffi-{}.c", std::process::id()));
+        let lib = dir.join(format!("libcrepl-ffi-{}.so", std::process::id()));
+        std::fs::write(&src, LIBRARY).unwrap();
+        let out = std::process::Command::new("cc")
+            .args(["-shared", "-fPIC", "-o"])
+            .arg(&lib)
+            .arg(&src)
+            .output()
+            .unwrap();
+        let _ = std::fs::remove_file(&src);
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+
+        load(lib.to_str().unwrap()).unwrap();
+        let _ = std::fs::remove_file(&lib);
+        *built = true;
+    }
+
+    // runs a program with both engines, returns its output or error
+    fn run(src: &str) -> std::result::Result<String, String> {
+        library();
+
+        let dl = sema::layout::DataLayout::default();
+        let src = include::expand(src.as_bytes(), &dl).unwrap();
+        let mut p = parser::Parser::from(String::from_utf8(src).unwrap());
+        let file = p.parse_file();
+        assert!(p.errors().is_empty(), "{:?}", p.errors());
+        let info = sema::check(&file);
+        assert_eq!(0, info.errors().count(), "{:?}", info.diagnostics);
+
+        let run = |engine| {
+            let mut out = Vec::new();
+            let mut it = Interpreter::new(&file, &info).with_output(&mut out);
+            it.engine = engine;
+            let status = it.run_main(&["prog".to_string()]);
+            drop(it);
+            match status {
+                Ok(_) => Ok(String::from_utf8(out).unwrap()),
+                Err(err) => Err(err.msg),
+            }
+        };
+        let out = run(Engine::Ast);
+        assert_eq!(out, run(Engine::Vm), "the engines disagree");
+        out
+    }
+
+    #[test]
+    fn test_scalars() {
+        let out = run(r#"
+#include <stdio.h>
+
+int add8(int, int, int, int, int, int, int, int);
+double sum9(double, double, double, double, double, double, double, double, float);
+double vsum(int n, ...);
+unsigned char low(unsigned x);
+int twice(int x);
+int apply(int (*f)(int), int x);
+
+int main(void) {
+    printf("%d\n", add8(1, 2, 3, 4, 5, 6, 7, -8));
+    printf("%.2f\n", sum9(1, 2, 3, 4, 5, 6, 7, 8, 0.25f));
+    printf("%.1f\n", vsum(3, 1.5, 2, 2.0f, 3, 0.5, -4));
+    printf("%d %d\n", low(0x1ff), apply(twice, 21));
+    return 0;
+}
+"#);
+        assert_eq!(Ok("76\n36.25\n7.0\n255 42\n".to_string()), out);
+    }
+
+    #[test]
+    fn test_records() {
+        let out = run(r#"
+#include <stdio.h>
+
+struct pair { int a; double b; };
+struct vec2 { float x, y; };
+struct big { long a, b, c; };
+
+struct pair make_pair(int a, double b);
+double pair_sum(struct pair p);
+struct vec2 scale(struct vec2 v, float k);
+struct big make_big(long x);
+long big_sum(struct big b);
+
+int main(void) {
+    struct pair p = make_pair(3, 0.5);
+    struct vec2 v = { 1.5f, -2 };
+    v = scale(v, 2);
+    struct big b = make_big(5);
+    printf("%d %.1f %.1f\n", p.a, p.b, pair_sum(p));
+    printf("%.1f %.1f\n", v.x, v.y);
+    printf("%ld %ld %ld %ld\n", b.a, b.b, b.c, big_sum(b));
+    return 0;
+}
+"#);
+        assert_eq!(Ok("3 0.5 3.5\n3.0 -4.0\n5 10 15 30\n".to_string()), out);
+    }
+
+    #[test]
+    fn test_pointers() {
+        let out = run(r#"
+#include <stdio.h>
+#include <stdlib.h>
+
+struct buf { char *data; int len; };
+
+int fill(struct buf *b, char c);
+char *find(struct buf *b, char c);
+void split(char *s, char **rest);
+const char *version(void);
+void *handle_new(int v);
+int handle_get(void *h);
+
+int main(void) {
+    struct buf b = { malloc(6), 6 };
+    int n = fill(&b, 'x');
+    b.data[2] = 'y';
+    char *y = find(&b, 'y');
+    printf("%d %s %s %d\n", n, b.data, y, (int)(y - b.data));
+
+    char s[] = "key,value";
+    char *rest = NULL;
+    split(s, &rest);
+    rest[0] = 'V';
+    printf("%s %s\n", s, rest);
+
+    void *h = handle_new(7);
+    printf("%s %d\n", version(), handle_get(h));
+    free(b.data);
+    return 0;
+}
+"#);
+        assert_eq!(Ok("6 xxyxx yxx 2\nkey Value\n1.2.3 7\n".to_string()), out);
+    }
+
+    #[test]
+    fn test_errors() {
+        let tests = [
+            (
+                "void *handle_new(int v); int main(void) { return *(int *)handle_new(1); }",
+                "invalid pointer dereference",
+            ),
+            (
+                "int apply(int (*f)(int), int x); int f(int x) { return x; }
+                 int main(void) { return apply(f, 1); }",
+                "the function 'f' cannot be passed to foreign code",
+            ),
+            (
+                "double sum9(double, double, double, double, double, double, double, double, long double);
+                 int main(void) { return sum9(1, 2, 3, 4, 5, 6, 7, 8, 9); }",
+                "long double cannot be passed to foreign code",
+            ),
+            (
+                "int missing(void); int main(void) { return missing(); }",
+                "undefined reference to 'missing'",
+            ),
+        ];
+        for (i, (src, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                Err(want.to_string()),
+                run(src),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+    }
+}
+
+#[cfg(all(test, not(all(target_arch = "x86_64", unix))))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsupported() {
+        assert_eq!(Err(UNSUPPORTED.to_string()), load("libm.so.6"));
+    }
+}
@@ -0,0 +1,203 @@
+//! The `#include` directives of a program. There is no preprocessor, the
+//! headers of the library the interpreter provides are bundled and each
+//! one is pasted on the line of its first `#include`, so the lines of the
+//! program keep their numbers. Any other directive but `#pragma` is an
+//! error.
+
+use sema::layout::DataLayout;
+
+/// The bundled headers, `stddef.h` depends on the data layout.
+const HEADERS: [(&str, &str); 6] = [
+    ("assert.h", include_str!("../include/assert.h")),
+    ("ctype.h", include_str!("../include/ctype.h")),
+    ("math.h", include_str!("../include/math.h")),
+    ("stdio.h", include_str!("../include/stdio.h")),
+    ("stdlib.h", include_str!("../include/stdlib.h")),
+    ("string.h", include_str!("../include/string.h")),
+];
+
+/// Returns the text of a bundled header.
+pub fn header(name: &str, dl: &DataLayout) -> Option<String> {
+    if name == "stddef.h" {
+        return Some(format!(
+            "typedef {} size_t; typedef {} ptrdiff_t; typedef {} wchar_t; enum {{ NULL = 0 }};",
+            dl.size_t.name(),
+            dl.ptrdiff_t.name(),
+            dl.wchar_t.name()
+        ));
+    }
+
+    HEADERS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, text)| text.to_string())
+}
+
+/// Returns the names of the bundled headers.
+pub fn headers() -> impl Iterator<Item = &'static str> {
+    HEADERS
+        .iter()
+        .map(|(name, _)| *name)
+        .chain(std::iter::once("stddef.h"))
+}
+
+/// Replaces the directives of `src` by the headers they include, returns
+/// the offset and message of the first invalid one.
+pub fn expand(src: &[u8], dl: &DataLayout) -> Result<Vec<u8>, (usize, String)> {
+    let mut seen = Vec::new();
+    expand_in(src, dl, &mut seen)
+}
+
+fn expand_in(
+    src: &[u8],
+    dl: &DataLayout,
+    seen: &mut Vec<String>,
+) -> Result<Vec<u8>, (usize, String)> {
+    let mut out = Vec::with_capacity(src.len());
+    let mut comment = false; // in a block comment at the start of the line
+    let mut offset = 0;
+
+    for line in src.split_inclusive(|&b| b == b'\n') {
+        let start = offset;
+        offset += line.len();
+
+        let indent = line.iter().take_while(|b| b.is_ascii_whitespace()).count();
+        if comment || line.get(indent) != Some(&b'#') {
+            comment = ends_in_comment(line, comment);
+            out.extend_from_slice(line);
+            continue;
+        }
+
+        let text = String::from_utf8_lossy(&line[indent + 1..]);
+        let text = text.trim();
+        let name = text
+            .split(|c: char| !c.is_ascii_alphabetic())
+            .next()
+            .unwrap_or("");
+        let pos = start + indent;
+
+        match name {
+            "" | "pragma" => {}
+            "include" => {
+                let h = included(&text[name.len()..])
+                    .ok_or((pos, "expected \"FILENAME\" or <FILENAME>".to_string()))?;
+                if !seen.iter().any(|s| s == h) {
+                    let Some(text) = header(h, dl) else {
+                        return Err((pos, format!("'{}' file not found", h)));
+                    };
+                    seen.push(h.to_string());
+
+                    // the header takes the place of the directive
+                    let text =
+                        expand_in(text.as_bytes(), dl, seen).map_err(|(_, msg)| (pos, msg))?;
+                    out.extend(text.iter().map(|&b| if b == b'\n' { b' ' } else { b }));
+                }
+            }
+            _ => {
+                return Err((
+                    pos,
+                    format!("unsupported preprocessing directive '#{}'", name),
+                ))
+            }
+        }
+
+        if line.ends_with(b"\n") {
+            out.push(b'\n');
+        }
+    }
+
+    Ok(out)
+}
+
+// the name in `<name>` or `"name"`
+fn included(s: &str) -> Option<&str> {
+    let s = s.trim();
+    let (open, close) = match s.chars().next()? {
+        '<' => ('<', '>'),
+        '"' => ('"', '"'),
+        _ => return None,
+    };
+    let s = s.strip_prefix(open)?;
+    let end = s.find(close)?;
+    s[end + 1..].trim().is_empty().then_some(&s[..end])
+}
+
+// reports whether a block comment is open at the end of `line`, strings
+// and character constants are skipped
+fn ends_in_comment(line: &[u8], mut comment: bool) -> bool {
+    let mut i = 0;
+    let mut quote = None;
+
+    while i < line.len() {
+        let b = line[i];
+        let next = line.get(i + 1).copied();
+        match quote {
+            Some(_) if b == b'\\' => i += 1,
+            Some(q) if b == q => quote = None,
+            Some(_) => {}
+            None if comment => {
+                if b == b'*' && next == Some(b'/') {
+                    comment = false;
+                    i += 1;
+                }
+            }
+            None => match (b, next) {
+                (b'/', Some(b'*')) => {
+                    comment = true;
+                    i += 1;
+                }
+                (b'/', Some(b'/')) => break,
+                (b'"' | b'\'', _) => quote = Some(b),
+                _ => {}
+            },
+        }
+        i += 1;
+    }
+
+    comment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let dl = DataLayout::default();
+        let tests = [
+            ("int x;\n", Ok::<_, (usize, &str)>("int x;\n")),
+            ("#pragma once\nint x;\n", Ok("\nint x;\n")),
+            ("  #\nint x;", Ok("\nint x;")),
+            ("#include <stddef.h>\n#include \"stddef.h\"\nint x;\n", Ok(
+                "typedef unsigned long size_t; typedef long ptrdiff_t; typedef int wchar_t; enum { NULL = 0 };\n\nint x;\n",
+            )),
+            ("/*\n#define X 1\n*/ int x;\n", Ok("/*\n#define X 1\n*/ int x;\n")),
+            ("int x;\n#define X 1\n", Err((7, "unsupported preprocessing directive '#define'"))),
+            ("#include <unistd.h>\n", Err((0, "'unistd.h' file not found"))),
+            ("#include stdio.h\n", Err((0, "expected \"FILENAME\" or <FILENAME>"))),
+        ];
+
+        for (i, (src, want)) in tests.iter().enumerate() {
+            let got = expand(src.as_bytes(), &dl);
+            let got = got
+                .as_ref()
+                .map(|s| String::from_utf8_lossy(s).into_owned())
+                .map_err(|(pos, msg)| (*pos, msg.as_str()));
+            let want = want.map(|s| s.to_string());
+            assert_eq!(
+                got,
+                want,
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+
+        // the lines after a header keep their numbers
+        let src = b"#include <stdio.h>\n#include <stdlib.h>\nint x;\n";
+        let got = expand(src, &dl).unwrap();
+        assert_eq!(got.iter().filter(|&&b| b == b'\n').count(), 3);
+        assert!(got.ends_with(b"\nint x;\n"));
+    }
+}
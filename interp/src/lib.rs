@@ -0,0 +1,1605 @@
+//! A tree-walking interpreter: runs the functions of a checked translation
+//! unit on the AST, with the integer widths, conversions and struct layout
+//! of the target it was checked for. Objects live in a byte-addressable
+//! memory, see `memory`.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ast::{DeclKind, File, FuncDecl, Node, StmtKind};
+use sema::layout::{FieldLayout, RecordLayout};
+use sema::types::{ArrayLen, FloatKind, IntKind, TagId, Type, TypeKind};
+use sema::{Info, SymbolId, SymbolKind};
+use token::Token;
+
+mod builtins;
+mod check;
+mod eval;
+mod exec;
+pub mod ffi;
+pub mod include;
+pub mod memory;
+mod show;
+pub mod value;
+mod vm;
+
+use memory::{func_address, func_at, le, Fault, Memory, Region};
+use value::{Pointer, Value};
+
+/// The default limit on the depth of calls, the interpreter recurses on
+/// the host stack.
+pub const MAX_DEPTH: usize = 1000;
+
+// the steps between two checks of the time and the interrupt
+const CHECK_EVERY: u64 = 1024;
+
+/// The limits on a run of a program, None for no limit. Hitting one stops
+/// the program with an error.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub depth: usize,
+    pub steps: Option<u64>,     // calls, iterations of loops and gotos
+    pub heap: Option<u64>,      // bytes of the live blocks of malloc
+    pub time: Option<Duration>, // wall-clock time
+    pub interrupt: Option<Arc<AtomicBool>>, // stops the program once set, by another thread
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            depth: MAX_DEPTH,
+            steps: None,
+            heap: None,
+            time: None,
+            interrupt: None,
+        }
+    }
+}
+
+/// An error that stops the program, at the expression or statement that
+/// caused it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeError {
+    pub pos: usize,
+    pub msg: String,
+    pub trace: Vec<StackFrame>, // the calls active at the error, innermost first
+}
+
+/// A function on the stack and the position its execution reached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    pub func: String,
+    pub pos: usize,
+}
+
+/// Why the evaluation of a function stopped early.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    Error(RuntimeError),
+    Exit(i32), // the program called exit
+}
+
+pub(crate) type Result<T> = std::result::Result<T, Stop>;
+
+pub(crate) fn error<T>(pos: usize, msg: impl Into<String>) -> Result<T> {
+    Err(Stop::Error(RuntimeError {
+        pos,
+        msg: msg.into(),
+        trace: Vec::new(),
+    }))
+}
+
+// reports a failed memory access at `pos`
+fn fault(pos: usize) -> impl Fn(Fault) -> Stop {
+    move |f| {
+        Stop::Error(RuntimeError {
+            pos,
+            msg: f.to_string(),
+            trace: Vec::new(),
+        })
+    }
+}
+
+// the automatic objects of a call, they are freed when it returns
+struct Frame {
+    func: SymbolId,
+    call: usize, // the position of the call
+
+    locals: HashMap<SymbolId, Pointer>,
+    literals: HashMap<usize, Pointer>, // compound literals and temporaries by position
+    allocs: Vec<usize>,
+    sp: u64, // the stack pointer at the call
+}
+
+/// What a program of a REPL session leaves to the next one, which is
+/// checked again with the inputs since: its memory, the objects of its
+/// globals and the state of the library. See `Interpreter::resume`.
+pub struct State {
+    mem: Memory,
+    libc: builtins::Libc,
+    mirrors: HashMap<Vec<u8>, Pointer>,
+    globals: HashMap<String, Pointer>, // by name, `f::n` for a static n of f
+    funcs: HashMap<SymbolId, String>,  // the names of the addresses of functions
+}
+
+impl State {
+    /// Drops the object of a global redefined since, and the static
+    /// objects of a function of that name.
+    pub fn forget(&mut self, name: &str) {
+        let statics = format!("{}::", name);
+        self.globals
+            .retain(|n, _| n != name && !n.starts_with(&statics));
+    }
+}
+
+/// How the functions of a program are run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    Ast, // by walking their AST
+    #[default]
+    Vm, // on bytecode compiled on their first call, see `vm`
+}
+
+pub struct Interpreter<'a> {
+    pub limits: Limits,
+    pub checked: bool, // undefined behavior stops the program, see `check`
+    pub engine: Engine,
+
+    file: &'a File,
+    info: &'a Info,
+    out: Box<dyn Write + 'a>,
+    input: Box<dyn BufRead + 'a>,
+    libc: builtins::Libc,
+
+    mem: Memory,
+    records: Vec<Option<RecordLayout>>, // by TagId
+
+    globals: HashMap<SymbolId, Pointer>, // objects of static storage duration
+    literals: HashMap<usize, Pointer>,   // string and file scope compound literals by position
+    funcs: HashMap<SymbolId, &'a FuncDecl>,
+    carried: HashMap<String, Pointer>, // the globals of the state resumed
+    mirrors: HashMap<Vec<u8>, Pointer>, // the strings foreign functions returned, see `ffi`
+    code: HashMap<SymbolId, Rc<vm::Chunk<'a>>>, // the compiled functions
+    frames: Vec<Frame>,
+
+    steps: u64,
+    deadline: Option<Instant>,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Returns an interpreter for a file checked without errors, the
+    /// program writes to the standard output.
+    pub fn new(file: &'a File, info: &'a Info) -> Self {
+        let mut funcs = HashMap::new();
+        for d in file.decls.iter() {
+            if let DeclKind::FuncDecl(f) = d.kind() {
+                let name = f.declarator.name.as_ref();
+                if let Some(&id) = name.and_then(|n| info.decls.get(&n.pos)) {
+                    funcs.insert(id, f);
+                }
+            }
+        }
+
+        let records = (0..info.tags.len())
+            .map(|id| info.layout.record(TagId(id), &info.tags))
+            .collect();
+
+        Self {
+            limits: Limits::default(),
+            checked: false,
+            engine: Engine::default(),
+            file,
+            info,
+            out: Box::new(std::io::BufWriter::new(std::io::stdout())),
+            input: Box::new(std::io::BufReader::new(std::io::stdin())),
+            libc: builtins::Libc::default(),
+            mem: Memory::new(info.layout.pointer.size as usize),
+            records,
+            globals: HashMap::new(),
+            literals: HashMap::new(),
+            funcs,
+            carried: HashMap::new(),
+            mirrors: HashMap::new(),
+            code: HashMap::new(),
+            frames: Vec::new(),
+            steps: 0,
+            deadline: None,
+        }
+    }
+
+    /// Sends the output of the program to `out`.
+    pub fn with_output(mut self, out: impl Write + 'a) -> Self {
+        self.out = Box::new(out);
+        self
+    }
+
+    /// Reads the standard input of the program from `input`.
+    pub fn with_input(mut self, input: impl BufRead + 'a) -> Self {
+        self.input = Box::new(input);
+        self
+    }
+
+    /// Continues the program that left `state`: the globals it defined
+    /// keep their objects and values, the pointers to its functions point
+    /// to the functions of the same names.
+    pub fn resume(mut self, state: State) -> Self {
+        let mut ids = HashMap::new();
+        for (i, sym) in self.info.symbols.iter().enumerate() {
+            if sym.kind == SymbolKind::Func && self.info.scope(sym.scope).parent.is_none() {
+                ids.entry(sym.name.as_str()).or_insert(SymbolId(i));
+            }
+        }
+
+        self.mem = state.mem;
+        self.mem.relocate(|old| {
+            state
+                .funcs
+                .get(&old)
+                .and_then(|n| ids.get(n.as_str()))
+                .copied()
+        });
+        self.libc = state.libc;
+        self.mirrors = state.mirrors;
+        self.carried = state.globals;
+        self
+    }
+
+    /// Returns what the program leaves to the next one of a session.
+    pub fn into_state(self) -> State {
+        let globals = self
+            .globals
+            .iter()
+            .filter_map(|(&id, &p)| Some((self.global_name(id)?, p)))
+            .collect();
+        let funcs = (0..self.info.symbols.len())
+            .map(SymbolId)
+            .filter(|&id| self.info.symbol(id).kind == SymbolKind::Func)
+            .map(|id| (id, self.info.symbol(id).name.clone()))
+            .collect();
+
+        State {
+            mem: self.mem,
+            libc: self.libc,
+            mirrors: self.mirrors,
+            globals,
+            funcs,
+        }
+    }
+
+    /// Allocates the globals the state resumed lacks and runs their
+    /// initializers, the first step of each input of a REPL.
+    pub fn run_globals(&mut self) -> std::result::Result<(), Stop> {
+        self.steps = 0;
+        self.deadline = self.limits.time.map(|t| Instant::now() + t);
+        self.mem.checked = self.checked;
+        builtins::init(self);
+
+        let r = self.init_globals();
+        builtins::flush(self);
+        r
+    }
+
+    /// Calls a function without parameters, after `run_globals`.
+    pub fn run_function(&mut self, name: &str) -> std::result::Result<(), Stop> {
+        let id = self.defined(name)?;
+        let pos = self.funcs[&id].declarator.pos;
+
+        let r = self.call(id, Vec::new(), pos);
+        builtins::flush(self);
+        r.map(|_| ())
+    }
+
+    /// Evaluates the expression statement that is the body of the function
+    /// `name`, after `run_globals`, and shows its value with its type:
+    /// `(int) 42`. A void expression has none.
+    pub fn run_expr(&mut self, name: &str) -> std::result::Result<Option<String>, Stop> {
+        let id = self.defined(name)?;
+        let f = self.funcs[&id];
+        let Some(StmtKind::ExprStmt(s)) = f.body.stmts.first().map(|s| s.kind()) else {
+            return error(f.body.lbrace, "expected an expression");
+        };
+        let x = s.x.as_ref();
+
+        self.push_frame(id, x.start());
+        let r = self.eval(x).and_then(|v| {
+            let ty = self.type_of(x);
+            self.show(v, &ty, x.start())
+        });
+        let r = self.traced(r);
+        self.pop_frame();
+
+        builtins::flush(self);
+        r
+    }
+
+    // the function `name` defined in the file
+    fn defined(&self, name: &str) -> Result<SymbolId> {
+        match self
+            .funcs
+            .keys()
+            .copied()
+            .find(|&id| self.info.symbol(id).name == name)
+        {
+            Some(id) => Ok(id),
+            None => error(self.file.eof, format!("undefined reference to '{}'", name)),
+        }
+    }
+
+    // the name a global keeps across a session, None for a local
+    fn global_name(&self, id: SymbolId) -> Option<String> {
+        let sym = self.info.symbol(id);
+        if self.info.scope(sym.scope).parent.is_none() {
+            return Some(sym.name.clone());
+        }
+
+        let f = self
+            .funcs
+            .values()
+            .find(|f| f.start() <= sym.pos && sym.pos < f.end())?;
+        let name = f.declarator.name.as_ref()?;
+        Some(format!("{}::{}", name.name, sym.name))
+    }
+
+    // the object a global had in the program resumed
+    pub(crate) fn carried(&self, id: SymbolId) -> Option<Pointer> {
+        if self.carried.is_empty() {
+            return None;
+        }
+        self.carried.get(&self.global_name(id)?).copied()
+    }
+
+    /// Runs `main` with the command line `args`, the name of the program
+    /// first, and returns the exit status.
+    pub fn run_main(&mut self, args: &[String]) -> std::result::Result<i32, RuntimeError> {
+        self.steps = 0;
+        self.deadline = self.limits.time.map(|t| Instant::now() + t);
+        let status = self.start(args);
+        builtins::finish(self);
+
+        match status {
+            Ok(v) => Ok(v),
+            Err(Stop::Exit(v)) => Ok(v),
+            Err(Stop::Error(err)) => Err(err),
+        }
+    }
+
+    fn start(&mut self, args: &[String]) -> Result<i32> {
+        let main = self
+            .funcs
+            .keys()
+            .copied()
+            .find(|&id| self.info.symbol(id).name == "main");
+        let Some(main) = main else {
+            return error(self.file.eof, "undefined reference to 'main'");
+        };
+        let pos = self.funcs[&main].declarator.pos;
+
+        self.mem.checked = self.checked;
+        builtins::init(self);
+        self.init_globals()?;
+
+        // argv holds the arguments and a null pointer, envp is empty
+        let ptr = Type::int(IntKind::Char).pointer_to();
+        let size = self.size_of(&ptr, pos)?;
+
+        let argv = self.alloc(size * (args.len() as u64 + 1), size, Region::Global, pos)?;
+        for (i, a) in args.iter().enumerate() {
+            let mut bytes = a.as_bytes().to_vec();
+            bytes.push(0);
+
+            let s = self.alloc(bytes.len() as u64, 1, Region::Global, pos)?;
+            self.mem.write(s, &bytes).map_err(fault(pos))?;
+            self.store(
+                argv.offset(i as i64 * size as i64),
+                &ptr,
+                Value::Pointer(s),
+                pos,
+            )?;
+        }
+        let envp = self.alloc(size, size, Region::Global, pos)?;
+
+        let params = self
+            .info
+            .symbol(main)
+            .ty
+            .func()
+            .map_or(0, |f| f.params.len());
+        let args = [
+            Value::Int(args.len() as i128),
+            Value::Pointer(argv),
+            Value::Pointer(envp),
+        ];
+        let args = args[..params.min(3)].to_vec();
+
+        let v = self.call(main, args, pos)?;
+        Ok(v.as_int() as i32)
+    }
+
+    // allocates the objects of static storage duration, then runs their
+    // initializers in order, they may take the address of later ones
+    fn init_globals(&mut self) -> Result<()> {
+        let mut inits = Vec::new();
+
+        for d in self.file.decls.iter() {
+            let DeclKind::GenDecl(g) = d.kind() else {
+                continue;
+            };
+            if matches!(g.specs.storage, Some(Token::TYPEDEF)) {
+                continue;
+            }
+
+            for d in g.decls.iter() {
+                let name = d.declarator.name.as_ref();
+                let Some(&id) = name.and_then(|n| self.info.decls.get(&n.pos)) else {
+                    continue;
+                };
+                let sym = self.info.symbol(id);
+                if sym.kind != SymbolKind::Var {
+                    continue;
+                }
+                if g.specs.storage == Some(Token::EXTERN) && d.init.is_none() {
+                    continue;
+                }
+
+                if !self.globals.contains_key(&id) {
+                    if let Some(p) = self.carried(id) {
+                        // initialized by the program resumed
+                        self.globals.insert(id, p);
+                        continue;
+                    }
+                    let p = self.alloc_object(&sym.ty, Region::Global, d.declarator.pos)?;
+                    self.globals.insert(id, p);
+                }
+                if let Some(x) = &d.init {
+                    inits.push((id, x.as_ref()));
+                }
+            }
+        }
+
+        for (id, x) in inits {
+            let ty = &self.info.symbol(id).ty;
+            let p = self.globals[&id];
+            self.init(p.into(), ty, x)?;
+        }
+
+        Ok(())
+    }
+
+    fn layout(&self) -> &'a sema::layout::DataLayout {
+        &self.info.layout
+    }
+
+    // the type of a checked expression
+    fn type_of(&self, x: &dyn ast::Expr) -> Type {
+        self.info.type_of(x).cloned().unwrap_or_default()
+    }
+
+    /// The size of a complete type, an array of unknown length has none.
+    fn size_of(&self, ty: &Type, pos: usize) -> Result<u64> {
+        match &ty.kind {
+            TypeKind::Array(elem, ArrayLen::Fixed(n)) => Ok(n * self.size_of(elem, pos)?),
+            TypeKind::Array(_, ArrayLen::Incomplete) => Ok(0),
+            TypeKind::Struct(id) | TypeKind::Union(id) => match &self.records[id.0] {
+                Some(r) => Ok(r.size),
+                None => error(pos, format!("incomplete type '{}'", self.info.spell(ty))),
+            },
+            _ => match self.layout().size_of(ty, &self.info.tags) {
+                Some(size) => Ok(size),
+                None if ty.is_vla() => {
+                    error(pos, "variable length array types are not supported here")
+                }
+                None => error(pos, format!("invalid type '{}'", self.info.spell(ty))),
+            },
+        }
+    }
+
+    /// Finds a member of a struct or union, in anonymous members too.
+    fn member(&self, id: TagId, name: &str) -> Option<FieldLayout> {
+        for f in self.records[id.0].as_ref()?.fields.iter() {
+            match (&f.name, &f.ty.kind) {
+                (Some(n), _) if n == name => return Some(f.clone()),
+                (None, TypeKind::Struct(inner) | TypeKind::Union(inner)) => {
+                    if let Some(mut m) = self.member(*inner, name) {
+                        m.offset += f.offset;
+                        return Some(m);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    fn align_of(&self, ty: &Type) -> u64 {
+        match &ty.kind {
+            TypeKind::Array(elem, _) => self.align_of(elem),
+            TypeKind::Struct(id) | TypeKind::Union(id) => {
+                self.records[id.0].as_ref().map_or(1, |r| r.align)
+            }
+            _ => self.layout().align_of(ty, &self.info.tags).unwrap_or(1),
+        }
+    }
+
+    /// Allocates `size` zero bytes, an allocation on the stack belongs to
+    /// the current call.
+    fn alloc(&mut self, size: u64, align: u64, region: Region, pos: usize) -> Result<Pointer> {
+        if let Some(max) = self.limits.heap.filter(|_| region == Region::Heap) {
+            if self.mem.heap.saturating_add(size) > max {
+                return error(
+                    pos,
+                    format!(
+                        "heap limit exceeded: {} bytes requested with {} in use, the limit is {}",
+                        size, self.mem.heap, max
+                    ),
+                );
+            }
+        }
+        let Some(p) = self.mem.alloc(size, align, region) else {
+            return error(pos, "stack overflow");
+        };
+        if region == Region::Stack {
+            if let Some(f) = self.frames.last_mut() {
+                f.allocs.push(p.alloc);
+            }
+        }
+        Ok(p)
+    }
+
+    // allocates a zeroed object of a complete type
+    fn alloc_object(&mut self, ty: &Type, region: Region, pos: usize) -> Result<Pointer> {
+        let size = self.size_of(ty, pos)?;
+        self.alloc(size, self.align_of(ty), region, pos)
+    }
+
+    /// Loads a value of type `ty` from memory, an array is not a value.
+    fn load(&self, p: Pointer, ty: &Type, pos: usize) -> Result<Value> {
+        let read = |n: u64| self.mem.read(p, n as usize).map_err(fault(pos));
+
+        match &ty.kind {
+            TypeKind::Int(_) | TypeKind::Enum(_) => {
+                let k = ty.int_kind().unwrap();
+                let raw = le(read(self.size_of(ty, pos)?)?);
+                Ok(Value::Int(self.layout().wrap(raw as i128, k)))
+            }
+            TypeKind::Float(FloatKind::Float) => {
+                let raw = le(read(4)?) as u32;
+                Ok(Value::Float(f32::from_bits(raw) as f64))
+            }
+            TypeKind::Float(_) => Ok(Value::Float(f64::from_bits(le(read(8)?) as u64))),
+            TypeKind::Pointer(_) => {
+                let q = self.mem.read_pointer(p).map_err(fault(pos))?;
+                match func_at(q.addr) {
+                    Some(id) if q.alloc == 0 => Ok(Value::Func(id)),
+                    _ => Ok(Value::Pointer(q)),
+                }
+            }
+            TypeKind::Struct(_) | TypeKind::Union(_) => {
+                let size = self.size_of(ty, pos)?;
+                let r = self.mem.snapshot(p, size as usize).map_err(fault(pos))?;
+                Ok(Value::Record(Rc::new(r)))
+            }
+            _ => error(
+                pos,
+                format!("cannot load a value of type '{}'", self.info.spell(ty)),
+            ),
+        }
+    }
+
+    /// Stores a value converted to type `ty` in memory.
+    fn store(&mut self, p: Pointer, ty: &Type, v: Value, pos: usize) -> Result<()> {
+        let r = match (&ty.kind, v) {
+            (TypeKind::Pointer(_), v) => {
+                let q = match v {
+                    Value::Pointer(q) => q,
+                    Value::Func(id) => Pointer {
+                        alloc: 0,
+                        addr: func_address(id),
+                    },
+                    v => Pointer {
+                        alloc: 0,
+                        addr: v.as_int() as u64,
+                    },
+                };
+                self.mem.write_pointer(p, q)
+            }
+            (TypeKind::Struct(_) | TypeKind::Union(_), Value::Record(r)) => self.mem.restore(p, &r),
+            (TypeKind::Float(FloatKind::Float), v) => {
+                let f = v.convert(ty, self.layout());
+                self.mem.write(p, &(f.as_float() as f32).to_le_bytes())
+            }
+            (TypeKind::Float(_), v) => {
+                let f = v.convert(ty, self.layout()).as_float();
+                let mut bytes = f.to_le_bytes().to_vec();
+                bytes.resize(self.size_of(ty, pos)? as usize, 0);
+                self.mem.write(p, &bytes)
+            }
+            (TypeKind::Int(_) | TypeKind::Enum(_), v) => {
+                let n = self.size_of(ty, pos)? as usize;
+                let v = v.convert(ty, self.layout()).as_int();
+                self.mem.write(p, &v.to_le_bytes()[..n])
+            }
+            _ => {
+                return error(
+                    pos,
+                    format!("cannot store a value of type '{}'", self.info.spell(ty)),
+                )
+            }
+        };
+
+        r.map_err(fault(pos))
+    }
+
+    /// Calls a function with arguments converted to its parameter types.
+    fn call(&mut self, id: SymbolId, args: Vec<Value>, pos: usize) -> Result<Value> {
+        let sym = self.info.symbol(id);
+        let Some(&f) = self.funcs.get(&id) else {
+            let ret = sym.ty.func().map_or_else(Type::default, |f| f.ret.clone());
+            return match builtins::call(self, &sym.name, &args, &ret, pos) {
+                // a function declared implicitly returns int, not a pointer
+                Some(Ok(Value::Pointer(p))) => Ok(Value::Pointer(p)),
+                Some(v) => v.map(|v| v.convert(&ret, self.layout())),
+                None => match self.call_foreign(id, &args, pos) {
+                    Some(v) => v,
+                    None => error(pos, format!("undefined reference to '{}'", sym.name)),
+                },
+            };
+        };
+
+        if self.frames.len() >= self.limits.depth {
+            return error(pos, "call depth limit exceeded");
+        }
+        self.tick(pos)?;
+
+        let ret = sym.ty.func().map_or_else(Type::default, |f| f.ret.clone());
+        let params = f.declarator.function().map_or(&[][..], |f| &f.params);
+
+        self.push_frame(id, pos);
+        let flow = self
+            .bind(params, args, pos)
+            .and_then(|_| match self.engine {
+                Engine::Ast => self.body(&f.body),
+                Engine::Vm => self.run(id, f),
+            });
+        let flow = self.traced(flow);
+        self.pop_frame();
+
+        match flow? {
+            Some(v) => Ok(v.convert(&ret, self.layout())),
+            None if ret.is_void() => Ok(Value::Void),
+            None => Ok(Value::zero(&ret)), // main returns 0
+        }
+    }
+
+    fn push_frame(&mut self, func: SymbolId, call: usize) {
+        self.frames.push(Frame {
+            func,
+            call,
+            locals: HashMap::new(),
+            literals: HashMap::new(),
+            allocs: Vec::new(),
+            sp: self.mem.sp,
+        });
+    }
+
+    // frees the automatic objects of the innermost call
+    fn pop_frame(&mut self) {
+        let frame = self.frames.pop().unwrap();
+        for id in frame.allocs {
+            self.mem.free(id);
+        }
+        self.mem.sp = frame.sp;
+    }
+
+    // adds the calls on the stack to an error raised in the innermost one
+    fn traced<T>(&self, r: Result<T>) -> Result<T> {
+        match r {
+            Err(Stop::Error(mut err)) if err.trace.is_empty() => {
+                err.trace = self.backtrace(err.pos);
+                Err(Stop::Error(err))
+            }
+            r => r,
+        }
+    }
+
+    /// Counts a step: a call, an iteration of a loop or a goto. The time
+    /// and the interrupt are checked every `CHECK_EVERY` steps.
+    pub(crate) fn tick(&mut self, pos: usize) -> Result<()> {
+        self.steps += 1;
+        if let Some(max) = self.limits.steps.filter(|&max| self.steps > max) {
+            return error(pos, format!("step limit exceeded ({} steps)", max));
+        }
+        if !self.steps.is_multiple_of(CHECK_EVERY) {
+            return Ok(());
+        }
+
+        if let (Some(deadline), Some(time)) = (self.deadline, self.limits.time) {
+            if Instant::now() >= deadline {
+                return error(pos, format!("time limit exceeded ({:?})", time));
+            }
+        }
+        match &self.limits.interrupt {
+            Some(flag) if flag.load(Ordering::Relaxed) => error(pos, "interrupted"),
+            _ => Ok(()),
+        }
+    }
+
+    // the functions on the stack, the innermost one is at `pos`
+    fn backtrace(&self, pos: usize) -> Vec<StackFrame> {
+        let mut trace = Vec::new();
+        let mut pos = pos;
+
+        for f in self.frames.iter().rev() {
+            trace.push(StackFrame {
+                func: self.info.symbol(f.func).name.clone(),
+                pos,
+            });
+            pos = f.call;
+        }
+        trace
+    }
+
+    // stores the arguments of a call in the objects of the parameters
+    fn bind(&mut self, params: &'a [ast::ParamDecl], args: Vec<Value>, pos: usize) -> Result<()> {
+        let mut args = args.into_iter();
+
+        for p in params.iter() {
+            let Some(name) = &p.declarator.name else {
+                continue;
+            };
+            let Some(&param) = self.info.decls.get(&name.pos) else {
+                continue;
+            };
+            let Some(v) = args.next() else {
+                let sym = self.info.symbol(param);
+                return error(
+                    pos,
+                    format!("too few arguments for parameter '{}'", sym.name),
+                );
+            };
+
+            let ty = &self.info.symbol(param).ty;
+            let obj = self.alloc_object(ty, Region::Stack, name.pos)?;
+            self.store(obj, ty, v.convert(ty, self.layout()), name.pos)?;
+            self.frames.last_mut().unwrap().locals.insert(param, obj);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(src: &str, args: &[&str]) -> std::result::Result<(i32, String), String> {
+        run_with(src, args, false).map_err(|(err, _)| err)
+    }
+
+    // runs a program, returns its exit status and output or the runtime
+    // error as `line:column: message` and its stack trace
+    fn run_with(
+        src: &str,
+        args: &[&str],
+        checked: bool,
+    ) -> std::result::Result<(i32, String), (String, Vec<String>)> {
+        run_input(src, args, "", checked)
+    }
+
+    // runs a program reading `input` from stdin
+    fn run_input(
+        src: &str,
+        args: &[&str],
+        input: &str,
+        checked: bool,
+    ) -> std::result::Result<(i32, String), (String, Vec<String>)> {
+        let limits = Limits {
+            depth: 100,
+            ..Limits::default()
+        };
+        run_limited(src, args, input, checked, limits)
+    }
+
+    // runs a program with limits
+    fn run_limited(
+        src: &str,
+        args: &[&str],
+        input: &str,
+        checked: bool,
+        limits: Limits,
+    ) -> std::result::Result<(i32, String), (String, Vec<String>)> {
+        let src = src.to_string();
+        let input = input.to_string();
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+
+        // the test threads have too small a stack for deep recursion
+        std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(move || exec(src, &args, &input, checked, limits))
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    fn exec(
+        src: String,
+        args: &[String],
+        input: &str,
+        checked: bool,
+        limits: Limits,
+    ) -> std::result::Result<(i32, String), (String, Vec<String>)> {
+        let src = include::expand(src.as_bytes(), &sema::layout::DataLayout::default()).unwrap();
+        let mut p = parser::Parser::from(String::from_utf8(src).unwrap());
+        let file = p.parse_file();
+        assert!(p.errors().is_empty(), "{:?}", p.errors());
+
+        let info = sema::check(&file);
+        assert_eq!(0, info.errors().count(), "{:?}", info.diagnostics);
+
+        // both engines run the program, with the same results
+        let run = |engine| {
+            let mut out = Vec::new();
+            let mut it = Interpreter::new(&file, &info)
+                .with_output(&mut out)
+                .with_input(input.as_bytes());
+            it.limits = limits.clone();
+            it.checked = checked;
+            it.engine = engine;
+            let status = it.run_main(args);
+            drop(it);
+            (status, out)
+        };
+        let (status, out) = run(Engine::Ast);
+        let vm = run(Engine::Vm);
+        assert_eq!((&status, &out), (&vm.0, &vm.1), "the engines disagree");
+
+        match status {
+            Ok(v) => Ok((v, String::from_utf8_lossy(&out).into_owned())),
+            Err(err) => {
+                let at = |pos| {
+                    let pos = p.position(pos);
+                    format!("{}:{}", pos.line, pos.column)
+                };
+                let trace = err
+                    .trace
+                    .iter()
+                    .map(|f| format!("{} {}", f.func, at(f.pos)))
+                    .collect();
+                Err((format!("{}: {}", at(err.pos), err.msg), trace))
+            }
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let tests = [
+            ("int main(void) { return 42; }", 42),
+            ("int main() { }", 0),
+            (
+                "int fib(int n) { return n < 2 ? n : fib(n - 1) + fib(n - 2); }\n\
+                 int main(void) { return fib(15); }",
+                610,
+            ),
+            (
+                "int main(void) { int s = 0; for (int i = 1; i <= 10; i++) s += i; return s; }",
+                55,
+            ),
+            (
+                "int main(void) { int i = 0, n = 0; while (1) { if (++i > 9) break; if (i % 2) continue; n += i; } return n; }",
+                20,
+            ),
+            (
+                "int main(void) { int n = 0; do n += 3; while (n < 10); return n; }",
+                12,
+            ),
+            // fallthrough, default in the middle and nested switches
+            (
+                "int f(int x) { int r = 0; switch (x) { case 1: r += 1; case 2: r += 2; break; default: r = 100; case 3: r += 3; } return r; }\n\
+                 int main(void) { return f(1) * 1000000 + f(2) * 10000 + f(3) * 100 + f(7) - 103; }",
+                3020300,
+            ),
+            (
+                "int main(void) { int r = 0; for (int i = 0; i < 3; i++) switch (i) { case 0: switch (i + 1) { case 1: r += 10; } break; case 1: continue; case 2: r += 1; } return r; }",
+                11,
+            ),
+            (
+                "int main(void) { int i = 0, s = 0; again: s += i; if (++i < 5) goto again; return s; }",
+                10,
+            ),
+            // jumps into a loop body and out of nested blocks
+            (
+                "int main(void) { int n = 0; goto in; while (n < 10) { n += 100; in: n++; { if (n > 200) goto out; } } out: return n; }",
+                102,
+            ),
+            (
+                "int count; int step = 5;\n\
+                 void tick(void) { static int calls; calls++; count += step * calls; }\n\
+                 int main(void) { tick(); tick(); tick(); return count; }",
+                30,
+            ),
+            // the usual arithmetic conversions and wrapping
+            ("int main(void) { unsigned u = 0; return (u - 1) > 0 && -1 < 0; }", 1),
+            ("int main(void) { return -1 < 0u; }", 0),
+            ("int main(void) { unsigned char c = 255; c++; return c; }", 0),
+            ("int main(void) { signed char c = (signed char)200; return c; }", -56),
+            ("int main(void) { unsigned u = 1u << 31; return (int)(u >> 31) + (-8 >> 1); }", -3),
+            ("int main(void) { return 7 / 2 + -7 / 2 + -7 % 3; }", -1),
+            ("int main(void) { double d = 7 / 2.0; return d * 10; }", 35),
+            ("int main(void) { float f = 0.1f; return f == 0.1; }", 0),
+            ("int main(void) { int x = 3.99; return x + (int)-2.5; }", 1),
+            ("int main(void) { return !!256 + (0.5 && 1) + !0.0; }", 3),
+            ("int main(void) { long long x = 1LL << 40; return (int)(x >> 38); }", 4),
+            (
+                "int main(void) { int a[5] = {1, 2, [4] = 5}, *p = a, s = 0; while (p < a + 5) s = s * 10 + *p++; return s; }",
+                12005,
+            ),
+            (
+                "int main(void) { int m[2][3] = {{1, 2, 3}, {4, 5, 6}}; int *p = &m[1][0]; return m[1][2] * 10 + p[-1] + (&m[1][2] - &m[0][0]); }",
+                68,
+            ),
+            (
+                "void swap(int *a, int *b) { int t = *a; *a = *b; *b = t; }\n\
+                 int main(void) { int x = 1, y = 2; swap(&x, &y); return x * 10 + y; }",
+                21,
+            ),
+            (
+                "int twice(int x) { return 2 * x; }\n\
+                 int apply(int (*f)(int), int x) { return f(x) + (*f)(x); }\n\
+                 int main(void) { return apply(twice, 5); }",
+                20,
+            ),
+            (
+                "int main(void) { char s[] = \"hello\"; int n = 0; while (s[n]) n++; return n + sizeof s; }",
+                11,
+            ),
+            ("int main(void) { int n = 4; int a[n]; a[3] = 7; return a[3] + sizeof a; }", 23),
+            ("int main(void) { int x = 5; int *p = &(int){3}; return x ? *p : 0, x; }", 5),
+            ("int f(void); int main(void) { return f(); } int f(void) { return 9; }", 9),
+            ("int main(void) { return g(4); } int g(int x) { return x * x; }", 16),
+            ("enum { A = 3, B }; int main(void) { return A * B; }", 12),
+        ];
+
+        for (i, (src, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                Ok((*want, String::new())),
+                run(src, &[]),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn test_main() {
+        let src = "int main(int argc, char **argv) {\n\
+                   for (int i = 0; i < argc; i++) { for (char *s = argv[i]; *s; s++) putchar(*s); putchar('\\n'); }\n\
+                   if (argv[argc] == 0) exit(argc + 1);\n\
+                   return 0; }";
+        assert_eq!(
+            Ok((4, "prog\na\nbc\n".to_string())),
+            run(src, &["prog", "a", "bc"])
+        );
+
+        let src = "int main(void) { putchar('x'); abort(); return 0; }";
+        assert_eq!(Ok((134, "x".to_string())), run(src, &[]));
+
+        // a failed assert aborts too
+        let src = "#include <assert.h>\nint main(void) { putchar('x'); assert(1); assert(1 > 2); return 0; }";
+        assert_eq!(Ok((134, "x".to_string())), run(src, &[]));
+    }
+
+    #[test]
+    fn test_memory() {
+        let tests = [
+            (
+                "struct P { int x; char c; double d; };\n\
+                 int main(void) { struct P p = {1, 'a', 2.5}; struct P q = p; q.x = 5; return sizeof(struct P) + p.x * 100 + q.x * 1000 + (int)(q.d * 2); }",
+                5121,
+            ),
+            // offsetof and the addresses of members agree with the layout
+            (
+                "struct S { char a; int b; short c; long long d; char e; };\n\
+                 int main(void) { struct S s; char *p = (char *)&s; return (offsetof(struct S, d) == (char *)&s.d - p) * 100 + ((char *)&s.e - p) + sizeof s * 1000; }",
+                32124,
+            ),
+            (
+                "union U { unsigned u; unsigned char b[4]; };\n\
+                 int main(void) { union U x; x.u = 0x01020304; return x.b[0] * 10 + x.b[3] + sizeof x; }",
+                45,
+            ),
+            (
+                "struct B { unsigned a : 3; int b : 4; unsigned c : 9; };\n\
+                 int main(void) { struct B s = {9, 7, 511}; s.b += 1; unsigned char *p = (unsigned char *)&s; return s.a * 1000 + (s.b == -8) * 100 + (s.c == 511) * 10 + sizeof s + p[0] % 8; }",
+                1115,
+            ),
+            ("int main(void) { int x = 0; char *p = (char *)&x; p[1] = 1; return x; }", 256),
+            (
+                "int main(void) { int x = 42; unsigned long a = (unsigned long)&x; return *(int *)a; }",
+                42,
+            ),
+            (
+                "void *memcpy(void *, const void *, unsigned long);\n\
+                 struct P { int a; int *p; };\n\
+                 int main(void) { int v = 7; struct P s = {3, &v}, t; memcpy(&t, &s, sizeof s); *t.p += t.a; return v; }",
+                10,
+            ),
+            (
+                "void *malloc(unsigned long); void free(void *);\n\
+                 struct N { int v; struct N *next; };\n\
+                 int main(void) { struct N *h = 0; for (int i = 1; i <= 4; i++) { struct N *n = malloc(sizeof *n); n->v = i; n->next = h; h = n; }\n\
+                 int s = 0; while (h) { struct N *n = h; s = s * 10 + n->v; h = n->next; free(n); } return s; }",
+                4321,
+            ),
+            (
+                "void *calloc(unsigned long, unsigned long); void *realloc(void *, unsigned long);\n\
+                 int main(void) { int *a = calloc(2, sizeof(int)); a[1] = 5; a = realloc(a, 4 * sizeof(int)); a[3] = 2; return a[0] + a[1] + a[3]; }",
+                7,
+            ),
+            (
+                "struct P { int x, y; };\n\
+                 struct P mk(int a) { struct P p = { .y = a, .x = a * 2 }; return p; }\n\
+                 int main(void) { return mk(3).x * 10 + mk(4).y; }",
+                64,
+            ),
+            // brace elision, anonymous members and designators
+            (
+                "struct Q { int a[2]; struct { int b, c; }; };\n\
+                 int main(void) { struct Q q[2] = { 1, 2, 3, 4, [1].c = 9 }; return q[0].a[1] * 1000 + q[0].c * 100 + q[1].b * 10 + q[1].c; }",
+                2409,
+            ),
+            (
+                "struct S { char name[4]; int n; };\n\
+                 int main(void) { struct S s = {\"ab\", 3}; return s.name[1] + s.n + s.name[3]; }",
+                101,
+            ),
+            (
+                "struct O { int (*f)(int); }; int sq(int x) { return x * x; }\n\
+                 int main(void) { struct O o = { sq }; return o.f(5); }",
+                25,
+            ),
+        ];
+
+        for (i, (src, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                Ok((*want, String::new())),
+                run(src, &[]),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let tests = [
+            (
+                "int main(void) { int z = 0; return 1 / z; }",
+                "1:38: division by zero",
+            ),
+            (
+                "int main(void) { int *p = 0; return *p; }",
+                "1:37: null pointer dereference",
+            ),
+            (
+                "int main(void) { int a[2]; return a[2]; }",
+                "1:35: out of bounds access",
+            ),
+            (
+                "int main(void) { char *s = \"ab\"; s[0] = 'x'; return 0; }",
+                "1:34: write to a string literal",
+            ),
+            (
+                "int f(void); int main(void) { return f(); }",
+                "1:38: undefined reference to 'f'",
+            ),
+            (
+                "int f(int n) { return f(n + 1); } int main(void) { return f(0); }",
+                "1:23: call depth limit exceeded",
+            ),
+            ("int x;", "1:7: undefined reference to 'main'"),
+            (
+                "void *malloc(unsigned long); void free(void *);\n\
+                 int main(void) { int *p = malloc(4); free(p); return *p; }",
+                "2:54: access to an object whose lifetime has ended",
+            ),
+            (
+                "void *malloc(unsigned long); void free(void *);\n\
+                 int main(void) { int *p = malloc(4); free(p); free(p); return 0; }",
+                "2:47: double free",
+            ),
+            (
+                "int *f(void) { int x = 1; return &x; } int main(void) { return *f(); }",
+                "1:64: access to an object whose lifetime has ended",
+            ),
+            (
+                "struct P { int x, y; }; int main(void) { struct P p; int *q = &p.y; return q[1]; }",
+                "1:76: out of bounds access",
+            ),
+            (
+                "void *memset(void *, int, unsigned long);\n\
+                 int main(void) { char a[4]; memset(a, 0, -1); return 0; }",
+                "2:29: out of bounds access",
+            ),
+            (
+                "char *strncpy(char *, const char *, unsigned long);\n\
+                 int main(void) { char a[4]; strncpy(a, \"ab\", 1ul << 40); return 0; }",
+                "2:29: out of bounds access",
+            ),
+        ];
+
+        for (i, (src, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                Err(want.to_string()),
+                run(src, &[]),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let interrupt = Arc::new(AtomicBool::new(true));
+        let tests = [
+            (
+                "int main(void) { int n = 0; while (1) n++; }",
+                Limits {
+                    steps: Some(1000),
+                    ..Limits::default()
+                },
+                "1:29: step limit exceeded (1000 steps)",
+                vec!["main 1:29"],
+            ),
+            (
+                "int f(int n) { if (n) goto again; again: return f(n + 1); }\n\
+                 int main(void) { return f(0); }",
+                Limits {
+                    steps: Some(51),
+                    ..Limits::default()
+                },
+                "1:23: step limit exceeded (51 steps)",
+                ["f 1:23"]
+                    .into_iter()
+                    .chain(["f 1:49"; 25])
+                    .chain(["main 2:25"])
+                    .collect(),
+            ),
+            (
+                "void *malloc(unsigned long);\n\
+                 int main(void) { for (;;) malloc(1000); }",
+                Limits {
+                    heap: Some(10000),
+                    ..Limits::default()
+                },
+                "2:27: heap limit exceeded: 1000 bytes requested with 10000 in use, the limit is 10000",
+                vec!["main 2:27"],
+            ),
+            (
+                "int spin(void) { do {} while (1); } int main(void) { return spin(); }",
+                Limits {
+                    time: Some(Duration::from_millis(20)),
+                    ..Limits::default()
+                },
+                "1:18: time limit exceeded (20ms)",
+                vec!["spin 1:18", "main 1:61"],
+            ),
+            (
+                "int main(void) { for (;;); }",
+                Limits {
+                    interrupt: Some(interrupt.clone()),
+                    ..Limits::default()
+                },
+                "1:18: interrupted",
+                vec!["main 1:18"],
+            ),
+        ];
+
+        let n = tests.len();
+        for (i, (src, limits, want, trace)) in tests.into_iter().enumerate() {
+            let trace = trace.into_iter().map(str::to_string).collect();
+            assert_eq!(
+                Err((want.to_string(), trace)),
+                run_limited(src, &[], "", false, limits),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                n,
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn test_checked() {
+        let tests = [
+            (
+                "int main(void) { int x = 2147483647; return x + 1; }",
+                "1:47: signed integer overflow: 2147483647 + 1 cannot be represented in type 'int'",
+            ),
+            (
+                "int main(void) { long x = -9223372036854775807L - 1; return -x == 0; }",
+                "1:61: negation of -9223372036854775808 cannot be represented in type 'long'",
+            ),
+            (
+                "int main(void) { int x = -2147483647 - 1, y = -1; return x / y; }",
+                "1:60: division of -2147483648 by -1 cannot be represented in type 'int'",
+            ),
+            (
+                "int main(void) { int i = 2147483647; i++; return 0; }",
+                "1:38: signed integer overflow: 2147483647 + 1 cannot be represented in type 'int'",
+            ),
+            (
+                "int main(void) { int n = 32; return 1 << n; }",
+                "1:39: shift exponent 32 is too large for 32-bit type 'int'",
+            ),
+            (
+                "int main(void) { int n = -1; return 1 >> n; }",
+                "1:39: shift exponent -1 is negative",
+            ),
+            (
+                "int main(void) { int x = -1; return x << 1; }",
+                "1:39: left shift of negative value -1",
+            ),
+            (
+                "int main(void) { int x = 1; return x << 31; }",
+                "1:38: left shift of 1 by 31 places cannot be represented in type 'int'",
+            ),
+            (
+                "int main(void) { int x; return x; }",
+                "1:32: read of uninitialized memory",
+            ),
+            (
+                "void *malloc(unsigned long);\n\
+                 int main(void) { int *p = malloc(2 * sizeof(int)); p[0] = 1; return p[0] + p[1]; }",
+                "2:76: read of uninitialized memory",
+            ),
+            (
+                "struct P { int x, y; }; int main(void) { struct P p; p.x = 1; struct P q = p; return q.y; }",
+                "1:86: read of uninitialized memory",
+            ),
+            (
+                "int main(void) { char b[8] = {0}; int *p = (int *)(b + 1); return *p; }",
+                "1:67: misaligned load of address 0x7fffeff9 for type 'int', which requires 4 byte alignment",
+            ),
+            (
+                "int main(void) { long b[2]; int *p = (int *)((char *)b + 2); *p = 1; return 0; }",
+                "1:62: misaligned store of address 0x7fffeff2 for type 'int', which requires 4 byte alignment",
+            ),
+            (
+                "int main(void) { float f = 1.0f; return *(int *)&f; }",
+                "1:41: strict aliasing violation: load of type 'int' from an object of type 'float'",
+            ),
+            (
+                "void *malloc(unsigned long);\n\
+                 int main(void) { long *p = malloc(8); *p = 1; return *(double *)p > 0; }",
+                "2:54: strict aliasing violation: load of type 'double' from an object of type 'long'",
+            ),
+        ];
+
+        for (i, (src, want)) in tests.iter().enumerate() {
+            let got = run_with(src, &[], true).map_err(|(err, _)| err);
+            assert_eq!(
+                Err(want.to_string()),
+                got,
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+            assert!(
+                run(src, &[]).is_ok(),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+
+        // what is defined passes the checks
+        let tests = [
+            ("union U { float f; unsigned u; }; int main(void) { union U x; x.f = 1.0f; return x.u >> 23; }", 127),
+            ("int main(void) { int x = 0x01020304; unsigned char *p = (unsigned char *)&x; return p[0] + p[3]; }", 5),
+            ("int main(void) { unsigned u = 4294967295u; u++; return u + (-2147483647 - 1 < 0); }", 1),
+            ("int main(void) { signed char c = 127; c++; return c; }", -128),
+            (
+                "void *memcpy(void *, const void *, unsigned long);\n\
+                 int main(void) { float f = 1.0f; unsigned u; memcpy(&u, &f, sizeof u); return u >> 23; }",
+                127,
+            ),
+            ("struct S { char c; int n; }; int main(void) { struct S a = {1, 2}, b; b = a; return b.c + b.n; }", 3),
+            ("int main(void) { unsigned x = 1; return (x << 31) >> 31; }", 1),
+        ];
+
+        for (i, (src, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                Ok((*want, String::new())),
+                run_with(src, &[], true),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn test_trace() {
+        let src = "void *malloc(unsigned long); void free(void *);\n\
+                   void release(int *p) { free(p); }\n\
+                   void twice(int *p) { release(p);\n release(p); }\n\
+                   int main(void) { twice(malloc(4)); return 0; }";
+        let want = (
+            "2:24: double free".to_string(),
+            vec![
+                "release 2:24".to_string(),
+                "twice 4:2".to_string(),
+                "main 5:18".to_string(),
+            ],
+        );
+        assert_eq!(Err(want), run_with(src, &[], true));
+    }
+
+    #[test]
+    fn test_libc() {
+        let tests = [
+            (
+                "#include <stdio.h>\n\
+                 int main(void) { printf(\"[%5d|%-4d|%03d|%+d|% d|%x|%#X|%#o|%.3d|%.0d]\\n\", 42, 7, 5, 1, 2, 255, 255, 8, 4, 0); return 0; }",
+                "",
+                "[   42|7   |005|+1| 2|ff|0XFF|010|004|]\n",
+            ),
+            (
+                "#include <stdio.h>\n\
+                 int main(void) { printf(\"%hhd %hu %ld %lld %zu %lu\\n\", 300, -1, -5L, 1LL << 40, sizeof(int), -1UL); return 0; }",
+                "",
+                "44 65535 -5 1099511627776 4 18446744073709551615\n",
+            ),
+            (
+                "#include <stdio.h>\n\
+                 int main(void) { printf(\"%f %.2f %8.3f %e %.0e %g %g %g %G %#g %a\\n\", 3.5, 2.675, -1.0, 12345.678, 5e10, 0.0001, 1e-5, 123456789.0, 1e100, 2.0, 0.5); return 0; }",
+                "",
+                "3.500000 2.67   -1.000 1.234568e+04 5e+10 0.0001 1e-05 1.23457e+08 1E+100 2.00000 0x1p-1\n",
+            ),
+            (
+                "#include <stdio.h>\n\
+                 int main(void) { printf(\"%s|%6s|%-6s|%.2s|%c|%%|%p|%*d|%-*d|\\n\", \"ab\", \"cd\", \"ef\", \"ghij\", 'k', (void *)0, 3, 1, 3, 2); return 0; }",
+                "",
+                "ab|    cd|ef    |gh|k|%|(nil)|  1|2  |\n",
+            ),
+            (
+                "#include <stdio.h>\n#include <string.h>\n\
+                 int main(void) { char b[8]; int n = snprintf(b, sizeof b, \"%d-%s\", 12345, \"xyz\"); int m; sprintf(b, \"%d%n\", 99, &m); printf(\"%d %d %s %d\\n\", n, (int)strlen(b), b, m); return 0; }",
+                "",
+                "9 2 99 2\n",
+            ),
+            (
+                "#include <stdio.h>\n\
+                 int main(void) { int n, s = 0; while (scanf(\"%d\", &n) == 1) s += n; printf(\"%d %d\\n\", s, getchar()); return 0; }",
+                "1 2\n 3 x",
+                "6 120\n",
+            ),
+            (
+                "#include <stdio.h>\n\
+                 int main(void) { char w[8], c; double d; int x, k = scanf(\"%7s %c%lf %x\", w, &c, &d, &x); printf(\"%d %s %c %g %d %d\\n\", k, w, c, d, x, scanf(\"%d\", &x)); return 0; }",
+                "word  z2.5e1 0x1F",
+                "4 word z 25 31 -1\n",
+            ),
+            (
+                "#include <stdio.h>\n\
+                 int main(void) { char a[8], b[8]; int n = sscanf(\"key=val;\", \"%[^=]=%[a-z]\", a, b); printf(\"%d %s %s\\n\", n, a, b); return 0; }",
+                "",
+                "2 key val\n",
+            ),
+            (
+                "#include <stdio.h>\n\
+                 int main(void) { char line[16]; while (fgets(line, sizeof line, stdin)) fputs(line, stdout); puts(\"end\"); return feof(stdin); }",
+                "one\ntwo",
+                "one\ntwoend\n",
+            ),
+            (
+                "#include <stdlib.h>\n#include <stdio.h>\n\
+                 int cmp(const void *a, const void *b) { return *(const int *)a - *(const int *)b; }\n\
+                 int main(void) { int a[] = {5, 3, 9, 1, 7, 3}, key = 7; qsort(a, 6, sizeof a[0], cmp);\n\
+                 for (int i = 0; i < 6; i++) printf(\"%d \", a[i]);\n\
+                 int *p = bsearch(&key, a, 6, sizeof(int), cmp); key = 4;\n\
+                 printf(\"%d %d\\n\", (int)(p - a), bsearch(&key, a, 6, sizeof(int), cmp) == NULL); return 0; }",
+                "",
+                "1 3 3 5 7 9 4 1\n",
+            ),
+            (
+                "#include <stdlib.h>\n#include <stdio.h>\n\
+                 int main(void) { char *end; long v = strtol(\"  -0x1fz\", &end, 0);\n\
+                 printf(\"%ld %c %lu %d %ld %g %d\\n\", v, *end, strtoul(\"-1\", NULL, 10), atoi(\"42abc\"), strtol(\"99999999999999999999\", NULL, 10), atof(\"1.5e3x\"), abs(-4));\n\
+                 srand(1); int r = rand(); srand(1); printf(\"%d %d\\n\", r, r == rand()); return 0; }",
+                "",
+                "-31 z 18446744073709551615 42 9223372036854775807 1500 4\n1804289383 1\n",
+            ),
+            (
+                "#include <string.h>\n#include <stdio.h>\n#include <stdlib.h>\n\
+                 int main(void) { char s[32] = \"hello\"; strcat(s, \", world\"); char *d = strdup(s);\n\
+                 printf(\"%d %d %s %s %s %d %d\\n\", (int)strlen(s), strcmp(\"a\", \"b\") < 0, strchr(s, 'o'), strrchr(s, 'o'), strstr(s, \"wor\"), (int)strspn(s, \"leh\"), strncmp(d, \"help\", 3));\n\
+                 char t[] = \"a,b,,c\"; for (char *p = strtok(t, \",\"); p; p = strtok(NULL, \",\")) printf(\"<%s>\", p);\n\
+                 free(d); return 0; }",
+                "",
+                "12 1 o, world orld world 4 0\n<a><b><c>",
+            ),
+            (
+                "#include <ctype.h>\n#include <math.h>\n#include <stdio.h>\n\
+                 int main(void) { printf(\"%d%d%d%d %c%c %.4f %.1f %g %g %d\\n\", isalpha('x') != 0, isdigit('x') != 0, isspace('\\t') != 0, isupper(EOF) != 0, toupper('q'), tolower('Q'), sqrt(2.0), pow(2, 10), floor(-1.5), fabsf(-0.25f), (int)round(2.5)); return 0; }",
+                "",
+                "1010 Qq 1.4142 1024.0 -2 0.25 3\n",
+            ),
+        ];
+
+        for (i, (src, input, want)) in tests.iter().enumerate() {
+            let got = run_input(src, &[], input, false).map(|(_, out)| out);
+            assert_eq!(
+                Ok(want.to_string()),
+                got.map_err(|(err, _)| err),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+
+        // files are written back when they are closed
+        let path = std::env::temp_dir().join(format!("crepl-libc-{}.txt", std::process::id()));
+        let src = format!(
+            "#include <stdio.h>\n\
+             int main(void) {{ FILE *f = fopen(\"{0}\", \"w\"); fprintf(f, \"%d lines\\n\", 2); fwrite(\"ab\", 1, 2, f); fclose(f);\n\
+             char buf[16]; f = fopen(\"{0}\", \"r\"); fgets(buf, sizeof buf, f); fseek(f, 0, SEEK_END); long n = ftell(f); rewind(f);\n\
+             int c = fgetc(f); fclose(f); printf(\"%s%ld %c %d\\n\", buf, n, c, fopen(\"{0}.none\", \"r\") == NULL); return 0; }}",
+            path.display()
+        );
+        let got = run(&src, &[]);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(Ok((0, "2 lines\n10 2 1\n".to_string())), got);
+
+        let tests = [
+            (
+                "#include <stdio.h>\nint main(void) { printf(\"%d %d\\n\", 1); return 0; }",
+                "2:18: too few arguments for the format string",
+            ),
+            (
+                "#include <stdio.h>\nint main(void) { FILE *f = stdout; fclose(f); return fputc('x', f); }",
+                "2:54: invalid FILE pointer",
+            ),
+        ];
+
+        for (i, (src, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                Err(want.to_string()),
+                run(src, &[]),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+    }
+
+    // runs the inputs of a REPL session in order, each a program that
+    // resumes the previous one and calls or evaluates the function `f`,
+    // and returns what they print
+    fn session(programs: &[(&str, bool)]) -> Vec<String> {
+        let mut state = None;
+        let mut shown = Vec::new();
+
+        for (src, expr) in programs.iter() {
+            let src =
+                include::expand(src.as_bytes(), &sema::layout::DataLayout::default()).unwrap();
+            let mut p = parser::Parser::from(String::from_utf8(src).unwrap());
+            let file = p.parse_file();
+            assert!(p.errors().is_empty(), "{:?}", p.errors());
+            let info = sema::check(&file);
+            assert_eq!(0, info.errors().count(), "{:?}", info.diagnostics);
+
+            let mut out = Vec::new();
+            let mut it = Interpreter::new(&file, &info).with_output(&mut out);
+            if let Some(state) = state.take() {
+                it = it.resume(state);
+            }
+            it.run_globals().unwrap();
+            let v = match expr {
+                true => it.run_expr("f").unwrap(),
+                false => it.run_function("f").map(|_| None).unwrap(),
+            };
+            state = Some(it.into_state());
+            shown.push(String::from_utf8(out).unwrap() + &v.unwrap_or_default());
+        }
+        shown
+    }
+
+    #[test]
+    fn test_session() {
+        let programs = [
+            (
+                "#include <stdio.h>\n\
+                 int n = 5; int sq(int x) { return x * x; } int (*fp)(int) = sq;\n\
+                 void f(void) { printf(\"%d\\n\", n); }",
+                false,
+            ),
+            // new functions move the old ones, the globals keep their values
+            (
+                "#include <stdio.h>\n\
+                 int twice(int x) { return 2 * x; } int n = 5; int sq(int x) { return x * x; }\n\
+                 int (*fp)(int) = sq; int m = 2;\n\
+                 void f(void) { n += fp(3) + m; }",
+                false,
+            ),
+            (
+                "int n = 5; int sq(int x) { return x * x; } int (*fp)(int) = sq;\n\
+                 int count(void) { static int c; return ++c; }\n\
+                 void f(void) { (count() + n); }",
+                true,
+            ),
+            (
+                "int n = 5; int count(void) { static int c; return ++c; }\n\
+                 void f(void) { (count() * 100 + n); }",
+                true,
+            ),
+            (
+                "struct p { int x; char s[4]; double d; struct { unsigned a : 3; }; } P = { 1, \"ab\", 2.5, { 5 } };\n\
+                 void f(void) { (P); }",
+                true,
+            ),
+            (
+                "int xs[3] = { 1, 2 }; const char *s = \"a\\tb\"; char c = 'q'; float x = 0.1f;\n\
+                 int sq(int x) { return x * x; } int *np;\n\
+                 void f(void) { (xs); } void g(void) { (s); } void h(void) { (c); }\n\
+                 void i(void) { (x); } void j(void) { (sq); } void k(void) { (np); }",
+                true,
+            ),
+        ];
+        let want = [
+            "5\n",
+            "",
+            "(int) 17",
+            "(int) 216",
+            "(struct p) { .x = 1, .s = \"ab\", .d = 2.5, { .a = 5 } }",
+            "(int [3]) { 1, 2, 0 }",
+        ];
+        assert_eq!(want.to_vec(), session(&programs));
+
+        // each of the values of the last program
+        let src = programs[5].0;
+        let want = [
+            ("g", "(const char *) \"a\\tb\""),
+            ("h", "(char) 'q'"),
+            ("i", "(float) 0.1"),
+            ("j", "(int (int)) sq"),
+            ("k", "(int *) NULL"),
+        ];
+        for (i, (name, shown)) in want.iter().enumerate() {
+            let src = src.replace(&format!("void {}(void)", name), "void f(void)");
+            let src = src.replacen("void f(void) { (xs); }", "", 1);
+            assert_eq!(
+                vec![shown.to_string()],
+                session(&[(&src, true)]),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                want.len(),
+                name
+            );
+        }
+
+        let file = parser::Parser::from("void f(void) { return; }".to_string()).parse_file();
+        let info = sema::check(&file);
+        let mut it = Interpreter::new(&file, &info);
+        it.run_globals().unwrap();
+        match it.run_expr("f") {
+            Err(Stop::Error(err)) => assert_eq!("expected an expression", err.msg),
+            r => panic!("{:?}", r),
+        }
+    }
+}
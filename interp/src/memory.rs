@@ -0,0 +1,625 @@
+//! The memory of an interpreted program: allocations of bytes at distinct
+//! addresses in the stack, the globals, the heap and the read-only string
+//! literals. A pointer remembers the allocation it was derived from, its
+//! provenance, so an access through it only ever reaches that allocation.
+//! Checked memory also knows which bytes were written and the type of the
+//! last value stored at each place, see `check`.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use sema::types::Type;
+use sema::SymbolId;
+
+use crate::value::{Pointer, Record};
+
+/// The addresses of functions, 16 bytes apart from `TEXT`.
+pub const TEXT: u64 = 0x40_0000;
+/// The first address of the globals, string literals and heap blocks.
+const DATA: u64 = 0x1000_0000;
+/// The stack grows down from `STACK`, at most `STACK_SIZE` bytes.
+const STACK: u64 = 0x7fff_f000;
+pub const STACK_SIZE: u64 = 8 << 20;
+
+/// The addresses of freed allocations are never reused, their records are
+/// once this many more were freed after them.
+const QUARANTINE: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Stack,
+    Global,
+    Heap,
+    String, // read-only
+}
+
+/// Why an access failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    Null,
+    Invalid, // an address no allocation holds
+    OutOfBounds,
+    Dead, // the allocation was freed or its function returned
+    ReadOnly,
+    Uninit, // checked memory only
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Fault::Null => "null pointer dereference",
+            Fault::Invalid => "invalid pointer dereference",
+            Fault::OutOfBounds => "out of bounds access",
+            Fault::Dead => "access to an object whose lifetime has ended",
+            Fault::ReadOnly => "write to a string literal",
+            Fault::Uninit => "read of uninitialized memory",
+        };
+        f.write_str(msg)
+    }
+}
+
+#[derive(Debug)]
+pub struct Allocation {
+    pub base: u64,
+    pub size: u64,
+    pub region: Region,
+    pub live: bool,
+
+    bytes: Vec<u8>,
+    pointers: BTreeMap<u64, usize>, // provenance of the pointers stored, by offset
+    init: Vec<bool>,                // the bytes written, in checked memory
+    types: BTreeMap<u64, (Type, u64)>, // the type and size of the values stored, by offset
+}
+
+/// The allocations lent to foreign code for a call, see `Memory::lend`.
+#[derive(Debug)]
+pub struct Loan {
+    hosts: Vec<(u64, usize)>, // the host address of the bytes of each allocation, sorted
+    slots: HashMap<(usize, u64), (u64, Pointer)>, // the pointers rewritten, by allocation and offset
+}
+
+#[derive(Debug)]
+pub struct Memory {
+    allocs: Vec<Allocation>, // 0 is the null allocation
+    bases: BTreeMap<u64, usize>,
+    free: Vec<usize>, // records of freed allocations, oldest first
+
+    data: u64, // the next address of the data region
+    pub sp: u64,
+    pub heap: u64, // the bytes of the live heap blocks
+    ptr_size: usize,
+
+    pub checked: bool,
+}
+
+impl Memory {
+    pub fn new(ptr_size: usize) -> Self {
+        let null = Allocation {
+            base: 0,
+            size: 0,
+            region: Region::Global,
+            live: false,
+            bytes: Vec::new(),
+            pointers: BTreeMap::new(),
+            init: Vec::new(),
+            types: BTreeMap::new(),
+        };
+
+        Self {
+            allocs: vec![null],
+            bases: BTreeMap::new(),
+            free: Vec::new(),
+            data: DATA,
+            sp: STACK,
+            heap: 0,
+            ptr_size,
+            checked: false,
+        }
+    }
+
+    pub fn get(&self, id: usize) -> &Allocation {
+        &self.allocs[id]
+    }
+
+    /// Allocates `size` zero bytes aligned to `align`, returns None when
+    /// the stack overflows. The bytes of the stack and the heap are not
+    /// initialized.
+    pub fn alloc(&mut self, size: u64, align: u64, region: Region) -> Option<Pointer> {
+        let align = align.max(1);
+
+        let base = if region == Region::Stack {
+            let sp = self.sp.checked_sub(size.max(1))? / align * align;
+            if sp < STACK - STACK_SIZE {
+                return None;
+            }
+            self.sp = sp;
+            sp
+        } else {
+            // a gap after each allocation keeps one past the end apart from
+            // the next one
+            let base = self.data.next_multiple_of(align.max(16));
+            self.data = base + size + 16;
+            base
+        };
+
+        if region == Region::Heap {
+            self.heap += size;
+        }
+        let a = Allocation {
+            base,
+            size,
+            region,
+            live: true,
+            bytes: vec![0; size as usize],
+            pointers: BTreeMap::new(),
+            init: match self.checked {
+                true => vec![matches!(region, Region::Global | Region::String); size as usize],
+                false => Vec::new(),
+            },
+            types: BTreeMap::new(),
+        };
+
+        let id = if self.free.len() > QUARANTINE {
+            let id = self.free.remove(0);
+            self.bases.remove(&self.allocs[id].base);
+            self.allocs[id] = a;
+            id
+        } else {
+            self.allocs.push(a);
+            self.allocs.len() - 1
+        };
+        self.bases.insert(base, id);
+
+        Some(Pointer {
+            alloc: id,
+            addr: base,
+        })
+    }
+
+    /// Allocates a read-only string literal holding `bytes`.
+    pub fn literal(&mut self, bytes: Vec<u8>, align: u64) -> Pointer {
+        let p = self
+            .alloc(bytes.len() as u64, align, Region::String)
+            .unwrap();
+        self.allocs[p.alloc].bytes = bytes;
+        p
+    }
+
+    /// Ends the lifetime of an allocation, the stack space of a function
+    /// is given back with `sp`.
+    pub fn free(&mut self, id: usize) {
+        let a = &mut self.allocs[id];
+        if a.region == Region::Heap && a.live {
+            self.heap -= a.size;
+        }
+        a.live = false;
+        a.bytes = Vec::new();
+        a.pointers.clear();
+        a.init = Vec::new();
+        a.types.clear();
+        self.free.push(id);
+    }
+
+    /// Returns the allocation holding an address, for pointers made from
+    /// integers.
+    pub fn find(&self, addr: u64) -> Option<usize> {
+        let (_, &id) = self.bases.range(..=addr).next_back()?;
+        let a = &self.allocs[id];
+        (addr <= a.base + a.size).then_some(id)
+    }
+
+    // the allocation and offset `n` bytes at `p` are in
+    fn locate(&self, p: Pointer, n: u64) -> Result<(usize, usize), Fault> {
+        let id = match p.alloc {
+            0 if p.addr == 0 => return Err(Fault::Null),
+            0 => self.find(p.addr).ok_or(Fault::Invalid)?,
+            id => id,
+        };
+
+        let a = &self.allocs[id];
+        if !a.live {
+            return Err(Fault::Dead);
+        }
+
+        let offset = p.addr.wrapping_sub(a.base);
+        if p.addr < a.base || offset.checked_add(n).is_none_or(|end| end > a.size) {
+            return Err(Fault::OutOfBounds);
+        }
+        Ok((id, offset as usize))
+    }
+
+    /// Reads the bytes of a string up to its null terminator, which must
+    /// be in the same allocation.
+    pub fn string(&self, p: Pointer) -> Result<Vec<u8>, Fault> {
+        let (id, offset) = self.locate(p, 0)?;
+        let bytes = &self.allocs[id].bytes[offset..];
+        let Some(n) = bytes.iter().position(|&b| b == 0) else {
+            return Err(Fault::OutOfBounds);
+        };
+        self.check_init(p, n + 1)?;
+        Ok(bytes[..n].to_vec())
+    }
+
+    pub fn read(&self, p: Pointer, n: usize) -> Result<&[u8], Fault> {
+        let (id, offset) = self.locate(p, n as u64)?;
+        Ok(&self.allocs[id].bytes[offset..offset + n])
+    }
+
+    /// Writes bytes that are not a pointer, they overwrite the provenance
+    /// of the pointers stored there.
+    pub fn write(&mut self, p: Pointer, bytes: &[u8]) -> Result<(), Fault> {
+        let (id, offset) = self.writable(p, bytes.len())?;
+        self.allocs[id].bytes[offset..offset + bytes.len()].copy_from_slice(bytes);
+        self.written(id, offset, bytes.len());
+        Ok(())
+    }
+
+    // marks the n bytes at `offset` just written as initialized values
+    // that are no longer pointers
+    fn written(&mut self, id: usize, offset: usize, n: usize) {
+        let a = &mut self.allocs[id];
+        a.clear_pointers(offset as u64, n as u64, self.ptr_size as u64);
+        if self.checked {
+            a.init[offset..offset + n].fill(true);
+            a.clear_types(offset as u64, n as u64);
+        }
+    }
+
+    fn writable(&self, p: Pointer, n: usize) -> Result<(usize, usize), Fault> {
+        let (id, offset) = self.locate(p, n as u64)?;
+        match self.allocs[id].region {
+            Region::String => Err(Fault::ReadOnly),
+            _ => Ok((id, offset)),
+        }
+    }
+
+    /// Reads a pointer, with the provenance it was stored with.
+    pub fn read_pointer(&self, p: Pointer) -> Result<Pointer, Fault> {
+        let (id, offset) = self.locate(p, self.ptr_size as u64)?;
+        let a = &self.allocs[id];
+
+        let addr = le(&a.bytes[offset..offset + self.ptr_size]) as u64;
+        let alloc = a.pointers.get(&(offset as u64)).copied().unwrap_or(0);
+        Ok(Pointer { alloc, addr })
+    }
+
+    pub fn write_pointer(&mut self, p: Pointer, v: Pointer) -> Result<(), Fault> {
+        let bytes = v.addr.to_le_bytes();
+        self.write(p, &bytes[..self.ptr_size])?;
+
+        if v.alloc != 0 {
+            let (id, offset) = self.locate(p, 0)?;
+            self.allocs[id].pointers.insert(offset as u64, v.alloc);
+        }
+        Ok(())
+    }
+
+    /// Copies `n` bytes with the pointers among them, the ranges may
+    /// overlap.
+    pub fn copy(&mut self, dst: Pointer, src: Pointer, n: usize) -> Result<(), Fault> {
+        let r = self.snapshot(src, n)?;
+        self.restore(dst, &r)
+    }
+
+    /// Returns `n` bytes and the provenance of the pointers among them, by
+    /// offset from `p`.
+    pub fn snapshot(&self, p: Pointer, n: usize) -> Result<Record, Fault> {
+        let (id, offset) = self.locate(p, n as u64)?;
+        let a = &self.allocs[id];
+
+        let start = offset as u64;
+        let pointers = a
+            .pointers
+            .range(start..start + n as u64)
+            .map(|(&o, &id)| (o - start, id))
+            .collect();
+        let uninit = match self.checked {
+            true => (0..n as u64)
+                .filter(|&i| !a.init[offset + i as usize])
+                .collect(),
+            false => Vec::new(),
+        };
+        Ok(Record {
+            bytes: a.bytes[offset..offset + n].to_vec(),
+            pointers,
+            uninit,
+        })
+    }
+
+    /// Writes bytes and the provenance of the pointers among them.
+    pub fn restore(&mut self, p: Pointer, r: &Record) -> Result<(), Fault> {
+        self.write(p, &r.bytes)?;
+
+        let (id, offset) = self.locate(p, 0)?;
+        let a = &mut self.allocs[id];
+        for &(o, alloc) in r.pointers.iter() {
+            a.pointers.insert(offset as u64 + o, alloc);
+        }
+        for &o in r.uninit.iter() {
+            a.init[offset + o as usize] = false;
+        }
+        Ok(())
+    }
+
+    /// Reports a read of `n` bytes that were not all written, in checked
+    /// memory.
+    pub fn check_init(&self, p: Pointer, n: usize) -> Result<(), Fault> {
+        if !self.checked {
+            return Ok(());
+        }
+        let (id, offset) = self.locate(p, n as u64)?;
+        match self.allocs[id].init[offset..offset + n].iter().all(|&b| b) {
+            true => Ok(()),
+            false => Err(Fault::Uninit),
+        }
+    }
+
+    /// Returns the type of a value stored over `n` bytes at `p`, in checked
+    /// memory.
+    pub fn stored_type(&self, p: Pointer, n: u64) -> Option<&Type> {
+        let (id, offset) = self.locate(p, n).ok()?;
+        let a = &self.allocs[id];
+
+        let offset = offset as u64;
+        a.types
+            .range(..offset + n)
+            .rev()
+            .find(|(&o, (_, size))| o + size > offset)
+            .map(|(_, (ty, _))| ty)
+    }
+
+    /// Records the type of the value just stored over `n` bytes at `p`.
+    pub fn set_stored_type(&mut self, p: Pointer, ty: Type, n: u64) {
+        if let Ok((id, offset)) = self.locate(p, n) {
+            self.allocs[id].types.insert(offset as u64, (ty, n));
+        }
+    }
+
+    /// Sets `n` bytes to `v`.
+    pub fn fill(&mut self, p: Pointer, v: u8, n: usize) -> Result<(), Fault> {
+        let (id, offset) = self.writable(p, n)?;
+        self.allocs[id].bytes[offset..offset + n].fill(v);
+        self.written(id, offset, n);
+        Ok(())
+    }
+
+    /// Lends the allocations `roots` point into, and those reachable from
+    /// them through the pointers they hold, to foreign code: the pointers
+    /// stored in them are rewritten to the host addresses of the bytes
+    /// they point to. `reclaim` takes them back after the call.
+    pub fn lend(&mut self, roots: &[Pointer]) -> Loan {
+        let mut lent = BTreeSet::new();
+        let mut todo: Vec<usize> = roots.iter().filter_map(|&p| self.provenance(p)).collect();
+        while let Some(id) = todo.pop() {
+            if self.allocs[id].live && lent.insert(id) {
+                todo.extend(self.allocs[id].pointers.values());
+            }
+        }
+
+        let mut hosts: Vec<(u64, usize)> = lent
+            .iter()
+            .map(|&id| (self.allocs[id].bytes.as_ptr() as u64, id))
+            .collect();
+        hosts.sort();
+
+        let n = self.ptr_size;
+        let mut slots = HashMap::new();
+        for &id in lent.iter() {
+            let stored: Vec<(u64, usize)> = self.allocs[id]
+                .pointers
+                .iter()
+                .map(|(&o, &a)| (o, a))
+                .collect();
+            for (o, alloc) in stored {
+                let bytes = &self.allocs[id].bytes[o as usize..o as usize + n];
+                let p = Pointer {
+                    alloc,
+                    addr: le(bytes) as u64,
+                };
+                // a dangling pointer keeps its address
+                let h = self.host(p).unwrap_or(p.addr);
+                self.allocs[id].bytes[o as usize..o as usize + n]
+                    .copy_from_slice(&h.to_le_bytes()[..n]);
+                slots.insert((id, o), (h, p));
+            }
+        }
+
+        Loan { hosts, slots }
+    }
+
+    /// The host address of the byte a pointer points to, a pointer into no
+    /// allocation keeps its address.
+    pub fn host(&self, p: Pointer) -> Result<u64, Fault> {
+        let Some(id) = self.provenance(p) else {
+            return Ok(p.addr);
+        };
+        let a = &self.allocs[id];
+        if !a.live {
+            return Err(Fault::Dead);
+        }
+        Ok((a.bytes.as_ptr() as u64).wrapping_add(p.addr.wrapping_sub(a.base)))
+    }
+
+    /// The pointer to a host address, into an allocation of the loan or to
+    /// foreign memory, which the program cannot access.
+    pub fn guest(&self, loan: &Loan, h: u64) -> Pointer {
+        let i = loan.hosts.partition_point(|&(start, _)| start <= h);
+        if let Some(&(start, id)) = i.checked_sub(1).map(|i| &loan.hosts[i]) {
+            let a = &self.allocs[id];
+            if h - start <= a.size {
+                return Pointer {
+                    alloc: id,
+                    addr: a.base + (h - start),
+                };
+            }
+        }
+        Pointer { alloc: 0, addr: h }
+    }
+
+    /// Takes back the allocations of a loan: the pointers rewritten and
+    /// those the foreign code stored in them, at aligned offsets, are
+    /// translated back. All their bytes count as written.
+    pub fn reclaim(&mut self, loan: Loan) {
+        let n = self.ptr_size;
+
+        for &(_, id) in loan.hosts.iter() {
+            let a = &self.allocs[id];
+            if a.region == Region::String {
+                continue;
+            }
+            let mut offsets: BTreeSet<u64> = (0..a.size / n as u64).map(|i| i * n as u64).collect();
+            offsets.extend(a.pointers.keys());
+
+            for o in offsets {
+                let at = o as usize..o as usize + n;
+                let h = le(&self.allocs[id].bytes[at.clone()]) as u64;
+                let p = match loan.slots.get(&(id, o)) {
+                    Some(&(written, p)) if written == h => p,
+                    _ => self.guest(&loan, h),
+                };
+
+                let a = &mut self.allocs[id];
+                if p.alloc == 0 {
+                    a.pointers.remove(&o);
+                    continue;
+                }
+                a.bytes[at].copy_from_slice(&p.addr.to_le_bytes()[..n]);
+                a.pointers.insert(o, p.alloc);
+            }
+
+            if self.checked {
+                let a = &mut self.allocs[id];
+                a.init.fill(true);
+                a.types.clear();
+            }
+        }
+    }
+
+    /// Moves the pointers to functions stored in the globals and the heap
+    /// to the addresses `to` gives their functions, None makes them null.
+    /// A word with no provenance that holds the address of a function is
+    /// taken for a pointer to it. The types of the values stored are
+    /// forgotten, they may name the tags of another program.
+    pub fn relocate(&mut self, to: impl Fn(SymbolId) -> Option<SymbolId>) {
+        let n = self.ptr_size;
+
+        for a in self.allocs.iter_mut() {
+            a.types.clear();
+            if !a.live || a.region == Region::String {
+                continue;
+            }
+            for o in (0..a.size as usize / n).map(|i| i * n) {
+                if a.pointers.contains_key(&(o as u64)) {
+                    continue;
+                }
+                let Some(old) = func_at(le(&a.bytes[o..o + n]) as u64) else {
+                    continue;
+                };
+                let addr = to(old).map_or(0, func_address);
+                a.bytes[o..o + n].copy_from_slice(&addr.to_le_bytes()[..n]);
+            }
+        }
+    }
+
+    // the allocation a pointer is into, the one holding its address if it
+    // was made from an integer
+    fn provenance(&self, p: Pointer) -> Option<usize> {
+        match p.alloc {
+            0 => self.find(p.addr),
+            id => Some(id),
+        }
+    }
+}
+
+impl Allocation {
+    // forgets the pointers overlapping `n` bytes at `offset`
+    fn clear_pointers(&mut self, offset: u64, n: u64, ptr_size: u64) {
+        let start = offset.saturating_sub(ptr_size - 1);
+        let stale: Vec<u64> = self
+            .pointers
+            .range(start..offset + n)
+            .map(|(&o, _)| o)
+            .collect();
+
+        for o in stale {
+            self.pointers.remove(&o);
+        }
+    }
+
+    // forgets the types of the values overlapping `n` bytes at `offset`
+    fn clear_types(&mut self, offset: u64, n: u64) {
+        let stale: Vec<u64> = self
+            .types
+            .range(..offset + n)
+            .rev()
+            .take_while(|(&o, _)| o + 16 > offset) // no scalar is larger
+            .filter(|(&o, (_, size))| o + size > offset)
+            .map(|(&o, _)| o)
+            .collect();
+
+        for o in stale {
+            self.types.remove(&o);
+        }
+    }
+}
+
+/// The address of a function.
+pub fn func_address(id: SymbolId) -> u64 {
+    TEXT + id.0 as u64 * 16
+}
+
+/// The function at an address, if it is one.
+pub fn func_at(addr: u64) -> Option<SymbolId> {
+    let off = addr.checked_sub(TEXT)?;
+    (addr < DATA && off % 16 == 0).then_some(SymbolId((off / 16) as usize))
+}
+
+/// Reads a little-endian unsigned integer.
+pub fn le(bytes: &[u8]) -> u128 {
+    bytes.iter().rev().fold(0, |v, &b| (v << 8) | b as u128)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory() {
+        let mut m = Memory::new(8);
+        let a = m.alloc(16, 8, Region::Global).unwrap();
+        let b = m.alloc(8, 8, Region::Heap).unwrap();
+        assert!(b.addr >= a.addr + 16 + 16);
+
+        m.write(a.offset(4), &[1, 2, 3, 4]).unwrap();
+        assert_eq!(&[0, 1, 2, 3, 4, 0][..], m.read(a.offset(3), 6).unwrap());
+        assert_eq!(Err(Fault::OutOfBounds), m.read(a.offset(12), 8));
+        assert_eq!(Err(Fault::OutOfBounds), m.read(a.offset(-1), 1));
+        assert_eq!(Err(Fault::Null), m.read(Pointer::NULL, 1));
+
+        // provenance survives a copy and is lost when overwritten
+        m.write_pointer(a, b).unwrap();
+        m.copy(a.offset(8), a, 8).unwrap();
+        assert_eq!(Ok(b), m.read_pointer(a.offset(8)));
+        m.write(a.offset(9), &[0]).unwrap();
+        assert_eq!(0, m.read_pointer(a.offset(8)).unwrap().alloc);
+
+        // an address past the end of `a` is not in `b`
+        assert_eq!(Some(a.alloc), m.find(a.addr + 16));
+        assert_eq!(None, m.find(a.addr + 20));
+        assert_eq!(Some(b.alloc), m.find(b.addr + 7));
+
+        m.free(b.alloc);
+        assert_eq!(Err(Fault::Dead), m.read(b, 1));
+
+        let s = m.alloc(4, 1, Region::String).unwrap();
+        assert_eq!(Err(Fault::ReadOnly), m.write(s, &[1]));
+
+        let sp = m.sp;
+        let x = m.alloc(12, 8, Region::Stack).unwrap();
+        assert_eq!(sp - 16, x.addr);
+        assert!(m.alloc(STACK_SIZE, 8, Region::Stack).is_none());
+
+        assert_eq!(Some(SymbolId(3)), func_at(func_address(SymbolId(3))));
+        assert_eq!(None, func_at(func_address(SymbolId(3)) + 8));
+    }
+}
@@ -0,0 +1,130 @@
+//! The values of the expressions a REPL evaluates, printed with their
+//! types: `(int) 42`, `(const char *) "hi"`, `(int [3]) { 1, 2, 3 }` or
+//! `(struct point) { .x = 1, .y = 2 }`.
+
+use sema::types::{ArrayLen, FloatKind, IntKind, Type, TypeKind};
+
+use crate::eval::{pointer, Place};
+use crate::memory::Region;
+use crate::value::Value;
+use crate::{Interpreter, Result};
+
+/// The elements of an array and the characters of a string shown, the
+/// rest is elided.
+const MAX_ELEMENTS: usize = 100;
+const MAX_STRING: usize = 200;
+
+impl<'a> Interpreter<'a> {
+    /// Shows a value of type `ty`, None for void. The value of an array
+    /// is a pointer to its first element.
+    pub(crate) fn show(&mut self, v: Value, ty: &Type, pos: usize) -> Result<Option<String>> {
+        if ty.is_void() {
+            return Ok(None);
+        }
+
+        // what is shown is not read by the program
+        let checked = std::mem::replace(&mut self.checked, false);
+        let text = match &ty.kind {
+            TypeKind::Array(..) => self.show_object(pointer(v).into(), ty, pos),
+            TypeKind::Struct(_) | TypeKind::Union(_) => {
+                self.alloc_object(ty, Region::Stack, pos).and_then(|p| {
+                    self.store(p, ty, v, pos)?;
+                    self.show_object(p.into(), ty, pos)
+                })
+            }
+            _ => Ok(self.show_scalar(&v, ty)),
+        };
+        self.checked = checked;
+
+        Ok(Some(format!("({}) {}", self.info.spell(ty), text?)))
+    }
+
+    // shows the object of type `ty` at `p`
+    fn show_object(&self, p: Place, ty: &Type, pos: usize) -> Result<String> {
+        match &ty.kind {
+            TypeKind::Array(elem, len) => {
+                let n = match len {
+                    ArrayLen::Fixed(n) => *n as usize,
+                    _ => 0,
+                };
+                if is_char(elem) {
+                    let bytes = self.mem.read(p.ptr, n).unwrap_or_default();
+                    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                    return Ok(quote(&bytes[..end]));
+                }
+
+                let size = self.size_of(elem, pos)? as i64;
+                let mut items = Vec::new();
+                for i in 0..n.min(MAX_ELEMENTS) {
+                    let e = p.ptr.offset(i as i64 * size).into();
+                    items.push(self.show_object(e, elem, pos)?);
+                }
+                if n > MAX_ELEMENTS {
+                    items.push("...".to_string());
+                }
+                Ok(braced(items))
+            }
+            TypeKind::Struct(id) | TypeKind::Union(id) => {
+                let fields = self.records[id.0]
+                    .as_ref()
+                    .map_or(&[][..], |r| &r.fields[..]);
+
+                let mut items = Vec::new();
+                for f in fields.iter() {
+                    let text = self.show_object(self.field(p, f, pos)?, &f.ty, pos)?;
+                    match &f.name {
+                        Some(name) => items.push(format!(".{} = {}", name, text)),
+                        None if f.ty.is_record() => items.push(text),
+                        None => {} // padding
+                    }
+                }
+                Ok(braced(items))
+            }
+            _ => {
+                let v = self.load_place(p, ty, pos)?;
+                Ok(self.show_scalar(&v, ty))
+            }
+        }
+    }
+
+    fn show_scalar(&self, v: &Value, ty: &Type) -> String {
+        match (&ty.kind, v) {
+            (_, Value::Func(id)) => self.info.symbol(*id).name.clone(),
+            (TypeKind::Int(IntKind::Char | IntKind::SChar | IntKind::UChar), v) => {
+                let c = v.as_int() as u8;
+                format!("'{}'", c.escape_ascii())
+            }
+            (TypeKind::Float(FloatKind::Float), v) => format!("{:?}", v.as_float() as f32),
+            (TypeKind::Float(_), v) => format!("{:?}", v.as_float()),
+            (TypeKind::Pointer(_), Value::Pointer(p)) if p.addr == 0 => "NULL".to_string(),
+            (TypeKind::Pointer(to), Value::Pointer(p)) => {
+                let string = self.mem.string(*p).ok().filter(|_| is_char(to));
+                match string {
+                    Some(s) if s.len() > MAX_STRING => format!("{}...", quote(&s[..MAX_STRING])),
+                    Some(s) => quote(&s),
+                    None => format!("{:#x}", p.addr),
+                }
+            }
+            (_, v) => v.as_int().to_string(),
+        }
+    }
+}
+
+fn is_char(ty: &Type) -> bool {
+    matches!(
+        ty.int_kind(),
+        Some(IntKind::Char | IntKind::SChar | IntKind::UChar)
+    ) && !matches!(ty.kind, TypeKind::Enum(_))
+}
+
+// a string literal of `bytes`
+fn quote(bytes: &[u8]) -> String {
+    format!("\"{}\"", bytes.escape_ascii())
+}
+
+fn braced(items: Vec<String>) -> String {
+    match items.is_empty() {
+        true => "{}".to_string(),
+        false => format!("{{ {} }}", items.join(", ")),
+    }
+}
@@ -0,0 +1,131 @@
+//! The values the interpreter computes with.
+
+use std::rc::Rc;
+
+use sema::layout::DataLayout;
+use sema::types::{FloatKind, IntKind, Type, TypeKind};
+use sema::SymbolId;
+
+use crate::memory::{func_address, func_at};
+
+/// A value. Integers are kept as the value of their type, in the range the
+/// target gives it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Void,
+    Int(i128),
+    Float(f64),
+    Pointer(Pointer),
+    Func(SymbolId), // a pointer to a function
+    Record(Rc<Record>),
+}
+
+/// An address and the allocation it was derived from, 0 for none: null
+/// and pointers made from integers, they point into the allocation that
+/// holds their address.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Pointer {
+    pub alloc: usize,
+    pub addr: u64,
+}
+
+/// The value of a struct or union: its bytes and the allocations of the
+/// pointers stored in it, by offset, and the offsets of the bytes that
+/// were never written in checked memory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub bytes: Vec<u8>,
+    pub pointers: Vec<(u64, usize)>,
+    pub uninit: Vec<u64>,
+}
+
+impl Pointer {
+    pub const NULL: Self = Self { alloc: 0, addr: 0 };
+
+    pub fn offset(self, n: i64) -> Self {
+        Self {
+            alloc: self.alloc,
+            addr: self.addr.wrapping_add(n as u64),
+        }
+    }
+}
+
+impl Value {
+    /// The zero value of a scalar type.
+    pub fn zero(ty: &Type) -> Self {
+        match ty.kind {
+            TypeKind::Float(_) => Value::Float(0.0),
+            TypeKind::Pointer(_) => Value::Pointer(Pointer::NULL),
+            _ => Value::Int(0),
+        }
+    }
+
+    /// Reports whether a scalar compares unequal to 0.
+    pub fn is_true(&self) -> bool {
+        match self {
+            Value::Int(v) => *v != 0,
+            Value::Float(f) => *f != 0.0,
+            Value::Pointer(p) => p.addr != 0,
+            Value::Func(_) => true,
+            Value::Void | Value::Record(_) => false,
+        }
+    }
+
+    /// The integer a scalar converts to, floating values are truncated.
+    pub fn as_int(&self) -> i128 {
+        match self {
+            Value::Int(v) => *v,
+            Value::Float(f) => *f as i128,
+            Value::Pointer(p) => p.addr as i128,
+            Value::Func(id) => func_address(*id) as i128,
+            Value::Void | Value::Record(_) => 0,
+        }
+    }
+
+    /// The floating value a scalar converts to.
+    pub fn as_float(&self) -> f64 {
+        match self {
+            Value::Float(f) => *f,
+            v => v.as_int() as f64,
+        }
+    }
+
+    /// Converts a value to a type, as by assignment or a cast.
+    pub fn convert(self, to: &Type, dl: &DataLayout) -> Self {
+        match &to.kind {
+            TypeKind::Void => Value::Void,
+            TypeKind::Int(IntKind::Bool) => Value::Int(self.is_true() as i128),
+            TypeKind::Int(_) | TypeKind::Enum(_) => {
+                let k = to.int_kind().unwrap();
+                Value::Int(dl.wrap(self.as_int(), k))
+            }
+            TypeKind::Float(k) => {
+                let f = match self {
+                    Value::Float(f) => f,
+                    v => v.as_int() as f64,
+                };
+                Value::Float(round(f, *k))
+            }
+            TypeKind::Pointer(_) => match self {
+                Value::Pointer(_) | Value::Func(_) => self,
+                v => {
+                    let addr = v.as_int() as u64 & (u64::MAX >> (64 - dl.pointer.size * 8));
+                    match func_at(addr) {
+                        Some(id) => Value::Func(id),
+                        None => Value::Pointer(Pointer { alloc: 0, addr }),
+                    }
+                }
+            },
+            _ => self,
+        }
+    }
+}
+
+/// Rounds a value to the precision of a floating type, long double is a
+/// double.
+pub fn round(f: f64, k: FloatKind) -> f64 {
+    match k {
+        FloatKind::Float => f as f32 as f64,
+        _ => f,
+    }
+}
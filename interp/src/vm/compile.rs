@@ -0,0 +1,792 @@
+//! Lowers the body of a function to bytecode. The instructions of an
+//! expression leave its value on the stack, or its object on the stack of
+//! lvalues, and statements become jumps. The errors the tree-walking
+//! interpreter would report when it reaches an expression, like a member
+//! of an incomplete type, become instructions that report them.
+
+use std::collections::HashMap;
+
+use ast::{AssignExpr, BlockStmt, DeclStmt, Expr, ExprKind, SizeofArg, Stmt, StmtKind};
+use sema::consts::{parse_float, Eval};
+use sema::types::{usual_arithmetic, Type, TypeKind};
+use sema::{SymbolId, SymbolKind};
+use token::Token;
+
+use super::{Chunk, Op, Switch};
+use crate::eval::{is_aggregate, is_lvalue};
+use crate::value::{round, Value};
+use crate::{Interpreter, Result, Stop};
+
+/// Compiles the body of a function.
+pub(crate) fn compile<'a>(it: &Interpreter<'a>, body: &'a BlockStmt) -> Chunk<'a> {
+    let mut c = Compiler {
+        it,
+        chunk: Chunk::default(),
+        slots: HashMap::new(),
+        labels: HashMap::new(),
+        gotos: Vec::new(),
+        jumps: Vec::new(),
+        cases: Vec::new(),
+    };
+
+    c.block(body);
+    c.emit(Op::Return(false));
+
+    for (at, label) in std::mem::take(&mut c.gotos) {
+        c.chunk.code[at] = match c.labels.get(label) {
+            Some(&to) => Op::Jump(to),
+            None => Op::Fail(body.lbrace, format!("label '{}' not found", label)),
+        };
+    }
+    c.chunk
+}
+
+struct Compiler<'i, 'a> {
+    it: &'i Interpreter<'a>,
+    chunk: Chunk<'a>,
+    slots: HashMap<SymbolId, usize>,
+
+    labels: HashMap<&'a str, usize>,
+    gotos: Vec<(usize, &'a str)>,
+    jumps: Vec<Jumps>, // of the enclosing loops and switches
+    cases: Vec<Vec<(Option<&'a dyn Expr>, usize)>>, // the labels of the enclosing switches
+}
+
+// the break and continue statements of a loop or switch, to be patched
+struct Jumps {
+    breaks: Vec<usize>,
+    continues: Option<Vec<usize>>, // None for a switch
+}
+
+impl<'i, 'a> Compiler<'i, 'a> {
+    fn emit(&mut self, op: Op<'a>) -> usize {
+        self.chunk.code.push(op);
+        self.chunk.code.len() - 1
+    }
+
+    // the address of the next instruction
+    fn here(&self) -> usize {
+        self.chunk.code.len()
+    }
+
+    // points the jump at `at` to `to`
+    fn patch(&mut self, at: usize, to: usize) {
+        match &mut self.chunk.code[at] {
+            Op::Jump(t) | Op::JumpIf(t) | Op::JumpUnless(t) => *t = to,
+            _ => unreachable!("not a jump"),
+        }
+    }
+
+    fn ty(&mut self, ty: Type) -> usize {
+        self.chunk.types.push(ty);
+        self.chunk.types.len() - 1
+    }
+
+    fn slot(&mut self, id: SymbolId) -> usize {
+        let n = self.chunk.slots.len();
+        *self.slots.entry(id).or_insert_with(|| {
+            self.chunk.slots.push(id);
+            n
+        })
+    }
+
+    // the value of a compile time computation, or the instruction that
+    // reports its error
+    fn check<T>(&mut self, r: Result<T>) -> Option<T> {
+        match r {
+            Ok(v) => Some(v),
+            Err(Stop::Error(err)) => {
+                self.emit(Op::Fail(err.pos, err.msg));
+                None
+            }
+            Err(Stop::Exit(_)) => None,
+        }
+    }
+
+    fn type_of(&self, x: &dyn Expr) -> Type {
+        self.it.type_of(x)
+    }
+
+    // converts the value on top from `from` to `to`, values already have
+    // the representation of their type
+    fn convert(&mut self, from: &Type, to: &Type) {
+        if from.unqualified() != to.unqualified() {
+            let t = self.ty(to.clone());
+            self.emit(Op::Convert(t));
+        }
+    }
+
+    fn block(&mut self, b: &'a BlockStmt) {
+        for s in b.stmts.iter() {
+            self.stmt(s.as_ref());
+        }
+    }
+
+    fn stmt(&mut self, s: &'a dyn Stmt) {
+        match s.kind() {
+            StmtKind::SemiColonStmt(_) | StmtKind::StaticAssert(_) => {}
+            StmtKind::ExprStmt(s) => self.effect(s.x.as_ref()),
+            StmtKind::DeclStmt(d) => self.decl(d),
+
+            StmtKind::ReturnStmt(r) => match &r.value {
+                Some(x) => {
+                    self.expr(x.as_ref());
+                    self.emit(Op::Return(true));
+                }
+                None => {
+                    self.emit(Op::Return(false));
+                }
+            },
+
+            // a break or continue outside a loop ends the function
+            StmtKind::BreakStmt(_) => {
+                let at = self.emit(Op::Jump(0));
+                match self.jumps.last_mut() {
+                    Some(j) => j.breaks.push(at),
+                    None => self.chunk.code[at] = Op::Return(false),
+                }
+            }
+            StmtKind::ContinueStmt(_) => {
+                let at = self.emit(Op::Jump(0));
+                match self
+                    .jumps
+                    .iter_mut()
+                    .rev()
+                    .find_map(|j| j.continues.as_mut())
+                {
+                    Some(c) => c.push(at),
+                    None => self.chunk.code[at] = Op::Return(false),
+                }
+            }
+            StmtKind::GotoStmt(g) => {
+                self.emit(Op::Tick(g.pos));
+                let at = self.emit(Op::Jump(0));
+                self.gotos.push((at, &g.label.name));
+            }
+
+            StmtKind::BlockStmt(b) => self.block(b),
+
+            StmtKind::IfStmt(s) => {
+                let mut branches = vec![(Some(s.cond.as_ref()), s.init.as_ref())];
+                branches.extend(
+                    s.elifs
+                        .iter()
+                        .map(|e| (Some(e.cond.as_ref()), e.init.as_ref())),
+                );
+                branches.extend(s._else.iter().map(|e| (None, e.init.as_ref())));
+
+                let mut ends = Vec::new();
+                for (cond, body) in branches {
+                    let Some(x) = cond else {
+                        self.stmt(body);
+                        break;
+                    };
+                    self.expr(x);
+                    let skip = self.emit(Op::JumpUnless(0));
+                    self.stmt(body);
+                    ends.push(self.emit(Op::Jump(0)));
+                    self.patch(skip, self.here());
+                }
+                for at in ends {
+                    self.patch(at, self.here());
+                }
+            }
+
+            StmtKind::WhileStmt(w) => {
+                let top = self.emit(Op::Tick(w.pos));
+                self.expr(w.cond.as_ref());
+                let exit = self.emit(Op::JumpUnless(0));
+                self.body(w.init.as_ref(), top);
+                self.emit(Op::Jump(top));
+                self.patch(exit, self.here());
+                self.end(self.here());
+            }
+
+            StmtKind::DowhileStmt(d) => {
+                let top = self.emit(Op::Tick(d.do_pos));
+                self.jumps.push(Jumps {
+                    breaks: Vec::new(),
+                    continues: Some(Vec::new()),
+                });
+                self.stmt(d.init.as_ref());
+
+                let cond = self.here();
+                let j = self.jumps.last_mut().unwrap();
+                for at in j.continues.take().unwrap() {
+                    self.patch(at, cond);
+                }
+                self.expr(d.cond.as_ref());
+                self.emit(Op::JumpIf(top));
+                self.end(self.here());
+            }
+
+            StmtKind::ForStmt(f) => {
+                if let Some(init) = &f.init {
+                    self.stmt(init.as_ref());
+                }
+
+                let top = self.emit(Op::Tick(f.pos));
+                let exit = f.cond.as_ref().map(|x| {
+                    self.expr(x.as_ref());
+                    self.emit(Op::JumpUnless(0))
+                });
+
+                // continue goes to the post expression, compiled after
+                // the body
+                self.jumps.push(Jumps {
+                    breaks: Vec::new(),
+                    continues: Some(Vec::new()),
+                });
+                self.stmt(f.body.as_ref());
+                let post = self.here();
+                let j = self.jumps.last_mut().unwrap();
+                for at in j.continues.take().unwrap() {
+                    self.patch(at, post);
+                }
+                if let Some(x) = &f.post {
+                    self.effect(x.as_ref());
+                }
+                self.emit(Op::Jump(top));
+
+                if let Some(at) = exit {
+                    self.patch(at, self.here());
+                }
+                self.end(self.here());
+            }
+
+            StmtKind::SwitchStmt(s) => {
+                let tag = s.tag.as_ref();
+                let ty = self.type_of(tag).promote();
+                self.expr(tag);
+                self.convert(&self.type_of(tag), &ty);
+
+                let i = self.chunk.switches.len();
+                self.chunk.switches.push(Switch::default());
+                self.emit(Op::Switch(i));
+
+                self.jumps.push(Jumps {
+                    breaks: Vec::new(),
+                    continues: None,
+                });
+                self.cases.push(Vec::new());
+                self.stmt(s.body.as_ref());
+
+                let end = self.here();
+                let mut table = Switch {
+                    end,
+                    ..Switch::default()
+                };
+                for (value, at) in self.cases.pop().unwrap() {
+                    let Some(x) = value else {
+                        table.default = Some(at);
+                        continue;
+                    };
+                    let c = match Eval::new(self.it.info).int(x) {
+                        Ok(c) => Ok(Value::Int(c).convert(&ty, self.it.layout()).as_int()),
+                        Err(n) => Err((n.pos, n.msg)),
+                    };
+                    table.cases.push((c, at));
+                }
+                self.chunk.switches[i] = table;
+                self.end(end);
+            }
+
+            StmtKind::CaseStmt(c) => {
+                let at = self.here();
+                if let Some(cases) = self.cases.last_mut() {
+                    cases.push((c.value.as_deref(), at));
+                }
+                self.stmt(c.stmt.as_ref());
+            }
+
+            StmtKind::LabeledStmt(l) => {
+                self.labels.insert(&l.label.name, self.here());
+                self.stmt(l.stmt.as_ref());
+            }
+        }
+    }
+
+    // the body of a while loop, continue goes to `top`
+    fn body(&mut self, s: &'a dyn Stmt, top: usize) {
+        self.jumps.push(Jumps {
+            breaks: Vec::new(),
+            continues: Some(Vec::new()),
+        });
+        self.stmt(s);
+        let j = self.jumps.last_mut().unwrap();
+        for at in j.continues.take().unwrap() {
+            self.patch(at, top);
+        }
+    }
+
+    // ends a loop or switch, break goes to `end`
+    fn end(&mut self, end: usize) {
+        for at in self.jumps.pop().unwrap().breaks {
+            self.patch(at, end);
+        }
+    }
+
+    // the declarations of automatic scalars and records are compiled, those
+    // with static storage or of variable length arrays run on the AST
+    fn decl(&mut self, d: &'a DeclStmt) {
+        let g = &d.decl;
+        if matches!(g.specs.storage, Some(Token::TYPEDEF | Token::EXTERN)) {
+            return;
+        }
+
+        let mut vars = Vec::new();
+        for d in g.decls.iter() {
+            let name = d.declarator.name.as_ref();
+            let Some(&id) = name.and_then(|n| self.it.info.decls.get(&n.pos)) else {
+                continue;
+            };
+            let sym = self.it.info.symbol(id);
+            if sym.kind == SymbolKind::Var {
+                vars.push((id, &sym.ty, d));
+            }
+        }
+
+        if g.specs.storage == Some(Token::STATIC) || vars.iter().any(|v| v.1.is_vla()) {
+            self.emit(Op::Declare(d));
+            return;
+        }
+
+        for (id, ty, d) in vars {
+            let slot = self.slot(id);
+            let pos = d.declarator.pos;
+            self.emit(Op::Alloc(slot, pos));
+
+            let Some(x) = &d.init else {
+                continue;
+            };
+            let x = x.as_ref();
+            self.emit(Op::Var(slot, pos));
+
+            // the parts an initializer list leaves out are zero
+            let t = self.ty(ty.clone());
+            if is_aggregate(ty) {
+                self.emit(Op::Zero(t, x.start()));
+            }
+
+            let string = matches!(x.kind(), ExprKind::BasicLit(b) if b.tok == Token::STRING);
+            if matches!(x.kind(), ExprKind::InitList(_)) || (string && ty.is_array()) {
+                self.emit(Op::Init(t, x));
+            } else {
+                self.expr(x);
+                self.convert(&self.type_of(x), ty);
+                self.emit(Op::Store {
+                    ty: t,
+                    pos: x.start(),
+                    keep: false,
+                });
+            }
+        }
+    }
+
+    // an expression evaluated for its side effects
+    fn effect(&mut self, x: &'a dyn Expr) {
+        match x.kind() {
+            ExprKind::ParenExpr(p) => self.effect(p.x.as_ref()),
+            ExprKind::AssignExpr(e) => self.assign(e, false),
+            ExprKind::UnaryExpr(u) if matches!(u.op, Token::INC | Token::DEC) => {
+                self.step(u.op, u.x.as_ref(), true, false)
+            }
+            ExprKind::PostfixExpr(p) => self.step(p.op, p.x.as_ref(), false, false),
+            ExprKind::InfixExpr(e) if e.op == Token::COMMA => {
+                self.effect(e.x.as_ref());
+                self.effect(e.y.as_ref());
+            }
+            _ => {
+                self.expr(x);
+                self.emit(Op::Pop);
+            }
+        }
+    }
+
+    /// Compiles an expression to the instructions that push its value.
+    fn expr(&mut self, x: &'a dyn Expr) {
+        match x.kind() {
+            ExprKind::BasicLit(b) => match b.tok {
+                Token::INTEGER | Token::CHARACTER => self.constant(x),
+                Token::FLOATING => {
+                    let f = parse_float(&b.lit).unwrap_or_default();
+                    let v = match self.type_of(x).kind {
+                        TypeKind::Float(k) => round(f, k),
+                        _ => f,
+                    };
+                    self.emit(Op::Const(Value::Float(v)));
+                }
+                _ => {
+                    self.emit(Op::Place(x));
+                    self.emit(Op::Address);
+                }
+            },
+
+            ExprKind::Ident(id) => match self.it.info.lookup(id.pos) {
+                Some(s) if s.kind == SymbolKind::EnumConst => {
+                    self.emit(Op::Const(Value::Int(s.value.unwrap_or(0))));
+                }
+                Some(s) if s.kind == SymbolKind::Func => {
+                    let op = match self.it.info.uses.get(&id.pos) {
+                        Some(&f) => Op::Const(Value::Func(f)),
+                        None => Op::Fail(id.pos, format!("undeclared identifier '{}'", id.name)),
+                    };
+                    self.emit(op);
+                }
+                _ => self.rvalue(x),
+            },
+
+            ExprKind::ParenExpr(p) => self.expr(p.x.as_ref()),
+
+            ExprKind::UnaryExpr(u) => match u.op {
+                Token::AND => match self.type_of(u.x.as_ref()).kind {
+                    TypeKind::Function(_) => self.expr(u.x.as_ref()),
+                    _ => {
+                        self.place(u.x.as_ref());
+                        self.emit(Op::Address);
+                    }
+                },
+                Token::INC | Token::DEC => self.step(u.op, u.x.as_ref(), true, true),
+                Token::NOT | Token::BANG => {
+                    self.expr(u.x.as_ref());
+                    self.emit(Op::Not);
+                }
+                op => {
+                    let ty = self.type_of(x);
+                    self.expr(u.x.as_ref());
+                    self.convert(&self.type_of(u.x.as_ref()), &ty);
+
+                    let t = self.ty(ty);
+                    match op {
+                        Token::MINUS => self.emit(Op::Neg(t, u.op_pos)),
+                        Token::TILDE => self.emit(Op::BitNot(t)),
+                        _ => 0,
+                    };
+                }
+            },
+
+            ExprKind::StarExpr(s) => match self.type_of(x).kind {
+                TypeKind::Function(_) => self.expr(s.x.as_ref()),
+                _ => self.rvalue(x),
+            },
+            ExprKind::IndexExpr(_) | ExprKind::CompositeLit(_) | ExprKind::SelectorExpr(_) => {
+                self.rvalue(x)
+            }
+
+            ExprKind::PostfixExpr(p) => self.step(p.op, p.x.as_ref(), false, true),
+
+            ExprKind::InfixExpr(e) => match e.op {
+                Token::COMMA => {
+                    self.effect(e.x.as_ref());
+                    self.expr(e.y.as_ref());
+                }
+
+                // the value of && and || is 0 or 1
+                Token::LAND | Token::LOR => {
+                    self.expr(e.x.as_ref());
+                    let short = match e.op {
+                        Token::LAND => self.emit(Op::JumpUnless(0)),
+                        _ => self.emit(Op::JumpIf(0)),
+                    };
+                    self.expr(e.y.as_ref());
+                    self.emit(Op::Truth);
+                    let end = self.emit(Op::Jump(0));
+
+                    self.patch(short, self.here());
+                    let v = (e.op == Token::LOR) as i128;
+                    self.emit(Op::Const(Value::Int(v)));
+                    self.patch(end, self.here());
+                }
+
+                op => {
+                    self.expr(e.x.as_ref());
+                    self.expr(e.y.as_ref());
+                    let (ta, tb) = (self.type_of(e.x.as_ref()), self.type_of(e.y.as_ref()));
+                    self.binary(op, e.op_pos, &ta, &tb);
+                }
+            },
+
+            ExprKind::AssignExpr(e) => self.assign(e, true),
+
+            ExprKind::CondExpr(e) => {
+                let ty = self.type_of(x).decay();
+                self.expr(e.cond.as_ref());
+                let other = self.emit(Op::JumpUnless(0));
+                self.expr(e.x.as_ref());
+                self.convert(&self.type_of(e.x.as_ref()), &ty);
+                let end = self.emit(Op::Jump(0));
+
+                self.patch(other, self.here());
+                self.expr(e.y.as_ref());
+                self.convert(&self.type_of(e.y.as_ref()), &ty);
+                self.patch(end, self.here());
+            }
+
+            ExprKind::CallExpr(c) => {
+                self.expr(c.fun.as_ref());
+
+                // the arguments convert to the types of the parameters of a
+                // prototype, the others are promoted
+                let ty = self.type_of(c.fun.as_ref());
+                let func = ty.func().or_else(|| ty.pointee().and_then(Type::func));
+                let params = func.filter(|f| f.prototype).map_or(&[][..], |f| &f.params);
+
+                for (i, a) in c.args.iter().enumerate() {
+                    let from = self.type_of(a.as_ref());
+                    let to = match params.get(i) {
+                        Some(t) => t.clone(),
+                        None => from.promote_arg(),
+                    };
+                    self.expr(a.as_ref());
+                    self.convert(&from, &to);
+                }
+                self.emit(Op::Call(c.args.len(), c.fun.start()));
+            }
+
+            ExprKind::CastExpr(c) => {
+                self.expr(c.x.as_ref());
+                self.convert(&self.type_of(c.x.as_ref()), &self.type_of(x));
+            }
+
+            // the size of a variable length array is only known at run time
+            ExprKind::SizeofExpr(s) => match Eval::new(self.it.info).int(x) {
+                Ok(v) => {
+                    self.emit(Op::Const(Value::Int(v)));
+                }
+                Err(_) if matches!(s.arg, SizeofArg::Expr(_)) => {
+                    self.emit(Op::Eval(x));
+                }
+                Err(n) => {
+                    self.emit(Op::Fail(n.pos, n.msg));
+                }
+            },
+
+            ExprKind::OffsetofExpr(_) => self.constant(x),
+
+            ExprKind::InitList(l) => {
+                self.emit(Op::Fail(
+                    l.lbrace,
+                    "unexpected initializer list".to_string(),
+                ));
+            }
+        }
+    }
+
+    // an integer constant expression
+    fn constant(&mut self, x: &dyn Expr) {
+        let op = match Eval::new(self.it.info).int(x) {
+            Ok(v) => Op::Const(Value::Int(v)),
+            Err(n) => Op::Fail(n.pos, n.msg),
+        };
+        self.emit(op);
+    }
+
+    // loads the value of an lvalue, an array decays to a pointer
+    fn rvalue(&mut self, x: &'a dyn Expr) {
+        self.place(x);
+        let ty = self.type_of(x);
+        match ty.kind {
+            TypeKind::Array(..) => self.emit(Op::Address),
+            _ => {
+                let t = self.ty(ty);
+                self.emit(Op::Load(t, x.start()))
+            }
+        };
+    }
+
+    /// Compiles an lvalue to the instructions that push its object.
+    fn place(&mut self, x: &'a dyn Expr) {
+        match x.kind() {
+            ExprKind::ParenExpr(p) => self.place(p.x.as_ref()),
+
+            ExprKind::Ident(id) => {
+                let op = match self.it.info.uses.get(&id.pos) {
+                    Some(&sym) if self.it.info.symbol(sym).ty.is_vla() => Op::Vla(sym, id.pos),
+                    Some(&sym) => Op::Var(self.slot(sym), id.pos),
+                    None => Op::Fail(id.pos, format!("undeclared identifier '{}'", id.name)),
+                };
+                self.emit(op);
+            }
+
+            ExprKind::StarExpr(s) => {
+                self.expr(s.x.as_ref());
+                self.emit(Op::Deref);
+            }
+
+            ExprKind::IndexExpr(e) => {
+                self.expr(e.x.as_ref());
+                self.expr(e.index.as_ref());
+                let size = self.it.size_of(&self.type_of(x), e.lbrack);
+                if let Some(size) = self.check(size) {
+                    self.emit(Op::Index(size as i64));
+                }
+            }
+
+            ExprKind::SelectorExpr(s) => {
+                let ty = if s.op == Token::ARROW {
+                    let ty = self.type_of(s.x.as_ref());
+                    self.expr(s.x.as_ref());
+                    self.emit(Op::Deref);
+                    ty.pointee().cloned().unwrap_or_default()
+                } else if is_lvalue(s.x.as_ref()) {
+                    self.place(s.x.as_ref());
+                    self.type_of(s.x.as_ref())
+                } else {
+                    // a member of a struct returned by a call or assigned
+                    let ty = self.type_of(s.x.as_ref());
+                    self.expr(s.x.as_ref());
+                    let t = self.ty(ty.clone());
+                    self.emit(Op::Temp {
+                        ty: t,
+                        pos: s.op_pos,
+                        at: s.x.start(),
+                    });
+                    ty
+                };
+
+                let (TypeKind::Struct(id) | TypeKind::Union(id)) = ty.kind else {
+                    let msg = "member access in a value that is not a record";
+                    self.emit(Op::Fail(s.op_pos, msg.to_string()));
+                    return;
+                };
+                let Some(f) = self.it.member(id, &s.sel.name) else {
+                    let msg = format!("no member named '{}'", s.sel.name);
+                    self.emit(Op::Fail(s.sel.pos, msg));
+                    return;
+                };
+                let union = matches!(ty.kind, TypeKind::Union(_));
+
+                // the storage unit of a bit-field is aligned to the size of
+                // its type
+                let Some(width) = f.bits else {
+                    let offset = (f.offset / 8) as i64;
+                    self.emit(Op::Field {
+                        offset,
+                        bits: None,
+                        union,
+                    });
+                    return;
+                };
+                let unit = self.it.size_of(&f.ty, s.sel.pos);
+                if let Some(unit) = self.check(unit) {
+                    let start = f.offset / (unit * 8) * unit;
+                    self.emit(Op::Field {
+                        offset: start as i64,
+                        bits: Some(((f.offset - start * 8) as u32, width)),
+                        union,
+                    });
+                }
+            }
+
+            ExprKind::BasicLit(b) if b.tok == Token::STRING => {
+                self.emit(Op::Place(x));
+            }
+            ExprKind::CompositeLit(_) => {
+                self.emit(Op::Place(x));
+            }
+
+            _ => {
+                self.emit(Op::Fail(
+                    x.start(),
+                    "expression is not an lvalue".to_string(),
+                ));
+            }
+        }
+    }
+
+    // an assignment, `keep` leaves its value on the stack
+    fn assign(&mut self, e: &'a AssignExpr, keep: bool) {
+        let x = e.x.as_ref();
+        let ty = self.type_of(x).unqualified();
+        let t = self.ty(ty.clone());
+
+        self.place(x);
+        self.expr(e.y.as_ref());
+        let tb = self.type_of(e.y.as_ref());
+        match e.op.binary_op() {
+            Token::ASSIGN => self.convert(&tb, &ty),
+            op => {
+                self.emit(Op::Fetch(t, x.start()));
+                self.binary(op, e.op_pos, &ty, &tb);
+                self.emit(Op::Convert(t));
+            }
+        }
+
+        self.emit(Op::Store {
+            ty: t,
+            pos: x.start(),
+            keep,
+        });
+    }
+
+    // ++ and --, `prefix` for the value after the step
+    fn step(&mut self, op: Token, x: &'a dyn Expr, prefix: bool, keep: bool) {
+        self.place(x);
+        let t = self.ty(self.type_of(x).unqualified());
+        self.emit(Op::Step {
+            ty: t,
+            delta: if op == Token::INC { 1 } else { -1 },
+            prefix,
+            keep,
+            pos: x.start(),
+        });
+    }
+
+    // a binary operator on the two values on top, the pointer arithmetic
+    // is in units of the type pointed to
+    fn binary(&mut self, op: Token, pos: usize, ta: &Type, tb: &Type) {
+        let (ta, tb) = (ta.decay(), tb.decay());
+
+        if ta.is_pointer() || tb.is_pointer() {
+            match op {
+                Token::PLUS | Token::MINUS if ta.is_pointer() && tb.is_integer() => {
+                    let stride = self.it.stride(&ta, pos);
+                    if let Some(stride) = self.check(stride) {
+                        let neg = op == Token::MINUS;
+                        self.emit(Op::Offset {
+                            stride,
+                            neg,
+                            swapped: false,
+                        });
+                    }
+                }
+                Token::PLUS if ta.is_integer() => {
+                    let stride = self.it.stride(&tb, pos);
+                    if let Some(stride) = self.check(stride) {
+                        self.emit(Op::Offset {
+                            stride,
+                            neg: false,
+                            swapped: true,
+                        });
+                    }
+                }
+                Token::MINUS if ta.is_pointer() && tb.is_pointer() => {
+                    let stride = self.it.stride(&ta, pos);
+                    if let Some(stride) = self.check(stride) {
+                        self.emit(Op::Diff(stride));
+                    }
+                }
+                op => {
+                    self.emit(Op::Compare(op));
+                }
+            }
+            return;
+        }
+
+        if matches!(op, Token::SHL | Token::SHR) {
+            let (ta, tb) = (self.ty(ta), self.ty(tb));
+            self.emit(Op::Binary { op, pos, ta, tb });
+            return;
+        }
+
+        let t = usual_arithmetic(&ta, &tb, self.it.layout());
+        let convert = (ta.unqualified() != t, tb.unqualified() != t);
+        let ty = self.ty(t);
+        self.emit(Op::Arith {
+            op,
+            ty,
+            pos,
+            convert,
+        });
+    }
+}
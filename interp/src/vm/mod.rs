@@ -0,0 +1,391 @@
+//! A bytecode backend: the body of a function is compiled once, on its
+//! first call, to the instructions of a stack machine, see `compile`. They
+//! run on the memory, frames and checks of the tree-walking interpreter,
+//! with the types, sizes, members and jump targets resolved up front. The
+//! few constructs that are not lowered, initializer lists and the
+//! declarations of static and variable length arrays, are run on the AST.
+
+use std::rc::Rc;
+
+use ast::{DeclStmt, Expr, FuncDecl};
+use sema::types::Type;
+use sema::SymbolId;
+use token::Token;
+
+use crate::eval::{compare, pointer, Place};
+use crate::memory::Region;
+use crate::value::{Pointer, Value};
+use crate::{error, fault, Interpreter, Result};
+
+mod compile;
+
+/// An instruction. Values and lvalues live on separate stacks, types are
+/// indexes in the types of the chunk and positions are those of the
+/// expressions the errors are reported at.
+pub(crate) enum Op<'a> {
+    Const(Value),
+    Pop,
+    Fail(usize, String), // an error the compiler found, when reached
+
+    // lvalues
+    Var(usize, usize),    // the variable of a slot, at a position
+    Vla(SymbolId, usize), // a variable length array, a new object at each declaration
+    Alloc(usize, usize),  // creates the object of an automatic variable
+    Deref,
+    Index(i64), // the size of an element
+    Field {
+        offset: i64,
+        bits: Option<(u32, u32)>,
+        union: bool,
+    },
+    Temp {
+        ty: usize,
+        pos: usize,
+        at: usize,
+    }, // stores the value on top in a temporary
+    Place(&'a dyn Expr),
+    Address,
+
+    // loads and stores
+    Load(usize, usize),
+    Fetch(usize, usize), // loads the lvalue on top below the value on top
+    Store {
+        ty: usize,
+        pos: usize,
+        keep: bool,
+    },
+    Step {
+        ty: usize,
+        delta: i64,
+        prefix: bool,
+        keep: bool,
+        pos: usize,
+    },
+    Zero(usize, usize), // fills the object of the lvalue on top with zeros
+    Init(usize, &'a dyn Expr),
+    Declare(&'a DeclStmt),
+
+    // operators
+    Eval(&'a dyn Expr),
+    Convert(usize),
+    Not,
+    Truth,
+    Neg(usize, usize),
+    BitNot(usize),
+    Arith {
+        op: Token,
+        ty: usize,
+        pos: usize,
+        convert: (bool, bool), // the operands to convert to `ty`
+    },
+    Binary {
+        op: Token,
+        pos: usize,
+        ta: usize,
+        tb: usize,
+    },
+    Offset {
+        stride: i64,
+        neg: bool,
+        swapped: bool, // the integer is the left operand
+    },
+    Diff(i64),
+    Compare(Token),
+
+    // control flow
+    Tick(usize), // counts an iteration of a loop or a goto, at a position
+    Jump(usize),
+    JumpIf(usize),
+    JumpUnless(usize),
+    Switch(usize),
+    Call(usize, usize), // the number of arguments and the position of the callee
+    Return(bool),
+}
+
+/// The code of a function.
+#[derive(Default)]
+pub(crate) struct Chunk<'a> {
+    pub code: Vec<Op<'a>>,
+    pub types: Vec<Type>,
+    pub slots: Vec<SymbolId>, // the variables by slot
+    pub switches: Vec<Switch>,
+}
+
+/// The jump table of a switch: the values of its case labels in order
+/// and their targets.
+#[derive(Debug, Default)]
+pub(crate) struct Switch {
+    pub cases: Vec<(Case, usize)>,
+    pub default: Option<usize>,
+    pub end: usize,
+}
+
+/// The value of a case label, or the error of one that is not a constant.
+pub(crate) type Case = std::result::Result<i128, (usize, String)>;
+
+impl<'a> Interpreter<'a> {
+    /// Runs the body of a function on the bytecode compiled for it.
+    pub(crate) fn run(&mut self, id: SymbolId, f: &'a FuncDecl) -> Result<Option<Value>> {
+        let chunk = match self.code.get(&id) {
+            Some(chunk) => chunk.clone(),
+            None => {
+                let chunk = Rc::new(compile::compile(self, &f.body));
+                self.code.insert(id, chunk.clone());
+                chunk
+            }
+        };
+        self.exec(&chunk)
+    }
+
+    fn exec(&mut self, chunk: &Chunk<'a>) -> Result<Option<Value>> {
+        let mut stack: Vec<Value> = Vec::new();
+        let mut places: Vec<Place> = Vec::new();
+        let mut slots = vec![Pointer::NULL; chunk.slots.len()];
+        let ty = |i: usize| &chunk.types[i];
+        let mut pc = 0;
+
+        loop {
+            let op = &chunk.code[pc];
+            pc += 1;
+
+            match op {
+                Op::Const(v) => stack.push(v.clone()),
+                Op::Pop => {
+                    stack.pop();
+                }
+                Op::Fail(pos, msg) => return error(*pos, msg.clone()),
+
+                Op::Var(slot, pos) => {
+                    // a slot is filled on the first use, the declaration
+                    // may have been jumped over
+                    if slots[*slot] == Pointer::NULL {
+                        slots[*slot] = self.var(chunk.slots[*slot], *pos)?;
+                    }
+                    places.push(slots[*slot].into());
+                }
+                Op::Vla(id, pos) => {
+                    let p = self.var(*id, *pos)?;
+                    places.push(p.into());
+                }
+                Op::Alloc(slot, pos) => {
+                    if slots[*slot] == Pointer::NULL {
+                        let id = chunk.slots[*slot];
+                        slots[*slot] = match self.frame().locals.get(&id) {
+                            Some(&p) => p,
+                            None => {
+                                let ty = &self.info.symbol(id).ty;
+                                let p = self.alloc_object(ty, Region::Stack, *pos)?;
+                                self.frame().locals.insert(id, p);
+                                p
+                            }
+                        };
+                    }
+                }
+                Op::Deref => {
+                    let v = stack.pop().unwrap();
+                    places.push(pointer(v).into());
+                }
+                Op::Index(size) => {
+                    let b = stack.pop().unwrap();
+                    let a = stack.pop().unwrap();
+                    let (p, i) = match (a, b) {
+                        (Value::Pointer(p), i) | (i, Value::Pointer(p)) => (p, i.as_int()),
+                        (a, i) => (pointer(a), i.as_int()),
+                    };
+                    places.push(p.offset(i as i64 * size).into());
+                }
+                Op::Field {
+                    offset,
+                    bits,
+                    union,
+                } => {
+                    let base = places.pop().unwrap();
+                    places.push(Place {
+                        ptr: base.ptr.offset(*offset),
+                        bits: *bits,
+                        punned: base.punned || *union,
+                    });
+                }
+                Op::Temp { ty: t, pos, at } => {
+                    let v = stack.pop().unwrap();
+                    let p = self.temporary(*pos, ty(*t))?;
+                    self.store(p, ty(*t), v, *at)?;
+                    places.push(p.into());
+                }
+                Op::Place(x) => {
+                    let p = self.place(*x)?;
+                    places.push(p);
+                }
+                Op::Address => {
+                    let p = places.pop().unwrap();
+                    stack.push(Value::Pointer(p.ptr));
+                }
+
+                Op::Load(t, pos) => {
+                    let p = places.pop().unwrap();
+                    stack.push(self.load_place(p, ty(*t), *pos)?);
+                }
+                Op::Fetch(t, pos) => {
+                    let p = *places.last().unwrap();
+                    let v = self.load_place(p, ty(*t), *pos)?;
+                    let b = stack.pop().unwrap();
+                    stack.push(v);
+                    stack.push(b);
+                }
+                Op::Store { ty: t, pos, keep } => {
+                    let v = stack.pop().unwrap();
+                    let p = places.pop().unwrap();
+                    if !keep {
+                        self.store_place(p, ty(*t), v, *pos)?;
+                        continue;
+                    }
+
+                    self.store_place(p, ty(*t), v.clone(), *pos)?;
+                    match p.bits {
+                        // the value of a bit-field is truncated to its width
+                        Some(_) => stack.push(self.load_place(p, ty(*t), *pos)?),
+                        None => stack.push(v),
+                    }
+                }
+                Op::Step {
+                    ty: t,
+                    delta,
+                    prefix,
+                    keep,
+                    pos,
+                } => {
+                    let p = places.pop().unwrap();
+                    let v = self.step_place(p, ty(*t), *delta, *prefix, *pos)?;
+                    if *keep {
+                        stack.push(v);
+                    }
+                }
+                Op::Zero(t, pos) => {
+                    let p = places.last().unwrap().ptr;
+                    let size = self.size_of(ty(*t), *pos)?;
+                    self.mem.fill(p, 0, size as usize).map_err(fault(*pos))?;
+                }
+                Op::Init(t, x) => {
+                    let p = places.pop().unwrap();
+                    self.init(p, ty(*t), *x)?;
+                }
+                Op::Declare(d) => self.decl(d)?,
+
+                Op::Eval(x) => {
+                    let v = self.eval(*x)?;
+                    stack.push(v);
+                }
+                Op::Convert(t) => {
+                    let v = stack.pop().unwrap();
+                    stack.push(v.convert(ty(*t), self.layout()));
+                }
+                Op::Not => {
+                    let v = stack.pop().unwrap();
+                    stack.push(Value::Int(!v.is_true() as i128));
+                }
+                Op::Truth => {
+                    let v = stack.pop().unwrap();
+                    stack.push(Value::Int(v.is_true() as i128));
+                }
+                Op::Neg(t, pos) => {
+                    let v = match stack.pop().unwrap() {
+                        Value::Float(f) => Value::Float(-f),
+                        v => {
+                            if self.checked {
+                                self.check_neg(v.as_int(), ty(*t), *pos)?;
+                            }
+                            Value::Int(-v.as_int()).convert(ty(*t), self.layout())
+                        }
+                    };
+                    stack.push(v);
+                }
+                Op::BitNot(t) => {
+                    let v = stack.pop().unwrap();
+                    stack.push(Value::Int(!v.as_int()).convert(ty(*t), self.layout()));
+                }
+                Op::Arith {
+                    op,
+                    ty: t,
+                    pos,
+                    convert,
+                } => {
+                    let (t, dl) = (ty(*t), self.layout());
+                    let mut b = stack.pop().unwrap();
+                    let mut a = stack.pop().unwrap();
+                    if convert.0 {
+                        a = a.convert(t, dl);
+                    }
+                    if convert.1 {
+                        b = b.convert(t, dl);
+                    }
+                    stack.push(self.arith(*op, *pos, a, b, t)?);
+                }
+                Op::Binary { op, pos, ta, tb } => {
+                    let b = stack.pop().unwrap();
+                    let a = stack.pop().unwrap();
+                    stack.push(self.binary(*op, *pos, a, ty(*ta), b, ty(*tb))?);
+                }
+                Op::Offset {
+                    stride,
+                    neg,
+                    swapped,
+                } => {
+                    let b = stack.pop().unwrap();
+                    let a = stack.pop().unwrap();
+                    let (p, n) = if *swapped { (b, a) } else { (a, b) };
+                    let n = n.as_int() as i64 * stride;
+                    let n = if *neg { -n } else { n };
+                    stack.push(Value::Pointer(pointer(p).offset(n)));
+                }
+                Op::Diff(stride) => {
+                    let b = stack.pop().unwrap();
+                    let a = stack.pop().unwrap();
+                    let d = (a.as_int() as i64).wrapping_sub(b.as_int() as i64);
+                    stack.push(Value::Int((d / stride.max(&1)) as i128));
+                }
+                Op::Compare(op) => {
+                    let b = stack.pop().unwrap();
+                    let a = stack.pop().unwrap();
+                    stack.push(Value::Int(compare(*op, a.as_int(), b.as_int()) as i128));
+                }
+
+                Op::Jump(to) => pc = *to,
+                Op::JumpIf(to) => {
+                    if stack.pop().unwrap().is_true() {
+                        pc = *to;
+                    }
+                }
+                Op::JumpUnless(to) => {
+                    if !stack.pop().unwrap().is_true() {
+                        pc = *to;
+                    }
+                }
+                Op::Switch(i) => {
+                    let v = stack.pop().unwrap().as_int();
+                    let s = &chunk.switches[*i];
+                    pc = s.default.unwrap_or(s.end);
+                    for (c, to) in s.cases.iter() {
+                        match c {
+                            Ok(c) if *c == v => {
+                                pc = *to;
+                                break;
+                            }
+                            Ok(_) => {}
+                            Err((pos, msg)) => return error(*pos, msg.clone()),
+                        }
+                    }
+                }
+                Op::Tick(pos) => self.tick(*pos)?,
+                Op::Call(argc, pos) => {
+                    let args = stack.split_off(stack.len() - argc);
+                    let Value::Func(id) = stack.pop().unwrap() else {
+                        return error(*pos, "call through a pointer that is not a function");
+                    };
+                    let v = self.call(id, args, *pos)?;
+                    stack.push(v);
+                }
+                Op::Return(value) => return Ok(value.then(|| stack.pop().unwrap())),
+            }
+        }
+    }
+}
@@ -0,0 +1,341 @@
+//! Constant propagation: folds the instructions whose operands are
+//! constants, and the branches on them, until nothing changes. The
+//! operations that would trap or are undefined, a division by zero or a
+//! shift by the width, are left for the program to run.
+
+use std::collections::HashMap;
+
+use crate::{BinOp, CastOp, CmpOp, Function, InstKind, Operand, Term, Ty, UnOp, ValueId};
+
+pub fn run(func: &mut Function) {
+    loop {
+        let mut map: HashMap<ValueId, Operand> = HashMap::new();
+
+        for b in 0..func.blocks.len() {
+            for i in 0..func.blocks[b].insts.len() {
+                let id = func.blocks[b].insts[i];
+                for v in func.insts[id.0].kind.operands_mut() {
+                    resolve(&map, v);
+                }
+                let inst = func.inst(id);
+                let to = match &inst.kind {
+                    InstKind::Phi(args) => phi(id, args, inst.ty),
+                    kind => fold(func, kind, inst.ty),
+                };
+                if let Some(to) = to {
+                    map.insert(id, to);
+                }
+            }
+            let term = &mut func.blocks[b].term;
+            if let Some(v) = term.operand_mut() {
+                resolve(&map, v);
+            }
+            if let Some(to) = branch(term) {
+                *term = Term::Jump(to);
+            }
+        }
+
+        for b in func.blocks.iter_mut() {
+            b.insts.retain(|v| !map.contains_key(v));
+        }
+        func.replace_uses(&map);
+        // a phi of an edge that went away may fold on the next round
+        let removed = func.remove_unreachable();
+        if map.is_empty() && !removed {
+            return;
+        }
+    }
+}
+
+fn resolve(map: &HashMap<ValueId, Operand>, v: &mut Operand) {
+    while let Operand::Value(id) = v {
+        match map.get(id) {
+            Some(to) => *v = to.clone(),
+            None => break,
+        }
+    }
+}
+
+// a phi whose operands are all the same value, or itself, is that value
+fn phi(id: ValueId, args: &[(crate::BlockId, Operand)], ty: Ty) -> Option<Operand> {
+    let mut value: Option<&Operand> = None;
+    for (_, v) in args.iter() {
+        match v {
+            Operand::Value(v) if *v == id => {}
+            Operand::Undef(_) => {}
+            v if value.is_none() => value = Some(v),
+            v if value == Some(v) => {}
+            _ => return None,
+        }
+    }
+    Some(value.cloned().unwrap_or(Operand::Undef(ty)))
+}
+
+// the target of a branch on a constant
+fn branch(term: &Term) -> Option<crate::BlockId> {
+    match term {
+        Term::Branch(_, t, f) if t == f => Some(*t),
+        Term::Branch(Operand::Int(c, _), t, f) => Some(if *c != 0 { *t } else { *f }),
+        Term::Branch(Operand::Global(..), t, _) => Some(*t), // never null
+        Term::Switch(Operand::Int(c, _), cases, default) => Some(
+            cases
+                .iter()
+                .find(|(n, _)| n == c)
+                .map_or(*default, |(_, b)| *b),
+        ),
+        _ => None,
+    }
+}
+
+/// The constant, or the operand, an instruction of type `ty` is equal to.
+pub(crate) fn fold(func: &Function, kind: &InstKind, ty: Ty) -> Option<Operand> {
+    match kind {
+        InstKind::Binary(op, a, b) => binary(*op, a, b, ty),
+        InstKind::Unary(op, a) => match (op, a) {
+            (UnOp::Neg, Operand::Int(a, _)) => Some(int(a.wrapping_neg(), ty)),
+            (UnOp::Not, Operand::Int(a, _)) => Some(int(!a, ty)),
+            (UnOp::FNeg, Operand::Float(a, _)) => Some(Operand::Float(-a, ty)),
+            _ => None,
+        },
+        InstKind::Cmp(op, a, b) => {
+            let t = func.type_of(a);
+            let r = match (a, b) {
+                (Operand::Int(a, _), Operand::Int(b, _)) => match op {
+                    CmpOp::Eq => a == b,
+                    CmpOp::Ne => a != b,
+                    CmpOp::SLt => a < b,
+                    CmpOp::SLe => a <= b,
+                    CmpOp::SGt => a > b,
+                    CmpOp::SGe => a >= b,
+                    CmpOp::ULt => unsigned(*a, t) < unsigned(*b, t),
+                    CmpOp::ULe => unsigned(*a, t) <= unsigned(*b, t),
+                    CmpOp::UGt => unsigned(*a, t) > unsigned(*b, t),
+                    CmpOp::UGe => unsigned(*a, t) >= unsigned(*b, t),
+                    _ => return None,
+                },
+                (Operand::Float(a, _), Operand::Float(b, _)) => match op {
+                    CmpOp::FEq => a == b,
+                    CmpOp::FNe => a != b,
+                    CmpOp::FLt => a < b,
+                    CmpOp::FLe => a <= b,
+                    CmpOp::FGt => a > b,
+                    CmpOp::FGe => a >= b,
+                    _ => return None,
+                },
+                _ => return None,
+            };
+            Some(Operand::Int(r as i64, Ty::I32))
+        }
+        InstKind::Cast(op, a) => cast(*op, a, func.type_of(a), ty),
+        InstKind::PtrAdd(p, Operand::Int(0, _)) => Some(p.clone()),
+        InstKind::PtrAdd(Operand::Global(name, n), Operand::Int(m, _)) => {
+            Some(Operand::Global(name.clone(), n.wrapping_add(*m)))
+        }
+        InstKind::PtrAdd(Operand::Int(n, _), Operand::Int(m, _)) => {
+            Some(Operand::Int(n.wrapping_add(*m), Ty::Ptr))
+        }
+        _ => None,
+    }
+}
+
+fn binary(op: BinOp, a: &Operand, b: &Operand, ty: Ty) -> Option<Operand> {
+    use Operand::{Float, Int};
+
+    if let (Float(x, _), Float(y, _)) = (a, b) {
+        let r = match op {
+            BinOp::FAdd => x + y,
+            BinOp::FSub => x - y,
+            BinOp::FMul => x * y,
+            BinOp::FDiv => x / y,
+            _ => return None,
+        };
+        return Some(float(r, ty));
+    }
+
+    let (x, y) = match (a, b) {
+        (Int(x, _), Int(y, _)) => (*x, *y),
+        // the identities that hold for any x
+        (x, Int(0, _))
+            if matches!(
+                op,
+                BinOp::Add
+                    | BinOp::Sub
+                    | BinOp::Or
+                    | BinOp::Xor
+                    | BinOp::Shl
+                    | BinOp::LShr
+                    | BinOp::AShr
+            ) =>
+        {
+            return Some(x.clone())
+        }
+        (Int(0, _), x) if matches!(op, BinOp::Add | BinOp::Or | BinOp::Xor) => {
+            return Some(x.clone())
+        }
+        (x, Int(1, _)) | (Int(1, _), x) if op == BinOp::Mul => return Some(x.clone()),
+        (x, Int(1, _)) if matches!(op, BinOp::SDiv | BinOp::UDiv) => return Some(x.clone()),
+        (_, Int(0, _)) | (Int(0, _), _) if matches!(op, BinOp::Mul | BinOp::And) => {
+            return Some(int(0, ty))
+        }
+        _ => return None,
+    };
+
+    let bits = ty.size() as u32 * 8;
+    let (ux, uy) = (unsigned(x, ty), unsigned(y, ty));
+    let r = match op {
+        BinOp::Add => x.wrapping_add(y),
+        BinOp::Sub => x.wrapping_sub(y),
+        BinOp::Mul => x.wrapping_mul(y),
+        BinOp::SDiv | BinOp::SRem if y == 0 || (y == -1 && x == min(ty)) => return None,
+        BinOp::SDiv => x / y,
+        BinOp::SRem => x % y,
+        BinOp::UDiv | BinOp::URem if uy == 0 => return None,
+        BinOp::UDiv => (ux / uy) as i64,
+        BinOp::URem => (ux % uy) as i64,
+        BinOp::And => x & y,
+        BinOp::Or => x | y,
+        BinOp::Xor => x ^ y,
+        BinOp::Shl | BinOp::LShr | BinOp::AShr if uy >= bits as u64 => return None,
+        BinOp::Shl => x << uy,
+        BinOp::LShr => (ux >> uy) as i64,
+        BinOp::AShr => x >> uy,
+        _ => return None,
+    };
+    Some(int(r, ty))
+}
+
+fn cast(op: CastOp, a: &Operand, from: Ty, to: Ty) -> Option<Operand> {
+    match (op, a) {
+        (
+            CastOp::Trunc | CastOp::SExt | CastOp::PtrToInt | CastOp::IntToPtr,
+            Operand::Int(n, _),
+        ) => Some(int(*n, to)),
+        (CastOp::ZExt, Operand::Int(n, _)) => Some(int(unsigned(*n, from) as i64, to)),
+        (CastOp::FpTrunc | CastOp::FpExt, Operand::Float(x, _)) => Some(float(*x, to)),
+        (CastOp::SiToFp, Operand::Int(n, _)) => Some(float(*n as f64, to)),
+        (CastOp::UiToFp, Operand::Int(n, _)) => Some(float(unsigned(*n, from) as f64, to)),
+        (CastOp::FpToSi, Operand::Float(x, _)) => {
+            let x = x.trunc();
+            let fits = x >= min(to) as f64 && x < -(min(to) as f64);
+            fits.then(|| int(x as i64, to))
+        }
+        (CastOp::FpToUi, Operand::Float(x, _)) => {
+            let x = x.trunc();
+            let fits = x >= 0.0 && x < 2f64.powi(to.size() as i32 * 8);
+            fits.then(|| int(x as u64 as i64, to))
+        }
+        (_, Operand::Undef(_)) => Some(Operand::Undef(to)),
+        _ => None,
+    }
+}
+
+/// An integer constant of a type, sign-extended from its width.
+pub(crate) fn int(n: i64, ty: Ty) -> Operand {
+    let shift = 64 - ty.size().clamp(1, 8) as u32 * 8;
+    Operand::Int((n << shift) >> shift, ty)
+}
+
+/// A floating constant of a type, rounded to its precision.
+pub(crate) fn float(x: f64, ty: Ty) -> Operand {
+    match ty {
+        Ty::F32 => Operand::Float(x as f32 as f64, ty),
+        _ => Operand::Float(x, ty),
+    }
+}
+
+// the bits of an integer of a type
+fn unsigned(n: i64, ty: Ty) -> u64 {
+    match ty.size() {
+        1..=7 => n as u64 & ((1 << (ty.size() * 8)) - 1),
+        _ => n as u64,
+    }
+}
+
+// the least value of a signed integer type
+fn min(ty: Ty) -> i64 {
+    i64::MIN >> (64 - ty.size().clamp(1, 8) * 8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold() {
+        let func = crate::tests::function("int f(void) { return 0; }");
+        let i = |n| Operand::Int(n, Ty::I32);
+        let b = |op, x: i64, y: i64| InstKind::Binary(op, i(x), i(y));
+        let c = |op, x: i64, y: i64| InstKind::Cmp(op, i(x), i(y));
+
+        let tests = [
+            (b(BinOp::Add, 2147483647, 1), Some(i(-2147483648))),
+            (b(BinOp::Mul, 6, 7), Some(i(42))),
+            (b(BinOp::SDiv, -7, 2), Some(i(-3))),
+            (b(BinOp::UDiv, -1, 2), Some(i(2147483647))),
+            (b(BinOp::SRem, -7, 2), Some(i(-1))),
+            (b(BinOp::SDiv, 1, 0), None),
+            (b(BinOp::SDiv, -2147483648, -1), None),
+            (b(BinOp::Shl, 1, 31), Some(i(-2147483648))),
+            (b(BinOp::Shl, 1, 32), None),
+            (b(BinOp::LShr, -1, 28), Some(i(15))),
+            (b(BinOp::AShr, -16, 2), Some(i(-4))),
+            (c(CmpOp::SLt, -1, 0), Some(i(1))),
+            (c(CmpOp::ULt, -1, 0), Some(i(0))),
+            (
+                InstKind::Binary(BinOp::Add, Operand::Value(ValueId(0)), i(0)),
+                Some(Operand::Value(ValueId(0))),
+            ),
+            (
+                InstKind::Binary(BinOp::Mul, i(0), Operand::Value(ValueId(0))),
+                Some(i(0)),
+            ),
+            (
+                InstKind::Cast(CastOp::Trunc, Operand::Int(4294967340, Ty::I64)),
+                Some(Operand::Int(44, Ty::I32)),
+            ),
+            (
+                InstKind::Cast(CastOp::FpToSi, Operand::Float(-2.5, Ty::F64)),
+                Some(i(-2)),
+            ),
+            (
+                InstKind::Cast(CastOp::FpToSi, Operand::Float(3e9, Ty::F64)),
+                None,
+            ),
+            (
+                InstKind::PtrAdd(
+                    Operand::Global("a".to_string(), 4),
+                    Operand::Int(8, Ty::I64),
+                ),
+                Some(Operand::Global("a".to_string(), 12)),
+            ),
+        ];
+
+        for (i, (kind, want)) in tests.iter().enumerate() {
+            let got = fold(&func, kind, Ty::I32);
+            assert_eq!(
+                &got,
+                want,
+                "[{}/{}] test case failed: {:?}",
+                i + 1,
+                tests.len(),
+                kind
+            );
+        }
+    }
+
+    #[test]
+    fn test_branches() {
+        let got = crate::tests::optimized(
+            "int f(int x) { if (2 > 1) x = x + 1; else x = x - 1; return x * 1; }",
+            &["mem2reg", "constprop", "dce"],
+        );
+        let want = "\
+define i32 @f(i32 %0) {
+bb0:
+  %1 = add i32 %0, 1
+  ret i32 %1
+}
+";
+        assert_eq!(got, want);
+    }
+}
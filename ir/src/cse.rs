@@ -0,0 +1,120 @@
+//! Common subexpression elimination: an instruction that computes what
+//! one in a dominating block already did is replaced by it. Only the
+//! instructions that neither read nor write memory are candidates.
+
+use std::collections::HashMap;
+
+use crate::dom::DomTree;
+use crate::{BinOp, Function, Inst, InstKind, Operand, ValueId};
+
+pub fn run(func: &mut Function) {
+    func.remove_unreachable();
+    let dom = DomTree::new(func);
+    let mut map: HashMap<ValueId, Operand> = HashMap::new();
+    let mut seen: HashMap<Inst, ValueId> = HashMap::new();
+
+    // a walk of the dominator tree, the expressions of a block are
+    // forgotten when it is left
+    enum Visit {
+        Enter(usize),
+        Leave(Vec<Inst>),
+    }
+    let mut work = vec![Visit::Enter(0)];
+    while let Some(visit) = work.pop() {
+        let b = match visit {
+            Visit::Enter(b) => b,
+            Visit::Leave(keys) => {
+                for k in keys {
+                    seen.remove(&k);
+                }
+                continue;
+            }
+        };
+
+        let mut keys = Vec::new();
+        let mut removed = Vec::new();
+        for &id in func.blocks[b].insts.iter() {
+            for v in func.insts[id.0].kind.operands_mut() {
+                if let Operand::Value(x) = v {
+                    if let Some(to) = map.get(x) {
+                        *v = to.clone();
+                    }
+                }
+            }
+            let Some(key) = key(func.inst(id)) else {
+                continue;
+            };
+            match seen.get(&key) {
+                Some(&v) => {
+                    map.insert(id, Operand::Value(v));
+                    removed.push(id);
+                }
+                None => {
+                    seen.insert(key.clone(), id);
+                    keys.push(key);
+                }
+            }
+        }
+        func.blocks[b].insts.retain(|v| !removed.contains(v));
+
+        work.push(Visit::Leave(keys));
+        for &c in dom.children[b].iter().rev() {
+            work.push(Visit::Enter(c.0));
+        }
+    }
+    func.replace_uses(&map);
+}
+
+// the instruction as a key, with the operands of the commutative
+// operators in one order
+fn key(inst: &Inst) -> Option<Inst> {
+    let mut inst = inst.clone();
+    match &mut inst.kind {
+        InstKind::Binary(op, a, b) => {
+            let commutative = matches!(
+                op,
+                BinOp::Add | BinOp::Mul | BinOp::And | BinOp::Or | BinOp::Xor
+            );
+            if commutative && order(a) > order(b) {
+                std::mem::swap(a, b);
+            }
+        }
+        InstKind::Unary(..) | InstKind::Cmp(..) | InstKind::Cast(..) | InstKind::PtrAdd(..) => {}
+        _ => return None,
+    }
+    Some(inst)
+}
+
+// values before constants
+fn order(v: &Operand) -> (bool, usize) {
+    match v {
+        Operand::Value(v) => (false, v.0),
+        _ => (true, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::optimized;
+
+    #[test]
+    fn test_cse() {
+        let got = optimized(
+            "int f(int a, int b) { int x = a * b; if (a) return b * a + x; return a * b; }",
+            &["mem2reg", "cse", "dce"],
+        );
+        let want = "\
+define i32 @f(i32 %0, i32 %1) {
+bb0:
+  %2 = mul i32 %0, %1
+  br i32 %0, bb1, bb2
+bb1:
+  %3 = add i32 %2, %2
+  ret i32 %3
+bb2:
+  ret i32 %2
+}
+";
+        assert_eq!(got, want);
+    }
+}
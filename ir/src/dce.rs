@@ -0,0 +1,172 @@
+//! Dead code elimination: removes the instructions no effect or
+//! terminator depends on, then tidies the control flow graph. A branch to
+//! the same block twice becomes a jump, an empty block that only jumps is
+//! bypassed and a block is merged into its only predecessor.
+
+use std::collections::HashMap;
+
+use crate::{BlockId, Function, InstKind, Operand, Term, ValueId};
+
+pub fn run(func: &mut Function) {
+    func.remove_unreachable();
+    loop {
+        sweep(func);
+        let changed = simplify(func);
+        let removed = func.remove_unreachable();
+        if !changed && !removed {
+            return;
+        }
+    }
+}
+
+// marks the values used from the effects and the terminators, and
+// removes the others, so that cycles of unused phis go too
+fn sweep(func: &mut Function) {
+    let mut live = vec![false; func.insts.len()];
+    let mut work: Vec<ValueId> = Vec::new();
+    let mut mark = |v: &Operand, work: &mut Vec<ValueId>| {
+        if let Operand::Value(v) = v {
+            if !std::mem::replace(&mut live[v.0], true) {
+                work.push(*v);
+            }
+        }
+    };
+
+    for b in func.blocks.iter() {
+        for &v in b.insts.iter() {
+            if func.inst(v).kind.has_effects() {
+                mark(&Operand::Value(v), &mut work);
+            }
+        }
+        if let Some(v) = b.term.operand() {
+            mark(v, &mut work);
+        }
+    }
+    while let Some(v) = work.pop() {
+        for x in func.inst(v).kind.operands() {
+            mark(x, &mut work);
+        }
+    }
+
+    for b in func.blocks.iter_mut() {
+        b.insts.retain(|v| live[v.0]);
+    }
+}
+
+// makes one change to the graph, reports whether there was one
+fn simplify(func: &mut Function) -> bool {
+    for b in func.blocks.iter_mut() {
+        if let Term::Branch(_, t, f) = b.term {
+            if t == f {
+                b.term = Term::Jump(t);
+            }
+        }
+    }
+
+    let preds = func.preds();
+    for b in 0..func.blocks.len() {
+        let Term::Jump(s) = func.blocks[b].term else {
+            continue;
+        };
+        if s.0 == b || s.0 == 0 {
+            continue;
+        }
+
+        if preds[s.0] == [BlockId(b)] {
+            merge(func, BlockId(b), s);
+            return true;
+        }
+
+        // the predecessors of an empty block can jump to its successor
+        // instead, unless phis there tell them apart
+        let phis = func.blocks[s.0]
+            .insts
+            .iter()
+            .any(|&v| matches!(func.inst(v).kind, InstKind::Phi(_)));
+        if b != 0 && func.blocks[b].insts.is_empty() && !phis {
+            for &p in preds[b].iter() {
+                for t in func.blocks[p.0].term.succs_mut() {
+                    if *t == BlockId(b) {
+                        *t = s;
+                    }
+                }
+            }
+            if !preds[b].is_empty() {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+// appends `s` to `b`, its only predecessor
+fn merge(func: &mut Function, b: BlockId, s: BlockId) {
+    let insts = std::mem::take(&mut func.blocks[s.0].insts);
+    let term = std::mem::replace(&mut func.blocks[s.0].term, Term::Unreachable);
+
+    let mut map: HashMap<ValueId, Operand> = HashMap::new();
+    for v in insts {
+        match &func.inst(v).kind {
+            InstKind::Phi(args) => {
+                let to = args
+                    .first()
+                    .map_or(Operand::Undef(func.inst(v).ty), |a| a.1.clone());
+                map.insert(v, to);
+            }
+            _ => func.blocks[b.0].insts.push(v),
+        }
+    }
+
+    // the successors of `s` are now reached from `b`
+    for t in term.succs() {
+        for &v in func.blocks[t.0].insts.iter() {
+            if let InstKind::Phi(args) = &mut func.insts[v.0].kind {
+                for (p, _) in args.iter_mut() {
+                    if *p == s {
+                        *p = b;
+                    }
+                }
+            }
+        }
+    }
+    func.blocks[b.0].term = term;
+    func.replace_uses(&map);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::optimized;
+
+    #[test]
+    fn test_dead_code() {
+        let got = optimized(
+            "int f(int x) { int y = x * 2; int z = y + 1; return x; }",
+            &["mem2reg", "dce"],
+        );
+        let want = "\
+define i32 @f(i32 %0) {
+bb0:
+  ret i32 %0
+}
+";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let got = optimized(
+            "int f(int x) { if (x) { } else { } while (0) { } return x; }",
+            &["dce"],
+        );
+        let want = "\
+define i32 @f(i32 %0) {
+bb0:
+  %1 = alloca 4, align 4
+  store i32 %0, ptr %1
+  %2 = load i32, ptr %1
+  ret i32 %2
+}
+";
+        assert_eq!(got, want);
+    }
+}
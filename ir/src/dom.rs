@@ -0,0 +1,194 @@
+//! Dominators, computed with the iterative algorithm of Cooper, Harvey and
+//! Kennedy over the reverse postorder of the blocks.
+
+use crate::{BlockId, Function};
+
+/// The dominator tree of the blocks the entry reaches.
+#[derive(Debug)]
+pub struct DomTree {
+    pub rpo: Vec<BlockId>,          // the reachable blocks in reverse postorder
+    pub idom: Vec<Option<BlockId>>, // the entry is its own immediate dominator
+    pub children: Vec<Vec<BlockId>>,
+    pub preds: Vec<Vec<BlockId>>,
+}
+
+impl DomTree {
+    pub fn new(func: &Function) -> DomTree {
+        let n = func.blocks.len();
+        let preds = func.preds();
+
+        // postorder, without recursion: a block is finished once all its
+        // successors are
+        let mut post = Vec::with_capacity(n);
+        let mut seen = vec![false; n];
+        let mut stack = vec![(BlockId(0), 0)];
+        seen[0] = true;
+        while let Some((b, i)) = stack.pop() {
+            let succs = func.blocks[b.0].term.succs();
+            match succs.get(i) {
+                Some(&s) => {
+                    stack.push((b, i + 1));
+                    if !std::mem::replace(&mut seen[s.0], true) {
+                        stack.push((s, 0));
+                    }
+                }
+                None => post.push(b),
+            }
+        }
+        let rpo: Vec<BlockId> = post.into_iter().rev().collect();
+        let mut order = vec![usize::MAX; n];
+        for (i, b) in rpo.iter().enumerate() {
+            order[b.0] = i;
+        }
+
+        let mut idom: Vec<Option<BlockId>> = vec![None; n];
+        idom[0] = Some(BlockId(0));
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &b in rpo.iter().skip(1) {
+                let mut new: Option<BlockId> = None;
+                for &p in preds[b.0].iter() {
+                    if idom[p.0].is_none() {
+                        continue;
+                    }
+                    new = Some(match new {
+                        None => p,
+                        Some(q) => intersect(&idom, &order, p, q),
+                    });
+                }
+                if new.is_some() && idom[b.0] != new {
+                    idom[b.0] = new;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut children = vec![Vec::new(); n];
+        for &b in rpo.iter().skip(1) {
+            if let Some(d) = idom[b.0] {
+                children[d.0].push(b);
+            }
+        }
+
+        DomTree {
+            rpo,
+            idom,
+            children,
+            preds,
+        }
+    }
+
+    /// Reports whether `a` dominates `b`.
+    pub fn dominates(&self, a: BlockId, mut b: BlockId) -> bool {
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.idom[b.0] {
+                Some(d) if d != b => b = d,
+                _ => return false,
+            }
+        }
+    }
+
+    /// The dominance frontier of each block: the blocks where its
+    /// dominance ends, where the definitions in it meet others.
+    pub fn frontiers(&self) -> Vec<Vec<BlockId>> {
+        let mut df: Vec<Vec<BlockId>> = vec![Vec::new(); self.idom.len()];
+        for &b in self.rpo.iter() {
+            let preds = &self.preds[b.0];
+            if preds.len() < 2 {
+                continue;
+            }
+            let Some(d) = self.idom[b.0] else { continue };
+            for &p in preds.iter() {
+                let mut runner = p;
+                while runner != d && self.idom[runner.0].is_some() {
+                    if !df[runner.0].contains(&b) {
+                        df[runner.0].push(b);
+                    }
+                    runner = self.idom[runner.0].unwrap();
+                }
+            }
+        }
+        df
+    }
+}
+
+fn intersect(idom: &[Option<BlockId>], order: &[usize], mut a: BlockId, mut b: BlockId) -> BlockId {
+    while a != b {
+        while order[a.0] > order[b.0] {
+            a = idom[a.0].unwrap();
+        }
+        while order[b.0] > order[a.0] {
+            b = idom[b.0].unwrap();
+        }
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Block, Operand, Term, Ty};
+
+    // a function of empty blocks with the given terminators
+    fn graph(terms: Vec<Term>) -> Function {
+        Function {
+            name: "f".to_string(),
+            params: Vec::new(),
+            ret: Ty::Void,
+            variadic: false,
+            internal: false,
+            insts: Vec::new(),
+            blocks: terms
+                .into_iter()
+                .map(|term| Block {
+                    insts: Vec::new(),
+                    term,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diamond_and_loop() {
+        let c = Operand::Int(1, Ty::I32);
+        // 0 -> 1 -> {2, 3} -> 4 -> {1, 5}
+        let func = graph(vec![
+            Term::Jump(BlockId(1)),
+            Term::Branch(c.clone(), BlockId(2), BlockId(3)),
+            Term::Jump(BlockId(4)),
+            Term::Jump(BlockId(4)),
+            Term::Branch(c, BlockId(1), BlockId(5)),
+            Term::Return(None),
+        ]);
+        let dom = DomTree::new(&func);
+
+        let idom: Vec<Option<usize>> = dom.idom.iter().map(|d| d.map(|b| b.0)).collect();
+        assert_eq!(
+            idom,
+            vec![Some(0), Some(0), Some(1), Some(1), Some(1), Some(4)]
+        );
+        assert!(dom.dominates(BlockId(1), BlockId(5)));
+        assert!(!dom.dominates(BlockId(2), BlockId(4)));
+
+        let df = dom.frontiers();
+        assert_eq!(df[2], vec![BlockId(4)]);
+        assert_eq!(df[3], vec![BlockId(4)]);
+        assert_eq!(df[4], vec![BlockId(1)]);
+        assert_eq!(df[1], vec![BlockId(1)]);
+        assert!(df[0].is_empty());
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let func = graph(vec![Term::Return(None), Term::Jump(BlockId(0))]);
+        let dom = DomTree::new(&func);
+        assert_eq!(dom.rpo, vec![BlockId(0)]);
+        assert_eq!(dom.idom[1], None);
+        // the edge of the unreachable block is not followed
+        assert!(dom.frontiers()[1].is_empty());
+    }
+}
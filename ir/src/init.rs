@@ -0,0 +1,264 @@
+//! Initializers, flattened to the scalars and strings they store at
+//! offsets in the object, with the rules of the interpreter: braces around
+//! the initializers of nested aggregates may be left out and a designator
+//! moves to the member or element it names.
+
+use ast::{Designator, Expr, ExprKind, InitList};
+use sema::consts::Eval;
+use sema::types::{ArrayLen, Type, TypeKind};
+use token::Token;
+
+use crate::lower::Types;
+use crate::{error, Error};
+
+/// A part of an object and the expression that initializes it: a scalar,
+/// a record or a character array initialized by a string literal.
+pub(crate) struct Leaf<'x> {
+    pub offset: u64,
+    pub bits: Option<(u32, u32)>, // the shift and width of a bit-field
+    pub ty: Type,
+    pub value: &'x dyn Expr,
+}
+
+// the position of an initializer in the object of a braced list
+struct Cursor {
+    ty: Type,
+    offset: u64,
+    index: usize, // the next element or member
+}
+
+/// The parts of an object of type `ty` that the initializer `x` sets.
+pub(crate) fn leaves<'x>(t: &Types, ty: &Type, x: &'x dyn Expr) -> Result<Vec<Leaf<'x>>, Error> {
+    let mut out = Vec::new();
+    init(t, 0, None, ty, x, &mut out)?;
+    Ok(out)
+}
+
+/// Reports whether `x` is a string literal.
+pub(crate) fn is_string(x: &dyn Expr) -> bool {
+    matches!(x.kind(), ExprKind::BasicLit(b) if b.tok == Token::STRING)
+}
+
+pub(crate) fn is_aggregate(ty: &Type) -> bool {
+    ty.is_array() || ty.is_record()
+}
+
+fn init<'x>(
+    t: &Types,
+    offset: u64,
+    bits: Option<(u32, u32)>,
+    ty: &Type,
+    x: &'x dyn Expr,
+    out: &mut Vec<Leaf<'x>>,
+) -> Result<(), Error> {
+    match x.kind() {
+        ExprKind::InitList(l) => init_list(t, offset, ty, l, out),
+        _ => {
+            out.push(Leaf {
+                offset,
+                bits,
+                ty: ty.clone(),
+                value: x,
+            });
+            Ok(())
+        }
+    }
+}
+
+fn init_list<'x>(
+    t: &Types,
+    offset: u64,
+    ty: &Type,
+    l: &'x InitList,
+    out: &mut Vec<Leaf<'x>>,
+) -> Result<(), Error> {
+    if !is_aggregate(ty) {
+        return match l.elems.first() {
+            Some(e) => init(t, offset, None, ty, e.value.as_ref(), out),
+            None => Ok(()),
+        };
+    }
+
+    // `char s[] = {"abc"}`
+    if let [e] = &l.elems[..] {
+        let x = e.value.as_ref();
+        if is_string(x) && e.designators.is_empty() && ty.elem().is_some_and(Type::is_integer) {
+            return init(t, offset, None, ty, x, out);
+        }
+    }
+
+    let mut stack = vec![Cursor {
+        ty: ty.clone(),
+        offset,
+        index: 0,
+    }];
+
+    for e in l.elems.iter() {
+        let x = e.value.as_ref();
+
+        if !e.designators.is_empty() {
+            stack.truncate(1);
+            for (i, d) in e.designators.iter().enumerate() {
+                designate(t, &mut stack, d)?;
+                if i + 1 < e.designators.len() {
+                    let c = stack.last().unwrap();
+                    let (ty, offset, _) = subobject(t, c, c.index, d.start())?;
+                    stack.push(Cursor {
+                        ty,
+                        offset,
+                        index: 0,
+                    });
+                }
+            }
+        } else {
+            // the next subobject, after those that are complete
+            while stack.len() > 1 && next(t, stack.last().unwrap()).is_none() {
+                stack.pop();
+                advance(stack.last_mut().unwrap());
+            }
+            let c = stack.last_mut().unwrap();
+            match next(t, c) {
+                Some(i) => c.index = i,
+                None => break, // excess initializers
+            }
+        }
+
+        // descends into aggregates until the initializer fits
+        loop {
+            let c = stack.last().unwrap();
+            let (ty, offset, bits) = subobject(t, c, c.index, x.start())?;
+
+            let fits = match x.kind() {
+                ExprKind::InitList(_) => true,
+                ExprKind::BasicLit(b) if b.tok == Token::STRING => {
+                    !is_aggregate(&ty) || ty.elem().is_some_and(Type::is_integer)
+                }
+                _ => !is_aggregate(&ty) || t.type_of(x).is_record(),
+            };
+            if fits {
+                init(t, offset, bits, &ty, x, out)?;
+                break;
+            }
+
+            stack.push(Cursor {
+                ty,
+                offset,
+                index: 0,
+            });
+            let c = stack.last_mut().unwrap();
+            match next(t, c) {
+                Some(i) => c.index = i,
+                None => break,
+            }
+        }
+
+        advance(stack.last_mut().unwrap());
+    }
+
+    Ok(())
+}
+
+// the element or member at or after the cursor that an initializer
+// without a designator initializes, unnamed bit-fields are skipped
+fn next(t: &Types, c: &Cursor) -> Option<usize> {
+    match &c.ty.kind {
+        TypeKind::Array(_, ArrayLen::Fixed(n)) => (c.index < *n as usize).then_some(c.index),
+        TypeKind::Struct(id) | TypeKind::Union(id) => {
+            let fields = &t.record(*id)?.fields;
+            (c.index..fields.len()).find(|&i| {
+                let f = &fields[i];
+                f.name.is_some() || f.ty.is_record()
+            })
+        }
+        _ => None,
+    }
+}
+
+// moves the innermost cursor to the subobject a designator names,
+// through the anonymous members that contain a named one
+fn designate(t: &Types, stack: &mut Vec<Cursor>, d: &Designator) -> Result<(), Error> {
+    match d {
+        Designator::Index { index, .. } => {
+            let n = match Eval::new(t.info).int(index.as_ref()) {
+                Ok(n) => n as usize,
+                Err(n) => return error(n.pos, n.msg),
+            };
+            let c = stack.last_mut().unwrap();
+            if !c.ty.is_array() {
+                return error(d.start(), "array designator in a non-array object");
+            }
+            c.index = n;
+        }
+
+        Designator::Field { name, .. } => loop {
+            let c = stack.last_mut().unwrap();
+            let (TypeKind::Struct(id) | TypeKind::Union(id)) = c.ty.kind else {
+                return error(d.start(), "field designator in a non-record object");
+            };
+            let Some(r) = t.record(id) else {
+                return error(d.start(), "field designator in an incomplete record");
+            };
+
+            if let Some(i) = r
+                .fields
+                .iter()
+                .position(|f| f.name.as_deref() == Some(&name.name))
+            {
+                c.index = i;
+                break;
+            }
+
+            // the anonymous member that contains it
+            let inner = r.fields.iter().position(|f| match (&f.name, &f.ty.kind) {
+                (None, TypeKind::Struct(inner) | TypeKind::Union(inner)) => {
+                    t.member(*inner, &name.name).is_some()
+                }
+                _ => false,
+            });
+            let Some(i) = inner else {
+                return error(name.pos, format!("no member named '{}'", name.name));
+            };
+
+            c.index = i;
+            let c = stack.last().unwrap();
+            let (ty, offset, _) = subobject(t, c, i, d.start())?;
+            stack.push(Cursor {
+                ty,
+                offset,
+                index: 0,
+            });
+        },
+    }
+
+    Ok(())
+}
+
+// the type, offset and bits of the element or member `i` of a cursor
+type Subobject = (Type, u64, Option<(u32, u32)>);
+
+fn subobject(t: &Types, c: &Cursor, i: usize, pos: usize) -> Result<Subobject, Error> {
+    match &c.ty.kind {
+        TypeKind::Array(elem, _) => {
+            let size = t.size_of(elem, pos)?;
+            Ok((elem.as_ref().clone(), c.offset + i as u64 * size, None))
+        }
+        TypeKind::Struct(id) | TypeKind::Union(id) => {
+            let fields = t.record(*id).map_or(&[][..], |r| &r.fields);
+            let Some(f) = fields.get(i) else {
+                return error(pos, "excess elements in initializer");
+            };
+            let (offset, bits) = t.field(f, pos)?;
+            Ok((f.ty.clone(), c.offset + offset, bits))
+        }
+        _ => error(pos, "excess elements in initializer"),
+    }
+}
+
+// moves a cursor past the subobject it is at, one member of a union is
+// initialized
+fn advance(c: &mut Cursor) {
+    c.index = match c.ty.kind {
+        TypeKind::Union(_) => usize::MAX,
+        _ => c.index + 1,
+    };
+}
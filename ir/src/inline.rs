@@ -0,0 +1,232 @@
+//! Inlining of the direct calls to small functions that do not call
+//! themselves. The block of a call is split after it, the blocks of the
+//! callee are copied in between with the arguments for its parameters,
+//! its returns jump to the rest of the block and a phi joins their
+//! values. The variables of the callee become variables of the caller.
+
+use std::collections::HashMap;
+
+use crate::{Block, BlockId, Function, InstKind, Module, Operand, Term, Ty, ValueId};
+
+/// The most instructions a callee may have.
+const MAX_SIZE: usize = 32;
+
+/// The most calls inlined into one function.
+const MAX_CALLS: usize = 64;
+
+pub fn run(m: &mut Module) {
+    for i in 0..m.funcs.len() {
+        for _ in 0..MAX_CALLS {
+            let Some((b, at, callee)) = find(m, i) else {
+                break;
+            };
+            let callee = m.funcs[callee].clone();
+            inline(&mut m.funcs[i], b, at, &callee);
+        }
+        m.funcs[i].remove_unreachable();
+    }
+}
+
+// the first call of a function that can be inlined into `caller`: its
+// block, its index there and the callee
+fn find(m: &Module, caller: usize) -> Option<(usize, usize, usize)> {
+    let func = &m.funcs[caller];
+    for (b, block) in func.blocks.iter().enumerate() {
+        for (at, &v) in block.insts.iter().enumerate() {
+            let InstKind::Call(Operand::Global(name, 0), args) = &func.inst(v).kind else {
+                continue;
+            };
+            let Some(callee) = m.funcs.iter().position(|f| f.name == *name) else {
+                continue;
+            };
+            let f = &m.funcs[callee];
+            let types = args.iter().map(|a| func.type_of(a));
+            if callee != caller
+                && !f.variadic
+                && f.size() <= MAX_SIZE
+                && f.params.iter().copied().eq(types)
+                && f.ret == func.inst(v).ty
+                && !calls(f, &f.name)
+            {
+                return Some((b, at, callee));
+            }
+        }
+    }
+    None
+}
+
+fn calls(func: &Function, name: &str) -> bool {
+    func.blocks.iter().flat_map(|b| b.insts.iter()).any(|&v| {
+        func.inst(v)
+            .kind
+            .operands()
+            .iter()
+            .any(|x| matches!(x, Operand::Global(n, _) if n == name))
+    })
+}
+
+// inlines the call at `at` in block `b`
+fn inline(func: &mut Function, b: usize, at: usize, callee: &Function) {
+    let call = func.blocks[b].insts[at];
+    let InstKind::Call(_, args) = func.inst(call).kind.clone() else {
+        unreachable!("not a call");
+    };
+
+    // the rest of the block goes to a new one after those of the callee,
+    // its successors are now reached from there
+    let rest = func.blocks[b].insts.split_off(at + 1);
+    func.blocks[b].insts.pop();
+    let first = func.blocks.len();
+    let cont = BlockId(first + callee.blocks.len());
+    let term = std::mem::replace(&mut func.blocks[b].term, Term::Unreachable);
+    for s in term.succs() {
+        for &v in func.blocks[s.0].insts.iter() {
+            if let InstKind::Phi(args) = &mut func.insts[v.0].kind {
+                for (p, _) in args.iter_mut() {
+                    if p.0 == b {
+                        *p = cont;
+                    }
+                }
+            }
+        }
+    }
+
+    // the values and blocks of the callee in the caller
+    let block = |c: BlockId| BlockId(first + c.0);
+    let mut values: HashMap<ValueId, Operand> = HashMap::new();
+    for &v in callee.blocks.iter().flat_map(|b| b.insts.iter()) {
+        let to = match callee.inst(v).kind {
+            InstKind::Param(i) => args[i].clone(),
+            _ => Operand::Value(func.add(callee.inst(v).ty, InstKind::Param(0))),
+        };
+        values.insert(v, to);
+    }
+    let value = |x: &Operand| match x {
+        Operand::Value(v) => values[v].clone(),
+        x => x.clone(),
+    };
+
+    let mut allocas = Vec::new();
+    let mut returns: Vec<(BlockId, Operand)> = Vec::new();
+    for (c, cb) in callee.blocks.iter().enumerate() {
+        let mut insts = Vec::new();
+        for &v in cb.insts.iter() {
+            // the parameters are the arguments
+            let to = match (&callee.inst(v).kind, &values[&v]) {
+                (InstKind::Param(_), _) => continue,
+                (_, Operand::Value(to)) => *to,
+                _ => unreachable!("not a value"),
+            };
+            let mut kind = callee.inst(v).kind.clone();
+            for x in kind.operands_mut() {
+                *x = value(x);
+            }
+            if let InstKind::Phi(args) = &mut kind {
+                for (p, _) in args.iter_mut() {
+                    *p = block(*p);
+                }
+            }
+            if let InstKind::Alloca { .. } = kind {
+                allocas.push(to);
+            } else {
+                insts.push(to);
+            }
+            func.insts[to.0].kind = kind;
+        }
+
+        let mut term = cb.term.clone();
+        for s in term.succs_mut() {
+            *s = block(*s);
+        }
+        if let Some(x) = term.operand_mut() {
+            *x = value(x);
+        }
+        if let Term::Return(v) = term {
+            returns.extend(v.map(|v| (BlockId(first + c), v)));
+            term = Term::Jump(cont);
+        }
+        func.blocks.push(Block { insts, term });
+    }
+    func.blocks.push(Block { insts: rest, term });
+    func.blocks[b].term = Term::Jump(BlockId(first));
+
+    // the variables go to the entry of the caller, after its parameters
+    let params = func.blocks[0]
+        .insts
+        .iter()
+        .take_while(|&&v| matches!(func.inst(v).kind, InstKind::Param(_)))
+        .count();
+    func.blocks[0].insts.splice(params..params, allocas);
+
+    let ty = func.inst(call).ty;
+    let result = match returns.len() {
+        _ if ty == Ty::Void => return,
+        0 => Operand::Undef(ty),
+        1 => returns.pop().unwrap().1,
+        _ => {
+            let phi = func.add(ty, InstKind::Phi(returns));
+            func.blocks[cont.0].insts.insert(0, phi);
+            Operand::Value(phi)
+        }
+    };
+    func.replace_uses(&HashMap::from([(call, result)]));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::optimized;
+
+    #[test]
+    fn test_inline() {
+        let got = optimized(
+            "static int sq(int x) { return x * x; }
+             static int abs(int x) { if (x < 0) return -x; return x; }
+             int f(int a) { return sq(a) + abs(a); }",
+            &["mem2reg", "inline", "dce"],
+        );
+        let want = "\
+define internal i32 @sq(i32 %0) {
+bb0:
+  %1 = mul i32 %0, %0
+  ret i32 %1
+}
+
+define internal i32 @abs(i32 %0) {
+bb0:
+  %1 = icmp slt i32 %0, 0
+  br i32 %1, bb1, bb2
+bb1:
+  %2 = neg i32 %0
+  ret i32 %2
+bb2:
+  ret i32 %0
+}
+
+define i32 @f(i32 %0) {
+bb0:
+  %1 = mul i32 %0, %0
+  %2 = icmp slt i32 %0, 0
+  br i32 %2, bb1, bb2
+bb1:
+  %3 = neg i32 %0
+  br bb3
+bb2:
+  br bb3
+bb3:
+  %4 = phi i32 [ %3, bb1 ], [ %0, bb2 ]
+  %5 = add i32 %1, %4
+  ret i32 %5
+}
+";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn test_recursive() {
+        let got = optimized(
+            "int f(int n) { return n ? n * f(n - 1) : 1; } int g(void) { return f(3); }",
+            &["inline"],
+        );
+        assert!(got.contains("call i32 @f(i32 3)"), "{}", got);
+    }
+}
@@ -0,0 +1,547 @@
+//! An SSA intermediate representation between the checked AST and the
+//! backends. A function is a graph of basic blocks of typed instructions,
+//! each defining at most one value, and ends in a terminator. `lower`
+//! builds it the way a C compiler without optimizations does, every local
+//! variable in memory, and the passes improve it: `mem2reg` promotes the
+//! variables to SSA values joined by phi nodes, then `constprop`, `cse`,
+//! `dce` and `inline` simplify the result. The text format is printed by
+//! the `Display` implementations, see `print`.
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+mod constprop;
+mod cse;
+mod dce;
+pub mod dom;
+mod init;
+mod inline;
+mod lower;
+mod mem2reg;
+mod print;
+
+pub use lower::lower;
+
+/// The types of values. Integers have no sign, the operations that care
+/// say how they treat them, and records and arrays only live in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ty {
+    Void,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    Ptr,
+}
+
+impl Ty {
+    /// The size of a value in bytes, pointers are 64 bits.
+    pub fn size(self) -> u64 {
+        match self {
+            Ty::Void => 0,
+            Ty::I8 => 1,
+            Ty::I16 => 2,
+            Ty::I32 | Ty::F32 => 4,
+            Ty::I64 | Ty::F64 | Ty::Ptr => 8,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, Ty::F32 | Ty::F64)
+    }
+
+    /// The integer type of a size in bytes.
+    pub fn int(size: u64) -> Ty {
+        match size {
+            1 => Ty::I8,
+            2 => Ty::I16,
+            4 => Ty::I32,
+            _ => Ty::I64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub usize);
+
+/// Indexes the instructions of a function, see `Function::insts`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ValueId(pub usize);
+
+/// An operand of an instruction. Integer constants are kept sign-extended
+/// from the width of their type.
+#[derive(Debug, Clone)]
+pub enum Operand {
+    Value(ValueId),
+    Int(i64, Ty),
+    Float(f64, Ty),
+    Global(String, i64), // the address of a global or function and an offset
+    Undef(Ty),
+}
+
+// floating constants are equal if they have the same bits, so that CSE
+// can key on operands
+impl PartialEq for Operand {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Operand::Value(a), Operand::Value(b)) => a == b,
+            (Operand::Int(a, s), Operand::Int(b, t)) => a == b && s == t,
+            (Operand::Float(a, s), Operand::Float(b, t)) => a.to_bits() == b.to_bits() && s == t,
+            (Operand::Global(a, m), Operand::Global(b, n)) => a == b && m == n,
+            (Operand::Undef(s), Operand::Undef(t)) => s == t,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Operand {}
+
+impl Hash for Operand {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Operand::Value(v) => v.hash(state),
+            Operand::Int(v, t) => (v, t).hash(state),
+            Operand::Float(f, t) => (f.to_bits(), t).hash(state),
+            Operand::Global(name, n) => (name, n).hash(state),
+            Operand::Undef(t) => t.hash(state),
+        }
+    }
+}
+
+impl Operand {
+    pub fn is_const(&self) -> bool {
+        matches!(
+            self,
+            Operand::Int(..) | Operand::Float(..) | Operand::Global(..)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    SDiv,
+    UDiv,
+    SRem,
+    URem,
+    And,
+    Or,
+    Xor,
+    Shl,
+    LShr,
+    AShr,
+    FAdd,
+    FSub,
+    FMul,
+    FDiv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnOp {
+    Neg,
+    Not, // bitwise
+    FNeg,
+}
+
+/// Comparisons, their result is an i32 0 or 1 like the operators of C.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    SLt,
+    SLe,
+    SGt,
+    SGe,
+    ULt,
+    ULe,
+    UGt,
+    UGe,
+    FEq,
+    FNe,
+    FLt,
+    FLe,
+    FGt,
+    FGe,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CastOp {
+    Trunc,
+    ZExt,
+    SExt,
+    FpTrunc,
+    FpExt,
+    SiToFp,
+    UiToFp,
+    FpToSi,
+    FpToUi,
+    PtrToInt,
+    IntToPtr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Inst {
+    pub ty: Ty, // of the value it defines, void for none
+    pub kind: InstKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InstKind {
+    Param(usize), // the entry block defines the parameters first
+    Phi(Vec<(BlockId, Operand)>),
+    Alloca { size: u64, align: u64 },
+    Load(Operand),
+    Store(Operand, Operand), // a pointer and the value stored
+    Binary(BinOp, Operand, Operand),
+    Unary(UnOp, Operand),
+    Cmp(CmpOp, Operand, Operand),
+    Cast(CastOp, Operand),
+    PtrAdd(Operand, Operand),    // a pointer and an i64 offset in bytes
+    Copy(Operand, Operand, u64), // copies bytes from the second pointer to the first
+    Zero(Operand, u64),          // fills bytes with zeros
+    Call(Operand, Vec<Operand>),
+}
+
+impl InstKind {
+    /// Reports whether the instruction does more than define its value.
+    pub fn has_effects(&self) -> bool {
+        matches!(
+            self,
+            InstKind::Store(..) | InstKind::Copy(..) | InstKind::Zero(..) | InstKind::Call(..)
+        )
+    }
+
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            InstKind::Param(_) | InstKind::Alloca { .. } => Vec::new(),
+            InstKind::Phi(args) => args.iter().map(|(_, v)| v).collect(),
+            InstKind::Load(p) | InstKind::Unary(_, p) | InstKind::Cast(_, p) => vec![p],
+            InstKind::Zero(p, _) => vec![p],
+            InstKind::Store(a, b)
+            | InstKind::Binary(_, a, b)
+            | InstKind::Cmp(_, a, b)
+            | InstKind::PtrAdd(a, b)
+            | InstKind::Copy(a, b, _) => vec![a, b],
+            InstKind::Call(f, args) => std::iter::once(f).chain(args.iter()).collect(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            InstKind::Param(_) | InstKind::Alloca { .. } => Vec::new(),
+            InstKind::Phi(args) => args.iter_mut().map(|(_, v)| v).collect(),
+            InstKind::Load(p) | InstKind::Unary(_, p) | InstKind::Cast(_, p) => vec![p],
+            InstKind::Zero(p, _) => vec![p],
+            InstKind::Store(a, b)
+            | InstKind::Binary(_, a, b)
+            | InstKind::Cmp(_, a, b)
+            | InstKind::PtrAdd(a, b)
+            | InstKind::Copy(a, b, _) => vec![a, b],
+            InstKind::Call(f, args) => std::iter::once(f).chain(args.iter_mut()).collect(),
+        }
+    }
+}
+
+/// How a block ends. The condition of a branch is an integer or a pointer
+/// compared to zero.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Jump(BlockId),
+    Branch(Operand, BlockId, BlockId),
+    Switch(Operand, Vec<(i64, BlockId)>, BlockId), // the cases and the default
+    Return(Option<Operand>),
+    Unreachable,
+}
+
+impl Term {
+    pub fn succs(&self) -> Vec<BlockId> {
+        match self {
+            Term::Jump(b) => vec![*b],
+            Term::Branch(_, t, f) => vec![*t, *f],
+            Term::Switch(_, cases, default) => {
+                let mut succs: Vec<BlockId> = cases.iter().map(|c| c.1).collect();
+                succs.push(*default);
+                succs
+            }
+            Term::Return(_) | Term::Unreachable => Vec::new(),
+        }
+    }
+
+    pub fn succs_mut(&mut self) -> Vec<&mut BlockId> {
+        match self {
+            Term::Jump(b) => vec![b],
+            Term::Branch(_, t, f) => vec![t, f],
+            Term::Switch(_, cases, default) => {
+                let mut succs: Vec<&mut BlockId> = cases.iter_mut().map(|c| &mut c.1).collect();
+                succs.push(default);
+                succs
+            }
+            Term::Return(_) | Term::Unreachable => Vec::new(),
+        }
+    }
+
+    pub fn operand(&self) -> Option<&Operand> {
+        match self {
+            Term::Branch(v, ..) | Term::Switch(v, ..) | Term::Return(Some(v)) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn operand_mut(&mut self) -> Option<&mut Operand> {
+        match self {
+            Term::Branch(v, ..) | Term::Switch(v, ..) | Term::Return(Some(v)) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub insts: Vec<ValueId>, // the phi nodes first
+    pub term: Term,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<Ty>,
+    pub ret: Ty,
+    pub variadic: bool,
+    pub internal: bool, // static functions are not seen by the linker
+
+    pub insts: Vec<Inst>,   // the removed ones are in no block
+    pub blocks: Vec<Block>, // the entry block first
+}
+
+impl Function {
+    pub fn inst(&self, v: ValueId) -> &Inst {
+        &self.insts[v.0]
+    }
+
+    /// The type of an operand.
+    pub fn type_of(&self, v: &Operand) -> Ty {
+        match v {
+            Operand::Value(v) => self.insts[v.0].ty,
+            Operand::Int(_, t) | Operand::Float(_, t) | Operand::Undef(t) => *t,
+            Operand::Global(..) => Ty::Ptr,
+        }
+    }
+
+    /// Adds an instruction to no block.
+    pub fn add(&mut self, ty: Ty, kind: InstKind) -> ValueId {
+        self.insts.push(Inst { ty, kind });
+        ValueId(self.insts.len() - 1)
+    }
+
+    /// The predecessors of each block, in the order of their edges.
+    pub fn preds(&self) -> Vec<Vec<BlockId>> {
+        let mut preds = vec![Vec::new(); self.blocks.len()];
+        for (i, b) in self.blocks.iter().enumerate() {
+            for s in b.term.succs() {
+                if !preds[s.0].contains(&BlockId(i)) {
+                    preds[s.0].push(BlockId(i));
+                }
+            }
+        }
+        preds
+    }
+
+    /// The number of instructions in blocks.
+    pub fn size(&self) -> usize {
+        self.blocks.iter().map(|b| b.insts.len()).sum()
+    }
+
+    /// Replaces the uses of values by operands, through chains of
+    /// replacements.
+    pub fn replace_uses(&mut self, map: &HashMap<ValueId, Operand>) {
+        if map.is_empty() {
+            return;
+        }
+        let resolve = |v: &mut Operand| {
+            while let Operand::Value(id) = v {
+                match map.get(id) {
+                    Some(to) if to != v => *v = to.clone(),
+                    _ => break,
+                }
+            }
+        };
+
+        for b in self.blocks.iter_mut() {
+            for &id in b.insts.iter() {
+                self.insts[id.0]
+                    .kind
+                    .operands_mut()
+                    .into_iter()
+                    .for_each(resolve);
+            }
+            if let Some(v) = b.term.operand_mut() {
+                resolve(v);
+            }
+        }
+    }
+
+    /// Removes the blocks the entry does not reach, renumbers the others
+    /// and drops the phi operands of the edges that went away. Reports
+    /// whether a block was removed.
+    pub fn remove_unreachable(&mut self) -> bool {
+        let mut reached = vec![false; self.blocks.len()];
+        let mut work = vec![BlockId(0)];
+        while let Some(b) = work.pop() {
+            if !std::mem::replace(&mut reached[b.0], true) {
+                work.extend(self.blocks[b.0].term.succs());
+            }
+        }
+
+        let removed = reached.iter().any(|r| !r);
+        let mut index = Vec::with_capacity(self.blocks.len());
+        let mut n = 0;
+        for &r in reached.iter() {
+            index.push(BlockId(n));
+            n += r as usize;
+        }
+
+        let blocks = std::mem::take(&mut self.blocks);
+        for (i, mut b) in blocks.into_iter().enumerate() {
+            if !reached[i] {
+                continue;
+            }
+            for s in b.term.succs_mut() {
+                *s = index[s.0];
+            }
+            self.blocks.push(b);
+        }
+
+        // a phi keeps the operands of the edges that still exist
+        let preds = self.preds();
+        for (i, b) in self.blocks.iter().enumerate() {
+            for &id in b.insts.iter() {
+                if let InstKind::Phi(args) = &mut self.insts[id.0].kind {
+                    args.retain(|(p, _)| reached[p.0]);
+                    for (p, _) in args.iter_mut() {
+                        *p = index[p.0];
+                    }
+                    args.retain(|(p, _)| preds[i].contains(p));
+                }
+            }
+        }
+        removed
+    }
+}
+
+/// An object of static storage duration: its initial bytes and the
+/// addresses stored in them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+    pub name: String,
+    pub size: u64,
+    pub align: u64,
+    pub data: Vec<u8>, // empty for zeros
+    pub relocs: Vec<Reloc>,
+    pub readonly: bool,
+    pub internal: bool,
+}
+
+/// The address of a symbol plus an addend, stored at an offset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reloc {
+    pub offset: u64,
+    pub symbol: String,
+    pub addend: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Module {
+    pub globals: Vec<Global>,
+    pub funcs: Vec<Function>,
+}
+
+impl Module {
+    pub fn func(&self, name: &str) -> Option<&Function> {
+        self.funcs.iter().find(|f| f.name == name)
+    }
+}
+
+/// Why a file cannot be lowered, at the construct that is not supported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub pos: usize,
+    pub msg: String,
+}
+
+pub(crate) fn error<T>(pos: usize, msg: impl Into<String>) -> Result<T, Error> {
+    Err(Error {
+        pos,
+        msg: msg.into(),
+    })
+}
+
+/// The passes in the order `optimize` first runs them.
+pub const PASSES: [&str; 5] = ["mem2reg", "constprop", "dce", "inline", "cse"];
+
+/// Runs a pass by name on every function of a module, reports whether the
+/// name is known.
+pub fn run_pass(m: &mut Module, name: &str) -> bool {
+    let pass: fn(&mut Function) = match name {
+        "mem2reg" => mem2reg::run,
+        "constprop" => constprop::run,
+        "dce" => dce::run,
+        "cse" => cse::run,
+        "inline" => {
+            inline::run(m);
+            return true;
+        }
+        _ => return false,
+    };
+    m.funcs.iter_mut().for_each(pass);
+    true
+}
+
+/// Runs the passes in an order that lets each one clean up after the
+/// others, the variables of inlined calls are promoted too.
+pub fn optimize(m: &mut Module) {
+    for name in PASSES
+        .iter()
+        .chain(["mem2reg", "constprop", "cse", "dce"].iter())
+    {
+        run_pass(m, name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // lowers a program checked without errors
+    pub(crate) fn module(src: &str) -> Module {
+        let mut p = parser::Parser::from(src.to_string());
+        let file = p.parse_file();
+        assert!(p.errors().is_empty(), "{:?}", p.errors());
+
+        let info = sema::check(&file);
+        assert_eq!(0, info.errors().count(), "{:?}", info.diagnostics);
+        lower(&file, &info).unwrap()
+    }
+
+    // the last function of a program
+    pub(crate) fn function(src: &str) -> Function {
+        module(src).funcs.pop().unwrap()
+    }
+
+    // the functions of a program after the passes, as `:ir` prints them
+    pub(crate) fn optimized(src: &str, passes: &[&str]) -> String {
+        let mut m = module(src);
+        for name in passes {
+            assert!(run_pass(&mut m, name), "unknown pass {}", name);
+        }
+        m.funcs
+            .iter()
+            .map(|f| f.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
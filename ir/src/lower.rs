@@ -0,0 +1,1688 @@
+//! Lowers a checked file to IR the way a C compiler does without
+//! optimizations: every variable lives in memory allocated in the entry
+//! block, expressions load and store it and statements become branches
+//! between blocks. A value of a record type is the address of a copy of
+//! it: records are passed as pointers to copies, and a function that
+//! returns one stores it at an address it takes as its first parameter.
+//! The initializers of globals are lowered the same way and must fold to
+//! constants.
+
+use std::collections::HashMap;
+
+use ast::{
+    AssignExpr, BlockStmt, CondExpr, DeclKind, DeclStmt, Expr, ExprKind, File, FuncDecl, GenDecl,
+    InfixExpr, Node, Stmt, StmtKind,
+};
+use sema::consts::{parse_float, string_units, Eval};
+use sema::layout::{FieldLayout, RecordLayout};
+use sema::types::{usual_arithmetic, ArrayLen, FloatKind, IntKind, TagId, Type, TypeKind};
+use sema::{Info, Linkage, SymbolId, SymbolKind};
+use token::Token;
+
+use crate::constprop::{float, fold, int};
+use crate::init::{is_aggregate, is_string, leaves};
+use crate::{
+    error, BinOp, Block, BlockId, CastOp, CmpOp, Error, Function, Global, InstKind, Module,
+    Operand, Reloc, Term, Ty, UnOp,
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Lowers a file checked without errors to a module, or reports the first
+/// construct the IR does not support.
+pub fn lower(file: &File, info: &Info) -> Result<Module> {
+    if info.layout.pointer.size != 8 {
+        return error(file.start(), "the IR supports targets with 64-bit pointers");
+    }
+
+    let mut l = Lower {
+        types: Types::new(info),
+        module: Module::default(),
+        globals: HashMap::new(),
+        strings: HashMap::new(),
+        names: HashMap::new(),
+        literals: 0,
+    };
+    for d in file.decls.iter() {
+        match d.kind() {
+            DeclKind::FuncDecl(f) => l.func(f)?,
+            DeclKind::GenDecl(g) => l.decl(g)?,
+            DeclKind::StaticAssert(_) => {}
+        }
+    }
+    Ok(l.module)
+}
+
+/// The types and layouts of a checked file, and the IR types of its values.
+pub(crate) struct Types<'a> {
+    pub info: &'a Info,
+    records: Vec<Option<RecordLayout>>, // by TagId
+}
+
+impl<'a> Types<'a> {
+    fn new(info: &'a Info) -> Self {
+        let records = (0..info.tags.len())
+            .map(|id| info.layout.record(TagId(id), &info.tags))
+            .collect();
+        Self { info, records }
+    }
+
+    pub fn record(&self, id: TagId) -> Option<&RecordLayout> {
+        self.records[id.0].as_ref()
+    }
+
+    /// Finds a member of a struct or union, in anonymous members too.
+    pub fn member(&self, id: TagId, name: &str) -> Option<FieldLayout> {
+        for f in self.record(id)?.fields.iter() {
+            match (&f.name, &f.ty.kind) {
+                (Some(n), _) if n == name => return Some(f.clone()),
+                (None, TypeKind::Struct(inner) | TypeKind::Union(inner)) => {
+                    if let Some(mut m) = self.member(*inner, name) {
+                        m.offset += f.offset;
+                        return Some(m);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// The offset in bytes of a member, or of the storage unit of a
+    /// bit-field, which is aligned to the size of its type, and the shift
+    /// and width of a bit-field in it.
+    pub fn field(&self, f: &FieldLayout, pos: usize) -> Result<(u64, Option<(u32, u32)>)> {
+        let Some(width) = f.bits else {
+            return Ok((f.offset / 8, None));
+        };
+        let unit = self.size_of(&f.ty, pos)?;
+        let start = f.offset / (unit * 8) * unit;
+        Ok((start, Some(((f.offset - start * 8) as u32, width))))
+    }
+
+    // the type of a checked expression
+    pub fn type_of(&self, x: &dyn Expr) -> Type {
+        self.info.type_of(x).cloned().unwrap_or_default()
+    }
+
+    /// The size of a complete type, an array of unknown length has none.
+    pub fn size_of(&self, ty: &Type, pos: usize) -> Result<u64> {
+        match &ty.kind {
+            TypeKind::Array(elem, ArrayLen::Fixed(n)) => Ok(n * self.size_of(elem, pos)?),
+            TypeKind::Array(_, ArrayLen::Incomplete) => Ok(0),
+            _ if ty.is_vla() => error(pos, "variable length arrays are not supported"),
+            TypeKind::Struct(id) | TypeKind::Union(id) => match self.record(*id) {
+                Some(r) => Ok(r.size),
+                None => error(pos, format!("incomplete type '{}'", self.info.spell(ty))),
+            },
+            _ => match self.info.layout.size_of(ty, &self.info.tags) {
+                Some(size) => Ok(size),
+                None => error(pos, format!("invalid type '{}'", self.info.spell(ty))),
+            },
+        }
+    }
+
+    pub fn align_of(&self, ty: &Type) -> u64 {
+        match &ty.kind {
+            TypeKind::Array(elem, _) => self.align_of(elem),
+            TypeKind::Struct(id) | TypeKind::Union(id) => self.record(*id).map_or(1, |r| r.align),
+            _ => self.info.layout.align_of(ty, &self.info.tags).unwrap_or(1),
+        }
+    }
+
+    /// The type of the values of a C type, aggregates and functions are
+    /// addresses.
+    pub fn ty(&self, t: &Type) -> Ty {
+        match &t.kind {
+            TypeKind::Void => Ty::Void,
+            TypeKind::Int(IntKind::Bool) => Ty::I8,
+            TypeKind::Int(_) | TypeKind::Enum(_) => {
+                Ty::int(self.info.layout.size_of(t, &self.info.tags).unwrap_or(4))
+            }
+            TypeKind::Float(FloatKind::Float) => Ty::F32,
+            TypeKind::Float(_) => Ty::F64, // long double too
+            _ => Ty::Ptr,
+        }
+    }
+
+    pub fn is_signed(&self, t: &Type) -> bool {
+        t.int_kind()
+            .is_some_and(|k| k != IntKind::Bool && self.info.layout.is_signed(k))
+    }
+
+    // the size of the type a pointer of type `ty` points to, 1 for void
+    // and functions
+    fn stride(&self, ty: &Type, pos: usize) -> Result<i64> {
+        match ty.pointee() {
+            Some(p) if !p.is_void() && !p.is_function() => Ok(self.size_of(p, pos)? as i64),
+            _ => Ok(1),
+        }
+    }
+}
+
+// the state of the module being built
+struct Lower<'a> {
+    types: Types<'a>,
+    module: Module,
+    globals: HashMap<SymbolId, usize>, // the globals of variables, by index
+    strings: HashMap<usize, String>,   // the globals of string literals by position
+    names: HashMap<SymbolId, String>,  // of static local variables
+    literals: usize,                   // compound literals at file scope
+}
+
+impl<'a> Lower<'a> {
+    fn decl(&mut self, g: &'a GenDecl) -> Result<()> {
+        if g.specs.storage == Some(Token::TYPEDEF) {
+            return Ok(());
+        }
+
+        for d in g.decls.iter() {
+            let name = d.declarator.name.as_ref();
+            let Some(&id) = name.and_then(|n| self.types.info.decls.get(&n.pos)) else {
+                continue;
+            };
+            let sym = self.types.info.symbol(id);
+            if sym.kind != SymbolKind::Var {
+                continue;
+            }
+            if g.specs.storage == Some(Token::EXTERN) && d.init.is_none() {
+                continue;
+            }
+            let internal = sym.linkage == Linkage::Internal;
+            self.define(id, sym.name.clone(), d.init.as_deref(), internal)?;
+        }
+        Ok(())
+    }
+
+    // defines the global of a variable, a later declaration may give it
+    // an initializer
+    fn define(
+        &mut self,
+        id: SymbolId,
+        name: String,
+        init: Option<&'a dyn Expr>,
+        internal: bool,
+    ) -> Result<()> {
+        let sym = self.types.info.symbol(id);
+        let ty = sym.ty.clone();
+        let mut g = Global {
+            name,
+            size: self.types.size_of(&ty, sym.pos)?,
+            align: self.types.align_of(&ty),
+            data: Vec::new(),
+            relocs: Vec::new(),
+            readonly: false,
+            internal,
+        };
+        if let Some(x) = init {
+            self.init(&mut g, &ty, x)?;
+        }
+
+        match self.globals.get(&id) {
+            Some(&i) if init.is_some() => self.module.globals[i] = g,
+            Some(_) => {}
+            None => {
+                self.globals.insert(id, self.module.globals.len());
+                self.module.globals.push(g);
+            }
+        }
+        Ok(())
+    }
+
+    // the initial bytes of a global, its initializer folded to constants
+    fn init(&mut self, g: &mut Global, ty: &Type, x: &'a dyn Expr) -> Result<()> {
+        let mut data = vec![0u8; g.size as usize];
+        for leaf in leaves(&self.types, ty, x)? {
+            let at = leaf.offset as usize;
+            let pos = leaf.value.start();
+
+            if is_string(leaf.value) && leaf.ty.is_array() {
+                let (bytes, _) = self.string_data(leaf.value)?;
+                let n = bytes.len().min(self.types.size_of(&leaf.ty, pos)? as usize);
+                put(&mut data, at, &bytes[..n], pos)?;
+                continue;
+            }
+            if leaf.ty.is_record() {
+                return error(pos, "initializer element is not a compile-time constant");
+            }
+
+            match self.constant(leaf.value, &leaf.ty)? {
+                Operand::Int(n, t) => match leaf.bits {
+                    None => put(&mut data, at, &n.to_le_bytes()[..t.size() as usize], pos)?,
+                    Some((shift, width)) => {
+                        let size = t.size() as usize;
+                        let Some(unit) = data.get_mut(at..at + size) else {
+                            return error(pos, "excess elements in initializer");
+                        };
+                        let mut raw = [0u8; 8];
+                        raw[..size].copy_from_slice(unit);
+                        let mask = ((1u64 << width) - 1) << shift;
+                        let v = (u64::from_le_bytes(raw) & !mask) | ((n as u64) << shift & mask);
+                        unit.copy_from_slice(&v.to_le_bytes()[..size]);
+                    }
+                },
+                Operand::Float(f, Ty::F32) => put(&mut data, at, &(f as f32).to_le_bytes(), pos)?,
+                Operand::Float(f, _) => put(&mut data, at, &f.to_le_bytes(), pos)?,
+                Operand::Global(symbol, addend) => {
+                    put(&mut data, at, &[0; 8], pos)?;
+                    g.relocs.push(Reloc {
+                        offset: leaf.offset,
+                        symbol,
+                        addend,
+                    });
+                }
+                _ => return error(pos, "initializer element is not a compile-time constant"),
+            }
+        }
+
+        if data.iter().any(|&b| b != 0) {
+            g.data = data;
+        }
+        Ok(())
+    }
+
+    // the value of the initializer of a global, converted to `ty`
+    fn constant(&mut self, x: &'a dyn Expr, ty: &Type) -> Result<Operand> {
+        let func = Function {
+            name: String::new(),
+            params: Vec::new(),
+            ret: Ty::Void,
+            variadic: false,
+            internal: true,
+            insts: Vec::new(),
+            blocks: Vec::new(),
+        };
+        let mut b = Builder::new(self, func, Type::void());
+        b.scratch = true;
+
+        let v = b.expr(x)?;
+        let v = b.convert(v, &b.l.types.type_of(x), ty);
+        match v.is_const() {
+            true => Ok(v),
+            false => error(
+                x.start(),
+                "initializer element is not a compile-time constant",
+            ),
+        }
+    }
+
+    // the bytes of a string literal with its null character, and their
+    // alignment
+    fn string_data(&self, x: &dyn Expr) -> Result<(Vec<u8>, u64)> {
+        let ExprKind::BasicLit(b) = x.kind() else {
+            unreachable!("not a string literal");
+        };
+        let t = &self.types;
+        let elem = t.type_of(x).elem().cloned().unwrap_or_default();
+        let size = t.size_of(&elem, b.pos)? as usize;
+        let k = elem.int_kind().unwrap_or(IntKind::Char);
+
+        let mut bytes = Vec::new();
+        for u in string_units(&b.lit, t.info.layout.wchar_t) {
+            let v = t.info.layout.wrap(u as i128, k);
+            bytes.extend_from_slice(&v.to_le_bytes()[..size]);
+        }
+        bytes.resize(bytes.len() + size, 0);
+        Ok((bytes, t.align_of(&elem)))
+    }
+
+    // the global of a string literal
+    fn string(&mut self, x: &dyn Expr) -> Result<Operand> {
+        let pos = x.start();
+        if let Some(name) = self.strings.get(&pos) {
+            return Ok(Operand::Global(name.clone(), 0));
+        }
+
+        let name = match self.strings.len() {
+            0 => ".str".to_string(),
+            n => format!(".str.{}", n),
+        };
+        let (data, align) = self.string_data(x)?;
+        self.module.globals.push(Global {
+            name: name.clone(),
+            size: data.len() as u64,
+            align,
+            data,
+            relocs: Vec::new(),
+            readonly: true,
+            internal: true,
+        });
+        self.strings.insert(pos, name.clone());
+        Ok(Operand::Global(name, 0))
+    }
+
+    // the global of a null terminated text the compiler makes up
+    fn text(&mut self, name: &str, text: &str) -> Operand {
+        let name = self.unique(name);
+        let mut data = text.as_bytes().to_vec();
+        data.push(0);
+        self.module.globals.push(Global {
+            name: name.clone(),
+            size: data.len() as u64,
+            align: 1,
+            data,
+            relocs: Vec::new(),
+            readonly: true,
+            internal: true,
+        });
+        Operand::Global(name, 0)
+    }
+
+    // a name for an internal global that no identifier has
+    fn unique(&self, name: &str) -> String {
+        let taken = |n: &str| self.module.globals.iter().any(|g| g.name == n);
+        if !taken(name) {
+            return name.to_string();
+        }
+        (1..)
+            .map(|i| format!("{}.{}", name, i))
+            .find(|n| !taken(n))
+            .unwrap()
+    }
+
+    fn func(&mut self, f: &'a FuncDecl) -> Result<()> {
+        let t = &self.types;
+        let Some(name) = &f.declarator.name else {
+            return Ok(());
+        };
+        let Some(&id) = t.info.decls.get(&name.pos) else {
+            return Ok(());
+        };
+        let sym = t.info.symbol(id);
+        let Some(fty) = sym.ty.func().cloned() else {
+            return Ok(());
+        };
+
+        // a record is returned through a pointer, the first parameter
+        let sret = fty.ret.is_record();
+        let mut params: Vec<Ty> = fty.params.iter().map(|p| t.ty(p)).collect();
+        if sret {
+            params.insert(0, Ty::Ptr);
+        }
+        let func = Function {
+            name: sym.name.clone(),
+            params: params.clone(),
+            ret: if sret { Ty::Void } else { t.ty(&fty.ret) },
+            variadic: fty.variadic,
+            internal: sym.linkage == Linkage::Internal,
+            insts: Vec::new(),
+            blocks: Vec::new(),
+        };
+        let decls = f.declarator.function().map_or(&[][..], |f| &f.params);
+        let main = sym.name == "main";
+
+        let mut b = Builder::new(self, func, fty.ret.clone());
+        b.main = main;
+        let mut values = Vec::new();
+        for (i, &ty) in params.iter().enumerate() {
+            values.push(b.param(i, ty));
+        }
+        if sret {
+            b.sret = Some(values.remove(0));
+        }
+
+        // the parameters are stored in variables, the records passed are
+        // copies already
+        for (i, (ty, v)) in fty.params.iter().zip(values).enumerate() {
+            let name = decls.get(i).and_then(|p| p.declarator.name.as_ref());
+            let Some(&param) = name.and_then(|n| b.l.types.info.decls.get(&n.pos)) else {
+                continue;
+            };
+            if ty.is_record() {
+                b.vars.insert(param, v);
+                continue;
+            }
+            let p = b.alloca(ty, name.map_or(0, |n| n.pos))?;
+            b.effect(InstKind::Store(p.clone(), v));
+            b.vars.insert(param, p);
+        }
+
+        b.block(&f.body)?;
+        let func = b.finish();
+        self.module.funcs.push(func);
+        Ok(())
+    }
+}
+
+// writes bytes in the data of a global
+fn put(data: &mut [u8], at: usize, bytes: &[u8], pos: usize) -> Result<()> {
+    match data.get_mut(at..at + bytes.len()) {
+        Some(d) => {
+            d.copy_from_slice(bytes);
+            Ok(())
+        }
+        None => error(pos, "excess elements in initializer"),
+    }
+}
+
+/// An lvalue: the object at an address, or a bit-field in the storage
+/// unit there with its shift and width.
+#[derive(Clone)]
+struct Place {
+    ptr: Operand,
+    bits: Option<(u32, u32)>,
+}
+
+impl From<Operand> for Place {
+    fn from(ptr: Operand) -> Self {
+        Place { ptr, bits: None }
+    }
+}
+
+// the state of the function being built
+struct Builder<'l, 'a> {
+    l: &'l mut Lower<'a>,
+    func: Function,
+    block: BlockId,      // where instructions go
+    order: Vec<BlockId>, // the blocks in the order they were entered
+    allocas: usize,      // where the next alloca goes in the entry block
+
+    vars: HashMap<SymbolId, Operand>, // the addresses of the local variables
+    temps: HashMap<usize, Operand>,   // of compound literals, by position
+    ret: Type,
+    sret: Option<Operand>, // where a record is returned
+    main: bool,
+    scratch: bool, // evaluates the initializer of a global
+
+    labels: HashMap<&'a str, BlockId>,
+    jumps: Vec<(BlockId, Option<BlockId>)>, // break and continue targets
+    cases: Vec<Vec<(Option<&'a dyn Expr>, BlockId)>>, // the labels of the enclosing switches
+}
+
+impl<'l, 'a> Builder<'l, 'a> {
+    fn new(l: &'l mut Lower<'a>, mut func: Function, ret: Type) -> Self {
+        func.blocks.push(Block {
+            insts: Vec::new(),
+            term: Term::Unreachable,
+        });
+        Builder {
+            l,
+            func,
+            block: BlockId(0),
+            order: vec![BlockId(0)],
+            allocas: 0,
+            vars: HashMap::new(),
+            temps: HashMap::new(),
+            ret,
+            sret: None,
+            main: false,
+            scratch: false,
+            labels: HashMap::new(),
+            jumps: Vec::new(),
+            cases: Vec::new(),
+        }
+    }
+
+    fn t(&self) -> &Types<'a> {
+        &self.l.types
+    }
+
+    fn type_of(&self, x: &dyn Expr) -> Type {
+        self.l.types.type_of(x)
+    }
+
+    fn ty(&self, t: &Type) -> Ty {
+        self.l.types.ty(t)
+    }
+
+    // adds an instruction that defines a value, folded if it can be
+    fn add(&mut self, ty: Ty, kind: InstKind) -> Operand {
+        if let Some(v) = fold(&self.func, &kind, ty) {
+            return v;
+        }
+        let v = self.func.add(ty, kind);
+        self.func.blocks[self.block.0].insts.push(v);
+        Operand::Value(v)
+    }
+
+    // adds an instruction for its effect
+    fn effect(&mut self, kind: InstKind) {
+        let v = self.func.add(Ty::Void, kind);
+        self.func.blocks[self.block.0].insts.push(v);
+    }
+
+    fn param(&mut self, i: usize, ty: Ty) -> Operand {
+        let v = self.func.add(ty, InstKind::Param(i));
+        self.func.blocks[0].insts.insert(self.allocas, v);
+        self.allocas += 1;
+        Operand::Value(v)
+    }
+
+    // the memory of an object of the function, in the entry block
+    fn alloca(&mut self, ty: &Type, pos: usize) -> Result<Operand> {
+        let size = self.t().size_of(ty, pos)?;
+        let align = self.t().align_of(ty);
+        let v = self.func.add(Ty::Ptr, InstKind::Alloca { size, align });
+        self.func.blocks[0].insts.insert(self.allocas, v);
+        self.allocas += 1;
+        Ok(Operand::Value(v))
+    }
+
+    fn phi(&mut self, ty: Ty, args: Vec<(BlockId, Operand)>) -> Operand {
+        let v = self.func.add(ty, InstKind::Phi(args));
+        self.func.blocks[self.block.0].insts.insert(0, v);
+        Operand::Value(v)
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.func.blocks.push(Block {
+            insts: Vec::new(),
+            term: Term::Unreachable,
+        });
+        BlockId(self.func.blocks.len() - 1)
+    }
+
+    fn enter(&mut self, b: BlockId) {
+        self.block = b;
+        self.order.push(b);
+    }
+
+    // ends the current block, what follows is unreachable until a block
+    // is entered
+    fn terminate(&mut self, term: Term) {
+        self.func.blocks[self.block.0].term = term;
+        self.block = self.new_block();
+    }
+
+    fn jump(&mut self, to: BlockId) {
+        self.terminate(Term::Jump(to));
+    }
+
+    fn branch(&mut self, c: Operand, t: BlockId, f: BlockId) {
+        match c {
+            Operand::Int(n, _) => self.jump(if n != 0 { t } else { f }),
+            Operand::Global(..) => self.jump(t),
+            c => self.terminate(Term::Branch(c, t, f)),
+        }
+    }
+
+    // the return of a function that reaches its end
+    fn fall_off(&self) -> Term {
+        match self.func.ret {
+            Ty::Void => Term::Return(None),
+            ty if self.main => Term::Return(Some(Operand::Int(0, ty))),
+            ty => Term::Return(Some(Operand::Undef(ty))),
+        }
+    }
+
+    fn finish(mut self) -> Function {
+        let term = self.fall_off();
+        self.terminate(term);
+
+        // the blocks go in the order they were entered
+        let mut index = vec![usize::MAX; self.func.blocks.len()];
+        let mut n = 0;
+        for b in self
+            .order
+            .iter()
+            .chain((0..index.len()).map(BlockId).collect::<Vec<_>>().iter())
+        {
+            if index[b.0] == usize::MAX {
+                index[b.0] = n;
+                n += 1;
+            }
+        }
+        let mut blocks: Vec<Option<Block>> = std::mem::take(&mut self.func.blocks)
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut order: Vec<usize> = (0..blocks.len()).collect();
+        order.sort_by_key(|&b| index[b]);
+
+        let func = &mut self.func;
+        for b in order {
+            let mut block = blocks[b].take().unwrap();
+            for s in block.term.succs_mut() {
+                *s = BlockId(index[s.0]);
+            }
+            for &v in block.insts.iter() {
+                if let InstKind::Phi(args) = &mut func.insts[v.0].kind {
+                    for (p, _) in args.iter_mut() {
+                        *p = BlockId(index[p.0]);
+                    }
+                }
+            }
+            func.blocks.push(block);
+        }
+        func.remove_unreachable();
+        self.func
+    }
+
+    fn block(&mut self, b: &'a BlockStmt) -> Result<()> {
+        for s in b.stmts.iter() {
+            self.stmt(s.as_ref())?;
+        }
+        Ok(())
+    }
+
+    fn stmt(&mut self, s: &'a dyn Stmt) -> Result<()> {
+        match s.kind() {
+            StmtKind::SemiColonStmt(_) | StmtKind::StaticAssert(_) => {}
+            StmtKind::ExprStmt(s) => {
+                self.expr(s.x.as_ref())?;
+            }
+            StmtKind::DeclStmt(d) => self.decl(d)?,
+
+            StmtKind::ReturnStmt(r) => {
+                let term = match &r.value {
+                    Some(x) => {
+                        let v = self.expr(x.as_ref())?;
+                        match &self.sret {
+                            Some(p) => {
+                                let size = self.t().size_of(&self.ret, x.start())?;
+                                self.effect(InstKind::Copy(p.clone(), v, size));
+                                Term::Return(None)
+                            }
+                            None if self.ret.is_void() => Term::Return(None),
+                            None => {
+                                let ret = self.ret.clone();
+                                let v = self.convert(v, &self.type_of(x.as_ref()), &ret);
+                                Term::Return(Some(v))
+                            }
+                        }
+                    }
+                    None => self.fall_off(),
+                };
+                self.terminate(term);
+            }
+
+            // a break or continue outside a loop ends the function
+            StmtKind::BreakStmt(_) => match self.jumps.last() {
+                Some(&(to, _)) => self.jump(to),
+                None => self.terminate(self.fall_off()),
+            },
+            StmtKind::ContinueStmt(_) => match self.jumps.iter().rev().find_map(|j| j.1) {
+                Some(to) => self.jump(to),
+                None => self.terminate(self.fall_off()),
+            },
+            StmtKind::GotoStmt(g) => {
+                let to = self.label(&g.label.name);
+                self.jump(to);
+            }
+
+            StmtKind::BlockStmt(b) => self.block(b)?,
+
+            StmtKind::IfStmt(s) => {
+                let mut branches = vec![(Some(s.cond.as_ref()), s.init.as_ref())];
+                branches.extend(
+                    s.elifs
+                        .iter()
+                        .map(|e| (Some(e.cond.as_ref()), e.init.as_ref())),
+                );
+                branches.extend(s._else.iter().map(|e| (None, e.init.as_ref())));
+
+                let end = self.new_block();
+                for (cond, body) in branches {
+                    let Some(x) = cond else {
+                        self.stmt(body)?;
+                        break;
+                    };
+                    let c = self.cond(x)?;
+                    let (t, f) = (self.new_block(), self.new_block());
+                    self.branch(c, t, f);
+                    self.enter(t);
+                    self.stmt(body)?;
+                    self.jump(end);
+                    self.enter(f);
+                }
+                self.jump(end);
+                self.enter(end);
+            }
+
+            StmtKind::WhileStmt(w) => {
+                let (cond, body, exit) = (self.new_block(), self.new_block(), self.new_block());
+                self.jump(cond);
+                self.enter(cond);
+                let c = self.cond(w.cond.as_ref())?;
+                self.branch(c, body, exit);
+                self.enter(body);
+                self.looped(w.init.as_ref(), exit, cond)?;
+                self.jump(cond);
+                self.enter(exit);
+            }
+
+            StmtKind::DowhileStmt(d) => {
+                let (body, cond, exit) = (self.new_block(), self.new_block(), self.new_block());
+                self.jump(body);
+                self.enter(body);
+                self.looped(d.init.as_ref(), exit, cond)?;
+                self.jump(cond);
+                self.enter(cond);
+                let c = self.cond(d.cond.as_ref())?;
+                self.branch(c, body, exit);
+                self.enter(exit);
+            }
+
+            StmtKind::ForStmt(f) => {
+                if let Some(init) = &f.init {
+                    self.stmt(init.as_ref())?;
+                }
+                let (cond, body) = (self.new_block(), self.new_block());
+                let (post, exit) = (self.new_block(), self.new_block());
+                self.jump(cond);
+                self.enter(cond);
+                match &f.cond {
+                    Some(x) => {
+                        let c = self.cond(x.as_ref())?;
+                        self.branch(c, body, exit);
+                    }
+                    None => self.jump(body),
+                }
+                self.enter(body);
+                self.looped(f.body.as_ref(), exit, post)?;
+                self.jump(post);
+                self.enter(post);
+                if let Some(x) = &f.post {
+                    self.expr(x.as_ref())?;
+                }
+                self.jump(cond);
+                self.enter(exit);
+            }
+
+            StmtKind::SwitchStmt(s) => {
+                let tag = s.tag.as_ref();
+                let ty = self.type_of(tag).promote();
+                let v = self.expr(tag)?;
+                let v = self.convert(v, &self.type_of(tag), &ty);
+
+                // the cases are known once the body is lowered, the
+                // statements before the first one are not reached
+                let at = self.block;
+                let exit = self.new_block();
+                self.terminate(Term::Unreachable);
+
+                self.jumps.push((exit, None));
+                self.cases.push(Vec::new());
+                self.stmt(s.body.as_ref())?;
+                self.jumps.pop();
+                self.jump(exit);
+
+                let mut cases = Vec::new();
+                let mut default = exit;
+                for (value, b) in self.cases.pop().unwrap() {
+                    let Some(x) = value else {
+                        default = b;
+                        continue;
+                    };
+                    let Operand::Int(n, _) = int(self.eval(x)? as i64, self.ty(&ty)) else {
+                        unreachable!();
+                    };
+                    cases.push((n, b));
+                }
+                self.func.blocks[at.0].term = Term::Switch(v, cases, default);
+                self.enter(exit);
+            }
+
+            StmtKind::CaseStmt(c) => {
+                let b = self.new_block();
+                self.jump(b);
+                self.enter(b);
+                if let Some(cases) = self.cases.last_mut() {
+                    cases.push((c.value.as_deref(), b));
+                }
+                self.stmt(c.stmt.as_ref())?;
+            }
+
+            StmtKind::LabeledStmt(l) => {
+                let b = self.label(&l.label.name);
+                self.jump(b);
+                self.enter(b);
+                self.stmt(l.stmt.as_ref())?;
+            }
+        }
+        Ok(())
+    }
+
+    // the body of a loop, break goes to `exit` and continue to `next`
+    fn looped(&mut self, s: &'a dyn Stmt, exit: BlockId, next: BlockId) -> Result<()> {
+        self.jumps.push((exit, Some(next)));
+        let r = self.stmt(s);
+        self.jumps.pop();
+        r
+    }
+
+    fn label(&mut self, name: &'a str) -> BlockId {
+        match self.labels.get(name) {
+            Some(&b) => b,
+            None => {
+                let b = self.new_block();
+                self.labels.insert(name, b);
+                b
+            }
+        }
+    }
+
+    // the declarations of automatic variables allocate them, those of
+    // static ones define internal globals
+    fn decl(&mut self, d: &'a DeclStmt) -> Result<()> {
+        let g = &d.decl;
+        if matches!(g.specs.storage, Some(Token::TYPEDEF | Token::EXTERN)) {
+            return Ok(());
+        }
+
+        for d in g.decls.iter() {
+            let name = d.declarator.name.as_ref();
+            let Some(&id) = name.and_then(|n| self.t().info.decls.get(&n.pos)) else {
+                continue;
+            };
+            let sym = self.t().info.symbol(id);
+            if sym.kind != SymbolKind::Var {
+                continue;
+            }
+            let pos = d.declarator.pos;
+            if sym.ty.is_vla() {
+                return error(pos, "variable length arrays are not supported");
+            }
+
+            if g.specs.storage == Some(Token::STATIC) {
+                let name = self.l.unique(&format!("{}.{}", self.func.name, sym.name));
+                self.l.names.insert(id, name.clone());
+                self.l.define(id, name, d.init.as_deref(), true)?;
+                continue;
+            }
+
+            let ty = sym.ty.clone();
+            let p = self.alloca(&ty, pos)?;
+            self.vars.insert(id, p.clone());
+            if let Some(x) = &d.init {
+                self.init(p, &ty, x.as_ref())?;
+            }
+        }
+        Ok(())
+    }
+
+    // initializes the object at `p`, the parts an initializer list leaves
+    // out are zero
+    fn init(&mut self, p: Operand, ty: &Type, x: &'a dyn Expr) -> Result<()> {
+        if is_aggregate(ty) {
+            let size = self.t().size_of(ty, x.start())?;
+            self.effect(InstKind::Zero(p.clone(), size));
+        }
+
+        for leaf in leaves(self.t(), ty, x)? {
+            let place = Place {
+                ptr: self.offset(p.clone(), leaf.offset as i64),
+                bits: leaf.bits,
+            };
+            let x = leaf.value;
+
+            if is_string(x) && leaf.ty.is_array() {
+                let s = self.expr(x)?;
+                let len = self.t().size_of(&self.type_of(x), x.start())?;
+                let n = self.t().size_of(&leaf.ty, x.start())?.min(len);
+                self.effect(InstKind::Copy(place.ptr, s, n));
+                continue;
+            }
+            let v = self.expr(x)?;
+            let v = self.convert(v, &self.type_of(x), &leaf.ty);
+            self.store(&place, &leaf.ty, v, x.start())?;
+        }
+        Ok(())
+    }
+
+    fn eval(&self, x: &dyn Expr) -> Result<i128> {
+        match Eval::new(self.t().info).int(x) {
+            Ok(v) => Ok(v),
+            Err(n) => error(n.pos, n.msg),
+        }
+    }
+
+    /// Lowers an expression to the instructions that compute its value,
+    /// of the type of the expression after it decays.
+    fn expr(&mut self, x: &'a dyn Expr) -> Result<Operand> {
+        let ty = self.type_of(x);
+        match x.kind() {
+            ExprKind::BasicLit(b) => match b.tok {
+                Token::INTEGER | Token::CHARACTER => Ok(int(self.eval(x)? as i64, self.ty(&ty))),
+                Token::FLOATING => {
+                    let f = parse_float(&b.lit).unwrap_or_default();
+                    Ok(float(f, self.ty(&ty)))
+                }
+                _ => self.l.string(x),
+            },
+
+            ExprKind::Ident(id) => match self.t().info.lookup(id.pos) {
+                Some(s) if s.kind == SymbolKind::EnumConst => {
+                    Ok(int(s.value.unwrap_or(0) as i64, self.ty(&ty)))
+                }
+                Some(s) if s.kind == SymbolKind::Func => Ok(Operand::Global(s.name.clone(), 0)),
+                _ => self.rvalue(x),
+            },
+
+            ExprKind::ParenExpr(p) => self.expr(p.x.as_ref()),
+
+            ExprKind::UnaryExpr(u) => {
+                let tx = self.type_of(u.x.as_ref());
+                match u.op {
+                    Token::AND if tx.is_function() => self.expr(u.x.as_ref()),
+                    Token::AND => Ok(self.place(u.x.as_ref())?.ptr),
+                    Token::INC | Token::DEC => self.step(u.op, u.x.as_ref(), true),
+                    Token::NOT | Token::BANG => {
+                        let v = self.expr(u.x.as_ref())?;
+                        Ok(self.compare_zero(v, &tx, true))
+                    }
+                    op => {
+                        let v = self.expr(u.x.as_ref())?;
+                        let v = self.convert(v, &tx, &ty);
+                        let t = self.ty(&ty);
+                        Ok(match op {
+                            Token::MINUS if t.is_float() => {
+                                self.add(t, InstKind::Unary(UnOp::FNeg, v))
+                            }
+                            Token::MINUS => self.add(t, InstKind::Unary(UnOp::Neg, v)),
+                            Token::TILDE => self.add(t, InstKind::Unary(UnOp::Not, v)),
+                            _ => v,
+                        })
+                    }
+                }
+            }
+
+            ExprKind::StarExpr(s) if ty.is_function() => self.expr(s.x.as_ref()),
+            ExprKind::StarExpr(_)
+            | ExprKind::IndexExpr(_)
+            | ExprKind::SelectorExpr(_)
+            | ExprKind::CompositeLit(_) => self.rvalue(x),
+
+            ExprKind::PostfixExpr(p) => self.step(p.op, p.x.as_ref(), false),
+
+            ExprKind::InfixExpr(e) => match e.op {
+                Token::COMMA => {
+                    self.expr(e.x.as_ref())?;
+                    self.expr(e.y.as_ref())
+                }
+                Token::LAND | Token::LOR => self.logical(e),
+                op => {
+                    let a = self.expr(e.x.as_ref())?;
+                    let b = self.expr(e.y.as_ref())?;
+                    let (ta, tb) = (self.type_of(e.x.as_ref()), self.type_of(e.y.as_ref()));
+                    let (v, t) = self.binary(op, a, &ta, b, &tb, e.op_pos)?;
+                    Ok(self.convert(v, &t, &ty))
+                }
+            },
+
+            ExprKind::AssignExpr(e) => self.assign(e),
+            ExprKind::CondExpr(e) => self.conditional(e, &ty),
+
+            ExprKind::CallExpr(c) => {
+                let fty = self.type_of(c.fun.as_ref());
+                let Some(func) = fty.func().or_else(|| fty.pointee().and_then(Type::func)) else {
+                    return error(c.fun.start(), "called object is not a function");
+                };
+                let func = func.clone();
+                let callee = self.expr(c.fun.as_ref())?;
+
+                // the arguments convert to the types of the parameters of a
+                // prototype, the others are promoted, records are copied
+                let params = if func.prototype {
+                    &func.params[..]
+                } else {
+                    &[]
+                };
+                let mut args = Vec::new();
+                let sret = match func.ret.is_record() {
+                    true => Some(self.alloca(&func.ret, c.lparen)?),
+                    false => None,
+                };
+                args.extend(sret.clone());
+                for (i, a) in c.args.iter().enumerate() {
+                    let ta = self.type_of(a.as_ref());
+                    let v = self.expr(a.as_ref())?;
+                    let ty = params.get(i).cloned().unwrap_or_else(|| ta.promote_arg());
+                    let v = self.convert(v, &ta, &ty);
+                    if ty.is_record() {
+                        let p = self.alloca(&ty, a.start())?;
+                        self.store(&p.clone().into(), &ty, v, a.start())?;
+                        args.push(p);
+                    } else {
+                        args.push(v);
+                    }
+                }
+
+                // the assert of the bundled header also takes the text of
+                // its argument, to print when it fails
+                let info = self.l.types.info;
+                if let Some(text) = info.asserts.get(&c.fun.start()) {
+                    if !info.lookup(c.fun.start()).is_some_and(|s| s.defined) {
+                        args.push(self.l.text(".assert", text));
+                    }
+                }
+
+                let ret = match sret {
+                    Some(_) => Ty::Void,
+                    None => self.ty(&func.ret),
+                };
+                let v = self.func.add(ret, InstKind::Call(callee, args));
+                self.func.blocks[self.block.0].insts.push(v);
+                Ok(match (sret, ret) {
+                    (Some(p), _) => p,
+                    (None, Ty::Void) => Operand::Undef(Ty::Void),
+                    (None, _) => Operand::Value(v),
+                })
+            }
+
+            ExprKind::CastExpr(c) => {
+                let v = self.expr(c.x.as_ref())?;
+                Ok(self.convert(v, &self.type_of(c.x.as_ref()), &ty))
+            }
+
+            ExprKind::SizeofExpr(_) | ExprKind::OffsetofExpr(_) => match self.eval(x) {
+                Ok(n) => Ok(int(n as i64, self.ty(&ty))),
+                Err(_) => error(x.start(), "variable length arrays are not supported"),
+            },
+
+            ExprKind::InitList(l) => error(l.lbrace, "unexpected initializer list"),
+        }
+    }
+
+    // loads the value of an lvalue, an array decays to its address
+    fn rvalue(&mut self, x: &'a dyn Expr) -> Result<Operand> {
+        let p = self.place(x)?;
+        let ty = self.type_of(x);
+        Ok(self.load(&p, &ty))
+    }
+
+    /// Lowers an lvalue to the address of the object it designates.
+    fn place(&mut self, x: &'a dyn Expr) -> Result<Place> {
+        match x.kind() {
+            ExprKind::ParenExpr(p) => self.place(p.x.as_ref()),
+
+            ExprKind::Ident(id) => match self.t().info.uses.get(&id.pos) {
+                Some(&sym) => Ok(self.var(sym).into()),
+                None => error(id.pos, format!("undeclared identifier '{}'", id.name)),
+            },
+
+            ExprKind::StarExpr(s) => Ok(self.expr(s.x.as_ref())?.into()),
+
+            ExprKind::IndexExpr(e) => {
+                let a = self.expr(e.x.as_ref())?;
+                let b = self.expr(e.index.as_ref())?;
+                let (ta, tb) = (
+                    self.type_of(e.x.as_ref()).decay(),
+                    self.type_of(e.index.as_ref()).decay(),
+                );
+                let (p, i, ti) = match ta.is_pointer() {
+                    true => (a, b, tb),
+                    false => (b, a, ta),
+                };
+                let size = self.t().size_of(&self.type_of(x), e.lbrack)?;
+                Ok(self.index(p, i, &ti, size as i64).into())
+            }
+
+            ExprKind::SelectorExpr(s) => {
+                let tx = self.type_of(s.x.as_ref());
+                let ty = match s.op {
+                    Token::ARROW => tx.pointee().cloned().unwrap_or_default(),
+                    _ => tx,
+                };
+                // the value of a record is its address
+                let base = self.expr(s.x.as_ref())?;
+
+                let (TypeKind::Struct(id) | TypeKind::Union(id)) = ty.kind else {
+                    return error(s.op_pos, "member access in a value that is not a record");
+                };
+                let Some(f) = self.t().member(id, &s.sel.name) else {
+                    return error(s.sel.pos, format!("no member named '{}'", s.sel.name));
+                };
+                let (offset, bits) = self.t().field(&f, s.sel.pos)?;
+                Ok(Place {
+                    ptr: self.offset(base, offset as i64),
+                    bits,
+                })
+            }
+
+            ExprKind::BasicLit(b) if b.tok == Token::STRING => Ok(self.l.string(x)?.into()),
+
+            // a compound literal is an object of the function, reinitialized
+            // each time it is evaluated, or a global outside functions
+            ExprKind::CompositeLit(c) => {
+                let ty = self.type_of(x);
+                if self.scratch {
+                    let name = match self.l.literals {
+                        0 => ".compoundliteral".to_string(),
+                        n => format!(".compoundliteral.{}", n),
+                    };
+                    self.l.literals += 1;
+                    let mut g = Global {
+                        name: name.clone(),
+                        size: self.t().size_of(&ty, c.lparen)?,
+                        align: self.t().align_of(&ty),
+                        data: Vec::new(),
+                        relocs: Vec::new(),
+                        readonly: false,
+                        internal: true,
+                    };
+                    self.l.init(&mut g, &ty, &c.init)?;
+                    self.l.module.globals.push(g);
+                    return Ok(Operand::Global(name, 0).into());
+                }
+
+                let p = match self.temps.get(&c.lparen) {
+                    Some(p) => p.clone(),
+                    None => {
+                        let p = self.alloca(&ty, c.lparen)?;
+                        self.temps.insert(c.lparen, p.clone());
+                        p
+                    }
+                };
+                self.init(p.clone(), &ty, &c.init)?;
+                Ok(p.into())
+            }
+
+            _ => error(x.start(), "expression is not an lvalue"),
+        }
+    }
+
+    // the address of a variable, the others are globals
+    fn var(&self, id: sema::SymbolId) -> Operand {
+        if let Some(p) = self.vars.get(&id) {
+            return p.clone();
+        }
+        match self.l.names.get(&id) {
+            Some(name) => Operand::Global(name.clone(), 0),
+            None => Operand::Global(self.t().info.symbol(id).name.clone(), 0),
+        }
+    }
+
+    fn offset(&mut self, p: Operand, n: i64) -> Operand {
+        self.add(Ty::Ptr, InstKind::PtrAdd(p, Operand::Int(n, Ty::I64)))
+    }
+
+    // the address `i` elements of `size` bytes after `p`
+    fn index(&mut self, p: Operand, i: Operand, ti: &Type, size: i64) -> Operand {
+        let i = self.resize(i, self.ty(ti), Ty::I64, self.t().is_signed(ti));
+        let n = self.add(
+            Ty::I64,
+            InstKind::Binary(BinOp::Mul, i, Operand::Int(size, Ty::I64)),
+        );
+        self.add(Ty::Ptr, InstKind::PtrAdd(p, n))
+    }
+
+    /// Loads a value of type `ty`, a record or an array is its address.
+    fn load(&mut self, p: &Place, ty: &Type) -> Operand {
+        if is_aggregate(ty) || ty.is_function() {
+            return p.ptr.clone();
+        }
+        let t = self.ty(ty);
+        let raw = self.add(t, InstKind::Load(p.ptr.clone()));
+        let Some((shift, width)) = p.bits else {
+            return raw;
+        };
+
+        // a signed bit-field extends its sign bit
+        let bits = t.size() as u32 * 8;
+        let c = |n: u32| Operand::Int(n as i64, t);
+        if self.t().is_signed(ty) {
+            let v = self.add(
+                t,
+                InstKind::Binary(BinOp::Shl, raw, c(bits - shift - width)),
+            );
+            self.add(t, InstKind::Binary(BinOp::AShr, v, c(bits - width)))
+        } else {
+            let v = self.add(t, InstKind::Binary(BinOp::LShr, raw, c(shift)));
+            self.add(t, InstKind::Binary(BinOp::And, v, int(mask(width), t)))
+        }
+    }
+
+    /// Stores a value of type `ty`, the other bits of the unit of a
+    /// bit-field are kept and a record is copied.
+    fn store(&mut self, p: &Place, ty: &Type, v: Operand, pos: usize) -> Result<()> {
+        if ty.is_record() {
+            let size = self.t().size_of(ty, pos)?;
+            self.effect(InstKind::Copy(p.ptr.clone(), v, size));
+            return Ok(());
+        }
+        let Some((shift, width)) = p.bits else {
+            self.effect(InstKind::Store(p.ptr.clone(), v));
+            return Ok(());
+        };
+
+        let t = self.ty(ty);
+        let m = mask(width) << shift;
+        let raw = self.add(t, InstKind::Load(p.ptr.clone()));
+        let kept = self.add(t, InstKind::Binary(BinOp::And, raw, int(!m, t)));
+        let v = self.add(t, InstKind::Binary(BinOp::Shl, v, int(shift as i64, t)));
+        let v = self.add(t, InstKind::Binary(BinOp::And, v, int(m, t)));
+        let v = self.add(t, InstKind::Binary(BinOp::Or, kept, v));
+        self.effect(InstKind::Store(p.ptr.clone(), v));
+        Ok(())
+    }
+
+    fn assign(&mut self, e: &'a AssignExpr) -> Result<Operand> {
+        let ty = self.type_of(e.x.as_ref()).unqualified();
+        let p = self.place(e.x.as_ref())?;
+        let b = self.expr(e.y.as_ref())?;
+        let tb = self.type_of(e.y.as_ref());
+
+        let v = match e.op.binary_op() {
+            Token::ASSIGN => self.convert(b, &tb, &ty),
+            op => {
+                let a = self.load(&p, &ty);
+                let (v, t) = self.binary(op, a, &ty, b, &tb, e.op_pos)?;
+                self.convert(v, &t, &ty)
+            }
+        };
+        self.store(&p, &ty, v.clone(), e.x.start())?;
+
+        // the value of a bit-field is truncated to its width
+        Ok(match (&p.bits, ty.is_record()) {
+            (Some(_), _) => self.load(&p, &ty),
+            (None, true) => p.ptr,
+            (None, false) => v,
+        })
+    }
+
+    // ++ and --, `prefix` for the value after the step
+    fn step(&mut self, op: Token, x: &'a dyn Expr, prefix: bool) -> Result<Operand> {
+        let ty = self.type_of(x).unqualified();
+        let p = self.place(x)?;
+        let old = self.load(&p, &ty);
+        let delta = if op == Token::INC { 1 } else { -1 };
+
+        let t = self.ty(&ty);
+        let new = if ty.is_pointer() {
+            let n = delta * self.t().stride(&ty, x.start())?;
+            self.offset(old.clone(), n)
+        } else if t.is_float() {
+            let one = float(delta as f64, t);
+            self.add(t, InstKind::Binary(BinOp::FAdd, old.clone(), one))
+        } else {
+            let pt = ty.promote();
+            let v = self.convert(old.clone(), &ty, &pt);
+            let n = self.ty(&pt);
+            let v = self.add(n, InstKind::Binary(BinOp::Add, v, int(delta, n)));
+            self.convert(v, &pt, &ty)
+        };
+        self.store(&p, &ty, new.clone(), x.start())?;
+
+        Ok(match (prefix, &p.bits) {
+            (true, Some(_)) => self.load(&p, &ty),
+            (true, None) => new,
+            (false, _) => old,
+        })
+    }
+
+    // && and ||, the right operand is evaluated when the left one does
+    // not decide
+    fn logical(&mut self, e: &'a InfixExpr) -> Result<Operand> {
+        let and = e.op == Token::LAND;
+        let c = self.cond(e.x.as_ref())?;
+        let from = self.block;
+        let (rhs, end) = (self.new_block(), self.new_block());
+        match and {
+            true => self.branch(c, rhs, end),
+            false => self.branch(c, end, rhs),
+        }
+
+        self.enter(rhs);
+        let ty = self.type_of(e.y.as_ref());
+        let v = self.expr(e.y.as_ref())?;
+        let v = self.compare_zero(v, &ty, false);
+        let last = self.block;
+        self.jump(end);
+
+        self.enter(end);
+        let short = Operand::Int(!and as i64, Ty::I32);
+        Ok(self.phi(Ty::I32, vec![(from, short), (last, v)]))
+    }
+
+    fn conditional(&mut self, e: &'a CondExpr, ty: &Type) -> Result<Operand> {
+        let ty = ty.decay();
+        let c = self.cond(e.cond.as_ref())?;
+        let (t, f, end) = (self.new_block(), self.new_block(), self.new_block());
+        self.branch(c, t, f);
+
+        let mut args = Vec::new();
+        for (b, x) in [(t, e.x.as_ref()), (f, e.y.as_ref())] {
+            self.enter(b);
+            let v = self.expr(x)?;
+            let v = self.convert(v, &self.type_of(x), &ty);
+            args.push((self.block, v));
+            self.jump(end);
+        }
+
+        self.enter(end);
+        match ty.is_void() {
+            true => Ok(Operand::Undef(Ty::Void)),
+            false => Ok(self.phi(self.ty(&ty), args)),
+        }
+    }
+
+    // the value of a condition, compared to zero by the branch on it
+    fn cond(&mut self, x: &'a dyn Expr) -> Result<Operand> {
+        let ty = self.type_of(x).decay();
+        let v = self.expr(x)?;
+        match ty.is_floating() {
+            true => Ok(self.compare_zero(v, &ty, false)),
+            false => Ok(v),
+        }
+    }
+
+    // 1 if a scalar is zero and `eq`, or is not and `!eq`, 0 otherwise
+    fn compare_zero(&mut self, v: Operand, ty: &Type, eq: bool) -> Operand {
+        // a comparison is 0 or 1 already
+        if let Operand::Value(id) = &v {
+            if !eq && matches!(self.func.inst(*id).kind, InstKind::Cmp(..)) {
+                return v;
+            }
+        }
+        let t = self.ty(&ty.decay());
+        let (op, zero) = match t {
+            Ty::F32 | Ty::F64 => (if eq { CmpOp::FEq } else { CmpOp::FNe }, float(0.0, t)),
+            t => (if eq { CmpOp::Eq } else { CmpOp::Ne }, Operand::Int(0, t)),
+        };
+        self.add(Ty::I32, InstKind::Cmp(op, v, zero))
+    }
+
+    /// Applies a binary operator to operands of types `ta` and `tb`, and
+    /// returns the value and its type.
+    fn binary(
+        &mut self,
+        op: Token,
+        a: Operand,
+        ta: &Type,
+        b: Operand,
+        tb: &Type,
+        pos: usize,
+    ) -> Result<(Operand, Type)> {
+        let (ta, tb) = (ta.decay(), tb.decay());
+        if ta.is_pointer() || tb.is_pointer() {
+            return self.pointer_op(op, a, &ta, b, &tb, pos);
+        }
+
+        if matches!(op, Token::SHL | Token::SHR) {
+            let t = ta.promote();
+            let a = self.convert(a, &ta, &t);
+            let b = self.convert(b, &tb, &t);
+            let op = match op {
+                Token::SHL => BinOp::Shl,
+                _ if self.t().is_signed(&t) => BinOp::AShr,
+                _ => BinOp::LShr,
+            };
+            let v = self.add(self.ty(&t), InstKind::Binary(op, a, b));
+            return Ok((v, t));
+        }
+
+        let t = usual_arithmetic(&ta, &tb, &self.t().info.layout);
+        let a = self.convert(a, &ta, &t);
+        let b = self.convert(b, &tb, &t);
+        let (float, signed) = (t.is_floating(), self.t().is_signed(&t));
+        if let Some(op) = compare_op(op, float, signed) {
+            return Ok((self.add(Ty::I32, InstKind::Cmp(op, a, b)), Type::default()));
+        }
+
+        let op = match (op, float, signed) {
+            (Token::PLUS, true, _) => BinOp::FAdd,
+            (Token::MINUS, true, _) => BinOp::FSub,
+            (Token::ASTERISK, true, _) => BinOp::FMul,
+            (Token::SLASH, true, _) => BinOp::FDiv,
+            (Token::PLUS, ..) => BinOp::Add,
+            (Token::MINUS, ..) => BinOp::Sub,
+            (Token::ASTERISK, ..) => BinOp::Mul,
+            (Token::SLASH, _, true) => BinOp::SDiv,
+            (Token::SLASH, _, false) => BinOp::UDiv,
+            (Token::REM, _, true) => BinOp::SRem,
+            (Token::REM, _, false) => BinOp::URem,
+            (Token::AND, ..) => BinOp::And,
+            (Token::OR, ..) => BinOp::Or,
+            (Token::XOR, ..) => BinOp::Xor,
+            (op, ..) => return error(pos, format!("unexpected operator {:?}", op)),
+        };
+        Ok((self.add(self.ty(&t), InstKind::Binary(op, a, b)), t))
+    }
+
+    // the arithmetic and comparisons of pointers, in units of the type
+    // pointed to
+    fn pointer_op(
+        &mut self,
+        op: Token,
+        a: Operand,
+        ta: &Type,
+        b: Operand,
+        tb: &Type,
+        pos: usize,
+    ) -> Result<(Operand, Type)> {
+        match op {
+            Token::PLUS | Token::MINUS if tb.is_integer() => {
+                let size = self.t().stride(ta, pos)?;
+                let size = if op == Token::MINUS { -size } else { size };
+                Ok((self.index(a, b, tb, size), ta.clone()))
+            }
+            Token::PLUS if ta.is_integer() => {
+                let size = self.t().stride(tb, pos)?;
+                Ok((self.index(b, a, ta, size), tb.clone()))
+            }
+            Token::MINUS => {
+                let size = self.t().stride(ta, pos)?;
+                let a = self.add(Ty::I64, InstKind::Cast(CastOp::PtrToInt, a));
+                let b = self.add(Ty::I64, InstKind::Cast(CastOp::PtrToInt, b));
+                let d = self.add(Ty::I64, InstKind::Binary(BinOp::Sub, a, b));
+                let size = Operand::Int(size, Ty::I64);
+                let v = self.add(Ty::I64, InstKind::Binary(BinOp::SDiv, d, size));
+                Ok((v, Type::int(self.t().info.layout.ptrdiff_t)))
+            }
+            op => {
+                // a null pointer constant compared to a pointer
+                let a = self.convert(a, ta, &ta.decay().pointer_to());
+                let b = self.convert(b, tb, &tb.decay().pointer_to());
+                let Some(op) = compare_op(op, false, false) else {
+                    return error(pos, format!("unexpected operator {:?}", op));
+                };
+                Ok((self.add(Ty::I32, InstKind::Cmp(op, a, b)), Type::default()))
+            }
+        }
+    }
+
+    /// Converts a value of type `from` to type `to`, the conversions of
+    /// assignments and casts.
+    fn convert(&mut self, v: Operand, from: &Type, to: &Type) -> Operand {
+        let (from, to) = (from.decay(), to.decay());
+        if to.is_void() {
+            return Operand::Undef(Ty::Void);
+        }
+        if to.kind == TypeKind::Int(IntKind::Bool) {
+            if from.kind == to.kind {
+                return v;
+            }
+            let c = self.compare_zero(v, &from, false);
+            return self.add(Ty::I8, InstKind::Cast(CastOp::Trunc, c));
+        }
+
+        let (tf, tt) = (self.ty(&from), self.ty(&to));
+        let signed = self.t().is_signed(&from);
+        match (tf, tt) {
+            _ if from.is_record() || to.is_record() => v,
+            (Ty::Ptr, Ty::Ptr) => v,
+            (Ty::Ptr, _) if tt.is_float() => v, // not a conversion of C
+            (Ty::Ptr, _) => self.add(tt, InstKind::Cast(CastOp::PtrToInt, v)),
+            (_, Ty::Ptr) if tf.is_float() => v,
+            (_, Ty::Ptr) => {
+                let v = self.resize(v, tf, Ty::I64, signed);
+                self.add(Ty::Ptr, InstKind::Cast(CastOp::IntToPtr, v))
+            }
+            (Ty::F32, Ty::F64) => self.add(tt, InstKind::Cast(CastOp::FpExt, v)),
+            (Ty::F64, Ty::F32) => self.add(tt, InstKind::Cast(CastOp::FpTrunc, v)),
+            _ if tf.is_float() && tt.is_float() => v,
+            _ if tf.is_float() => {
+                let op = match self.t().is_signed(&to) {
+                    true => CastOp::FpToSi,
+                    false => CastOp::FpToUi,
+                };
+                self.add(tt, InstKind::Cast(op, v))
+            }
+            _ if tt.is_float() => {
+                let op = if signed {
+                    CastOp::SiToFp
+                } else {
+                    CastOp::UiToFp
+                };
+                self.add(tt, InstKind::Cast(op, v))
+            }
+            _ => self.resize(v, tf, tt, signed),
+        }
+    }
+
+    // converts an integer to an integer type of another size
+    fn resize(&mut self, v: Operand, from: Ty, to: Ty, signed: bool) -> Operand {
+        let op = match from.size().cmp(&to.size()) {
+            std::cmp::Ordering::Equal => return v,
+            std::cmp::Ordering::Greater => CastOp::Trunc,
+            _ if signed => CastOp::SExt,
+            _ => CastOp::ZExt,
+        };
+        self.add(to, InstKind::Cast(op, v))
+    }
+}
+
+// the comparison of an operator, on operands of a kind
+fn compare_op(op: Token, float: bool, signed: bool) -> Option<CmpOp> {
+    Some(match (op, float, signed) {
+        (Token::EQL, true, _) => CmpOp::FEq,
+        (Token::NEQ, true, _) => CmpOp::FNe,
+        (Token::LT, true, _) => CmpOp::FLt,
+        (Token::LEQ, true, _) => CmpOp::FLe,
+        (Token::GT, true, _) => CmpOp::FGt,
+        (Token::GEQ, true, _) => CmpOp::FGe,
+        (Token::EQL, ..) => CmpOp::Eq,
+        (Token::NEQ, ..) => CmpOp::Ne,
+        (Token::LT, _, true) => CmpOp::SLt,
+        (Token::LEQ, _, true) => CmpOp::SLe,
+        (Token::GT, _, true) => CmpOp::SGt,
+        (Token::GEQ, _, true) => CmpOp::SGe,
+        (Token::LT, ..) => CmpOp::ULt,
+        (Token::LEQ, ..) => CmpOp::ULe,
+        (Token::GT, ..) => CmpOp::UGt,
+        (Token::GEQ, ..) => CmpOp::UGe,
+        _ => return None,
+    })
+}
+
+// the low `width` bits
+fn mask(width: u32) -> i64 {
+    match width {
+        64.. => -1,
+        w => (1i64 << w) - 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{function, module};
+
+    #[test]
+    fn test_globals() {
+        let m = module(
+            r#"struct P { int x; unsigned y : 3; char s[4]; };
+               static struct P g = { 1, 5, "ab" };
+               char *names[] = { "one", "two" };
+               int *q = &g.x + 1;
+               double d = 1.5f;
+               extern int e;"#,
+        );
+        let want = r#"@g = internal global 12, align 4 c"\01\00\00\00\05ab\00\00\00\00\00"
+@.str = internal constant 4, align 1 c"one\00"
+@.str.1 = internal constant 4, align 1 c"two\00"
+@names = global 16, align 8, [0] = @.str, [8] = @.str.1
+@q = global 8, align 8, [0] = @g+4
+@d = global 8, align 8 c"\00\00\00\00\00\00\F8?"
+"#;
+        assert_eq!(m.to_string(), want);
+    }
+
+    #[test]
+    fn test_function() {
+        let got = function(
+            "struct P { int x; unsigned y : 3; };
+             int f(struct P *p, int n) {
+                 static int calls;
+                 int s = 0;
+                 for (int i = 0; i < n && i < 3; i++)
+                     s += p[i].y;
+                 calls++;
+                 return s;
+             }",
+        );
+        let want = "\
+define i32 @f(ptr %0, i32 %1) {
+bb0:
+  %2 = alloca 8, align 8
+  %3 = alloca 4, align 4
+  %4 = alloca 4, align 4
+  %5 = alloca 4, align 4
+  store ptr %0, ptr %2
+  store i32 %1, ptr %3
+  store i32 0, ptr %4
+  store i32 0, ptr %5
+  br bb1
+bb1:
+  %6 = load i32, ptr %5
+  %7 = load i32, ptr %3
+  %8 = icmp slt i32 %6, %7
+  br i32 %8, bb2, bb3
+bb2:
+  %9 = load i32, ptr %5
+  %10 = icmp slt i32 %9, 3
+  br bb3
+bb3:
+  %11 = phi i32 [ 0, bb1 ], [ %10, bb2 ]
+  br i32 %11, bb4, bb6
+bb4:
+  %12 = load ptr, ptr %2
+  %13 = load i32, ptr %5
+  %14 = sext i32 %13 to i64
+  %15 = mul i64 %14, 8
+  %16 = ptradd ptr %12, i64 %15
+  %17 = ptradd ptr %16, i64 4
+  %18 = load i32, ptr %17
+  %19 = and i32 %18, 7
+  %20 = load i32, ptr %4
+  %21 = add i32 %20, %19
+  store i32 %21, ptr %4
+  br bb5
+bb5:
+  %22 = load i32, ptr %5
+  %23 = add i32 %22, 1
+  store i32 %23, ptr %5
+  br bb1
+bb6:
+  %24 = load i32, ptr @f.calls
+  %25 = add i32 %24, 1
+  store i32 %25, ptr @f.calls
+  %26 = load i32, ptr %4
+  ret i32 %26
+}
+";
+        assert_eq!(got.to_string(), want);
+    }
+
+    #[test]
+    fn test_records() {
+        let got = function(
+            "struct P { int x, y; };
+             struct P mk(int x) { struct P p = { .y = x }; return p; }
+             int g(void) { struct P p = mk(3); return p.y; }",
+        );
+        let want = "\
+define i32 @g() {
+bb0:
+  %0 = alloca 8, align 4
+  %1 = alloca 8, align 4
+  zero ptr %0, 8
+  call void @mk(ptr %1, i32 3)
+  copy ptr %0, ptr %1, 8
+  %2 = ptradd ptr %0, i64 4
+  %3 = load i32, ptr %2
+  ret i32 %3
+}
+";
+        assert_eq!(got.to_string(), want);
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let mut p = parser::Parser::from("void f(int n) { int a[n]; }".to_string());
+        let file = p.parse_file();
+        let info = sema::check(&file);
+        let err = crate::lower(&file, &info).unwrap_err();
+        assert_eq!(err.msg, "variable length arrays are not supported");
+    }
+}
@@ -0,0 +1,224 @@
+//! Promotes the variables in memory to SSA values, the algorithm of Cytron
+//! et al: phis go at the iterated dominance frontier of the stores, and a
+//! walk of the dominator tree renames the loads to the value stored last.
+//! A variable is promoted if its address is only loaded from and stored
+//! to, with one type of the size of the variable.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::dom::DomTree;
+use crate::{BlockId, Function, InstKind, Operand, Ty, ValueId};
+
+pub fn run(func: &mut Function) {
+    func.remove_unreachable();
+    let vars = promotable(func);
+    if vars.is_empty() {
+        return;
+    }
+    let index: HashMap<ValueId, usize> = vars.iter().enumerate().map(|(i, v)| (v.0, i)).collect();
+    let dom = DomTree::new(func);
+    let df = dom.frontiers();
+
+    // the phis, by block, and the variable of each
+    let mut phis: Vec<Vec<(ValueId, usize)>> = vec![Vec::new(); func.blocks.len()];
+    for (i, &(_, ty)) in vars.iter().enumerate() {
+        let mut work: Vec<BlockId> = Vec::new();
+        for (b, block) in func.blocks.iter().enumerate() {
+            let stores = block.insts.iter().any(|&v| match &func.inst(v).kind {
+                InstKind::Store(Operand::Value(p), _) => index.get(p) == Some(&i),
+                _ => false,
+            });
+            if stores {
+                work.push(BlockId(b));
+            }
+        }
+        let mut placed = vec![false; func.blocks.len()];
+        while let Some(b) = work.pop() {
+            for &f in df[b.0].iter() {
+                if !std::mem::replace(&mut placed[f.0], true) {
+                    let phi = func.add(ty, InstKind::Phi(Vec::new()));
+                    func.blocks[f.0].insts.insert(0, phi);
+                    phis[f.0].push((phi, i));
+                    work.push(f);
+                }
+            }
+        }
+    }
+
+    // the walk, with the values of the variables on stacks
+    let mut map: HashMap<ValueId, Operand> = HashMap::new();
+    let mut stacks: Vec<Vec<Operand>> = vars.iter().map(|v| vec![Operand::Undef(v.1)]).collect();
+    let mut dead: HashSet<ValueId> = HashSet::new();
+    enum Visit {
+        Enter(BlockId),
+        Leave(Vec<usize>),
+    }
+    let mut work = vec![Visit::Enter(BlockId(0))];
+    while let Some(visit) = work.pop() {
+        let b = match visit {
+            Visit::Enter(b) => b,
+            Visit::Leave(pushed) => {
+                for i in pushed {
+                    stacks[i].pop();
+                }
+                continue;
+            }
+        };
+
+        let mut pushed = Vec::new();
+        for &(phi, i) in phis[b.0].iter() {
+            stacks[i].push(Operand::Value(phi));
+            pushed.push(i);
+        }
+        for &v in func.blocks[b.0].insts.iter() {
+            match &func.inst(v).kind {
+                InstKind::Load(Operand::Value(p)) if index.contains_key(p) => {
+                    map.insert(v, stacks[index[p]].last().unwrap().clone());
+                    dead.insert(v);
+                }
+                InstKind::Store(Operand::Value(p), x) if index.contains_key(p) => {
+                    let mut x = x.clone();
+                    while let Operand::Value(id) = &x {
+                        match map.get(id) {
+                            Some(to) => x = to.clone(),
+                            None => break,
+                        }
+                    }
+                    stacks[index[p]].push(x);
+                    pushed.push(index[p]);
+                    dead.insert(v);
+                }
+                _ => {}
+            }
+        }
+
+        for s in func.blocks[b.0].term.succs() {
+            for &(phi, i) in phis[s.0].iter() {
+                let v = stacks[i].last().unwrap().clone();
+                if let InstKind::Phi(args) = &mut func.insts[phi.0].kind {
+                    if !args.iter().any(|a| a.0 == b) {
+                        args.push((b, v));
+                    }
+                }
+            }
+        }
+
+        work.push(Visit::Leave(pushed));
+        for &c in dom.children[b.0].iter().rev() {
+            work.push(Visit::Enter(c));
+        }
+    }
+
+    dead.extend(vars.iter().map(|v| v.0));
+    for b in func.blocks.iter_mut() {
+        b.insts.retain(|v| !dead.contains(v));
+    }
+    func.replace_uses(&map);
+}
+
+// the allocas of the entry block that can be promoted, with the type of
+// their values
+fn promotable(func: &Function) -> Vec<(ValueId, Ty)> {
+    let mut types: HashMap<ValueId, Option<Ty>> = HashMap::new();
+    for &v in func.blocks[0].insts.iter() {
+        if let InstKind::Alloca { .. } = func.inst(v).kind {
+            types.insert(v, None);
+        }
+    }
+
+    // any other use of the address, or a second type, disqualifies
+    let mut escaped: HashSet<ValueId> = HashSet::new();
+    let mut typed = |p: ValueId, ty: Ty, types: &mut HashMap<ValueId, Option<Ty>>| {
+        if let Some(t) = types.get_mut(&p) {
+            match t {
+                None => *t = Some(ty),
+                Some(t) if *t == ty => {}
+                Some(_) => {
+                    escaped.insert(p);
+                }
+            }
+        }
+    };
+    let mut others: Vec<&Operand> = Vec::new();
+    for b in func.blocks.iter() {
+        for &v in b.insts.iter() {
+            match &func.inst(v).kind {
+                InstKind::Load(Operand::Value(p)) => typed(*p, func.inst(v).ty, &mut types),
+                InstKind::Store(Operand::Value(p), x) => {
+                    typed(*p, func.type_of(x), &mut types);
+                    others.push(x);
+                }
+                kind => others.extend(kind.operands()),
+            }
+        }
+        others.extend(b.term.operand());
+    }
+    for v in others {
+        if let Operand::Value(v) = v {
+            escaped.insert(*v);
+        }
+    }
+
+    let mut vars: Vec<(ValueId, Ty)> = func.blocks[0]
+        .insts
+        .iter()
+        .filter_map(|v| match (types.get(v), &func.inst(*v).kind) {
+            (Some(Some(ty)), InstKind::Alloca { size, .. }) if ty.size() == *size => {
+                Some((*v, *ty))
+            }
+            _ => None,
+        })
+        .collect();
+    vars.retain(|v| !escaped.contains(&v.0));
+    vars
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::optimized;
+
+    #[test]
+    fn test_loop() {
+        let got = optimized(
+            "int f(int n) { int s = 0; for (int i = 0; i < n; i++) s += i; return s; }",
+            &["mem2reg", "dce"],
+        );
+        let want = "\
+define i32 @f(i32 %0) {
+bb0:
+  br bb1
+bb1:
+  %1 = phi i32 [ 0, bb0 ], [ %5, bb2 ]
+  %2 = phi i32 [ 0, bb0 ], [ %4, bb2 ]
+  %3 = icmp slt i32 %1, %0
+  br i32 %3, bb2, bb3
+bb2:
+  %4 = add i32 %2, %1
+  %5 = add i32 %1, 1
+  br bb1
+bb3:
+  ret i32 %2
+}
+";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn test_address_taken() {
+        let got = optimized(
+            "void g(int *); int f(void) { int x = 1; g(&x); return x; }",
+            &["mem2reg"],
+        );
+        let want = "\
+define i32 @f() {
+bb0:
+  %0 = alloca 4, align 4
+  store i32 1, ptr %0
+  call void @g(ptr %0)
+  %1 = load i32, ptr %0
+  ret i32 %1
+}
+";
+        assert_eq!(got, want);
+    }
+}
@@ -0,0 +1,299 @@
+//! The text format, close to LLVM's: values are numbered in the order
+//! they are defined, the parameters first, and blocks by their index.
+//!
+//! ```text
+//! define i32 @f(i32 %0) {
+//! bb0:
+//!   %1 = icmp slt i32 %0, 10
+//!   br i32 %1, bb1, bb2
+//! ...
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter, Write};
+
+use crate::{
+    BinOp, CastOp, CmpOp, Function, Global, InstKind, Module, Operand, Term, Ty, UnOp, ValueId,
+};
+
+impl Display for Ty {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Ty::Void => "void",
+            Ty::I8 => "i8",
+            Ty::I16 => "i16",
+            Ty::I32 => "i32",
+            Ty::I64 => "i64",
+            Ty::F32 => "float",
+            Ty::F64 => "double",
+            Ty::Ptr => "ptr",
+        })
+    }
+}
+
+impl Display for Module {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for g in self.globals.iter() {
+            writeln!(f, "{}", g)?;
+        }
+        for (i, func) in self.funcs.iter().enumerate() {
+            if i > 0 || !self.globals.is_empty() {
+                writeln!(f)?;
+            }
+            write!(f, "{}", func)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Global {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "@{} = {}{} {}, align {}",
+            self.name,
+            if self.internal { "internal " } else { "" },
+            if self.readonly { "constant" } else { "global" },
+            self.size,
+            self.align
+        )?;
+
+        if !self.data.is_empty() {
+            f.write_str(" c\"")?;
+            for &b in self.data.iter() {
+                match b {
+                    b' '..=b'~' if b != b'"' && b != b'\\' => f.write_char(b as char)?,
+                    b => write!(f, "\\{:02X}", b)?,
+                }
+            }
+            f.write_char('"')?;
+        }
+        for r in self.relocs.iter() {
+            write!(f, ", [{}] = @{}", r.offset, r.symbol)?;
+            if r.addend != 0 {
+                write!(f, "{:+}", r.addend)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let names = numbers(self);
+        let p = Printer { func: self, names };
+
+        let params: Vec<String> = self
+            .blocks
+            .first()
+            .map_or(&[][..], |b| &b.insts[..])
+            .iter()
+            .filter(|&&v| matches!(self.inst(v).kind, InstKind::Param(_)))
+            .map(|&v| format!("{} {}", self.inst(v).ty, p.value(v)))
+            .chain(self.variadic.then(|| "...".to_string()))
+            .collect();
+        writeln!(
+            f,
+            "define {}{} @{}({}) {{",
+            if self.internal { "internal " } else { "" },
+            self.ret,
+            self.name,
+            params.join(", ")
+        )?;
+
+        for (i, b) in self.blocks.iter().enumerate() {
+            writeln!(f, "bb{}:", i)?;
+            for &v in b.insts.iter() {
+                if !matches!(self.inst(v).kind, InstKind::Param(_)) {
+                    writeln!(f, "  {}", p.inst(v))?;
+                }
+            }
+            writeln!(f, "  {}", p.term(&b.term))?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+// numbers the values that are defined, the parameters first
+fn numbers(func: &Function) -> HashMap<ValueId, usize> {
+    let mut names = HashMap::new();
+    let insts = func.blocks.iter().flat_map(|b| b.insts.iter());
+
+    for &v in insts.clone() {
+        if let InstKind::Param(i) = func.inst(v).kind {
+            names.insert(v, i);
+        }
+    }
+    let mut n = names.len();
+    for &v in insts {
+        let inst = func.inst(v);
+        if inst.ty != Ty::Void && !matches!(inst.kind, InstKind::Param(_)) {
+            names.insert(v, n);
+            n += 1;
+        }
+    }
+    names
+}
+
+struct Printer<'f> {
+    func: &'f Function,
+    names: HashMap<ValueId, usize>,
+}
+
+impl Printer<'_> {
+    fn value(&self, v: ValueId) -> String {
+        match self.names.get(&v) {
+            Some(n) => format!("%{}", n),
+            None => format!("%?{}", v.0), // defined in no block
+        }
+    }
+
+    fn operand(&self, v: &Operand) -> String {
+        match v {
+            Operand::Value(v) => self.value(*v),
+            Operand::Int(n, Ty::Ptr) if *n == 0 => "null".to_string(),
+            Operand::Int(n, _) => n.to_string(),
+            Operand::Float(x, _) => format!("{:?}", x),
+            Operand::Global(name, 0) => format!("@{}", name),
+            Operand::Global(name, n) => format!("@{}{:+}", name, n),
+            Operand::Undef(_) => "undef".to_string(),
+        }
+    }
+
+    // an operand with its type
+    fn typed(&self, v: &Operand) -> String {
+        format!("{} {}", self.func.type_of(v), self.operand(v))
+    }
+
+    fn inst(&self, v: ValueId) -> String {
+        let inst = self.func.inst(v);
+        let body = match &inst.kind {
+            InstKind::Param(i) => format!("param {}", i),
+            InstKind::Phi(args) => {
+                let args: Vec<String> = args
+                    .iter()
+                    .map(|(b, v)| format!("[ {}, bb{} ]", self.operand(v), b.0))
+                    .collect();
+                format!("phi {} {}", inst.ty, args.join(", "))
+            }
+            InstKind::Alloca { size, align } => format!("alloca {}, align {}", size, align),
+            InstKind::Load(p) => format!("load {}, {}", inst.ty, self.typed(p)),
+            InstKind::Store(p, x) => format!("store {}, {}", self.typed(x), self.typed(p)),
+            InstKind::Binary(op, a, b) => format!(
+                "{} {} {}, {}",
+                binary(*op),
+                inst.ty,
+                self.operand(a),
+                self.operand(b)
+            ),
+            InstKind::Unary(op, a) => {
+                let op = match op {
+                    UnOp::Neg => "neg",
+                    UnOp::Not => "not",
+                    UnOp::FNeg => "fneg",
+                };
+                format!("{} {}", op, self.typed(a))
+            }
+            InstKind::Cmp(op, a, b) => {
+                let (kind, op) = compare(*op);
+                format!("{} {} {}, {}", kind, op, self.typed(a), self.operand(b))
+            }
+            InstKind::Cast(op, a) => format!("{} {} to {}", cast(*op), self.typed(a), inst.ty),
+            InstKind::PtrAdd(p, n) => format!("ptradd {}, {}", self.typed(p), self.typed(n)),
+            InstKind::Copy(d, s, n) => format!("copy {}, {}, {}", self.typed(d), self.typed(s), n),
+            InstKind::Zero(p, n) => format!("zero {}, {}", self.typed(p), n),
+            InstKind::Call(callee, args) => {
+                let args: Vec<String> = args.iter().map(|a| self.typed(a)).collect();
+                format!(
+                    "call {} {}({})",
+                    inst.ty,
+                    self.operand(callee),
+                    args.join(", ")
+                )
+            }
+        };
+
+        match inst.ty {
+            Ty::Void => body,
+            _ => format!("{} = {}", self.value(v), body),
+        }
+    }
+
+    fn term(&self, t: &Term) -> String {
+        match t {
+            Term::Jump(b) => format!("br bb{}", b.0),
+            Term::Branch(c, t, f) => format!("br {}, bb{}, bb{}", self.typed(c), t.0, f.0),
+            Term::Switch(v, cases, default) => {
+                let mut s = format!("switch {}, bb{} [", self.typed(v), default.0);
+                for (i, (n, b)) in cases.iter().enumerate() {
+                    let sep = if i > 0 { ", " } else { "" };
+                    let _ = write!(s, "{}{}: bb{}", sep, n, b.0);
+                }
+                s.push(']');
+                s
+            }
+            Term::Return(Some(v)) => format!("ret {}", self.typed(v)),
+            Term::Return(None) => "ret void".to_string(),
+            Term::Unreachable => "unreachable".to_string(),
+        }
+    }
+}
+
+fn binary(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "add",
+        BinOp::Sub => "sub",
+        BinOp::Mul => "mul",
+        BinOp::SDiv => "sdiv",
+        BinOp::UDiv => "udiv",
+        BinOp::SRem => "srem",
+        BinOp::URem => "urem",
+        BinOp::And => "and",
+        BinOp::Or => "or",
+        BinOp::Xor => "xor",
+        BinOp::Shl => "shl",
+        BinOp::LShr => "lshr",
+        BinOp::AShr => "ashr",
+        BinOp::FAdd => "fadd",
+        BinOp::FSub => "fsub",
+        BinOp::FMul => "fmul",
+        BinOp::FDiv => "fdiv",
+    }
+}
+
+fn compare(op: CmpOp) -> (&'static str, &'static str) {
+    match op {
+        CmpOp::Eq => ("icmp", "eq"),
+        CmpOp::Ne => ("icmp", "ne"),
+        CmpOp::SLt => ("icmp", "slt"),
+        CmpOp::SLe => ("icmp", "sle"),
+        CmpOp::SGt => ("icmp", "sgt"),
+        CmpOp::SGe => ("icmp", "sge"),
+        CmpOp::ULt => ("icmp", "ult"),
+        CmpOp::ULe => ("icmp", "ule"),
+        CmpOp::UGt => ("icmp", "ugt"),
+        CmpOp::UGe => ("icmp", "uge"),
+        CmpOp::FEq => ("fcmp", "oeq"),
+        CmpOp::FNe => ("fcmp", "une"),
+        CmpOp::FLt => ("fcmp", "olt"),
+        CmpOp::FLe => ("fcmp", "ole"),
+        CmpOp::FGt => ("fcmp", "ogt"),
+        CmpOp::FGe => ("fcmp", "oge"),
+    }
+}
+
+fn cast(op: CastOp) -> &'static str {
+    match op {
+        CastOp::Trunc => "trunc",
+        CastOp::ZExt => "zext",
+        CastOp::SExt => "sext",
+        CastOp::FpTrunc => "fptrunc",
+        CastOp::FpExt => "fpext",
+        CastOp::SiToFp => "sitofp",
+        CastOp::UiToFp => "uitofp",
+        CastOp::FpToSi => "fptosi",
+        CastOp::FpToUi => "fptoui",
+        CastOp::PtrToInt => "ptrtoint",
+        CastOp::IntToPtr => "inttoptr",
+    }
+}
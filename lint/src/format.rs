@@ -0,0 +1,263 @@
+//! Checks the arguments of the printf family against the conversions of
+//! a constant format string.
+
+use ast::{CallExpr, Expr, ExprKind};
+use sema::types::{FloatKind, IntKind, Type, TypeKind};
+use sema::SymbolKind;
+use token::Token;
+
+use crate::lints::strip_parens;
+use crate::{Context, Lint};
+
+/// `printf("%d", 1.5)`: a conversion of a type the argument does not have,
+/// or a number of conversions that is not the number of arguments.
+pub(crate) struct Format;
+
+// the functions checked and the index of their format argument
+const FUNCS: [(&str, usize); 7] = [
+    ("printf", 0),
+    ("fprintf", 1),
+    ("dprintf", 1),
+    ("sprintf", 1),
+    ("snprintf", 2),
+    ("asprintf", 1),
+    ("syslog", 1),
+];
+
+// what a conversion takes
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Arg {
+    Int(IntKind, &'static str), // the type and how clang spells it
+    Float(FloatKind),
+    Str,     // char *
+    WStr,    // wchar_t *
+    Pointer, // void *, any pointer
+    Count,   // int *, written by %n
+}
+
+// a conversion specification in a format string
+struct Spec {
+    pos: usize,       // position of the '%' in the file
+    stars: usize,     // the int arguments of '*' width and precision
+    arg: Option<Arg>, // None for %%
+}
+
+impl Lint for Format {
+    fn name(&self) -> &'static str {
+        "format"
+    }
+
+    fn expr(&self, cx: &mut Context, x: &dyn Expr) {
+        let ExprKind::CallExpr(call) = x.kind() else {
+            return;
+        };
+        let ExprKind::Ident(f) = call.fun.kind() else {
+            return;
+        };
+        let Some(&(_, index)) = FUNCS.iter().find(|(name, _)| *name == f.name) else {
+            return;
+        };
+        if !cx
+            .info
+            .lookup(f.pos)
+            .is_some_and(|s| s.kind == SymbolKind::Func)
+        {
+            return;
+        }
+
+        let Some(fmt) = call.args.get(index) else {
+            return;
+        };
+        let ExprKind::BasicLit(lit) = strip_parens(fmt.as_ref()).kind() else {
+            return;
+        };
+        if lit.tok != Token::STRING || !lit.lit.starts_with('"') {
+            return;
+        }
+
+        let specs = match parse(lit.pos, &lit.lit, cx) {
+            Ok(specs) => specs,
+            Err((pos, msg)) => return cx.warn(pos, msg),
+        };
+        check(cx, call, index + 1, &specs);
+    }
+}
+
+fn check(cx: &mut Context, call: &CallExpr, first: usize, specs: &[Spec]) {
+    let mut args = call.args.iter().skip(first);
+
+    for spec in specs.iter() {
+        let Some(want) = spec.arg else {
+            continue;
+        };
+
+        let int = Arg::Int(IntKind::Int, "int");
+        for want in std::iter::repeat_n(int, spec.stars).chain([want]) {
+            let Some(arg) = args.next() else {
+                cx.warn(
+                    spec.pos,
+                    "more '%' conversions than data arguments".to_string(),
+                );
+                return;
+            };
+
+            let Some(ty) = cx.info.type_of(arg.as_ref()).map(Type::promote_arg) else {
+                continue;
+            };
+
+            if !matches(want, &ty) {
+                let msg = format!(
+                    "format specifies type '{}' but the argument has type '{}'",
+                    spell(want),
+                    cx.spell(&ty)
+                );
+                cx.warn(arg.start(), msg);
+            }
+        }
+    }
+
+    if let Some(arg) = args.next() {
+        cx.warn(
+            arg.start(),
+            "data argument not used by format string".to_string(),
+        );
+    }
+}
+
+// reports whether a promoted argument fits a conversion, integers of the
+// same rank and different signedness do
+fn matches(want: Arg, ty: &Type) -> bool {
+    match (want, &ty.kind) {
+        (Arg::Int(k, _), _) => ty.int_kind().is_some_and(|a| a.rank() == k.rank()),
+        (Arg::Float(k), TypeKind::Float(a)) => {
+            k == *a || k == FloatKind::Double && *a == FloatKind::Float
+        }
+        (Arg::Str, _) => ty
+            .pointee()
+            .and_then(Type::int_kind)
+            .is_some_and(|k| k.rank() == IntKind::Char.rank()),
+        (Arg::WStr, _) => ty.pointee().is_some_and(|t| t.int_kind().is_some()),
+        (Arg::Pointer, _) => ty.is_pointer(),
+        (Arg::Count, _) => ty.pointee().and_then(Type::int_kind) == Some(IntKind::Int),
+        _ => false,
+    }
+}
+
+fn spell(a: Arg) -> &'static str {
+    match a {
+        Arg::Int(_, name) => name,
+        Arg::Float(k) => k.name(),
+        Arg::Str => "char *",
+        Arg::WStr => "wchar_t *",
+        Arg::Pointer => "void *",
+        Arg::Count => "int *",
+    }
+}
+
+// parses the conversions of a string literal at `pos`, maybe made of
+// adjacent literals
+fn parse(pos: usize, lit: &str, cx: &Context) -> Result<Vec<Spec>, (usize, String)> {
+    let dl = &cx.info.layout;
+    let mut specs = Vec::new();
+
+    // the characters inside the quotes and their offsets in the literal
+    let mut chars = Vec::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in lit.char_indices() {
+        match c {
+            '"' if !escaped => quoted = !quoted,
+            _ if quoted => {
+                escaped = c == '\\' && !escaped;
+                chars.push((i, c));
+            }
+            _ => {}
+        }
+    }
+
+    let mut it = chars.into_iter().peekable();
+    while let Some((i, c)) = it.next() {
+        if c != '%' {
+            continue;
+        }
+        let at = pos + i;
+
+        let mut next_if = |f: &dyn Fn(char) -> bool| it.next_if(|&(_, c)| f(c)).map(|(_, c)| c);
+
+        let mut stars = 0;
+        while next_if(&|c| "-+ #0'".contains(c)).is_some() {}
+
+        // the width and the precision
+        if next_if(&|c| c == '*').is_some() {
+            stars += 1;
+        }
+        while next_if(&|c| c.is_ascii_digit()).is_some() {}
+        if next_if(&|c| c == '.').is_some() {
+            if next_if(&|c| c == '*').is_some() {
+                stars += 1;
+            }
+            while next_if(&|c| c.is_ascii_digit()).is_some() {}
+        }
+
+        let mut length = String::new();
+        while let Some(c) = next_if(&|c| "hljztL".contains(c)) {
+            length.push(c);
+        }
+
+        let Some(conv) = next_if(&|_| true) else {
+            return Err((at, "incomplete format specifier".to_string()));
+        };
+
+        let pick = |s: (IntKind, &'static str), u: (IntKind, &'static str)| {
+            let (k, name) = if matches!(conv, 'd' | 'i') { s } else { u };
+            Some(Arg::Int(k, name))
+        };
+        let int = match length.as_str() {
+            "" | "hh" | "h" => pick((IntKind::Int, "int"), (IntKind::UInt, "unsigned int")),
+            "l" => pick((IntKind::Long, "long"), (IntKind::ULong, "unsigned long")),
+            "ll" => pick(
+                (IntKind::LongLong, "long long"),
+                (IntKind::ULongLong, "unsigned long long"),
+            ),
+            "j" => pick((IntKind::Long, "intmax_t"), (IntKind::ULong, "uintmax_t")),
+            "z" => pick((dl.size_t, "ssize_t"), (dl.size_t, "size_t")),
+            "t" => pick((dl.ptrdiff_t, "ptrdiff_t"), (dl.ptrdiff_t, "ptrdiff_t")),
+            _ => None,
+        };
+
+        let arg = match (conv, length.as_str()) {
+            ('%', "") => None,
+            ('d' | 'i' | 'o' | 'u' | 'x' | 'X', _) if int.is_some() => int,
+            ('c', "") => Some(Arg::Int(IntKind::Int, "int")),
+            ('s', "") => Some(Arg::Str),
+            ('s', "l") => Some(Arg::WStr),
+            ('p', "") => Some(Arg::Pointer),
+            ('n', "") => Some(Arg::Count),
+            ('f' | 'F' | 'e' | 'E' | 'g' | 'G' | 'a' | 'A', "" | "l") => {
+                Some(Arg::Float(FloatKind::Double))
+            }
+            ('f' | 'F' | 'e' | 'E' | 'g' | 'G' | 'a' | 'A', "L") => {
+                Some(Arg::Float(FloatKind::LongDouble))
+            }
+            _ if "%diouxXcspnfFeEgGaA".contains(conv) => {
+                let msg = format!(
+                    "length modifier '{}' results in undefined behavior or no effect with '{}' conversion specifier",
+                    length, conv
+                );
+                return Err((at, msg));
+            }
+            _ => {
+                let msg = format!("invalid conversion specifier '{}'", conv);
+                return Err((at, msg));
+            }
+        };
+
+        specs.push(Spec {
+            pos: at,
+            stars,
+            arg,
+        });
+    }
+
+    Ok(specs)
+}
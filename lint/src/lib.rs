@@ -0,0 +1,546 @@
+//! Lints: warnings about legal C that is likely a mistake, checked on a
+//! file after semantic analysis. Each lint has a name, like the `-W`
+//! flags of gcc, that turns it on or off and silences it in a comment:
+//!
+//! ```c
+//! if (n < len) // NOLINT(sign-compare)
+//! // NOLINTNEXTLINE
+//! while (c = next())
+//! ```
+
+use std::collections::HashMap;
+
+use ast::{
+    Decl, DeclKind, Designator, Expr, ExprKind, File, FuncDecl, GenDecl, InitDecl, Stmt, StmtKind,
+};
+use sema::consts::Eval;
+use sema::types::Type;
+use sema::Info;
+
+mod format;
+mod lints;
+
+/// A check run on every node of a file. The hooks do nothing by default,
+/// a lint implements the ones it needs.
+pub trait Lint {
+    /// The name that turns the lint on and off, e.g. `sign-compare`.
+    fn name(&self) -> &'static str;
+
+    /// Checks the whole file, after its nodes.
+    fn file(&self, _cx: &mut Context, _file: &File) {}
+
+    fn func(&self, _cx: &mut Context, _f: &FuncDecl) {}
+
+    /// Checks a declarator and its initializer.
+    fn decl(&self, _cx: &mut Context, _d: &InitDecl) {}
+
+    fn stmt(&self, _cx: &mut Context, _s: &dyn Stmt) {}
+
+    fn expr(&self, _cx: &mut Context, _x: &dyn Expr) {}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub pos: usize,
+    pub lint: &'static str,
+    pub msg: String,
+}
+
+/// What a lint sees of the file: the result of its semantic analysis and
+/// the function being checked.
+pub struct Context<'a> {
+    pub info: &'a Info,
+    pub func: Option<&'a FuncDecl>,
+
+    lint: &'static str,
+    warnings: Vec<Warning>,
+}
+
+impl Context<'_> {
+    pub fn warn(&mut self, pos: usize, msg: String) {
+        self.warnings.push(Warning {
+            pos,
+            lint: self.lint,
+            msg,
+        });
+    }
+
+    /// Returns the type of an expression after lvalue conversion, arrays
+    /// and functions decayed to pointers.
+    pub fn type_of(&self, x: &dyn Expr) -> Option<Type> {
+        self.info.type_of(x).map(Type::decay)
+    }
+
+    /// Returns the value of an integer constant expression, quietly.
+    pub fn constant(&self, x: &dyn Expr) -> Option<i128> {
+        Eval::new(self.info).int(x).ok()
+    }
+
+    /// Returns the return type of the function being checked.
+    pub fn ret(&self) -> Option<Type> {
+        let name = self.func?.declarator.name.as_ref()?;
+        let f = self.info.lookup(name.pos)?.ty.func()?;
+        Some(f.ret.clone())
+    }
+
+    pub fn spell(&self, t: &Type) -> String {
+        self.info.spell(t)
+    }
+}
+
+/// Runs a set of lints, each of them turned on or off.
+pub struct Linter {
+    lints: Vec<(Box<dyn Lint>, bool)>,
+}
+
+impl Default for Linter {
+    /// Returns a linter running all the built-in lints.
+    fn default() -> Self {
+        let mut l = Self::empty();
+        for lint in lints::all() {
+            l.register(lint);
+        }
+        l
+    }
+}
+
+impl Linter {
+    /// Returns a linter without lints.
+    pub fn empty() -> Self {
+        Self { lints: Vec::new() }
+    }
+
+    /// Adds a lint, turned on.
+    pub fn register(&mut self, lint: Box<dyn Lint>) {
+        self.lints.push((lint, true));
+    }
+
+    /// Returns the names of the lints in the order they were added.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.lints.iter().map(|(l, _)| l.name())
+    }
+
+    /// Turns a lint on or off, reports whether it exists.
+    pub fn set(&mut self, name: &str, on: bool) -> bool {
+        let mut found = false;
+
+        for (l, enabled) in self.lints.iter_mut() {
+            if l.name() == name {
+                *enabled = on;
+                found = true;
+            }
+        }
+
+        found
+    }
+
+    pub fn enabled(&self, name: &str) -> bool {
+        self.lints.iter().any(|(l, on)| *on && l.name() == name)
+    }
+
+    /// Checks a file resolved into `info`. `line` maps a position to its
+    /// line, to find the suppression comments. The warnings are in source
+    /// order.
+    pub fn run(&self, file: &File, info: &Info, line: impl Fn(usize) -> usize) -> Vec<Warning> {
+        let lints: Vec<_> = self
+            .lints
+            .iter()
+            .filter(|(_, on)| *on)
+            .map(|(l, _)| l.as_ref())
+            .collect();
+
+        let mut w = Walker {
+            lints: &lints,
+            cx: Context {
+                info,
+                func: None,
+                lint: "",
+                warnings: Vec::new(),
+            },
+        };
+
+        for d in file.decls.iter() {
+            w.decl(d.as_ref());
+        }
+        w.each(|l, cx| l.file(cx, file));
+
+        let mut warnings = w.cx.warnings;
+        warnings.sort_by_key(|w| w.pos);
+
+        let suppressed = suppressions(file, &line);
+        warnings.retain(|w| {
+            !suppressed
+                .get(&line(w.pos))
+                .is_some_and(|names| names.iter().any(|n| n == "*" || n == w.lint))
+        });
+
+        warnings
+    }
+}
+
+// the lints silenced on each line, `*` for all of them: `NOLINT` silences
+// the line of the comment and `NOLINTNEXTLINE` the next one, all the lints
+// or the ones listed in parentheses
+fn suppressions(file: &File, line: &impl Fn(usize) -> usize) -> HashMap<usize, Vec<String>> {
+    let mut lines: HashMap<usize, Vec<String>> = HashMap::new();
+
+    for c in file.comments.iter() {
+        let text = c.text.trim_start_matches(['/', '*']).trim_start();
+
+        let (next, rest) = if let Some(rest) = text.strip_prefix("NOLINTNEXTLINE") {
+            (true, rest)
+        } else if let Some(rest) = text.strip_prefix("NOLINT") {
+            (false, rest)
+        } else {
+            continue;
+        };
+
+        let names: Vec<String> = rest
+            .strip_prefix('(')
+            .and_then(|r| r.split_once(')'))
+            .map(|(names, _)| names.split(',').map(|n| n.trim().to_string()).collect())
+            .unwrap_or_default();
+
+        let names = if names.is_empty() {
+            vec!["*".to_string()]
+        } else {
+            names
+        };
+        lines
+            .entry(line(c.pos) + usize::from(next))
+            .or_default()
+            .extend(names);
+    }
+
+    lines
+}
+
+// walks a file and calls the hooks of the lints on each node
+struct Walker<'a, 'l> {
+    lints: &'l [&'l dyn Lint],
+    cx: Context<'a>,
+}
+
+impl<'a> Walker<'a, '_> {
+    fn each(&mut self, f: impl Fn(&dyn Lint, &mut Context<'a>)) {
+        for l in self.lints.iter() {
+            self.cx.lint = l.name();
+            f(*l, &mut self.cx);
+        }
+    }
+
+    fn decl(&mut self, d: &'a dyn Decl) {
+        match d.kind() {
+            DeclKind::GenDecl(d) => self.gen_decl(d),
+            DeclKind::FuncDecl(f) => {
+                let outer = self.cx.func.replace(f);
+                self.each(|l, cx| l.func(cx, f));
+
+                for s in f.body.stmts.iter() {
+                    self.stmt(s.as_ref());
+                }
+
+                self.cx.func = outer;
+            }
+            DeclKind::StaticAssert(s) => self.expr(s.cond.as_ref()),
+        }
+    }
+
+    fn gen_decl(&mut self, d: &'a GenDecl) {
+        for init in d.decls.iter() {
+            self.each(|l, cx| l.decl(cx, init));
+
+            if let Some(x) = &init.init {
+                self.expr(x.as_ref());
+            }
+        }
+    }
+
+    fn stmt(&mut self, s: &'a dyn Stmt) {
+        self.each(|l, cx| l.stmt(cx, s));
+
+        match s.kind() {
+            StmtKind::SemiColonStmt(_) | StmtKind::BreakStmt(_) | StmtKind::ContinueStmt(_) => {}
+            StmtKind::GotoStmt(_) => {}
+
+            StmtKind::ExprStmt(s) => self.expr(s.x.as_ref()),
+            StmtKind::DeclStmt(s) => self.gen_decl(&s.decl),
+            StmtKind::StaticAssert(s) => self.expr(s.cond.as_ref()),
+
+            StmtKind::ReturnStmt(s) => {
+                if let Some(x) = &s.value {
+                    self.expr(x.as_ref());
+                }
+            }
+
+            StmtKind::IfStmt(s) => {
+                self.expr(s.cond.as_ref());
+                self.stmt(s.init.as_ref());
+
+                for e in s.elifs.iter() {
+                    self.expr(e.cond.as_ref());
+                    self.stmt(e.init.as_ref());
+                }
+                if let Some(e) = &s._else {
+                    self.stmt(e.init.as_ref());
+                }
+            }
+
+            StmtKind::BlockStmt(b) => {
+                for s in b.stmts.iter() {
+                    self.stmt(s.as_ref());
+                }
+            }
+
+            StmtKind::WhileStmt(s) => {
+                self.expr(s.cond.as_ref());
+                self.stmt(s.init.as_ref());
+            }
+
+            StmtKind::DowhileStmt(s) => {
+                self.stmt(s.init.as_ref());
+                self.expr(s.cond.as_ref());
+            }
+
+            StmtKind::ForStmt(s) => {
+                if let Some(init) = &s.init {
+                    self.stmt(init.as_ref());
+                }
+                for x in [&s.cond, &s.post].into_iter().flatten() {
+                    self.expr(x.as_ref());
+                }
+                self.stmt(s.body.as_ref());
+            }
+
+            StmtKind::SwitchStmt(s) => {
+                self.expr(s.tag.as_ref());
+                self.stmt(s.body.as_ref());
+            }
+
+            StmtKind::CaseStmt(s) => self.stmt(s.stmt.as_ref()),
+            StmtKind::LabeledStmt(s) => self.stmt(s.stmt.as_ref()),
+        }
+    }
+
+    fn expr(&mut self, x: &'a dyn Expr) {
+        self.each(|l, cx| l.expr(cx, x));
+
+        match x.kind() {
+            ExprKind::BasicLit(_) | ExprKind::Ident(_) | ExprKind::OffsetofExpr(_) => {}
+
+            // the operand of sizeof is not evaluated
+            ExprKind::SizeofExpr(_) => {}
+
+            ExprKind::UnaryExpr(e) => self.expr(e.x.as_ref()),
+            ExprKind::StarExpr(e) => self.expr(e.x.as_ref()),
+            ExprKind::ParenExpr(e) => self.expr(e.x.as_ref()),
+            ExprKind::PostfixExpr(e) => self.expr(e.x.as_ref()),
+            ExprKind::SelectorExpr(e) => self.expr(e.x.as_ref()),
+            ExprKind::CastExpr(e) => self.expr(e.x.as_ref()),
+
+            ExprKind::InfixExpr(e) => {
+                self.expr(e.x.as_ref());
+                self.expr(e.y.as_ref());
+            }
+
+            ExprKind::AssignExpr(e) => {
+                self.expr(e.x.as_ref());
+                self.expr(e.y.as_ref());
+            }
+
+            ExprKind::CondExpr(e) => {
+                self.expr(e.cond.as_ref());
+                self.expr(e.x.as_ref());
+                self.expr(e.y.as_ref());
+            }
+
+            ExprKind::CallExpr(e) => {
+                self.expr(e.fun.as_ref());
+                for a in e.args.iter() {
+                    self.expr(a.as_ref());
+                }
+            }
+
+            ExprKind::IndexExpr(e) => {
+                self.expr(e.x.as_ref());
+                self.expr(e.index.as_ref());
+            }
+
+            ExprKind::CompositeLit(e) => {
+                for e in e.init.elems.iter() {
+                    self.initializer(&e.designators, e.value.as_ref());
+                }
+            }
+
+            ExprKind::InitList(l) => {
+                for e in l.elems.iter() {
+                    self.initializer(&e.designators, e.value.as_ref());
+                }
+            }
+        }
+    }
+
+    fn initializer(&mut self, designators: &'a [Designator], value: &'a dyn Expr) {
+        for d in designators.iter() {
+            if let Designator::Index { index, .. } = d {
+                self.expr(index.as_ref());
+            }
+        }
+        self.expr(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // lints a file with all the lints but the `off` ones, the warnings are
+    // formatted as `line:column: message [-Wname]`
+    fn lint(src: &str, off: &[&str]) -> Vec<String> {
+        let mut p = parser::Parser::from(src.to_string());
+        let file = p.parse_file();
+        assert!(p.errors().is_empty(), "{:?}", p.errors());
+
+        let info = sema::check(&file);
+        assert_eq!(0, info.errors().count(), "{:?}", info.diagnostics);
+
+        let mut linter = Linter::default();
+        for name in off {
+            assert!(linter.set(name, false), "{}", name);
+        }
+
+        linter
+            .run(&file, &info, |pos| p.position(pos).line)
+            .iter()
+            .map(|w| {
+                let pos = p.position(w.pos);
+                format!("{}:{}: {} [-W{}]", pos.line, pos.column, w.msg, w.lint)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lints() {
+        let tests = [
+            (
+                "int f(int x) { if (x = 0) return x; while ((x = 1)) ; return x == 1 ? 0 : (x = 2) ? x : 2; }",
+                vec![
+                    "1:22: using the result of an assignment as a condition without parentheses [-Wparentheses]",
+                ],
+            ),
+            (
+                "int f(int i, unsigned n, long l, unsigned char c) { return (i < n) + (l < n) + (c < n) + (0 < n) + (i == sizeof i) + (-1 < n); }",
+                vec![
+                    "1:63: comparison of integers of different signs: 'int' and 'unsigned int' [-Wsign-compare]",
+                    "1:103: comparison of integers of different signs: 'int' and 'unsigned long' [-Wsign-compare]",
+                    "1:122: comparison of integers of different signs: 'int' and 'unsigned int' [-Wsign-compare]",
+                ],
+            ),
+            (
+                "long f(int x, long l) { x <<= 32; return (x << -1) + (l >> 40) + (x >> 31) + ((char)x << 31); }",
+                vec![
+                    "1:31: shift count >= width of type [-Wshift-count]",
+                    "1:48: shift count is negative [-Wshift-count]",
+                ],
+            ),
+            (
+                "void g(short s);\nchar f(long l, double d, float x) { int i = l; char c = 'a'; short s = 70000; x = d; x = 1.5; i = d; g(i); g(1); return i; }",
+                vec![
+                    "2:45: implicit conversion loses integer precision: 'long' to 'int' [-Wconversion]",
+                    "2:53: unused variable 'c' [-Wunused-variable]",
+                    "2:68: unused variable 's' [-Wunused-variable]",
+                    "2:72: implicit conversion loses integer precision: 'int' to 'short' [-Wconversion]",
+                    "2:83: implicit conversion loses floating-point precision: 'double' to 'float' [-Wconversion]",
+                    "2:99: implicit conversion turns floating-point number into integer: 'double' to 'int' [-Wconversion]",
+                    "2:104: implicit conversion loses integer precision: 'int' to 'short' [-Wconversion]",
+                    "2:121: implicit conversion loses integer precision: 'int' to 'char' [-Wconversion]",
+                ],
+            ),
+            (
+                "int g;\nint f(int a, int b, int c) { int x, y = 1; extern int z; static int s; (void)b; return y + g; }\nint h(int unused);",
+                vec![
+                    "2:11: unused parameter 'a' [-Wunused-parameter]",
+                    "2:25: unused parameter 'c' [-Wunused-parameter]",
+                    "2:34: unused variable 'x' [-Wunused-variable]",
+                    "2:69: unused variable 's' [-Wunused-variable]",
+                ],
+            ),
+            (
+                "int printf(const char *fmt, ...);\nvoid f(int i, long l, double d, char *s, unsigned long z) {\nprintf(\"%d %ld %5.2f %s %%\", i, l, d, s);\nprintf(\"%d %s\", d, i);\nprintf(\"%lu %*d %c\\n\", z, i, 1, 'a');\nprintf(\"%d %d\", i);\nprintf(\"%d\", i, i);\nprintf(\"%y\", i);\nprintf(\"%\" \"ld\", i);\n}",
+                vec![
+                    "4:17: format specifies type 'int' but the argument has type 'double' [-Wformat]",
+                    "4:20: format specifies type 'char *' but the argument has type 'int' [-Wformat]",
+                    "6:12: more '%' conversions than data arguments [-Wformat]",
+                    "7:17: data argument not used by format string [-Wformat]",
+                    "8:9: invalid conversion specifier 'y' [-Wformat]",
+                    "9:18: format specifies type 'long' but the argument has type 'int' [-Wformat]",
+                ],
+            ),
+            (
+                "int f(int x, unsigned n) {\nif (x = 1) return x < n; // NOLINT\nif (x = 2) return x < n; // NOLINT(sign-compare)\n// NOLINTNEXTLINE(parentheses, sign-compare)\nif (x = 3) return x < n;\nreturn 0; }",
+                vec!["3:7: using the result of an assignment as a condition without parentheses [-Wparentheses]"],
+            ),
+        ];
+
+        for (i, (src, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                *want,
+                lint(src, &["uninitialized", "dead-store"]),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn test_flow() {
+        let src = "int f(int c) { int x, y; if (c) x = 1; y = c; y = 2; return x + y; }";
+
+        assert_eq!(
+            vec![
+                "1:40: value stored to 'y' is never read [-Wdead-store]",
+                "1:61: variable 'x' may be uninitialized when used here [-Wuninitialized]",
+            ],
+            lint(src, &[])
+        );
+    }
+
+    #[test]
+    fn test_toggle() {
+        let src = "int f(int x, unsigned n) { int y; if (x = 1) return x < n; return 0; }";
+
+        assert_eq!(
+            vec![
+                "1:32: unused variable 'y' [-Wunused-variable]",
+                "1:41: using the result of an assignment as a condition without parentheses [-Wparentheses]",
+            ],
+            lint(src, &["sign-compare"])
+        );
+        assert_eq!(
+            Vec::<String>::new(),
+            lint(src, &["sign-compare", "parentheses", "unused-variable"])
+        );
+
+        let mut linter = Linter::default();
+        assert!(!linter.set("no-such-lint", false));
+        assert!(linter.set("format", false));
+        assert!(!linter.enabled("format"));
+        assert!(linter.enabled("conversion"));
+        assert_eq!(
+            vec![
+                "parentheses",
+                "sign-compare",
+                "shift-count",
+                "conversion",
+                "unused-variable",
+                "unused-parameter",
+                "format",
+                "uninitialized",
+                "dead-store"
+            ],
+            linter.names().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,342 @@
+//! The built-in lints, named after the gcc warnings they mirror.
+
+use std::collections::HashSet;
+
+use ast::{Expr, ExprKind, File, FuncDecl, InitDecl, Stmt, StmtKind};
+use dataflow::ReportKind;
+use sema::types::{IntKind, Type, TypeKind};
+use sema::{Linkage, ScopeKind, SymbolKind};
+use token::Token;
+
+use crate::format::Format;
+use crate::{Context, Lint};
+
+pub(crate) fn all() -> Vec<Box<dyn Lint>> {
+    vec![
+        Box::new(Parentheses),
+        Box::new(SignCompare),
+        Box::new(ShiftCount),
+        Box::new(Conversion),
+        Box::new(Unused(SymbolKind::Var)),
+        Box::new(Unused(SymbolKind::Param)),
+        Box::new(Format),
+        Box::new(Flow(&[
+            ReportKind::Uninitialized,
+            ReportKind::MaybeUninitialized,
+        ])),
+        Box::new(Flow(&[ReportKind::DeadStore])),
+    ]
+}
+
+/// `if (x = 0)`: an assignment used as a condition, likely meant to be a
+/// comparison. Extra parentheses silence it.
+struct Parentheses;
+
+impl Parentheses {
+    fn cond(cx: &mut Context, x: &dyn Expr) {
+        if let ExprKind::AssignExpr(e) = x.kind() {
+            if e.op == Token::ASSIGN {
+                cx.warn(
+                    e.op_pos,
+                    "using the result of an assignment as a condition without parentheses"
+                        .to_string(),
+                );
+            }
+        }
+    }
+}
+
+impl Lint for Parentheses {
+    fn name(&self) -> &'static str {
+        "parentheses"
+    }
+
+    fn stmt(&self, cx: &mut Context, s: &dyn Stmt) {
+        match s.kind() {
+            StmtKind::IfStmt(s) => {
+                Self::cond(cx, s.cond.as_ref());
+                for e in s.elifs.iter() {
+                    Self::cond(cx, e.cond.as_ref());
+                }
+            }
+            StmtKind::WhileStmt(s) => Self::cond(cx, s.cond.as_ref()),
+            StmtKind::DowhileStmt(s) => Self::cond(cx, s.cond.as_ref()),
+            StmtKind::ForStmt(s) => {
+                if let Some(x) = &s.cond {
+                    Self::cond(cx, x.as_ref());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn expr(&self, cx: &mut Context, x: &dyn Expr) {
+        if let ExprKind::CondExpr(e) = x.kind() {
+            Self::cond(cx, e.cond.as_ref());
+        }
+    }
+}
+
+/// `i < n` with a signed `i` and an unsigned `n`: the negative values of
+/// `i` convert to large unsigned ones.
+struct SignCompare;
+
+impl Lint for SignCompare {
+    fn name(&self) -> &'static str {
+        "sign-compare"
+    }
+
+    fn expr(&self, cx: &mut Context, x: &dyn Expr) {
+        let ExprKind::InfixExpr(e) = x.kind() else {
+            return;
+        };
+        if !matches!(
+            e.op,
+            Token::LT | Token::GT | Token::LEQ | Token::GEQ | Token::EQL | Token::NEQ
+        ) {
+            return;
+        }
+
+        let (Some(a), Some(b)) = (cx.type_of(e.x.as_ref()), cx.type_of(e.y.as_ref())) else {
+            return;
+        };
+        // the types before promotion, an unsigned char is never negative
+        let (Some(x), Some(y)) = (a.int_kind(), b.int_kind()) else {
+            return;
+        };
+
+        let dl = &cx.info.layout;
+        if dl.is_signed(x) == dl.is_signed(y) {
+            return;
+        }
+
+        // the comparison is safe when the signed operand converts to a
+        // wider signed type, or is a constant that is not negative
+        let common = sema::types::usual_arithmetic(&a, &b, dl);
+        if common.int_kind().is_some_and(|k| dl.is_signed(k)) {
+            return;
+        }
+        let signed = if dl.is_signed(x) { &e.x } else { &e.y };
+        if cx.constant(signed.as_ref()).is_some_and(|v| v >= 0) {
+            return;
+        }
+
+        let msg = format!(
+            "comparison of integers of different signs: '{}' and '{}'",
+            cx.spell(&a),
+            cx.spell(&b)
+        );
+        cx.warn(e.op_pos, msg);
+    }
+}
+
+/// `x << 32` on an int: shifting by a negative count, or by the width of
+/// the promoted operand or more, is undefined.
+struct ShiftCount;
+
+impl Lint for ShiftCount {
+    fn name(&self) -> &'static str {
+        "shift-count"
+    }
+
+    fn expr(&self, cx: &mut Context, x: &dyn Expr) {
+        let (x, y) = match x.kind() {
+            ExprKind::InfixExpr(e) if matches!(e.op, Token::SHL | Token::SHR) => (&e.x, &e.y),
+            ExprKind::AssignExpr(e) if matches!(e.op, Token::SHL_ASSIGN | Token::SHR_ASSIGN) => {
+                (&e.x, &e.y)
+            }
+            _ => return,
+        };
+
+        let Some(count) = cx.constant(y.as_ref()) else {
+            return;
+        };
+        let Some(k) = cx.type_of(x.as_ref()).and_then(|t| t.promote().int_kind()) else {
+            return;
+        };
+
+        if count < 0 {
+            cx.warn(y.start(), "shift count is negative".to_string());
+        } else if count >= cx.info.layout.width(k) as i128 {
+            cx.warn(y.start(), "shift count >= width of type".to_string());
+        }
+    }
+}
+
+/// `int n = strlen(s)`: an implicit conversion that loses the high bits
+/// of an integer, the fraction of a floating value or its precision.
+struct Conversion;
+
+impl Conversion {
+    // checks the conversion of `x` to the type of what it is assigned to
+    fn check(cx: &mut Context, to: &Type, x: &dyn Expr) {
+        let Some(from) = cx.type_of(x) else {
+            return;
+        };
+        let dl = &cx.info.layout;
+
+        let msg = match (&from.kind, to.int_kind(), &to.kind) {
+            (_, Some(IntKind::Bool), _) => return,
+
+            (TypeKind::Float(_), Some(_), _) => {
+                "implicit conversion turns floating-point number into integer"
+            }
+
+            (TypeKind::Float(a), None, TypeKind::Float(b)) if a > b => {
+                // a constant rounds like any literal would
+                if matches!(strip_parens(x).kind(), ExprKind::BasicLit(_)) {
+                    return;
+                }
+                "implicit conversion loses floating-point precision"
+            }
+
+            (TypeKind::Int(_) | TypeKind::Enum(_), Some(k), _) => {
+                let a = from.int_kind().unwrap();
+                if dl.width(a) <= dl.width(k) {
+                    return;
+                }
+
+                // constants that fit are fine, like `char c = 'a'`
+                if let Some(v) = cx.constant(x) {
+                    if v >= dl.min(k) && v <= dl.max(k) as i128 {
+                        return;
+                    }
+                }
+                "implicit conversion loses integer precision"
+            }
+
+            _ => return,
+        };
+
+        let msg = format!(
+            "{}: '{}' to '{}'",
+            msg,
+            cx.spell(&from),
+            cx.spell(&to.unqualified())
+        );
+        cx.warn(x.start(), msg);
+    }
+}
+
+impl Lint for Conversion {
+    fn name(&self) -> &'static str {
+        "conversion"
+    }
+
+    fn decl(&self, cx: &mut Context, d: &InitDecl) {
+        let (Some(name), Some(x)) = (&d.declarator.name, &d.init) else {
+            return;
+        };
+        if matches!(x.kind(), ExprKind::InitList(_)) {
+            return;
+        }
+
+        if let Some(s) = cx.info.lookup(name.pos) {
+            if s.kind == SymbolKind::Var {
+                let to = s.ty.clone();
+                Self::check(cx, &to, x.as_ref());
+            }
+        }
+    }
+
+    fn stmt(&self, cx: &mut Context, s: &dyn Stmt) {
+        let StmtKind::ReturnStmt(s) = s.kind() else {
+            return;
+        };
+
+        if let (Some(x), Some(ret)) = (&s.value, cx.ret()) {
+            Self::check(cx, &ret, x.as_ref());
+        }
+    }
+
+    fn expr(&self, cx: &mut Context, x: &dyn Expr) {
+        match x.kind() {
+            ExprKind::AssignExpr(e) if e.op == Token::ASSIGN => {
+                if let Some(to) = cx.info.type_of(e.x.as_ref()).cloned() {
+                    Self::check(cx, &to, e.y.as_ref());
+                }
+            }
+
+            ExprKind::CallExpr(e) => {
+                let f = cx
+                    .type_of(e.fun.as_ref())
+                    .and_then(|t| t.pointee().and_then(|t| t.func()).cloned());
+                let Some(f) = f.filter(|f| f.prototype) else {
+                    return;
+                };
+
+                for (param, arg) in f.params.iter().zip(e.args.iter()) {
+                    Self::check(cx, param, arg.as_ref());
+                }
+            }
+
+            _ => {}
+        }
+    }
+}
+
+/// A local variable or a parameter that is never referred to.
+struct Unused(SymbolKind); // Var or Param
+
+impl Lint for Unused {
+    fn name(&self) -> &'static str {
+        match self.0 {
+            SymbolKind::Param => "unused-parameter",
+            _ => "unused-variable",
+        }
+    }
+
+    fn file(&self, cx: &mut Context, _file: &File) {
+        let info = cx.info;
+        let used: HashSet<_> = info.uses.values().collect();
+
+        let mut unused: Vec<_> = info
+            .decls
+            .iter()
+            .filter(|(_, id)| !used.contains(id))
+            .map(|(_, &id)| info.symbol(id))
+            .filter(|s| s.kind == self.0 && s.linkage == Linkage::None)
+            // the parameters of a definition are in the scope of its body,
+            // the ones of a prototype are not used anyway
+            .filter(|s| info.scope(s.scope).kind == ScopeKind::Block)
+            .collect();
+        unused.sort_by_key(|s| s.pos);
+        unused.dedup_by_key(|s| s.pos);
+
+        let what = match self.0 {
+            SymbolKind::Param => "parameter",
+            _ => "variable",
+        };
+        for s in unused {
+            cx.warn(s.pos, format!("unused {} '{}'", what, s.name));
+        }
+    }
+}
+
+/// The reports of the data-flow checks: reads of variables before they
+/// are assigned, and stores that are never read.
+struct Flow(&'static [ReportKind]);
+
+impl Lint for Flow {
+    fn name(&self) -> &'static str {
+        match self.0 {
+            [ReportKind::DeadStore] => "dead-store",
+            _ => "uninitialized",
+        }
+    }
+
+    fn func(&self, cx: &mut Context, f: &FuncDecl) {
+        for r in dataflow::check(f, cx.info) {
+            if self.0.contains(&r.kind) {
+                cx.warn(r.pos, r.msg);
+            }
+        }
+    }
+}
+
+pub(crate) fn strip_parens(mut x: &dyn Expr) -> &dyn Expr {
+    while let ExprKind::ParenExpr(p) = x.kind() {
+        x = p.x.as_ref();
+    }
+    x
+}
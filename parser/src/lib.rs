@@ -1,7 +1,7 @@
-use std::usize;
+use std::collections::HashMap;
 
 use scanner::Scanner;
-use token::Token;
+use token::{Position, Token};
 
 #[derive(Default, Debug)]
 pub struct Parser {
@@ -10,313 +10,1552 @@ pub struct Parser {
     tok: Token,
     pos: usize,
     lit: String,
+    prev_end: usize, // position just past the previous token
+
+    peeked: Option<(Token, usize, String)>, // one token of lookahead
 
     errors: Vec<(usize, String)>,
+    comments: Vec<ast::Comment>,
+
+    // the ordinary identifiers declared in each open scope, true for typedef
+    // names: `T * x;` declares x when T names a type
+    scopes: Vec<HashMap<String, bool>>,
+}
+
+impl From<String> for Parser {
+    fn from(src: String) -> Self {
+        Self::from(Scanner::from(src))
+    }
+}
+
+impl From<Scanner> for Parser {
+    fn from(scanner: Scanner) -> Self {
+        let mut p = Self {
+            scanner,
+            scopes: vec![HashMap::new()],
+            ..Self::default()
+        };
+        p.next();
+        p
+    }
 }
 
-impl From<String> for Parser {
-    fn from(src: String) -> Self {
-        Self::from(Scanner::from(src))
+impl Parser {
+    // reads a token, recording the comments and errors before it
+    fn scan(&mut self) -> (Token, usize, String) {
+        loop {
+            match self.scanner.scan() {
+                Ok((Token::COMMENT, pos, lit)) => {
+                    let text = lit.to_string();
+
+                    // the current token is still the one before the comment,
+                    // no token has been read while it is ILLEGAL
+                    let trailing = self.tok != Token::ILLEGAL
+                        && self.scanner.position(self.pos).line == self.scanner.position(pos).line;
+
+                    self.comments.push(ast::Comment {
+                        pos,
+                        text,
+                        trailing,
+                    });
+                }
+
+                Ok((tok, pos, lit)) => return (tok, pos, lit.to_string()),
+
+                Err((_, pos, _, msg)) => {
+                    self.errors.push((pos, msg));
+                }
+            }
+        }
+    }
+
+    fn next(&mut self) {
+        let (tok, pos, lit) = match self.peeked.take() {
+            Some(t) => t,
+            None => self.scan(),
+        };
+
+        if self.tok != Token::ILLEGAL {
+            self.prev_end = self.pos + self.lit.len();
+        }
+
+        self.tok = tok;
+        self.pos = pos;
+        self.lit = lit;
+    }
+
+    // returns the token after the current one
+    fn peek(&mut self) -> (Token, &str) {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.scan());
+        }
+
+        let (tok, _, lit) = self.peeked.as_ref().unwrap();
+        (*tok, lit)
+    }
+
+    fn expect(&mut self, t: Token) {
+        if self.tok == t {
+            self.next();
+        } else {
+            self.error_expected(&t.to_str());
+        }
+    }
+
+    fn expect2(&mut self, look_ahead: Token) -> Option<(Token, usize, String)> {
+        if self.tok == look_ahead {
+            let (tok, pos, lit) = (self.tok, self.pos, self.lit.clone());
+            self.next();
+            Some((tok, pos, lit))
+        } else {
+            // while (34535 == 4 { 2 = 12; }
+            // expected ')' got 'INTEGER'
+            self.error_expected(&look_ahead.to_str());
+
+            None
+        }
+    }
+
+    fn error_expected(&mut self, what: &str) {
+        let msg = if self.tok.is_literal() {
+            format!("expected {}, got '{}'", what, self.lit)
+        } else {
+            format!("expected {}, got {}", what, self.tok.to_str())
+        };
+
+        self.errors.push((self.pos, msg));
+    }
+
+    pub fn errors(&self) -> &[(usize, String)] {
+        &self.errors
+    }
+
+    /// Returns the position of the current token.
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// See `Scanner::position`.
+    pub fn position(&self, offset: usize) -> Position {
+        self.scanner.position(offset)
+    }
+
+    fn open_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn close_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &ast::Ident, typedef: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.name.clone(), typedef);
+        }
+    }
+
+    fn is_typedef(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|s| s.get(name))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    // reports whether a token starts declaration specifiers
+    fn starts_decl(&self, tok: Token, lit: &str) -> bool {
+        match tok {
+            Token::TYPEDEF
+            | Token::EXTERN
+            | Token::STATIC
+            | Token::AUTO
+            | Token::REGISTER
+            | Token::INLINE => true,
+            Token::IDENT => self.is_typedef(lit),
+            _ => starts_type(tok),
+        }
+    }
+
+    // reports whether the token after the current one starts a type name
+    fn peek_type_name(&mut self) -> bool {
+        let (tok, lit) = self.peek();
+        let lit = lit.to_string();
+
+        match tok {
+            Token::IDENT => self.is_typedef(&lit),
+            _ => starts_type(tok),
+        }
+    }
+
+    pub fn parse_file(&mut self) -> ast::File {
+        let mut decls = Vec::new();
+
+        while self.tok != Token::EOF {
+            let pos = self.pos;
+
+            match self.parse_decl() {
+                Some(d) => decls.push(d),
+                None => {
+                    self.error_expected("declaration");
+
+                    // skip the offending token
+                    if self.pos == pos {
+                        self.next();
+                    }
+                }
+            }
+        }
+
+        ast::File {
+            decls,
+            comments: std::mem::take(&mut self.comments),
+            eof: self.pos,
+        }
+    }
+
+    /// Parses an external declaration: a declaration or a function
+    /// definition.
+    pub fn parse_decl(&mut self) -> Option<Box<dyn ast::Decl>> {
+        if self.tok == Token::STATIC_ASSERT {
+            return Some(Box::new(self.parse_static_assert()?));
+        }
+
+        if !self.starts_decl(self.tok, &self.lit) {
+            return None;
+        }
+
+        let specs = self.parse_decl_spec();
+
+        if self.tok == Token::SEMICOLON {
+            return Some(Box::new(self.parse_gen_decl_rest(specs, None)));
+        }
+
+        let declarator = self.parse_declarator();
+
+        if declarator.function().is_some() && self.tok == Token::LBRACE {
+            return Some(Box::new(self.parse_func_decl(specs, declarator)));
+        }
+
+        Some(Box::new(self.parse_gen_decl_rest(specs, Some(declarator))))
+    }
+
+    fn parse_func_decl(
+        &mut self,
+        specs: ast::DeclSpec,
+        declarator: ast::Declarator,
+    ) -> ast::FuncDecl {
+        if let Some(name) = &declarator.name {
+            self.declare(name, false);
+        }
+
+        // the parameters are visible in the body
+        self.open_scope();
+        if let Some(f) = declarator.function() {
+            for p in f.params.iter() {
+                if let Some(name) = &p.declarator.name {
+                    self.scopes
+                        .last_mut()
+                        .unwrap()
+                        .insert(name.name.clone(), false);
+                }
+            }
+        }
+
+        let body = self.parse_block_stmt();
+        self.close_scope();
+
+        ast::FuncDecl {
+            specs,
+            declarator,
+            body,
+        }
+    }
+
+    // parses the declarators of a declaration after its specifiers, `first`
+    // is the first declarator when it is already parsed
+    fn parse_gen_decl_rest(
+        &mut self,
+        specs: ast::DeclSpec,
+        mut first: Option<ast::Declarator>,
+    ) -> ast::GenDecl {
+        let typedef = specs.storage == Some(Token::TYPEDEF);
+        let mut decls = Vec::new();
+
+        if first.is_some() || self.tok != Token::SEMICOLON {
+            loop {
+                let declarator = first.take().unwrap_or_else(|| self.parse_declarator());
+
+                match &declarator.name {
+                    Some(name) => self.declare(name, typedef),
+                    None => self.error_expected("identifier"),
+                }
+
+                let init = if self.tok == Token::ASSIGN {
+                    self.next();
+                    self.parse_initializer()
+                } else {
+                    None
+                };
+
+                decls.push(ast::InitDecl { declarator, init });
+
+                if self.tok != Token::COMMA {
+                    break;
+                }
+                self.next();
+            }
+        }
+
+        let semi = self.pos;
+        self.expect(Token::SEMICOLON);
+
+        ast::GenDecl { specs, decls, semi }
+    }
+
+    // _Static_assert(constant-expression, string-literal);
+    fn parse_static_assert(&mut self) -> Option<ast::StaticAssert> {
+        let pos = self.pos;
+        self.next();
+
+        self.expect2(Token::LPAREN)?;
+        let cond = self.parse_operand_or_error(Self::parse_cond_expr)?;
+        self.expect2(Token::COMMA)?;
+
+        if self.tok != Token::STRING {
+            self.error_expected("string literal");
+            return None;
+        }
+        let msg = self.parse_basic_lit();
+        self.expect2(Token::RPAREN)?;
+
+        let semi = self.pos;
+        self.expect(Token::SEMICOLON);
+
+        Some(ast::StaticAssert {
+            pos,
+            cond,
+            msg,
+            semi,
+        })
+    }
+
+    fn parse_decl_spec(&mut self) -> ast::DeclSpec {
+        let pos = self.pos;
+
+        let mut storage = None;
+        let mut quals = Vec::new();
+        let mut inline = false;
+        let mut basic = Vec::new();
+        let mut typ = None;
+
+        loop {
+            match self.tok {
+                Token::TYPEDEF | Token::EXTERN | Token::STATIC | Token::AUTO | Token::REGISTER => {
+                    if storage.is_some() {
+                        self.errors.push((
+                            self.pos,
+                            "multiple storage classes in declaration specifiers".to_string(),
+                        ));
+                    }
+                    storage = Some(self.tok);
+                    self.next();
+                }
+
+                Token::CONST | Token::VOLATILE | Token::RESTRICT => {
+                    quals.push(self.tok);
+                    self.next();
+                }
+
+                Token::INLINE => {
+                    inline = true;
+                    self.next();
+                }
+
+                Token::STRUCT | Token::UNION | Token::ENUM => {
+                    if typ.is_some() || !basic.is_empty() {
+                        self.errors.push((
+                            self.pos,
+                            "two or more data types in declaration specifiers".to_string(),
+                        ));
+                    }
+
+                    typ = Some(if self.tok == Token::ENUM {
+                        ast::TypeSpec::Enum(self.parse_enum_spec())
+                    } else {
+                        ast::TypeSpec::Struct(self.parse_struct_spec())
+                    });
+                }
+
+                Token::IDENT if typ.is_none() && basic.is_empty() && self.is_typedef(&self.lit) => {
+                    typ = Some(ast::TypeSpec::Typedef(self.parse_ident()));
+                }
+
+                tok if starts_type(tok) => {
+                    if typ.is_some() {
+                        self.errors.push((
+                            self.pos,
+                            "two or more data types in declaration specifiers".to_string(),
+                        ));
+                    }
+                    basic.push(tok);
+                    self.next();
+                }
+
+                _ => break,
+            }
+        }
+
+        if typ.is_none() && basic.is_empty() {
+            self.error_expected("type specifier");
+        }
+
+        ast::DeclSpec {
+            pos,
+            storage,
+            quals,
+            inline,
+            typ: typ.unwrap_or(ast::TypeSpec::Basic(basic)),
+            end: self.prev_end,
+        }
+    }
+
+    fn parse_struct_spec(&mut self) -> ast::StructSpec {
+        let pos = self.pos;
+        let kw = self.tok;
+        self.next();
+
+        let tag = self.parse_tag();
+
+        let fields = if self.tok == Token::LBRACE {
+            self.next();
+
+            let mut fields = Vec::new();
+
+            while self.tok != Token::RBRACE && self.tok != Token::EOF {
+                if !self.starts_decl(self.tok, &self.lit) {
+                    self.error_expected("field declaration");
+                    self.skip_past(Token::SEMICOLON);
+                    continue;
+                }
+
+                fields.push(self.parse_field_decl());
+            }
+
+            self.expect(Token::RBRACE);
+            Some(fields)
+        } else {
+            None
+        };
+
+        if tag.is_none() && fields.is_none() {
+            self.error_expected("identifier or '{'");
+        }
+
+        ast::StructSpec {
+            pos,
+            kw,
+            tag,
+            fields,
+            end: self.prev_end,
+        }
+    }
+
+    fn parse_field_decl(&mut self) -> ast::FieldDecl {
+        let specs = self.parse_decl_spec();
+        let mut decls = Vec::new();
+
+        while self.tok != Token::SEMICOLON {
+            let declarator = if self.tok == Token::COLON {
+                self.empty_declarator()
+            } else {
+                self.parse_declarator()
+            };
+
+            let bits = if self.tok == Token::COLON {
+                self.next();
+                self.parse_cond_expr()
+            } else {
+                None
+            };
+
+            decls.push(ast::FieldDeclarator { declarator, bits });
+
+            if self.tok != Token::COMMA {
+                break;
+            }
+            self.next();
+        }
+
+        let semi = self.pos;
+        self.expect(Token::SEMICOLON);
+
+        ast::FieldDecl { specs, decls, semi }
+    }
+
+    fn parse_enum_spec(&mut self) -> ast::EnumSpec {
+        let pos = self.pos;
+        self.next();
+
+        let tag = self.parse_tag();
+
+        let enumerators = if self.tok == Token::LBRACE {
+            self.next();
+
+            let mut enumerators = Vec::new();
+
+            while self.tok == Token::IDENT {
+                let name = self.parse_ident();
+                self.declare(&name, false);
+
+                let value = if self.tok == Token::ASSIGN {
+                    self.next();
+                    self.parse_cond_expr()
+                } else {
+                    None
+                };
+
+                enumerators.push(ast::Enumerator { name, value });
+
+                if self.tok != Token::COMMA {
+                    break;
+                }
+                self.next();
+            }
+
+            self.expect(Token::RBRACE);
+            Some(enumerators)
+        } else {
+            None
+        };
+
+        if tag.is_none() && enumerators.is_none() {
+            self.error_expected("identifier or '{'");
+        }
+
+        ast::EnumSpec {
+            pos,
+            tag,
+            enumerators,
+            end: self.prev_end,
+        }
+    }
+
+    fn parse_tag(&mut self) -> Option<ast::Ident> {
+        if self.tok == Token::IDENT {
+            Some(self.parse_ident())
+        } else {
+            None
+        }
+    }
+
+    fn empty_declarator(&self) -> ast::Declarator {
+        ast::Declarator {
+            pos: self.pos,
+            name: None,
+            derived: Vec::new(),
+            end: self.pos,
+        }
+    }
+
+    /// Parses a declarator, with or without a name.
+    fn parse_declarator(&mut self) -> ast::Declarator {
+        let pos = self.pos;
+
+        let mut pointers = Vec::new();
+        while self.tok == Token::ASTERISK {
+            let pos = self.pos;
+            self.next();
+
+            let mut quals = Vec::new();
+            while matches!(self.tok, Token::CONST | Token::VOLATILE | Token::RESTRICT) {
+                quals.push(self.tok);
+                self.next();
+            }
+
+            pointers.push(ast::Derived::Pointer { pos, quals });
+        }
+
+        let tok = self.tok;
+        let (name, mut derived) = match tok {
+            Token::IDENT => (Some(self.parse_ident()), Vec::new()),
+
+            // a parenthesized declarator, not a parameter list
+            Token::LPAREN if self.peek_nested_declarator() => {
+                self.next();
+                let inner = self.parse_declarator();
+                self.expect(Token::RPAREN);
+
+                (inner.name, inner.derived)
+            }
+
+            _ => (None, Vec::new()),
+        };
+
+        loop {
+            match self.tok {
+                Token::LBRACK => {
+                    let lbrack = self.pos;
+                    self.next();
+
+                    // array parameters may have qualifiers and static
+                    while matches!(
+                        self.tok,
+                        Token::CONST | Token::VOLATILE | Token::RESTRICT | Token::STATIC
+                    ) {
+                        self.next();
+                    }
+
+                    let len = if self.tok == Token::RBRACK {
+                        None
+                    } else {
+                        self.parse_assign_expr()
+                    };
+
+                    let rbrack = self.pos;
+                    self.expect(Token::RBRACK);
+
+                    derived.push(ast::Derived::Array {
+                        lbrack,
+                        len,
+                        rbrack,
+                    });
+                }
+
+                Token::LPAREN => derived.push(ast::Derived::Function(self.parse_params())),
+
+                _ => break,
+            }
+        }
+
+        derived.extend(pointers.into_iter().rev());
+
+        ast::Declarator {
+            pos,
+            name,
+            derived,
+            end: self.prev_end.max(pos),
+        }
+    }
+
+    fn peek_nested_declarator(&mut self) -> bool {
+        let (tok, lit) = self.peek();
+        let lit = lit.to_string();
+
+        match tok {
+            Token::ASTERISK | Token::LPAREN | Token::LBRACK => true,
+            Token::IDENT => !self.is_typedef(&lit),
+            _ => false,
+        }
+    }
+
+    fn parse_params(&mut self) -> ast::FuncType {
+        let lparen = self.pos;
+        self.next();
+
+        let mut params = Vec::new();
+        let mut variadic = false;
+
+        while self.tok != Token::RPAREN && self.tok != Token::EOF {
+            if self.tok == Token::ELLIPSE {
+                variadic = true;
+                self.next();
+                break;
+            }
+
+            if !self.starts_decl(self.tok, &self.lit) {
+                self.error_expected("parameter declaration");
+                break;
+            }
+
+            let specs = self.parse_decl_spec();
+            let declarator = self.parse_declarator();
+
+            params.push(ast::ParamDecl { specs, declarator });
+
+            if self.tok != Token::COMMA {
+                break;
+            }
+            self.next();
+        }
+
+        let rparen = self.pos;
+        self.expect(Token::RPAREN);
+
+        ast::FuncType {
+            lparen,
+            params,
+            variadic,
+            rparen,
+        }
+    }
+
+    fn parse_type_name(&mut self) -> ast::TypeName {
+        let specs = self.parse_decl_spec();
+        let declarator = self.parse_declarator();
+
+        if let Some(name) = &declarator.name {
+            self.errors.push((
+                name.pos,
+                format!("unexpected identifier '{}' in type name", name.name),
+            ));
+        }
+
+        ast::TypeName { specs, declarator }
+    }
+
+    fn parse_initializer(&mut self) -> Option<Box<dyn ast::Expr>> {
+        if self.tok == Token::LBRACE {
+            Some(Box::new(self.parse_init_list()))
+        } else {
+            self.parse_assign_expr()
+        }
+    }
+
+    fn parse_init_list(&mut self) -> ast::InitList {
+        let lbrace = self.pos;
+        self.next();
+
+        let mut elems = Vec::new();
+
+        while self.tok != Token::RBRACE && self.tok != Token::EOF {
+            let designators = self.parse_designators();
+
+            if !designators.is_empty() {
+                self.expect(Token::ASSIGN);
+            }
+
+            match self.parse_initializer() {
+                Some(value) => elems.push(ast::Initializer { designators, value }),
+                None => {
+                    self.error_expected("expression");
+                    self.skip_past(Token::RBRACE);
+
+                    return ast::InitList {
+                        lbrace,
+                        elems,
+                        rbrace: self.prev_end - 1,
+                    };
+                }
+            }
+
+            if self.tok != Token::COMMA {
+                break;
+            }
+            self.next();
+        }
+
+        let rbrace = self.pos;
+        self.expect(Token::RBRACE);
+
+        ast::InitList {
+            lbrace,
+            elems,
+            rbrace,
+        }
+    }
+
+    // skips tokens up to and including `tok`
+    fn skip_past(&mut self, tok: Token) {
+        while self.tok != tok && self.tok != Token::EOF {
+            self.next();
+        }
+        if self.tok == tok {
+            self.next();
+        }
+    }
+
+    pub fn parse_stmt(&mut self) -> Option<Box<dyn ast::Stmt>> {
+        let tok = self.tok;
+
+        Some(match tok {
+            Token::BREAK => {
+                let pos = self.pos;
+                self.next();
+
+                let semi = self.pos;
+                self.expect(Token::SEMICOLON);
+
+                Box::new(ast::BreakStmt { pos, semi })
+            }
+
+            Token::CONTINUE => Box::new(self.parse_continue_stmt()),
+
+            Token::RETURN => {
+                let pos = self.pos;
+                self.next();
+
+                let value = self.parse_expr();
+
+                let semi = self.pos;
+                self.expect(Token::SEMICOLON);
+
+                Box::new(ast::ReturnStmt { pos, value, semi })
+            }
+
+            Token::IF => Box::new(self.parse_if_stmt()?),
+
+            Token::WHILE => Box::new(self.parse_while_stmt()?),
+
+            Token::DO => Box::new(self.parse_dowhile_stmt()?),
+
+            Token::FOR => Box::new(self.parse_for_stmt()?),
+
+            Token::SWITCH => {
+                let pos = self.pos;
+                self.next();
+
+                let (_, lparen_pos, _) = self.expect2(Token::LPAREN)?;
+                let tag = self.parse_expr()?;
+                let (_, rparen_pos, _) = self.expect2(Token::RPAREN)?;
+
+                let body = self.parse_stmt()?;
+
+                Box::new(ast::SwitchStmt {
+                    pos,
+                    lparen_pos,
+                    tag,
+                    rparen_pos,
+                    body,
+                })
+            }
+
+            Token::CASE | Token::DEFAULT => {
+                let pos = self.pos;
+                let case = self.tok == Token::CASE;
+                self.next();
+
+                let value = if case {
+                    Some(self.parse_cond_expr()?)
+                } else {
+                    None
+                };
+
+                let (_, colon, _) = self.expect2(Token::COLON)?;
+                let stmt = self.parse_labeled()?;
+
+                Box::new(ast::CaseStmt {
+                    pos,
+                    value,
+                    colon,
+                    stmt,
+                })
+            }
+
+            Token::GOTO => {
+                let pos = self.pos;
+                self.next();
+
+                if self.tok != Token::IDENT {
+                    self.error_expected("label");
+                    return None;
+                }
+                let label = self.parse_ident();
+
+                let semi = self.pos;
+                self.expect(Token::SEMICOLON);
+
+                Box::new(ast::GotoStmt { pos, label, semi })
+            }
+
+            Token::IDENT if self.peek().0 == Token::COLON => {
+                let label = self.parse_ident();
+
+                let colon = self.pos;
+                self.next();
+
+                let stmt = self.parse_labeled()?;
+
+                Box::new(ast::LabeledStmt { label, colon, stmt })
+            }
+
+            Token::SEMICOLON => {
+                let pos = self.pos;
+                self.next();
+
+                Box::new(ast::SemiColonStmt { pos })
+            }
+
+            Token::LBRACE => Box::new(self.parse_block_stmt()),
+
+            Token::STATIC_ASSERT => Box::new(self.parse_static_assert()?),
+
+            tok if self.starts_decl(tok, &self.lit) => Box::new(self.parse_decl_stmt()),
+
+            _ => {
+                let x = self.parse_expr()?;
+
+                let semi = self.pos;
+                self.expect(Token::SEMICOLON);
+
+                Box::new(ast::ExprStmt { x, semi })
+            }
+        })
+    }
+
+    // the statement after a label, which C requires
+    fn parse_labeled(&mut self) -> Option<Box<dyn ast::Stmt>> {
+        let s = self.parse_stmt();
+
+        if s.is_none() {
+            self.error_expected("statement after label");
+        }
+
+        s
+    }
+
+    fn parse_decl_stmt(&mut self) -> ast::DeclStmt {
+        let specs = self.parse_decl_spec();
+
+        if self.tok == Token::SEMICOLON {
+            let decl = self.parse_gen_decl_rest(specs, None);
+            return ast::DeclStmt { decl };
+        }
+
+        let declarator = self.parse_declarator();
+
+        if declarator.function().is_some() && self.tok == Token::LBRACE {
+            self.errors.push((
+                self.pos,
+                "function definition is not allowed here".to_string(),
+            ));
+
+            // skip the body and keep the declaration
+            let f = self.parse_func_decl(specs, declarator);
+            let decl = ast::GenDecl {
+                specs: f.specs,
+                decls: vec![ast::InitDecl {
+                    declarator: f.declarator,
+                    init: None,
+                }],
+                semi: f.body.rbrace,
+            };
+            return ast::DeclStmt { decl };
+        }
+
+        let decl = self.parse_gen_decl_rest(specs, Some(declarator));
+        ast::DeclStmt { decl }
+    }
+
+    fn parse_block_stmt(&mut self) -> ast::BlockStmt {
+        let mut stmts = Vec::new();
+
+        let lbrace = self.pos;
+        self.expect(Token::LBRACE);
+
+        // {
+        //   break;
+        //   return 0;
+        //   {
+        //     break;
+        //     return ;
+        //   }
+        // }
+
+        self.open_scope();
+        while self.tok != Token::RBRACE && self.tok != Token::EOF {
+            let pos = self.pos;
+
+            match self.parse_stmt() {
+                Some(s) => stmts.push(s),
+                None => {
+                    self.error_expected("statement");
+
+                    // skip the offending token
+                    if self.pos == pos {
+                        self.next();
+                    }
+                }
+            }
+        }
+        self.close_scope();
+
+        let rbrace = self.pos;
+        self.expect(Token::RBRACE);
+
+        ast::BlockStmt {
+            lbrace,
+            stmts,
+            rbrace,
+        }
+    }
+
+    fn parse_if_stmt(&mut self) -> Option<ast::IfStmt> {
+        let if_pos: usize = self.pos;
+        self.next();
+
+        let (_, lparen_pos, _) = self.expect2(Token::LPAREN)?;
+
+        let cond = self.parse_expr()?;
+
+        let (_, rparen_pos, _) = self.expect2(Token::RPAREN)?;
+
+        let init = self.parse_stmt()?;
+
+        let mut elifs: Vec<ast::ElseIf> = Vec::new();
+
+        while self.tok == Token::ELSE && self.peek().0 == Token::IF {
+            let else_pos = self.pos;
+            self.next();
+
+            let if_pos: usize = self.pos;
+            self.next();
+
+            let (_, lparen_pos, _) = self.expect2(Token::LPAREN)?;
+
+            let cond = self.parse_expr()?;
+
+            let (_, rparen_pos, _) = self.expect2(Token::RPAREN)?;
+
+            let init = self.parse_stmt()?;
+
+            elifs.push(ast::ElseIf {
+                else_pos,
+                if_pos,
+                lparen_pos,
+                cond,
+                rparen_pos,
+                init,
+            })
+        }
+
+        let _else = if self.tok == Token::ELSE {
+            let pos = self.pos;
+            self.next();
+
+            let init = self.parse_stmt()?;
+
+            Some(ast::Else { pos, init })
+        } else {
+            None
+        };
+
+        Some(ast::IfStmt {
+            if_pos,
+            lparen_pos,
+            cond,
+            rparen_pos,
+            init,
+            elifs,
+            _else,
+        })
+    }
+
+    fn parse_while_stmt(&mut self) -> Option<ast::WhileStmt> {
+        let pos = self.pos;
+        self.next();
+
+        let (_, lparen_pos, _) = self.expect2(Token::LPAREN)?;
+
+        let cond = self.parse_expr()?;
+
+        let (_, rparen_pos, _) = self.expect2(Token::RPAREN)?;
+
+        let init = self.parse_stmt()?;
+
+        Some(ast::WhileStmt {
+            pos,
+            lparen_pos,
+            cond,
+            rparen_pos,
+            init,
+        })
+    }
+
+    fn parse_dowhile_stmt(&mut self) -> Option<ast::DowhileStmt> {
+        let do_pos = self.pos;
+        self.next();
+
+        let init = self.parse_stmt()?;
+
+        let (_, while_pos, _) = self.expect2(Token::WHILE)?;
+        let (_, lparen_pos, _) = self.expect2(Token::LPAREN)?;
+
+        let cond = self.parse_expr()?;
+
+        let (_, rparen_pos, _) = self.expect2(Token::RPAREN)?;
+
+        let semi = self.pos;
+        self.expect(Token::SEMICOLON);
+
+        Some(ast::DowhileStmt {
+            do_pos,
+            init,
+            while_pos,
+            lparen_pos,
+            cond,
+            rparen_pos,
+            semi,
+        })
+    }
+
+    fn parse_for_stmt(&mut self) -> Option<ast::ForStmt> {
+        let pos = self.pos;
+        self.next();
+
+        let (_, lparen_pos, _) = self.expect2(Token::LPAREN)?;
+
+        // a declaration in the first clause is scoped to the loop
+        self.open_scope();
+
+        let init: Option<Box<dyn ast::Stmt>> = if self.tok == Token::SEMICOLON {
+            self.next();
+            None
+        } else if self.starts_decl(self.tok, &self.lit) {
+            Some(Box::new(self.parse_decl_stmt()))
+        } else {
+            let x = self.parse_expr();
+            let semi = self.pos;
+            self.expect(Token::SEMICOLON);
+
+            x.map(|x| Box::new(ast::ExprStmt { x, semi }) as Box<dyn ast::Stmt>)
+        };
+
+        let cond = if self.tok == Token::SEMICOLON {
+            None
+        } else {
+            self.parse_expr()
+        };
+        self.expect(Token::SEMICOLON);
+
+        let post = if self.tok == Token::RPAREN {
+            None
+        } else {
+            self.parse_expr()
+        };
+
+        let rparen_pos = self.pos;
+        self.expect(Token::RPAREN);
+
+        let body = self.parse_stmt();
+        self.close_scope();
+
+        Some(ast::ForStmt {
+            pos,
+            lparen_pos,
+            init,
+            cond,
+            post,
+            rparen_pos,
+            body: body?,
+        })
+    }
+
+    fn parse_continue_stmt(&mut self) -> ast::ContinueStmt {
+        let pos = self.pos;
+        self.next();
+
+        let semi = self.pos;
+        self.expect(Token::SEMICOLON);
+
+        ast::ContinueStmt { pos, semi }
+    }
+
+    fn parse_ident(&mut self) -> ast::Ident {
+        let pos = self.pos;
+        let name = self.lit.clone();
+
+        self.next();
+
+        ast::Ident { pos, name }
     }
-}
+    // - - 4     + . -3;
+    // (-(-4)) + (-3)
 
-impl From<Scanner> for Parser {
-    fn from(scanner: Scanner) -> Self {
-        let mut p = Self {
-            scanner,
-            ..Self::default()
-        };
-        p.next();
-        p
+    /// Parses an expression, including the comma operator.
+    pub fn parse_expr(&mut self) -> Option<Box<dyn ast::Expr>> {
+        let mut x = self.parse_assign_expr()?;
+
+        while self.tok == Token::COMMA {
+            let op_pos = self.pos;
+            self.next();
+
+            let y = self.parse_operand_or_error(Self::parse_assign_expr)?;
+
+            x = Box::new(ast::InfixExpr {
+                x,
+                op_pos,
+                op: Token::COMMA,
+                y,
+            });
+        }
+
+        Some(x)
     }
-}
 
-impl Parser {
-    fn next(&mut self) {
-        loop {
-            match self.scanner.scan() {
-                Ok((tok, pos, lit)) => {
-                    self.tok = tok;
-                    self.pos = pos;
-                    self.lit = lit.to_string();
+    // assignments are right associative
+    fn parse_assign_expr(&mut self) -> Option<Box<dyn ast::Expr>> {
+        let x = self.parse_cond_expr()?;
 
-                    break;
-                }
-                Err((_, pos, _, msg)) => {
-                    self.errors.push((pos, msg));
-                }
-            }
+        if !self.tok.is_assign() {
+            return Some(x);
         }
+
+        let op_pos = self.pos;
+        let op = self.tok;
+        self.next();
+
+        let y = self.parse_operand_or_error(Self::parse_assign_expr)?;
+
+        Some(Box::new(ast::AssignExpr { x, op_pos, op, y }))
     }
 
-    fn expect(&mut self, t: Token) {
-        if self.tok == t {
-            self.next();
-        } else {
-            let msg = if self.tok.is_literal() {
-                format!("expected {}, got '{}'", t.to_str(), self.lit)
-            } else {
-                format!("expected {}, got {}", t.to_str(), self.tok.to_str())
-            };
+    fn parse_cond_expr(&mut self) -> Option<Box<dyn ast::Expr>> {
+        let cond = self.parse_binary_expr(Token::TERNERY.precedence() + 1)?;
 
-            self.errors.push((self.pos, msg));
+        if self.tok != Token::TERNERY {
+            return Some(cond);
         }
+
+        let question = self.pos;
+        self.next();
+
+        let x = self.parse_operand_or_error(Self::parse_expr)?;
+        let (_, colon, _) = self.expect2(Token::COLON)?;
+        let y = self.parse_operand_or_error(Self::parse_cond_expr)?;
+
+        Some(Box::new(ast::CondExpr {
+            cond,
+            question,
+            x,
+            colon,
+            y,
+        }))
     }
 
-    fn expect2(&mut self, look_ahead: Token) -> Option<(Token, usize, String)> {
-        if self.tok == look_ahead {
-            let (tok, pos, lit) = (self.tok, self.pos, self.lit.clone());
+    // parses the left associative binary operators binding at least as
+    // tight as `prec1`
+    fn parse_binary_expr(&mut self, prec1: usize) -> Option<Box<dyn ast::Expr>> {
+        let mut x = self.parse_cast_expr()?;
+
+        loop {
+            let prec = self.tok.precedence();
+            if prec < prec1 {
+                return Some(x);
+            }
+
+            let op_pos = self.pos;
+            let op = self.tok;
             self.next();
-            Some((tok, pos, lit))
-        } else {
-            // while (34535 == 4 { 2 = 12; }
-            // expected ')' got 'INTEGER'
-            let msg = if self.tok.is_literal() {
-                format!("expected {}, got '{}'", look_ahead.to_str(), self.lit)
-            } else {
-                format!("expected {}, got {}", look_ahead.to_str(), self.tok.to_str())
-            };
 
-            self.errors.push((self.pos, msg));
+            let y = self.parse_operand_or_error(|p| p.parse_binary_expr(prec + 1))?;
 
-            None
+            x = Box::new(ast::InfixExpr { x, op_pos, op, y });
         }
     }
 
-    fn parse_stmt(&mut self) -> Option<Box<dyn ast::Stmt>> {
-        Some(match self.tok {
-            Token::BREAK => {
-                let pos = self.pos;
-                self.next();
+    // parses the operand after an operator, which must be there
+    fn parse_operand_or_error(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Option<Box<dyn ast::Expr>>,
+    ) -> Option<Box<dyn ast::Expr>> {
+        let x = f(self);
 
-                if self.tok != Token::SEMICOLON {
-                    return None;
-                }
+        if x.is_none() {
+            self.error_expected("expression");
+        }
 
-                let semi = self.pos;
-                self.next();
+        x
+    }
 
-                Box::new(ast::BreakStmt { pos, semi })
-            }
+    fn parse_cast_expr(&mut self) -> Option<Box<dyn ast::Expr>> {
+        if self.tok != Token::LPAREN || !self.peek_type_name() {
+            return self.parse_unary_expr();
+        }
 
-            Token::RETURN => {
-                let pos = self.pos;
-                self.next();
+        let lparen = self.pos;
+        self.next();
 
-                let value = self.parse_expr();
+        let typ = self.parse_type_name();
 
-                if self.tok != Token::SEMICOLON {
-                    return None;
-                }
-                let semi = self.pos;
-                self.next();
+        let rparen = self.pos;
+        self.expect(Token::RPAREN);
 
-                Box::new(ast::ReturnStmt { pos, value, semi })
-            }
+        if self.tok == Token::LBRACE {
+            let init = self.parse_init_list();
 
-            Token::IF => {
-                Box::new(self.parse_if_stmt()?)
-            }
+            let x = Box::new(ast::CompositeLit {
+                lparen,
+                typ,
+                rparen,
+                init,
+            });
 
-            Token::WHILE => {
-                Box::new(self.parse_while_stmt()?)
-            }
+            return self.parse_postfix_expr(x);
+        }
 
-            Token::LBRACE => {
-                let mut stmts = Vec::new();
+        let x = self.parse_operand_or_error(Self::parse_cast_expr)?;
 
-                let lbrace = self.pos;
-                self.next();
+        Some(Box::new(ast::CastExpr {
+            lparen,
+            typ,
+            rparen,
+            x,
+        }))
+    }
 
-                // {
-                //   break;
-                //   return 0;
-                //   {
-                //     break;
-                //     return ;
-                //   }
-                // }
-
-                loop {
-                    match self.parse_stmt() {
-                        Some(s) => stmts.push(s),
-                        None => break,
-                    }
-                }
+    fn parse_unary_expr(&mut self) -> Option<Box<dyn ast::Expr>> {
+        match self.tok {
+            Token::PLUS | Token::MINUS | Token::NOT | Token::BANG | Token::TILDE | Token::AND => {
+                let op_pos = self.pos;
+                let op = self.tok;
 
-                let rbrace = self.pos;
                 self.next();
 
-                Box::new(ast::BlockStmt {
-                    lbrace,
-                    stmts,
-                    rbrace,
-                })
-            }
+                let x = self.parse_operand_or_error(Self::parse_cast_expr)?;
 
-            _ => return None,
-        })
-    }
+                Some(Box::new(ast::UnaryExpr { op_pos, op, x }))
+            }
 
-    fn parse_if_stmt(&mut self) -> Option<ast::IfStmt>{
-        let if_pos: usize = self.pos;
-        self.next();
+            Token::INC | Token::DEC => {
+                let op_pos = self.pos;
+                let op = self.tok;
 
-    
-        let (_, lparen_pos, _) = self.expect2(Token::LPAREN)?;
+                self.next();
 
-        let cond = self.parse_expr()?;
+                let x = self.parse_operand_or_error(Self::parse_unary_expr)?;
 
-        let(_, rparen_pos, _) = self.expect2(Token::RPAREN)?;
+                Some(Box::new(ast::UnaryExpr { op_pos, op, x }))
+            }
 
-        let init  = self.parse_stmt()?;
+            Token::ASTERISK => {
+                let pos = self.pos;
+                self.next();
 
-        let mut elifs: Vec<ast::ElseIf> = Vec::new();
+                let x = self.parse_operand_or_error(Self::parse_cast_expr)?;
 
-        loop {
-            if self.tok != Token::ELSE {
-                break;
+                Some(Box::new(ast::StarExpr { pos, x }))
             }
-            let else_pos = self.pos;
 
-            if self.tok != Token::IF {
-                break;
-            }
+            Token::SIZEOF | Token::ALIGNOF => {
+                let pos = self.pos;
+                let op = self.tok;
+                self.next();
 
-            let if_pos: usize = self.pos;
-            self.next();
+                if self.tok == Token::LPAREN && self.peek_type_name() {
+                    self.next();
 
-    
-            let (_, lparen_pos, _) = self.expect2(Token::LPAREN)?;
+                    let typ = self.parse_type_name();
 
-            let cond = self.parse_expr()?;
+                    let end = self.pos + 1;
+                    self.expect(Token::RPAREN);
 
-            let(_, rparen_pos, _) = self.expect2(Token::RPAREN)?;
+                    return Some(Box::new(ast::SizeofExpr {
+                        pos,
+                        op,
+                        arg: ast::SizeofArg::Type(typ),
+                        end,
+                    }));
+                }
+
+                let x = self.parse_operand_or_error(Self::parse_unary_expr)?;
+                let end = x.end();
 
-            let init  = self.parse_stmt()?;
+                Some(Box::new(ast::SizeofExpr {
+                    pos,
+                    op,
+                    arg: ast::SizeofArg::Expr(x),
+                    end,
+                }))
+            }
 
-            elifs.push( ast::ElseIf {else_pos, if_pos, lparen_pos, cond, rparen_pos, init })
+            _ => {
+                let x = self.parse_operand()?;
+                self.parse_postfix_expr(x)
+            }
         }
+    }
 
-        let _else = if self.tok == Token::ELSE {
-            let pos = self.pos;
-            let init = self.parse_stmt()?;
+    fn parse_postfix_expr(&mut self, mut x: Box<dyn ast::Expr>) -> Option<Box<dyn ast::Expr>> {
+        loop {
+            x = match self.tok {
+                Token::LBRACK => {
+                    let lbrack = self.pos;
+                    self.next();
+
+                    let index = self.parse_operand_or_error(Self::parse_expr)?;
+
+                    let rbrack = self.pos;
+                    self.expect(Token::RBRACK);
+
+                    Box::new(ast::IndexExpr {
+                        x,
+                        lbrack,
+                        index,
+                        rbrack,
+                    })
+                }
 
-            Some(ast::Else { pos, init })
-        } else { None };
+                Token::LPAREN => {
+                    let lparen = self.pos;
+                    self.next();
 
-        Some(ast::IfStmt {if_pos , lparen_pos , cond , rparen_pos , init, elifs, _else})
+                    let mut args = Vec::new();
+                    while self.tok != Token::RPAREN {
+                        args.push(self.parse_operand_or_error(Self::parse_assign_expr)?);
 
-    }
+                        if self.tok != Token::COMMA {
+                            break;
+                        }
+                        self.next();
+                    }
 
-    fn parse_while_stmt(&mut self) -> Option<ast::WhileStmt> {
-        let pos = self.pos;
-        self.next();
+                    let rparen = self.pos;
+                    self.expect(Token::RPAREN);
 
-        let (_, lparen_pos, _) = self.expect2(Token::LPAREN)?;
+                    Box::new(ast::CallExpr {
+                        fun: x,
+                        lparen,
+                        args,
+                        rparen,
+                    })
+                }
 
-        let cond = self.parse_expr()?;
+                Token::DOT | Token::ARROW => {
+                    let op_pos = self.pos;
+                    let op = self.tok;
+                    self.next();
 
-        let (_, rparen_pos, _) = self.expect2(Token::RPAREN)?;
+                    if self.tok != Token::IDENT {
+                        self.error_expected("field name");
+                        return None;
+                    }
+                    let sel = self.parse_ident();
 
-        let init = self.parse_stmt()?;
+                    Box::new(ast::SelectorExpr { x, op_pos, op, sel })
+                }
 
-        Some(ast::WhileStmt { pos, lparen_pos, cond, rparen_pos, init })
-    }
+                Token::INC | Token::DEC => {
+                    let op_pos = self.pos;
+                    let op = self.tok;
+                    self.next();
 
-    fn parse_continue_stmt(&mut self) -> ast::ContinueStmt {
-        let pos = self.pos;
-        self.expect(Token::SEMICOLON);
-        ast::ContinueStmt { pos }
+                    Box::new(ast::PostfixExpr { x, op_pos, op })
+                }
+
+                _ => return Some(x),
+            };
+        }
     }
 
-    fn parse_ident(&mut self) -> ast::Ident {
-        let pos = self.pos;
-        let name = self.lit.clone();
+    // parses designators like .a[1].b, up to the first other token
+    fn parse_designators(&mut self) -> Vec<ast::Designator> {
+        let mut designators = Vec::new();
 
-        self.next();
+        loop {
+            match self.tok {
+                Token::DOT => {
+                    let dot = self.pos;
+                    self.next();
+
+                    if self.tok != Token::IDENT {
+                        self.error_expected("field name");
+                        break;
+                    }
+                    let name = self.parse_ident();
 
-        ast::Ident { pos, name }
-    }
-    // - - 4     + . -3;
-    // (-(-4)) + (-3)
+                    designators.push(ast::Designator::Field { dot, name });
+                }
 
-    fn parse_expr(&mut self) -> Option<Box<dyn ast::Expr>> {
-        let x = self.parse_operand()?;
-        match self.tok {
-            Token::PLUS
-            | Token::MINUS
-            | Token::ASTERISK
-            | Token::SLASH
-            | Token::REM
-            | Token::AND
-            | Token::OR
-            | Token::XOR
-            | Token::SHL
-            | Token::SHR
-            | Token::LAND
-            | Token::LOR
-            | Token::EQL
-            | Token::NEQ
-            | Token::LT
-            | Token::GT
-            | Token::LEQ
-            | Token::GEQ => {
-                //parsing infix operation
-                let op_pos = self.pos;
-                let op = self.tok;
+                Token::LBRACK => {
+                    let lbrack = self.pos;
+                    self.next();
 
-                self.next();
+                    let Some(index) = self.parse_cond_expr() else {
+                        self.error_expected("expression");
+                        break;
+                    };
+
+                    let rbrack = self.pos;
+                    self.expect(Token::RBRACK);
 
-                let y = self.parse_expr()?;
+                    designators.push(ast::Designator::Index {
+                        lbrack,
+                        index,
+                        rbrack,
+                    });
+                }
 
-                Some(Box::new(ast::InfixExpr { x, op_pos, op, y }))
+                _ => break,
             }
+        }
+
+        designators
+    }
 
-            _ => Some(x),
+    fn parse_offsetof(&mut self, name: ast::Ident) -> Option<Box<dyn ast::Expr>> {
+        let lparen = self.pos;
+        self.next();
+
+        let typ = self.parse_type_name();
+        self.expect(Token::COMMA);
+
+        if self.tok != Token::IDENT {
+            self.error_expected("field name");
+            return None;
         }
+        let member = self.parse_ident();
+        let path = self.parse_designators();
+
+        let rparen = self.pos;
+        self.expect(Token::RPAREN);
+
+        Some(Box::new(ast::OffsetofExpr {
+            name,
+            lparen,
+            typ,
+            member,
+            path,
+            rparen,
+        }))
     }
 
     fn parse_basic_lit(&mut self) -> ast::BasicLit {
         let tok = self.tok;
         let pos = self.pos;
-        let lit = self.lit.clone();
+        let mut lit = self.lit.clone();
 
         self.next();
 
+        // adjacent string literals are concatenated
+        while tok == Token::STRING && self.tok == Token::STRING {
+            lit.push(' ');
+            lit.push_str(&self.lit);
+            self.next();
+        }
+
         ast::BasicLit { pos, tok, lit }
     }
 
     fn parse_operand(&mut self) -> Option<Box<dyn ast::Expr>> {
         match self.tok {
             Token::IDENT => {
-                let pos = self.pos;
-                let name = self.lit.clone();
+                let name = self.parse_ident();
 
-                self.next();
+                // offsetof from <stddef.h> takes a type, it is parsed like
+                // the builtin it expands to
+                let offsetof = matches!(name.name.as_str(), "offsetof" | "__builtin_offsetof");
+                if offsetof && self.tok == Token::LPAREN && self.peek_type_name() {
+                    return self.parse_offsetof(name);
+                }
 
-                Some(Box::new(ast::Ident { pos, name }))
+                Some(Box::new(name))
             }
 
-            Token::PLUS | Token::MINUS => {
-                let op_pos = self.pos;
-                let op = self.tok;
+            Token::INTEGER | Token::FLOATING | Token::STRING | Token::CHARACTER => {
+                Some(Box::new(self.parse_basic_lit()))
+            }
 
+            Token::LPAREN => {
+                let lparen = self.pos;
                 self.next();
 
-                let x = self.parse_operand()?;
-
-                Some(Box::new(ast::UnaryExpr { op_pos, op, x }))
-            }
-
-            Token::INTEGER | Token::FLOATING | Token::STRING => {
-                let pos = self.pos;
-                let tok = self.tok;
-                let lit = self.lit.clone();
+                let x = self.parse_operand_or_error(Self::parse_expr)?;
 
-                self.next();
+                let rparen = self.pos;
+                self.expect(Token::RPAREN);
 
-                Some(Box::new(ast::BasicLit { pos, tok, lit }))
+                Some(Box::new(ast::ParenExpr { lparen, x, rparen }))
             }
 
             _ => None,
@@ -324,6 +1563,29 @@ impl Parser {
     }
 }
 
+// reports whether a token is a type specifier or qualifier keyword
+fn starts_type(tok: Token) -> bool {
+    matches!(
+        tok,
+        Token::VOID
+            | Token::BOOL
+            | Token::CHAR
+            | Token::SHORT
+            | Token::INT
+            | Token::LONG
+            | Token::FLOAT
+            | Token::DOUBLE
+            | Token::SIGNED
+            | Token::UNSIGNED
+            | Token::STRUCT
+            | Token::UNION
+            | Token::ENUM
+            | Token::CONST
+            | Token::VOLATILE
+            | Token::RESTRICT
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -336,7 +1598,7 @@ mod tests {
         // (12 - (-(-3)))
         //(12/6)
         // let tests = ["(-1)", "(+2)", "x", "12", "(-(-(-3)))"];
-        let tests = ["((-1) + (2 + x))", "(12 - (-(-3)))"];
+        let tests = ["(((-1) + 2) + x)", "(12 - (-(-3)))"];
 
         let mut p = Parser::from(source.to_string());
 
@@ -391,7 +1653,6 @@ return 69         ;
             "break;",
             "return;",
             "return 69;",
-           
             // if (age >= 18) {
             //    if (age <= 21) {
             //        return 0;
@@ -414,4 +1675,285 @@ return 69         ;
             );
         }
     }
+
+    #[test]
+    fn test_parse_expr_precedence() {
+        let tests = [
+            ("a = b = c", "(a = (b = c))"),
+            (
+                "a || b && c | d ^ e & f",
+                "(a || (b && (c | (d ^ (e & f)))))",
+            ),
+            (
+                "a == b < c << d + e * f",
+                "(a == (b < (c << (d + (e * f)))))",
+            ),
+            ("a ? b : c ? d : e", "(a ? b : (c ? d : e))"),
+            ("x += 1, y--", "((x += 1) , (y--))"),
+            ("-*p++", "(-(*(p++)))"),
+            ("f(a, b)[i].x->y", "f(a, b)[i].x->y"),
+            ("sizeof x + sizeof(int *)", "(sizeof x + sizeof(int *))"),
+            ("(long)(a) + 1", "(((long) a) + 1)"),
+            ("_Alignof(double) * 2", "(_Alignof(double) * 2)"),
+            (
+                "offsetof(struct s, a.b[i + 1]) - 1",
+                "(offsetof(struct s, a.b[(i + 1)]) - 1)",
+            ),
+            ("offsetof(x, y)", "offsetof(x, y)"),
+        ];
+
+        for (i, (src, t)) in tests.iter().enumerate() {
+            let mut p = Parser::from(src.to_string());
+            let x = p.parse_expr().unwrap();
+
+            assert!(p.errors().is_empty(), "{}: {:?}", src, p.errors());
+            assert_eq!(
+                *t,
+                x.string(),
+                "[{}/{}] test case failed.",
+                i + 1,
+                tests.len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_decl() {
+        let source = "\
+typedef unsigned long size_t;
+static int *a[3], (*b)[3];
+int (*fp)(int, char *), f(void);
+struct point { int x, y : 4; } p = { .x = 1, [0] = 2 };
+enum { RED, GREEN = 2 } c;
+int g(size_t n, ...) { size_t * m; return (size_t)n * m; }
+_Static_assert(sizeof(size_t) == 8, \"LP64\");
+";
+
+        let mut p = Parser::from(source.to_string());
+        let file = p.parse_file();
+
+        assert!(p.errors().is_empty(), "{:?}", p.errors());
+
+        let tree = ast::dump::Dumper::new(|offset| p.position(offset)).file(&file);
+
+        assert_eq!(
+            "(File \
+             (GenDecl typedef (InitDecl size_t 'unsigned long')) \
+             (GenDecl static (InitDecl a 'int *[3]') (InitDecl b 'int (*)[3]')) \
+             (GenDecl (InitDecl fp 'int (*)(int, char *)') (InitDecl f 'int (void)')) \
+             (GenDecl (RecordDecl struct point (FieldDecl x 'int') (FieldDecl y 'int' (BasicLit 4))) \
+             (InitDecl p 'struct point' (InitList (Designation .x (BasicLit 1)) (Designation [0] (BasicLit 2))))) \
+             (GenDecl (EnumDecl enum <anonymous> (Enumerator RED) (Enumerator GREEN (BasicLit 2))) \
+             (InitDecl c 'enum <anonymous>')) \
+             (FuncDecl g 'int (size_t, ...)' (ParamDecl n 'size_t') (BlockStmt \
+             (DeclStmt (GenDecl (InitDecl m 'size_t *'))) \
+             (ReturnStmt (InfixExpr * (CastExpr 'size_t' (Ident n)) (Ident m))))) \
+             (StaticAssert (InfixExpr == (SizeofExpr 'size_t') (BasicLit 8)) (BasicLit \"LP64\")))",
+            tree.to_sexpr()
+        );
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let source =
+            "int f(int x) {\n  while (x < 10) {\n    return -x;\n    continue;\n  }\n  break;\n}\n";
+
+        let mut p = Parser::from(source.to_string());
+        let file = p.parse_file();
+
+        assert!(p.errors().is_empty(), "{:?}", p.errors());
+
+        let tree = ast::dump::Dumper::new(|offset| p.position(offset)).file(&file);
+
+        assert_eq!(
+            "(File (FuncDecl f 'int (int)' (ParamDecl x 'int') (BlockStmt \
+             (WhileStmt (InfixExpr < (Ident x) (BasicLit 10)) \
+             (BlockStmt (ReturnStmt (UnaryExpr - (Ident x))) (ContinueStmt))) (BreakStmt))))",
+            tree.to_sexpr()
+        );
+
+        assert_eq!(
+            "\
+File <line:1:1, line:7:2>
+`-FuncDecl <line:1:1, line:7:1> f 'int (int)'
+  |-ParamDecl <line:1:7, col:11> x 'int'
+  `-BlockStmt <col:14, line:7:1>
+    |-WhileStmt <line:2:3, line:5:3>
+    | |-InfixExpr <line:2:10, col:15> <
+    | | |-Ident <col:10, col:10> x
+    | | `-BasicLit <col:14, col:15> 10
+    | `-BlockStmt <col:18, line:5:3>
+    |   |-ReturnStmt <line:3:5, col:14>
+    |   | `-UnaryExpr <col:12, col:13> -
+    |   |   `-Ident <col:13, col:13> x
+    |   `-ContinueStmt <line:4:5, col:13>
+    `-BreakStmt <line:6:3, col:8>
+",
+            tree.to_text()
+        );
+
+        // a block after a declaration is a statement, a function body is not
+        let tests = [
+            (
+                "int main(void) { int y = 1; { int y = 2; } return y; }",
+                "(File (FuncDecl main 'int (void)' (ParamDecl 'void') (BlockStmt \
+                 (DeclStmt (GenDecl (InitDecl y 'int' (BasicLit 1)))) \
+                 (BlockStmt (DeclStmt (GenDecl (InitDecl y 'int' (BasicLit 2))))) \
+                 (ReturnStmt (Ident y)))))",
+                Vec::<(usize, &str)>::new(),
+            ),
+            (
+                "typedef int T; int f(void) { T y; { T z; } return 0; }",
+                "(File (GenDecl typedef (InitDecl T 'int')) (FuncDecl f 'int (void)' (ParamDecl 'void') \
+                 (BlockStmt (DeclStmt (GenDecl (InitDecl y 'T'))) \
+                 (BlockStmt (DeclStmt (GenDecl (InitDecl z 'T')))) (ReturnStmt (BasicLit 0)))))",
+                vec![],
+            ),
+            (
+                "int main(void) { int g(void) { return 1; } return g(); }",
+                "(File (FuncDecl main 'int (void)' (ParamDecl 'void') (BlockStmt \
+                 (DeclStmt (GenDecl (InitDecl g 'int (void)'))) (ReturnStmt (CallExpr (Ident g))))))",
+                vec![(29, "function definition is not allowed here")],
+            ),
+        ];
+
+        for (i, (src, sexpr, errors)) in tests.iter().enumerate() {
+            let mut p = Parser::from(src.to_string());
+            let file = p.parse_file();
+            let tree = ast::dump::Dumper::new(|offset| p.position(offset)).file(&file);
+
+            let got: Vec<_> = p.errors().iter().map(|(i, e)| (*i, e.as_str())).collect();
+            assert_eq!(
+                (*sexpr, errors),
+                (tree.to_sexpr().as_str(), &got),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn test_dump_json() {
+        let source = "int x = 1;\n";
+
+        let mut p = Parser::from(source.to_string());
+        let file = p.parse_file();
+
+        assert!(p.errors().is_empty(), "{:?}", p.errors());
+
+        let tree = ast::dump::Dumper::new(|offset| p.position(offset)).file(&file);
+        let json: serde_json::Value = serde_json::from_str(&tree.to_json()).unwrap();
+
+        assert_eq!(
+            serde_json::json!({
+                "kind": "File",
+                "range": {
+                    "begin": { "offset": 0, "line": 1, "col": 1 },
+                    "end": { "offset": 10, "line": 1, "col": 11 }
+                },
+                "inner": [{
+                    "kind": "GenDecl",
+                    "range": {
+                        "begin": { "offset": 0, "line": 1, "col": 1 },
+                        "end": { "offset": 9, "line": 1, "col": 10 }
+                    },
+                    "inner": [{
+                        "kind": "InitDecl",
+                        "range": {
+                            "begin": { "offset": 4, "line": 1, "col": 5 },
+                            "end": { "offset": 8, "line": 1, "col": 9 }
+                        },
+                        "detail": "x 'int'",
+                        "inner": [{
+                            "kind": "BasicLit",
+                            "range": {
+                                "begin": { "offset": 8, "line": 1, "col": 9 },
+                                "end": { "offset": 8, "line": 1, "col": 9 }
+                            },
+                            "detail": "1"
+                        }]
+                    }]
+                }]
+            }),
+            json
+        );
+    }
+
+    #[test]
+    fn test_print_file() {
+        let source = "// count\nint x;\nint f(void) { while (x < 10) { if (a) return - -x; else x += 1, y--; } \
+                      switch (x) { case 1: break; default: x = (a + b) * c; } \
+                      return 1 * 2 + 3; /* end */ }\n";
+
+        let tests = [
+            (
+                ast::printer::Style::default(),
+                "\
+// count
+int x;
+
+int f(void) {
+    while (x < 10) {
+        if (a)
+            return - -x;
+        else
+            x += 1, y--;
+    }
+    switch (x) {
+    case 1:
+        break;
+    default:
+        x = (a + b) * c;
+    }
+    return 1 * 2 + 3;  /* end */
+}
+",
+            ),
+            (
+                ast::printer::Style {
+                    brace: ast::printer::BraceStyle::Allman,
+                    indent: 2,
+                    ..Default::default()
+                },
+                "\
+// count
+int x;
+
+int f(void)
+{
+  while (x < 10)
+  {
+    if (a)
+      return - -x;
+    else
+      x += 1, y--;
+  }
+  switch (x)
+  {
+  case 1:
+    break;
+  default:
+    x = (a + b) * c;
+  }
+  return 1 * 2 + 3;  /* end */
+}
+",
+            ),
+        ];
+
+        for (i, (style, t)) in tests.iter().enumerate() {
+            let mut p = Parser::from(source.to_string());
+            let file = p.parse_file();
+
+            assert_eq!(
+                *t,
+                ast::printer::Printer::new(*style).file(&file),
+                "[{}/{}] test case failed.",
+                i + 1,
+                tests.len()
+            );
+        }
+    }
 }
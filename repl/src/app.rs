@@ -18,7 +18,7 @@ pub struct App {
     redraw: bool,
 }
 
-const SIMBA: &'static str = r#"
+const SIMBA: &str = r#"
                    ,   __, ,
    _.._         )\/(,-' (-' `.__
   /_   `-.      )'_      ` _  (_    _.---._
@@ -42,7 +42,7 @@ const SIMBA: &'static str = r#"
                   `-.______,'
 "#;
 
-const LOGO: &'static str = r#"
+const LOGO: &str = r#"
    ______  _____
   / ____/ / ___ \___  ____  __
  / /     / /__/ / _ \/ __ \/ /
@@ -51,7 +51,7 @@ const LOGO: &'static str = r#"
       /_/  \_\    /_/
 "#;
 
-const LOGO1: &'static str = r#"
+const LOGO1: &str = r#"
    _____   _____                   _      
   / ____| |  __ \                 | |     
  | |      | |__) |   ___   _ __   | |     
@@ -61,7 +61,7 @@ const LOGO1: &'static str = r#"
                           | |             
                           |_|             "#;
 
-const LOGO2: &'static str = r#"
+const LOGO2: &str = r#"
   ______        _______        _______        _______       ___       
  /" _  "\      /"      \      /"     "|      |   __ "\     |"  |      
 (: ( \___)    |:        |    (: ______)      (. |__) :)    ||  |      
@@ -71,7 +71,7 @@ const LOGO2: &'static str = r#"
  \_______)    |__|  \___)     \_______)    (_______)        \_______) 
 "#;
 
-const LOGO3: &'static str = r#"
+const LOGO3: &str = r#"
  ░▒▓██████▓▒░       ░▒▓███████▓▒░       ░▒▓████████▓▒░      ░▒▓███████▓▒░       ░▒▓█▓▒░        
 ░▒▓█▓▒░░▒▓█▓▒░      ░▒▓█▓▒░░▒▓█▓▒░      ░▒▓█▓▒░             ░▒▓█▓▒░░▒▓█▓▒░      ░▒▓█▓▒░        
 ░▒▓█▓▒░             ░▒▓█▓▒░░▒▓█▓▒░      ░▒▓█▓▒░             ░▒▓█▓▒░░▒▓█▓▒░      ░▒▓█▓▒░        
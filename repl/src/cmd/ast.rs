@@ -0,0 +1,57 @@
+use ast::dump::Dumper;
+use parser::Parser;
+
+use super::{read_source, Options};
+
+pub const USAGE: &str = "usage: crepl ast [--format tree|json|sexpr] [-trigraphs] FILE";
+
+pub fn run(args: &[String]) -> i32 {
+    let opts = match Options::parse(args, &[]) {
+        Ok(opts) if opts.files.len() == 1 => opts,
+        Ok(_) => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+        Err(err) => {
+            eprintln!("crepl ast: {}\n{}", err, USAGE);
+            return 2;
+        }
+    };
+
+    let format = opts.format.as_deref().unwrap_or("tree");
+    if !matches!(format, "tree" | "json" | "sexpr") {
+        eprintln!("crepl ast: unknown ast format '{}'", format);
+        return 2;
+    }
+
+    let file = &opts.files[0];
+    let src = match read_source(file) {
+        Ok(src) => src,
+        Err(err) => {
+            eprintln!("crepl ast: {}: {}", file, err);
+            return 1;
+        }
+    };
+
+    let mut p = Parser::from(opts.scanner(src));
+    let root = p.parse_file();
+
+    for (pos, msg) in p.errors() {
+        let pos = p.position(*pos);
+        eprintln!("{}:{}:{}: error: {}", file, pos.line, pos.column, msg);
+    }
+
+    let tree = Dumper::new(|offset| p.position(offset)).file(&root);
+
+    match format {
+        "json" => println!("{}", tree.to_json()),
+        "sexpr" => println!("{}", tree.to_sexpr()),
+        _ => print!("{}", tree.to_text()),
+    }
+
+    if p.errors().is_empty() {
+        0
+    } else {
+        1
+    }
+}
@@ -0,0 +1,114 @@
+use parser::Parser;
+use sema::layout::DataLayout;
+
+use super::{expand, read_source, Options};
+
+pub const USAGE: &str = "usage: crepl build [-O0] [-S] [-trigraphs] FILE [-o OUTPUT]";
+
+pub fn run(args: &[String]) -> i32 {
+    // -o takes a value, -O0 and -S are flags of this command only
+    let mut output = None;
+    let mut optimize = true;
+    let mut assembly = false;
+    let mut rest = Vec::new();
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "-o" => match it.next() {
+                Some(out) => output = Some(out.clone()),
+                None => {
+                    eprintln!("crepl build: missing value for '-o'\n{}", USAGE);
+                    return 2;
+                }
+            },
+            "-O0" => optimize = false,
+            "-O" => optimize = true,
+            "-S" => assembly = true,
+            _ => rest.push(arg.clone()),
+        }
+    }
+
+    let opts = match Options::parse(&rest, &[]) {
+        Ok(opts) if opts.files.len() == 1 => opts,
+        Ok(_) => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+        Err(err) => {
+            eprintln!("crepl build: {}\n{}", err, USAGE);
+            return 2;
+        }
+    };
+
+    // native code is for x86-64 Linux, whose layout is the default
+    let layout = DataLayout::default();
+
+    let file = opts.files[0].clone();
+    let src = match read_source(&file) {
+        Ok(src) => src,
+        Err(err) => {
+            eprintln!("crepl build: {}: {}", file, err);
+            return 1;
+        }
+    };
+
+    let Some(src) = expand(&file, &src, &layout) else {
+        return 1;
+    };
+
+    let mut p = Parser::from(opts.scanner(src));
+    let root = p.parse_file();
+
+    let print = |pos: usize, msg: &str| {
+        let pos = p.position(pos);
+        eprintln!("{}:{}:{}: error: {}", file, pos.line, pos.column, msg);
+    };
+
+    if !p.errors().is_empty() {
+        for (pos, msg) in p.errors() {
+            print(*pos, msg);
+        }
+        return 1;
+    }
+
+    let info = sema::check_with(&root, layout);
+    if info.errors().count() > 0 {
+        for d in info.errors() {
+            print(d.pos, &d.msg);
+        }
+        return 1;
+    }
+
+    let asm = match codegen::compile(&root, &info, optimize) {
+        Ok(asm) => asm,
+        Err(err) => {
+            print(err.pos, &err.msg);
+            return 1;
+        }
+    };
+
+    // -S writes the assembly, to the standard output without -o
+    if assembly {
+        let written = match output.as_deref() {
+            None | Some("-") => {
+                print!("{}", asm);
+                Ok(())
+            }
+            Some(path) => std::fs::write(path, &asm),
+        };
+        if let Err(err) = written {
+            eprintln!("crepl build: {}: {}", output.unwrap_or_default(), err);
+            return 1;
+        }
+        return 0;
+    }
+
+    let output = output.unwrap_or_else(|| "a.out".to_string());
+    match codegen::build(&asm, std::path::Path::new(&output)) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("crepl build: {}", err);
+            1
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use ast::printer::{BraceStyle, Printer, Style};
+use parser::Parser;
+
+use super::{read_source, Options};
+
+pub const USAGE: &str =
+    "usage: crepl fmt [--brace=attach|allman] [--indent=N] [--tabs] [--width=N] FILE";
+
+pub fn run(args: &[String]) -> i32 {
+    let opts = match Options::parse(args, &["brace", "indent", "tabs", "width"]) {
+        Ok(opts) if opts.files.len() == 1 => opts,
+        Ok(_) => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+        Err(err) => {
+            eprintln!("crepl fmt: {}\n{}", err, USAGE);
+            return 2;
+        }
+    };
+
+    let style = match style(&opts.settings) {
+        Ok(style) => style,
+        Err(err) => {
+            eprintln!("crepl fmt: {}", err);
+            return 2;
+        }
+    };
+
+    let file = &opts.files[0];
+    let src = match read_source(file) {
+        Ok(src) => src,
+        Err(err) => {
+            eprintln!("crepl fmt: {}: {}", file, err);
+            return 1;
+        }
+    };
+
+    let mut p = Parser::from(opts.scanner(src));
+    let root = p.parse_file();
+
+    // never print a file that did not parse, it would lose code
+    if !p.errors().is_empty() {
+        for (pos, msg) in p.errors() {
+            let pos = p.position(*pos);
+            eprintln!("{}:{}:{}: error: {}", file, pos.line, pos.column, msg);
+        }
+        return 1;
+    }
+
+    print!("{}", Printer::new(style).file(&root));
+
+    0
+}
+
+fn style(settings: &[(String, String)]) -> Result<Style, String> {
+    let mut style = Style::default();
+
+    let number = |name: &str, value: &str| {
+        value
+            .parse::<usize>()
+            .map_err(|_| format!("invalid value '{}' for '--{}'", value, name))
+    };
+
+    for (name, value) in settings {
+        match name.as_str() {
+            "brace" => {
+                style.brace = match value.as_str() {
+                    "attach" => BraceStyle::Attach,
+                    "allman" => BraceStyle::Allman,
+                    _ => return Err(format!("unknown brace style '{}'", value)),
+                }
+            }
+            "indent" => style.indent = number(name, value)?,
+            "width" => style.line_width = number(name, value)?,
+            "tabs" => style.tabs = true,
+            _ => {}
+        }
+    }
+
+    Ok(style)
+}
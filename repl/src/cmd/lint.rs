@@ -0,0 +1,131 @@
+use lint::Linter;
+use parser::Parser;
+use sema::layout::DataLayout;
+use sema::Severity;
+
+use super::{expand, read_source, Options};
+
+pub const USAGE: &str =
+    "usage: crepl lint [-Wname] [-Wno-name] [--layout=lp64|ilp32|llp64] [-trigraphs] FILE";
+
+pub fn run(args: &[String]) -> i32 {
+    let mut linter = Linter::default();
+
+    // -Wname and -Wno-name turn lints on and off, in order
+    let (toggles, args): (Vec<String>, Vec<String>) =
+        args.iter().cloned().partition(|a| a.starts_with("-W"));
+
+    for arg in toggles.iter() {
+        let name = &arg[2..];
+        let (name, on) = match name.strip_prefix("no-") {
+            Some(name) => (name, false),
+            None => (name, true),
+        };
+
+        if !linter.set(name, on) {
+            let names: Vec<_> = linter.names().collect();
+            eprintln!(
+                "crepl lint: unknown warning option '{}' ({})",
+                arg,
+                names.join(", ")
+            );
+            return 2;
+        }
+    }
+
+    let opts = match Options::parse(&args, &["layout"]) {
+        Ok(opts) if opts.files.len() == 1 => opts,
+        Ok(_) => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+        Err(err) => {
+            eprintln!("crepl lint: {}\n{}", err, USAGE);
+            return 2;
+        }
+    };
+
+    let mut layout = DataLayout::default();
+    for (name, value) in opts.settings.iter() {
+        if name == "layout" {
+            match DataLayout::from_name(value) {
+                Some(l) => layout = l,
+                None => {
+                    eprintln!("crepl lint: unknown data layout '{}'", value);
+                    return 2;
+                }
+            }
+        }
+    }
+
+    let file = &opts.files[0];
+    let src = match read_source(file) {
+        Ok(src) => src,
+        Err(err) => {
+            eprintln!("crepl lint: {}: {}", file, err);
+            return 1;
+        }
+    };
+
+    let Some(expanded) = expand(file, &src, &layout) else {
+        return 1;
+    };
+
+    let mut p = Parser::from(opts.scanner(expanded));
+    let root = p.parse_file();
+
+    // the diagnostics of the file itself, not of the headers on the lines
+    // of its directives
+    let own = |pos: usize| {
+        let line = src.split(|&b| b == b'\n').nth(p.position(pos).line - 1);
+        !line.is_some_and(|l| l.trim_ascii_start().starts_with(b"#"))
+    };
+
+    let print = |pos: usize, severity: &str, msg: &str| {
+        let pos = p.position(pos);
+        println!(
+            "{}:{}:{}: {}: {}",
+            file, pos.line, pos.column, severity, msg
+        );
+    };
+
+    if !p.errors().is_empty() {
+        for (pos, msg) in p.errors() {
+            print(*pos, "error", msg);
+        }
+        return 1;
+    }
+
+    let info = sema::check_with(&root, layout);
+    let severity = |s: Severity| match s {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+    };
+
+    // the lints need well typed code
+    if info.errors().count() > 0 {
+        for d in info.diagnostics.iter().filter(|d| own(d.pos)) {
+            print(d.pos, severity(d.severity), &d.msg);
+        }
+        return 1;
+    }
+
+    // the warnings of the checker and of the lints, in source order
+    let mut warnings: Vec<_> = info
+        .diagnostics
+        .iter()
+        .map(|d| (d.pos, severity(d.severity), d.msg.clone()))
+        .collect();
+    for w in linter.run(&root, &info, |pos| p.position(pos).line) {
+        warnings.push((w.pos, "warning", format!("{} [-W{}]", w.msg, w.lint)));
+    }
+    warnings.retain(|w| own(w.0));
+    warnings.sort_by_key(|w| w.0);
+
+    for (pos, severity, msg) in warnings.iter() {
+        print(*pos, severity, msg);
+    }
+
+    0
+}
@@ -0,0 +1,193 @@
+use std::io::Read;
+
+use sema::layout::DataLayout;
+
+pub mod ast;
+pub mod build;
+pub mod fmt;
+pub mod lint;
+pub mod run;
+pub mod tokens;
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub format: Option<String>,
+    pub trigraphs: bool,
+    pub files: Vec<String>,
+
+    pub settings: Vec<(String, String)>, // `--name=value` options of a command
+}
+
+impl Options {
+    /// Parses the arguments of a command, `settings` lists the names of the
+    /// `--name=value` options it accepts besides the common ones.
+    pub fn parse(args: &[String], settings: &[&str]) -> Result<Self, String> {
+        let mut opts = Self::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-trigraphs" | "--trigraphs" => opts.trigraphs = true,
+
+                "-f" | "--format" => match args.next() {
+                    Some(format) => opts.format = Some(format.clone()),
+                    None => return Err(format!("missing value for '{}'", arg)),
+                },
+
+                _ if arg.starts_with("--format=") => {
+                    opts.format = Some(arg["--format=".len()..].to_string())
+                }
+
+                _ if arg.starts_with("--") => {
+                    let (name, value) = arg[2..].split_once('=').unwrap_or((&arg[2..], ""));
+
+                    if !settings.contains(&name) {
+                        return Err(format!("unknown option '{}'", arg));
+                    }
+                    opts.settings.push((name.to_string(), value.to_string()));
+                }
+
+                "-" => opts.files.push(arg.clone()),
+                _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+                _ => opts.files.push(arg.clone()),
+            }
+        }
+
+        Ok(opts)
+    }
+
+    pub fn scanner(&self, src: Vec<u8>) -> scanner::Scanner {
+        let s = scanner::Scanner::from(src);
+
+        if self.trigraphs {
+            s.with_trigraphs()
+        } else {
+            s
+        }
+    }
+}
+
+/// The `--name=value` options of the limits of the interpreter.
+pub const LIMITS: [&str; 4] = ["max-steps", "max-depth", "max-heap", "timeout"];
+
+/// Sets the limit an option of `LIMITS` names, `none` removes it, or for
+/// `max-depth` sets the most the stack has room for. Sizes take a k, M or G
+/// suffix and times an ms or s one, seconds by default.
+pub fn set_limit(limits: &mut interp::Limits, name: &str, value: &str) -> Result<(), String> {
+    let invalid = || format!("invalid value '{}' for '{}'", value, name);
+    let none = value == "none";
+
+    match name {
+        "max-steps" => {
+            limits.steps = match none {
+                true => None,
+                false => Some(value.parse().map_err(|_| invalid())?),
+            };
+        }
+        "max-depth" => {
+            limits.depth = match none {
+                true => run::MAX_DEPTH,
+                false => match value.parse().map_err(|_| invalid())? {
+                    depth if depth > run::MAX_DEPTH => {
+                        return Err(format!("'{}' is at most {}", name, run::MAX_DEPTH))
+                    }
+                    depth => depth,
+                },
+            };
+        }
+        "max-heap" => {
+            let (digits, unit) = match value.char_indices().last() {
+                Some((i, 'k' | 'K')) => (&value[..i], 1 << 10),
+                Some((i, 'M')) => (&value[..i], 1 << 20),
+                Some((i, 'G')) => (&value[..i], 1 << 30),
+                _ => (value, 1),
+            };
+            limits.heap = match none {
+                true => None,
+                false => Some(digits.parse::<u64>().map_err(|_| invalid())? * unit),
+            };
+        }
+        "timeout" => {
+            let (digits, unit) = match value.strip_suffix("ms") {
+                Some(ms) => (ms, 1e-3),
+                None => (value.strip_suffix('s').unwrap_or(value), 1.0),
+            };
+            limits.time = match none {
+                true => None,
+                false => {
+                    let secs = digits
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|s| s.is_finite() && *s >= 0.0);
+                    Some(std::time::Duration::from_secs_f64(
+                        secs.ok_or_else(invalid)? * unit,
+                    ))
+                }
+            };
+        }
+        _ => return Err(format!("unknown limit '{}'", name)),
+    }
+    Ok(())
+}
+
+/// Replaces the includes of a source file by their headers, which take the
+/// lines of their directives. Prints the error of an invalid directive.
+pub fn expand(file: &str, src: &[u8], layout: &DataLayout) -> Option<Vec<u8>> {
+    match interp::include::expand(src, layout) {
+        Ok(expanded) => Some(expanded),
+        Err((pos, msg)) => {
+            let line = src[..pos].iter().filter(|&&b| b == b'\n').count() + 1;
+            let column = pos
+                - src[..pos]
+                    .iter()
+                    .rposition(|&b| b == b'\n')
+                    .map_or(0, |i| i + 1)
+                + 1;
+            eprintln!("{}:{}:{}: error: {}", file, line, column, msg);
+            None
+        }
+    }
+}
+
+/// Reads a whole source file, `-` stands for the standard input.
+pub fn read_source(path: &str) -> std::io::Result<Vec<u8>> {
+    if path == "-" {
+        let mut src = Vec::new();
+        std::io::stdin().read_to_end(&mut src)?;
+        Ok(src)
+    } else {
+        std::fs::read(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_limit_depth() {
+        let cases = [
+            ("100", Ok(100)),
+            ("none", Ok(run::MAX_DEPTH)),
+            (&run::MAX_DEPTH.to_string(), Ok(run::MAX_DEPTH)),
+            (
+                &(run::MAX_DEPTH + 1).to_string(),
+                Err(format!("'max-depth' is at most {}", run::MAX_DEPTH)),
+            ),
+            ("-1", Err("invalid value '-1' for 'max-depth'".to_string())),
+        ];
+
+        for (i, (value, expected)) in cases.iter().enumerate() {
+            let mut limits = interp::Limits::default();
+            let result = set_limit(&mut limits, "max-depth", value).map(|_| limits.depth);
+            assert_eq!(
+                *expected,
+                result,
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                cases.len(),
+                value
+            );
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use interp::{Engine, Interpreter};
+use parser::Parser;
+use sema::layout::DataLayout;
+
+use super::{expand, read_source, set_limit, Options, LIMITS};
+
+pub const USAGE: &str =
+    "usage: crepl run [--checked] [--engine=vm|ast] [--layout=lp64|ilp32|llp64] [--load=LIB]... [--max-steps=N] [--max-depth=N] [--max-heap=SIZE] [--timeout=SECS] [-trigraphs] FILE [ARGS...]";
+
+// the interpreter recurses on the host stack, the programs run on a thread
+// with room for deep recursion, see `spawn`
+pub const STACK_SIZE: usize = 256 << 20;
+
+// the host stack a call of the interpreter takes at most, with room to spare
+// for the nested statements and expressions of debug builds
+const FRAME_SIZE: usize = 64 << 10;
+
+/// The deepest calls `STACK_SIZE` has room for, `--max-depth` stops there.
+pub const MAX_DEPTH: usize = STACK_SIZE / FRAME_SIZE;
+
+// the calls of a stack trace that are printed, the others are counted
+pub const MAX_TRACE: usize = 32;
+
+/// Runs `f` on a thread with a stack of `STACK_SIZE` and waits for it,
+/// returns None if it panicked.
+pub fn spawn<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> Option<T> {
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(f)
+        .expect("cannot start the interpreter")
+        .join()
+        .ok()
+}
+
+pub fn run(args: &[String]) -> i32 {
+    let args = args.to_vec();
+    spawn(move || exec(&args)).unwrap_or(1)
+}
+
+fn exec(args: &[String]) -> i32 {
+    // the arguments after the file are the program's
+    let split = args
+        .iter()
+        .position(|a| a == "-" || !a.starts_with('-'))
+        .map_or(args.len(), |i| i + 1);
+    let (args, argv) = args.split_at(split);
+
+    let settings = [&["layout", "checked", "engine", "load"][..], &LIMITS].concat();
+    let opts = match Options::parse(args, &settings) {
+        Ok(opts) if opts.files.len() == 1 => opts,
+        Ok(_) => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+        Err(err) => {
+            eprintln!("crepl run: {}\n{}", err, USAGE);
+            return 2;
+        }
+    };
+
+    let mut layout = DataLayout::default();
+    let mut checked = false;
+    let mut engine = Engine::default();
+    let mut limits = interp::Limits::default();
+    for (name, value) in opts.settings.iter() {
+        if LIMITS.contains(&name.as_str()) {
+            if let Err(err) = set_limit(&mut limits, name, value) {
+                eprintln!("crepl run: {}", err);
+                return 2;
+            }
+        } else if name == "checked" {
+            checked = true;
+        } else if name == "engine" {
+            match value.as_str() {
+                "vm" => engine = Engine::Vm,
+                "ast" => engine = Engine::Ast,
+                _ => {
+                    eprintln!("crepl run: unknown engine '{}' (vm or ast)", value);
+                    return 2;
+                }
+            }
+        } else if name == "load" {
+            if let Err(err) = interp::ffi::load(value) {
+                eprintln!("crepl run: {}", err);
+                return 1;
+            }
+        } else if name == "layout" {
+            match DataLayout::from_name(value) {
+                Some(l) => layout = l,
+                None => {
+                    eprintln!("crepl run: unknown data layout '{}'", value);
+                    return 2;
+                }
+            }
+        }
+    }
+
+    let file = opts.files[0].clone();
+    let src = match read_source(&file) {
+        Ok(src) => src,
+        Err(err) => {
+            eprintln!("crepl run: {}: {}", file, err);
+            return 1;
+        }
+    };
+
+    let Some(src) = expand(&file, &src, &layout) else {
+        return 1;
+    };
+
+    let mut p = Parser::from(opts.scanner(src));
+    let root = p.parse_file();
+
+    let print = |pos: usize, msg: &str| {
+        let pos = p.position(pos);
+        eprintln!("{}:{}:{}: error: {}", file, pos.line, pos.column, msg);
+    };
+
+    if !p.errors().is_empty() {
+        for (pos, msg) in p.errors() {
+            print(*pos, msg);
+        }
+        return 1;
+    }
+
+    let info = sema::check_with(&root, layout);
+    if info.errors().count() > 0 {
+        for d in info.errors() {
+            print(d.pos, &d.msg);
+        }
+        return 1;
+    }
+
+    let mut args = vec![file.clone()];
+    args.extend(argv.iter().cloned());
+
+    let mut it = Interpreter::new(&root, &info);
+    it.checked = checked;
+    it.engine = engine;
+    it.limits = limits;
+    let status = it.run_main(&args);
+
+    match status {
+        Ok(status) => status,
+        Err(err) => {
+            let pos = p.position(err.pos);
+            eprintln!(
+                "{}:{}:{}: runtime error: {}",
+                file, pos.line, pos.column, err.msg
+            );
+
+            for f in err.trace.iter().take(MAX_TRACE) {
+                let pos = p.position(f.pos);
+                eprintln!("    in {} at {}:{}:{}", f.func, file, pos.line, pos.column);
+            }
+            if err.trace.len() > MAX_TRACE {
+                eprintln!("    ... {} more calls", err.trace.len() - MAX_TRACE);
+            }
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_depth() {
+        // main and the calls of f fill the stack to the limit
+        let src = format!(
+            "int f(int n) {{ struct {{ int a[8]; }} s = {{{{n}}}}; \
+             return n == 0 ? 0 : (s.a[0] - n + 1) + f(n - 1); }}\n\
+             int main(void) {{ return f({}) == {} ? 0 : 1; }}\n",
+            MAX_DEPTH - 2,
+            MAX_DEPTH - 2
+        );
+
+        let status = spawn(move || {
+            let mut p = Parser::from(src);
+            let root = p.parse_file();
+            assert!(p.errors().is_empty(), "{:?}", p.errors());
+            let info = sema::check(&root);
+
+            let mut limits = interp::Limits::default();
+            set_limit(&mut limits, "max-depth", "none").unwrap();
+
+            [Engine::Ast, Engine::Vm].map(|engine| {
+                let mut it = Interpreter::new(&root, &info);
+                it.engine = engine;
+                it.limits = limits.clone();
+                it.run_main(&[]).map_err(|err| err.msg)
+            })
+        });
+
+        assert_eq!(Some([Ok(0), Ok(0)]), status);
+    }
+}
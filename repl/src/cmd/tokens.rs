@@ -0,0 +1,361 @@
+use std::io::Write;
+
+use serde::Serialize;
+use token::Token;
+
+use super::{read_source, Options};
+
+pub const USAGE: &str = "usage: crepl tokens [--format text|json|clang] [-trigraphs] FILE";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,  // aligned columns
+    Json,  // one JSON object per line
+    Clang, // compatible with `clang -fsyntax-only -Xclang -dump-tokens`
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" | "jsonl" => Ok(Self::Json),
+            "clang" => Ok(Self::Clang),
+            _ => Err(format!("unknown token format '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Row {
+    pub kind: String,
+    pub text: String,
+    pub line: usize,
+    pub column: usize,
+
+    #[serde(skip)]
+    tok: Token,
+    #[serde(skip)]
+    start_of_line: bool,
+    #[serde(skip)]
+    leading_space: bool,
+}
+
+/// Scans the whole source, the returned errors are formatted as
+/// `line:column: error: message`.
+pub fn rows(mut s: scanner::Scanner) -> (Vec<Row>, Vec<String>) {
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+
+    let mut prev_end = 0;
+    let mut prev_line = 0;
+
+    loop {
+        let (tok, pos, text) = match s.scan() {
+            Ok((tok, pos, lit)) => (tok, pos, lit.to_string()),
+            Err((_, pos, _, msg)) => {
+                let p = s.position(pos);
+                errors.push(format!("{}:{}: error: {}", p.line, p.column, msg));
+                continue;
+            }
+        };
+
+        let p = s.position(pos);
+
+        // whitespace or comments between the previous token on this line
+        let gap = &s.src()[prev_end.min(pos)..pos];
+        let leading_space = gap.rsplit('\n').next().is_some_and(|g| !g.is_empty());
+
+        rows.push(Row {
+            kind: format!("{:?}", tok),
+            text: text.clone(),
+            line: p.line,
+            column: p.column,
+            tok,
+            start_of_line: p.line != prev_line,
+            leading_space,
+        });
+
+        if tok == Token::EOF {
+            break;
+        }
+
+        if tok != Token::COMMENT {
+            prev_end = pos + text.len();
+            prev_line = p.line;
+        }
+    }
+
+    (rows, errors)
+}
+
+pub fn write(w: &mut dyn Write, rows: &[Row], format: Format, file: &str) -> std::io::Result<()> {
+    match format {
+        Format::Text => {
+            let locs: Vec<_> = rows
+                .iter()
+                .map(|r| format!("{}:{}", r.line, r.column))
+                .collect();
+
+            let loc_width = locs.iter().map(|l| l.len()).max().unwrap_or(0);
+            let kind_width = rows.iter().map(|r| r.kind.len()).max().unwrap_or(0);
+
+            for (r, loc) in rows.iter().zip(locs) {
+                let line = format!("{:loc_width$}  {:kind_width$}  {}", loc, r.kind, r.text);
+                writeln!(w, "{}", line.trim_end())?;
+            }
+        }
+
+        Format::Json => {
+            for r in rows {
+                writeln!(w, "{}", serde_json::to_string(r)?)?;
+            }
+        }
+
+        Format::Clang => {
+            for r in rows.iter().filter(|r| r.tok != Token::COMMENT) {
+                let mut flags = String::new();
+                if r.start_of_line {
+                    flags.push_str(" [StartOfLine]");
+                }
+                if r.leading_space {
+                    flags.push_str(" [LeadingSpace]");
+                }
+
+                writeln!(
+                    w,
+                    "{} '{}'\t{}\tLoc=<{}:{}:{}>",
+                    clang_kind(r.tok, &r.text),
+                    r.text,
+                    flags,
+                    file,
+                    r.line,
+                    r.column
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn run(args: &[String]) -> i32 {
+    let opts = match Options::parse(args, &[]) {
+        Ok(opts) if opts.files.len() == 1 => opts,
+        Ok(_) => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+        Err(err) => {
+            eprintln!("crepl tokens: {}\n{}", err, USAGE);
+            return 2;
+        }
+    };
+
+    let format = match opts.format.as_deref().unwrap_or("text").parse() {
+        Ok(format) => format,
+        Err(err) => {
+            eprintln!("crepl tokens: {}", err);
+            return 2;
+        }
+    };
+
+    let file = &opts.files[0];
+    let src = match read_source(file) {
+        Ok(src) => src,
+        Err(err) => {
+            eprintln!("crepl tokens: {}: {}", file, err);
+            return 1;
+        }
+    };
+
+    let (rows, errors) = rows(opts.scanner(src));
+
+    for err in &errors {
+        eprintln!("{}:{}", file, err);
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    match write(&mut stdout, &rows, format, file) {
+        Err(err) if err.kind() != std::io::ErrorKind::BrokenPipe => {
+            eprintln!("crepl tokens: {}", err);
+            return 1;
+        }
+        _ => {}
+    }
+
+    if errors.is_empty() {
+        0
+    } else {
+        1
+    }
+}
+
+// returns the token kind name used by clang
+fn clang_kind(tok: Token, text: &str) -> String {
+    use Token::*;
+
+    // keywords are named after their spelling
+    if tok.is_keyword() {
+        return tok.to_str();
+    }
+
+    let prefix = |quote| text.split(quote).next().unwrap_or_default();
+
+    match tok {
+        EOF => "eof",
+        COMMENT => "comment",
+        IDENT => "identifier",
+        INTEGER | FLOATING => "numeric_constant",
+        STRING => match prefix('"') {
+            "L" => "wide_string_literal",
+            "u8" => "utf8_string_literal",
+            "u" => "utf16_string_literal",
+            "U" => "utf32_string_literal",
+            _ => "string_literal",
+        },
+        CHARACTER => match prefix('\'') {
+            "L" => "wide_char_constant",
+            "u8" => "utf8_char_constant",
+            "u" => "utf16_char_constant",
+            "U" => "utf32_char_constant",
+            _ => "char_constant",
+        },
+
+        ASSIGN => "equal",
+        PLUS => "plus",
+        MINUS => "minus",
+        ASTERISK => "star",
+        SLASH => "slash",
+        REM => "percent",
+        BANG | NOT => "exclaim",
+        TILDE => "tilde",
+        AND => "amp",
+        OR => "pipe",
+        XOR => "caret",
+        DOT => "period",
+        TERNERY => "question",
+        INC => "plusplus",
+        DEC => "minusminus",
+        ARROW => "arrow",
+        LT => "less",
+        GT => "greater",
+        LAND => "ampamp",
+        LOR => "pipepipe",
+        EQL => "equalequal",
+        NEQ => "exclaimequal",
+        LEQ => "lessequal",
+        GEQ => "greaterequal",
+        SHL => "lessless",
+        SHR => "greatergreater",
+        PLUS_ASSIGN => "plusequal",
+        MINUS_ASSIGN => "minusequal",
+        MUL_ASSIGN => "starequal",
+        DIV_ASSIGN => "slashequal",
+        REM_ASSIGN => "percentequal",
+        AND_ASSIGN => "ampequal",
+        OR_ASSIGN => "pipeequal",
+        XOR_ASSIGN => "caretequal",
+        SHL_ASSIGN => "lesslessequal",
+        SHR_ASSIGN => "greatergreaterequal",
+        ELLIPSE => "ellipsis",
+        HASH => "hash",
+        HASH_HASH => "hashhash",
+        LPAREN => "l_paren",
+        LBRACK => "l_square",
+        LBRACE => "l_brace",
+        COMMA => "comma",
+        RPAREN => "r_paren",
+        RBRACK => "r_square",
+        RBRACE => "r_brace",
+        SEMICOLON => "semi",
+        COLON => "colon",
+
+        _ => "unknown",
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_clang() {
+        let source = "int main() {\n  return L'x'; /* c */\n}\n";
+
+        let expected = "\
+int 'int'\t [StartOfLine]\tLoc=<t.c:1:1>
+identifier 'main'\t [LeadingSpace]\tLoc=<t.c:1:5>
+l_paren '('\t\tLoc=<t.c:1:9>
+r_paren ')'\t\tLoc=<t.c:1:10>
+l_brace '{'\t [LeadingSpace]\tLoc=<t.c:1:12>
+return 'return'\t [StartOfLine] [LeadingSpace]\tLoc=<t.c:2:3>
+wide_char_constant 'L'x''\t [LeadingSpace]\tLoc=<t.c:2:10>
+semi ';'\t\tLoc=<t.c:2:14>
+r_brace '}'\t [StartOfLine]\tLoc=<t.c:3:1>
+eof ''\t [StartOfLine]\tLoc=<t.c:4:1>
+";
+
+        let (rows, errors) = rows(scanner::Scanner::from(source.to_string()));
+        assert!(errors.is_empty());
+
+        let mut out = Vec::new();
+        write(&mut out, &rows, Format::Clang, "t.c").unwrap();
+
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_write_text() {
+        let source = "x = 10;\n// done\n";
+
+        let expected = "\
+1:1  IDENT      x
+1:3  ASSIGN     =
+1:5  INTEGER    10
+1:7  SEMICOLON  ;
+2:1  COMMENT    // done
+3:1  EOF
+";
+
+        let (rows, errors) = rows(scanner::Scanner::from(source.to_string()));
+        assert!(errors.is_empty());
+
+        let mut out = Vec::new();
+        write(&mut out, &rows, Format::Text, "t.c").unwrap();
+
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_write_json() {
+        let source = "puts(\"a\\\"b\\\\\");";
+
+        let expected = r#"{"kind":"IDENT","text":"puts","line":1,"column":1}
+{"kind":"LPAREN","text":"(","line":1,"column":5}
+{"kind":"STRING","text":"\"a\\\"b\\\\\"","line":1,"column":6}
+{"kind":"RPAREN","text":")","line":1,"column":14}
+{"kind":"SEMICOLON","text":";","line":1,"column":15}
+{"kind":"EOF","text":"","line":1,"column":16}
+"#;
+
+        let (rows, errors) = rows(scanner::Scanner::from(source.to_string()));
+        assert!(errors.is_empty());
+
+        let mut out = Vec::new();
+        write(&mut out, &rows, Format::Json, "t.c").unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(expected, out);
+
+        // each line is an object of its own
+        for line in out.lines() {
+            let v: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert!(v.is_object());
+        }
+        let v: serde_json::Value = serde_json::from_str(out.lines().nth(2).unwrap()).unwrap();
+        assert_eq!(Some(rows[2].text.as_str()), v["text"].as_str());
+    }
+}
@@ -0,0 +1,420 @@
+//! Tab completion in the REPL. The word before the cursor completes to the
+//! REPL's commands at the start of a line, to a bundled header after
+//! `#include <`, to a member after `.` or `->`, to a tag after `struct`,
+//! `union` or `enum`, and to a keyword, a name the session declares or a
+//! function of the library elsewhere. Each completion is shown with what
+//! it is: `sq: int (int)`, `x: int` or `int: keyword`.
+
+use rustyline::completion::Candidate;
+use sema::layout::DataLayout;
+use sema::types::{Type, TypeKind};
+use sema::{ScopeKind, SymbolKind};
+use token::Token;
+
+use crate::editor;
+use crate::theme::Class;
+
+/// The commands of the REPL, and what they do.
+pub const COMMANDS: [(&str, &str); 10] = [
+    (":type", "print the type of an expression"),
+    (":check", "check declarations with the lints"),
+    (":layout", "print the layout of a struct or union"),
+    (":ir", "print the SSA form of functions"),
+    (":bench", "time a program with both engines"),
+    (":limits", "set the limits of the programs"),
+    (":load", "load a shared library"),
+    (":theme", "set the colours of the highlighting"),
+    (":tokens", "print the tokens of a line"),
+    (":reset", "start a new program"),
+];
+
+// the function the expression before a member access is typed in
+const PROBE: &str = "__crepl_complete";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub name: String,
+    pub detail: String, // the type or signature, or what it is
+    display: String,
+    replacement: String,
+}
+
+impl Completion {
+    fn new(name: &str, detail: String) -> Self {
+        Self::with_replacement(name, detail, name.to_string())
+    }
+
+    fn with_replacement(name: &str, detail: String, replacement: String) -> Self {
+        Self {
+            name: name.to_string(),
+            display: format!("{}: {}", name, detail),
+            detail,
+            replacement,
+        }
+    }
+}
+
+impl Candidate for Completion {
+    fn display(&self) -> &str {
+        &self.display
+    }
+
+    fn replacement(&self) -> &str {
+        &self.replacement
+    }
+}
+
+/// Completes the word before `pos` in an input typed after the
+/// declarations `decls` of the session, returns where the word starts and
+/// its completions sorted by name.
+pub fn complete(
+    decls: &str,
+    layout: DataLayout,
+    line: &str,
+    pos: usize,
+) -> (usize, Vec<Completion>) {
+    let before = &line[..pos];
+
+    // a command
+    let text = before.trim_start();
+    if text.starts_with(':') && !text.contains(char::is_whitespace) {
+        let found = COMMANDS
+            .iter()
+            .filter(|(name, _)| name.starts_with(text))
+            .map(|(name, what)| Completion::new(name, what.to_string()));
+        return (pos - text.len(), sorted(found));
+    }
+
+    // a header
+    let current = before.rsplit('\n').next().unwrap();
+    if let Some(prefix) = header(current) {
+        let found = interp::include::headers()
+            .filter(|name| name.starts_with(prefix))
+            .map(|name| {
+                Completion::with_replacement(name, "header".to_string(), format!("{}>", name))
+            });
+        return (pos - prefix.len(), sorted(found));
+    }
+
+    // nothing in comments and literals
+    if pos > 0 {
+        let classes = editor::classes(line, None);
+        if matches!(classes[pos - 1], Some(Class::Comment | Class::String)) {
+            return (pos, Vec::new());
+        }
+    }
+
+    let start = before
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| !scanner::is_letter_or_digit(c))
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    let word = &before[start..];
+    if word.starts_with(|c: char| c.is_ascii_digit()) {
+        return (pos, Vec::new());
+    }
+    let lead = before[..start].trim_end();
+
+    let found = if let Some(x) = lead.strip_suffix("->") {
+        members(decls, layout, operand(x), true)
+    } else if let Some(x) = lead.strip_suffix('.') {
+        members(decls, layout, operand(x), false)
+    } else if let Some(kind) = tag_keyword(lead) {
+        tags(decls, layout, kind)
+    } else {
+        names(decls, layout)
+    };
+    let found = found.into_iter().filter(|c| c.name.starts_with(word));
+    (start, sorted(found))
+}
+
+// the name of a header being typed after `#include <`
+fn header(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix('#')?;
+    let rest = rest.trim_start().strip_prefix("include")?;
+    let name = rest.trim_start().strip_prefix('<')?;
+    (!name.contains(|c: char| c == '>' || c.is_whitespace())).then_some(name)
+}
+
+// the kind of tag of the keyword at the end of a text
+fn tag_keyword(text: &str) -> Option<Token> {
+    let start = text
+        .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .map_or(0, |i| i + 1);
+    match token::lookup(&text[start..]) {
+        tok @ (Token::STRUCT | Token::UNION | Token::ENUM) => Some(tok),
+        _ => None,
+    }
+}
+
+/// Returns the postfix expression at the end of a text: names, calls,
+/// subscripts and member accesses, e.g. `ps[i].next->`.
+fn operand(text: &str) -> &str {
+    let b = text.as_bytes();
+    // the bytes of non-ASCII characters are taken as parts of identifiers,
+    // so the operand starts at a character boundary
+    let ident = |c: u8| c.is_ascii_alphanumeric() || c == b'_' || !c.is_ascii();
+    let skip = |mut i: usize| {
+        while i > 0 && b[i - 1].is_ascii_whitespace() {
+            i -= 1;
+        }
+        i
+    };
+
+    let mut i = skip(b.len());
+    let end = i;
+    loop {
+        let group = match b.get(i.wrapping_sub(1)) {
+            Some(&c) if ident(c) => {
+                while i > 0 && ident(b[i - 1]) {
+                    i -= 1;
+                }
+                false
+            }
+            Some(b')' | b']') => {
+                // back to the bracket it closes
+                let mut depth = 0;
+                loop {
+                    i -= 1;
+                    match b[i] {
+                        b')' | b']' => depth += 1,
+                        b'(' | b'[' => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                    if i == 0 {
+                        return "";
+                    }
+                }
+                true
+            }
+            _ => break,
+        };
+
+        // what the part follows
+        let j = skip(i);
+        if text[..j].ends_with("->") {
+            i = skip(j - 2);
+        } else if text[..j].ends_with('.') {
+            i = skip(j - 1);
+        } else if group && j > 0 && (ident(b[j - 1]) || matches!(b[j - 1], b')' | b']')) {
+            i = j;
+        } else {
+            break;
+        }
+    }
+    text[i..end].trim()
+}
+
+// checks the declarations of the session, with an expression typed in a
+// function after them
+fn check(decls: &str, layout: DataLayout, x: &str) -> Option<(sema::Info, usize)> {
+    let src = format!("{}void {}(void) {{\n{};\n}}\n", decls, PROBE, x);
+    let src = interp::include::expand(src.as_bytes(), &layout).ok()?;
+
+    // the function is past the directives, so its offsets are kept
+    let start = src.len() - x.len() - ";\n}\n".len();
+    let mut p = parser::Parser::from(String::from_utf8(src).ok()?);
+    let file = p.parse_file();
+    Some((sema::check_with(&file, layout), start))
+}
+
+// the members of the struct or union an expression is, or points to
+fn members(decls: &str, layout: DataLayout, x: &str, arrow: bool) -> Vec<Completion> {
+    if x.is_empty() {
+        return Vec::new();
+    }
+    let Some((info, start)) = check(decls, layout, x) else {
+        return Vec::new();
+    };
+    let Some(ty) = info.types.get(&(start, start + x.len())) else {
+        return Vec::new();
+    };
+    let ty = match arrow {
+        true => ty.pointee().or(ty.elem()),
+        false => Some(ty),
+    };
+
+    let mut found = Vec::new();
+    if let Some(ty) = ty {
+        add_members(&info, ty, &mut found);
+    }
+    found
+}
+
+fn add_members(info: &sema::Info, ty: &Type, found: &mut Vec<Completion>) {
+    let (TypeKind::Struct(id) | TypeKind::Union(id)) = ty.kind else {
+        return;
+    };
+    for m in info.tags[id.0].members.iter().flatten() {
+        match &m.name {
+            Some(name) => {
+                let detail = match m.bits {
+                    Some(bits) => format!("{} : {}", info.spell(&m.ty), bits),
+                    None => info.spell(&m.ty),
+                };
+                found.push(Completion::new(name, detail));
+            }
+            // the members of an anonymous struct or union are its own
+            None => add_members(info, &m.ty, found),
+        }
+    }
+}
+
+// the tags of a kind the session declares
+fn tags(decls: &str, layout: DataLayout, kind: Token) -> Vec<Completion> {
+    let Some((info, _)) = check(decls, layout, "0") else {
+        return Vec::new();
+    };
+    info.symbols
+        .iter()
+        .filter(|s| s.kind == SymbolKind::Tag(kind) && at_file_scope(&info, s))
+        .map(|s| Completion::new(&s.name, info.spell(&s.ty)))
+        .collect()
+}
+
+// the keywords, the names the session declares and the functions of the
+// library
+fn names(decls: &str, layout: DataLayout) -> Vec<Completion> {
+    let mut found: Vec<Completion> = token::maps::KEYWORDS
+        .keys()
+        .map(|k| Completion::new(k, "keyword".to_string()))
+        .collect();
+    if let Some((info, _)) = check(decls, layout, "0") {
+        found.extend(declared(&info, |_| true));
+    }
+
+    let headers: String = interp::include::headers()
+        .map(|h| format!("#include <{}>\n", h))
+        .collect();
+    if let Some((info, _)) = check(&headers, layout, "0") {
+        // after the names of the session, which `sorted` keeps
+        found.extend(declared(&info, |s| s.kind == SymbolKind::Func));
+    }
+    found
+}
+
+// the ordinary identifiers declared at file scope
+fn declared<'a>(
+    info: &'a sema::Info,
+    filter: impl Fn(&sema::Symbol) -> bool + 'a,
+) -> impl Iterator<Item = Completion> + 'a {
+    info.symbols
+        .iter()
+        .filter(move |s| at_file_scope(info, s) && !s.name.starts_with("__") && filter(s))
+        .filter_map(|s| {
+            let detail = match s.kind {
+                SymbolKind::Var | SymbolKind::Func => info.spell(&s.ty),
+                SymbolKind::Typedef => format!("typedef {}", info.spell(&s.ty)),
+                SymbolKind::EnumConst => format!("{} = {}", info.spell(&s.ty), s.value?),
+                _ => return None,
+            };
+            Some(Completion::new(&s.name, detail))
+        })
+}
+
+fn at_file_scope(info: &sema::Info, s: &sema::Symbol) -> bool {
+    info.scope(s.scope).kind == ScopeKind::File
+}
+
+// sorts completions by name, the first of the same name is kept
+fn sorted(found: impl Iterator<Item = Completion>) -> Vec<Completion> {
+    let mut found: Vec<Completion> = found.collect();
+    found.sort_by(|a, b| a.name.cmp(&b.name));
+    found.dedup_by(|a, b| a.name == b.name);
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DECLS: &str = "#include <stdio.h>\n\
+        struct point { int x, y; };\n\
+        struct node { int value : 4; struct node *next; union { long l; double d; }; };\n\
+        enum color { RED, GREEN };\n\
+        typedef struct point point;\n\
+        struct point p, café;\n\
+        struct node *list;\n\
+        point ps[3];\n\
+        int square(int n) { return n * n; }\n";
+
+    #[test]
+    fn test_complete() {
+        let tests = [
+            (
+                ":t",
+                vec![
+                    ":theme: set the colours of the highlighting",
+                    ":tokens: print the tokens of a line",
+                    ":type: print the type of an expression",
+                ],
+            ),
+            (
+                "#include <st",
+                vec![
+                    "stddef.h: header",
+                    "stdio.h: header",
+                    "stdlib.h: header",
+                    "string.h: header",
+                ],
+            ),
+            ("p.", vec!["x: int", "y: int"]),
+            ("ps[1].y", vec!["y: int"]),
+            ("(&p)->", vec!["x: int", "y: int"]),
+            (
+                "list->next->",
+                vec![
+                    "d: double",
+                    "l: long",
+                    "next: struct node *",
+                    "value: int : 4",
+                ],
+            ),
+            ("p->", vec![]),
+            ("struct n", vec!["node: struct node"]),
+            ("squ", vec!["square: int (int)"]),
+            ("sw", vec!["switch: keyword"]),
+            ("x = GR", vec!["GREEN: int = 1"]),
+            ("poi", vec!["point: typedef struct point"]),
+            ("print", vec!["printf: int (const char *, ...)"]),
+            ("strl", vec!["strlen: unsigned long (const char *)"]),
+            ("puts(\"squ", vec![]),
+            ("x = 1.", vec![]),
+            ("int naïve", vec![]),
+            ("x = caf", vec!["café: struct point"]),
+            ("café.", vec!["x: int", "y: int"]),
+        ];
+        for (i, (input, want)) in tests.iter().enumerate() {
+            let (_, found) = complete(DECLS, DataLayout::default(), input, input.len());
+            let got: Vec<&str> = found.iter().map(|c| c.display()).collect();
+            assert_eq!(
+                *want,
+                got,
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_replacement() {
+        let (start, found) = complete("", DataLayout::default(), "#include <ctyp", 14);
+        assert_eq!(10, start);
+        assert_eq!(
+            vec!["ctype.h>"],
+            found.iter().map(|c| c.replacement()).collect::<Vec<_>>()
+        );
+
+        let (start, found) = complete("", DataLayout::default(), "x = ret; y", 7);
+        assert_eq!(4, start);
+        assert_eq!(
+            vec!["return"],
+            found.iter().map(|c| c.replacement()).collect::<Vec<_>>()
+        );
+    }
+}
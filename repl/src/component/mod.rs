@@ -62,7 +62,7 @@ impl Text {
 
         self.text.lines().for_each(|s| {
             height += 1;
-            width = s.len().max(width);
+            width = s.chars().count().max(width);
         });
 
         (width as u16, height)
@@ -0,0 +1,479 @@
+//! The line editor of the REPL: a rustyline helper that keeps reading
+//! lines while the input is incomplete, see `incomplete`, highlights the
+//! input as it is typed, see `classes`, and completes the word at the
+//! cursor, see `complete::complete`. The editor indents the lines that
+//! continue an input with non-breaking spaces as wide as `CONTINUATION`,
+//! which is shown in their place, the indentation typed after it is kept.
+
+use std::borrow::Cow;
+use std::cell::Cell;
+
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Cmd, ConditionalEventHandler, Context, Event, EventContext, RepeatCount};
+use sema::layout::DataLayout;
+use token::Token;
+
+use crate::complete::{self, Completion};
+use crate::theme::{Class, Theme};
+
+pub const PROMPT: &str = "> ";
+pub const CONTINUATION: &str = "... ";
+
+#[derive(Default)]
+pub struct Helper {
+    pub theme: Theme,
+    pub decls: String, // of the session, for completions
+    pub layout: DataLayout,
+    brackets: Cell<bool>, // whether the brackets at the cursor are matched
+}
+
+impl rustyline::Helper for Helper {}
+
+impl Completer for Helper {
+    type Candidate = Completion;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context,
+    ) -> rustyline::Result<(usize, Vec<Completion>)> {
+        let (src, at) = spaced(line, pos);
+        let (start, found) = complete::complete(&self.decls, self.layout, &src, at);
+        Ok((start + pos - at, found))
+    }
+}
+
+impl Hinter for Helper {
+    type Hint = String;
+}
+
+impl Highlighter for Helper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        let (src, pos) = spaced(line, pos);
+        let pos = self.brackets.get().then_some(pos);
+        // the continuation prompts are as wide as the indentation
+        let shown = line
+            .replace(&format!("\n{}", indent()), &format!("\n{}", CONTINUATION))
+            .replace(NBSP, " ");
+
+        let mut out = String::new();
+        for (text, class) in runs(&shown, &classes(&src, pos)) {
+            match class {
+                Some(class) => out.push_str(&self.theme.paint(class, text)),
+                None => out.push_str(text),
+            }
+        }
+        Cow::Owned(out)
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, forced: bool) -> bool {
+        // an accepted line is shown without its brackets matched
+        self.brackets.set(!forced);
+        true
+    }
+}
+
+impl Validator for Helper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        match incomplete(&spaced(ctx.input(), 0).0) {
+            true => Ok(ValidationResult::Incomplete),
+            false => Ok(ValidationResult::Valid(None)),
+        }
+    }
+}
+
+/// Enter at the end of an incomplete input starts a continuation line,
+/// the editor would start one without its indentation.
+pub struct Continue;
+
+impl ConditionalEventHandler for Continue {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+        let line = ctx.line();
+        (ctx.pos() == line.len() && incomplete(&spaced(line, 0).0))
+            .then(|| Cmd::Insert(1, format!("\n{}", indent())))
+    }
+}
+
+const NBSP: char = '\u{a0}';
+
+// the indentation the editor inserts, told from the spaces typed
+fn indent() -> String {
+    NBSP.to_string().repeat(CONTINUATION.len())
+}
+
+// an input with its non-breaking spaces as the spaces the scanner reads,
+// and where `pos` of the input is in it
+fn spaced(input: &str, pos: usize) -> (String, usize) {
+    let shrunk = input[..pos].matches(NBSP).count() * (NBSP.len_utf8() - 1);
+    (input.replace(NBSP, " "), pos - shrunk)
+}
+
+/// Removes the indentation the editor inserted on the continuation lines
+/// of an input, once a line.
+pub fn strip(input: &str) -> String {
+    input.replace(&format!("\n{}", indent()), "\n")
+}
+
+/// Classifies the bytes of an input for highlighting. A directive and the
+/// command of a REPL command are highlighted as directives, the rest as the
+/// tokens the scanner reads, what it rejects as errors. The brackets at or
+/// before the cursor `pos` and the ones they match are highlighted too.
+pub fn classes(input: &str, pos: Option<usize>) -> Vec<Option<Class>> {
+    let mut classes = vec![None; input.len()];
+    let mut src = input.to_string();
+
+    let start = input.len() - input.trim_start().len();
+    if input[start..].starts_with(':') {
+        let end = input[start..]
+            .find(char::is_whitespace)
+            .map_or(input.len(), |i| start + i);
+        classes[start..end].fill(Some(Class::Directive));
+        src.replace_range(start..end, &" ".repeat(end - start));
+    }
+
+    let mut s = scanner::Scanner::from(src.into_bytes());
+    let mut brackets = Vec::new(); // the open ones, and the pairs
+    let mut pairs = Vec::new();
+    let mut directive = None; // the start of `#` at the start of a line
+    let mut header = 0; // the end of the header name of an include
+    loop {
+        let (tok, start, end, class) = match s.scan() {
+            Ok((Token::EOF, ..)) => break,
+            Ok((tok, pos, lit)) => {
+                let class = match tok {
+                    Token::VOID
+                    | Token::BOOL
+                    | Token::CHAR
+                    | Token::SHORT
+                    | Token::INT
+                    | Token::LONG
+                    | Token::FLOAT
+                    | Token::DOUBLE
+                    | Token::SIGNED
+                    | Token::UNSIGNED
+                    | Token::STRUCT
+                    | Token::UNION
+                    | Token::ENUM => Some(Class::Type),
+                    _ if tok.is_keyword() => Some(Class::Keyword),
+                    Token::INTEGER | Token::FLOATING => Some(Class::Number),
+                    Token::STRING | Token::CHARACTER => Some(Class::String),
+                    Token::COMMENT => Some(Class::Comment),
+                    Token::LPAREN
+                    | Token::LBRACK
+                    | Token::LBRACE
+                    | Token::RPAREN
+                    | Token::RBRACK
+                    | Token::RBRACE
+                    | Token::COMMA
+                    | Token::SEMICOLON => None,
+                    _ if tok > Token::literal_end && tok < Token::keyword_beg => {
+                        Some(Class::Operator)
+                    }
+                    _ => None,
+                };
+                (tok, pos, pos + lit.len(), class)
+            }
+            // an unterminated comment or literal is still being typed
+            Err((tok @ (Token::COMMENT | Token::STRING | Token::CHARACTER), pos, lit, _))
+                if pos + lit.len() >= input.trim_end().len() =>
+            {
+                let class = match tok {
+                    Token::COMMENT => Class::Comment,
+                    _ => Class::String,
+                };
+                (tok, pos, pos + lit.len(), Some(class))
+            }
+            Err((tok, pos, lit, _)) => (tok, pos, pos + lit.len().max(1), Some(Class::Error)),
+        };
+        let end = end.min(input.len());
+        if start < header {
+            continue;
+        }
+
+        match (tok, directive) {
+            (Token::HASH, _)
+                if input[..start]
+                    .rsplit('\n')
+                    .next()
+                    .unwrap()
+                    .trim()
+                    .is_empty() =>
+            {
+                directive = Some(start);
+                continue;
+            }
+            (Token::COMMENT, _) => {}
+            (_, Some(hash)) => {
+                // the name of the directive, and the header it includes
+                directive = None;
+                classes[hash..end].fill(Some(Class::Directive));
+                let rest = &input[end..];
+                let name = rest.trim_start();
+                if &input[start..end] == "include" && name.starts_with('<') {
+                    if let Some(i) = name.find('>').filter(|&i| !name[..i].contains('\n')) {
+                        let open = end + rest.len() - name.len();
+                        header = open + i + 1;
+                        classes[open..header].fill(Some(Class::String));
+                    }
+                }
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(hash) = directive.take() {
+            classes[hash..hash + 1].fill(Some(Class::Directive));
+        }
+
+        match tok {
+            Token::LPAREN | Token::LBRACK | Token::LBRACE if class.is_none() => {
+                brackets.push(start)
+            }
+            Token::RPAREN | Token::RBRACK | Token::RBRACE if class.is_none() => {
+                if let Some(open) = brackets.pop() {
+                    pairs.push((open, start));
+                }
+            }
+            _ => {}
+        }
+        if class.is_some() {
+            classes[start..end].fill(class);
+        }
+    }
+    if let Some(hash) = directive {
+        classes[hash..hash + 1].fill(Some(Class::Directive));
+    }
+
+    let at = |p: usize| pairs.iter().find(|&&(open, close)| open == p || close == p);
+    let pair = pos.and_then(|pos| at(pos).or_else(|| at(pos.checked_sub(1)?)));
+    if let Some(&(open, close)) = pair {
+        classes[open] = Some(Class::Bracket);
+        classes[close] = Some(Class::Bracket);
+    }
+    classes
+}
+
+// splits an input into runs of bytes of the same class
+fn runs<'a>(input: &'a str, classes: &[Option<Class>]) -> Vec<(&'a str, Option<Class>)> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for i in 1..=input.len() {
+        if i == input.len() || (input.is_char_boundary(i) && classes[i] != classes[start]) {
+            runs.push((&input[start..i], classes[start]));
+            start = i;
+        }
+    }
+    runs
+}
+
+/// Reports whether an input needs more lines: it ends with a backslash,
+/// in a comment or a string, inside brackets, or in the middle of a
+/// statement or declaration. A line of the REPL's commands, a directive
+/// and an expression are complete.
+pub fn incomplete(input: &str) -> bool {
+    let text = input.trim_end();
+    if text.ends_with('\\') {
+        return true;
+    }
+    if text.is_empty() || text.starts_with(':') || text.trim_start().starts_with('#') {
+        return false;
+    }
+
+    let mut s = scanner::Scanner::from(text.as_bytes().to_vec());
+    let mut depth = 0;
+    let mut end = 0; // of the last token but comments
+    loop {
+        match s.scan() {
+            Ok((Token::EOF, ..)) => break,
+            Ok((Token::COMMENT, ..)) => {}
+            Ok((tok, pos, lit)) => {
+                match tok {
+                    Token::LPAREN | Token::LBRACK | Token::LBRACE => depth += 1,
+                    Token::RPAREN | Token::RBRACK | Token::RBRACE => depth -= 1,
+                    _ => {}
+                }
+                end = pos + lit.len();
+            }
+            Err((Token::COMMENT | Token::STRING | Token::CHARACTER, pos, lit, _))
+                if pos + lit.len() >= text.len() =>
+            {
+                return true;
+            }
+            Err((_, pos, lit, _)) => end = pos + lit.len(),
+        }
+    }
+    if depth != 0 {
+        return depth > 0;
+    }
+
+    let mut p = parser::Parser::from(text.to_string());
+    if let Some(x) = p.parse_expr() {
+        if p.errors().is_empty() && x.end() >= end {
+            return false;
+        }
+    }
+
+    // a statement is cut short if its first error is past its end
+    let mut p = parser::Parser::from(format!("{{{}\n}}", text));
+    p.parse_stmt();
+    p.errors().first().is_some_and(|&(pos, _)| pos > text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incomplete() {
+        let tests = [
+            ("int f(void) {", true),
+            ("int f(void) {\n  return 1;\n}", false),
+            ("printf(\"%d\\n\",", true),
+            ("int xs[] = { 1, 2,", true),
+            ("/* a comment", true),
+            ("/* a comment */ x", false),
+            ("x // a comment", false),
+            ("puts(\"abc", true),
+            ("#define N \\", true),
+            ("#include <stdio.h>", false),
+            ("int x = 1 + \\", true),
+            ("int x =", true),
+            ("int x", true),
+            ("x +", true),
+            ("for (int i = 0; i < 3; i++)", true),
+            ("if (x) y = 1; else", true),
+            ("do x++;", true),
+            ("struct s { int a; }", true),
+            ("struct s { int a; };", false),
+            ("x", false),
+            ("f(1, 2)", false),
+            ("x = 1;", false),
+            ("x = 1; y", true),
+            ("int x = 1)", false),
+            ("else", false),
+            (":bench int main(void) {", false),
+            ("", false),
+        ];
+        for (i, (input, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                *want,
+                incomplete(input),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_classes() {
+        use Class::*;
+
+        let tests = [
+            (
+                "int x = 42;",
+                None,
+                vec![("int", Type), ("=", Operator), ("42", Number)],
+            ),
+            (
+                "return s->n + 1.5; // done",
+                None,
+                vec![
+                    ("return", Keyword),
+                    ("->", Operator),
+                    ("+", Operator),
+                    ("1.5", Number),
+                    ("// done", Comment),
+                ],
+            ),
+            ("puts(\"hi\")", None, vec![("\"hi\"", String)]),
+            ("puts(\"hi", None, vec![("\"hi", String)]),
+            ("/* a comment", None, vec![("/* a comment", Comment)]),
+            (
+                "x = 1 @ 2",
+                None,
+                vec![("=", Operator), ("1", Number), ("@", Error), ("2", Number)],
+            ),
+            (
+                "#include <stdio.h>",
+                None,
+                vec![("#include", Directive), ("<stdio.h>", String)],
+            ),
+            (
+                "  # define N (1 << 4)",
+                None,
+                vec![
+                    ("# define", Directive),
+                    ("1", Number),
+                    ("<<", Operator),
+                    ("4", Number),
+                ],
+            ),
+            (
+                ":tokens int",
+                None,
+                vec![(":tokens", Directive), ("int", Type)],
+            ),
+            (
+                "f(a[1])",
+                Some(7),
+                vec![("(", Bracket), ("1", Number), (")", Bracket)],
+            ),
+            (
+                "f(a[1])",
+                Some(3),
+                vec![("[", Bracket), ("1", Number), ("]", Bracket)],
+            ),
+            ("f(a[1])", Some(0), vec![("1", Number)]),
+            (
+                "f(\")\")",
+                Some(2),
+                vec![("(", Bracket), ("\")\"", String), (")", Bracket)],
+            ),
+        ];
+        for (i, (input, pos, want)) in tests.iter().enumerate() {
+            let got: Vec<_> = runs(input, &classes(input, *pos))
+                .into_iter()
+                .filter_map(|(text, class)| Some((text, class?)))
+                .collect();
+            assert_eq!(
+                *want,
+                got,
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_continuation() {
+        let input = format!("int f(void) {{\n{}return 1;\n{}}}", indent(), indent());
+        assert_eq!(
+            "int f(void) {\n... return 1;\n... }",
+            Helper {
+                theme: Theme::preset("none").unwrap(),
+                ..Helper::default()
+            }
+            .highlight(&input, 0)
+        );
+        assert_eq!("int f(void) {\nreturn 1;\n}", strip(&input));
+
+        // the indentation typed, or pasted, is kept
+        let input = format!("int f(void) {{\n{}    return 1;\n    }}", indent());
+        assert_eq!(
+            "int f(void) {\n...     return 1;\n    }",
+            Helper {
+                theme: Theme::preset("none").unwrap(),
+                ..Helper::default()
+            }
+            .highlight(&input, 0)
+        );
+        assert_eq!("int f(void) {\n    return 1;\n    }", strip(&input));
+    }
+}
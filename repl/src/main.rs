@@ -1,39 +1,595 @@
 #![allow(unused)]
 
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
 // use app::App;
+use ast::{DeclKind, Node, StructSpec, TypeSpec};
 use scanner::Scanner;
+use sema::layout::DataLayout;
+use sema::types::{TagId, TypeKind};
 use token::{Position, Token};
 
 mod app;
+mod cmd;
+mod complete;
+mod editor;
+mod session;
+mod theme;
 
 fn main() {
     // let mut app = App::new();
     // app.start().unwrap();
 
-    let mut rl = rustyline::DefaultEditor::new().unwrap();
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-    loop {
-        match rl.readline("> ") {
-            Ok(line) => {
-                let mut scanner = Scanner::from(line);
+    match args.first().map(String::as_str) {
+        Some("tokens") => std::process::exit(cmd::tokens::run(&args[1..])),
+        Some("ast") => std::process::exit(cmd::ast::run(&args[1..])),
+        Some("fmt") => std::process::exit(cmd::fmt::run(&args[1..])),
+        Some("lint") => std::process::exit(cmd::lint::run(&args[1..])),
+        Some("run") => std::process::exit(cmd::run::run(&args[1..])),
+        Some("build") => std::process::exit(cmd::build::run(&args[1..])),
+        _ => {
+            cmd::run::spawn(move || repl(&args));
+        }
+    }
+}
+
+fn repl(args: &[String]) {
+    let settings = [&["layout", "theme"][..], &cmd::LIMITS].concat();
+    let opts = match cmd::Options::parse(args, &settings) {
+        Ok(opts) => opts,
+        Err(err) => {
+            eprintln!("crepl: {}", err);
+            std::process::exit(2);
+        }
+    };
+
+    let mut layout = DataLayout::default();
+    let mut helper = editor::Helper::default();
+    let mut limits = interp::Limits {
+        interrupt: Some(catch_interrupt()),
+        ..interp::Limits::default()
+    };
+    for (name, value) in opts.settings.iter() {
+        if cmd::LIMITS.contains(&name.as_str()) {
+            if let Err(err) = cmd::set_limit(&mut limits, name, value) {
+                eprintln!("crepl: {}", err);
+                std::process::exit(2);
+            }
+        } else if name == "layout" {
+            layout = DataLayout::from_name(value).unwrap_or_else(|| {
+                eprintln!(
+                    "crepl: unknown data layout '{}' (lp64, ilp32 or llp64)",
+                    value
+                );
+                std::process::exit(2);
+            });
+        } else if name == "theme" {
+            if let Err(err) = helper.theme.apply(value) {
+                eprintln!("crepl: {}", err);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let mut session = session::Session::new(layout, limits);
+    session.trigraphs = opts.trigraphs;
+
+    // the completions are listed with their types
+    let config = rustyline::Config::builder()
+        .completion_type(rustyline::CompletionType::List)
+        .build();
+    let mut rl = rustyline::Editor::with_config(config).unwrap();
+    helper.layout = session.layout;
+    rl.set_helper(Some(helper));
+    rl.bind_sequence(
+        rustyline::KeyEvent(rustyline::KeyCode::Enter, rustyline::Modifiers::NONE),
+        rustyline::EventHandler::Conditional(Box::new(editor::Continue)),
+    );
+
+    while let Ok(line) = rl.readline(editor::PROMPT) {
+        let line = editor::strip(&line);
+        if let Some(src) = line.trim_start().strip_prefix(":type") {
+            if let Some((src, from)) = with_declarations(&session, &line, src) {
+                print_type(&src, from, session.layout);
+            }
+            continue;
+        }
+
+        if let Some(src) = line.trim_start().strip_prefix(":check") {
+            if let Some((src, from)) = with_declarations(&session, &line, src) {
+                print_check(&src, from, session.layout);
+            }
+            continue;
+        }
+
+        if let Some(src) = line.trim_start().strip_prefix(":layout") {
+            if let Some((src, from)) = with_declarations(&session, &line, src) {
+                print_layout(&src, from, session.layout);
+            }
+            continue;
+        }
+
+        if let Some(src) = line.trim_start().strip_prefix(":ir") {
+            if let Some((src, from)) = with_declarations(&session, &line, src) {
+                print_ir(&src, from, session.layout);
+            }
+            continue;
+        }
+
+        if let Some(arg) = line.trim_start().strip_prefix(":bench") {
+            bench(arg.trim(), session.layout, &session.limits);
+            continue;
+        }
 
-                loop {
-                    match scanner.scan() {
-                        Ok((Token::EOF, ..)) => break,
+        if let Some(arg) = line.trim_start().strip_prefix(":limits") {
+            set_limits(arg, &mut session.limits);
+            continue;
+        }
 
-                        Ok((tok, _, lit)) => {
-                            println!("({:?}, {})", tok, lit);
-                        }
+        if let Some(arg) = line.trim_start().strip_prefix(":load") {
+            load(arg.trim());
+            continue;
+        }
 
-                        Err((_, pos, _, err)) => {
-                            println!("Scanner Error: {}. at column {}.", err, pos + 1)
-                        }
+        if let Some(arg) = line.trim_start().strip_prefix(":theme") {
+            let theme = &mut rl.helper_mut().unwrap().theme;
+            match arg.trim() {
+                "" => println!("{}", theme),
+                spec => {
+                    if let Err(err) = theme.apply(spec) {
+                        println!("Error: {}.", err);
                     }
                 }
             }
-            Err(_) => {
-                break;
+            continue;
+        }
+
+        if let Some(src) = line.trim_start().strip_prefix(":tokens") {
+            print_tokens(
+                opts.scanner(format!("{:1$}{2}", "", line.len() - src.len(), src).into_bytes()),
+            );
+            continue;
+        }
+
+        if line.trim() == ":reset" {
+            session.reset();
+        } else {
+            session.run(&line, &mut std::io::stdout());
+        }
+        rl.helper_mut().unwrap().decls = session.declarations();
+    }
+}
+
+// the source of a command: the declarations of the session, with their
+// headers, and then the rest of its line, which keeps its columns; returns
+// where the line starts
+fn with_declarations(session: &session::Session, line: &str, src: &str) -> Option<(String, usize)> {
+    let decls = session.declarations();
+    let decls = match interp::include::expand(decls.as_bytes(), &session.layout) {
+        Ok(decls) => String::from_utf8_lossy(&decls).into_owned(),
+        Err((_, msg)) => {
+            println!("Error: {}.", msg);
+            return None;
+        }
+    };
+    let from = decls.len();
+    Some((
+        format!("{}{:2$}{3}", decls, "", line.len() - src.len(), src),
+        from,
+    ))
+}
+
+// `:tokens int x;` prints the tokens of a line
+fn print_tokens(mut scanner: Scanner) {
+    loop {
+        match scanner.scan() {
+            Ok((Token::EOF, ..)) => break,
+
+            Ok((tok, _, lit)) => {
+                println!("({:?}, {})", tok, lit);
+            }
+
+            Err((_, pos, _, err)) => {
+                let column = scanner.position(pos).column;
+                println!("Scanner Error: {}. at column {}.", err, column)
+            }
+        }
+    }
+}
+
+// `:type expr` prints the type of an expression, in the scope of the
+// declarations before `from`
+fn print_type(src: &str, from: usize, layout: DataLayout) {
+    let mut p = parser::Parser::from(src.to_string());
+    let mut decls = Vec::new();
+    while p.pos() < from {
+        match p.parse_decl() {
+            Some(d) => decls.push(d),
+            None => break,
+        }
+    }
+    let x = p.parse_expr();
+
+    let mut errors = p.errors().to_vec();
+
+    match &x {
+        Some(x) if x.end() < src.trim_end().len() => {
+            errors.push((x.end(), "expected end of expression".to_string()))
+        }
+        None if errors.is_empty() => {
+            let pos = src.len() - src[from..].trim_start().len();
+            errors.push((pos, "expected expression".to_string()))
+        }
+        _ => {}
+    }
+
+    for (pos, msg) in errors.iter() {
+        println!(
+            "Parser Error: {}. at column {}.",
+            msg,
+            p.position(*pos).column
+        );
+    }
+
+    let Some(x) = x.filter(|_| errors.is_empty()) else {
+        return;
+    };
+
+    let info = sema::check_expr(&decls, x.as_ref(), layout);
+    print_diagnostics(&p, &info, from);
+
+    if let Some(t) = info.type_of(x.as_ref()) {
+        println!("{}", info.spell(t));
+    }
+}
+
+// `:layout struct S { ... }` prints the offsets of the members of a struct
+// or union, and its size and alignment; `:layout struct S` those of one
+// declared before `from`
+fn print_layout(src: &str, from: usize, layout: DataLayout) {
+    let mut src = src.trim_end().to_string();
+    if !src.ends_with(';') {
+        src.push(';');
+    }
+
+    let mut p = parser::Parser::from(src);
+    let mut file = p.parse_file();
+
+    for (pos, msg) in p.errors().iter() {
+        println!(
+            "Parser Error: {}. at column {}.",
+            msg,
+            p.position(*pos).column
+        );
+    }
+    if !p.errors().is_empty() {
+        return;
+    }
+
+    let info = sema::check_with(&file, layout);
+    print_diagnostics(&p, &info, from);
+
+    // the type being laid out is the first the line names, or declares
+    // without a name after the tags of the declarations before it
+    let line = file.decls.partition_point(|d| d.start() < from);
+    let typ = file.decls[line..].iter().find_map(|d| match d.kind() {
+        DeclKind::GenDecl(g) => Some(&g.specs.typ),
+        DeclKind::FuncDecl(_) | DeclKind::StaticAssert(_) => None,
+    });
+    let id = match typ {
+        Some(
+            TypeSpec::Struct(StructSpec {
+                tag: Some(name), ..
+            })
+            | TypeSpec::Typedef(name),
+        ) => info.lookup(name.pos).and_then(|sym| match sym.ty.kind {
+            TypeKind::Struct(id) | TypeKind::Union(id) => Some(id),
+            _ => None,
+        }),
+        Some(TypeSpec::Struct(_)) => {
+            file.decls.truncate(line);
+            Some(TagId(sema::check_with(&file, layout).tags.len()))
+        }
+        _ => None,
+    };
+    let Some(id) = id else {
+        println!("Error: expected a struct or union type.");
+        return;
+    };
+
+    match layout.dump_record(id, &info.tags) {
+        Some(dump) => print!("{}", dump),
+        None => {
+            let ty = match info.tags[id.0].kind {
+                Token::UNION => TypeKind::Union(id),
+                _ => TypeKind::Struct(id),
+            };
+            println!("Error: incomplete type '{}'.", info.spell(&ty.into()));
+        }
+    }
+}
+
+// `:check int f(void) { ... }` prints the diagnostics of the checker and
+// the warnings of the lints, like reads of uninitialized variables, of the
+// declarations from `from`
+fn print_check(src: &str, from: usize, layout: DataLayout) {
+    let mut p = parser::Parser::from(src.to_string());
+    let file = p.parse_file();
+
+    for (pos, msg) in p.errors().iter() {
+        println!(
+            "Parser Error: {}. at column {}.",
+            msg,
+            p.position(*pos).column
+        );
+    }
+    if !p.errors().is_empty() {
+        return;
+    }
+
+    let info = sema::check_with(&file, layout);
+    print_diagnostics(&p, &info, from);
+    if info.errors().count() > 0 {
+        return;
+    }
+
+    let warnings = lint::Linter::default().run(&file, &info, |pos| p.position(pos).line);
+    for w in warnings.iter().filter(|w| w.pos >= from) {
+        let column = p.position(w.pos).column;
+        println!("Warning: {} [-W{}]. at column {}.", w.msg, w.lint, column);
+    }
+}
+
+// `:ir [-O] [--passes=mem2reg,dce] int f(int x) { ... }` prints the IR of
+// the declarations from `from`, unoptimized or after the passes
+fn print_ir(src: &str, from: usize, layout: DataLayout) {
+    let mut src = src.to_string();
+    let (mut optimize, mut passes) = (false, Vec::new());
+    while let Some(arg) = src[from..]
+        .split_whitespace()
+        .next()
+        .filter(|a| a.starts_with('-'))
+    {
+        match arg {
+            "-O" => optimize = true,
+            "-O0" => optimize = false,
+            _ => match arg.strip_prefix("--passes=") {
+                Some(names) => passes = names.split(',').map(str::to_string).collect(),
+                None => {
+                    println!("Error: unknown option '{}'.", arg);
+                    return;
+                }
+            },
+        }
+        // keep the columns of the declarations
+        let at = from + src[from..].find(arg).unwrap();
+        let n = arg.len();
+        src.replace_range(at..at + n, &" ".repeat(n));
+    }
+    if let Some(bad) = passes.iter().find(|n| !ir::PASSES.contains(&n.as_str())) {
+        println!(
+            "Error: unknown pass '{}', the passes are {}.",
+            bad,
+            ir::PASSES.join(", ")
+        );
+        return;
+    }
+
+    let mut p = parser::Parser::from(src);
+    let mut file = p.parse_file();
+
+    for (pos, msg) in p.errors().iter() {
+        println!(
+            "Parser Error: {}. at column {}.",
+            msg,
+            p.position(*pos).column
+        );
+    }
+    if !p.errors().is_empty() {
+        return;
+    }
+
+    let info = sema::check_with(&file, layout);
+    print_diagnostics(&p, &info, from);
+    if info.errors().count() > 0 {
+        return;
+    }
+
+    // the functions of the session are referenced, not lowered again
+    file.decls.retain(|d| d.start() >= from);
+    match ir::lower(&file, &info) {
+        Ok(mut m) => {
+            if optimize {
+                ir::optimize(&mut m);
+            }
+            for name in passes.iter() {
+                ir::run_pass(&mut m, name);
+            }
+            print!("{}", m);
+        }
+        Err(err) => println!(
+            "Error: {}. at column {}.",
+            err.msg,
+            p.position(err.pos).column
+        ),
+    }
+}
+
+// Ctrl+C sets the interrupt of the program running instead of ending the
+// REPL, the line editor reads it as a key while it waits for a line
+fn catch_interrupt() -> Arc<AtomicBool> {
+    static FLAG: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+    let flag = FLAG.get_or_init(|| Arc::new(AtomicBool::new(false)));
+
+    #[cfg(unix)]
+    {
+        extern "C" fn interrupt(_: libc::c_int) {
+            if let Some(flag) = FLAG.get() {
+                flag.store(true, Ordering::Relaxed);
+            }
+        }
+        let handler = interrupt as extern "C" fn(libc::c_int);
+        unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) };
+    }
+    flag.clone()
+}
+
+// `:limits max-steps=1000000 timeout=2s` sets the limits of the programs
+// run, `none` removes one; `:limits` prints them
+fn set_limits(args: &str, limits: &mut interp::Limits) {
+    if args.trim().is_empty() {
+        let none = || "none".to_string();
+        println!(
+            "max-steps={}",
+            limits.steps.map_or_else(none, |n| n.to_string())
+        );
+        println!("max-depth={}", limits.depth);
+        println!(
+            "max-heap={}",
+            limits.heap.map_or_else(none, |n| n.to_string())
+        );
+        println!(
+            "timeout={}",
+            limits.time.map_or_else(none, |t| format!("{:?}", t))
+        );
+        return;
+    }
+
+    for arg in args.split_whitespace() {
+        let (name, value) = arg.split_once('=').unwrap_or((arg, ""));
+        if let Err(err) = cmd::set_limit(limits, name, value) {
+            println!("Error: {}.", err);
+            return;
+        }
+    }
+}
+
+// `:bench FILE` or `:bench int main(void) { ... }` runs a program with
+// both engines and compares their times, its output is discarded
+fn bench(arg: &str, layout: DataLayout, limits: &interp::Limits) {
+    let src = match std::path::Path::new(arg).is_file() {
+        true => match std::fs::read(arg) {
+            Ok(src) => src,
+            Err(err) => {
+                println!("Error: {}: {}.", arg, err);
+                return;
             }
+        },
+        false => arg.as_bytes().to_vec(),
+    };
+
+    let src = match interp::include::expand(&src, &layout) {
+        Ok(src) => src,
+        Err((_, msg)) => {
+            println!("Error: {}.", msg);
+            return;
+        }
+    };
+
+    let src = String::from_utf8_lossy(&src).into_owned();
+    let limits = limits.clone();
+    if let Some(flag) = &limits.interrupt {
+        flag.store(false, Ordering::Relaxed);
+    }
+    cmd::run::spawn(move || bench_program(src, layout, limits));
+}
+
+fn bench_program(src: String, layout: DataLayout, limits: interp::Limits) {
+    let mut p = parser::Parser::from(src);
+    let file = p.parse_file();
+    let at = |pos| {
+        let pos = p.position(pos);
+        format!("at line {}, column {}", pos.line, pos.column)
+    };
+
+    for (pos, msg) in p.errors().iter() {
+        println!("Parser Error: {}. {}.", msg, at(*pos));
+    }
+    if !p.errors().is_empty() {
+        return;
+    }
+
+    let info = sema::check_with(&file, layout);
+    if info.errors().count() > 0 {
+        for d in info.errors() {
+            println!("Error: {}. {}.", d.msg, at(d.pos));
         }
+        return;
+    }
+
+    let run = |engine| {
+        let mut out = Vec::new();
+        let mut it = interp::Interpreter::new(&file, &info)
+            .with_output(&mut out)
+            .with_input(std::io::empty());
+        it.engine = engine;
+        it.limits = limits.clone();
+
+        let start = std::time::Instant::now();
+        let status = it.run_main(&["bench".to_string()]);
+        let time = start.elapsed();
+        drop(it);
+        (status, out, time)
+    };
+
+    let (status, out, ast) = run(interp::Engine::Ast);
+    let (vm_status, vm_out, vm) = run(interp::Engine::Vm);
+
+    if let Err(err) = &status {
+        println!("Runtime Error: {}. {}.", err.msg, at(err.pos));
+        for f in err.trace.iter().take(cmd::run::MAX_TRACE) {
+            println!("    in {} {}.", f.func, at(f.pos));
+        }
+        if err.trace.len() > cmd::run::MAX_TRACE {
+            println!(
+                "    ... {} more calls.",
+                err.trace.len() - cmd::run::MAX_TRACE
+            );
+        }
+        return;
+    }
+    if status != vm_status || out != vm_out {
+        println!("Error: the engines disagree.");
+        return;
+    }
+
+    println!("ast: {:.3}s", ast.as_secs_f64());
+    println!(
+        "vm:  {:.3}s ({:.1}x faster)",
+        vm.as_secs_f64(),
+        ast.as_secs_f64() / vm.as_secs_f64().max(1e-9)
+    );
+}
+
+// `:load libz.so` loads a shared library, the functions it defines can be
+// called by the programs run after; `:load` lists the libraries loaded
+fn load(path: &str) {
+    if path.is_empty() {
+        for lib in interp::ffi::libraries() {
+            println!("{}", lib);
+        }
+        return;
+    }
+
+    match interp::ffi::load(path) {
+        Ok(()) => println!("Loaded {}.", path),
+        Err(err) => println!("Error: {}.", err),
+    }
+}
+
+// prints the diagnostics from `from`, those of the declarations of the
+// session were printed when they ran
+fn print_diagnostics(p: &parser::Parser, info: &sema::Info, from: usize) {
+    for d in info.diagnostics.iter().filter(|d| d.pos >= from) {
+        let severity = match d.severity {
+            sema::Severity::Error => "Error",
+            sema::Severity::Warning => "Warning",
+            sema::Severity::Note => "Note",
+        };
+        let column = p.position(d.pos).column;
+        println!("{}: {}. at column {}.", severity, d.msg, column);
     }
 }
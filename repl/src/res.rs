@@ -1,4 +1,4 @@
-pub const SIMBA: &'static str = r#"
+pub const SIMBA: &str = r#"
                    ,   __, ,
    _.._         )\/(,-' (-' `.__
   /_   `-.      )'_      ` _  (_    _.---._
@@ -22,7 +22,7 @@ pub const SIMBA: &'static str = r#"
                   `-.______,'
 "#;
 
-pub const LOGO4: &'static str = r#"
+pub const LOGO4: &str = r#"
  ██████╗    ██████╗     ███████╗    ██████╗     ██╗     
 ██╔════╝    ██╔══██╗    ██╔════╝    ██╔══██╗    ██║     
 ██║         ██████╔╝    █████╗      ██████╔╝    ██║     
@@ -31,7 +31,7 @@ pub const LOGO4: &'static str = r#"
  ╚═════╝    ╚═╝  ╚═╝    ╚══════╝    ╚═╝         ╚══════╝
 "#;
 
-pub const LOGO5: &'static str = r#"
+pub const LOGO5: &str = r#"
 
 
  ▄▄·     ▄▄▄      ▄▄▄ .     ▄▄▄·    ▄▄▌  
@@ -43,7 +43,7 @@ pub const LOGO5: &'static str = r#"
 
 "#;
 
-pub const LOGO: &'static str = r#"
+pub const LOGO: &str = r#"
    ______  _____
   / ____/ / ___ \___  ____  __
  / /     / /__/ / _ \/ __ \/ /
@@ -52,7 +52,7 @@ pub const LOGO: &'static str = r#"
       /_/  \_\    /_/
 "#;
 
-pub const LOGO1: &'static str = r#"
+pub const LOGO1: &str = r#"
    _____   _____                   _      
   / ____| |  __ \                 | |     
  | |      | |__) |   ___   _ __   | |     
@@ -62,7 +62,7 @@ pub const LOGO1: &'static str = r#"
                           | |             
                           |_|             "#;
 
-pub const LOGO2: &'static str = r#"
+pub const LOGO2: &str = r#"
   ______        _______        _______        _______       ___       
  /" _  "\      /"      \      /"     "|      |   __ "\     |"  |      
 (: ( \___)    |:        |    (: ______)      (. |__) :)    ||  |      
@@ -72,7 +72,7 @@ pub const LOGO2: &'static str = r#"
  \_______)    |__|  \___)     \_______)    (_______)        \_______) 
 "#;
 
-pub const LOGO3: &'static str = r#"
+pub const LOGO3: &str = r#"
  ░▒▓██████▓▒░       ░▒▓███████▓▒░       ░▒▓████████▓▒░      ░▒▓███████▓▒░       ░▒▓█▓▒░        
 ░▒▓█▓▒░░▒▓█▓▒░      ░▒▓█▓▒░░▒▓█▓▒░      ░▒▓█▓▒░             ░▒▓█▓▒░░▒▓█▓▒░      ░▒▓█▓▒░        
 ░▒▓█▓▒░             ░▒▓█▓▒░░▒▓█▓▒░      ░▒▓█▓▒░             ░▒▓█▓▒░░▒▓█▓▒░      ░▒▓█▓▒░        
@@ -0,0 +1,709 @@
+//! The session of the REPL. The declarations and functions typed so far
+//! make a translation unit that grows with each input, a statement runs in
+//! a function of its own and an expression prints its value and type, e.g.
+//! `(int) 42`. The memory of the program carries over from one input to
+//! the next, a definition of a name defined before replaces the earlier.
+
+use std::io::Write;
+use std::ops::Range;
+use std::sync::atomic::Ordering;
+
+use ast::{DeclKind, TypeSpec};
+use interp::{Interpreter, Stop};
+use parser::Parser;
+use sema::layout::DataLayout;
+use token::Token;
+
+// the function that holds a statement or an expression
+const INPUT: &str = "__crepl_input";
+
+/// What an input is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Declaration, // of variables, types or prototypes
+    Definition,  // of functions
+    Statement,
+    Expression,
+}
+
+#[derive(Default)]
+pub struct Session {
+    pub layout: DataLayout,
+    pub limits: interp::Limits,
+    pub trigraphs: bool,
+
+    items: Vec<Item>,
+    state: Option<interp::State>,
+}
+
+// a declaration of an input, with the text in front of it, and the names
+// it defines
+#[derive(Clone)]
+struct Item {
+    src: String,
+    names: Vec<String>,
+
+    // where the specifiers of a declaration end, and where its declarators
+    // are and the name each defines
+    specs: usize,
+    declarators: Vec<(Range<usize>, Option<String>)>,
+}
+
+// a translation unit made of the items and an input, the input takes the
+// lines from `first` to `end`
+struct Unit {
+    src: String,
+    first: usize,
+    end: usize,
+    input: String,
+}
+
+impl Item {
+    // the item without what defines `names`, None if nothing is left: a
+    // declaration keeps the declarators of the other names
+    fn without(&self, names: &[String]) -> Option<Item> {
+        if !self.names.iter().any(|n| names.contains(n)) {
+            return Some(self.clone());
+        }
+
+        // the names of the specifiers go with the whole declaration
+        let declared = |n: &String| self.declarators.iter().any(|(_, d)| d.as_ref() == Some(n));
+        let mut specs = self.names.iter().filter(|n| !declared(n));
+        let kept: Vec<_> = self
+            .declarators
+            .iter()
+            .filter(|(_, name)| name.as_ref().is_none_or(|n| !names.contains(n)))
+            .collect();
+        if kept.is_empty() || specs.any(|n| names.contains(n)) {
+            return None;
+        }
+
+        let mut src = self.src[..self.specs].to_string();
+        let mut declarators = Vec::new();
+        for (i, (span, name)) in kept.into_iter().enumerate() {
+            src.push_str(if i == 0 { " " } else { ", " });
+            let start = src.len();
+            src.push_str(&self.src[span.clone()]);
+            declarators.push((start..src.len(), name.clone()));
+        }
+        src.push(';');
+
+        Some(Item {
+            src,
+            names: self
+                .names
+                .iter()
+                .filter(|n| !names.contains(n))
+                .cloned()
+                .collect(),
+            specs: self.specs,
+            declarators,
+        })
+    }
+}
+
+impl Session {
+    pub fn new(layout: DataLayout, limits: interp::Limits) -> Self {
+        Self {
+            layout,
+            limits,
+            ..Self::default()
+        }
+    }
+
+    /// Forgets the declarations and the memory of the program.
+    pub fn reset(&mut self) {
+        self.items.clear();
+        self.state = None;
+    }
+
+    /// Returns the declarations and functions of the session.
+    pub fn declarations(&self) -> String {
+        let mut src = String::new();
+        for item in self.items.iter() {
+            src.push_str(&item.src);
+            src.push('\n');
+        }
+        src
+    }
+
+    /// Runs an input and writes what it prints to `out`, returns None if
+    /// it has errors.
+    pub fn run(&mut self, input: &str, out: &mut dyn Write) -> Option<Kind> {
+        let text = input.trim_end();
+        if text.is_empty() {
+            return None;
+        }
+
+        // an expression has no `;`, and a statement may declare a
+        // typedef name first used by the rest of the line
+        let mut tries = Vec::new();
+        if !text.ends_with(';') && !text.ends_with('}') {
+            tries.push(Kind::Expression);
+        }
+        tries.push(Kind::Declaration);
+        tries.push(Kind::Statement);
+
+        let mut failed: Option<(Unit, Parser)> = None;
+        for kind in tries {
+            let unit = self.unit(&self.items, self.items.len(), input, kind);
+            let (p, file) = self.parse(&unit, out)?;
+            if !p.errors().is_empty() {
+                // the errors of the try that got furthest are reported
+                let furthest = |p: &Parser, unit: &Unit| {
+                    let pos = p.position(p.errors()[0].0);
+                    (pos.line.saturating_sub(unit.first), pos.column)
+                };
+                if failed
+                    .as_ref()
+                    .is_none_or(|(u, q)| furthest(&p, &unit) > furthest(q, u))
+                {
+                    failed = Some((unit, p));
+                }
+                continue;
+            }
+
+            return match kind {
+                Kind::Declaration => self.declare(&unit, &p, &file, out),
+                _ => self.exec(&unit, &p, &file, kind, out).then_some(kind),
+            };
+        }
+
+        // the errors past the end of the input follow the first
+        let (unit, p) = failed.unwrap();
+        let end = unit.first + unit.input.lines().count();
+        for (pos, msg) in p.errors().iter() {
+            let _ = writeln!(out, "Parser Error: {}. {}.", msg, at(&p, &unit, *pos));
+            if p.position(*pos).line >= end {
+                break;
+            }
+        }
+        None
+    }
+
+    // checks and runs the declarations of an input, the items defining
+    // the names they define are replaced in their place
+    fn declare(
+        &mut self,
+        unit: &Unit,
+        p: &Parser,
+        file: &ast::File,
+        out: &mut dyn Write,
+    ) -> Option<Kind> {
+        let mut decls = Vec::new();
+        let mut kind = Kind::Declaration;
+        for d in file.decls.iter() {
+            let line = p.position(d.start()).line;
+            if line < unit.first || directive(&unit.input, line - unit.first) {
+                continue; // declared before or by a header
+            }
+            if let DeclKind::FuncDecl(_) = d.kind() {
+                kind = Kind::Definition;
+            }
+            let start = match decls.is_empty() {
+                true => 0,
+                false => offset(p, unit, d.start()),
+            };
+            decls.push((start, d.as_ref()));
+        }
+
+        let mut input = Vec::new();
+        for (i, (start, d)) in decls.iter().enumerate() {
+            let end = decls.get(i + 1).map_or(unit.input.len(), |(end, _)| *end);
+            input.push(item(p, unit, *d, *start..end));
+        }
+        if input.is_empty() {
+            input.push(Item {
+                src: unit.input.clone(),
+                names: Vec::new(),
+                specs: 0,
+                declarators: Vec::new(),
+            });
+        }
+        let names: Vec<String> = input.iter().flat_map(|i| i.names.clone()).collect();
+
+        // the input goes where the first item it replaces was, the items
+        // after it defining the names it uses, and the headers, move in
+        // front of it
+        let mut kept = Vec::new();
+        let mut replaced = Vec::new();
+        let mut slot = None;
+        for item in self.items.iter() {
+            if item.names.iter().any(|n| names.contains(n)) && slot.is_none() {
+                slot = Some(kept.len());
+            }
+            match item.without(&names) {
+                Some(item) => kept.push(item),
+                None => replaced.extend(item.names.iter().cloned()),
+            }
+        }
+        let slot = slot.unwrap_or(kept.len());
+        let mut needed = used(&unit.input);
+        let mut moved = vec![false; kept.len()];
+        for (i, item) in kept.iter().enumerate().skip(slot).rev() {
+            let header = item.src.lines().any(|l| l.trim_start().starts_with('#'));
+            if header || item.names.iter().any(|n| needed.contains(n)) {
+                moved[i] = true;
+                needed.extend(used(&item.src));
+            }
+        }
+        let after = kept.split_off(slot);
+        let (front, back): (Vec<_>, Vec<_>) = after
+            .into_iter()
+            .zip(moved.into_iter().skip(slot))
+            .partition(|(_, moved)| *moved);
+        kept.extend(front.into_iter().map(|(item, _)| item));
+        let slot = kept.len();
+        kept.extend(back.into_iter().map(|(item, _)| item));
+
+        let redone = self.unit(&kept, slot, &unit.input, kind);
+        let ok = match self.parse(&redone, out) {
+            Some((p, file)) => self.exec(&redone, &p, &file, kind, out),
+            None => false,
+        };
+
+        // the objects of the names redefined, or of an input that failed
+        // to run, are dropped
+        let mut dropped: Vec<&String> = names.iter().collect();
+        if ok {
+            dropped.extend(replaced.iter());
+        }
+        if let Some(state) = &mut self.state {
+            for name in dropped {
+                state.forget(name);
+            }
+        }
+        if !ok {
+            return None;
+        }
+
+        kept.splice(slot..slot, input);
+        self.items = kept;
+        Some(kind)
+    }
+
+    // the unit of an input of a kind, between the items before `slot` and
+    // the items after
+    fn unit(&self, items: &[Item], slot: usize, input: &str, kind: Kind) -> Unit {
+        let mut src = String::new();
+        for item in items[..slot].iter() {
+            src.push_str(&item.src);
+            src.push('\n');
+        }
+        match kind {
+            Kind::Expression => src.push_str(&format!("void {}(void) {{(\n", INPUT)),
+            Kind::Statement => src.push_str(&format!("void {}(void) {{\n", INPUT)),
+            _ => {}
+        }
+
+        let first = src.lines().count() + 1;
+        src.push_str(input.trim_end());
+        match kind {
+            Kind::Expression => src.push_str("\n);}\n"),
+            Kind::Statement => src.push_str("\n}\n"),
+            _ => src.push('\n'),
+        }
+
+        let end = src.lines().count() + 1;
+        for item in items[slot..].iter() {
+            src.push_str(&item.src);
+            src.push('\n');
+        }
+        Unit {
+            src,
+            first,
+            end,
+            input: input.trim_end().to_string(),
+        }
+    }
+
+    // expands the includes of a unit and parses it, the errors of its
+    // directives are reported
+    fn parse(&self, unit: &Unit, out: &mut dyn Write) -> Option<(Parser, ast::File)> {
+        let src = match interp::include::expand(unit.src.as_bytes(), &self.layout) {
+            Ok(src) => src,
+            Err((_, msg)) => {
+                let _ = writeln!(out, "Error: {}.", msg);
+                return None;
+            }
+        };
+
+        let s = scanner::Scanner::from(src);
+        let s = if self.trigraphs {
+            s.with_trigraphs()
+        } else {
+            s
+        };
+        let mut p = Parser::from(s);
+        let file = p.parse_file();
+        Some((p, file))
+    }
+
+    // checks a unit and runs its input, reports whether it had no errors
+    fn exec(
+        &mut self,
+        unit: &Unit,
+        p: &Parser,
+        file: &ast::File,
+        kind: Kind,
+        out: &mut dyn Write,
+    ) -> bool {
+        let info = sema::check_with(file, self.layout);
+        for d in info.diagnostics.iter() {
+            let line = p.position(d.pos).line;
+            if d.severity != sema::Severity::Error && !(unit.first..unit.end).contains(&line) {
+                continue; // reported with their input
+            }
+            let severity = match d.severity {
+                sema::Severity::Error => "Error",
+                sema::Severity::Warning => "Warning",
+                sema::Severity::Note => "Note",
+            };
+            let _ = writeln!(out, "{}: {}. {}.", severity, d.msg, at(p, unit, d.pos));
+        }
+        if info.errors().count() > 0 {
+            return false;
+        }
+
+        if let Some(flag) = &self.limits.interrupt {
+            flag.store(false, Ordering::Relaxed);
+        }
+        let mut it = Interpreter::new(file, &info).with_output(&mut *out);
+        if let Some(state) = self.state.take() {
+            it = it.resume(state);
+        }
+        it.limits = self.limits.clone();
+
+        let r = it.run_globals().and_then(|_| match kind {
+            Kind::Statement => it.run_function(INPUT).map(|_| None),
+            Kind::Expression => it.run_expr(INPUT),
+            _ => Ok(None),
+        });
+        self.state = Some(it.into_state());
+
+        match r {
+            Ok(Some(v)) => {
+                let _ = writeln!(out, "{}", v);
+                true
+            }
+            Ok(None) => true,
+            Err(Stop::Exit(status)) => {
+                let _ = writeln!(out, "Program exited with status {}.", status);
+                kind != Kind::Declaration
+            }
+            Err(Stop::Error(err)) => {
+                let _ = writeln!(out, "Runtime Error: {}. {}.", err.msg, at(p, unit, err.pos));
+                let trace = err.trace.iter().filter(|f| f.func != INPUT);
+                for f in trace.take(crate::cmd::run::MAX_TRACE) {
+                    let _ = match (unit.first..unit.end).contains(&p.position(f.pos).line) {
+                        false => writeln!(out, "    in {}.", f.func),
+                        true => writeln!(out, "    in {} {}.", f.func, at(p, unit, f.pos)),
+                    };
+                }
+                kind != Kind::Declaration
+            }
+        }
+    }
+}
+
+// where `pos` is in the input of a unit: its column, and its line if the
+// input has several
+fn at(p: &Parser, unit: &Unit, pos: usize) -> String {
+    let pos = p.position(pos);
+    if pos.line < unit.first {
+        return "in an earlier input".to_string();
+    }
+    if pos.line >= unit.end {
+        return "in a later input".to_string();
+    }
+
+    let lines: Vec<&str> = unit.input.lines().collect();
+    let (line, column) = match pos.line - unit.first {
+        // the end of a wrapped input
+        n if n >= lines.len() => (lines.len(), lines.last().map_or(0, |l| l.len()) + 1),
+        n => (n + 1, pos.column),
+    };
+    match lines.len() {
+        1 => format!("at column {}", column),
+        _ => format!("at line {}, column {}", line, column),
+    }
+}
+
+// the offset in the input of a unit of a position on one of its lines
+fn offset(p: &Parser, unit: &Unit, pos: usize) -> usize {
+    let pos = p.position(pos);
+    let start: usize = unit
+        .input
+        .split_inclusive('\n')
+        .take(pos.line - unit.first)
+        .map(str::len)
+        .sum();
+    let line = &unit.input[start..];
+    start
+        + line
+            .char_indices()
+            .nth(pos.column - 1)
+            .map_or(line.len(), |(i, _)| i)
+}
+
+// the item of a declaration of the input of a unit, its text is the part
+// of the input at `span`
+fn item(p: &Parser, unit: &Unit, d: &dyn ast::Decl, span: Range<usize>) -> Item {
+    let at = |pos| offset(p, unit, pos) - span.start;
+    let mut item = Item {
+        src: unit.input[span.clone()].to_string(),
+        names: Vec::new(),
+        specs: 0,
+        declarators: Vec::new(),
+    };
+    defined(d, &mut item.names);
+
+    if let DeclKind::GenDecl(g) = d.kind() {
+        item.specs = at(g.specs.end);
+        for d in g.decls.iter() {
+            let end = d.init.as_ref().map_or(d.declarator.end, |x| x.end());
+            let name = d.declarator.name.as_ref().filter(|_| defines(g, d));
+            let span = at(d.declarator.pos)..at(end);
+            item.declarators.push((span, name.map(|n| n.name.clone())));
+        }
+    }
+    item
+}
+
+// the names an input uses, of identifiers and of tags
+fn used(input: &str) -> Vec<String> {
+    let mut s = scanner::Scanner::from(input.to_string());
+    let mut names = Vec::new();
+    let mut tag = None;
+    loop {
+        let (tok, lit) = match s.scan() {
+            Ok((tok, _, lit)) => (tok, lit.to_string()),
+            Err(_) => continue,
+        };
+        match tok {
+            Token::EOF => break,
+            Token::IDENT => names.push(match tag.take() {
+                Some(kw) => format!("{} {}", kw, lit),
+                None => lit,
+            }),
+            Token::STRUCT | Token::UNION | Token::ENUM => tag = Some(tok.to_str()),
+            _ => tag = None,
+        }
+    }
+    names
+}
+
+// reports whether the line `n` of an input, from 0, is a directive, the
+// declarations of a header are on the line of its `#include`
+fn directive(input: &str, n: usize) -> bool {
+    input
+        .lines()
+        .nth(n)
+        .is_some_and(|l| l.trim_start().starts_with('#'))
+}
+
+// adds the names a declaration defines: the objects, the functions with a
+// body, the typedef names, the tags with a body and the enumerators
+fn defined(d: &dyn ast::Decl, names: &mut Vec<String>) {
+    let g = match d.kind() {
+        DeclKind::FuncDecl(f) => {
+            names.extend(f.declarator.name.as_ref().map(|n| n.name.clone()));
+            return;
+        }
+        DeclKind::GenDecl(g) => g,
+        DeclKind::StaticAssert(_) => return,
+    };
+
+    match &g.specs.typ {
+        TypeSpec::Struct(s) if s.fields.is_some() => {
+            let kw = if s.kw == Token::UNION {
+                "union"
+            } else {
+                "struct"
+            };
+            if let Some(tag) = &s.tag {
+                names.push(format!("{} {}", kw, tag.name));
+            }
+        }
+        TypeSpec::Enum(e) => {
+            if let Some(list) = &e.enumerators {
+                if let Some(tag) = &e.tag {
+                    names.push(format!("enum {}", tag.name));
+                }
+                names.extend(list.iter().map(|c| c.name.name.clone()));
+            }
+        }
+        _ => {}
+    }
+
+    for d in g.decls.iter() {
+        if let Some(name) = d.declarator.name.as_ref().filter(|_| defines(g, d)) {
+            names.push(name.name.clone());
+        }
+    }
+}
+
+// reports whether a declarator defines its name: a typedef, or an object
+// not external
+fn defines(g: &ast::GenDecl, d: &ast::InitDecl) -> bool {
+    let typedef = g.specs.storage == Some(Token::TYPEDEF);
+    let prototype = d.declarator.function().is_some();
+    let external = g.specs.storage == Some(Token::EXTERN) && d.init.is_none();
+    typedef || !(prototype || external)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // runs the inputs of a session, returns what each printed
+    fn run(inputs: &[&str]) -> Vec<String> {
+        let mut s = Session::default();
+        inputs
+            .iter()
+            .map(|input| {
+                let mut out = Vec::new();
+                s.run(input, &mut out);
+                String::from_utf8(out).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_kinds() {
+        let tests = [
+            ("int x = 5;", Some(Kind::Declaration)),
+            ("typedef struct { int a; } T;", Some(Kind::Declaration)),
+            ("int f(int n) { return n + 1; }", Some(Kind::Definition)),
+            ("x = f(x);", Some(Kind::Statement)),
+            ("for (int i = 0; i < 3; i++) x += i;", Some(Kind::Statement)),
+            ("{ T t = { 1 }; x += t.a; }", Some(Kind::Statement)),
+            ("x * 2", Some(Kind::Expression)),
+            ("T t;", Some(Kind::Declaration)),
+            ("x +", None),
+            ("y", None),
+        ];
+
+        let mut s = Session::default();
+        for (i, (input, want)) in tests.iter().enumerate() {
+            let mut out = Vec::new();
+            assert_eq!(
+                *want,
+                s.run(input, &mut out),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_session() {
+        let got = run(&[
+            "#include <stdio.h>",
+            "int n = 2;",
+            "int sq(int x) { return x * x; }",
+            "sq(n) + 1",
+            "n = 7;",
+            "printf(\"%d\\n\", sq(n));",
+            "int sq(int x) { return x * x * x; }",
+            "sq(n)",
+            "struct point { int x, y; };",
+            "struct point p = { 1, 2 };",
+            "p",
+            "int n = 3;",
+            "n",
+            "\"abc\"",
+            "void hello(void) { puts(\"hello\"); }",
+            "hello()",
+            "static int calls(void) { static int c; return ++c; }",
+            "calls();",
+            "calls()",
+        ]);
+        let want = [
+            "",
+            "",
+            "",
+            "(int) 5\n",
+            "",
+            "49\n",
+            "",
+            "(int) 343\n",
+            "",
+            "",
+            "(struct point) { .x = 1, .y = 2 }\n",
+            "",
+            "(int) 3\n",
+            "(char [4]) \"abc\"\n",
+            "",
+            "hello\n",
+            "",
+            "",
+            "(int) 2\n",
+        ];
+        assert_eq!(want.to_vec(), got);
+    }
+
+    #[test]
+    fn test_errors() {
+        let got = run(&[
+            "int x = 1;",
+            "x +",
+            "y = 2;",
+            "int *p = 0;",
+            "*p = 1;",
+            "x",
+            "int f(int n) { return 10 / n; }",
+            "f(0)",
+            "exit(3);",
+            "x",
+            "int g(void) {\n  return 1\n}",
+        ]);
+        let want = [
+            "",
+            "Parser Error: expected expression, got ). at column 4.\n",
+            "Error: use of undeclared identifier 'y'. at column 1.\n",
+            "",
+            "Runtime Error: null pointer dereference. at column 1.\n",
+            "(int) 1\n",
+            "",
+            "Runtime Error: division by zero. in an earlier input.\n    in f.\n",
+            "Warning: implicit declaration of function 'exit'. at column 1.\nProgram exited with status 3.\n",
+            "(int) 1\n",
+            "Parser Error: expected ;, got }. at line 3, column 1.\n",
+        ];
+        assert_eq!(want.to_vec(), got);
+    }
+
+    #[test]
+    fn test_redefine() {
+        let got = run(&[
+            "int a = 1, b = 2, *p = &b;",
+            "int a = 3;",
+            "a + b + *p",
+            "int f(void) { return 1; } int g(void) { return 2; }",
+            "int f(void) { return 10; }",
+            "f() + g()",
+            "struct s { int x; };",
+            "int get(struct s v) { return v.x; }",
+            "typedef int num;",
+            "struct s { num y, x; };",
+            "get((struct s){ 6, 7 })",
+        ]);
+        let want = [
+            "",
+            "",
+            "(int) 7\n",
+            "",
+            "",
+            "(int) 12\n",
+            "",
+            "",
+            "",
+            "",
+            "(int) 7\n",
+        ];
+        assert_eq!(want.to_vec(), got);
+    }
+}
@@ -0,0 +1,219 @@
+//! The colours of the REPL's syntax highlighting. A theme gives each class
+//! of tokens the parameters of an ANSI SGR escape sequence, e.g. `1;35` for
+//! bold magenta, and is set from a preset and overrides:
+//! `dark,comment=gray,keyword=bold+blue`.
+
+use std::fmt;
+
+/// What a piece of the input is coloured as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    Keyword,
+    Type, // the keywords of type specifiers
+    Number,
+    String, // string and character literals
+    Comment,
+    Directive,
+    Operator,
+    Bracket, // the brackets matching at the cursor
+    Error,   // what the scanner rejects
+}
+
+impl Class {
+    pub const ALL: [Class; 9] = [
+        Class::Keyword,
+        Class::Type,
+        Class::Number,
+        Class::String,
+        Class::Comment,
+        Class::Directive,
+        Class::Operator,
+        Class::Bracket,
+        Class::Error,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Class::Keyword => "keyword",
+            Class::Type => "type",
+            Class::Number => "number",
+            Class::String => "string",
+            Class::Comment => "comment",
+            Class::Directive => "directive",
+            Class::Operator => "operator",
+            Class::Bracket => "bracket",
+            Class::Error => "error",
+        }
+    }
+}
+
+/// The presets, their styles are in the order of `Class::ALL`.
+const PRESETS: [(&str, [&str; 9]); 3] = [
+    (
+        "dark",
+        ["1;35", "36", "33", "32", "90", "34", "94", "1;4", "4;31"],
+    ),
+    (
+        "light",
+        ["1;34", "36", "35", "32", "90", "35", "1", "1;4", "4;31"],
+    ),
+    ("none", [""; 9]),
+];
+
+// the names of the colours and attributes a style may combine with `+`
+const NAMES: [(&str, &str); 22] = [
+    ("bold", "1"),
+    ("dim", "2"),
+    ("italic", "3"),
+    ("underline", "4"),
+    ("reverse", "7"),
+    ("black", "30"),
+    ("red", "31"),
+    ("green", "32"),
+    ("yellow", "33"),
+    ("blue", "34"),
+    ("magenta", "35"),
+    ("cyan", "36"),
+    ("white", "37"),
+    ("gray", "90"),
+    ("grey", "90"),
+    ("bright-red", "91"),
+    ("bright-green", "92"),
+    ("bright-yellow", "93"),
+    ("bright-blue", "94"),
+    ("bright-magenta", "95"),
+    ("bright-cyan", "96"),
+    ("bright-white", "97"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    styles: [String; 9], // by class, empty for none
+}
+
+impl Default for Theme {
+    /// The dark preset, none if the NO_COLOR variable is set.
+    fn default() -> Self {
+        let name = match std::env::var_os("NO_COLOR") {
+            Some(v) if !v.is_empty() => "none",
+            _ => "dark",
+        };
+        Self::preset(name).unwrap()
+    }
+}
+
+impl Theme {
+    pub fn preset(name: &str) -> Option<Self> {
+        let (_, styles) = PRESETS.iter().find(|(n, _)| *n == name)?;
+        Some(Self {
+            styles: styles.map(str::to_string),
+        })
+    }
+
+    /// Applies a comma-separated list of presets and `class=style`
+    /// overrides, a style combines names or SGR parameters with `+`. The
+    /// theme is unchanged if an item is invalid.
+    pub fn apply(&mut self, spec: &str) -> Result<(), String> {
+        let mut theme = self.clone();
+        for item in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let Some((class, style)) = item.split_once('=') else {
+                theme = Self::preset(item)
+                    .ok_or_else(|| format!("unknown theme '{}' (dark, light or none)", item))?;
+                continue;
+            };
+
+            let Some(i) = Class::ALL.iter().position(|c| c.name() == class.trim()) else {
+                return Err(format!("unknown class '{}'", class.trim()));
+            };
+            theme.styles[i] = parse_style(style.trim())?;
+        }
+        *self = theme;
+        Ok(())
+    }
+
+    /// Wraps text in the escape sequences of the style of its class.
+    pub fn paint(&self, class: Class, text: &str) -> String {
+        match self.styles[class as usize].as_str() {
+            "" => text.to_string(),
+            sgr => format!("\x1b[{}m{}\x1b[0m", sgr, text),
+        }
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, class) in Class::ALL.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            let style = match self.styles[i].as_str() {
+                "" => "none",
+                sgr => sgr,
+            };
+            write!(f, "{}={}", class.name(), style)?;
+        }
+        Ok(())
+    }
+}
+
+// the SGR parameters of a style, `none` for none
+fn parse_style(style: &str) -> Result<String, String> {
+    if style == "none" {
+        return Ok(String::new());
+    }
+
+    let mut params = Vec::new();
+    for part in style.split('+') {
+        match NAMES.iter().find(|(n, _)| *n == part) {
+            Some((_, sgr)) => params.push(*sgr),
+            None if !part.is_empty() && part.chars().all(|c| c.is_ascii_digit() || c == ';') => {
+                params.push(part)
+            }
+            None => return Err(format!("invalid style '{}'", style)),
+        }
+    }
+    Ok(params.join(";"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let tests = [
+            ("none", Ok("int")),
+            ("none,keyword=bold+blue", Ok("\x1b[1;34mint\x1b[0m")),
+            ("dark,keyword=1;32", Ok("\x1b[1;32mint\x1b[0m")),
+            ("light,keyword=none", Ok("int")),
+            ("keyword=bold+sky", Err("invalid style 'bold+sky'")),
+            ("keywords=red", Err("unknown class 'keywords'")),
+            (
+                "solarized",
+                Err("unknown theme 'solarized' (dark, light or none)"),
+            ),
+        ];
+        for (i, (spec, want)) in tests.iter().enumerate() {
+            let mut t = Theme::preset("dark").unwrap();
+            let got = t.apply(spec).map(|_| t.paint(Class::Keyword, "int"));
+            assert_eq!(
+                want.map(str::to_string).map_err(str::to_string),
+                got,
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                spec
+            );
+        }
+
+        let t = Theme::preset("dark").unwrap();
+        let mut u = Theme::preset("none").unwrap();
+        u.apply(&t.to_string()).unwrap();
+        assert_eq!(t, u);
+
+        // an invalid item leaves the theme as it was
+        let mut u = t.clone();
+        assert!(u.apply("none,keyword=bold,keywords=red").is_err());
+        assert_eq!(t, u);
+    }
+}
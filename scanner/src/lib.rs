@@ -1,4 +1,4 @@
-use token::Token;
+use token::{Position, Token};
 
 pub struct LineInfo {
     pub offset: usize,
@@ -6,66 +6,153 @@ pub struct LineInfo {
     pub column: usize,
 }
 
+pub type ScanResult<'a> = Result<(Token, usize, &'a str), (Token, usize, &'a str, String)>;
+
+const BOM: char = '\u{FEFF}';
+
 #[derive(Debug, Default)]
 pub struct Scanner {
     src: String,
 
-    ch: u8,           // currently processing character
+    ch: char,         // currently processing character
     offset: usize,    // position of the current character
     rd_offset: usize, // position of the next character
 
-    lines: Vec<usize>, // offset of the lines
+    lines: Vec<usize>,   // offset of the lines
+    invalid: Vec<usize>, // offset of the invalid UTF-8 sequences
+
+    trigraphs: bool, // replace `??x` trigraph sequences
 }
 
 impl From<String> for Scanner {
     fn from(src: String) -> Self {
-        let mut s = Self {
-            src,
-            ch: b' ',
-            offset: 0,
-            rd_offset: 0,
-            lines: vec![0],
-        };
-        s.next();
-        s
+        Self::new(src, Vec::new())
+    }
+}
+
+impl From<Vec<u8>> for Scanner {
+    fn from(src: Vec<u8>) -> Self {
+        match String::from_utf8(src) {
+            Ok(src) => Self::new(src, Vec::new()),
+            Err(e) => {
+                let (src, invalid) = decode_utf8(e.as_bytes());
+                Self::new(src, invalid)
+            }
+        }
     }
 }
 
 impl Scanner {
+    fn new(src: String, invalid: Vec<usize>) -> Self {
+        Self {
+            src,
+            invalid,
+            ..Self::default()
+        }
+        .init()
+    }
+
+    // rewinds the scanner to the start of the source
+    fn init(mut self) -> Self {
+        self.ch = ' ';
+        self.offset = 0;
+        self.rd_offset = 0;
+        self.lines = vec![0];
+        self.next();
+
+        // a byte order mark is only meaningful at the very start of the file
+        if self.ch == BOM {
+            self.next();
+        }
+
+        self
+    }
+
+    /// Enables the replacement of the nine `??x` trigraph sequences, like
+    /// the `-trigraphs` option of gcc. They are ignored by default.
+    pub fn with_trigraphs(mut self) -> Self {
+        self.trigraphs = true;
+        self.init()
+    }
+
+    // decodes the character at `offset` and returns it along with the
+    // number of bytes it occupies in the source.
+    fn decode(&self, offset: usize) -> Option<(char, usize)> {
+        let rest = &self.src[offset..];
+        let ch = rest.chars().next()?;
+
+        if self.trigraphs && rest.starts_with("??") {
+            if let Some(c) = rest[2..].chars().next().and_then(trigraph) {
+                return Some((c, 3));
+            }
+        }
+
+        Some((ch, ch.len_utf8()))
+    }
+
     fn next(&mut self) {
-        if let Some(&ch) = self.src.as_bytes().get(self.rd_offset) {
+        if let Some((ch, width)) = self.decode(self.rd_offset) {
             self.offset = self.rd_offset;
 
-            if self.ch == b'\n' {
+            if self.ch == '\n' {
                 self.lines.push(self.offset);
             }
 
             self.ch = ch;
-            self.rd_offset += 1;
+            self.rd_offset += width;
         } else {
             self.offset = self.src.len();
-            self.ch = 0;
+
+            if self.ch == '\n' {
+                self.lines.push(self.offset);
+            }
+
+            self.ch = '\0';
         }
     }
 
-    fn peek(&mut self) -> u8 {
-        *self.src.as_bytes().get(self.rd_offset).unwrap_or(&0)
+    fn peek(&self) -> char {
+        self.decode(self.rd_offset).map_or('\0', |(c, _)| c)
+    }
+
+    // returns the character after the one returned by peek
+    fn peek2(&self) -> char {
+        self.decode(self.rd_offset)
+            .and_then(|(_, width)| self.decode(self.rd_offset + width))
+            .map_or('\0', |(c, _)| c)
+    }
+
+    fn at_eof(&self) -> bool {
+        self.offset >= self.src.len()
+    }
+
+    // reports whether the current character replaces an invalid UTF-8 sequence
+    fn at_invalid(&self) -> bool {
+        self.ch == char::REPLACEMENT_CHARACTER && self.invalid.binary_search(&self.offset).is_ok()
     }
 
     fn skip_whitespace(&mut self) {
-        while self.ch == b' ' || self.ch == b'\t' || self.ch == b'\n' || self.ch == b'\r' {
-            self.next();
+        loop {
+            match self.ch {
+                ' ' | '\t' | '\n' | '\r' | '\x0B' | '\x0C' => self.next(),
+
+                // line splice
+                '\\' if self.peek() == '\n' => self.advance(2),
+                '\\' if self.peek() == '\r' && self.peek2() == '\n' => self.advance(3),
+
+                _ => break,
+            }
         }
     }
 
+    // moves past the next `len` characters
     fn advance(&mut self, len: usize) {
-        let offset = (self.offset + len).min(self.src.len());
-        self.offset = offset;
-        self.rd_offset = self.offset + 1;
-        self.ch = *self.src.as_bytes().get(offset).unwrap_or(&0);
+        for _ in 0..len {
+            self.next();
+        }
     }
 
-    fn switch(&mut self, def: Token, alts: &[(u8, Token)]) -> Token {
+    fn switch(&mut self, def: Token, alts: &[(char, Token)]) -> Token {
         let p = self.peek();
 
         for &(c, t) in alts {
@@ -89,7 +176,7 @@ impl Scanner {
                 li.push(LineInfo {
                     offset: *s,
                     line: i + 1,
-                    column: *e - *s,
+                    column: self.src[*s..*e].chars().count(),
                 })
             });
 
@@ -97,124 +184,122 @@ impl Scanner {
             li.push(LineInfo {
                 offset: *i,
                 line: self.lines.len(),
-                column: self.src.len() - i,
+                column: self.src[*i..].chars().count(),
             });
         }
 
         li
     }
 
-    pub fn scan(&mut self) -> Result<(Token, usize, &str), (Token, usize, &str, String)> {
-        self.skip_whitespace();
-
-        let pos = self.offset;
+    /// Converts a byte offset of an already scanned token into a line and
+    /// column. Columns are counted in characters and start at 1.
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.src.len());
 
-        let tok = match self.ch {
-            b'a'..=b'z' | b'A'..=b'Z' | b'_' | b'$' => {
-                let len = count_if(self.src[pos..].bytes(), is_letter);
-                self.advance(len);
-                let lit = &self.src[pos..pos + len];
-                let tok = token::lookup(lit);
-
-                return Ok((tok, pos, lit));
-            }
+        let i = match self.lines.binary_search(&offset) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
 
-            b'0' => {
-                self.next();
-                let prefix = self.ch;
-
-                match prefix {
-                    b'b' => {
-                        self.next();
-
-                        let digits = count_if(self.src[self.offset..].bytes(), is_binary_digit);
-                        self.advance(digits);
-                        let lit = &self.src[pos..self.offset];
-
-                        if digits == 0 {
-                            let msg = format!(
-                                "at least one binary digit required after '0{}'",
-                                prefix as char
-                            );
-                            return Err((Token::ILLEGAL, pos, lit, msg));
-                        }
-                        return Ok((Token::INTEGER, pos, lit));
-                    }
+        let mut start = self.lines[i];
+        if start == 0 && self.src.starts_with(BOM) {
+            start = BOM.len_utf8();
+        }
 
-                    b'x' | b'X' => {
-                        self.next();
+        Position {
+            offset,
+            line: i + 1,
+            column: self.src[start.min(offset)..offset].chars().count() + 1,
+        }
+    }
 
-                        let digits = count_if(self.src[self.offset..].bytes(), is_hex_digit);
-                        self.advance(digits);
-                        let lit = &self.src[pos..self.offset];
+    pub fn src(&self) -> &str {
+        &self.src
+    }
 
-                        if digits == 0 {
-                            let msg = format!(
-                                "at least one hex digit required after '0{}'",
-                                prefix as char
-                            );
-                            return Err((Token::ILLEGAL, pos, lit, msg));
-                        }
-                        return Ok((Token::INTEGER, pos, lit));
-                    }
+    pub fn scan(&mut self) -> ScanResult<'_> {
+        self.skip_whitespace();
 
-                    _ => {
-                        let digits = count_if(self.src[self.offset..].bytes(), is_octal_digit);
-                        self.advance(digits);
-                        let lit = &self.src[pos..self.offset];
+        let pos = self.offset;
 
-                        return Ok((Token::INTEGER, pos, lit));
-                    }
-                }
-            }
+        let tok = match self.ch {
+            c if is_letter(c) => return self.scan_identifier(pos),
+            '\\' if matches!(self.peek(), 'u' | 'U') => return self.scan_identifier(pos),
 
-            b'1'..=b'9' => {
-                let digits = count_if(self.src[self.offset..].bytes(), is_digit);
-                self.advance(digits);
-                let lit = &self.src[pos..self.offset];
+            '0'..='9' => return self.scan_number(pos),
 
-                return Ok((Token::INTEGER, pos, lit));
-            }
+            '"' => return self.scan_quoted(pos, Token::STRING),
+            '\'' => return self.scan_quoted(pos, Token::CHARACTER),
 
-            b'+' => self.switch(
-                Token::PLUS,
-                &[(b'+', Token::INC), (b'=', Token::PLUS_ASSIGN)],
-            ),
+            '+' => self.switch(Token::PLUS, &[('+', Token::INC), ('=', Token::PLUS_ASSIGN)]),
 
-            b'-' => self.switch(
+            '-' => self.switch(
                 Token::MINUS,
                 &[
-                    (b'-', Token::DEC),
-                    (b'>', Token::ARROW),
-                    (b'=', Token::MINUS_ASSIGN),
+                    ('-', Token::DEC),
+                    ('>', Token::ARROW),
+                    ('=', Token::MINUS_ASSIGN),
                 ],
             ),
 
-            b'*' => self.switch(Token::ASTERISK, &[(b'=', Token::MUL_ASSIGN)]),
-            b'/' => self.switch(Token::SLASH, &[(b'=', Token::DIV_ASSIGN)]),
-            b'%' => self.switch(Token::REM, &[(b'=', Token::REM_ASSIGN)]),
+            '*' => self.switch(Token::ASTERISK, &[('=', Token::MUL_ASSIGN)]),
+            '/' => match self.peek() {
+                '/' | '*' => return self.scan_comment(pos),
+                _ => self.switch(Token::SLASH, &[('=', Token::DIV_ASSIGN)]),
+            },
+            '%' => match self.peek() {
+                // %: and %:%: digraphs
+                ':' => {
+                    self.next();
 
-            b'&' => self.switch(
-                Token::AND,
-                &[(b'=', Token::AND_ASSIGN), (b'&', Token::LAND)],
-            ),
-            b'~' => Token::TILDE,
-            b'?' => Token::TERNERY,
-            b'.' => Token::DOT,
-            b'=' => self.switch(Token::ASSIGN, &[(b'=', Token::EQL)]),
-            b'|' => self.switch(Token::OR, &[(b'=', Token::OR_ASSIGN), (b'|', Token::LOR)]),
-            b'^' => self.switch(Token::XOR, &[(b'=', Token::XOR_ASSIGN)]),
-            b'!' => self.switch(Token::NOT, &[(b'=', Token::NEQ)]),
-            b'<' => match self.peek() {
-                b'=' => {
+                    if self.peek() == '%' && self.peek2() == ':' {
+                        self.advance(2);
+                        Token::HASH_HASH
+                    } else {
+                        Token::HASH
+                    }
+                }
+                _ => self.switch(
+                    Token::REM,
+                    &[('=', Token::REM_ASSIGN), ('>', Token::RBRACE)],
+                ),
+            },
+
+            '&' => self.switch(Token::AND, &[('=', Token::AND_ASSIGN), ('&', Token::LAND)]),
+            '~' => Token::TILDE,
+            '?' => Token::TERNERY,
+            '.' if is_digit(self.peek()) => return self.scan_number(pos),
+            '.' => {
+                if self.peek() == '.' && self.peek2() == '.' {
+                    self.advance(2);
+                    Token::ELLIPSE
+                } else {
+                    Token::DOT
+                }
+            }
+            '#' => self.switch(Token::HASH, &[('#', Token::HASH_HASH)]),
+            '=' => self.switch(Token::ASSIGN, &[('=', Token::EQL)]),
+            '|' => self.switch(Token::OR, &[('=', Token::OR_ASSIGN), ('|', Token::LOR)]),
+            '^' => self.switch(Token::XOR, &[('=', Token::XOR_ASSIGN)]),
+            '!' => self.switch(Token::NOT, &[('=', Token::NEQ)]),
+            '<' => match self.peek() {
+                '=' => {
                     self.next();
                     Token::LEQ
                 }
-                b'<' => {
+                ':' => {
+                    self.next();
+                    Token::LBRACK
+                }
+                '%' => {
+                    self.next();
+                    Token::LBRACE
+                }
+                '<' => {
                     self.next();
 
                     match self.peek() {
-                        b'=' => {
+                        '=' => {
                             self.next();
                             Token::SHL_ASSIGN
                         }
@@ -224,15 +309,15 @@ impl Scanner {
                 _ => Token::LT,
             },
 
-            b'>' => match self.peek() {
-                b'=' => {
+            '>' => match self.peek() {
+                '=' => {
                     self.next();
                     Token::GEQ
                 }
-                b'>' => {
+                '>' => {
                     self.next();
                     match self.peek() {
-                        b'=' => {
+                        '=' => {
                             self.next();
                             Token::SHR_ASSIGN
                         }
@@ -242,24 +327,26 @@ impl Scanner {
                 _ => Token::GT,
             },
 
-            b';' => Token::SEMICOLON,
-            b',' => Token::COMMA,
-            b'(' => Token::LPAREN,
-            b')' => Token::RPAREN,
-            b'{' => Token::LBRACE,
-            b'}' => Token::RBRACE,
-            b'[' => Token::LBRACK,
-            b']' => Token::RBRACK,
-            b':' => Token::COLON,
-
-            0 => return Ok((Token::EOF, pos, "")),
+            ';' => Token::SEMICOLON,
+            ',' => Token::COMMA,
+            '(' => Token::LPAREN,
+            ')' => Token::RPAREN,
+            '{' => Token::LBRACE,
+            '}' => Token::RBRACE,
+            '[' => Token::LBRACK,
+            ']' => Token::RBRACK,
+            ':' => self.switch(Token::COLON, &[('>', Token::RBRACK)]),
+
+            _ if self.at_eof() => return Ok((Token::EOF, pos, "")),
             _ => {
-                let ch = self.ch;
+                let msg = if self.at_invalid() {
+                    "invalid UTF-8 encoding".to_string()
+                } else {
+                    format!("illegal character {:?} found", self.ch)
+                };
 
                 self.next();
 
-                let msg = format!("illegal character '{}' found", ch as char);
-
                 return Err((Token::ILLEGAL, pos, &self.src[pos..self.offset], msg));
             }
         };
@@ -268,26 +355,428 @@ impl Scanner {
 
         Ok((tok, pos, &self.src[pos..self.offset]))
     }
+
+    // scans an integer or a floating constant with its suffix
+    fn scan_number(&mut self, pos: usize) -> ScanResult<'_> {
+        let mut tok = Token::INTEGER;
+        let mut err = None;
+
+        let digits = |s: &Self, f: fn(char) -> bool| count_if(s.src[s.offset..].chars(), f);
+
+        if self.ch == '0' && matches!(self.peek(), 'b' | 'B' | 'x' | 'X') {
+            self.next();
+            let prefix = self.ch;
+            self.next();
+
+            let hex = matches!(prefix, 'x' | 'X');
+            let is_digit = if hex { is_hex_digit } else { is_binary_digit };
+
+            let mut mantissa = digits(self, is_digit);
+            self.advance(mantissa);
+
+            if hex && self.ch == '.' {
+                tok = Token::FLOATING;
+                self.next();
+
+                let n = digits(self, is_hex_digit);
+                self.advance(n);
+                mantissa += n;
+            }
+
+            if mantissa == 0 {
+                let kind = if hex { "hex" } else { "binary" };
+                err = Some(format!(
+                    "at least one {} digit required after '0{}'",
+                    kind, prefix
+                ));
+            }
+
+            if hex && matches!(self.ch, 'p' | 'P') {
+                tok = Token::FLOATING;
+                self.scan_exponent(&mut err);
+            } else if tok == Token::FLOATING {
+                err.get_or_insert("hexadecimal floating constant requires an exponent".to_string());
+            }
+        } else {
+            let n = digits(self, is_digit);
+            self.advance(n);
+
+            if self.ch == '.' {
+                tok = Token::FLOATING;
+                self.next();
+
+                let n = digits(self, is_digit);
+                self.advance(n);
+            }
+
+            if matches!(self.ch, 'e' | 'E') {
+                tok = Token::FLOATING;
+                self.scan_exponent(&mut err);
+            }
+
+            let lit = &self.src[pos..self.offset];
+            if tok == Token::INTEGER && lit.starts_with('0') {
+                if let Some(c) = lit.chars().find(|&c| !is_octal_digit(c)) {
+                    err = Some(format!("invalid digit '{}' in octal constant", c));
+                }
+            }
+        }
+
+        let start = self.offset;
+        let n = count_if(self.src[self.offset..].chars(), is_letter_or_digit);
+        self.advance(n);
+
+        let suffix = &self.src[start..self.offset];
+        if !suffix.is_empty() && !valid_suffix(tok, suffix) {
+            let kind = if tok == Token::INTEGER {
+                "integer"
+            } else {
+                "floating"
+            };
+            err.get_or_insert(format!("invalid suffix '{}' on {} constant", suffix, kind));
+        }
+
+        let lit = &self.src[pos..self.offset];
+
+        match err {
+            Some(msg) => Err((Token::ILLEGAL, pos, lit, msg)),
+            None => Ok((tok, pos, lit)),
+        }
+    }
+
+    // scans the exponent of a floating constant, `e` or `p` is the current
+    // character
+    fn scan_exponent(&mut self, err: &mut Option<String>) {
+        self.next();
+
+        if matches!(self.ch, '+' | '-') {
+            self.next();
+        }
+
+        let digits = count_if(self.src[self.offset..].chars(), is_digit);
+        self.advance(digits);
+
+        if digits == 0 {
+            err.get_or_insert("exponent has no digits".to_string());
+        }
+    }
+
+    fn scan_identifier(&mut self, pos: usize) -> ScanResult<'_> {
+        let mut err = None;
+        let mut ucn = false;
+
+        loop {
+            if self.ch == '\\' && matches!(self.peek(), 'u' | 'U') {
+                ucn = true;
+                if let Err(msg) = self.scan_ucn(self.offset == pos) {
+                    err.get_or_insert(msg);
+                }
+            } else if is_letter_or_digit(self.ch) {
+                self.next();
+            } else {
+                break;
+            }
+        }
+
+        if let Some(msg) = err {
+            return Err((Token::IDENT, pos, &self.src[pos..self.offset], msg));
+        }
+
+        // encoding prefix of a string or character literal
+        if matches!(&self.src[pos..self.offset], "L" | "u" | "U" | "u8") {
+            match self.ch {
+                '"' => return self.scan_quoted(pos, Token::STRING),
+                '\'' => return self.scan_quoted(pos, Token::CHARACTER),
+                _ => {}
+            }
+        }
+
+        let lit = &self.src[pos..self.offset];
+        let tok = if ucn {
+            Token::IDENT
+        } else {
+            token::lookup(lit)
+        };
+
+        Ok((tok, pos, lit))
+    }
+
+    // scans a universal character name `\uXXXX` or `\UXXXXXXXX` inside an
+    // identifier, `first` tells whether it starts the identifier.
+    fn scan_ucn(&mut self, first: bool) -> Result<(), String> {
+        let start = self.offset;
+        let c = self.scan_ucn_value()?;
+
+        if (first && !unicode_ident::is_xid_start(c)) || !unicode_ident::is_xid_continue(c) {
+            return Err(format!(
+                "universal character name '{}' is not allowed in an identifier",
+                &self.src[start..self.offset]
+            ));
+        }
+
+        Ok(())
+    }
+
+    // scans the value of a universal character name, the current character
+    // must be the leading '\'
+    fn scan_ucn_value(&mut self) -> Result<char, String> {
+        let start = self.offset;
+        self.next();
+
+        let n = if self.ch == 'u' { 4 } else { 8 };
+        self.next();
+
+        let digits = count_if(self.src[self.offset..].chars().take(n), is_hex_digit);
+        self.advance(digits);
+
+        if digits != n {
+            return Err("incomplete universal character name".to_string());
+        }
+
+        let spelling = &self.src[start..self.offset];
+        let value = u32::from_str_radix(&spelling[2..], 16).unwrap_or(u32::MAX);
+
+        // C11 6.4.3: only '$', '@' and '`' may be named below U+00A0
+        match char::from_u32(value) {
+            Some(c) if value >= 0xA0 || matches!(c, '$' | '@' | '`') => Ok(c),
+            _ => Err(format!("invalid universal character name '{}'", spelling)),
+        }
+    }
+
+    // scans a string or character literal, the current character is the
+    // opening quote.
+    fn scan_quoted(&mut self, pos: usize, tok: Token) -> ScanResult<'_> {
+        let quote = self.ch;
+        self.next();
+
+        let mut err = None;
+        let mut n = 0;
+
+        loop {
+            if self.ch == '\n' || self.at_eof() {
+                let msg = match tok {
+                    Token::STRING => "string literal not terminated",
+                    _ => "character literal not terminated",
+                };
+                return Err((tok, pos, &self.src[pos..self.offset], msg.to_string()));
+            }
+
+            if self.ch == quote {
+                self.next();
+                break;
+            }
+
+            if self.ch == '\\' {
+                if let Err(msg) = self.scan_escape() {
+                    err.get_or_insert(msg);
+                }
+            } else {
+                if self.at_invalid() {
+                    err.get_or_insert("invalid UTF-8 encoding".to_string());
+                }
+                self.next();
+            }
+
+            n += 1;
+        }
+
+        if tok == Token::CHARACTER && n == 0 {
+            err.get_or_insert("empty character literal".to_string());
+        }
+
+        let lit = &self.src[pos..self.offset];
+
+        match err {
+            Some(msg) => Err((tok, pos, lit, msg)),
+            None => Ok((tok, pos, lit)),
+        }
+    }
+
+    // scans an escape sequence, the current character is the '\'
+    fn scan_escape(&mut self) -> Result<(), String> {
+        match self.peek() {
+            '\'' | '"' | '?' | '\\' | 'a' | 'b' | 'f' | 'n' | 'r' | 't' | 'v' => {
+                self.advance(2);
+                Ok(())
+            }
+
+            '0'..='7' => {
+                self.next();
+                let digits = count_if(self.src[self.offset..].chars().take(3), is_octal_digit);
+                self.advance(digits);
+                Ok(())
+            }
+
+            'x' => {
+                self.advance(2);
+                let digits = count_if(self.src[self.offset..].chars(), is_hex_digit);
+                self.advance(digits);
+
+                if digits == 0 {
+                    return Err("at least one hex digit required after '\\x'".to_string());
+                }
+                Ok(())
+            }
+
+            'u' | 'U' => self.scan_ucn_value().map(|_| ()),
+
+            // line splice
+            '\n' => {
+                self.advance(2);
+                Ok(())
+            }
+
+            '\0' => {
+                self.next();
+                Ok(())
+            }
+
+            c => {
+                self.advance(2);
+                Err(format!("unknown escape sequence '\\{}'", c))
+            }
+        }
+    }
+
+    // scans a `//` or `/* */` comment, the current character is the first '/'
+    fn scan_comment(&mut self, pos: usize) -> ScanResult<'_> {
+        self.next();
+
+        let mut invalid = false;
+
+        if self.ch == '/' {
+            while self.ch != '\n' && !self.at_eof() {
+                invalid |= self.at_invalid();
+                self.next();
+            }
+        } else {
+            self.next();
+
+            loop {
+                if self.at_eof() {
+                    let msg = "comment not terminated".to_string();
+                    return Err((Token::COMMENT, pos, &self.src[pos..self.offset], msg));
+                }
+
+                invalid |= self.at_invalid();
+
+                let ch = self.ch;
+                self.next();
+
+                if ch == '*' && self.ch == '/' {
+                    self.next();
+                    break;
+                }
+            }
+        }
+
+        let lit = &self.src[pos..self.offset];
+
+        if invalid {
+            return Err((
+                Token::COMMENT,
+                pos,
+                lit,
+                "invalid UTF-8 encoding".to_string(),
+            ));
+        }
+
+        Ok((Token::COMMENT, pos, lit))
+    }
+}
+
+// decodes `src` replacing every invalid UTF-8 sequence with U+FFFD, it also
+// returns the offsets of the replacements.
+fn decode_utf8(mut src: &[u8]) -> (String, Vec<usize>) {
+    let mut s = String::with_capacity(src.len());
+    let mut invalid = Vec::new();
+
+    loop {
+        match std::str::from_utf8(src) {
+            Ok(valid) => {
+                s.push_str(valid);
+                return (s, invalid);
+            }
+
+            Err(e) => {
+                let (valid, rest) = src.split_at(e.valid_up_to());
+                s.push_str(std::str::from_utf8(valid).unwrap_or_default());
+
+                invalid.push(s.len());
+                s.push(char::REPLACEMENT_CHARACTER);
+
+                src = &rest[e.error_len().unwrap_or(rest.len())..];
+            }
+        }
+    }
+}
+
+// returns the replacement of the trigraph `??c`
+fn trigraph(c: char) -> Option<char> {
+    Some(match c {
+        '=' => '#',
+        '(' => '[',
+        '/' => '\\',
+        ')' => ']',
+        '\'' => '^',
+        '<' => '{',
+        '!' => '|',
+        '>' => '}',
+        '-' => '~',
+        _ => return None,
+    })
 }
 
-fn is_letter(c: u8) -> bool {
-    c.is_ascii_alphanumeric() || c == b'_' || c == b'$'
+fn is_letter(c: char) -> bool {
+    c.is_ascii_alphabetic()
+        || c == '_'
+        || c == '$'
+        || (!c.is_ascii() && unicode_ident::is_xid_start(c))
 }
 
-fn is_digit(c: u8) -> bool {
+/// Reports whether a character can be part of an identifier: a letter, a
+/// digit, `_`, `$` or a Unicode XID_Continue character.
+pub fn is_letter_or_digit(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || c == '_'
+        || c == '$'
+        || (!c.is_ascii() && unicode_ident::is_xid_continue(c))
+}
+
+fn is_digit(c: char) -> bool {
     c.is_ascii_digit()
 }
 
-fn is_binary_digit(c: u8) -> bool {
-    c == b'0' || c == b'1'
+fn is_binary_digit(c: char) -> bool {
+    c == '0' || c == '1'
 }
 
-fn is_hex_digit(c: u8) -> bool {
+fn is_hex_digit(c: char) -> bool {
     c.is_ascii_hexdigit()
 }
 
-fn is_octal_digit(c: u8) -> bool {
-    c >= b'0' && c <= b'7'
+fn is_octal_digit(c: char) -> bool {
+    ('0'..='7').contains(&c)
+}
+
+// reports whether `suffix` is a valid suffix of an integer (u, l, ll and
+// their combinations) or a floating (f or l) constant
+fn valid_suffix(tok: Token, suffix: &str) -> bool {
+    let lower = suffix.to_ascii_lowercase();
+
+    if tok == Token::FLOATING {
+        return lower == "f" || lower == "l";
+    }
+
+    // ll must not mix cases
+    if suffix.contains("lL") || suffix.contains("Ll") {
+        return false;
+    }
+
+    matches!(
+        lower.as_str(),
+        "u" | "l" | "ul" | "lu" | "ll" | "ull" | "llu"
+    )
 }
 
 fn count_if<I: Copy>(src: impl Iterator<Item = I>, check: impl Fn(I) -> bool) -> usize {
@@ -310,24 +799,40 @@ mod tests {
             (IDENT, "intIs_32bit"),
             (IDENT, "_Give_me_100$"),
             (IDENT, "$"),
+            (IDENT, "naïve_größe"),
+            (IDENT, "変数"),
+            (IDENT, "\\u00E9t\\U000000E9"),
             (INTEGER, "1234567890"),
             (INTEGER, "01234567"),
             (INTEGER, "0x123456790abcdefABCDEF"),
             (INTEGER, "0b1010"),
-            // (FLOATING, "0."),
-            // (FLOATING, ".1"),
-            // (FLOATING, "3.1"),
-            // (FLOATING, "9.e10"),
-            // (FLOATING, "9.e-10"),
-            // (FLOATING, "9.e+10"),
-            // (FLOATING, "9.1e10"),
-            // (FLOATING, "9.1e-10"),
-            // (FLOATING, "9.1e+10"),
-            // (FLOATING, ".1e10"),
-            // (FLOATING, ".1e-10"),
-            // (FLOATING, ".1e+10"),
-            // (STRING, "\"crepl\""),
-            // (STRING, "\"He said, \\\"I can eat 4 mango\\\".\""),
+            (FLOATING, "0."),
+            (FLOATING, ".1"),
+            (FLOATING, "3.1"),
+            (FLOATING, "9.e10"),
+            (FLOATING, "9.e-10"),
+            (FLOATING, "9.e+10"),
+            (FLOATING, "9.1e10"),
+            (FLOATING, "9.1e-10"),
+            (FLOATING, "9.1e+10"),
+            (FLOATING, ".1e10"),
+            (FLOATING, ".1e-10"),
+            (FLOATING, ".1e+10"),
+            (FLOATING, "1.5f"),
+            (FLOATING, "2e3L"),
+            (FLOATING, "0x1.8p3"),
+            (INTEGER, "10u"),
+            (INTEGER, "10LL"),
+            (INTEGER, "0xffUL"),
+            (INTEGER, "0"),
+            (STRING, "\"crepl\""),
+            (STRING, "\"He said, \\\"I can eat 4 mango\\\".\""),
+            (STRING, "u8\"héllo wörld\""),
+            (STRING, "L\"\\x41\\101\\u00e9\""),
+            (CHARACTER, "'a'"),
+            (CHARACTER, "'\\n'"),
+            (CHARACTER, "U'é'"),
+            (COMMENT, "/* ünïcödé ✓ */"),
             (ASSIGN, "="),
             (PLUS_ASSIGN, "+="),
             (MINUS_ASSIGN, "-="),
@@ -364,7 +869,15 @@ mod tests {
             (TERNERY, "?"),
             (DOT, "."),
             (ARROW, "->"),
-            //(ELLIPSE, "..."),
+            (ELLIPSE, "..."),
+            (HASH, "#"),
+            (HASH_HASH, "##"),
+            (LBRACK, "<:"),
+            (RBRACK, ":>"),
+            (LBRACE, "<%"),
+            (RBRACE, "%>"),
+            (HASH, "%:"),
+            (HASH_HASH, "%:%:"),
             (COMMA, ","),
             (SEMICOLON, ";"),
             (COLON, ":"),
@@ -431,4 +944,118 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_scan_position() {
+        let source = "\u{FEFF}int été;\n  /* ✓ */ x = 'é';";
+
+        let tests = [
+            (INT, 1, 1),
+            (IDENT, 1, 5),
+            (SEMICOLON, 1, 8),
+            (COMMENT, 2, 3),
+            (IDENT, 2, 11),
+            (ASSIGN, 2, 13),
+            (CHARACTER, 2, 15),
+            (SEMICOLON, 2, 18),
+            (EOF, 2, 19),
+        ];
+
+        let mut s = Scanner::from(source.to_string());
+
+        for (i, t) in tests.iter().enumerate() {
+            let (tok, pos, _) = s.scan().unwrap();
+            let p = s.position(pos);
+
+            assert_eq!(
+                *t,
+                (tok, p.line, p.column),
+                "[{}/{}] test failed.",
+                i + 1,
+                tests.len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_scan_errors() {
+        let tests: [(&[u8], &str); 13] = [
+            (b"\xff", "invalid UTF-8 encoding"),
+            (b"\"a\xc3\"", "invalid UTF-8 encoding"),
+            (b"// \xe2\x82", "invalid UTF-8 encoding"),
+            (b"\\u12", "incomplete universal character name"),
+            (b"a\\u0041", "invalid universal character name '\\u0041'"),
+            (
+                b"\\u0301x",
+                "universal character name '\\u0301' is not allowed in an identifier",
+            ),
+            (b"\"abc", "string literal not terminated"),
+            (b"/* abc", "comment not terminated"),
+            (b"089", "invalid digit '8' in octal constant"),
+            (b"1e+", "exponent has no digits"),
+            (
+                b"0x1.8",
+                "hexadecimal floating constant requires an exponent",
+            ),
+            (b"10lL", "invalid suffix 'lL' on integer constant"),
+            (b"1.5u", "invalid suffix 'u' on floating constant"),
+        ];
+
+        for (i, (src, msg)) in tests.iter().enumerate() {
+            let mut s = Scanner::from(src.to_vec());
+            let err = s.scan().unwrap_err();
+
+            assert_eq!(*msg, err.3, "[{}/{}] test failed.", i + 1, tests.len());
+        }
+
+        let mut s = Scanner::from("x € y".to_string());
+        assert_eq!(IDENT, s.scan().unwrap().0);
+        assert_eq!("illegal character '€' found", s.scan().unwrap_err().3);
+        assert_eq!(IDENT, s.scan().unwrap().0);
+    }
+
+    #[test]
+    fn test_scan_trigraphs() {
+        let source = "??=define ARR(x) x ??( 0 ??) ??< a ??! b ??' ??-c ??> ??/\n ??? ";
+
+        let tests = [
+            (HASH, "??="),
+            (IDENT, "define"),
+            (IDENT, "ARR"),
+            (LPAREN, "("),
+            (IDENT, "x"),
+            (RPAREN, ")"),
+            (IDENT, "x"),
+            (LBRACK, "??("),
+            (INTEGER, "0"),
+            (RBRACK, "??)"),
+            (LBRACE, "??<"),
+            (IDENT, "a"),
+            (OR, "??!"),
+            (IDENT, "b"),
+            (XOR, "??'"),
+            (TILDE, "??-"),
+            (IDENT, "c"),
+            (RBRACE, "??>"),
+            (TERNERY, "?"),
+            (TERNERY, "?"),
+            (TERNERY, "?"),
+            (EOF, ""),
+        ];
+
+        let mut s = Scanner::from(source.to_string()).with_trigraphs();
+
+        for (i, t) in tests.iter().enumerate() {
+            let (tok, _, lit) = s.scan().unwrap();
+
+            assert_eq!(*t, (tok, lit), "[{}/{}] test failed.", i + 1, tests.len());
+        }
+
+        // without the option they are plain question marks
+        let mut s = Scanner::from("??=".to_string());
+
+        assert_eq!(TERNERY, s.scan().unwrap().0);
+        assert_eq!(TERNERY, s.scan().unwrap().0);
+        assert_eq!(ASSIGN, s.scan().unwrap().0);
+    }
 }
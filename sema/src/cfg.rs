@@ -0,0 +1,606 @@
+//! Control-flow graphs of function bodies, and the checks made on them:
+//! jumps and labels out of place, functions falling off their end and
+//! code that is never executed.
+
+use std::collections::HashMap;
+
+use ast::{BlockStmt, DeclSpec, Expr, ExprKind, GenDecl, Ident, InitDecl, Stmt, StmtKind};
+
+use crate::consts::Eval;
+use crate::types::Type;
+use crate::{Diagnostic, Info, Resolver, Severity, SymbolKind};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub usize);
+
+/// The work done by a block, in order.
+pub enum Node<'a> {
+    Decl(&'a DeclSpec, &'a InitDecl), // a local declaration, its initializer runs here
+    Expr(&'a dyn Expr),               // a full expression: statement, condition or return value
+}
+
+/// A straight sequence of nodes. A block ending with a condition has the
+/// true edge first; the successors of a switch are its cases in order,
+/// then the default or the end of the switch.
+#[derive(Default)]
+pub struct Block<'a> {
+    pub nodes: Vec<Node<'a>>,
+    pub succs: Vec<BlockId>,
+    pub pos: Option<usize>, // the first statement in the block
+}
+
+pub struct Cfg<'a> {
+    pub blocks: Vec<Block<'a>>,
+    pub entry: BlockId,
+    pub exit: BlockId,         // reached by returns and by the end of the body
+    pub falls_off: BlockId,    // ends the body, without a return
+    pub returns: Vec<BlockId>, // the blocks ending with a return
+}
+
+impl<'a> Cfg<'a> {
+    /// Builds the graph of a function body checked into `info`, and the
+    /// errors for jumps and labels out of place.
+    pub fn build(body: &'a BlockStmt, info: &Info) -> (Self, Vec<Diagnostic>) {
+        let mut b = Builder {
+            info,
+            blocks: vec![Block::default(), Block::default()],
+            cur: 0,
+            breaks: Vec::new(),
+            continues: Vec::new(),
+            switches: Vec::new(),
+            labels: HashMap::new(),
+            gotos: Vec::new(),
+            vlas: Vec::new(),
+            returns: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+
+        b.cur = b.new_block();
+        b.edge(0, b.cur);
+        b.block(body);
+
+        let falls_off = b.cur;
+        b.edge(falls_off, 1);
+        b.goto_vlas();
+
+        let cfg = Cfg {
+            blocks: b.blocks,
+            entry: BlockId(0),
+            exit: BlockId(1),
+            falls_off: BlockId(falls_off),
+            returns: b.returns.into_iter().map(BlockId).collect(),
+        };
+
+        (cfg, b.diagnostics)
+    }
+
+    pub fn block(&self, id: BlockId) -> &Block<'a> {
+        &self.blocks[id.0]
+    }
+
+    pub fn preds(&self) -> Vec<Vec<BlockId>> {
+        let mut preds = vec![Vec::new(); self.blocks.len()];
+
+        for (i, b) in self.blocks.iter().enumerate() {
+            for s in b.succs.iter() {
+                preds[s.0].push(BlockId(i));
+            }
+        }
+
+        preds
+    }
+
+    /// Marks the blocks reachable from `from`.
+    pub fn reachable(&self, from: BlockId) -> Vec<bool> {
+        let mut seen = vec![false; self.blocks.len()];
+        let mut work = vec![from];
+
+        while let Some(id) = work.pop() {
+            if !std::mem::replace(&mut seen[id.0], true) {
+                work.extend(self.blocks[id.0].succs.iter().copied());
+            }
+        }
+
+        seen
+    }
+}
+
+// library functions that never return, like clang knows from their
+// declarations
+const NORETURN: [&str; 5] = ["exit", "_Exit", "abort", "quick_exit", "longjmp"];
+
+struct Switch {
+    dispatch: usize,
+    default: Option<usize>, // position of the default label
+    vlas: usize,            // the VLAs in scope at the switch
+}
+
+struct Builder<'a, 'i> {
+    info: &'i Info,
+    blocks: Vec<Block<'a>>,
+    cur: usize,
+
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+    switches: Vec<Switch>,
+
+    labels: HashMap<&'a str, (usize, Vec<usize>)>, // label blocks and the VLAs in scope
+    gotos: Vec<(usize, &'a Ident, Vec<usize>)>,    // positions, labels and the VLAs in scope
+    vlas: Vec<usize>,                              // positions of the VLAs in scope
+
+    returns: Vec<usize>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Builder<'a, '_> {
+    fn new_block(&mut self) -> usize {
+        self.blocks.push(Block::default());
+        self.blocks.len() - 1
+    }
+
+    fn edge(&mut self, from: usize, to: usize) {
+        self.blocks[from].succs.push(BlockId(to));
+    }
+
+    // ends the current block with a jump, what follows is unreachable
+    // unless it is labeled
+    fn jump(&mut self, to: usize) {
+        self.edge(self.cur, to);
+        self.cur = self.new_block();
+    }
+
+    // starts a block the current one falls into
+    fn enter(&mut self, b: usize) {
+        self.edge(self.cur, b);
+        self.cur = b;
+    }
+
+    fn mark(&mut self, pos: usize) {
+        self.blocks[self.cur].pos.get_or_insert(pos);
+    }
+
+    fn expr(&mut self, x: &'a dyn Expr) {
+        self.blocks[self.cur].nodes.push(Node::Expr(x));
+    }
+
+    fn error(&mut self, pos: usize, msg: &str) {
+        self.diagnostics.push(Diagnostic {
+            pos,
+            severity: Severity::Error,
+            msg: msg.to_string(),
+        });
+    }
+
+    fn note(&mut self, pos: usize, msg: &str) {
+        self.diagnostics.push(Diagnostic {
+            pos,
+            severity: Severity::Note,
+            msg: msg.to_string(),
+        });
+    }
+
+    // reports whether a loop condition is a nonzero constant
+    fn always(&self, x: &dyn Expr) -> bool {
+        Eval::new(self.info).int(x).is_ok_and(|v| v != 0)
+    }
+
+    fn block(&mut self, b: &'a BlockStmt) {
+        let vlas = self.vlas.len();
+
+        for s in b.stmts.iter() {
+            self.stmt(s.as_ref());
+        }
+
+        self.vlas.truncate(vlas);
+    }
+
+    // builds a loop body, with the targets of break and continue
+    fn body(&mut self, s: &'a dyn Stmt, brk: usize, cont: usize) {
+        self.breaks.push(brk);
+        self.continues.push(cont);
+        self.stmt(s);
+        self.breaks.pop();
+        self.continues.pop();
+    }
+
+    fn decl(&mut self, d: &'a GenDecl) {
+        for init in d.decls.iter() {
+            self.blocks[self.cur].nodes.push(Node::Decl(&d.specs, init));
+
+            let vla = init.declarator.name.as_ref().and_then(|n| {
+                let s = self
+                    .info
+                    .decls
+                    .get(&n.pos)
+                    .map(|&id| self.info.symbol(id))?;
+                (s.kind == SymbolKind::Var && s.ty.is_vla()).then_some(n.pos)
+            });
+            self.vlas.extend(vla);
+        }
+    }
+
+    fn stmt(&mut self, s: &'a dyn Stmt) {
+        match s.kind() {
+            StmtKind::SemiColonStmt(_) | StmtKind::StaticAssert(_) => {}
+
+            StmtKind::ExprStmt(s) => {
+                self.mark(s.x.start());
+                self.expr(s.x.as_ref());
+
+                if self.noreturn(s.x.as_ref()) {
+                    self.cur = self.new_block();
+                }
+            }
+
+            StmtKind::DeclStmt(s) => {
+                self.mark(s.decl.specs.pos);
+                self.decl(&s.decl);
+            }
+
+            StmtKind::ReturnStmt(s) => {
+                self.mark(s.pos);
+                if let Some(x) = &s.value {
+                    self.expr(x.as_ref());
+                }
+                self.returns.push(self.cur);
+                self.jump(1);
+            }
+
+            StmtKind::BreakStmt(s) => match self.breaks.last() {
+                Some(&b) => self.jump(b),
+                None => self.error(s.pos, "'break' statement not in loop or switch statement"),
+            },
+
+            StmtKind::ContinueStmt(s) => match self.continues.last() {
+                Some(&b) => self.jump(b),
+                None => self.error(s.pos, "'continue' statement not in loop statement"),
+            },
+
+            StmtKind::IfStmt(s) => {
+                self.mark(s.if_pos);
+                let end = self.new_block();
+
+                let branches = std::iter::once((s.cond.as_ref(), s.init.as_ref()))
+                    .chain(s.elifs.iter().map(|e| (e.cond.as_ref(), e.init.as_ref())));
+
+                for (cond, then) in branches {
+                    self.expr(cond);
+                    let (t, f) = (self.new_block(), self.new_block());
+                    self.edge(self.cur, t);
+                    self.edge(self.cur, f);
+
+                    self.cur = t;
+                    self.stmt(then);
+                    self.edge(self.cur, end);
+                    self.cur = f;
+                }
+
+                if let Some(e) = &s._else {
+                    self.stmt(e.init.as_ref());
+                }
+                self.enter(end);
+            }
+
+            StmtKind::BlockStmt(s) => self.block(s),
+
+            StmtKind::WhileStmt(s) => {
+                self.mark(s.pos);
+                let (cond, body, end) = (self.new_block(), self.new_block(), self.new_block());
+
+                self.enter(cond);
+                self.expr(s.cond.as_ref());
+                self.edge(cond, body);
+                if !self.always(s.cond.as_ref()) {
+                    self.edge(cond, end);
+                }
+
+                self.cur = body;
+                self.body(s.init.as_ref(), end, cond);
+                self.edge(self.cur, cond);
+                self.cur = end;
+            }
+
+            StmtKind::DowhileStmt(s) => {
+                self.mark(s.do_pos);
+                let (body, cond, end) = (self.new_block(), self.new_block(), self.new_block());
+
+                self.enter(body);
+                self.body(s.init.as_ref(), end, cond);
+                self.enter(cond);
+                self.expr(s.cond.as_ref());
+                self.edge(cond, body);
+                if !self.always(s.cond.as_ref()) {
+                    self.edge(cond, end);
+                }
+                self.cur = end;
+            }
+
+            StmtKind::ForStmt(s) => {
+                self.mark(s.pos);
+                let vlas = self.vlas.len();
+
+                match s.init.as_ref().map(|i| i.kind()) {
+                    Some(StmtKind::DeclStmt(d)) => self.decl(&d.decl),
+                    Some(StmtKind::ExprStmt(e)) => self.expr(e.x.as_ref()),
+                    _ => {}
+                }
+
+                let (cond, body, post, end) = (
+                    self.new_block(),
+                    self.new_block(),
+                    self.new_block(),
+                    self.new_block(),
+                );
+
+                self.enter(cond);
+                self.edge(cond, body);
+                if let Some(x) = &s.cond {
+                    self.expr(x.as_ref());
+                    if !self.always(x.as_ref()) {
+                        self.edge(cond, end);
+                    }
+                }
+
+                self.cur = body;
+                self.body(s.body.as_ref(), end, post);
+                self.enter(post);
+                if let Some(x) = &s.post {
+                    self.expr(x.as_ref());
+                }
+                self.edge(post, cond);
+
+                self.cur = end;
+                self.vlas.truncate(vlas);
+            }
+
+            StmtKind::SwitchStmt(s) => {
+                self.mark(s.pos);
+                self.expr(s.tag.as_ref());
+
+                let dispatch = self.cur;
+                let end = self.new_block();
+
+                self.switches.push(Switch {
+                    dispatch,
+                    default: None,
+                    vlas: self.vlas.len(),
+                });
+                self.breaks.push(end);
+
+                // statements before the first case are never executed
+                self.cur = self.new_block();
+                self.stmt(s.body.as_ref());
+                self.enter(end);
+
+                self.breaks.pop();
+                let sw = self.switches.pop().unwrap();
+
+                // the default goes last
+                let succs = &mut self.blocks[dispatch].succs;
+                if sw.default.is_some() {
+                    let default = succs.remove(0);
+                    succs.push(default);
+                } else {
+                    succs.push(BlockId(end));
+                }
+            }
+
+            StmtKind::CaseStmt(s) => {
+                let what = if s.value.is_some() { "case" } else { "default" };
+
+                let Some(sw) = self.switches.last() else {
+                    let msg = format!("'{}' statement not in switch statement", what);
+                    self.error(s.pos, &msg);
+                    self.stmt(s.stmt.as_ref());
+                    return;
+                };
+                let (dispatch, vlas, default) = (sw.dispatch, sw.vlas, sw.default);
+
+                if let Some(&vla) = self.vlas.get(vlas) {
+                    self.error(
+                        s.pos,
+                        "cannot jump from switch statement to this case label",
+                    );
+                    self.note(vla, "jump bypasses initialization of variable length array");
+                }
+
+                let b = self.new_block();
+                self.enter(b);
+
+                if s.value.is_some() {
+                    self.edge(dispatch, b);
+                } else if let Some(prev) = default {
+                    self.error(s.pos, "multiple default labels in one switch");
+                    self.note(prev, "previous case defined here");
+                } else {
+                    // kept first until the end of the switch
+                    self.blocks[dispatch].succs.insert(0, BlockId(b));
+                    self.switches.last_mut().unwrap().default = Some(s.pos);
+                }
+
+                self.stmt(s.stmt.as_ref());
+            }
+
+            StmtKind::LabeledStmt(s) => {
+                let b = self.label(&s.label.name);
+                self.enter(b);
+                self.labels.get_mut(s.label.name.as_str()).unwrap().1 = self.vlas.clone();
+                self.stmt(s.stmt.as_ref());
+            }
+
+            StmtKind::GotoStmt(s) => {
+                self.mark(s.pos);
+                self.gotos.push((s.pos, &s.label, self.vlas.clone()));
+
+                let b = self.label(&s.label.name);
+                self.jump(b);
+            }
+        }
+    }
+
+    // the block of a label, created by its first goto or its statement
+    fn label(&mut self, name: &'a str) -> usize {
+        if let Some((b, _)) = self.labels.get(name) {
+            return *b;
+        }
+
+        let b = self.new_block();
+        self.labels.insert(name, (b, Vec::new()));
+        b
+    }
+
+    // a goto cannot jump into the scope of a VLA
+    fn goto_vlas(&mut self) {
+        for (pos, label, vlas) in std::mem::take(&mut self.gotos) {
+            let Some((_, scope)) = self.labels.get(label.name.as_str()) else {
+                continue;
+            };
+
+            if let Some(&vla) = scope.iter().find(|v| !vlas.contains(v)) {
+                self.error(pos, "cannot jump from this goto statement to its label");
+                self.note(vla, "jump bypasses initialization of variable length array");
+            }
+        }
+    }
+
+    // calls to library functions that never return end a block
+    fn noreturn(&self, x: &dyn Expr) -> bool {
+        let ExprKind::CallExpr(c) = x.kind() else {
+            return false;
+        };
+        let ExprKind::Ident(f) = c.fun.kind() else {
+            return false;
+        };
+
+        NORETURN.contains(&f.name.as_str())
+            && self
+                .info
+                .lookup(f.pos)
+                .is_some_and(|s| s.kind == SymbolKind::Func)
+    }
+}
+
+impl Resolver {
+    /// Checks the control flow of a function: where it falls off its end
+    /// without a value, and the code it never executes.
+    pub(crate) fn flow(&mut self, name: &str, ret: &Type, body: &BlockStmt) {
+        let (cfg, diagnostics) = Cfg::build(body, &self.info);
+        self.info.diagnostics.extend(diagnostics);
+
+        let reachable = cfg.reachable(cfg.entry);
+
+        // main returns 0 when it falls off its end
+        if reachable[cfg.falls_off.0] && !ret.is_void() && name != "main" {
+            let returns = cfg.returns.iter().any(|b| reachable[b.0]);
+            let msg = if returns {
+                "non-void function does not return a value in all control paths"
+            } else {
+                "non-void function does not return a value"
+            };
+            self.warning(body.rbrace, msg.to_string());
+        }
+
+        // one warning for each region of dead code, at its first statement
+        let mut dead: Vec<_> = cfg
+            .blocks
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !reachable[*i])
+            .filter_map(|(i, b)| Some((b.pos?, BlockId(i))))
+            .collect();
+        dead.sort();
+
+        let mut covered = vec![false; cfg.blocks.len()];
+        for (pos, id) in dead {
+            if covered[id.0] {
+                continue;
+            }
+
+            self.warning(pos, "code will never be executed".to_string());
+            for (i, r) in cfg.reachable(id).into_iter().enumerate() {
+                covered[i] |= r;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::DeclKind;
+
+    // builds the graph of the last function in `src`
+    fn shape(src: &str, want: &[&[usize]]) {
+        let mut p = parser::Parser::from(src.to_string());
+        let file = p.parse_file();
+        assert!(p.errors().is_empty(), "{:?}", p.errors());
+
+        let info = crate::check(&file);
+        let DeclKind::FuncDecl(f) = file.decls.last().unwrap().kind() else {
+            panic!("{}", src);
+        };
+
+        let (cfg, diagnostics) = Cfg::build(&f.body, &info);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+        let got: Vec<Vec<usize>> = cfg
+            .blocks
+            .iter()
+            .map(|b| b.succs.iter().map(|s| s.0).collect())
+            .collect();
+        assert_eq!(want, got, "{}", src);
+    }
+
+    #[test]
+    fn test_build() {
+        // entry, exit, body, then the blocks each statement adds
+        shape("void f(void) {}", &[&[2], &[], &[1]]);
+        shape(
+            "int f(int x) { if (x) return 1; return 0; }",
+            &[&[2], &[], &[4, 5], &[1], &[1], &[3], &[3], &[1]],
+        );
+        shape(
+            "void f(int x) { while (x) x--; }",
+            &[&[2], &[], &[3], &[4, 5], &[3], &[1]],
+        );
+        shape(
+            "void f(int x) { for (;;) if (x) break; }",
+            &[
+                &[2],
+                &[],
+                &[3],
+                &[4],
+                &[8, 9],
+                &[3],
+                &[1],
+                &[5],
+                &[6],
+                &[7],
+                &[7],
+            ],
+        );
+        shape(
+            "void f(int x) { switch (x) { case 1: break; default: ; } }",
+            &[&[2], &[], &[5, 7], &[1], &[5], &[3], &[7], &[3]],
+        );
+    }
+
+    #[test]
+    fn test_reachable() {
+        let src = "int f(int x) { return x; x++; }";
+        let mut p = parser::Parser::from(src.to_string());
+        let file = p.parse_file();
+        let info = crate::check(&file);
+        let DeclKind::FuncDecl(f) = file.decls[0].kind() else {
+            panic!("{}", src);
+        };
+
+        let (cfg, _) = Cfg::build(&f.body, &info);
+        let reachable = cfg.reachable(cfg.entry);
+
+        assert_eq!(vec![cfg.block(BlockId(2)).pos], vec![Some(15)]);
+        assert!(reachable[cfg.returns[0].0]);
+        assert!(!reachable[cfg.falls_off.0]);
+        assert_eq!(vec![BlockId(0)], cfg.preds()[2]);
+    }
+}
@@ -0,0 +1,726 @@
+//! Integer constant expressions (C11 6.6): folds a checked expression to
+//! its value with the integer widths, signedness and overflow rules of the
+//! target.
+
+use ast::{Designator, Expr, ExprKind, OffsetofExpr, SizeofArg};
+use token::Token;
+
+use crate::expr::{parse_int, split_suffix, string_kind, string_segments, strip_parens};
+use crate::types::{usual_arithmetic, IntKind, Type, TypeKind};
+use crate::{Info, Resolver, SymbolKind};
+
+/// Why an expression is not constant, at the sub-expression to blame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotConst {
+    pub pos: usize,
+    pub msg: String,
+}
+
+fn not_const<T>(pos: usize, msg: impl Into<String>) -> Result<T, NotConst> {
+    Err(NotConst {
+        pos,
+        msg: msg.into(),
+    })
+}
+
+// the operators and operands a constant expression cannot contain
+fn not_allowed<T>(pos: usize, what: &str) -> Result<T, NotConst> {
+    not_const(
+        pos,
+        format!("{} is not allowed in a constant expression", what),
+    )
+}
+
+/// Evaluates the integer constant expressions of a checked file.
+pub struct Eval<'a> {
+    info: &'a Info,
+    pub warnings: Vec<(usize, String)>, // overflows of signed arithmetic
+}
+
+impl<'a> Eval<'a> {
+    pub fn new(info: &'a Info) -> Self {
+        Self {
+            info,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Returns the value of an integer constant expression, converted to
+    /// its type.
+    pub fn int(&mut self, x: &dyn Expr) -> Result<i128, NotConst> {
+        let k = self.kind(x)?;
+        let info = self.info;
+        let dl = &info.layout;
+
+        let v = match x.kind() {
+            ExprKind::BasicLit(b) => match b.tok {
+                Token::INTEGER => match parse_int(split_suffix(&b.lit).0) {
+                    Some(v) => v as i128,
+                    None => return not_const(b.pos, "integer literal is too large"),
+                },
+                Token::CHARACTER => self.char_value(&b.lit),
+                _ => return not_allowed(b.pos, "a literal of this type"),
+            },
+
+            ExprKind::Ident(id) => match self.info.lookup(id.pos) {
+                Some(s) if s.kind == SymbolKind::EnumConst => s.value.unwrap_or_default(),
+                Some(s) if matches!(s.kind, SymbolKind::Var | SymbolKind::Param) => {
+                    let msg = format!(
+                        "read of non-const variable '{}' is not allowed in a constant expression",
+                        id.name
+                    );
+                    return not_const(id.pos, msg);
+                }
+                _ => return not_const(id.pos, format!("'{}' is not a constant", id.name)),
+            },
+
+            ExprKind::ParenExpr(p) => self.int(p.x.as_ref())?,
+
+            ExprKind::UnaryExpr(u) => match u.op {
+                Token::INC | Token::DEC => return not_allowed(u.op_pos, "increment"),
+                Token::AND => return not_allowed(u.op_pos, "taking an address"),
+                Token::NOT | Token::BANG => (self.int(u.x.as_ref())? == 0) as i128,
+                Token::MINUS => {
+                    let v = -self.int(u.x.as_ref())?;
+                    self.fit(u.op_pos, v, k)
+                }
+                Token::TILDE => !self.int(u.x.as_ref())?,
+                _ => self.int(u.x.as_ref())?,
+            },
+
+            ExprKind::StarExpr(s) => return not_allowed(s.pos, "indirection"),
+            ExprKind::PostfixExpr(p) => return not_allowed(p.op_pos, "increment"),
+            ExprKind::AssignExpr(a) => return not_allowed(a.op_pos, "assignment"),
+            ExprKind::CallExpr(c) => return not_allowed(c.fun.start(), "a function call"),
+            ExprKind::IndexExpr(i) => return not_allowed(i.lbrack, "an array subscript"),
+            ExprKind::SelectorExpr(s) => return not_allowed(s.op_pos, "member access"),
+            ExprKind::CompositeLit(c) => return not_allowed(c.lparen, "a compound literal"),
+            ExprKind::InitList(l) => return not_allowed(l.lbrace, "an initializer list"),
+
+            ExprKind::InfixExpr(e) => {
+                let (x, y) = (e.x.as_ref(), e.y.as_ref());
+
+                match e.op {
+                    Token::COMMA => return not_allowed(e.op_pos, "the comma operator"),
+
+                    // the unevaluated operand may be anything
+                    Token::LAND => (self.int(x)? != 0 && self.int(y)? != 0) as i128,
+                    Token::LOR => (self.int(x)? != 0 || self.int(y)? != 0) as i128,
+
+                    Token::SHL | Token::SHR => self.shift(e.op, e.op_pos, x, y, k)?,
+
+                    Token::LT | Token::GT | Token::LEQ | Token::GEQ | Token::EQL | Token::NEQ => {
+                        let (a, b) = self.operands(x, y)?;
+
+                        (match e.op {
+                            Token::LT => a < b,
+                            Token::GT => a > b,
+                            Token::LEQ => a <= b,
+                            Token::GEQ => a >= b,
+                            Token::EQL => a == b,
+                            _ => a != b,
+                        }) as i128
+                    }
+
+                    op => {
+                        let (a, b) = self.operands(x, y)?;
+
+                        if matches!(op, Token::SLASH | Token::REM) && b == 0 {
+                            let what = if op == Token::SLASH {
+                                "division"
+                            } else {
+                                "remainder"
+                            };
+                            return not_const(y.start(), format!("{} by zero", what));
+                        }
+
+                        let v = match op {
+                            Token::PLUS => a + b,
+                            Token::MINUS => a - b,
+                            Token::ASTERISK => a.wrapping_mul(b),
+                            Token::SLASH => a / b,
+                            Token::REM => a % b,
+                            Token::AND => a & b,
+                            Token::OR => a | b,
+                            Token::XOR => a ^ b,
+                            _ => return not_allowed(e.op_pos, "this operator"),
+                        };
+                        self.fit(e.op_pos, v, k)
+                    }
+                }
+            }
+
+            ExprKind::CondExpr(c) => {
+                if self.int(c.cond.as_ref())? != 0 {
+                    self.int(c.x.as_ref())?
+                } else {
+                    self.int(c.y.as_ref())?
+                }
+            }
+
+            ExprKind::CastExpr(c) => match self.info.type_of(c.x.as_ref()) {
+                // a floating constant may be the operand of a cast
+                Some(t) if t.is_floating() => {
+                    let Some(f) = float_const(c.x.as_ref()) else {
+                        return not_const(
+                            c.x.start(),
+                            "floating point arithmetic is not allowed in an integer constant expression",
+                        );
+                    };
+
+                    let v = f.trunc();
+                    if k == IntKind::Bool {
+                        (f != 0.0) as i128
+                    } else if v < dl.min(k) as f64 || v > dl.max(k) as f64 || v.is_nan() {
+                        let msg = format!(
+                            "value {} is outside the range of representable values of type '{}'",
+                            f,
+                            k.name()
+                        );
+                        return not_const(c.x.start(), msg);
+                    } else {
+                        v as i128
+                    }
+                }
+                _ if k == IntKind::Bool => (self.int(c.x.as_ref())? != 0) as i128,
+                _ => self.int(c.x.as_ref())?,
+            },
+
+            ExprKind::SizeofExpr(s) => {
+                let t = match &s.arg {
+                    SizeofArg::Expr(e) => self.info.type_of(e.as_ref()),
+                    SizeofArg::Type(typ) => self.info.type_names.get(&typ.specs.pos),
+                };
+                let Some(t) = t else {
+                    return not_allowed(s.pos, "an invalid operand");
+                };
+
+                let n = if s.op == Token::ALIGNOF {
+                    dl.align_of(t, &self.info.tags)
+                } else if t.is_vla() {
+                    return not_const(s.pos, "the size of a variable length array is not constant");
+                } else {
+                    dl.size_of(t, &self.info.tags)
+                };
+                match n {
+                    Some(n) => n as i128,
+                    None => return not_allowed(s.pos, "an incomplete type"),
+                }
+            }
+
+            ExprKind::OffsetofExpr(o) => self.offsetof(o)?,
+        };
+
+        Ok(dl.wrap(v, k))
+    }
+
+    // the integer type of an expression
+    fn kind(&self, x: &dyn Expr) -> Result<IntKind, NotConst> {
+        match self.info.type_of(x) {
+            Some(t) => match t.int_kind() {
+                Some(k) => Ok(k),
+                None => {
+                    let msg = format!(
+                        "value of type '{}' is not an integer constant",
+                        self.info.spell(t)
+                    );
+                    not_const(x.start(), msg)
+                }
+            },
+            None => not_const(x.start(), "expression has an invalid type"),
+        }
+    }
+
+    // evaluates the operands of a binary operator converted to their
+    // common type
+    fn operands(&mut self, x: &dyn Expr, y: &dyn Expr) -> Result<(i128, i128), NotConst> {
+        let (a, b) = (self.int(x)?, self.int(y)?);
+
+        let (Some(t), Some(u)) = (self.info.type_of(x), self.info.type_of(y)) else {
+            return not_const(x.start(), "expression has an invalid type");
+        };
+        let dl = &self.info.layout;
+        let k = usual_arithmetic(t, u, dl)
+            .int_kind()
+            .unwrap_or(IntKind::Int);
+
+        Ok((dl.wrap(a, k), dl.wrap(b, k)))
+    }
+
+    fn shift(
+        &mut self,
+        op: Token,
+        pos: usize,
+        x: &dyn Expr,
+        y: &dyn Expr,
+        k: IntKind,
+    ) -> Result<i128, NotConst> {
+        let (a, b) = (self.int(x)?, self.int(y)?);
+        let dl = &self.info.layout;
+        let width = dl.width(k) as i128;
+
+        if b < 0 {
+            return not_const(y.start(), format!("negative shift count {}", b));
+        }
+        if b >= width {
+            let msg = format!(
+                "shift count {} >= width of type '{}' ({} bits)",
+                b,
+                k.name(),
+                width
+            );
+            return not_const(y.start(), msg);
+        }
+
+        if op == Token::SHR {
+            return Ok(a >> b);
+        }
+        if a < 0 {
+            return not_const(x.start(), format!("left shift of negative value {}", a));
+        }
+
+        Ok(self.fit(pos, a << b, k))
+    }
+
+    // converts the exact result of an operation to its type, warning when
+    // a signed result does not fit
+    fn fit(&mut self, pos: usize, v: i128, k: IntKind) -> i128 {
+        let dl = &self.info.layout;
+        let w = dl.wrap(v, k);
+
+        if w != v && dl.is_signed(k) {
+            self.warnings.push((
+                pos,
+                format!(
+                    "overflow in expression; result is {} with type '{}'",
+                    w,
+                    k.name()
+                ),
+            ));
+        }
+
+        w
+    }
+
+    fn offsetof(&mut self, o: &OffsetofExpr) -> Result<i128, NotConst> {
+        let info = self.info;
+        let dl = &info.layout;
+
+        let invalid = || not_allowed(o.name.pos, "an invalid offsetof");
+        let Some(mut ty) = info.type_names.get(&o.typ.specs.pos).cloned() else {
+            return invalid();
+        };
+
+        let mut bits = 0;
+        let mut field = Some(&o.member);
+        let mut path = o.path.iter();
+
+        loop {
+            if let Some(name) = field {
+                let (TypeKind::Struct(id) | TypeKind::Union(id)) = ty.kind else {
+                    return invalid();
+                };
+                let Some(m) = dl.member(id, &name.name, &info.tags) else {
+                    return invalid();
+                };
+
+                bits += m.offset as i128;
+                ty = m.ty;
+            }
+
+            field = match path.next() {
+                Some(Designator::Field { name, .. }) => Some(name),
+                Some(Designator::Index { index, .. }) => {
+                    let i = self.int(index.as_ref())?;
+                    let Some(elem) = ty.elem().cloned() else {
+                        return invalid();
+                    };
+                    let Some(size) = dl.size_of(&elem, &info.tags) else {
+                        return invalid();
+                    };
+
+                    bits += i * size as i128 * 8;
+                    ty = elem;
+                    None
+                }
+                None => return Ok(bits / 8),
+            };
+        }
+    }
+
+    // the value of a character constant, `'ab'` packs its bytes into an int
+    fn char_value(&self, lit: &str) -> i128 {
+        let dl = &self.info.layout;
+        let (prefix, body) = lit.split_once('\'').unwrap_or(("", lit));
+        let body = body.strip_suffix('\'').unwrap_or(body);
+
+        let units = decode(body, prefix.is_empty() || prefix == "u8");
+
+        match prefix {
+            "" if units.len() > 1 => units.iter().fold(0, |v, &u| (v << 8) | (u as i128 & 0xff)),
+            "" => dl.wrap(units.first().copied().unwrap_or(0) as i128, IntKind::Char),
+            "u8" => units.first().copied().unwrap_or(0) as i128 & 0xff,
+            _ => units.first().copied().unwrap_or(0) as i128,
+        }
+    }
+}
+
+/// Returns the code units of a string literal without its terminating null
+/// character: bytes of UTF-8 for plain and u8 literals, UTF-16 units for u
+/// literals and code points for the others.
+pub fn string_units(lit: &str, wchar: IntKind) -> Vec<u32> {
+    let segments = string_segments(lit);
+    let kind = string_kind(&segments, wchar);
+
+    let mut units = Vec::new();
+    for (_, body) in segments.iter() {
+        let decoded = decode(body, kind == IntKind::Char);
+
+        if kind != IntKind::UShort {
+            units.extend(decoded);
+            continue;
+        }
+        for u in decoded {
+            match char::from_u32(u) {
+                Some(c) => {
+                    let mut buf = [0; 2];
+                    units.extend(c.encode_utf16(&mut buf).iter().map(|&u| u as u32));
+                }
+                None => units.push(u),
+            }
+        }
+    }
+
+    units
+}
+
+// decodes the escapes of a character constant to code units, bytes of
+// UTF-8 for plain and u8 constants and code points for the others
+fn decode(body: &str, bytes: bool) -> Vec<u32> {
+    let mut units = Vec::new();
+    let mut chars = body.chars().peekable();
+
+    let push = |units: &mut Vec<u32>, c: u32, escape: bool| match char::from_u32(c) {
+        Some(c) if bytes && !escape => {
+            let mut buf = [0; 4];
+            units.extend(c.encode_utf8(&mut buf).bytes().map(u32::from));
+        }
+        _ => units.push(c),
+    };
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            push(&mut units, c as u32, false);
+            continue;
+        }
+
+        let v = match chars.next() {
+            Some('n') => '\n' as u32,
+            Some('t') => '\t' as u32,
+            Some('r') => '\r' as u32,
+            Some('a') => 7,
+            Some('b') => 8,
+            Some('f') => 12,
+            Some('v') => 11,
+            Some('e') => 27,
+            Some('x') => {
+                let mut v = 0u32;
+                while let Some(d) = chars.next_if(char::is_ascii_hexdigit) {
+                    v = v.wrapping_mul(16) + d.to_digit(16).unwrap();
+                }
+                v
+            }
+            Some(d @ '0'..='7') => {
+                let mut v = d.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    if let Some(d) = chars.next_if(|c| ('0'..='7').contains(c)) {
+                        v = v * 8 + d.to_digit(8).unwrap();
+                    }
+                }
+                v
+            }
+            Some(c @ ('u' | 'U')) => {
+                let len = if c == 'u' { 4 } else { 8 };
+                let hex: String = (0..len)
+                    .filter_map(|_| chars.next_if(char::is_ascii_hexdigit))
+                    .collect();
+
+                // a universal character name is a character, not a unit
+                let c = u32::from_str_radix(&hex, 16).unwrap_or(0);
+                push(&mut units, c, false);
+                continue;
+            }
+            Some(c) => c as u32, // \\, \', \", \?
+            None => 0,
+        };
+
+        push(&mut units, v, true);
+    }
+
+    units
+}
+
+// the value of a floating constant, maybe negated
+fn float_const(x: &dyn Expr) -> Option<f64> {
+    match strip_parens(x).kind() {
+        ExprKind::BasicLit(b) if b.tok == Token::FLOATING => parse_float(&b.lit),
+        ExprKind::UnaryExpr(u) if u.op == Token::MINUS => float_const(u.x.as_ref()).map(|f| -f),
+        ExprKind::UnaryExpr(u) if u.op == Token::PLUS => float_const(u.x.as_ref()),
+        _ => None,
+    }
+}
+
+/// Returns the value of a floating literal, decimal or hexadecimal.
+pub fn parse_float(lit: &str) -> Option<f64> {
+    let lit = lit.trim_end_matches(['f', 'F', 'l', 'L']);
+
+    let Some(hex) = lit.strip_prefix("0x").or(lit.strip_prefix("0X")) else {
+        return lit.parse().ok();
+    };
+
+    let (mantissa, exp) = hex.split_once(['p', 'P'])?;
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+    let mut v = 0f64;
+    for d in int.chars().chain(frac.chars()) {
+        v = v * 16.0 + d.to_digit(16)? as f64;
+    }
+
+    let exp: i32 = exp.parse().ok()?;
+    Some(v * 2f64.powi(exp - 4 * frac.len() as i32))
+}
+
+impl Resolver {
+    /// Evaluates an integer constant expression, `what` names it in the
+    /// error for a non-integer type. Reports why the expression is not
+    /// constant.
+    pub(crate) fn constant(&mut self, x: &dyn Expr, what: &str) -> Option<i128> {
+        if !self.integer(x, what) {
+            return None;
+        }
+
+        match self.eval(x) {
+            Ok(v) => Some(v),
+            Err(n) => {
+                self.error(
+                    x.start(),
+                    "expression is not an integer constant expression".to_string(),
+                );
+                self.note(n.pos, n.msg);
+                None
+            }
+        }
+    }
+
+    /// Evaluates an integer expression that may not be constant, like the
+    /// length of an array.
+    pub(crate) fn try_constant(&mut self, x: &dyn Expr) -> Option<i128> {
+        if !self.info.type_of(x).is_some_and(Type::is_integer) {
+            return None;
+        }
+
+        self.eval(x).ok()
+    }
+
+    fn eval(&mut self, x: &dyn Expr) -> Result<i128, NotConst> {
+        let mut e = Eval::new(&self.info);
+        let v = e.int(x);
+
+        if v.is_ok() {
+            for (pos, msg) in e.warnings {
+                self.warning(pos, msg);
+            }
+        }
+
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::DeclKind;
+
+    // evaluates the initializer of `int v_ = x;` after some declarations
+    fn eval(decls: &str, x: &str) -> Result<(i128, Vec<String>), NotConst> {
+        let src = format!("{}\nunsigned long long v_ = {};", decls, x);
+        let mut p = parser::Parser::from(src.clone());
+        let file = p.parse_file();
+        assert!(p.errors().is_empty(), "{:?}", p.errors());
+
+        let info = crate::check(&file);
+        assert_eq!(0, info.errors().count(), "{:?}", info.diagnostics);
+
+        let DeclKind::GenDecl(d) = file.decls.last().unwrap().kind() else {
+            panic!("{}", src);
+        };
+        let x = d.decls[0].init.as_ref().unwrap();
+
+        let mut e = Eval::new(&info);
+        let v = e.int(x.as_ref())?;
+        Ok((v, e.warnings.into_iter().map(|w| w.1).collect()))
+    }
+
+    #[test]
+    fn test_eval() {
+        let tests = [
+            ("", "1 + 2 * 3", 7),
+            ("", "-1 / 2", 0),
+            ("", "-7 % 3", -1),
+            ("", "0x7fffffff + 1u", 0x80000000),
+            ("", "-1 < 0u", 0),
+            ("", "-1L < 0u", 1),
+            ("", "(unsigned char)300", 44),
+            ("", "(char)200", -56),
+            ("", "(long)0.5e1", 5),
+            ("", "(int)-3.9", -3),
+            ("", "(short)0x1p4", 16),
+            ("", "~0u", 0xffffffff),
+            ("", "(int)(1u << 31) >> 31", -1),
+            ("", "-8 >> 1", -4),
+            ("", "1 ? 2 : 1 / 0", 2),
+            ("", "0 && 1 / 0", 0),
+            ("", "!5 + !0", 1),
+            ("", "'a' + '\\n' + '\\x41' + '\\101'", 97 + 10 + 65 + 65),
+            ("", "'\\xff'", -1),
+            ("", "'ab'", 0x6162),
+            ("", "L'\\u00e9'", 0xe9),
+            ("", "sizeof(long) + sizeof \"abc\"", 12),
+            ("", "_Alignof(double)", 8),
+            ("enum e { A, B = 5, C };", "A + B + C", 11),
+            ("enum e { A = -1, B };", "B", 0),
+            (
+                "struct s { char c; int a[4]; };",
+                "offsetof(struct s, a[2])",
+                12,
+            ),
+            ("int a[10];", "sizeof a / sizeof a[0]", 10),
+            ("", "18446744073709551615u", u64::MAX as i128),
+            ("", "18446744073709551615u * 2", u64::MAX as i128 - 1),
+        ];
+
+        for (i, (decls, x, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                Ok((*want, vec![])),
+                eval(decls, x),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                x
+            );
+        }
+    }
+
+    #[test]
+    fn test_overflow() {
+        let tests = [
+            ("2147483647 + 1", -2147483648, "'int'"),
+            ("-(-2147483647 - 1)", -2147483648, "'int'"),
+            ("9223372036854775807L * 2", -2, "'long'"),
+            ("1 << 31", -2147483648, "'int'"),
+        ];
+
+        for (i, (x, want, ty)) in tests.iter().enumerate() {
+            let (v, warnings) = eval("", x).unwrap();
+            let msg = format!(
+                "overflow in expression; result is {} with type {}",
+                want, ty
+            );
+
+            assert_eq!(
+                (*want, vec![msg]),
+                (v, warnings),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                x
+            );
+        }
+    }
+
+    #[test]
+    fn test_not_const() {
+        let tests = [
+            (
+                "int n;",
+                "1 + n",
+                5,
+                "read of non-const variable 'n' is not allowed in a constant expression",
+            ),
+            ("", "1 / (2 - 2)", 5, "division by zero"),
+            (
+                "int f(void);",
+                "2 * f()",
+                5,
+                "a function call is not allowed in a constant expression",
+            ),
+            (
+                "",
+                "1 << 32",
+                6,
+                "shift count 32 >= width of type 'int' (32 bits)",
+            ),
+            ("", "-1 << 2", 1, "left shift of negative value -1"),
+            (
+                "",
+                "(1, 2)",
+                3,
+                "the comma operator is not allowed in a constant expression",
+            ),
+            (
+                "",
+                "(int)(1.5 + 1)",
+                6,
+                "floating point arithmetic is not allowed in an integer constant expression",
+            ),
+            (
+                "",
+                "(int)1e10",
+                6,
+                "value 10000000000 is outside the range of representable values of type 'int'",
+            ),
+            (
+                "int n;",
+                "sizeof(int [n])",
+                1,
+                "the size of a variable length array is not constant",
+            ),
+            (
+                "int x;",
+                "(long)&x",
+                7,
+                "value of type 'int *' is not an integer constant",
+            ),
+        ];
+
+        for (i, (decls, x, col, msg)) in tests.iter().enumerate() {
+            // the expressions start at column 25 of the second line
+            let pos = decls.len() + 1 + 24 + col - 1;
+            let want = Err(NotConst {
+                pos,
+                msg: msg.to_string(),
+            });
+
+            assert_eq!(
+                want,
+                eval(decls, x),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                x
+            );
+        }
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(Some(2.5), parse_float("2.5f"));
+        assert_eq!(Some(24.0), parse_float("0x1.8p4"));
+
+        let units = |lit: &str| string_units(lit, IntKind::Int);
+        assert_eq!(vec![b'a' as u32, 10, b'b' as u32], units("\"a\\n\" \"b\""));
+        assert_eq!(vec![0xc3, 0xa9], units("\"\\u00e9\""));
+        assert_eq!(vec![0xe9, 0x41], units("L\"\\u00e9\" \"A\""));
+        assert_eq!(vec![0xd83d, 0xde00], units("u\"\\U0001F600\""));
+    }
+}
@@ -0,0 +1,1638 @@
+//! Expression typing: the integer promotions and usual arithmetic
+//! conversions, pointer arithmetic, lvalues, and the conversion of values
+//! by assignment and initialization.
+
+use std::iter::Peekable;
+use std::slice::Iter;
+
+use ast::printer::{Printer, Style};
+use ast::{
+    BasicLit, Designator, Expr, ExprKind, Ident, InitList, Initializer, OffsetofExpr, SizeofArg,
+};
+use token::Token;
+
+use crate::consts::Eval;
+use crate::types::{
+    usual_arithmetic, ArrayLen, FloatKind, FuncType, IntKind, TagId, Type, TypeKind,
+};
+use crate::{Linkage, Resolver, Symbol, SymbolId, SymbolKind};
+
+type Elems<'a> = Peekable<Iter<'a, Initializer>>;
+
+/// How a value is converted to the type of an object, for the wording of
+/// diagnostics.
+#[derive(Debug, Clone, Copy)]
+enum Conversion {
+    Assign,
+    Init,
+    Arg,
+    Return,
+}
+
+impl Conversion {
+    fn describe(self, to: &str, from: &str, incompatible: bool) -> String {
+        let inc = if incompatible { "incompatible " } else { "" };
+
+        match self {
+            Conversion::Assign if incompatible => {
+                format!("assigning to '{}' from incompatible type '{}'", to, from)
+            }
+            Conversion::Assign => format!("assigning to '{}' from '{}'", to, from),
+            Conversion::Init => format!(
+                "initializing '{}' with an expression of {}type '{}'",
+                to, inc, from
+            ),
+            Conversion::Arg => format!("passing '{}' to parameter of {}type '{}'", from, inc, to),
+            Conversion::Return => format!(
+                "returning '{}' from a function with {}result type '{}'",
+                from, inc, to
+            ),
+        }
+    }
+}
+
+impl Resolver {
+    /// Types an expression and records its type, returns None after an
+    /// error.
+    pub(crate) fn expr(&mut self, x: &dyn Expr) -> Option<Type> {
+        let t = self.typ(x)?;
+        self.info.types.insert((x.start(), x.end()), t.clone());
+        Some(t)
+    }
+
+    // the type of the value of an expression: arrays and functions decay to
+    // pointers and the qualifiers are dropped
+    fn value(&mut self, x: &dyn Expr) -> Option<Type> {
+        self.expr(x).map(|t| t.decay())
+    }
+
+    // like value, for an expression that may have been typed already
+    fn value_once(&mut self, x: &dyn Expr) -> Option<Type> {
+        match self.info.type_of(x) {
+            Some(t) => Some(t.decay()),
+            None => self.value(x),
+        }
+    }
+
+    /// Checks the condition of an if, a loop or a switch.
+    pub(crate) fn condition(&mut self, x: &dyn Expr, integer: bool) {
+        let Some(t) = self.value(x) else {
+            return;
+        };
+
+        let (ok, what) = if integer {
+            (t.is_integer(), "integer")
+        } else {
+            (t.is_scalar(), "scalar")
+        };
+
+        if !ok {
+            let msg = format!(
+                "statement requires expression of {} type ('{}' invalid)",
+                what,
+                self.info.spell(&t)
+            );
+            self.error(x.start(), msg);
+        }
+    }
+
+    /// Checks that an expression has an integer type, `what` names it in
+    /// the diagnostic.
+    pub(crate) fn integer(&mut self, x: &dyn Expr, what: &str) -> bool {
+        let Some(t) = self.value(x) else {
+            return false;
+        };
+
+        if !t.is_integer() {
+            let msg = format!("{} has non-integer type '{}'", what, self.info.spell(&t));
+            self.error(x.start(), msg);
+        }
+        t.is_integer()
+    }
+
+    pub(crate) fn return_stmt(&mut self, pos: usize, value: Option<&dyn Expr>) {
+        let Some((name, ret)) = self.func.clone() else {
+            if let Some(x) = value {
+                self.expr(x);
+            }
+            return;
+        };
+
+        match value {
+            Some(x) => {
+                let Some(t) = self.value(x) else {
+                    return;
+                };
+
+                if !ret.is_void() {
+                    self.convert(&ret, &t, x, Conversion::Return);
+                } else if !t.is_void() {
+                    self.error(
+                        pos,
+                        format!("void function '{}' should not return a value", name),
+                    );
+                }
+            }
+
+            None if !ret.is_void() => self.error(
+                pos,
+                format!("non-void function '{}' should return a value", name),
+            ),
+
+            None => {}
+        }
+    }
+
+    fn typ(&mut self, x: &dyn Expr) -> Option<Type> {
+        match x.kind() {
+            ExprKind::BasicLit(x) => self.literal(x),
+
+            ExprKind::Ident(x) => {
+                let id = self.resolve(x)?;
+                let sym = self.info.symbol(id);
+
+                match sym.kind {
+                    SymbolKind::Typedef => {
+                        self.error(
+                            x.pos,
+                            format!("unexpected type name '{}': expected expression", x.name),
+                        );
+                        None
+                    }
+                    SymbolKind::EnumConst => Some(Type::int(IntKind::Int)),
+                    _ => Some(sym.ty.clone()),
+                }
+            }
+
+            ExprKind::UnaryExpr(u) => match u.op {
+                Token::AND => self.address(u.x.as_ref(), u.op_pos),
+                Token::INC | Token::DEC => self.increment(u.op, u.x.as_ref()),
+
+                op => {
+                    let t = self.value(u.x.as_ref())?;
+
+                    let ok = match op {
+                        Token::PLUS | Token::MINUS => t.is_arithmetic(),
+                        Token::TILDE => t.is_integer(),
+                        _ => t.is_scalar(),
+                    };
+
+                    if !ok {
+                        let msg = format!(
+                            "invalid argument type '{}' to unary expression",
+                            self.info.spell(&t)
+                        );
+                        self.error(u.op_pos, msg);
+                        return None;
+                    }
+
+                    match op {
+                        Token::NOT | Token::BANG => Some(Type::int(IntKind::Int)),
+                        _ => Some(t.promote()),
+                    }
+                }
+            },
+
+            ExprKind::StarExpr(x) => {
+                let t = self.value(x.x.as_ref())?;
+
+                match t.pointee() {
+                    Some(p) => Some(p.clone()),
+                    None => {
+                        let msg = format!(
+                            "indirection requires pointer operand ('{}' invalid)",
+                            self.info.spell(&t)
+                        );
+                        self.error(x.pos, msg);
+                        None
+                    }
+                }
+            }
+
+            ExprKind::ParenExpr(x) => self.expr(x.x.as_ref()),
+            ExprKind::PostfixExpr(x) => self.increment(x.op, x.x.as_ref()),
+
+            ExprKind::InfixExpr(x) => {
+                let a = self.value(x.x.as_ref());
+                let b = self.value(x.y.as_ref());
+
+                self.binary(x.op, x.op_pos, x.x.as_ref(), a?, x.y.as_ref(), b?)
+            }
+
+            ExprKind::AssignExpr(x) => {
+                let to = self.expr(x.x.as_ref());
+                let from = self.value(x.y.as_ref());
+                let (to, from) = (to?, from?);
+
+                if !self.modifiable(x.x.as_ref(), &to) {
+                    return Some(to.unqualified());
+                }
+
+                if x.op == Token::ASSIGN {
+                    self.convert(&to, &from, x.y.as_ref(), Conversion::Assign);
+                } else {
+                    let op = x.op.binary_op();
+                    self.binary(op, x.op_pos, x.x.as_ref(), to.decay(), x.y.as_ref(), from);
+                }
+
+                Some(to.unqualified())
+            }
+
+            ExprKind::CondExpr(x) => {
+                let c = self.value(x.cond.as_ref());
+                let a = self.value(x.x.as_ref());
+                let b = self.value(x.y.as_ref());
+                let (c, a, b) = (c?, a?, b?);
+
+                if !c.is_scalar() {
+                    let msg = format!(
+                        "used type '{}' where arithmetic or pointer type is required",
+                        self.info.spell(&c)
+                    );
+                    self.error(x.cond.start(), msg);
+                }
+
+                self.conditional(x.question, x.x.as_ref(), a, x.y.as_ref(), b)
+            }
+
+            ExprKind::CallExpr(x) => {
+                let f = match x.fun.kind() {
+                    // calling an undeclared function declares it (C89)
+                    ExprKind::Ident(f) if self.lookup(&f.name).is_none() => {
+                        self.warning(
+                            f.pos,
+                            format!("implicit declaration of function '{}'", f.name),
+                        );
+
+                        let id = self.implicit_func(f);
+                        let t = self.info.symbol(id).ty.clone();
+                        self.info
+                            .types
+                            .insert((x.fun.start(), x.fun.end()), t.clone());
+
+                        Some(t.decay())
+                    }
+                    _ => self.value(x.fun.as_ref()),
+                };
+
+                let args: Vec<_> = x.args.iter().map(|a| self.value(a.as_ref())).collect();
+
+                let f = f?;
+                let Some(func) = f.pointee().and_then(|t| t.func()).cloned() else {
+                    let msg = format!(
+                        "called object type '{}' is not a function or function pointer",
+                        self.info.spell(&f)
+                    );
+                    self.error(x.fun.start(), msg);
+                    return None;
+                };
+
+                if func.prototype {
+                    let (want, have) = (func.params.len(), x.args.len());
+
+                    if have < want {
+                        self.error(
+                            x.rparen,
+                            format!(
+                                "too few arguments to function call, expected {}, have {}",
+                                want, have
+                            ),
+                        );
+                    } else if have > want && !func.variadic {
+                        self.error(
+                            x.args[want].start(),
+                            format!(
+                                "too many arguments to function call, expected {}, have {}",
+                                want, have
+                            ),
+                        );
+                    }
+
+                    for (p, (a, t)) in func.params.iter().zip(x.args.iter().zip(args.iter())) {
+                        if let Some(t) = t {
+                            self.convert(p, t, a.as_ref(), Conversion::Arg);
+                        }
+                    }
+                }
+
+                // a failed assert prints the expression it was given
+                if let (ExprKind::Ident(f), [a]) = (x.fun.kind(), &x.args[..]) {
+                    if f.name == "assert" {
+                        let text = Printer::new(Style::default()).print_expr(a.as_ref());
+                        self.info.asserts.insert(f.pos, text);
+                    }
+                }
+
+                Some(func.ret)
+            }
+
+            ExprKind::IndexExpr(x) => {
+                let a = self.value(x.x.as_ref());
+                let b = self.value(x.index.as_ref());
+                let (a, b) = (a?, b?);
+
+                // `i[a]` is `a[i]`
+                let (p, i, index) = if a.is_pointer() {
+                    (a, b, x.index.as_ref())
+                } else if b.is_pointer() {
+                    (b, a, x.x.as_ref())
+                } else {
+                    self.error(
+                        x.x.start(),
+                        "subscripted value is not an array, pointer, or vector".to_string(),
+                    );
+                    return None;
+                };
+
+                if !i.is_integer() {
+                    self.error(
+                        index.start(),
+                        "array subscript is not an integer".to_string(),
+                    );
+                    return None;
+                }
+
+                let elem = p.pointee().unwrap().clone();
+
+                if !elem.is_complete(&self.info.tags) {
+                    let msg = format!(
+                        "subscript of pointer to incomplete type '{}'",
+                        self.info.spell(&elem)
+                    );
+                    self.error(x.lbrack, msg);
+                    return None;
+                }
+
+                Some(elem)
+            }
+
+            ExprKind::SelectorExpr(x) => {
+                let t = self.expr(x.x.as_ref())?;
+
+                let rec = if x.op == Token::ARROW {
+                    match t.decay().pointee() {
+                        Some(p) => p.clone(),
+                        None => {
+                            let msg = format!(
+                                "member reference type '{}' is not a pointer",
+                                self.info.spell(&t)
+                            );
+                            self.error(x.op_pos, msg);
+                            return None;
+                        }
+                    }
+                } else {
+                    t
+                };
+
+                let (TypeKind::Struct(id) | TypeKind::Union(id)) = rec.kind else {
+                    let msg = format!(
+                        "member reference base type '{}' is not a structure or union",
+                        self.info.spell(&rec)
+                    );
+                    self.error(x.op_pos, msg);
+                    return None;
+                };
+
+                if self.info.tags[id.0].members.is_none() {
+                    let msg = format!("incomplete definition of type '{}'", self.info.spell(&rec));
+                    self.error(x.op_pos, msg);
+                    return None;
+                }
+
+                match self.find_member(id, &x.sel.name) {
+                    Some((_, t)) => Some(t.qualified(rec.quals)),
+                    None => {
+                        let msg = format!(
+                            "no member named '{}' in '{}'",
+                            x.sel.name,
+                            self.info.spell(&rec.unqualified())
+                        );
+                        self.error(x.sel.pos, msg);
+                        None
+                    }
+                }
+            }
+
+            ExprKind::CastExpr(x) => {
+                let to = self.type_name(&x.typ);
+                let from = self.value(x.x.as_ref())?;
+
+                if to.is_void() {
+                    return Some(Type::void());
+                }
+
+                let msg = if !to.is_scalar() {
+                    format!(
+                        "used type '{}' where arithmetic or pointer type is required",
+                        self.info.spell(&to)
+                    )
+                } else if !from.is_scalar() {
+                    format!(
+                        "operand of type '{}' where arithmetic or pointer type is required",
+                        self.info.spell(&from)
+                    )
+                } else if to.is_pointer() && from.is_floating() {
+                    format!(
+                        "operand of type '{}' cannot be cast to a pointer type",
+                        self.info.spell(&from)
+                    )
+                } else if to.is_floating() && from.is_pointer() {
+                    format!("pointer cannot be cast to type '{}'", self.info.spell(&to))
+                } else {
+                    return Some(to.unqualified());
+                };
+
+                self.error(x.x.start(), msg);
+                None
+            }
+
+            ExprKind::SizeofExpr(x) => {
+                let op = x.op.to_str();
+                let t = match &x.arg {
+                    SizeofArg::Expr(e) => {
+                        if x.op == Token::ALIGNOF {
+                            self.warning(
+                                x.pos,
+                                "'_Alignof' applied to an expression is a GNU extension"
+                                    .to_string(),
+                            );
+                        }
+                        self.expr(e.as_ref())?
+                    }
+                    SizeofArg::Type(typ) => self.type_name(typ),
+                };
+
+                if t.is_function() {
+                    self.error(
+                        x.pos,
+                        format!("invalid application of '{}' to a function type", op),
+                    );
+                    return None;
+                }
+
+                if !t.is_complete(&self.info.tags) {
+                    let msg = format!(
+                        "invalid application of '{}' to an incomplete type '{}'",
+                        op,
+                        self.info.spell(&t)
+                    );
+                    self.error(x.pos, msg);
+                    return None;
+                }
+
+                Some(Type::int(self.info.layout.size_t))
+            }
+
+            ExprKind::OffsetofExpr(x) => {
+                let t = self.type_name(&x.typ);
+                self.offsetof(&t, x)?;
+                Some(Type::int(self.info.layout.size_t))
+            }
+
+            ExprKind::CompositeLit(x) => {
+                let t = self.type_name(&x.typ);
+                Some(self.initializer(&t, &x.init))
+            }
+
+            // lists only appear in initializers
+            ExprKind::InitList(x) => {
+                for e in x.elems.iter() {
+                    self.skip(e);
+                }
+                None
+            }
+        }
+    }
+
+    fn literal(&mut self, x: &BasicLit) -> Option<Type> {
+        match x.tok {
+            Token::INTEGER => self.int_literal(x),
+
+            Token::FLOATING => {
+                let kind = match x.lit.chars().last() {
+                    Some('f' | 'F') => FloatKind::Float,
+                    Some('l' | 'L') => FloatKind::LongDouble,
+                    _ => FloatKind::Double,
+                };
+
+                Some(Type::float(kind))
+            }
+
+            Token::CHARACTER => {
+                let kind = match &x.lit[..x.lit.find('\'').unwrap_or(0)] {
+                    "u" => IntKind::UShort,
+                    "U" => IntKind::UInt,
+                    "u8" => IntKind::UChar,
+                    "L" => self.info.layout.wchar_t,
+                    _ => IntKind::Int,
+                };
+
+                Some(Type::int(kind))
+            }
+
+            Token::STRING => Some(string_type(&x.lit, self.info.layout.wchar_t)),
+
+            _ => None,
+        }
+    }
+
+    // the type of an integer constant is the first of a list that can
+    // represent its value, the list depends on its suffix and base
+    fn int_literal(&mut self, x: &BasicLit) -> Option<Type> {
+        use IntKind::*;
+
+        let (digits, suffix) = split_suffix(&x.lit);
+        let decimal = !digits.starts_with('0') || digits == "0";
+        let unsigned = suffix.contains(['u', 'U']);
+        let long = suffix.chars().filter(|c| matches!(c, 'l' | 'L')).count();
+
+        let kinds: &[IntKind] = match (long, unsigned, decimal) {
+            (0, false, true) => &[Int, Long, LongLong],
+            (0, false, false) => &[Int, UInt, Long, ULong, LongLong, ULongLong],
+            (0, true, _) => &[UInt, ULong, ULongLong],
+            (1, false, true) => &[Long, LongLong],
+            (1, false, false) => &[Long, ULong, LongLong, ULongLong],
+            (1, true, _) => &[ULong, ULongLong],
+            (_, false, true) => &[LongLong],
+            (_, false, false) => &[LongLong, ULongLong],
+            (_, true, _) => &[ULongLong],
+        };
+
+        let value = parse_int(digits);
+        let dl = &self.info.layout;
+
+        if let Some(&k) = kinds
+            .iter()
+            .find(|&&k| value.is_some_and(|v| v <= dl.max(k)))
+        {
+            return Some(Type::int(k));
+        }
+
+        if value.is_some_and(|v| v <= dl.max(ULongLong)) {
+            self.warning(
+                x.pos,
+                "integer literal is too large to be represented in a signed integer type, interpreting as unsigned".to_string(),
+            );
+            return Some(Type::int(ULongLong));
+        }
+
+        self.error(
+            x.pos,
+            "integer literal is too large to be represented in any integer type".to_string(),
+        );
+        None
+    }
+
+    fn address(&mut self, x: &dyn Expr, pos: usize) -> Option<Type> {
+        let t = self.expr(x)?;
+
+        if t.is_function() || self.is_lvalue(x) {
+            return Some(t.pointer_to());
+        }
+
+        let msg = format!(
+            "cannot take the address of an rvalue of type '{}'",
+            self.info.spell(&t)
+        );
+        self.error(pos, msg);
+        None
+    }
+
+    // ++ and --, prefix or postfix
+    fn increment(&mut self, op: Token, x: &dyn Expr) -> Option<Type> {
+        let t = self.expr(x)?;
+
+        if !self.modifiable(x, &t) {
+            return Some(t.unqualified());
+        }
+
+        if t.is_pointer() {
+            self.pointer_arith(x.start(), &t);
+        } else if !t.is_arithmetic() {
+            let what = if op == Token::INC {
+                "increment"
+            } else {
+                "decrement"
+            };
+            let msg = format!("cannot {} value of type '{}'", what, self.info.spell(&t));
+            self.error(x.start(), msg);
+        }
+
+        Some(t.unqualified())
+    }
+
+    fn binary(
+        &mut self,
+        op: Token,
+        pos: usize,
+        x: &dyn Expr,
+        a: Type,
+        y: &dyn Expr,
+        b: Type,
+    ) -> Option<Type> {
+        let arithmetic = a.is_arithmetic() && b.is_arithmetic();
+        let integer = a.is_integer() && b.is_integer();
+
+        let t = match op {
+            Token::COMMA => Some(b.clone()),
+
+            Token::ASTERISK | Token::SLASH if arithmetic => {
+                Some(usual_arithmetic(&a, &b, &self.info.layout))
+            }
+            Token::PLUS | Token::MINUS if arithmetic => {
+                Some(usual_arithmetic(&a, &b, &self.info.layout))
+            }
+            Token::REM | Token::AND | Token::OR | Token::XOR if integer => {
+                Some(usual_arithmetic(&a, &b, &self.info.layout))
+            }
+            Token::SHL | Token::SHR if integer => Some(a.promote()),
+
+            Token::PLUS if a.is_pointer() && b.is_integer() => Some(self.pointer_arith(pos, &a)),
+            Token::PLUS if a.is_integer() && b.is_pointer() => Some(self.pointer_arith(pos, &b)),
+            Token::MINUS if a.is_pointer() && b.is_integer() => Some(self.pointer_arith(pos, &a)),
+
+            Token::MINUS
+                if a.is_pointer()
+                    && b.is_pointer()
+                    && a.pointee()?.compatible_unqualified(b.pointee()?) =>
+            {
+                self.pointer_arith(pos, &a);
+                Some(Type::int(self.info.layout.ptrdiff_t))
+            }
+
+            Token::LAND | Token::LOR if a.is_scalar() && b.is_scalar() => {
+                Some(Type::int(IntKind::Int))
+            }
+
+            Token::LT | Token::GT | Token::LEQ | Token::GEQ | Token::EQL | Token::NEQ => {
+                self.compare(op, pos, x, &a, y, &b)
+            }
+
+            _ => None,
+        };
+
+        if t.is_none() {
+            let msg = format!(
+                "invalid operands to binary expression ('{}' and '{}')",
+                self.info.spell(&a),
+                self.info.spell(&b)
+            );
+            self.error(pos, msg);
+        }
+
+        t
+    }
+
+    // checks that a pointer points to a complete object type
+    fn pointer_arith(&mut self, pos: usize, p: &Type) -> Type {
+        let t = p.pointee().unwrap();
+
+        if t.is_function() {
+            let msg = format!(
+                "arithmetic on a pointer to the function type '{}'",
+                self.info.spell(t)
+            );
+            self.error(pos, msg);
+        } else if !t.is_void() && !t.is_complete(&self.info.tags) {
+            let msg = format!(
+                "arithmetic on a pointer to an incomplete type '{}'",
+                self.info.spell(t)
+            );
+            self.error(pos, msg);
+        }
+
+        p.clone()
+    }
+
+    fn compare(
+        &mut self,
+        op: Token,
+        pos: usize,
+        x: &dyn Expr,
+        a: &Type,
+        y: &dyn Expr,
+        b: &Type,
+    ) -> Option<Type> {
+        let int = Type::int(IntKind::Int);
+
+        if a.is_arithmetic() && b.is_arithmetic() {
+            return Some(int);
+        }
+
+        let equality = matches!(op, Token::EQL | Token::NEQ);
+
+        let msg = match (a.pointee(), b.pointee()) {
+            (Some(p), Some(q)) => {
+                let void = equality && (p.is_void() || q.is_void());
+
+                if void || p.compatible_unqualified(q) {
+                    return Some(int);
+                }
+                "comparison of distinct pointer types"
+            }
+
+            (Some(_), None) if b.is_integer() => {
+                if equality && self.is_null(y) {
+                    return Some(int);
+                }
+                "comparison between pointer and integer"
+            }
+
+            (None, Some(_)) if a.is_integer() => {
+                if equality && self.is_null(x) {
+                    return Some(int);
+                }
+                "comparison between pointer and integer"
+            }
+
+            _ => return None,
+        };
+
+        let msg = format!(
+            "{} ('{}' and '{}')",
+            msg,
+            self.info.spell(a),
+            self.info.spell(b)
+        );
+        self.warning(pos, msg);
+
+        Some(int)
+    }
+
+    fn conditional(
+        &mut self,
+        pos: usize,
+        x: &dyn Expr,
+        a: Type,
+        y: &dyn Expr,
+        b: Type,
+    ) -> Option<Type> {
+        if a.is_arithmetic() && b.is_arithmetic() {
+            return Some(usual_arithmetic(&a, &b, &self.info.layout));
+        }
+
+        if (a.is_void() && b.is_void()) || (a.is_record() && a.compatible_unqualified(&b)) {
+            return Some(a);
+        }
+
+        let mismatch = |r: &mut Self, msg: &str| {
+            let msg = format!(
+                "{} ('{}' and '{}')",
+                msg,
+                r.info.spell(&a),
+                r.info.spell(&b)
+            );
+            r.warning(pos, msg);
+        };
+
+        match (a.pointee(), b.pointee()) {
+            (Some(_), _) if self.is_null(y) => Some(a),
+            (_, Some(_)) if self.is_null(x) => Some(b),
+
+            // the result points to a type with the qualifiers of both
+            (Some(p), Some(q)) => {
+                let quals = p.quals.union(q.quals);
+
+                let t = if p.compatible_unqualified(q) {
+                    p.unqualified()
+                } else {
+                    if !p.is_void() && !q.is_void() {
+                        mismatch(self, "pointer type mismatch");
+                    }
+                    Type::void()
+                };
+
+                Some(t.qualified(quals).pointer_to())
+            }
+
+            (Some(_), None) if b.is_integer() => {
+                mismatch(
+                    self,
+                    "pointer/integer type mismatch in conditional expression",
+                );
+                Some(a.clone())
+            }
+
+            (None, Some(_)) if a.is_integer() => {
+                mismatch(
+                    self,
+                    "pointer/integer type mismatch in conditional expression",
+                );
+                Some(b.clone())
+            }
+
+            _ => {
+                let msg = format!(
+                    "incompatible operand types ('{}' and '{}')",
+                    self.info.spell(&a),
+                    self.info.spell(&b)
+                );
+                self.error(pos, msg);
+                None
+            }
+        }
+    }
+
+    // converts the value of `x` of type `from` to the type of an object
+    fn convert(&mut self, to: &Type, from: &Type, x: &dyn Expr, how: Conversion) {
+        if (to.is_arithmetic() && from.is_arithmetic())
+            || (to.is_record() && to.compatible_unqualified(from))
+        {
+            return;
+        }
+
+        let describe = |r: &Self, incompatible| {
+            how.describe(
+                &r.info.spell(&to.unqualified()),
+                &r.info.spell(from),
+                incompatible,
+            )
+        };
+
+        match (&to.kind, &from.kind) {
+            (TypeKind::Pointer(p), TypeKind::Pointer(q)) => {
+                if !p.compatible_unqualified(q) && !p.is_void() && !q.is_void() {
+                    let msg = format!("incompatible pointer types {}", describe(self, false));
+                    self.warning(x.start(), msg);
+                } else if !p.quals.contains(q.quals) {
+                    let msg = format!("{} discards qualifiers", describe(self, false));
+                    self.warning(x.start(), msg);
+                }
+            }
+
+            (TypeKind::Pointer(_), _) if self.is_null(x) => {}
+
+            (TypeKind::Pointer(_), _) if from.is_integer() => {
+                let msg = format!(
+                    "incompatible integer to pointer conversion {}",
+                    describe(self, false)
+                );
+                self.warning(x.start(), msg);
+            }
+
+            (TypeKind::Int(IntKind::Bool), TypeKind::Pointer(_)) => {}
+
+            (_, TypeKind::Pointer(_)) if to.is_integer() => {
+                let msg = format!(
+                    "incompatible pointer to integer conversion {}",
+                    describe(self, false)
+                );
+                self.warning(x.start(), msg);
+            }
+
+            _ => {
+                let msg = describe(self, true);
+                self.error(x.start(), msg);
+            }
+        }
+    }
+
+    fn is_lvalue(&self, x: &dyn Expr) -> bool {
+        match x.kind() {
+            ExprKind::Ident(x) => self.info.uses.get(&x.pos).is_some_and(|&id| {
+                matches!(
+                    self.info.symbol(id).kind,
+                    SymbolKind::Var | SymbolKind::Param
+                )
+            }),
+
+            ExprKind::ParenExpr(x) => self.is_lvalue(x.x.as_ref()),
+            ExprKind::StarExpr(_) | ExprKind::IndexExpr(_) | ExprKind::CompositeLit(_) => true,
+            ExprKind::SelectorExpr(x) => x.op == Token::ARROW || self.is_lvalue(x.x.as_ref()),
+            ExprKind::BasicLit(x) => x.tok == Token::STRING,
+
+            _ => false,
+        }
+    }
+
+    // checks that `x` of type `t` can be assigned to
+    fn modifiable(&mut self, x: &dyn Expr, t: &Type) -> bool {
+        let msg = if !self.is_lvalue(x) {
+            "expression is not assignable".to_string()
+        } else if t.is_array() {
+            format!("array type '{}' is not assignable", self.info.spell(t))
+        } else if t.quals.is_const {
+            match strip_parens(x).kind() {
+                ExprKind::Ident(id) => format!(
+                    "cannot assign to variable '{}' with const-qualified type '{}'",
+                    id.name,
+                    self.info.spell(t)
+                ),
+                _ => "read-only variable is not assignable".to_string(),
+            }
+        } else if self.has_const_member(t) {
+            format!(
+                "cannot assign to a value of type '{}' with a const-qualified member",
+                self.info.spell(t)
+            )
+        } else if !t.is_complete(&self.info.tags) {
+            format!("incomplete type '{}' is not assignable", self.info.spell(t))
+        } else {
+            return true;
+        };
+
+        self.error(x.start(), msg);
+        false
+    }
+
+    fn has_const_member(&self, t: &Type) -> bool {
+        let (TypeKind::Struct(id) | TypeKind::Union(id)) = t.kind else {
+            return false;
+        };
+
+        self.info.tags[id.0]
+            .members
+            .iter()
+            .flatten()
+            .any(|m| m.ty.quals.is_const || self.has_const_member(&m.ty))
+    }
+
+    // a null pointer constant: 0, or 0 cast to void *
+    fn is_null(&self, x: &dyn Expr) -> bool {
+        match x.kind() {
+            ExprKind::ParenExpr(p) => self.is_null(p.x.as_ref()),
+
+            ExprKind::CastExpr(c) => {
+                let void = self.info.type_of(x).and_then(|t| t.pointee()) == Some(&Type::void());
+                void && self.is_null(c.x.as_ref())
+            }
+
+            // an integer constant expression with the value 0
+            _ => {
+                self.info.type_of(x).is_some_and(Type::is_integer)
+                    && Eval::new(&self.info).int(x) == Ok(0)
+            }
+        }
+    }
+
+    // checks the type and member designator of offsetof
+    fn offsetof(&mut self, ty: &Type, x: &OffsetofExpr) -> Option<()> {
+        let mut ty = ty.clone();
+        let mut field = Some(&x.member);
+        let mut path = x.path.iter();
+
+        loop {
+            if let Some(name) = field {
+                let (TypeKind::Struct(id) | TypeKind::Union(id)) = ty.kind else {
+                    let msg = format!(
+                        "offsetof requires struct, union, or class type, '{}' invalid",
+                        self.info.spell(&ty)
+                    );
+                    self.error(name.pos, msg);
+                    return None;
+                };
+
+                if !ty.is_complete(&self.info.tags) {
+                    let msg = format!("offsetof of incomplete type '{}'", self.info.spell(&ty));
+                    self.error(name.pos, msg);
+                    return None;
+                }
+
+                let Some(m) = self.info.layout.member(id, &name.name, &self.info.tags) else {
+                    let msg = format!(
+                        "no member named '{}' in '{}'",
+                        name.name,
+                        self.info.spell(&ty)
+                    );
+                    self.error(name.pos, msg);
+                    return None;
+                };
+
+                if m.bits.is_some() {
+                    let msg = format!("cannot compute offset of bit-field '{}'", name.name);
+                    self.error(name.pos, msg);
+                    return None;
+                }
+
+                ty = m.ty;
+            }
+
+            field = match path.next() {
+                Some(Designator::Field { name, .. }) => Some(name),
+                Some(Designator::Index { lbrack, index, .. }) => {
+                    if self.value(index.as_ref()).is_some_and(|t| !t.is_integer()) {
+                        self.error(
+                            index.start(),
+                            "array subscript is not an integer".to_string(),
+                        );
+                    }
+
+                    let Some(elem) = ty.elem().cloned() else {
+                        self.error(
+                            *lbrack,
+                            "subscripted value is not an array, pointer, or vector".to_string(),
+                        );
+                        return None;
+                    };
+
+                    ty = elem;
+                    None
+                }
+                None => return Some(()),
+            };
+        }
+    }
+
+    // finds a member by name, looking into anonymous struct and union
+    // members, and returns the index of the member holding it
+    fn find_member(&self, id: TagId, name: &str) -> Option<(usize, Type)> {
+        let members = self.info.tags[id.0].members.as_ref()?;
+
+        members
+            .iter()
+            .enumerate()
+            .find_map(|(i, m)| match (&m.name, &m.ty.kind) {
+                (Some(n), _) if n == name => Some((i, m.ty.clone())),
+                (None, TypeKind::Struct(id) | TypeKind::Union(id)) => self
+                    .find_member(*id, name)
+                    .map(|(_, t)| (i, t.qualified(m.ty.quals))),
+                _ => None,
+            })
+    }
+
+    // declares `int f()` at file scope for a call to an undeclared function
+    fn implicit_func(&mut self, f: &Ident) -> SymbolId {
+        let id = match self.linked.get(&f.name) {
+            Some(&id) => id,
+            None => {
+                let file = self.stack[0];
+                let id = SymbolId(self.info.symbols.len());
+
+                let ty = TypeKind::Function(Box::new(FuncType {
+                    ret: Type::int(IntKind::Int),
+                    params: Vec::new(),
+                    variadic: false,
+                    prototype: false,
+                }));
+
+                self.info.symbols.push(Symbol {
+                    name: f.name.clone(),
+                    kind: SymbolKind::Func,
+                    pos: f.pos,
+                    scope: file,
+                    linkage: Linkage::External,
+                    defined: false,
+                    ty: ty.into(),
+                    value: None,
+                });
+                self.linked.insert(f.name.clone(), id);
+                self.info.scopes[file.0].names.insert(f.name.clone(), id);
+
+                id
+            }
+        };
+
+        self.info.uses.insert(f.pos, id);
+        id
+    }
+
+    /// Checks the initializer of an object of type `ty` and returns the
+    /// type it completes, like `int [2]` for `int a[] = {1, 2}`.
+    pub(crate) fn initializer(&mut self, ty: &Type, x: &dyn Expr) -> Type {
+        if let ExprKind::InitList(list) = x.kind() {
+            let t = self.init_list(ty, list);
+            self.info.types.insert((x.start(), x.end()), t.clone());
+            return t;
+        }
+
+        if ty.is_array() {
+            let Some(t) = self.expr(x) else {
+                return ty.clone();
+            };
+
+            if let Some(t) = self.string_init(ty, &t, x) {
+                return t;
+            }
+
+            self.error(
+                x.start(),
+                "array initializer must be an initializer list".to_string(),
+            );
+            return ty.clone();
+        }
+
+        if let Some(from) = self.value_once(x) {
+            self.convert(ty, &from, x, Conversion::Init);
+        }
+
+        ty.clone()
+    }
+
+    // initializes a character array with a string literal `x` of type `s`
+    fn string_init(&mut self, ty: &Type, s: &Type, x: &dyn Expr) -> Option<Type> {
+        if !is_string(x) {
+            return None;
+        }
+
+        let (TypeKind::Array(elem, len), TypeKind::Array(c, ArrayLen::Fixed(n))) =
+            (&ty.kind, &s.kind)
+        else {
+            return None;
+        };
+
+        let (e, c) = (elem.int_kind()?, c.int_kind()?);
+        let chars = |k| matches!(k, IntKind::Char | IntKind::SChar | IntKind::UChar);
+
+        if e != c && !(chars(e) && chars(c)) {
+            return None;
+        }
+
+        match len {
+            ArrayLen::Incomplete => {
+                return Some(elem.as_ref().clone().array_of(ArrayLen::Fixed(*n)))
+            }
+
+            // the terminating null may be left out
+            ArrayLen::Fixed(m) if n - 1 > *m => self.warning(
+                x.start(),
+                "initializer-string for char array is too long".to_string(),
+            ),
+
+            ArrayLen::Variable => self.error(
+                x.start(),
+                "variable-sized object may not be initialized".to_string(),
+            ),
+
+            _ => {}
+        }
+
+        Some(ty.clone())
+    }
+
+    fn init_list(&mut self, ty: &Type, list: &InitList) -> Type {
+        let mut elems = list.elems.iter().peekable();
+
+        let t = if ty.is_vla() {
+            self.error(
+                list.lbrace,
+                "variable-sized object may not be initialized".to_string(),
+            );
+            ty.clone()
+        } else if ty.is_scalar() {
+            // `int x = {1};`
+            if let Some(e) = elems.next() {
+                if let Some(d) = e.designators.first() {
+                    let msg = format!(
+                        "designator in initializer for scalar type '{}'",
+                        self.info.spell(ty)
+                    );
+                    self.error(d.start(), msg);
+                }
+                self.initializer(ty, e.value.as_ref());
+            }
+
+            if let Some(e) = elems.peek() {
+                self.warning(
+                    e.value.start(),
+                    "excess elements in scalar initializer".to_string(),
+                );
+            }
+            ty.clone()
+        } else {
+            self.fill(ty, &mut elems, true)
+        };
+
+        for e in elems {
+            self.skip(e);
+        }
+
+        t
+    }
+
+    // initializes the elements of an aggregate from `elems`, all of them in
+    // braces, else as many as it holds (brace elision)
+    fn fill(&mut self, ty: &Type, elems: &mut Elems, braced: bool) -> Type {
+        match &ty.kind {
+            TypeKind::Array(elem, len) => self.fill_array(ty, elem, *len, elems, braced),
+
+            TypeKind::Struct(id) | TypeKind::Union(id) => {
+                self.fill_record(ty, *id, elems, braced);
+                ty.clone()
+            }
+
+            _ => ty.clone(),
+        }
+    }
+
+    fn fill_array(
+        &mut self,
+        ty: &Type,
+        elem: &Type,
+        len: ArrayLen,
+        elems: &mut Elems,
+        braced: bool,
+    ) -> Type {
+        // `char s[] = {"abc"};`
+        if let Some(&e) = elems.peek() {
+            if braced
+                && e.designators.is_empty()
+                && elem.is_integer()
+                && is_string(e.value.as_ref())
+            {
+                elems.next();
+                let t = self.initializer(ty, e.value.as_ref());
+
+                if let Some(e) = elems.peek() {
+                    self.warning(
+                        e.value.start(),
+                        "excess elements in char array initializer".to_string(),
+                    );
+                }
+                return t;
+            }
+        }
+
+        let bound = match len {
+            ArrayLen::Fixed(n) => Some(n),
+            _ => None,
+        };
+
+        let (mut next, mut max) = (0, 0);
+        let mut excess = false;
+
+        while let Some(&e) = elems.peek() {
+            if let Some(d) = e.designators.first() {
+                if !braced {
+                    break;
+                }
+                elems.next();
+
+                match self.designator(ty, d) {
+                    Some(i) => {
+                        next = i;
+                        self.designated(elem, &e.designators[1..], e.value.as_ref());
+                    }
+                    None => self.skip(e),
+                }
+            } else if bound.is_some_and(|n| next >= n) {
+                if !braced {
+                    break;
+                }
+                elems.next();
+
+                if !excess {
+                    self.warning(
+                        e.value.start(),
+                        "excess elements in array initializer".to_string(),
+                    );
+                    excess = true;
+                }
+                self.skip(e);
+                continue;
+            } else {
+                self.elem(elem, elems);
+            }
+
+            next += 1;
+            max = max.max(next);
+        }
+
+        match len {
+            ArrayLen::Incomplete => elem.clone().array_of(ArrayLen::Fixed(max)),
+            _ => ty.clone(),
+        }
+    }
+
+    fn fill_record(&mut self, ty: &Type, id: TagId, elems: &mut Elems, braced: bool) {
+        let Some(members) = self.info.tags[id.0].members.clone() else {
+            return;
+        };
+        let union = self.info.tags[id.0].kind == Token::UNION;
+
+        let mut next = 0;
+        let mut excess = false;
+
+        while let Some(&e) = elems.peek() {
+            if let Some(d) = e.designators.first() {
+                if !braced {
+                    break;
+                }
+                elems.next();
+
+                match (self.designator(ty, d), d) {
+                    (Some(i), Designator::Field { name, .. }) => {
+                        let (_, t) = self.find_member(id, &name.name).unwrap();
+                        self.designated(&t, &e.designators[1..], e.value.as_ref());
+                        next = i as usize + 1;
+                    }
+                    _ => self.skip(e),
+                }
+            } else {
+                // unnamed bit-fields aren't initialized
+                while members
+                    .get(next)
+                    .is_some_and(|m| m.name.is_none() && m.bits.is_some())
+                {
+                    next += 1;
+                }
+
+                if next >= members.len() {
+                    if !braced {
+                        break;
+                    }
+                    elems.next();
+
+                    if !excess {
+                        let what = if union { "union" } else { "struct" };
+                        self.warning(
+                            e.value.start(),
+                            format!("excess elements in {} initializer", what),
+                        );
+                        excess = true;
+                    }
+                    self.skip(e);
+                    continue;
+                }
+
+                self.elem(&members[next].ty, elems);
+                next += 1;
+            }
+
+            // a union initializes one member
+            if union {
+                next = members.len();
+            }
+        }
+    }
+
+    // initializes an element of an aggregate with the next initializer, or
+    // with as many as it takes when its braces are left out
+    fn elem(&mut self, ty: &Type, elems: &mut Elems) {
+        let Some(&e) = elems.peek() else {
+            return;
+        };
+        let value = e.value.as_ref();
+
+        let whole = match &ty.kind {
+            _ if matches!(value.kind(), ExprKind::InitList(_)) => true,
+            TypeKind::Array(elem, _) => elem.is_integer() && is_string(value),
+            TypeKind::Struct(_) | TypeKind::Union(_) => self
+                .value_once(value)
+                .is_none_or(|t| t.compatible_unqualified(ty)),
+            _ => true,
+        };
+
+        let left = elems.len();
+
+        if !whole {
+            self.fill(ty, elems, false);
+        }
+
+        // an empty aggregate takes nothing
+        if elems.len() == left {
+            elems.next();
+            self.initializer(ty, value);
+        }
+    }
+
+    // checks the first designator of an initializer for an aggregate of
+    // type `ty`, and returns the index of the element it selects
+    fn designator(&mut self, ty: &Type, d: &Designator) -> Option<u64> {
+        match (d, &ty.kind) {
+            (Designator::Index { index, .. }, TypeKind::Array(_, len)) => {
+                let i = self.constant(index.as_ref(), "array designator")?;
+
+                if i < 0 {
+                    let msg = format!("array designator value '{}' is negative", i);
+                    self.error(index.start(), msg);
+                    return None;
+                }
+
+                if let ArrayLen::Fixed(n) = len {
+                    if i >= *n as i128 {
+                        self.error(
+                            index.start(),
+                            format!(
+                                "array designator index ({}) exceeds array bounds ({})",
+                                i, n
+                            ),
+                        );
+                        return None;
+                    }
+                }
+
+                Some(i as u64)
+            }
+
+            (Designator::Field { name, .. }, TypeKind::Struct(id) | TypeKind::Union(id)) => {
+                match self.find_member(*id, &name.name) {
+                    Some((i, _)) => Some(i as u64),
+                    None => {
+                        let msg = format!(
+                            "field designator '{}' does not refer to any field in type '{}'",
+                            name.name,
+                            self.info.spell(ty)
+                        );
+                        self.error(name.pos, msg);
+                        None
+                    }
+                }
+            }
+
+            (Designator::Index { lbrack, .. }, _) => {
+                let msg = format!(
+                    "array designator cannot initialize non-array type '{}'",
+                    self.info.spell(ty)
+                );
+                self.error(*lbrack, msg);
+                None
+            }
+
+            (Designator::Field { dot, .. }, _) => {
+                let msg = format!(
+                    "field designator cannot initialize a non-struct, non-union type '{}'",
+                    self.info.spell(ty)
+                );
+                self.error(*dot, msg);
+                None
+            }
+        }
+    }
+
+    // initializes the subobject of `ty` selected by the rest of the
+    // designators of an initializer
+    fn designated(&mut self, ty: &Type, designators: &[Designator], value: &dyn Expr) {
+        let Some(d) = designators.first() else {
+            self.initializer(ty, value);
+            return;
+        };
+
+        let sub = match (self.designator(ty, d), &ty.kind, d) {
+            (None, ..) => None,
+            (Some(_), TypeKind::Array(elem, _), _) => Some(elem.as_ref().clone()),
+            (
+                Some(_),
+                TypeKind::Struct(id) | TypeKind::Union(id),
+                Designator::Field { name, .. },
+            ) => self.find_member(*id, &name.name).map(|(_, t)| t),
+            _ => None,
+        };
+
+        match sub {
+            Some(t) => self.designated(&t, &designators[1..], value),
+            None => self.skip_value(value),
+        }
+    }
+
+    // resolves the names in an initializer that can't be checked
+    fn skip(&mut self, e: &Initializer) {
+        for d in e.designators.iter() {
+            if let Designator::Index { index, .. } = d {
+                self.expr(index.as_ref());
+            }
+        }
+        self.skip_value(e.value.as_ref());
+    }
+
+    fn skip_value(&mut self, x: &dyn Expr) {
+        match x.kind() {
+            ExprKind::InitList(list) => {
+                for e in list.elems.iter() {
+                    self.skip(e);
+                }
+            }
+            _ => {
+                self.value_once(x);
+            }
+        }
+    }
+}
+
+/// Returns the value of an integer literal, in parentheses or not.
+// the value of the digits of an integer constant, with their prefix
+pub(crate) fn parse_int(s: &str) -> Option<u128> {
+    let (digits, radix) = if let Some(s) = s.strip_prefix("0x").or(s.strip_prefix("0X")) {
+        (s, 16)
+    } else if let Some(s) = s.strip_prefix("0b").or(s.strip_prefix("0B")) {
+        (s, 2)
+    } else if s.len() > 1 && s.starts_with('0') {
+        (&s[1..], 8)
+    } else {
+        (s, 10)
+    };
+
+    digits.chars().try_fold(0u128, |n, c| {
+        n.checked_mul(radix as u128)?
+            .checked_add(c.to_digit(radix)? as u128)
+    })
+}
+
+// splits the `u`, `l` and `ll` suffixes off an integer constant
+pub(crate) fn split_suffix(lit: &str) -> (&str, &str) {
+    let digits = lit.trim_end_matches(['u', 'U', 'l', 'L']);
+    (digits, &lit[digits.len()..])
+}
+
+fn is_string(x: &dyn Expr) -> bool {
+    matches!(strip_parens(x).kind(), ExprKind::BasicLit(b) if b.tok == Token::STRING)
+}
+
+pub(crate) fn strip_parens(x: &dyn Expr) -> &dyn Expr {
+    match x.kind() {
+        ExprKind::ParenExpr(p) => strip_parens(p.x.as_ref()),
+        _ => x,
+    }
+}
+
+// the type of a string literal, adjacent literals are joined by spaces and
+// each may have an encoding prefix
+fn string_type(lit: &str, wchar: IntKind) -> Type {
+    let segments = string_segments(lit);
+    let kind = string_kind(&segments, wchar);
+
+    let units = |c: char| match kind {
+        IntKind::Char => c.len_utf8(),
+        IntKind::UShort => c.len_utf16(),
+        _ => 1,
+    };
+
+    let len: usize = segments.iter().map(|(_, s)| count_units(s, units)).sum();
+
+    Type::int(kind).array_of(ArrayLen::Fixed(len as u64 + 1))
+}
+
+/// Splits a string literal into the encoding prefix and the body of each
+/// of the adjacent literals it joins.
+pub(crate) fn string_segments(lit: &str) -> Vec<(&str, &str)> {
+    let mut segments = Vec::new();
+    let mut rest = lit;
+
+    while let Some(q) = rest.find('"') {
+        let prefix = rest[..q].trim();
+        let body = &rest[q + 1..];
+
+        let mut escaped = false;
+        let end = body
+            .char_indices()
+            .find(|&(_, c)| {
+                let end = c == '"' && !escaped;
+                escaped = c == '\\' && !escaped;
+                end
+            })
+            .map_or(body.len(), |(i, _)| i);
+
+        segments.push((prefix, &body[..end]));
+        rest = body.get(end + 1..).unwrap_or("");
+    }
+
+    segments
+}
+
+/// Returns the element type of a string literal, the first wide prefix of
+/// its segments decides it.
+pub(crate) fn string_kind(segments: &[(&str, &str)], wchar: IntKind) -> IntKind {
+    match segments
+        .iter()
+        .map(|s| s.0)
+        .find(|p| matches!(*p, "L" | "u" | "U"))
+    {
+        Some("L") => wchar,
+        Some("u") => IntKind::UShort,
+        Some("U") => IntKind::UInt,
+        _ => IntKind::Char,
+    }
+}
+
+// counts the code units of the body of a string literal
+fn count_units(s: &str, units: impl Fn(char) -> usize) -> usize {
+    let mut n = 0;
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            n += units(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('x') => {
+                while chars.next_if(char::is_ascii_hexdigit).is_some() {}
+                n += 1;
+            }
+
+            Some('0'..='7') => {
+                for _ in 0..2 {
+                    chars.next_if(|c| ('0'..='7').contains(c));
+                }
+                n += 1;
+            }
+
+            Some(c @ ('u' | 'U')) => {
+                let len = if c == 'u' { 4 } else { 8 };
+                let hex: String = (0..len)
+                    .filter_map(|_| chars.next_if(char::is_ascii_hexdigit))
+                    .collect();
+
+                n += u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .map_or(1, &units);
+            }
+
+            _ => n += 1,
+        }
+    }
+
+    n
+}
@@ -0,0 +1,585 @@
+//! Target data layouts: the sizes, alignments and signedness of the basic
+//! types, and the layout of structs and unions with their padding and
+//! bit-fields.
+
+use crate::types::{ArrayLen, FloatKind, IntKind, Tag, TagId, Type, TypeKind};
+use token::Token;
+
+/// The size and alignment of a type, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeAlign {
+    pub size: u64,
+    pub align: u64,
+}
+
+const fn sa(size: u64, align: u64) -> SizeAlign {
+    SizeAlign { size, align }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataLayout {
+    pub name: &'static str,
+    pub char_signed: bool,
+    pub ms_bitfields: bool, // bit-fields are packed like MSVC does
+
+    pub short: SizeAlign,
+    pub int: SizeAlign,
+    pub long: SizeAlign,
+    pub long_long: SizeAlign,
+    pub pointer: SizeAlign,
+    pub float: SizeAlign,
+    pub double: SizeAlign,
+    pub long_double: SizeAlign,
+
+    pub size_t: IntKind,
+    pub ptrdiff_t: IntKind,
+    pub wchar_t: IntKind,
+}
+
+impl Default for DataLayout {
+    fn default() -> Self {
+        Self::LP64
+    }
+}
+
+impl DataLayout {
+    /// x86-64 Linux and macOS.
+    pub const LP64: Self = Self {
+        name: "lp64",
+        char_signed: true,
+        ms_bitfields: false,
+        short: sa(2, 2),
+        int: sa(4, 4),
+        long: sa(8, 8),
+        long_long: sa(8, 8),
+        pointer: sa(8, 8),
+        float: sa(4, 4),
+        double: sa(8, 8),
+        long_double: sa(16, 16),
+        size_t: IntKind::ULong,
+        ptrdiff_t: IntKind::Long,
+        wchar_t: IntKind::Int,
+    };
+
+    /// i386 Linux, where 8 byte types are only aligned to 4 bytes.
+    pub const ILP32: Self = Self {
+        name: "ilp32",
+        char_signed: true,
+        ms_bitfields: false,
+        short: sa(2, 2),
+        int: sa(4, 4),
+        long: sa(4, 4),
+        long_long: sa(8, 4),
+        pointer: sa(4, 4),
+        float: sa(4, 4),
+        double: sa(8, 4),
+        long_double: sa(12, 4),
+        size_t: IntKind::UInt,
+        ptrdiff_t: IntKind::Int,
+        wchar_t: IntKind::Long,
+    };
+
+    /// 64-bit Windows.
+    pub const LLP64: Self = Self {
+        name: "llp64",
+        char_signed: true,
+        ms_bitfields: true,
+        short: sa(2, 2),
+        int: sa(4, 4),
+        long: sa(4, 4),
+        long_long: sa(8, 8),
+        pointer: sa(8, 8),
+        float: sa(4, 4),
+        double: sa(8, 8),
+        long_double: sa(8, 8),
+        size_t: IntKind::ULongLong,
+        ptrdiff_t: IntKind::LongLong,
+        wchar_t: IntKind::UShort,
+    };
+
+    pub const ALL: [Self; 3] = [Self::LP64, Self::ILP32, Self::LLP64];
+
+    /// Looks up a preset by name, e.g. `ilp32`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|l| l.name.eq_ignore_ascii_case(name))
+    }
+
+    fn int(&self, k: IntKind) -> SizeAlign {
+        match k {
+            IntKind::Bool | IntKind::Char | IntKind::SChar | IntKind::UChar => sa(1, 1),
+            IntKind::Short | IntKind::UShort => self.short,
+            IntKind::Int | IntKind::UInt => self.int,
+            IntKind::Long | IntKind::ULong => self.long,
+            IntKind::LongLong | IntKind::ULongLong => self.long_long,
+        }
+    }
+
+    fn float(&self, k: FloatKind) -> SizeAlign {
+        match k {
+            FloatKind::Float => self.float,
+            FloatKind::Double => self.double,
+            FloatKind::LongDouble => self.long_double,
+        }
+    }
+
+    pub fn is_signed(&self, k: IntKind) -> bool {
+        match k {
+            IntKind::Char => self.char_signed,
+            IntKind::SChar | IntKind::Short | IntKind::Int | IntKind::Long | IntKind::LongLong => {
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The width in bits, 1 for _Bool.
+    pub fn width(&self, k: IntKind) -> u32 {
+        match k {
+            IntKind::Bool => 1,
+            k => self.int(k).size as u32 * 8,
+        }
+    }
+
+    /// The largest value of the type.
+    pub fn max(&self, k: IntKind) -> u128 {
+        let bits = if self.is_signed(k) {
+            self.width(k) - 1
+        } else {
+            self.width(k)
+        };
+
+        (1u128 << bits) - 1
+    }
+
+    /// The smallest value of the type.
+    pub fn min(&self, k: IntKind) -> i128 {
+        if self.is_signed(k) {
+            -(1i128 << (self.width(k) - 1))
+        } else {
+            0
+        }
+    }
+
+    /// Converts a value to an integer type: modulo 2^width, the way
+    /// unsigned arithmetic wraps and two's complement truncates.
+    pub fn wrap(&self, v: i128, k: IntKind) -> i128 {
+        if k == IntKind::Bool {
+            return (v != 0) as i128;
+        }
+
+        let w = self.width(k);
+        let m = v & ((1i128 << w) - 1);
+
+        if self.is_signed(k) && m >> (w - 1) != 0 {
+            m - (1i128 << w)
+        } else {
+            m
+        }
+    }
+
+    /// Returns the size of a complete type with a constant size, or None for
+    /// incomplete types, functions and VLAs.
+    pub fn size_of(&self, t: &Type, tags: &[Tag]) -> Option<u64> {
+        self.size_align(t, tags).map(|s| s.size)
+    }
+
+    /// Returns the alignment of a complete type, VLAs included.
+    pub fn align_of(&self, t: &Type, tags: &[Tag]) -> Option<u64> {
+        match &t.kind {
+            TypeKind::Array(elem, len) if *len != ArrayLen::Incomplete => self.align_of(elem, tags),
+            _ => self.size_align(t, tags).map(|s| s.align),
+        }
+    }
+
+    fn size_align(&self, t: &Type, tags: &[Tag]) -> Option<SizeAlign> {
+        match &t.kind {
+            TypeKind::Void | TypeKind::Function(_) => None,
+            TypeKind::Int(k) => Some(self.int(*k)),
+            TypeKind::Float(k) => Some(self.float(*k)),
+            TypeKind::Pointer(_) => Some(self.pointer),
+            TypeKind::Array(elem, ArrayLen::Fixed(n)) => {
+                let elem = self.size_align(elem, tags)?;
+                Some(sa(elem.size.checked_mul(*n)?, elem.align))
+            }
+            TypeKind::Array(..) => None,
+            TypeKind::Struct(id) | TypeKind::Union(id) => {
+                let r = self.record(*id, tags)?;
+                Some(sa(r.size, r.align))
+            }
+            TypeKind::Enum(id) => tags[id.0].members.as_ref().map(|_| self.int),
+        }
+    }
+
+    /// Lays out the members of a complete struct or union.
+    pub fn record(&self, id: TagId, tags: &[Tag]) -> Option<RecordLayout> {
+        let tag = &tags[id.0];
+        let members = tag.members.as_ref()?;
+
+        let mut fields = Vec::new();
+        let mut align = 1;
+        let mut end = 0; // in bits
+        let mut unit: Option<(u64, u64, u64)> = None; // MSVC bit-field unit: size, next bit, end
+
+        for m in members.iter() {
+            let flexible = matches!(m.ty.kind, TypeKind::Array(_, ArrayLen::Incomplete));
+            let s = if flexible {
+                sa(0, self.align_of(m.ty.elem()?, tags)?)
+            } else {
+                self.size_align(&m.ty, tags)?
+            };
+
+            if tag.kind == Token::UNION {
+                if m.bits == Some(0) {
+                    continue;
+                }
+                let size = match m.bits {
+                    Some(bits) if !self.ms_bitfields => (bits as u64).div_ceil(8),
+                    _ => s.size,
+                };
+                if m.name.is_some() || m.bits.is_none() || self.ms_bitfields {
+                    align = align.max(s.align);
+                }
+                end = end.max(size * 8);
+                fields.push(FieldLayout::new(m.name.clone(), &m.ty, 0, m.bits));
+                continue;
+            }
+
+            let offset = match m.bits {
+                None => {
+                    unit = None;
+                    align = align.max(s.align);
+                    let offset = end.next_multiple_of(s.align * 8);
+                    end = offset + s.size * 8;
+                    offset
+                }
+
+                Some(bits) if self.ms_bitfields => {
+                    let bits = bits as u64;
+
+                    match unit {
+                        _ if bits == 0 => {
+                            // ends the unit of the previous bit-field
+                            unit = None;
+                            continue;
+                        }
+                        Some((size, next, unit_end))
+                            if size == s.size && next + bits <= unit_end =>
+                        {
+                            unit = Some((size, next + bits, unit_end));
+                            next
+                        }
+                        _ => {
+                            align = align.max(s.align);
+                            let offset = end.next_multiple_of(s.align * 8);
+                            end = offset + s.size * 8;
+                            unit = Some((s.size, offset + bits, end));
+                            offset
+                        }
+                    }
+                }
+
+                Some(bits) => {
+                    let bits = bits as u64;
+                    let unit = s.size * 8;
+
+                    // unnamed bit-fields do not affect the alignment
+                    if m.name.is_some() {
+                        align = align.max(s.align);
+                    }
+
+                    if bits == 0 {
+                        end = end.next_multiple_of(s.align * 8);
+                        continue;
+                    }
+
+                    // a bit-field does not straddle a unit of its type
+                    let mut offset = end;
+                    if offset / unit != (offset + bits - 1) / unit {
+                        offset = offset.next_multiple_of(unit);
+                    }
+                    end = offset + bits;
+                    offset
+                }
+            };
+
+            fields.push(FieldLayout::new(m.name.clone(), &m.ty, offset, m.bits));
+        }
+
+        Some(RecordLayout {
+            size: end.div_ceil(8).next_multiple_of(align),
+            align,
+            fields,
+        })
+    }
+
+    /// Finds a member of a struct or union, in anonymous members too. The
+    /// offset of the result is from the start of the record `id`.
+    pub fn member(&self, id: TagId, name: &str, tags: &[Tag]) -> Option<FieldLayout> {
+        let r = self.record(id, tags)?;
+
+        for f in r.fields.into_iter() {
+            match (&f.name, &f.ty.kind) {
+                (Some(n), _) if n == name => return Some(f),
+                (None, TypeKind::Struct(inner) | TypeKind::Union(inner)) => {
+                    if let Some(mut m) = self.member(*inner, name, tags) {
+                        m.offset += f.offset;
+                        return Some(m);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    /// Prints the layout of a struct or union like clang's
+    /// -fdump-record-layouts, nested records included.
+    pub fn dump_record(&self, id: TagId, tags: &[Tag]) -> Option<String> {
+        let r = self.record(id, tags)?;
+        let mut out = String::new();
+
+        let ty: Type = match tags[id.0].kind {
+            Token::UNION => TypeKind::Union(id).into(),
+            _ => TypeKind::Struct(id).into(),
+        };
+        out.push_str(&format!("{:>10} | {}\n", 0, ty.spell(tags)));
+        self.dump_fields(&r, 0, 1, tags, &mut out);
+        out.push_str(&format!(
+            "{:>10} | [sizeof={}, align={}]\n",
+            "", r.size, r.align
+        ));
+
+        Some(out)
+    }
+
+    fn dump_fields(
+        &self,
+        r: &RecordLayout,
+        base: u64,
+        depth: usize,
+        tags: &[Tag],
+        out: &mut String,
+    ) {
+        for f in r.fields.iter() {
+            let offset = base + f.offset;
+            let at = match f.bits {
+                Some(bits) => format!(
+                    "{}:{}-{}",
+                    offset / 8,
+                    offset % 8,
+                    (offset % 8 + bits as u64).max(1) - 1
+                ),
+                None => (offset / 8).to_string(),
+            };
+
+            let mut decl = f.ty.spell(tags);
+            if let Some(name) = &f.name {
+                decl.push(' ');
+                decl.push_str(name);
+            }
+            out.push_str(&format!("{:>10} | {:2$}{3}\n", at, "", depth * 2, decl));
+
+            if let TypeKind::Struct(id) | TypeKind::Union(id) = f.ty.kind {
+                if let Some(inner) = self.record(id, tags) {
+                    self.dump_fields(&inner, offset, depth + 1, tags, out);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordLayout {
+    pub size: u64,
+    pub align: u64,
+    pub fields: Vec<FieldLayout>, // in declaration order, zero-width bit-fields left out
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldLayout {
+    pub name: Option<String>,
+    pub ty: Type,
+    pub offset: u64, // in bits from the start of the record
+    pub bits: Option<u32>,
+}
+
+impl FieldLayout {
+    fn new(name: Option<String>, ty: &Type, offset: u64, bits: Option<u32>) -> Self {
+        Self {
+            name,
+            ty: ty.clone(),
+            offset,
+            bits,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // checks a declaration and lays out the first struct or union in it
+    fn record(src: &str, layout: DataLayout) -> RecordLayout {
+        let mut p = parser::Parser::from(src.to_string());
+        let file = p.parse_file();
+        assert!(p.errors().is_empty(), "{:?}", p.errors());
+
+        let info = crate::check_with(&file, layout);
+        assert!(info.diagnostics.is_empty(), "{:?}", info.diagnostics);
+
+        layout.record(TagId(0), &info.tags).unwrap()
+    }
+
+    #[test]
+    fn test_scalars() {
+        let ptr = Type::int(IntKind::Char).pointer_to();
+
+        // sizes and alignments on LP64, ILP32 and LLP64
+        let tests = [
+            (Type::int(IntKind::Short), [(2, 2), (2, 2), (2, 2)]),
+            (Type::int(IntKind::Long), [(8, 8), (4, 4), (4, 4)]),
+            (Type::int(IntKind::ULongLong), [(8, 8), (8, 4), (8, 8)]),
+            (ptr.clone(), [(8, 8), (4, 4), (8, 8)]),
+            (Type::float(FloatKind::Double), [(8, 8), (8, 4), (8, 8)]),
+            (
+                Type::float(FloatKind::LongDouble),
+                [(16, 16), (12, 4), (8, 8)],
+            ),
+            (
+                ptr.array_of(ArrayLen::Fixed(3)),
+                [(24, 8), (12, 4), (24, 8)],
+            ),
+        ];
+
+        for (i, (t, want)) in tests.iter().enumerate() {
+            for (layout, want) in DataLayout::ALL.iter().zip(want.iter()) {
+                let got = (
+                    layout.size_of(t, &[]).unwrap(),
+                    layout.align_of(t, &[]).unwrap(),
+                );
+                assert_eq!(
+                    *want,
+                    got,
+                    "[{}/{}] test case failed: {}",
+                    i + 1,
+                    tests.len(),
+                    layout.name
+                );
+            }
+        }
+
+        let l = DataLayout::LP64;
+        assert_eq!(127, l.max(IntKind::Char));
+        assert_eq!(-32768, l.min(IntKind::Short));
+        assert_eq!(u64::MAX as u128, l.max(IntKind::ULong));
+        assert_eq!(u32::MAX as u128, DataLayout::ILP32.max(IntKind::ULong));
+        assert_eq!(None, l.size_of(&Type::void(), &[]));
+    }
+
+    #[test]
+    fn test_records() {
+        // the offsets in bits of the fields, and the size and alignment on
+        // LP64, ILP32 and LLP64
+        let tests = [
+            (
+                "struct s { char c; int i; };",
+                [
+                    (vec![0, 32], 8, 4),
+                    (vec![0, 32], 8, 4),
+                    (vec![0, 32], 8, 4),
+                ],
+            ),
+            (
+                "struct s { char c; double d; };",
+                [
+                    (vec![0, 64], 16, 8),
+                    (vec![0, 32], 12, 4),
+                    (vec![0, 64], 16, 8),
+                ],
+            ),
+            (
+                "struct s { char c; long l; short s; };",
+                [
+                    (vec![0, 64, 128], 24, 8),
+                    (vec![0, 32, 64], 12, 4),
+                    (vec![0, 32, 64], 12, 4),
+                ],
+            ),
+            (
+                "struct s { int a : 3; int b : 5; char c; };",
+                [
+                    (vec![0, 3, 8], 4, 4),
+                    (vec![0, 3, 8], 4, 4),
+                    (vec![0, 3, 32], 8, 4),
+                ],
+            ),
+            (
+                "struct s { char a : 4; int b : 30; };",
+                [
+                    (vec![0, 32], 8, 4),
+                    (vec![0, 32], 8, 4),
+                    (vec![0, 32], 8, 4),
+                ],
+            ),
+            (
+                "struct s { char a; int : 0; char b; };",
+                [(vec![0, 32], 5, 1), (vec![0, 32], 5, 1), (vec![0, 8], 2, 1)],
+            ),
+            (
+                "struct s { short n; struct { char c; int i; }; char tail[]; };",
+                [
+                    (vec![0, 32, 96], 12, 4),
+                    (vec![0, 32, 96], 12, 4),
+                    (vec![0, 32, 96], 12, 4),
+                ],
+            ),
+            (
+                "union u { char c[5]; int i; double d; };",
+                [
+                    (vec![0, 0, 0], 8, 8),
+                    (vec![0, 0, 0], 8, 4),
+                    (vec![0, 0, 0], 8, 8),
+                ],
+            ),
+        ];
+
+        for (i, (src, want)) in tests.iter().enumerate() {
+            for (layout, (offsets, size, align)) in DataLayout::ALL.iter().zip(want.iter()) {
+                let r = record(src, *layout);
+                let got: Vec<u64> = r.fields.iter().map(|f| f.offset).collect();
+
+                assert_eq!(
+                    (offsets, *size, *align),
+                    (&got, r.size, r.align),
+                    "[{}/{}] test case failed: {}",
+                    i + 1,
+                    tests.len(),
+                    layout.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_dump_record() {
+        let src = "struct s { char c; int a : 3; struct { short x; } in; };";
+        let mut p = parser::Parser::from(src.to_string());
+        let info = crate::check(&p.parse_file());
+
+        let want = "         0 | struct s
+         0 |   char c
+     1:0-2 |   int a
+         2 |   struct (anonymous) in
+         2 |     short x
+           | [sizeof=4, align=4]
+";
+
+        assert_eq!(
+            want,
+            DataLayout::LP64.dump_record(TagId(0), &info.tags).unwrap()
+        );
+    }
+}
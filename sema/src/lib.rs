@@ -0,0 +1,1774 @@
+//! Semantic analysis: builds the file, function and block scopes of a
+//! translation unit, binds every identifier to its declaration and types
+//! every expression.
+
+use std::collections::{HashMap, HashSet};
+
+use ast::{
+    BlockStmt, Decl, DeclKind, DeclSpec, Declarator, Derived, Expr, File, FuncDecl, GenDecl, Ident,
+    StaticAssert, Stmt, StmtKind, TypeName, TypeSpec,
+};
+use token::Token;
+
+use layout::DataLayout;
+use types::{ArrayLen, FloatKind, IntKind, Member, Quals, Tag, TagId, Type, TypeKind};
+
+pub mod cfg;
+pub mod consts;
+mod expr;
+pub mod layout;
+pub mod types;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SymbolId(pub usize);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScopeId(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Var,
+    Func,
+    Param,
+    Typedef,
+    EnumConst,
+    Tag(Token), // STRUCT, UNION or ENUM
+    Label,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Linkage {
+    None,
+    Internal, // static at file scope
+    External,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub pos: usize, // position of the first declaration
+    pub scope: ScopeId,
+    pub linkage: Linkage,
+    pub defined: bool,       // has a body, an initializer or a member list
+    pub ty: Type,            // int for labels
+    pub value: Option<i128>, // of an enumeration constant
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    File,
+    Function,  // holds the labels of a function
+    Block,     // the parameters of a definition share the body's scope
+    Prototype, // the parameters of a function declarator
+}
+
+#[derive(Debug)]
+pub struct Scope {
+    pub kind: ScopeKind,
+    pub parent: Option<ScopeId>,
+
+    names: HashMap<String, SymbolId>, // ordinary identifiers
+    tags: HashMap<String, SymbolId>,  // struct, union and enum tags
+    labels: HashMap<String, SymbolId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note, // explains the diagnostic before it
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub pos: usize,
+    pub severity: Severity,
+    pub msg: String,
+}
+
+/// The result of resolving a file.
+#[derive(Debug, Default)]
+pub struct Info {
+    pub symbols: Vec<Symbol>,
+    pub scopes: Vec<Scope>,
+
+    pub decls: HashMap<usize, SymbolId>, // declaring identifiers by position
+    pub uses: HashMap<usize, SymbolId>,  // other identifiers by position
+
+    pub tags: Vec<Tag>,                       // indexed by TagId
+    pub types: HashMap<(usize, usize), Type>, // expression types by start and end
+    pub type_names: HashMap<usize, Type>,     // types of casts, sizeof and offsetof by position
+    pub asserts: HashMap<usize, String>,      // texts of the arguments of assert by callee position
+
+    pub diagnostics: Vec<Diagnostic>, // in source order
+
+    pub layout: DataLayout, // the target the file is checked for
+}
+
+impl Info {
+    pub fn symbol(&self, id: SymbolId) -> &Symbol {
+        &self.symbols[id.0]
+    }
+
+    pub fn scope(&self, id: ScopeId) -> &Scope {
+        &self.scopes[id.0]
+    }
+
+    /// Returns the symbol an identifier at `pos` declares or refers to.
+    pub fn lookup(&self, pos: usize) -> Option<&Symbol> {
+        self.decls
+            .get(&pos)
+            .or_else(|| self.uses.get(&pos))
+            .map(|&id| self.symbol(id))
+    }
+
+    /// Returns the type of a checked expression, before arrays and
+    /// functions decay to pointers.
+    pub fn type_of(&self, x: &dyn Expr) -> Option<&Type> {
+        self.types.get(&(x.start(), x.end()))
+    }
+
+    /// Spells a type, e.g. `struct s *`.
+    pub fn spell(&self, t: &Type) -> String {
+        t.spell(&self.tags)
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+    }
+}
+
+/// Resolves the names of a file.
+pub fn check(file: &File) -> Info {
+    check_with(file, DataLayout::default())
+}
+
+/// Checks a file for the target described by `layout`.
+pub fn check_with(file: &File, layout: DataLayout) -> Info {
+    let mut r = Resolver::default();
+    r.info.layout = layout;
+
+    r.open(ScopeKind::File);
+    for d in file.decls.iter() {
+        r.decl(d.as_ref());
+    }
+    r.close();
+
+    r.finish()
+}
+
+/// Types an expression in the file scope of the declarations before it.
+pub fn check_expr(decls: &[Box<dyn Decl>], x: &dyn Expr, layout: DataLayout) -> Info {
+    let mut r = Resolver::default();
+    r.info.layout = layout;
+
+    r.open(ScopeKind::File);
+    for d in decls.iter() {
+        r.decl(d.as_ref());
+    }
+    r.expr(x);
+    r.close();
+
+    r.finish()
+}
+
+#[derive(Default)]
+struct Resolver {
+    info: Info,
+    stack: Vec<ScopeId>, // the open scopes, innermost last
+
+    linked: HashMap<String, SymbolId>, // every name with linkage
+    func: Option<(String, Type)>,      // the function being checked, and its result type
+
+    switches: Vec<(IntKind, HashMap<i128, usize>)>, // the promoted type and cases of open switches
+}
+
+impl Resolver {
+    // sorts the diagnostics, keeping notes after the diagnostic they
+    // explain, initializers with elided braces may check an expression twice
+    fn finish(mut self) -> Info {
+        let mut owner = 0;
+        let mut keyed: Vec<_> = std::mem::take(&mut self.info.diagnostics)
+            .into_iter()
+            .map(|d| {
+                if d.severity != Severity::Note {
+                    owner = d.pos;
+                }
+                (owner, d)
+            })
+            .collect();
+
+        let mut seen = HashSet::new();
+        keyed.retain(|(_, d)| seen.insert((d.pos, d.msg.clone())));
+        keyed.sort_by_key(|(pos, _)| *pos);
+
+        self.info.diagnostics = keyed.into_iter().map(|(_, d)| d).collect();
+        self.info
+    }
+
+    fn open(&mut self, kind: ScopeKind) {
+        let id = ScopeId(self.info.scopes.len());
+
+        self.info.scopes.push(Scope {
+            kind,
+            parent: self.stack.last().copied(),
+            names: HashMap::new(),
+            tags: HashMap::new(),
+            labels: HashMap::new(),
+        });
+        self.stack.push(id);
+    }
+
+    fn close(&mut self) {
+        self.stack.pop();
+    }
+
+    fn current(&self) -> ScopeId {
+        *self.stack.last().unwrap()
+    }
+
+    fn scope_kind(&self) -> ScopeKind {
+        self.info.scope(self.current()).kind
+    }
+
+    fn error(&mut self, pos: usize, msg: String) {
+        self.info.diagnostics.push(Diagnostic {
+            pos,
+            severity: Severity::Error,
+            msg,
+        });
+    }
+
+    fn warning(&mut self, pos: usize, msg: String) {
+        self.info.diagnostics.push(Diagnostic {
+            pos,
+            severity: Severity::Warning,
+            msg,
+        });
+    }
+
+    fn note(&mut self, pos: usize, msg: String) {
+        self.info.diagnostics.push(Diagnostic {
+            pos,
+            severity: Severity::Note,
+            msg,
+        });
+    }
+
+    fn new_symbol(
+        &mut self,
+        name: &Ident,
+        kind: SymbolKind,
+        linkage: Linkage,
+        defined: bool,
+        ty: Type,
+    ) -> SymbolId {
+        let id = SymbolId(self.info.symbols.len());
+
+        self.info.symbols.push(Symbol {
+            name: name.name.clone(),
+            kind,
+            pos: name.pos,
+            scope: self.current(),
+            linkage,
+            defined,
+            ty,
+            value: None,
+        });
+
+        id
+    }
+
+    // looks an ordinary identifier up from the innermost scope outward
+    fn lookup(&self, name: &str) -> Option<SymbolId> {
+        self.stack
+            .iter()
+            .rev()
+            .find_map(|&s| self.info.scope(s).names.get(name))
+            .copied()
+    }
+
+    fn lookup_tag(&self, name: &str) -> Option<SymbolId> {
+        self.stack
+            .iter()
+            .rev()
+            .find_map(|&s| self.info.scope(s).tags.get(name))
+            .copied()
+    }
+
+    // the innermost function scope, which holds the labels
+    fn function_scope(&self) -> Option<ScopeId> {
+        self.stack
+            .iter()
+            .rev()
+            .find(|&&s| self.info.scope(s).kind == ScopeKind::Function)
+            .copied()
+    }
+
+    /// Declares an ordinary identifier in the current scope.
+    fn declare(
+        &mut self,
+        name: &Ident,
+        kind: SymbolKind,
+        storage: Option<Token>,
+        defined: bool,
+        ty: Type,
+    ) -> SymbolId {
+        let file_scope = self.scope_kind() == ScopeKind::File;
+
+        let linkage = match kind {
+            SymbolKind::Var | SymbolKind::Func if storage == Some(Token::STATIC) && file_scope => {
+                Linkage::Internal
+            }
+            SymbolKind::Func => Linkage::External,
+            SymbolKind::Var if file_scope || storage == Some(Token::EXTERN) => Linkage::External,
+            _ => Linkage::None,
+        };
+
+        let scope = self.current();
+
+        if let Some(&prev) = self.info.scope(scope).names.get(&name.name) {
+            if self.redeclare(prev, name, kind, linkage, storage, defined) {
+                self.merge_type(prev, name, ty);
+            }
+            return prev;
+        }
+
+        // an outer declaration hidden by this one
+        if let Some(outer) = self.lookup(&name.name) {
+            let outer = self.info.symbol(outer);
+            let same_entity = linkage != Linkage::None && outer.linkage != Linkage::None;
+
+            if !file_scope && !same_entity && self.scope_kind() != ScopeKind::Prototype {
+                self.warning(
+                    name.pos,
+                    format!(
+                        "declaration of '{}' shadows a previous declaration",
+                        name.name
+                    ),
+                );
+            }
+        }
+
+        // block scope `extern` declarations refer to the file scope entity
+        let id = match self.linked.get(&name.name) {
+            Some(&id) if linkage != Linkage::None => {
+                let prev = self.info.symbol(id).clone();
+
+                if prev.kind != kind {
+                    self.error(
+                        name.pos,
+                        format!(
+                            "redefinition of '{}' as different kind of symbol",
+                            name.name
+                        ),
+                    );
+                } else {
+                    self.merge_type(id, name, ty);
+                }
+
+                self.info.symbols[id.0].defined |= defined;
+                id
+            }
+
+            _ => {
+                let id = self.new_symbol(name, kind, linkage, defined, ty);
+                if linkage != Linkage::None {
+                    self.linked.insert(name.name.clone(), id);
+                }
+                id
+            }
+        };
+
+        self.info.scopes[scope.0]
+            .names
+            .insert(name.name.clone(), id);
+        self.info.decls.insert(name.pos, id);
+
+        id
+    }
+
+    // handles a second declaration of a name in the same scope, and reports
+    // whether both declare the same entity, whose types must then agree
+    fn redeclare(
+        &mut self,
+        prev: SymbolId,
+        name: &Ident,
+        kind: SymbolKind,
+        linkage: Linkage,
+        storage: Option<Token>,
+        defined: bool,
+    ) -> bool {
+        self.info.decls.insert(name.pos, prev);
+
+        let p = self.info.symbol(prev).clone();
+
+        // a parameter and a local variable are both objects
+        let objects = |k| matches!(k, SymbolKind::Var | SymbolKind::Param);
+
+        if p.kind != kind && !(objects(p.kind) && objects(kind)) {
+            self.error(
+                name.pos,
+                format!(
+                    "redefinition of '{}' as different kind of symbol",
+                    name.name
+                ),
+            );
+            return false;
+        }
+
+        if linkage == Linkage::None || p.linkage == Linkage::None {
+            // declarations without linkage can't be repeated, but typedefs
+            // may be (C11)
+            if kind != SymbolKind::Typedef {
+                self.error(name.pos, format!("redefinition of '{}'", name.name));
+            }
+        } else if p.linkage == Linkage::Internal
+            && linkage == Linkage::External
+            && storage != Some(Token::EXTERN)
+        {
+            // `static int x; extern int x;` keeps the internal linkage
+            self.error(
+                name.pos,
+                format!(
+                    "non-static declaration of '{}' follows static declaration",
+                    name.name
+                ),
+            );
+        } else if p.linkage == Linkage::External && linkage == Linkage::Internal {
+            self.error(
+                name.pos,
+                format!(
+                    "static declaration of '{}' follows non-static declaration",
+                    name.name
+                ),
+            );
+        } else if p.defined && defined {
+            self.error(name.pos, format!("redefinition of '{}'", name.name));
+        }
+
+        self.info.symbols[prev.0].defined |= defined;
+
+        kind == SymbolKind::Typedef || (linkage != Linkage::None && p.linkage != Linkage::None)
+    }
+
+    // completes the type of a symbol from a compatible redeclaration, like
+    // `int a[]; int a[3];` or a prototype after `int f();`
+    fn merge_type(&mut self, id: SymbolId, name: &Ident, ty: Type) {
+        let prev = &self.info.symbol(id).ty;
+
+        if !prev.compatible(&ty) {
+            let msg = if self.info.symbol(id).kind == SymbolKind::Typedef {
+                format!(
+                    "typedef redefinition with different types ('{}' vs '{}')",
+                    self.info.spell(&ty),
+                    self.info.spell(prev)
+                )
+            } else {
+                format!("conflicting types for '{}'", name.name)
+            };
+            self.error(name.pos, msg);
+            return;
+        }
+
+        let more_complete = match (&prev.kind, &ty.kind) {
+            (TypeKind::Array(_, ArrayLen::Incomplete), TypeKind::Array(_, len)) => {
+                *len != ArrayLen::Incomplete
+            }
+            (TypeKind::Function(f), TypeKind::Function(g)) => !f.prototype && g.prototype,
+            _ => false,
+        };
+
+        if more_complete {
+            self.info.symbols[id.0].ty = ty;
+        }
+    }
+
+    // binds an identifier use to its declaration
+    fn resolve(&mut self, name: &Ident) -> Option<SymbolId> {
+        let id = self.lookup(&name.name);
+
+        match id {
+            Some(id) => {
+                self.info.uses.insert(name.pos, id);
+            }
+            None => self.error(
+                name.pos,
+                format!("use of undeclared identifier '{}'", name.name),
+            ),
+        }
+
+        id
+    }
+
+    fn decl(&mut self, d: &dyn Decl) {
+        match d.kind() {
+            DeclKind::GenDecl(d) => self.gen_decl(d),
+            DeclKind::FuncDecl(d) => self.func_decl(d),
+            DeclKind::StaticAssert(s) => self.static_assert(s),
+        }
+    }
+
+    // _Static_assert(x, "msg") fails to compile when x is zero
+    fn static_assert(&mut self, s: &StaticAssert) {
+        if self.constant(s.cond.as_ref(), "static assertion expression") == Some(0) {
+            self.error(
+                s.cond.start(),
+                format!("static assertion failed: {}", s.msg.lit),
+            );
+        }
+    }
+
+    fn gen_decl(&mut self, d: &GenDecl) {
+        let base = self.specs(&d.specs, d.decls.is_empty());
+
+        for init in d.decls.iter() {
+            let mut ty = self.declarator(base.clone(), &init.declarator);
+
+            let kind = if d.specs.storage == Some(Token::TYPEDEF) {
+                SymbolKind::Typedef
+            } else if ty.is_function() {
+                SymbolKind::Func
+            } else {
+                SymbolKind::Var
+            };
+
+            // `extern int x;` and tentative definitions at file scope
+            // don't define, an initializer does
+            let defined = init.init.is_some()
+                || (kind == SymbolKind::Var
+                    && self.scope_kind() != ScopeKind::File
+                    && d.specs.storage != Some(Token::EXTERN));
+
+            let mut id = None;
+
+            if let Some(name) = &init.declarator.name {
+                if init.init.is_some() && kind != SymbolKind::Var {
+                    self.error(name.pos, format!("illegal initializer for '{}'", name.name));
+                }
+
+                id = Some(self.declare(name, kind, d.specs.storage, defined, ty.clone()));
+            }
+
+            if kind == SymbolKind::Var && ty.is_vla() {
+                self.vla_decl(&init.declarator, d.specs.storage);
+            }
+
+            // the scope of a name starts after its declarator, `int x = x;`
+            // refers to itself
+            if let Some(x) = &init.init {
+                ty = self.initializer(&ty, x.as_ref());
+            }
+
+            let (Some(id), Some(name)) = (id, &init.declarator.name) else {
+                continue;
+            };
+
+            // the initializer gives the length of `int a[] = {1, 2};`
+            if init.init.is_some() && kind == SymbolKind::Var {
+                self.merge_type(id, name, ty.clone());
+            }
+
+            if defined && kind == SymbolKind::Var && !ty.is_complete(&self.info.tags) {
+                let msg = if ty.is_array() {
+                    "definition of variable with array type needs an explicit size or an initializer"
+                        .to_string()
+                } else {
+                    format!("variable has incomplete type '{}'", self.info.spell(&ty))
+                };
+                self.error(name.pos, msg);
+            }
+        }
+    }
+
+    // VLAs only have automatic storage
+    fn vla_decl(&mut self, d: &Declarator, storage: Option<Token>) {
+        let msg = if self.scope_kind() == ScopeKind::File {
+            "variable length array declaration not allowed at file scope"
+        } else if storage == Some(Token::STATIC) {
+            "variable length array declaration cannot have 'static' storage duration"
+        } else if storage == Some(Token::EXTERN) {
+            "variable length array declaration cannot have 'extern' linkage"
+        } else {
+            return;
+        };
+
+        self.error(d.name.as_ref().map_or(d.pos, |n| n.pos), msg.to_string());
+    }
+
+    fn func_decl(&mut self, d: &FuncDecl) {
+        let base = self.specs(&d.specs, false);
+
+        self.open(ScopeKind::Function);
+        self.labels(&d.body);
+
+        // the parameters are declared in the scope of the body
+        self.open(ScopeKind::Block);
+
+        // the derivations outside the function, like the pointer in a
+        // function returning a pointer to an array
+        let ret = self.derived(base, d.declarator.derived.get(1..).unwrap_or_default());
+        let ty = match d.declarator.function() {
+            Some(f) => self.func_type(ret, f),
+            None => ret,
+        };
+
+        // the function itself belongs to the file scope
+        if let Some(name) = &d.declarator.name {
+            let inner = self.stack.split_off(self.stack.len() - 2);
+            self.declare(name, SymbolKind::Func, d.specs.storage, true, ty.clone());
+            self.stack.extend(inner);
+        }
+
+        let name = d.declarator.name.as_ref().map_or("", |n| &n.name);
+        let ret = ty.func().map_or_else(Type::default, |f| f.ret.clone());
+        let outer = self.func.replace((name.to_string(), ret));
+
+        for s in d.body.stmts.iter() {
+            self.stmt(s.as_ref());
+        }
+
+        if let Some((name, ret)) = std::mem::replace(&mut self.func, outer) {
+            self.flow(&name, &ret, &d.body);
+        }
+
+        self.close();
+        self.close();
+    }
+
+    // declares the labels of a function body, they are visible before
+    // their statement
+    fn labels(&mut self, body: &BlockStmt) {
+        let mut labels = Vec::new();
+        for s in body.stmts.iter() {
+            collect_labels(s.as_ref(), &mut labels);
+        }
+
+        let scope = self.function_scope().unwrap();
+
+        for label in labels {
+            if self.info.scope(scope).labels.contains_key(&label.name) {
+                self.error(label.pos, format!("redefinition of label '{}'", label.name));
+                continue;
+            }
+
+            let id = self.new_symbol(
+                label,
+                SymbolKind::Label,
+                Linkage::None,
+                true,
+                Type::default(),
+            );
+            self.info.symbols[id.0].scope = scope;
+            self.info.scopes[scope.0]
+                .labels
+                .insert(label.name.clone(), id);
+            self.info.decls.insert(label.pos, id);
+        }
+    }
+
+    // resolves the specifiers and returns the type they name, `alone` is
+    // set when they aren't followed by declarators, like in `struct S;`
+    fn specs(&mut self, specs: &DeclSpec, alone: bool) -> Type {
+        let ty = match &specs.typ {
+            TypeSpec::Basic(toks) => self.basic_type(specs.pos, toks),
+
+            TypeSpec::Typedef(name) => match self.resolve(name) {
+                Some(id) if self.info.symbol(id).kind == SymbolKind::Typedef => {
+                    self.info.symbol(id).ty.clone()
+                }
+                Some(_) => {
+                    self.error(name.pos, format!("'{}' does not name a type", name.name));
+                    Type::default()
+                }
+                None => Type::default(),
+            },
+
+            TypeSpec::Struct(s) => {
+                let id = self.tag(s.tag.as_ref(), s.kw, s.fields.is_some(), alone);
+
+                if let Some(fields) = &s.fields {
+                    let members = self.fields(fields, s.kw);
+                    self.info.tags[id.0].members.get_or_insert(members);
+                }
+
+                self.tag_type(id)
+            }
+
+            TypeSpec::Enum(e) => {
+                let id = self.tag(e.tag.as_ref(), Token::ENUM, e.enumerators.is_some(), alone);
+
+                // an enumerator without a value follows the previous one
+                let mut next = 0;
+
+                for c in e.enumerators.iter().flatten() {
+                    let value = match &c.value {
+                        Some(x) => self.constant(x.as_ref(), "enumerator value"),
+                        None => None,
+                    }
+                    .unwrap_or(next);
+
+                    let int = IntKind::Int;
+                    if value < self.info.layout.min(int)
+                        || value > self.info.layout.max(int) as i128
+                    {
+                        let pos = c.value.as_ref().map_or(c.name.pos, |x| x.start());
+                        let msg = format!(
+                            "ISO C restricts enumerator values to range of 'int' ({} is too large)",
+                            value
+                        );
+                        self.warning(pos, msg);
+                    }
+
+                    let ty = Type::int(int);
+                    let id = self.declare(&c.name, SymbolKind::EnumConst, None, true, ty);
+                    self.info.symbols[id.0].value = Some(value);
+                    next = value + 1;
+                }
+
+                if e.enumerators.is_some() {
+                    self.info.tags[id.0].members.get_or_insert(Vec::new());
+                }
+
+                self.tag_type(id)
+            }
+        };
+
+        ty.qualified(Quals::from_tokens(&specs.quals))
+    }
+
+    // the type named by keywords like `unsigned long int`, in any order
+    fn basic_type(&mut self, pos: usize, toks: &[Token]) -> Type {
+        let n = |t: Token| toks.iter().filter(|&&x| x == t).count();
+
+        let (int, long) = (n(Token::INT), n(Token::LONG));
+        let (sign, unsigned) = (
+            n(Token::SIGNED) + n(Token::UNSIGNED),
+            n(Token::UNSIGNED) > 0,
+        );
+        let other: Vec<_> = toks
+            .iter()
+            .filter(|t| {
+                !matches!(
+                    t,
+                    Token::INT | Token::LONG | Token::SIGNED | Token::UNSIGNED
+                )
+            })
+            .collect();
+
+        let int_kind = |k: IntKind| Some(TypeKind::Int(if unsigned { k.to_unsigned() } else { k }));
+
+        let kind = match (other.as_slice(), long, int, sign) {
+            (_, _, 2.., _) | (_, _, _, 2..) => None,
+
+            ([], 0, _, _) => int_kind(IntKind::Int),
+            ([], 1, _, _) => int_kind(IntKind::Long),
+            ([], 2, _, _) => int_kind(IntKind::LongLong),
+            ([Token::SHORT], 0, _, _) => int_kind(IntKind::Short),
+
+            ([Token::CHAR], 0, 0, 0) => Some(TypeKind::Int(IntKind::Char)),
+            ([Token::CHAR], 0, 0, _) if unsigned => Some(TypeKind::Int(IntKind::UChar)),
+            ([Token::CHAR], 0, 0, _) => Some(TypeKind::Int(IntKind::SChar)),
+
+            ([Token::VOID], 0, 0, 0) => Some(TypeKind::Void),
+            ([Token::BOOL], 0, 0, 0) => Some(TypeKind::Int(IntKind::Bool)),
+            ([Token::FLOAT], 0, 0, 0) => Some(TypeKind::Float(FloatKind::Float)),
+            ([Token::DOUBLE], 0, 0, 0) => Some(TypeKind::Float(FloatKind::Double)),
+            ([Token::DOUBLE], 1, 0, 0) => Some(TypeKind::Float(FloatKind::LongDouble)),
+
+            _ => None,
+        };
+
+        kind.map(Type::from).unwrap_or_else(|| {
+            self.error(pos, "invalid combination of type specifiers".to_string());
+            Type::default()
+        })
+    }
+
+    // types the members of a struct or union body
+    fn fields(&mut self, fields: &[ast::FieldDecl], kw: Token) -> Vec<Member> {
+        let mut members = Vec::new();
+        let mut names: HashMap<&str, usize> = HashMap::new();
+
+        for (i, f) in fields.iter().enumerate() {
+            let base = self.specs(&f.specs, f.decls.is_empty());
+
+            // an anonymous struct or union member (C11)
+            if f.decls.is_empty() {
+                if matches!(&f.specs.typ, TypeSpec::Struct(s) if s.tag.is_none()) {
+                    members.push(Member {
+                        name: None,
+                        ty: base,
+                        bits: None,
+                        pos: f.specs.pos,
+                    });
+                }
+                continue;
+            }
+
+            for (j, d) in f.decls.iter().enumerate() {
+                let ty = self.declarator(base.clone(), &d.declarator);
+                let pos = d.declarator.pos;
+
+                if let Some(name) = &d.declarator.name {
+                    if names.insert(&name.name, name.pos).is_some() {
+                        self.error(name.pos, format!("duplicate member '{}'", name.name));
+                    }
+                }
+
+                let name = d.declarator.name.as_ref();
+                let bits = match &d.bits {
+                    Some(x) => self.bit_width(&ty, x.as_ref(), name, pos),
+                    None => None,
+                };
+
+                // a flexible array member ends a struct
+                let last = i + 1 == fields.len() && j + 1 == f.decls.len();
+                let flexible = matches!(ty.kind, TypeKind::Array(_, ArrayLen::Incomplete))
+                    && last
+                    && kw == Token::STRUCT;
+
+                if !flexible && !ty.is_complete(&self.info.tags) {
+                    let msg = format!("field has incomplete type '{}'", self.info.spell(&ty));
+                    self.error(pos, msg);
+                } else if ty.is_vla() {
+                    self.error(
+                        pos,
+                        "fields must have a constant size: 'variable length array in structure' extension will never be supported".to_string(),
+                    );
+                }
+
+                members.push(Member {
+                    name: d.declarator.name.as_ref().map(|n| n.name.clone()),
+                    ty,
+                    bits,
+                    pos,
+                });
+            }
+        }
+
+        members
+    }
+
+    fn bit_width(
+        &mut self,
+        ty: &Type,
+        x: &dyn Expr,
+        name: Option<&Ident>,
+        pos: usize,
+    ) -> Option<u32> {
+        let bits = self.constant(x, "bit-field width");
+
+        if !ty.is_integer() {
+            let msg = format!("bit-field has non-integral type '{}'", self.info.spell(ty));
+            self.error(pos, msg);
+            return None;
+        }
+
+        let bits = bits?;
+        let width = self.info.layout.width(ty.int_kind().unwrap()) as i128;
+
+        if bits < 0 {
+            let msg = match name {
+                Some(n) => format!("bit-field '{}' has negative width ({})", n.name, bits),
+                None => format!("anonymous bit-field has negative width ({})", bits),
+            };
+            self.error(x.start(), msg);
+            return None;
+        }
+
+        if let Some(n) = name.filter(|_| bits == 0) {
+            self.error(
+                n.pos,
+                format!("named bit-field '{}' has zero width", n.name),
+            );
+            return None;
+        }
+
+        if bits > width {
+            self.error(
+                x.start(),
+                format!(
+                    "width of bit-field ({} bits) exceeds the width of its type ({} bits)",
+                    bits, width
+                ),
+            );
+            return None;
+        }
+
+        Some(bits as u32)
+    }
+
+    // resolves or declares a struct, union or enum tag, a tag without a
+    // name declares a new type
+    fn tag(&mut self, tag: Option<&Ident>, kw: Token, body: bool, alone: bool) -> TagId {
+        let Some(tag) = tag else {
+            return self.new_tag(kw, None);
+        };
+
+        let scope = self.current();
+
+        // a reference finds the tag in an enclosing scope, a body or a
+        // declaration of its own declares it here
+        let prev = if body || alone {
+            self.info.scope(scope).tags.get(&tag.name).copied()
+        } else {
+            self.lookup_tag(&tag.name)
+        };
+
+        let Some(prev) = prev else {
+            let id = self.new_tag(kw, Some(tag));
+            let ty = self.tag_type(id);
+
+            let sym = self.new_symbol(tag, SymbolKind::Tag(kw), Linkage::None, body, ty);
+            self.info.scopes[scope.0].tags.insert(tag.name.clone(), sym);
+            self.info.decls.insert(tag.pos, sym);
+            return id;
+        };
+
+        let p = self.info.symbol(prev).clone();
+
+        if p.kind != SymbolKind::Tag(kw) {
+            self.error(
+                tag.pos,
+                format!(
+                    "use of '{}' with tag type that does not match previous declaration",
+                    tag.name
+                ),
+            );
+        } else if p.defined && body {
+            self.error(
+                tag.pos,
+                format!("redefinition of '{} {}'", kw.to_str(), tag.name),
+            );
+        }
+
+        self.info.symbols[prev.0].defined |= body;
+
+        if body {
+            self.info.decls.insert(tag.pos, prev);
+        } else {
+            self.info.uses.insert(tag.pos, prev);
+        }
+
+        match p.ty.kind {
+            TypeKind::Struct(id) | TypeKind::Union(id) | TypeKind::Enum(id) => id,
+            _ => unreachable!(),
+        }
+    }
+
+    fn new_tag(&mut self, kind: Token, name: Option<&Ident>) -> TagId {
+        let id = TagId(self.info.tags.len());
+
+        self.info.tags.push(Tag {
+            kind,
+            name: name.map(|n| n.name.clone()),
+            members: None,
+        });
+
+        id
+    }
+
+    fn tag_type(&self, id: TagId) -> Type {
+        match self.info.tags[id.0].kind {
+            Token::STRUCT => TypeKind::Struct(id),
+            Token::UNION => TypeKind::Union(id),
+            _ => TypeKind::Enum(id),
+        }
+        .into()
+    }
+
+    // applies the derivations of a declarator to the type of its
+    // specifiers
+    fn declarator(&mut self, base: Type, d: &Declarator) -> Type {
+        self.derived(base, &d.derived)
+    }
+
+    // `derived` lists the derivations from the name outward, so the type
+    // is built from the last one in
+    fn derived(&mut self, base: Type, derived: &[Derived]) -> Type {
+        let mut ty = base;
+
+        for d in derived.iter().rev() {
+            ty = match d {
+                Derived::Pointer { quals, .. } => {
+                    ty.pointer_to().qualified(Quals::from_tokens(quals))
+                }
+
+                Derived::Array { lbrack, len, .. } => {
+                    if !ty.is_complete(&self.info.tags) {
+                        let msg = format!(
+                            "array has incomplete element type '{}'",
+                            self.info.spell(&ty)
+                        );
+                        self.error(*lbrack, msg);
+                    }
+
+                    let len = match len {
+                        Some(x) => self.array_len(x.as_ref()),
+                        None => ArrayLen::Incomplete,
+                    };
+
+                    ty.array_of(len)
+                }
+
+                // the parameters of a function declarator get a prototype
+                // scope of their own
+                Derived::Function(f) => {
+                    self.open(ScopeKind::Prototype);
+                    let ty = self.func_type(ty, f);
+                    self.close();
+                    ty
+                }
+            };
+        }
+
+        ty
+    }
+
+    // the length of an array is a constant or the array is a VLA
+    fn array_len(&mut self, x: &dyn Expr) -> ArrayLen {
+        self.integer(x, "size of array");
+
+        match self.try_constant(x) {
+            Some(n) if n < 0 => {
+                self.error(x.start(), "array size is negative".to_string());
+                ArrayLen::Fixed(0)
+            }
+            Some(n) => ArrayLen::Fixed(n as u64),
+            None => ArrayLen::Variable,
+        }
+    }
+
+    // builds a function type, declaring the named parameters in the
+    // current scope
+    fn func_type(&mut self, ret: Type, f: &ast::FuncType) -> Type {
+        if ret.is_array() || ret.is_function() {
+            let what = if ret.is_array() { "array" } else { "function" };
+            let msg = format!(
+                "function cannot return {} type '{}'",
+                what,
+                self.info.spell(&ret)
+            );
+            self.error(f.lparen, msg);
+        }
+
+        let mut params = Vec::new();
+
+        for p in f.params.iter() {
+            let base = self.specs(&p.specs, false);
+            let mut ty = self.declarator(base, &p.declarator);
+
+            // array and function parameters are adjusted to pointers
+            if ty.is_array() || ty.is_function() {
+                ty = ty.decay();
+            }
+
+            if let Some(name) = &p.declarator.name {
+                self.declare(name, SymbolKind::Param, None, true, ty.clone());
+            }
+
+            params.push((
+                ty,
+                p.declarator.name.is_some() || !p.declarator.derived.is_empty(),
+            ));
+        }
+
+        let prototype = !params.is_empty() || f.variadic;
+
+        // `(void)` declares no parameters
+        if let [(ty, false)] = params.as_slice() {
+            if *ty == Type::void() && !f.variadic {
+                params.clear();
+            }
+        }
+
+        if params.iter().any(|(ty, _)| ty.is_void()) {
+            self.error(
+                f.lparen,
+                "'void' must be the first and only parameter if specified".to_string(),
+            );
+        }
+
+        TypeKind::Function(Box::new(types::FuncType {
+            ret,
+            prototype,
+            params: params.into_iter().map(|(ty, _)| ty).collect(),
+            variadic: f.variadic,
+        }))
+        .into()
+    }
+
+    // records the value of a case label, converted to the type of the
+    // switch, and reports duplicates
+    fn case_value(&mut self, v: i128, pos: usize) {
+        let Some((kind, cases)) = self.switches.last_mut() else {
+            return;
+        };
+
+        let v = self.info.layout.wrap(v, *kind);
+        if let Some(&prev) = cases.get(&v) {
+            self.error(pos, format!("duplicate case value '{}'", v));
+            self.note(prev, "previous case defined here".to_string());
+        } else {
+            cases.insert(v, pos);
+        }
+    }
+
+    fn type_name(&mut self, typ: &TypeName) -> Type {
+        let base = self.specs(&typ.specs, false);
+        let ty = self.declarator(base, &typ.declarator);
+
+        self.info.type_names.insert(typ.specs.pos, ty.clone());
+        ty
+    }
+
+    fn stmt(&mut self, s: &dyn Stmt) {
+        match s.kind() {
+            StmtKind::SemiColonStmt(_) | StmtKind::BreakStmt(_) | StmtKind::ContinueStmt(_) => {}
+
+            StmtKind::ExprStmt(s) => {
+                self.expr(s.x.as_ref());
+            }
+
+            StmtKind::DeclStmt(s) => self.gen_decl(&s.decl),
+            StmtKind::StaticAssert(s) => self.static_assert(s),
+            StmtKind::ReturnStmt(s) => self.return_stmt(s.pos, s.value.as_deref()),
+
+            StmtKind::IfStmt(s) => {
+                self.condition(s.cond.as_ref(), false);
+                self.stmt(s.init.as_ref());
+
+                for elif in s.elifs.iter() {
+                    self.condition(elif.cond.as_ref(), false);
+                    self.stmt(elif.init.as_ref());
+                }
+
+                if let Some(_else) = &s._else {
+                    self.stmt(_else.init.as_ref());
+                }
+            }
+
+            StmtKind::BlockStmt(s) => {
+                self.open(ScopeKind::Block);
+                for s in s.stmts.iter() {
+                    self.stmt(s.as_ref());
+                }
+                self.close();
+            }
+
+            StmtKind::WhileStmt(s) => {
+                self.condition(s.cond.as_ref(), false);
+                self.stmt(s.init.as_ref());
+            }
+
+            StmtKind::DowhileStmt(s) => {
+                self.stmt(s.init.as_ref());
+                self.condition(s.cond.as_ref(), false);
+            }
+
+            // a declaration in the first clause is scoped to the loop
+            StmtKind::ForStmt(s) => {
+                self.open(ScopeKind::Block);
+
+                if let Some(init) = &s.init {
+                    self.stmt(init.as_ref());
+                }
+                if let Some(x) = &s.cond {
+                    self.condition(x.as_ref(), false);
+                }
+                if let Some(x) = &s.post {
+                    self.expr(x.as_ref());
+                }
+                self.stmt(s.body.as_ref());
+
+                self.close();
+            }
+
+            StmtKind::SwitchStmt(s) => {
+                self.condition(s.tag.as_ref(), true);
+
+                let kind = self
+                    .info
+                    .type_of(s.tag.as_ref())
+                    .map(|t| t.promote().int_kind());
+                self.switches
+                    .push((kind.flatten().unwrap_or(IntKind::Int), HashMap::new()));
+                self.stmt(s.body.as_ref());
+                self.switches.pop();
+            }
+
+            StmtKind::CaseStmt(s) => {
+                if let Some(x) = &s.value {
+                    if let Some(v) = self.constant(x.as_ref(), "case value") {
+                        self.case_value(v, x.start());
+                    }
+                }
+                self.stmt(s.stmt.as_ref());
+            }
+
+            StmtKind::LabeledStmt(s) => self.stmt(s.stmt.as_ref()),
+
+            StmtKind::GotoStmt(s) => {
+                let label = self
+                    .function_scope()
+                    .and_then(|f| self.info.scope(f).labels.get(&s.label.name))
+                    .copied();
+
+                match label {
+                    Some(id) => {
+                        self.info.uses.insert(s.label.pos, id);
+                    }
+                    None => self.error(
+                        s.label.pos,
+                        format!("use of undeclared label '{}'", s.label.name),
+                    ),
+                }
+            }
+        }
+    }
+}
+
+fn collect_labels<'a>(s: &'a dyn Stmt, labels: &mut Vec<&'a Ident>) {
+    match s.kind() {
+        StmtKind::LabeledStmt(s) => {
+            labels.push(&s.label);
+            collect_labels(s.stmt.as_ref(), labels);
+        }
+
+        StmtKind::BlockStmt(s) => {
+            for s in s.stmts.iter() {
+                collect_labels(s.as_ref(), labels);
+            }
+        }
+
+        StmtKind::IfStmt(s) => {
+            collect_labels(s.init.as_ref(), labels);
+            for elif in s.elifs.iter() {
+                collect_labels(elif.init.as_ref(), labels);
+            }
+            if let Some(_else) = &s._else {
+                collect_labels(_else.init.as_ref(), labels);
+            }
+        }
+
+        StmtKind::WhileStmt(s) => collect_labels(s.init.as_ref(), labels),
+        StmtKind::DowhileStmt(s) => collect_labels(s.init.as_ref(), labels),
+        StmtKind::ForStmt(s) => collect_labels(s.body.as_ref(), labels),
+        StmtKind::SwitchStmt(s) => collect_labels(s.body.as_ref(), labels),
+        StmtKind::CaseStmt(s) => collect_labels(s.stmt.as_ref(), labels),
+
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_src(src: &str) -> (Info, parser::Parser) {
+        let mut p = parser::Parser::from(src.to_string());
+        let file = p.parse_file();
+
+        assert!(p.errors().is_empty(), "{:?}", p.errors());
+
+        (check(&file), p)
+    }
+
+    // formats the diagnostics as `line:col: severity: msg`
+    fn diagnostics(src: &str) -> Vec<String> {
+        let (info, p) = check_src(src);
+
+        info.diagnostics
+            .iter()
+            .map(|d| {
+                let pos = p.position(d.pos);
+                let severity = match d.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                    Severity::Note => "note",
+                };
+                format!("{}:{}: {}: {}", pos.line, pos.column, severity, d.msg)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve() {
+        let src = "\
+int x;
+int f(int x) {
+    { int x = x; return x; }
+    return x;
+}
+int g(void) { return x + f(1); }
+";
+        let (info, _) = check_src(src);
+        assert_eq!(0, info.errors().count(), "{:?}", info.diagnostics);
+
+        // the position of each use, and of the declaration it refers to
+        let at = |line: usize, col: usize| {
+            src.lines()
+                .take(line - 1)
+                .map(|l| l.len() + 1)
+                .sum::<usize>()
+                + col
+                - 1
+        };
+
+        let tests = [
+            ((3, 15), (3, 11)), // `int x = x` is in the scope of the new x
+            ((3, 25), (3, 11)),
+            ((4, 12), (2, 11)), // the parameter
+            ((6, 22), (1, 5)),  // the global
+            ((6, 26), (2, 5)),
+        ];
+
+        for (i, (use_, decl)) in tests.iter().enumerate() {
+            let sym = info.lookup(at(use_.0, use_.1)).unwrap();
+
+            assert_eq!(
+                at(decl.0, decl.1),
+                sym.pos,
+                "[{}/{}] test case failed.",
+                i + 1,
+                tests.len()
+            );
+        }
+
+        assert_eq!(Linkage::External, info.lookup(at(1, 5)).unwrap().linkage);
+        assert_eq!(SymbolKind::Param, info.lookup(at(2, 11)).unwrap().kind);
+        assert_eq!(Linkage::None, info.lookup(at(3, 11)).unwrap().linkage);
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let src = "\
+struct s { int s; } s;
+static int count;
+int main(void) {
+    struct s *p;
+    goto s;
+s:
+    extern int count;
+    return count + p->s;
+}
+";
+        let (info, _) = check_src(src);
+        assert!(info.diagnostics.is_empty(), "{:?}", info.diagnostics);
+
+        let find = |pat: &str, n: usize| src.match_indices(pat).nth(n).unwrap().0;
+
+        let tag = info.lookup(find("s", 1)).unwrap();
+        assert_eq!(SymbolKind::Tag(Token::STRUCT), tag.kind);
+        assert_eq!(tag.pos, info.lookup(find("struct s", 1) + 7).unwrap().pos);
+
+        let var = info.lookup(find("s;", 1)).unwrap();
+        assert_eq!(SymbolKind::Var, var.kind);
+
+        let label = info.lookup(find("goto s", 0) + 5).unwrap();
+        assert_eq!(SymbolKind::Label, label.kind);
+        assert_eq!(find("s:", 0), label.pos);
+
+        // the block scope extern refers to the static at file scope
+        let count = info.lookup(find("count +", 0)).unwrap();
+        assert_eq!(find("count", 0), count.pos);
+        assert_eq!(Linkage::Internal, count.linkage);
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let tests = [
+            ("int f(void) { return y; }", vec!["1:22: error: use of undeclared identifier 'y'"]),
+            (
+                "int x = 1; int x = 2; int x; extern int x;",
+                vec!["1:16: error: redefinition of 'x'"],
+            ),
+            (
+                "static int x; int x;\nint y; static int y;",
+                vec![
+                    "1:19: error: non-static declaration of 'x' follows static declaration",
+                    "2:19: error: static declaration of 'y' follows non-static declaration",
+                ],
+            ),
+            (
+                "int f(void); int f(void) { return 0; } int f(void) { return 1; }",
+                vec!["1:44: error: redefinition of 'f'"],
+            ),
+            (
+                "typedef int T; int T;",
+                vec!["1:20: error: redefinition of 'T' as different kind of symbol"],
+            ),
+            (
+                "int f(int a, int a) { int b; int b; return 0; }",
+                vec![
+                    "1:18: error: redefinition of 'a'",
+                    "1:34: error: redefinition of 'b'",
+                ],
+            ),
+            (
+                "int f(int x) { int x = 1; return x; }",
+                vec!["1:20: error: redefinition of 'x'"],
+            ),
+            (
+                "int x; int f(int x) { { int x; } return 0; }",
+                vec![
+                    "1:18: warning: declaration of 'x' shadows a previous declaration",
+                    "1:29: warning: declaration of 'x' shadows a previous declaration",
+                ],
+            ),
+            (
+                "struct s { int a; int a; }; struct s { int b; }; union s *p;",
+                vec![
+                    "1:23: error: duplicate member 'a'",
+                    "1:36: error: redefinition of 'struct s'",
+                    "1:56: error: use of 's' with tag type that does not match previous declaration",
+                ],
+            ),
+            (
+                "int f(void) { a: goto b; a: return 0; }",
+                vec![
+                    "1:23: error: use of undeclared label 'b'",
+                    "1:26: error: redefinition of label 'a'",
+                ],
+            ),
+            (
+                "int f(void) { return g(1); }",
+                vec!["1:22: warning: implicit declaration of function 'g'"],
+            ),
+            ("enum e { A, B = A + 1 }; int f(void) { return B; }", vec![]),
+            ("int f(int n, int a[n]); int n;", vec![]),
+        ];
+
+        for (i, (src, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                *want,
+                diagnostics(src),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn test_types() {
+        // types an expression statement after some declarations
+        let type_of = |decls: &str, x: &str| {
+            let src = format!("{}\nvoid f_(void) {{ {}; }}", decls, x);
+            let (info, _) = check_src(&src);
+            assert_eq!(0, info.errors().count(), "{:?}", info.diagnostics);
+
+            let start = src.rfind(x).unwrap();
+            let t = info.types.get(&(start, start + x.len())).unwrap();
+            info.spell(t)
+        };
+
+        let tests = [
+            ("char c;", "c + 1", "int"),
+            ("unsigned u; long l;", "u + l", "long"),
+            ("unsigned u;", "u + 1", "unsigned int"),
+            ("", "1.5f * 2", "float"),
+            ("", "4000000000", "long"),
+            ("", "0xffffffff", "unsigned int"),
+            ("", "10ul", "unsigned long"),
+            ("", "\"abc\"", "char [4]"),
+            ("", "L\"ab\" \"\\x41\"", "int [4]"),
+            ("", "'a'", "int"),
+            ("int a[3];", "a", "int [3]"),
+            ("int a[3];", "&a", "int (*)[3]"),
+            ("int a[3];", "a + 1", "int *"),
+            ("int *p, *q;", "p - q", "long"),
+            ("const char *s;", "*s", "const char"),
+            ("struct s { int x; const int y; } *p;", "p->y", "const int"),
+            ("int f(int, char *);", "f", "int (int, char *)"),
+            ("int f(int, char *);", "&f", "int (*)(int, char *)"),
+            ("int f(int, char *);", "f(1, 0)", "int"),
+            ("int x; double d;", "x ? d : 1", "double"),
+            ("int *p; const void *v;", "1 ? p : v", "const void *"),
+            ("short s;", "sizeof s", "unsigned long"),
+            ("short s;", "s <<= 2", "short"),
+            ("short s;", "-s", "int"),
+            ("", "(int []){1, 2, 3}", "int [3]"),
+            ("int i;", "(char)i", "char"),
+            ("enum e { A } v;", "v", "enum e"),
+            ("int x;", "x = 1, 2.0", "double"),
+            ("typedef int T[2];", "(T *)0", "int (*)[2]"),
+            ("int (*fp[2])(void);", "fp[0]()", "int"),
+            ("", "_Alignof(double)", "unsigned long"),
+            (
+                "int *p, *q;",
+                "offsetof(struct { int a[2]; }, a[1]) + (p - q)",
+                "unsigned long",
+            ),
+            ("", "L'a'", "int"),
+            ("_Bool b; int x;", "b = x", "_Bool"),
+            ("_Bool b;", "b + 1", "int"),
+        ];
+
+        for (i, (decls, x, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                *want,
+                type_of(decls, x),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                x
+            );
+        }
+    }
+
+    #[test]
+    fn test_type_errors() {
+        let tests = [
+            (
+                "int f(void) { int *p; p = 1; return 0; }",
+                vec!["1:27: warning: incompatible integer to pointer conversion assigning to 'int *' from 'int'"],
+            ),
+            (
+                "struct s { int a; }; int f(struct s v) { return v; }",
+                vec!["1:49: error: returning 'struct s' from a function with incompatible result type 'int'"],
+            ),
+            (
+                "int f(int a, int b); int g(void) { return f(1); }",
+                vec!["1:46: error: too few arguments to function call, expected 2, have 1"],
+            ),
+            (
+                "int g(void) { const int c = 1; c = 2; return c; }",
+                vec!["1:32: error: cannot assign to variable 'c' with const-qualified type 'const int'"],
+            ),
+            (
+                "int g(void) { int a[2], b[2]; a = b; 1 = 2; return 0; }",
+                vec![
+                    "1:31: error: array type 'int [2]' is not assignable",
+                    "1:38: error: expression is not assignable",
+                ],
+            ),
+            (
+                "struct s { int a; }; int g(struct s *p) { return p.a + p->b; }",
+                vec![
+                    "1:51: error: member reference base type 'struct s *' is not a structure or union",
+                    "1:59: error: no member named 'b' in 'struct s'",
+                ],
+            ),
+            (
+                "int g(int x) { return *x; }",
+                vec!["1:23: error: indirection requires pointer operand ('int' invalid)"],
+            ),
+            (
+                "void v(void); int g(void) { return v() + 1; }",
+                vec!["1:40: error: invalid operands to binary expression ('void' and 'int')"],
+            ),
+            (
+                "int g(const char *s) { char *t = s; return 0; }",
+                vec!["1:34: warning: initializing 'char *' with an expression of type 'const char *' discards qualifiers"],
+            ),
+            (
+                "void g(void) { return 1; } int h(void) { return; }",
+                vec![
+                    "1:16: error: void function 'g' should not return a value",
+                    "1:42: error: non-void function 'h' should return a value",
+                ],
+            ),
+            (
+                "int a[2] = {1, 2, 3}; char s[2] = \"abc\"; struct p { int x; } q = {.z = 1};",
+                vec![
+                    "1:19: warning: excess elements in array initializer",
+                    "1:35: warning: initializer-string for char array is too long",
+                    "1:68: error: field designator 'z' does not refer to any field in type 'struct p'",
+                ],
+            ),
+            (
+                "struct s; int f(struct s *p) { struct s v; return sizeof *p; }",
+                vec![
+                    "1:41: error: variable has incomplete type 'struct s'",
+                    "1:51: error: invalid application of 'sizeof' to an incomplete type 'struct s'",
+                ],
+            ),
+            (
+                "int g(int *p, double d) { if (d) {} switch (d) {} return p[d]; }",
+                vec![
+                    "1:45: error: statement requires expression of integer type ('double' invalid)",
+                    "1:60: error: array subscript is not an integer",
+                ],
+            ),
+            ("int f(int); long f(int);", vec!["1:18: error: conflicting types for 'f'"]),
+            (
+                "int g(int *p, long *q) { return p == q; }",
+                vec!["1:35: warning: comparison of distinct pointer types ('int *' and 'long *')"],
+            ),
+            (
+                "int m[2][2] = {1, 2, 3, 4}; struct { int a[2]; int b; } s = {1, 2, 3};\nint n[] = {[4] = 1}; int x = sizeof n / sizeof n[0];",
+                vec![],
+            ),
+            (
+                "struct s; struct t { int a : 3; double d[2]; };\nunsigned long g(void) { return _Alignof(struct s) + offsetof(struct t, a) + offsetof(struct t, d[0].x) + offsetof(int, a); }",
+                vec![
+                    "2:32: error: invalid application of '_Alignof' to an incomplete type 'struct s'",
+                    "2:72: error: cannot compute offset of bit-field 'a'",
+                    "2:101: error: offsetof requires struct, union, or class type, 'double' invalid",
+                    "2:120: error: offsetof requires struct, union, or class type, 'int' invalid",
+                ],
+            ),
+        ];
+
+        for (i, (src, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                *want,
+                diagnostics(src),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn test_asserts() {
+        let src = "void assert(int);\nint f(int x) { assert(x*(x+1)<5&&\"small\"); assert((x)); return x; }";
+        let (info, p) = check_src(src);
+        assert_eq!(0, info.errors().count(), "{:?}", info.diagnostics);
+
+        let mut asserts: Vec<_> = info
+            .asserts
+            .iter()
+            .map(|(&pos, text)| (p.position(pos).column, text.as_str()))
+            .collect();
+        asserts.sort();
+        assert_eq!(
+            vec![(16, "x * (x + 1) < 5 && \"small\""), (44, "(x)")],
+            asserts
+        );
+    }
+
+    #[test]
+    fn test_flow() {
+        let tests = [
+            (
+                "int main() { break;\n\n return 0; }",
+                vec!["1:14: error: 'break' statement not in loop or switch statement"],
+            ),
+            (
+                "void f(int x) { while (x) { if (x) continue; break; } continue; case 1: default: ; }",
+                vec![
+                    "1:55: error: 'continue' statement not in loop statement",
+                    "1:65: error: 'case' statement not in switch statement",
+                    "1:73: error: 'default' statement not in switch statement",
+                ],
+            ),
+            (
+                "void f(int x) { switch (x) { default: break; case 1: default: break; } }",
+                vec![
+                    "1:54: error: multiple default labels in one switch",
+                    "1:30: note: previous case defined here",
+                ],
+            ),
+            (
+                "int f(int x) { if (x) return 1; }\nint g(int x) { x++; }\nint h(int x) { for (;;) if (x) return 1; }\nint k(int x) { while (1) { if (x) break; } }",
+                vec![
+                    "1:33: warning: non-void function does not return a value in all control paths",
+                    "2:21: warning: non-void function does not return a value",
+                    "4:44: warning: non-void function does not return a value",
+                ],
+            ),
+            (
+                "void exit(int);\nint f(int x) { if (x) return 1; else exit(1); }\nint g(int x) { switch (x) { case 1: return 1; default: return 0; } }",
+                vec![],
+            ),
+            (
+                "int f(int x) { return x; x++; }\nvoid g(int x) { while (x) { break; x--; x++; } goto end; x = 1; end: ; }\nvoid h(int x) { switch (x) { x++; case 1: ; } do { continue; } while (0); }",
+                vec![
+                    "1:26: warning: code will never be executed",
+                    "2:36: warning: code will never be executed",
+                    "2:58: warning: code will never be executed",
+                    "3:30: warning: code will never be executed",
+                ],
+            ),
+            (
+                "void f(int n) { goto l; int a[n]; l: a[0] = 1; }\nvoid g(int n) { int a[n]; goto l; l: a[0] = 1; }\nvoid h(int n) { switch (n) { case 0: ; int a[n]; case 1: a[0] = 1; } }",
+                vec![
+                    "1:17: error: cannot jump from this goto statement to its label",
+                    "1:29: note: jump bypasses initialization of variable length array",
+                    "1:25: warning: code will never be executed",
+                    "3:50: error: cannot jump from switch statement to this case label",
+                    "3:44: note: jump bypasses initialization of variable length array",
+                ],
+            ),
+        ];
+
+        for (i, (src, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                *want,
+                diagnostics(src),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn test_constants() {
+        let tests = [
+            (
+                "int f(int x) { switch (x) { case 1: case 2 - 1: case 'a': break; case x: break; } return 0; }",
+                vec![
+                    "1:42: error: duplicate case value '1'",
+                    "1:34: note: previous case defined here",
+                    "1:71: error: expression is not an integer constant expression",
+                    "1:71: note: read of non-const variable 'x' is not allowed in a constant expression",
+                ],
+            ),
+            (
+                "enum e { A = 2147483647, B };\nstruct s { int a : -1; int b : 0; int : 0; int c : 1 << 6; };",
+                vec![
+                    "1:26: warning: ISO C restricts enumerator values to range of 'int' (2147483648 is too large)",
+                    "2:20: error: bit-field 'a' has negative width (-1)",
+                    "2:28: error: named bit-field 'b' has zero width",
+                    "2:52: error: width of bit-field (64 bits) exceeds the width of its type (32 bits)",
+                ],
+            ),
+            (
+                "int n = 3;\nint a[n]; int b[1 - 2]; char c[(1 << 31) > 0 ? 1 : 2];\nvoid f(void) { static int d[n]; int e[n] = {1}; struct { int x[n]; } s; }",
+                vec![
+                    "2:5: error: variable length array declaration not allowed at file scope",
+                    "2:17: error: array size is negative",
+                    "2:35: warning: overflow in expression; result is -2147483648 with type 'int'",
+                    "3:27: error: variable length array declaration cannot have 'static' storage duration",
+                    "3:44: error: variable-sized object may not be initialized",
+                    "3:62: error: fields must have a constant size: 'variable length array in structure' extension will never be supported",
+                ],
+            ),
+            (
+                "int a[4] = {[1 + 1] = 1, [-1] = 2}; enum { N = sizeof(a) / sizeof a[0] };\nint *p = (void *)(N - 4), *q = 1 - 1, b[N];",
+                vec!["1:27: error: array designator value '-1' is negative"],
+            ),
+            (
+                "_Static_assert(sizeof(int) == 4, \"int\");\n_Static_assert(sizeof(long) < 8, \"long\");\nint f(int x) { _Static_assert(1 - 1, \"zero\"); _Static_assert(x, \"x\"); return x; }",
+                vec![
+                    "2:16: error: static assertion failed: \"long\"",
+                    "3:31: error: static assertion failed: \"zero\"",
+                    "3:62: error: expression is not an integer constant expression",
+                    "3:62: note: read of non-const variable 'x' is not allowed in a constant expression",
+                ],
+            ),
+        ];
+
+        for (i, (src, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                *want,
+                diagnostics(src),
+                "[{}/{}] test case failed: {}",
+                i + 1,
+                tests.len(),
+                src
+            );
+        }
+    }
+}
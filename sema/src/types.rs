@@ -0,0 +1,608 @@
+//! The C type model: qualified object, function and incomplete types, and
+//! the conversions between them.
+
+use crate::layout::DataLayout;
+use token::Token;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Quals {
+    pub is_const: bool,
+    pub is_volatile: bool,
+    pub is_restrict: bool,
+}
+
+impl Quals {
+    pub fn from_tokens(toks: &[Token]) -> Self {
+        let mut q = Self::default();
+
+        for t in toks.iter() {
+            match t {
+                Token::CONST => q.is_const = true,
+                Token::VOLATILE => q.is_volatile = true,
+                Token::RESTRICT => q.is_restrict = true,
+                _ => {}
+            }
+        }
+
+        q
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            is_const: self.is_const || other.is_const,
+            is_volatile: self.is_volatile || other.is_volatile,
+            is_restrict: self.is_restrict || other.is_restrict,
+        }
+    }
+
+    /// Reports whether every qualifier of `other` is in `self`.
+    pub fn contains(self, other: Self) -> bool {
+        self.union(other) == self
+    }
+
+    pub fn is_empty(self) -> bool {
+        self == Self::default()
+    }
+
+    fn words(self) -> Vec<&'static str> {
+        let mut words = Vec::new();
+
+        if self.is_const {
+            words.push("const");
+        }
+        if self.is_volatile {
+            words.push("volatile");
+        }
+        if self.is_restrict {
+            words.push("restrict");
+        }
+
+        words
+    }
+}
+
+/// The integer types in increasing conversion rank.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum IntKind {
+    Bool,
+    Char,
+    SChar,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Long,
+    ULong,
+    LongLong,
+    ULongLong,
+}
+
+impl IntKind {
+    pub fn rank(self) -> u8 {
+        match self {
+            IntKind::Bool => 0,
+            IntKind::Char | IntKind::SChar | IntKind::UChar => 1,
+            IntKind::Short | IntKind::UShort => 2,
+            IntKind::Int | IntKind::UInt => 3,
+            IntKind::Long | IntKind::ULong => 4,
+            IntKind::LongLong | IntKind::ULongLong => 5,
+        }
+    }
+
+    pub fn to_unsigned(self) -> Self {
+        match self {
+            IntKind::Char | IntKind::SChar => IntKind::UChar,
+            IntKind::Short => IntKind::UShort,
+            IntKind::Int => IntKind::UInt,
+            IntKind::Long => IntKind::ULong,
+            IntKind::LongLong => IntKind::ULongLong,
+            k => k,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            IntKind::Bool => "_Bool",
+            IntKind::Char => "char",
+            IntKind::SChar => "signed char",
+            IntKind::UChar => "unsigned char",
+            IntKind::Short => "short",
+            IntKind::UShort => "unsigned short",
+            IntKind::Int => "int",
+            IntKind::UInt => "unsigned int",
+            IntKind::Long => "long",
+            IntKind::ULong => "unsigned long",
+            IntKind::LongLong => "long long",
+            IntKind::ULongLong => "unsigned long long",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FloatKind {
+    Float,
+    Double,
+    LongDouble,
+}
+
+impl FloatKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            FloatKind::Float => "float",
+            FloatKind::Double => "double",
+            FloatKind::LongDouble => "long double",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArrayLen {
+    Fixed(u64),
+    Incomplete, // `int a[]`
+    Variable,   // a VLA, its length is only known at run time
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncType {
+    pub ret: Type,
+    pub params: Vec<Type>,
+    pub variadic: bool,
+    pub prototype: bool, // false for `int f()`
+}
+
+/// Indexes the struct, union and enum types of a file, see `Info::tags`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TagId(pub usize);
+
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub kind: Token, // STRUCT, UNION or ENUM
+    pub name: Option<String>,
+    pub members: Option<Vec<Member>>, // None while incomplete
+}
+
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub name: Option<String>, // None for anonymous members and padding
+    pub ty: Type,
+    pub bits: Option<u32>, // width of a bit-field
+    pub pos: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeKind {
+    Void,
+    Int(IntKind),
+    Float(FloatKind),
+    Pointer(Box<Type>),
+    Array(Box<Type>, ArrayLen),
+    Function(Box<FuncType>),
+    Struct(TagId),
+    Union(TagId),
+    Enum(TagId),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Type {
+    pub kind: TypeKind,
+    pub quals: Quals,
+}
+
+impl Default for Type {
+    fn default() -> Self {
+        Self::int(IntKind::Int)
+    }
+}
+
+impl From<TypeKind> for Type {
+    fn from(kind: TypeKind) -> Self {
+        Self {
+            kind,
+            quals: Quals::default(),
+        }
+    }
+}
+
+impl Type {
+    pub fn void() -> Self {
+        TypeKind::Void.into()
+    }
+
+    pub fn int(kind: IntKind) -> Self {
+        TypeKind::Int(kind).into()
+    }
+
+    pub fn float(kind: FloatKind) -> Self {
+        TypeKind::Float(kind).into()
+    }
+
+    pub fn pointer_to(self) -> Self {
+        TypeKind::Pointer(Box::new(self)).into()
+    }
+
+    pub fn array_of(self, len: ArrayLen) -> Self {
+        TypeKind::Array(Box::new(self), len).into()
+    }
+
+    pub fn qualified(mut self, quals: Quals) -> Self {
+        self.quals = self.quals.union(quals);
+        self
+    }
+
+    pub fn unqualified(&self) -> Self {
+        Self {
+            kind: self.kind.clone(),
+            quals: Quals::default(),
+        }
+    }
+
+    pub fn is_void(&self) -> bool {
+        self.kind == TypeKind::Void
+    }
+
+    /// Integer types include enums and _Bool.
+    pub fn is_integer(&self) -> bool {
+        matches!(self.kind, TypeKind::Int(_) | TypeKind::Enum(_))
+    }
+
+    pub fn is_floating(&self) -> bool {
+        matches!(self.kind, TypeKind::Float(_))
+    }
+
+    pub fn is_arithmetic(&self) -> bool {
+        self.is_integer() || self.is_floating()
+    }
+
+    pub fn is_pointer(&self) -> bool {
+        matches!(self.kind, TypeKind::Pointer(_))
+    }
+
+    pub fn is_scalar(&self) -> bool {
+        self.is_arithmetic() || self.is_pointer()
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self.kind, TypeKind::Array(..))
+    }
+
+    pub fn is_function(&self) -> bool {
+        matches!(self.kind, TypeKind::Function(_))
+    }
+
+    pub fn is_record(&self) -> bool {
+        matches!(self.kind, TypeKind::Struct(_) | TypeKind::Union(_))
+    }
+
+    pub fn is_vla(&self) -> bool {
+        match &self.kind {
+            TypeKind::Array(elem, len) => *len == ArrayLen::Variable || elem.is_vla(),
+            _ => false,
+        }
+    }
+
+    /// Returns the type pointed to by a pointer.
+    pub fn pointee(&self) -> Option<&Type> {
+        match &self.kind {
+            TypeKind::Pointer(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    /// Returns the element type of an array.
+    pub fn elem(&self) -> Option<&Type> {
+        match &self.kind {
+            TypeKind::Array(t, _) => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn func(&self) -> Option<&FuncType> {
+        match &self.kind {
+            TypeKind::Function(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    pub fn int_kind(&self) -> Option<IntKind> {
+        match self.kind {
+            TypeKind::Int(k) => Some(k),
+            TypeKind::Enum(_) => Some(IntKind::Int),
+            _ => None,
+        }
+    }
+
+    /// Reports whether the size of the type is known. Arrays of unknown
+    /// length, void and structs without members are incomplete.
+    pub fn is_complete(&self, tags: &[Tag]) -> bool {
+        match &self.kind {
+            TypeKind::Void | TypeKind::Function(_) => false,
+            TypeKind::Array(_, len) => *len != ArrayLen::Incomplete,
+            TypeKind::Struct(id) | TypeKind::Union(id) | TypeKind::Enum(id) => {
+                tags[id.0].members.is_some()
+            }
+            _ => true,
+        }
+    }
+
+    /// Converts arrays and functions to pointers, and drops the qualifiers
+    /// of a value (lvalue conversion).
+    pub fn decay(&self) -> Self {
+        match &self.kind {
+            TypeKind::Array(elem, _) => elem.as_ref().clone().pointer_to(),
+            TypeKind::Function(_) => self.clone().pointer_to(),
+            _ => self.unqualified(),
+        }
+    }
+
+    /// Applies the integer promotions: types ranking below int are
+    /// converted to int, all their values fit in it.
+    pub fn promote(&self) -> Self {
+        match self.kind {
+            TypeKind::Int(k) if k.rank() < IntKind::Int.rank() => Self::int(IntKind::Int),
+            TypeKind::Enum(_) => Self::int(IntKind::Int),
+            _ => self.unqualified(),
+        }
+    }
+
+    /// Applies the default argument promotions of calls without a
+    /// prototype and of variadic arguments.
+    pub fn promote_arg(&self) -> Self {
+        match self.kind {
+            TypeKind::Float(FloatKind::Float) => Self::float(FloatKind::Double),
+            _ => self.decay().promote(),
+        }
+    }
+
+    /// Reports whether two types are compatible (C11 6.2.7), qualifiers
+    /// included.
+    pub fn compatible(&self, other: &Type) -> bool {
+        self.quals == other.quals && self.compatible_unqualified(other)
+    }
+
+    pub fn compatible_unqualified(&self, other: &Type) -> bool {
+        match (&self.kind, &other.kind) {
+            (TypeKind::Pointer(a), TypeKind::Pointer(b)) => a.compatible(b),
+
+            (TypeKind::Array(a, n), TypeKind::Array(b, m)) => {
+                let fixed = |l: &ArrayLen| matches!(l, ArrayLen::Fixed(_));
+                a.compatible(b) && (n == m || !fixed(n) || !fixed(m))
+            }
+
+            (TypeKind::Function(f), TypeKind::Function(g)) => {
+                if !f.ret.compatible(&g.ret) {
+                    return false;
+                }
+
+                if !f.prototype || !g.prototype {
+                    return true;
+                }
+
+                f.variadic == g.variadic
+                    && f.params.len() == g.params.len()
+                    && f.params
+                        .iter()
+                        .zip(g.params.iter())
+                        .all(|(a, b)| a.compatible_unqualified(b))
+            }
+
+            (a, b) => a == b,
+        }
+    }
+
+    /// Spells the type like a declaration without a name, e.g.
+    /// `int (*)[3]`.
+    pub fn spell(&self, tags: &[Tag]) -> String {
+        let mut base = self;
+        let mut s = String::new();
+        let mut pointer = false; // s starts with a pointer
+
+        loop {
+            match &base.kind {
+                TypeKind::Pointer(t) => {
+                    let mut p = "*".to_string();
+                    for q in base.quals.words() {
+                        p.push_str(q);
+                        p.push(' ');
+                    }
+                    s = p + &s;
+                    pointer = true;
+                    base = t;
+                }
+
+                TypeKind::Array(t, len) => {
+                    if pointer {
+                        s = format!("({})", s.trim_end());
+                    }
+                    s = match len {
+                        ArrayLen::Fixed(n) => format!("{}[{}]", s, n),
+                        ArrayLen::Incomplete => format!("{}[]", s),
+                        ArrayLen::Variable => format!("{}[*]", s),
+                    };
+                    pointer = false;
+                    base = t;
+                }
+
+                TypeKind::Function(f) => {
+                    if pointer {
+                        s = format!("({})", s.trim_end());
+                    }
+
+                    let mut params: Vec<_> = f.params.iter().map(|p| p.spell(tags)).collect();
+                    if f.variadic {
+                        params.push("...".to_string());
+                    } else if f.prototype && params.is_empty() {
+                        params.push("void".to_string());
+                    }
+
+                    s = format!("{}({})", s, params.join(", "));
+                    pointer = false;
+                    base = &f.ret;
+                }
+
+                _ => break,
+            }
+        }
+
+        let mut words = base.quals.words();
+        let name = match &base.kind {
+            TypeKind::Void => "void".to_string(),
+            TypeKind::Int(k) => k.name().to_string(),
+            TypeKind::Float(k) => k.name().to_string(),
+            TypeKind::Struct(id) | TypeKind::Union(id) | TypeKind::Enum(id) => {
+                let tag = &tags[id.0];
+                format!(
+                    "{} {}",
+                    tag.kind.to_str(),
+                    tag.name.as_deref().unwrap_or("(anonymous)")
+                )
+            }
+            _ => unreachable!(),
+        };
+        words.push(&name);
+
+        let base = words.join(" ");
+        let s = s.trim_end();
+
+        if s.is_empty() {
+            base
+        } else {
+            format!("{} {}", base, s)
+        }
+    }
+}
+
+/// Applies the usual arithmetic conversions to the operands of a binary
+/// operator and returns their common type.
+pub fn usual_arithmetic(a: &Type, b: &Type, dl: &DataLayout) -> Type {
+    if let (TypeKind::Float(x), TypeKind::Float(y)) = (&a.kind, &b.kind) {
+        return Type::float(*x.max(y));
+    }
+    if let TypeKind::Float(x) = a.kind {
+        return Type::float(x);
+    }
+    if let TypeKind::Float(y) = b.kind {
+        return Type::float(y);
+    }
+
+    let (Some(x), Some(y)) = (a.promote().int_kind(), b.promote().int_kind()) else {
+        return a.unqualified();
+    };
+
+    let kind = if x == y {
+        x
+    } else if dl.is_signed(x) == dl.is_signed(y) {
+        if x.rank() > y.rank() {
+            x
+        } else {
+            y
+        }
+    } else {
+        let (s, u) = if dl.is_signed(x) { (x, y) } else { (y, x) };
+
+        if u.rank() >= s.rank() {
+            u
+        } else if dl.max(s) >= dl.max(u) {
+            // the signed type can represent all the unsigned values
+            s
+        } else {
+            s.to_unsigned()
+        }
+    };
+
+    Type::int(kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usual_arithmetic() {
+        use IntKind::*;
+
+        let tests = [
+            (Type::int(Char), Type::int(Short), Type::int(Int)),
+            (Type::int(Int), Type::int(UInt), Type::int(UInt)),
+            (Type::int(Long), Type::int(UInt), Type::int(Long)),
+            (Type::int(LongLong), Type::int(ULong), Type::int(ULongLong)),
+            (
+                Type::int(Int),
+                Type::float(FloatKind::Float),
+                Type::float(FloatKind::Float),
+            ),
+            (
+                Type::float(FloatKind::Double),
+                Type::float(FloatKind::LongDouble),
+                Type::float(FloatKind::LongDouble),
+            ),
+        ];
+
+        for (i, (a, b, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                *want,
+                usual_arithmetic(a, b, &DataLayout::LP64),
+                "[{}/{}] test case failed.",
+                i + 1,
+                tests.len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_spell() {
+        let int = Type::int(IntKind::Int);
+        let konst = Quals {
+            is_const: true,
+            ..Default::default()
+        };
+
+        let func = |ret: Type, params: Vec<Type>| -> Type {
+            TypeKind::Function(Box::new(FuncType {
+                ret,
+                params,
+                variadic: false,
+                prototype: true,
+            }))
+            .into()
+        };
+
+        let tests = [
+            (int.clone().pointer_to(), "int *"),
+            (
+                Type::int(IntKind::Char).qualified(konst).pointer_to(),
+                "const char *",
+            ),
+            (int.clone().pointer_to().qualified(konst), "int *const"),
+            (
+                int.clone().pointer_to().array_of(ArrayLen::Fixed(3)),
+                "int *[3]",
+            ),
+            (
+                int.clone().array_of(ArrayLen::Fixed(3)).pointer_to(),
+                "int (*)[3]",
+            ),
+            (func(int.clone(), vec![]), "int (void)"),
+            (
+                func(int.clone(), vec![int.clone().pointer_to()]).pointer_to(),
+                "int (*)(int *)",
+            ),
+            (
+                int.clone()
+                    .array_of(ArrayLen::Incomplete)
+                    .array_of(ArrayLen::Variable),
+                "int [*][]",
+            ),
+        ];
+
+        for (i, (t, want)) in tests.iter().enumerate() {
+            assert_eq!(
+                *want,
+                t.spell(&[]),
+                "[{}/{}] test case failed.",
+                i + 1,
+                tests.len()
+            );
+        }
+    }
+}
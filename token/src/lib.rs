@@ -1,4 +1,4 @@
-mod maps;
+pub mod maps;
 
 #[allow(non_camel_case_types)]
 #[derive(Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
@@ -15,6 +15,7 @@ pub enum Token {
     INTEGER,
     FLOATING,
     STRING,
+    CHARACTER,
     literal_end,
 
     ASSIGN,   // =
@@ -58,7 +59,9 @@ pub enum Token {
     SHL_ASSIGN,   // <<=
     SHR_ASSIGN,   // >>=
 
-    ELLIPSE, // ...
+    ELLIPSE,   // ...
+    HASH,      // #
+    HASH_HASH, // ##
 
     LPAREN, // (
     LBRACK, // [
@@ -72,6 +75,9 @@ pub enum Token {
     COLON,     // :
 
     keyword_beg,
+    ALIGNOF,
+    BOOL,
+    STATIC_ASSERT,
     AUTO,
     BREAK,
     CASE,
@@ -125,6 +131,10 @@ pub enum Token {
     preprocessor_end,
 }
 
+pub const LOWEST_PREC: usize = 0; // non-operators
+pub const UNARY_PREC: usize = 14;
+pub const HIGHEST_PREC: usize = 15; // postfix expressions and operands
+
 impl Token {
     pub fn to_str(&self) -> String {
         maps::TOKENS
@@ -143,6 +153,51 @@ impl Token {
     pub fn is_preprocessor(&self) -> bool {
         *self > Self::preprocessor_beg && *self < Self::preprocessor_end
     }
+
+    /// Returns the precedence of a binary operator, higher binds tighter.
+    /// `?` and the assignment operators are right associative, the others
+    /// left associative. Other tokens have the lowest precedence.
+    pub fn precedence(&self) -> usize {
+        match self {
+            Self::COMMA => 1,
+            _ if self.is_assign() => 2,
+            Self::TERNERY => 3,
+            Self::LOR => 4,
+            Self::LAND => 5,
+            Self::OR => 6,
+            Self::XOR => 7,
+            Self::AND => 8,
+            Self::EQL | Self::NEQ => 9,
+            Self::LT | Self::GT | Self::LEQ | Self::GEQ => 10,
+            Self::SHL | Self::SHR => 11,
+            Self::PLUS | Self::MINUS => 12,
+            Self::ASTERISK | Self::SLASH | Self::REM => 13,
+            _ => LOWEST_PREC,
+        }
+    }
+
+    /// Reports whether the token is `=` or a compound assignment.
+    pub fn is_assign(&self) -> bool {
+        *self == Self::ASSIGN || (*self >= Self::PLUS_ASSIGN && *self <= Self::SHR_ASSIGN)
+    }
+
+    /// Returns the binary operator of a compound assignment, `+` for `+=`.
+    /// Other tokens are returned unchanged.
+    pub fn binary_op(&self) -> Token {
+        match self {
+            Self::PLUS_ASSIGN => Self::PLUS,
+            Self::MINUS_ASSIGN => Self::MINUS,
+            Self::MUL_ASSIGN => Self::ASTERISK,
+            Self::DIV_ASSIGN => Self::SLASH,
+            Self::REM_ASSIGN => Self::REM,
+            Self::AND_ASSIGN => Self::AND,
+            Self::OR_ASSIGN => Self::OR,
+            Self::XOR_ASSIGN => Self::XOR,
+            Self::SHL_ASSIGN => Self::SHL,
+            Self::SHR_ASSIGN => Self::SHR,
+            op => *op,
+        }
+    }
 }
 
 pub fn lookup(ident: &str) -> Token {
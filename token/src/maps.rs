@@ -11,6 +11,7 @@ lazy_static! {
         (Token::INTEGER, "INTEGER"),
         (Token::FLOATING, "FLOATING"),
         (Token::STRING, "STRING"),
+        (Token::CHARACTER, "CHARACTER"),
         (Token::ASSIGN, "="),
         (Token::PLUS, "+"),
         (Token::MINUS, "-"),
@@ -49,6 +50,8 @@ lazy_static! {
         (Token::SHL_ASSIGN, "<<="),
         (Token::SHR_ASSIGN, ">>="),
         (Token::ELLIPSE, "..."),
+        (Token::HASH, "#"),
+        (Token::HASH_HASH, "##"),
         (Token::LPAREN, "("),
         (Token::LBRACK, "["),
         (Token::LBRACE, "{"),
@@ -58,6 +61,9 @@ lazy_static! {
         (Token::RBRACE, "}"),
         (Token::SEMICOLON, ";"),
         (Token::COLON, ":"),
+        (Token::ALIGNOF, "_Alignof"),
+        (Token::BOOL, "_Bool"),
+        (Token::STATIC_ASSERT, "_Static_assert"),
         (Token::AUTO, "auto"),
         (Token::BREAK, "break"),
         (Token::CASE, "case"),
@@ -110,6 +116,9 @@ lazy_static! {
 
 lazy_static! {
     pub static ref KEYWORDS: HashMap<&'static str, Token> = HashMap::from([
+        ("_Alignof", Token::ALIGNOF),
+        ("_Bool", Token::BOOL),
+        ("_Static_assert", Token::STATIC_ASSERT),
         ("auto", Token::AUTO),
         ("break", Token::BREAK),
         ("case", Token::CASE),